@@ -0,0 +1,249 @@
+package com.splats.app;
+
+import android.Manifest;
+import android.annotation.SuppressLint;
+import android.app.Activity;
+import android.content.Intent;
+import android.content.pm.PackageManager;
+import android.graphics.ImageFormat;
+import android.graphics.Rect;
+import android.graphics.YuvImage;
+import android.media.Image;
+import android.opengl.GLES11Ext;
+import android.opengl.GLES20;
+import android.opengl.GLSurfaceView;
+import android.os.Bundle;
+import android.util.Log;
+import android.view.Gravity;
+import android.widget.Button;
+import android.widget.FrameLayout;
+
+import com.google.ar.core.Camera;
+import com.google.ar.core.CameraIntrinsics;
+import com.google.ar.core.Frame;
+import com.google.ar.core.Pose;
+import com.google.ar.core.Session;
+import com.google.ar.core.TrackingState;
+import com.google.ar.core.exceptions.NotYetAvailableException;
+
+import java.io.ByteArrayOutputStream;
+import java.nio.ByteBuffer;
+
+import javax.microedition.khronos.egl.EGLConfig;
+import javax.microedition.khronos.opengles.GL10;
+
+public class ArCapture extends Activity implements GLSurfaceView.Renderer {
+    @SuppressLint("StaticFieldLeak")
+    private static Activity _activity;
+    private static final int REQUEST_CODE_CAMERA = 2;
+
+    // Only keep a frame when the camera moved or turned enough since the last one.
+    private static final float MIN_TRANSLATION = 0.05f;
+    private static final float MIN_ROTATION_DOT = 0.995f;
+    private static final int JPEG_QUALITY = 90;
+
+    private static native void onCaptureFrame(byte[] jpeg, float[] pose, float[] intrinsics, int width, int height);
+    private static native void onCaptureFinished();
+
+    private GLSurfaceView surfaceView;
+    private Session session;
+    private Pose lastPose;
+    private boolean finished = false;
+
+    public static void Register(Activity activity) {
+        _activity = activity;
+    }
+
+    public static String startCapture() {
+        Intent intent = new Intent(_activity, ArCapture.class);
+        _activity.startActivity(intent);
+        return _activity.getFilesDir().getAbsolutePath();
+    }
+
+    @Override
+    protected void onCreate(Bundle savedInstanceState) {
+        super.onCreate(savedInstanceState);
+
+        surfaceView = new GLSurfaceView(this);
+        surfaceView.setEGLContextClientVersion(2);
+        surfaceView.setRenderer(this);
+        surfaceView.setRenderMode(GLSurfaceView.RENDERMODE_CONTINUOUSLY);
+
+        Button done = new Button(this);
+        done.setText("Done");
+        done.setOnClickListener(v -> finish());
+
+        FrameLayout layout = new FrameLayout(this);
+        layout.addView(surfaceView);
+        layout.addView(done, new FrameLayout.LayoutParams(
+                FrameLayout.LayoutParams.WRAP_CONTENT,
+                FrameLayout.LayoutParams.WRAP_CONTENT,
+                Gravity.BOTTOM | Gravity.CENTER_HORIZONTAL));
+        setContentView(layout);
+
+        if (checkSelfPermission(Manifest.permission.CAMERA) != PackageManager.PERMISSION_GRANTED) {
+            requestPermissions(new String[]{Manifest.permission.CAMERA}, REQUEST_CODE_CAMERA);
+        }
+    }
+
+    @Override
+    public void onRequestPermissionsResult(int requestCode, String[] permissions, int[] results) {
+        super.onRequestPermissionsResult(requestCode, permissions, results);
+        if (requestCode == REQUEST_CODE_CAMERA
+                && (results.length == 0 || results[0] != PackageManager.PERMISSION_GRANTED)) {
+            finish();
+        }
+    }
+
+    @Override
+    protected void onResume() {
+        super.onResume();
+        if (checkSelfPermission(Manifest.permission.CAMERA) != PackageManager.PERMISSION_GRANTED) {
+            return;
+        }
+        try {
+            if (session == null) {
+                session = new Session(this);
+            }
+            session.resume();
+        } catch (Exception e) {
+            Log.e("ArCapture", "Failed to start ARCore session", e);
+            finish();
+            return;
+        }
+        surfaceView.onResume();
+    }
+
+    @Override
+    protected void onPause() {
+        super.onPause();
+        surfaceView.onPause();
+        if (session != null) {
+            session.pause();
+        }
+    }
+
+    @Override
+    protected void onDestroy() {
+        super.onDestroy();
+        if (session != null) {
+            session.close();
+            session = null;
+        }
+        if (!finished) {
+            finished = true;
+            onCaptureFinished();
+        }
+    }
+
+    @Override
+    public void onSurfaceCreated(GL10 gl, EGLConfig config) {
+        // ARCore needs a texture to render the camera feed into, even though
+        // we only use the CPU images.
+        int[] textures = new int[1];
+        GLES20.glGenTextures(1, textures, 0);
+        GLES20.glBindTexture(GLES11Ext.GL_TEXTURE_EXTERNAL_OES, textures[0]);
+        if (session != null) {
+            session.setCameraTextureName(textures[0]);
+        }
+    }
+
+    @Override
+    public void onSurfaceChanged(GL10 gl, int width, int height) {
+        GLES20.glViewport(0, 0, width, height);
+        if (session != null) {
+            session.setDisplayGeometry(getWindowManager().getDefaultDisplay().getRotation(), width, height);
+        }
+    }
+
+    @Override
+    public void onDrawFrame(GL10 gl) {
+        GLES20.glClear(GLES20.GL_COLOR_BUFFER_BIT);
+        if (session == null || finished) {
+            return;
+        }
+
+        try {
+            Frame frame = session.update();
+            Camera camera = frame.getCamera();
+            if (camera.getTrackingState() != TrackingState.TRACKING) {
+                return;
+            }
+
+            // The physical camera pose matches the image as read out from the sensor,
+            // unlike the display oriented pose.
+            Pose pose = camera.getPose();
+            if (!movedEnough(pose)) {
+                return;
+            }
+
+            try (Image image = frame.acquireCameraImage()) {
+                CameraIntrinsics intrinsics = camera.getImageIntrinsics();
+                float[] focal = intrinsics.getFocalLength();
+                float[] principal = intrinsics.getPrincipalPoint();
+                float[] matrix = new float[16];
+                pose.toMatrix(matrix, 0);
+
+                onCaptureFrame(
+                        toJpeg(image),
+                        matrix,
+                        new float[]{focal[0], focal[1], principal[0], principal[1]},
+                        image.getWidth(),
+                        image.getHeight());
+                lastPose = pose;
+            }
+        } catch (NotYetAvailableException ignored) {
+        } catch (Exception e) {
+            Log.e("ArCapture", "Failed to capture frame", e);
+        }
+    }
+
+    private boolean movedEnough(Pose pose) {
+        if (lastPose == null) {
+            return true;
+        }
+        float[] a = lastPose.getTranslation();
+        float[] b = pose.getTranslation();
+        float dx = a[0] - b[0], dy = a[1] - b[1], dz = a[2] - b[2];
+        if (dx * dx + dy * dy + dz * dz > MIN_TRANSLATION * MIN_TRANSLATION) {
+            return true;
+        }
+        float[] qa = lastPose.getRotationQuaternion();
+        float[] qb = pose.getRotationQuaternion();
+        float dot = Math.abs(qa[0] * qb[0] + qa[1] * qb[1] + qa[2] * qb[2] + qa[3] * qb[3]);
+        return dot < MIN_ROTATION_DOT;
+    }
+
+    private static byte[] toJpeg(Image image) {
+        int width = image.getWidth();
+        int height = image.getHeight();
+        Image.Plane[] planes = image.getPlanes();
+
+        // Convert YUV_420_888 to NV21, which YuvImage can compress.
+        byte[] nv21 = new byte[width * height * 3 / 2];
+        ByteBuffer y = planes[0].getBuffer();
+        int yStride = planes[0].getRowStride();
+        for (int row = 0; row < height; row++) {
+            y.position(row * yStride);
+            y.get(nv21, row * width, width);
+        }
+
+        ByteBuffer u = planes[1].getBuffer();
+        ByteBuffer v = planes[2].getBuffer();
+        int uvStride = planes[1].getRowStride();
+        int uvPixelStride = planes[1].getPixelStride();
+        int offset = width * height;
+        for (int row = 0; row < height / 2; row++) {
+            for (int col = 0; col < width / 2; col++) {
+                int index = row * uvStride + col * uvPixelStride;
+                nv21[offset++] = v.get(index);
+                nv21[offset++] = u.get(index);
+            }
+        }
+
+        YuvImage yuv = new YuvImage(nv21, ImageFormat.NV21, width, height, null);
+        ByteArrayOutputStream out = new ByteArrayOutputStream();
+        yuv.compressToJpeg(new Rect(0, 0, width, height), JPEG_QUALITY, out);
+        return out.toByteArray();
+    }
+}
@@ -74,5 +74,6 @@ public class MainActivity extends GameActivity {
         WindowCompat.setDecorFitsSystemWindows(getWindow(), false);
         hideSystemUI();
         FilePicker.Register(this);
+        ArCapture.Register(this);
     }
 }
@@ -16,7 +16,7 @@ pub extern "system" fn JNI_OnLoad(vm: jni::JavaVM, _: *mut c_void) -> jint {
 fn android_main(app: winit::platform::android::activity::AndroidApp) {
     use winit::platform::android::EventLoopBuilderExtAndroid;
 
-    let wgpu_options = brush_ui::create_egui_options();
+    let wgpu_options = brush_ui::create_egui_options(true);
     let runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
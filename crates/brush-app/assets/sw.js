@@ -1,25 +1,127 @@
-var cacheName = 'egui-template-pwa';
-var filesToCache = [
+// Service worker that keeps the viewer working offline after the first visit, eg. for
+// kiosk demos.
+//
+// Everything is fetched from the network first, so updates show up as soon as they're
+// deployed, and served from the cache when the network fails. Besides the viewer itself,
+// the most recently viewed splats are kept, as they can be large.
+
+var viewerCache = 'brush-viewer-v1';
+var splatCache = 'brush-splats-v1';
+var maxSplats = 8;
+
+// Trunk is set up without file hashes, so the names of the build output are fixed.
+var viewerFiles = [
   './',
   './index.html',
-  './eframe_template.js',
-  './eframe_template_bg.wasm',
+  './brush_app.js',
+  './brush_app_bg.wasm',
+  './manifest.json',
+  './favicon.ico',
+  './icon-256.png',
+  './icon-1024.png',
+  './icon_ios_touch_192.png',
+  './maskable_icon_x512.png',
 ];
 
-/* Start the service worker and cache all of the app's content */
+var splatExtensions = ['.ply', '.zip', '.splat', '.spz', '.safetensors', '.las', '.laz', '.e57'];
+
 self.addEventListener('install', function (e) {
   e.waitUntil(
-    caches.open(cacheName).then(function (cache) {
-      return cache.addAll(filesToCache);
-    })
+    caches
+      .open(viewerCache)
+      .then(function (cache) {
+        return cache.addAll(viewerFiles);
+      })
+      .then(function () {
+        return self.skipWaiting();
+      })
+  );
+});
+
+/* Remove the caches of older versions of this worker */
+self.addEventListener('activate', function (e) {
+  e.waitUntil(
+    caches
+      .keys()
+      .then(function (names) {
+        return Promise.all(
+          names
+            .filter(function (name) {
+              return name !== viewerCache && name !== splatCache;
+            })
+            .map(function (name) {
+              return caches.delete(name);
+            })
+        );
+      })
+      .then(function () {
+        return self.clients.claim();
+      })
   );
 });
 
-/* Serve cached content when offline */
+function isSplat(url) {
+  var path = new URL(url).pathname.toLowerCase();
+  return splatExtensions.some(function (ext) {
+    return path.endsWith(ext);
+  });
+}
+
+/* Keep only the most recently stored splats. Cache keys are in the order they were added. */
+function trimSplats(cache) {
+  return cache.keys().then(function (keys) {
+    return Promise.all(
+      keys.slice(0, Math.max(0, keys.length - maxSplats)).map(function (key) {
+        return cache.delete(key);
+      })
+    );
+  });
+}
+
+function store(request, response, splat) {
+  return caches.open(splat ? splatCache : viewerCache).then(function (cache) {
+    // Re-adding moves a splat to the back of the queue.
+    return cache
+      .delete(request)
+      .then(function () {
+        return cache.put(request, response);
+      })
+      .then(function () {
+        return splat ? trimSplats(cache) : undefined;
+      });
+  });
+}
+
 self.addEventListener('fetch', function (e) {
+  // Uploads and other requests can't be cached.
+  if (e.request.method !== 'GET') {
+    return;
+  }
+
+  var splat = isSplat(e.request.url);
+  // Only the viewer's own files are kept, not other requests it makes.
+  if (!splat && new URL(e.request.url).origin !== self.location.origin) {
+    return;
+  }
+
   e.respondWith(
-    caches.match(e.request).then(function (response) {
-      return response || fetch(e.request);
-    })
+    fetch(e.request)
+      .then(function (response) {
+        // Opaque responses can't be checked, so might be errors.
+        if (response.ok && response.type !== 'opaque') {
+          // Store in the background, the viewer streams splats as they download.
+          e.waitUntil(store(e.request, response.clone(), splat));
+        }
+        return response;
+      })
+      .catch(function (err) {
+        // The viewer is opened with its settings in the query, the cached page has none.
+        return caches.match(e.request, { ignoreSearch: !splat }).then(function (cached) {
+          if (cached) {
+            return cached;
+          }
+          throw err;
+        });
+      })
   );
 });
@@ -0,0 +1,78 @@
+use brush_render::camera::Camera;
+use glam::{Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+/// A note pinned to a point in the scene.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    /// Position in world space.
+    pub position: Vec3,
+    pub text: String,
+}
+
+impl Annotation {
+    /// Project the anchor into an image of `img_size` pixels, if it's in front of the camera.
+    pub fn project(&self, camera: &Camera, img_size: glam::UVec2) -> Option<glam::Vec2> {
+        let local = camera.world_to_local().transform_point3(self.position);
+        if local.z <= 0.0 {
+            return None;
+        }
+        Some(camera.focal(img_size) * local.truncate() / local.z + camera.center(img_size))
+    }
+}
+
+/// Parse notes as stored in the `notes` link parameter.
+pub fn notes_from_uri(uri: &str) -> Option<Vec<Annotation>> {
+    serde_json::from_str(uri).ok()
+}
+
+/// Query string for a web viewer link that opens `url` with the given view and notes.
+pub fn share_query(url: &str, position: Vec3, rotation: Quat, notes: &[Annotation]) -> String {
+    let mut query = format!(
+        "?url={}&position={},{},{}&rotation={},{},{},{}",
+        urlencoding::encode(url),
+        position.x,
+        position.y,
+        position.z,
+        rotation.x,
+        rotation.y,
+        rotation.z,
+        rotation.w,
+    );
+    if !notes.is_empty() {
+        let notes = serde_json::to_string(notes).expect("Notes are always serializable");
+        query += &format!("&notes={}", urlencoding::encode(&notes));
+    }
+    query
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_round_trip_through_link() {
+        let notes = vec![
+            Annotation {
+                position: Vec3::new(1.0, -2.0, 0.5),
+                text: "Crack in the *wall* & more; see ?this".to_owned(),
+            },
+            Annotation {
+                position: Vec3::ZERO,
+                text: "Second, with\nnew lines".to_owned(),
+            },
+        ];
+        let query = share_query(
+            "https://example.com/a.ply",
+            Vec3::ONE,
+            Quat::IDENTITY,
+            &notes,
+        );
+        let encoded = query
+            .split('&')
+            .find_map(|p| p.strip_prefix("notes="))
+            .expect("Link has notes");
+        let decoded = urlencoding::decode(encoded).expect("Valid encoding");
+        assert_eq!(notes_from_uri(&decoded), Some(notes));
+    }
+}
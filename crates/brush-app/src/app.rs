@@ -1,21 +1,37 @@
 use std::sync::{Arc, RwLock};
 
+use crate::annotations::{Annotation, notes_from_uri, share_query};
 use crate::camera_controls::{self, CameraController};
+use crate::i18n::{self, LOCALE_STORAGE_KEY};
+use crate::job_queue::{JobQueue, JobStatus};
+use crate::keymap::{Action, KEYMAP_STORAGE_KEY, Keymap};
+use crate::kiosk::Kiosk;
 use crate::panels::SettingsPanel;
-use crate::panels::{DatasetPanel, PresetsPanel, ScenePanel, StatsPanel, TracingPanel};
+use crate::panels::{
+    DatasetPanel, LocalDatasetsPanel, LogPanel, PresetsPanel, QueuePanel, ScenePanel, StatsPanel,
+    TracingPanel,
+};
 use crate::running_process::{ControlMessage, RunningProcess, start_process};
-use brush_dataset::Dataset;
+use crate::share::{SHARE_STORAGE_KEY, ShareSettings};
 use brush_dataset::scene::SceneView;
+use brush_dataset::{ColorSpace, Dataset};
 use brush_process::data_source::DataSource;
+use brush_process::error::ProcessError;
 use brush_process::process_loop::{ProcessArgs, ProcessMessage};
 use brush_render::camera::Camera;
+use brush_render::gaussian_splats::Splats;
+use brush_render::post_process::PostProcess;
+use burn::prelude::Backend;
 use burn_wgpu::WgpuDevice;
 use eframe::egui;
 use egui::ThemePreference;
 use egui_tiles::SimplificationOptions;
 use egui_tiles::{Container, Tile, TileId, Tiles};
 use glam::{Affine3A, Quat, Vec3};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc::error::TryRecvError;
 
 pub(crate) trait AppPanel {
     fn title(&self) -> String;
@@ -29,7 +45,7 @@ pub(crate) trait AppPanel {
         let _ = context;
     }
 
-    fn on_error(&mut self, error: &anyhow::Error, context: &mut AppContext) {
+    fn on_error(&mut self, error: &ProcessError, context: &mut AppContext) {
         let _ = error;
         let _ = context;
     }
@@ -81,7 +97,7 @@ impl egui_tiles::Behavior<PaneType> for AppTree {
     }
 }
 
-fn parse_search(search: &str) -> HashMap<String, String> {
+pub fn parse_search(search: &str) -> HashMap<String, String> {
     let mut params = HashMap::new();
     let search = search.trim_start_matches('?');
 
@@ -108,10 +124,82 @@ pub struct CameraSettings {
     pub clamping: camera_controls::CameraClamping,
 }
 
+/// Viewer settings to limit time spent redrawing, eg. to save battery during long training runs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PowerSettings {
+    pub low_power: bool,
+    pub max_fps: u32,
+    /// How many times per second the UI is updated with new training steps.
+    pub ui_update_hz: u32,
+    /// Frame rate limit that also applies outside of low power mode.
+    pub fps_cap: Option<u32>,
+    /// Render at the full resolution of high-DPI screens, rather than one pixel per point.
+    pub hidpi: bool,
+    /// Resolution of the render relative to the view. Above 1 the render is supersampled,
+    /// below 1 it's upscaled, which is faster.
+    pub render_scale: f32,
+}
+
+impl Default for PowerSettings {
+    fn default() -> Self {
+        Self {
+            low_power: false,
+            max_fps: 10,
+            ui_update_hz: 20,
+            fps_cap: None,
+            hidpi: true,
+            render_scale: 1.0,
+        }
+    }
+}
+
+impl PowerSettings {
+    /// How many pixels to render per point of the view.
+    pub fn pixels_per_point(&self, ctx: &egui::Context) -> f32 {
+        let dpi = if self.hidpi {
+            ctx.pixels_per_point()
+        } else {
+            1.0
+        };
+        dpi * self.render_scale
+    }
+
+    /// Minimum time between redraws, if redraws are throttled.
+    pub fn frame_interval(&self) -> Option<Duration> {
+        let low_power_fps = self.low_power.then_some(self.max_fps);
+        let fps = low_power_fps.into_iter().chain(self.fps_cap).min()?;
+        Some(Duration::from_secs_f32(1.0 / fps.max(1) as f32))
+    }
+}
+
+pub(crate) const PROJECTS_STORAGE_KEY: &str = "brush_projects";
+pub(crate) const DATASET_ARGS_STORAGE_KEY: &str = "brush_dataset_args";
+
+/// Viewer state that's saved with each project.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectSettings {
+    pub post_process: PostProcess,
+    pub annotations: Vec<Annotation>,
+}
+
+/// Projects are keyed by where they were loaded from. Picked files have no stable
+/// name, so their settings aren't saved.
+fn project_key(source: &DataSource) -> Option<String> {
+    match source {
+        DataSource::Url(s) | DataSource::Path(s) => Some(s.clone()),
+        DataSource::PickFile | DataSource::PickDirectory => None,
+    }
+}
+
 pub struct App {
     tree: egui_tiles::Tree<PaneType>,
     datasets: Option<TileId>,
+    side_panel: Option<TileId>,
+    kiosk: Option<Kiosk>,
     tree_ctx: AppTree,
+    #[cfg(not(target_family = "wasm"))]
+    crash_report: Option<crate::crash::CrashReport>,
 }
 
 // TODO: Bit too much random shared state here.
@@ -123,17 +211,53 @@ pub struct AppContext {
     pub model_local_to_world: Affine3A,
     pub device: WgpuDevice,
     pub egui_ctx: egui::Context,
+    pub keymap: Keymap,
+    /// Estimate of the GPU memory available for training, in bytes.
+    pub memory_budget: u64,
+    /// Keep showing the current scene until a new one is done loading, rather than clearing
+    /// the view as soon as it starts.
+    pub keep_scene_while_loading: bool,
+    /// Keep the camera where it is when a new model is loaded.
+    pub keep_view: bool,
+    /// Animate the camera when jumping to a view, rather than cutting to it.
+    pub smooth_transitions: bool,
+    /// Scale the camera speed by the distance to the splats in front of it.
+    pub auto_speed: bool,
+    /// Args to show in the settings panel, eg. the remembered args of a dataset that's
+    /// trained again.
+    pub prefill_args: Option<ProcessArgs>,
+    /// Training jobs to run after each other.
+    pub(crate) queue: JobQueue,
 
     loading: bool,
     training: bool,
+    // The running process has sent all its messages.
+    process_finished: bool,
 
     cam_settings: CameraSettings,
+    power: PowerSettings,
+    project: ProjectSettings,
+    saved_projects: HashMap<String, ProjectSettings>,
+    // Args each dataset was last trained with, keyed like projects.
+    dataset_args: HashMap<String, ProcessArgs>,
+    share: ShareSettings,
 
     running_process: Option<RunningProcess>,
+    /// How the model before the current process was shown, to go back to it.
+    previous_view: Option<(Affine3A, ProjectSettings)>,
 }
 
 impl AppContext {
-    fn new(device: WgpuDevice, ctx: egui::Context, cam_settings: CameraSettings) -> Self {
+    fn new(
+        device: WgpuDevice,
+        ctx: egui::Context,
+        cam_settings: CameraSettings,
+        keymap: Keymap,
+        memory_budget: u64,
+        saved_projects: HashMap<String, ProjectSettings>,
+        dataset_args: HashMap<String, ProcessArgs>,
+        share: ShareSettings,
+    ) -> Self {
         let model_transform = Affine3A::IDENTITY;
         let controls = CameraController::new(
             cam_settings.position,
@@ -158,12 +282,27 @@ impl AppContext {
             model_local_to_world: model_transform,
             device,
             egui_ctx: ctx,
+            keymap,
+            memory_budget,
+            keep_scene_while_loading: false,
+            keep_view: false,
+            smooth_transitions: true,
+            auto_speed: true,
+            prefill_args: None,
+            queue: JobQueue::default(),
             view_aspect: None,
             loading: false,
             training: false,
+            process_finished: false,
             dataset: Dataset::empty(),
             running_process: None,
+            previous_view: None,
             cam_settings,
+            power: PowerSettings::default(),
+            project: ProjectSettings::default(),
+            saved_projects,
+            dataset_args,
+            share,
         }
     }
 
@@ -176,6 +315,16 @@ impl AppContext {
     }
 
     pub fn set_cam_settings(&mut self, settings: CameraSettings) {
+        self.cam_settings = settings;
+        self.reset_camera();
+        let cam = self.camera.clone();
+        self.match_controls_to(&cam);
+    }
+
+    /// Move the camera back to where it started.
+    pub fn reset_camera(&mut self) {
+        let settings = &self.cam_settings;
+        let lock_horizon = self.controls.lock_horizon;
         self.controls = CameraController::new(
             settings.position,
             settings.rotation,
@@ -183,9 +332,7 @@ impl AppContext {
             settings.speed_scale,
             settings.clamping.clone(),
         );
-        self.cam_settings = settings;
-        let cam = self.camera.clone();
-        self.match_controls_to(&cam);
+        self.controls.lock_horizon = lock_horizon;
     }
 
     pub fn set_model_up(&mut self, up_axis: Vec3) {
@@ -199,9 +346,16 @@ impl AppContext {
     }
 
     pub fn focus_view(&mut self, view: &SceneView) {
+        let (position, rotation) = (self.controls.position, self.controls.rotation);
+        let fov = (self.camera.fov_x, self.camera.fov_y);
         self.camera = view.camera.clone();
         self.match_controls_to(&view.camera);
         self.controls.stop_movement();
+        if self.smooth_transitions {
+            let target_fov = (view.camera.fov_x, view.camera.fov_y);
+            self.controls
+                .transition_from(position, rotation, fov, target_fov);
+        }
         self.view_aspect = Some(view.image.width() as f32 / view.image.height() as f32);
         if let Some(extent) = self.dataset.train.estimate_extent() {
             self.controls.focus_distance = extent / 3.0;
@@ -211,13 +365,228 @@ impl AppContext {
     }
 
     pub fn connect_to(&mut self, process: RunningProcess) {
+        // Starting something else stops the queued job that was running.
+        self.queue.finish(JobStatus::Cancelled);
+        self.connect(process);
+    }
+
+    fn connect(&mut self, process: RunningProcess) {
+        let saved_projects = std::mem::take(&mut self.saved_projects);
+        let dataset_args = std::mem::take(&mut self.dataset_args);
+        let queue = std::mem::take(&mut self.queue);
         // reset context & view.
-        *self = Self::new(
+        let fresh = Self::new(
             self.device.clone(),
             self.egui_ctx.clone(),
             self.cam_settings.clone(),
+            self.keymap.clone(),
+            self.memory_budget,
+            saved_projects,
+            dataset_args,
+            self.share.clone(),
         );
+        let old = std::mem::replace(self, fresh);
+        self.queue = queue;
+
+        if let Some(key) = project_key(&process.source) {
+            self.project = self.saved_projects.get(&key).cloned().unwrap_or_default();
+            self.dataset_args.insert(key, process.start_args.clone());
+        }
         self.running_process = Some(process);
+        self.keep_scene_while_loading = old.keep_scene_while_loading;
+        self.keep_view = old.keep_view;
+        self.smooth_transitions = old.smooth_transitions;
+        self.auto_speed = old.auto_speed;
+        self.controls.lock_horizon = old.controls.lock_horizon;
+        if old.keep_view {
+            self.camera = old.camera;
+            self.controls = old.controls;
+            self.model_local_to_world = old.model_local_to_world;
+        }
+        self.previous_view = old
+            .running_process
+            .is_some()
+            .then(|| (old.model_local_to_world, old.project));
+        self.set_power_settings(old.power);
+    }
+
+    /// Stop the running process, and go back to how the model before it was shown.
+    ///
+    /// Returns false if there's no previous model.
+    pub fn restore_previous_view(&mut self) -> bool {
+        let Some((model_local_to_world, project)) = self.previous_view.take() else {
+            return false;
+        };
+        self.running_process = None;
+        self.loading = false;
+        self.training = false;
+        self.model_local_to_world = model_local_to_world;
+        self.project = project;
+        let cam = self.camera.clone();
+        self.match_controls_to(&cam);
+        true
+    }
+
+    pub fn post_process(&self) -> PostProcess {
+        // Splats trained in linear color are shown as sRGB.
+        let linear_colors = self
+            .dataset
+            .train
+            .views
+            .first()
+            .is_some_and(|view| view.image.color_space == ColorSpace::Linear);
+        PostProcess {
+            linear_colors,
+            ..self.project.post_process
+        }
+    }
+
+    pub fn set_post_process(&mut self, post_process: PostProcess) {
+        self.project.post_process = post_process;
+        self.save_project();
+    }
+
+    pub fn annotations(&self) -> &[Annotation] {
+        &self.project.annotations
+    }
+
+    pub fn set_annotations(&mut self, annotations: Vec<Annotation>) {
+        self.project.annotations = annotations;
+        self.save_project();
+    }
+
+    pub fn share_settings(&self) -> &ShareSettings {
+        &self.share
+    }
+
+    pub fn set_share_settings(&mut self, share: ShareSettings) {
+        self.share = share;
+    }
+
+    /// Where the current project was loaded from, if it can be loaded again.
+    pub fn project_key(&self) -> Option<String> {
+        self.running_process
+            .as_ref()
+            .and_then(|p| project_key(&p.source))
+    }
+
+    /// Query string that opens `url` in the web viewer with the current view and notes.
+    pub fn share_query(&self, url: &str) -> String {
+        share_query(
+            url,
+            self.controls.position,
+            self.controls.rotation,
+            &self.project.annotations,
+        )
+    }
+
+    /// Splats with the model transform baked in, so they show up the same as in the viewer
+    /// when loaded somewhere else.
+    pub fn splats_in_world<B: Backend>(&self, splats: Splats<B>) -> Splats<B> {
+        if self.model_local_to_world == Affine3A::IDENTITY {
+            return splats;
+        }
+        splats.transform(self.model_local_to_world.inverse())
+    }
+
+    /// Like [`Self::share_query`], for splats exported with [`Self::splats_in_world`].
+    pub fn export_query(&self, url: &str) -> String {
+        let to_world = self.model_local_to_world.inverse();
+        let notes: Vec<_> = self
+            .project
+            .annotations
+            .iter()
+            .map(|note| Annotation {
+                position: to_world.transform_point3(note.position),
+                text: note.text.clone(),
+            })
+            .collect();
+        share_query(url, self.controls.position, self.controls.rotation, &notes)
+    }
+
+    /// Link to open the current project in the web viewer, with the current view and notes.
+    ///
+    /// Only projects loaded from a URL can be shared.
+    pub fn share_link(&self) -> Option<String> {
+        let Some(DataSource::Url(url)) = self.running_process.as_ref().map(|p| &p.source) else {
+            return None;
+        };
+        let query = self.share_query(url);
+
+        // In the browser, link to this page. Elsewhere there's no page to link to, so
+        // just give the query to add to a viewer URL.
+        #[cfg(target_family = "wasm")]
+        let base = web_sys::window()
+            .and_then(|w| {
+                let location = w.location();
+                Some(format!(
+                    "{}{}",
+                    location.origin().ok()?,
+                    location.pathname().ok()?
+                ))
+            })
+            .unwrap_or_default();
+        #[cfg(not(target_family = "wasm"))]
+        let base = String::new();
+
+        Some(format!("{base}{query}"))
+    }
+
+    fn save_project(&mut self) {
+        if let Some(key) = self.project_key() {
+            self.saved_projects.insert(key, self.project.clone());
+        }
+    }
+
+    /// Where the running process loads from, if it can be loaded again.
+    pub fn reloadable_source(&self) -> Option<DataSource> {
+        let source = &self.running_process.as_ref()?.source;
+        project_key(source).map(|_| source.clone())
+    }
+
+    /// The args `source` was last trained with.
+    pub fn dataset_args(&self, source: &DataSource) -> Option<ProcessArgs> {
+        project_key(source).and_then(|key| self.dataset_args.get(&key).cloned())
+    }
+
+    /// Start the next queued job once the device is free.
+    fn advance_queue(&mut self) {
+        // A queued job is done once its process stops.
+        if self.process_finished {
+            self.queue.finish(JobStatus::Done);
+        }
+        if self.running_process.is_some() && !self.process_finished {
+            return;
+        }
+        if let Some((source, args)) = self.queue.start_next() {
+            self.connect(start_process(
+                source,
+                args,
+                self.device.clone(),
+                self.egui_ctx.clone(),
+            ));
+        }
+    }
+
+    /// Take a job out of the queue, stopping it if it's running.
+    pub(crate) fn cancel_job(&mut self, id: u64) {
+        if self.queue.running() == Some(id) {
+            self.running_process = None;
+            self.loading = false;
+            self.training = false;
+        }
+        self.queue.cancel(id);
+    }
+
+    pub fn power_settings(&self) -> PowerSettings {
+        self.power
+    }
+
+    pub fn set_power_settings(&mut self, power: PowerSettings) {
+        self.power = power;
+        if let Some(process) = self.running_process.as_ref() {
+            process.set_power_settings(&power);
+        }
     }
 
     pub(crate) fn control_message(&self, msg: ControlMessage) {
@@ -256,38 +625,35 @@ impl App {
             .wgpu_render_state
             .as_ref()
             .expect("No wgpu renderer enabled in egui");
+        crate::crash::set_adapter(&state.adapter.get_info());
+
         let device = brush_render::burn_init_device(
             state.adapter.clone(),
             state.device.clone(),
             state.queue.clone(),
         );
 
-        #[cfg(feature = "tracing")]
         {
+            use tracing_subscriber::layer::SubscriberExt;
+
+            // Tracing events show up in the log panel, next to the profiling layers if any.
+            let subscriber =
+                tracing_subscriber::registry().with(crate::log_capture::tracing_layer());
+
             // TODO: In debug only?
-            #[cfg(target_family = "wasm")]
-            {
-                use tracing_subscriber::layer::SubscriberExt;
-
-                tracing::subscriber::set_global_default(
-                    tracing_subscriber::registry()
-                        .with(tracing_wasm::WASMLayer::new(Default::default())),
-                )
-                .expect("Failed to set tracing subscriber");
-            }
+            #[cfg(all(feature = "tracing", target_family = "wasm"))]
+            let subscriber = subscriber.with(tracing_wasm::WASMLayer::new(Default::default()));
 
             #[cfg(all(feature = "tracy", not(target_family = "wasm")))]
-            {
-                use tracing_subscriber::layer::SubscriberExt;
-
-                tracing::subscriber::set_global_default(
-                    tracing_subscriber::registry()
-                        .with(tracing_tracy::TracyLayer::default())
-                        .with(sync_span::SyncLayer::<
-                            burn_cubecl::CubeBackend<burn_wgpu::WgpuRuntime, f32, i32, u32>,
-                        >::new(device.clone())),
-                )
-                .expect("Failed to set tracing subscriber");
+            let subscriber =
+                subscriber
+                    .with(tracing_tracy::TracyLayer::default())
+                    .with(sync_span::SyncLayer::<
+                        burn_cubecl::CubeBackend<burn_wgpu::WgpuRuntime, f32, i32, u32>,
+                    >::new(device.clone()));
+
+            if tracing::subscriber::set_global_default(subscriber).is_err() {
+                log::warn!("A tracing subscriber was already set");
             }
         }
 
@@ -303,6 +669,9 @@ impl App {
         if let Some(z) = search_params.get("zen") {
             zen = z.parse::<bool>().unwrap_or(false);
         }
+        // Kiosk mode hides all UI, like zen mode.
+        let kiosk = Kiosk::from_search(&search_params);
+        zen |= kiosk.is_some();
 
         fn vec_from_uri(uri: &str) -> Option<Vec3> {
             let parts: Vec<&str> = uri.split(',').collect();
@@ -361,7 +730,43 @@ impl App {
             clamping: Default::default(),
         };
 
-        let context = AppContext::new(device.clone(), cc.egui_ctx.clone(), settings);
+        let keymap = cc
+            .storage
+            .and_then(|s| eframe::get_value(s, KEYMAP_STORAGE_KEY))
+            .unwrap_or_default();
+        if let Some(locale) = cc
+            .storage
+            .and_then(|s| eframe::get_value(s, LOCALE_STORAGE_KEY))
+        {
+            i18n::set_locale(locale);
+        }
+
+        let saved_projects = cc
+            .storage
+            .and_then(|s| eframe::get_value(s, PROJECTS_STORAGE_KEY))
+            .unwrap_or_default();
+        let dataset_args = cc
+            .storage
+            .and_then(|s| eframe::get_value(s, DATASET_ARGS_STORAGE_KEY))
+            .unwrap_or_default();
+        let share = cc
+            .storage
+            .and_then(|s| eframe::get_value(s, SHARE_STORAGE_KEY))
+            .unwrap_or_default();
+
+        let memory_budget = brush_train::memory::memory_budget(&state.adapter.limits());
+        let mut context = AppContext::new(
+            device.clone(),
+            cc.egui_ctx.clone(),
+            settings,
+            keymap,
+            memory_budget,
+            saved_projects,
+            dataset_args,
+            share,
+        );
+        // Don't flash an empty screen between the splats of a playlist.
+        context.keep_scene_while_loading = kiosk.is_some();
 
         let mut tiles: Tiles<PaneType> = Tiles::default();
         let scene_pane = ScenePanel::new(
@@ -369,14 +774,19 @@ impl App {
             state.queue.clone(),
             state.renderer.clone(),
             zen,
+            kiosk.is_some(),
         );
 
         let scene_pane_id = tiles.insert_pane(Box::new(scene_pane));
 
+        let mut side_panel = None;
+
         let root_container = if !zen {
             let loading_subs = vec![
                 tiles.insert_pane(Box::new(SettingsPanel::new())),
                 tiles.insert_pane(Box::new(PresetsPanel::new())),
+                tiles.insert_pane(Box::new(LocalDatasetsPanel::new())),
+                tiles.insert_pane(Box::new(QueuePanel::new())),
             ];
             let loading_pane = tiles.insert_tab_tile(loading_subs);
 
@@ -387,19 +797,21 @@ impl App {
                     device.clone(),
                     state.adapter.get_info(),
                 ))),
+                tiles.insert_pane(Box::new(LogPanel::new())),
             ];
 
             if cfg!(feature = "tracing") {
                 sides.push(tiles.insert_pane(Box::new(TracingPanel::default())));
             }
 
-            let side_panel = tiles.insert_vertical_tile(sides);
+            let side_id = tiles.insert_vertical_tile(sides);
+            side_panel = Some(side_id);
 
             let mut lin = egui_tiles::Linear::new(
                 egui_tiles::LinearDir::Horizontal,
-                vec![side_panel, scene_pane_id],
+                vec![side_id, scene_pane_id],
             );
-            lin.shares.set_share(side_panel, 0.4);
+            lin.shares.set_share(side_id, 0.4);
             tiles.insert_container(lin)
         } else {
             scene_pane_id
@@ -414,7 +826,11 @@ impl App {
 
         let tree_ctx = AppTree { zen, context };
 
-        let url = search_params.get("url");
+        let url = kiosk
+            .as_ref()
+            .map_or(search_params.get("url").map(String::as_str), |k| {
+                k.first_url()
+            });
         if let Some(url) = url {
             let running = start_process(
                 DataSource::Url(url.to_owned()),
@@ -422,17 +838,23 @@ impl App {
                 device,
                 cc.egui_ctx.clone(),
             );
-            tree_ctx
-                .context
-                .write()
-                .expect("Lock poisoned")
-                .connect_to(running);
+            let mut context = tree_ctx.context.write().expect("Lock poisoned");
+            context.connect_to(running);
+
+            // Notes in the link replace any saved ones.
+            if let Some(notes) = search_params.get("notes").and_then(|n| notes_from_uri(n)) {
+                context.set_annotations(notes);
+            }
         }
 
         Self {
             tree,
             tree_ctx,
             datasets: None,
+            side_panel,
+            kiosk,
+            #[cfg(not(target_family = "wasm"))]
+            crash_report: crate::crash::take_last_crash(),
         }
     }
 }
@@ -447,13 +869,24 @@ impl App {
         };
 
         let mut messages = vec![];
-        while let Ok(message) = process.messages.try_recv() {
-            messages.push(message);
+        let mut finished = false;
+        loop {
+            match process.messages.try_recv() {
+                Ok(message) => messages.push(message),
+                Err(TryRecvError::Disconnected) => {
+                    finished = true;
+                    break;
+                }
+                Err(TryRecvError::Empty) => break,
+            }
         }
+        context.process_finished = finished;
 
         for message in messages {
             match message {
                 Ok(message) => {
+                    crate::crash::record_message(&message);
+
                     match message {
                         ProcessMessage::Dataset { dataset: _ } => {
                             // Show the dataset panel if we've loaded one.
@@ -477,6 +910,10 @@ impl App {
                         ProcessMessage::DoneLoading { training: _ } => {
                             context.loading = false;
                         }
+                        // Downloads are reported before loading starts.
+                        ProcessMessage::LoadProgress { .. } => {
+                            context.loading = true;
+                        }
                         _ => (),
                     }
 
@@ -490,6 +927,9 @@ impl App {
                     }
                 }
                 Err(e) => {
+                    crate::crash::record_error(&e);
+                    context.queue.finish(JobStatus::Failed(format!("{e:#}")));
+
                     for (_, pane) in self.tree.tiles.iter_mut() {
                         match pane {
                             Tile::Pane(pane) => {
@@ -504,9 +944,82 @@ impl App {
     }
 }
 
+#[cfg(not(target_family = "wasm"))]
+impl App {
+    /// Offer to open or copy the report of the last crash.
+    fn crash_report_ui(&mut self, ctx: &egui::Context) {
+        use crate::i18n::tr;
+
+        let Some(report) = &self.crash_report else {
+            return;
+        };
+
+        let mut open = true;
+        let mut dismissed = false;
+        egui::Window::new(tr("crash.title"))
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(tr("crash.body"));
+                ui.label(report.path.display().to_string());
+                ui.horizontal(|ui| {
+                    if ui.button(tr("crash.copy")).clicked() {
+                        ui.ctx().copy_text(report.text.clone());
+                    }
+                    if ui.button(tr("crash.open_folder")).clicked() {
+                        ui.ctx().open_url(egui::OpenUrl::same_tab(format!(
+                            "file://{}",
+                            report.dir().display()
+                        )));
+                    }
+                    dismissed = ui.button(tr("crash.dismiss")).clicked();
+                });
+            });
+
+        if !open || dismissed {
+            self.crash_report = None;
+        }
+    }
+}
+
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
         self.receive_messages();
+        self.tree_ctx
+            .context
+            .write()
+            .expect("Lock poisoned")
+            .advance_queue();
+
+        if let Some(kiosk) = self.kiosk.as_mut() {
+            let mut context = self.tree_ctx.context.write().expect("Lock poisoned");
+            if let Some(url) = kiosk.tick(ctx, &mut context) {
+                let running = start_process(
+                    DataSource::Url(url),
+                    ProcessArgs::default(),
+                    context.device.clone(),
+                    ctx.clone(),
+                );
+                context.connect_to(running);
+            }
+        }
+
+        #[cfg(not(target_family = "wasm"))]
+        self.crash_report_ui(ctx);
+
+        if let Some(side_panel) = self.side_panel {
+            let toggle = self
+                .tree_ctx
+                .context
+                .read()
+                .expect("Lock poisoned")
+                .keymap
+                .pressed(ctx, Action::TogglePanels);
+            if toggle {
+                let visible = self.tree.tiles.is_visible(side_panel);
+                self.tree.tiles.set_visible(side_panel, !visible);
+            }
+        }
 
         let main_panel_frame = egui::Frame::central_panel(ctx.style().as_ref()).inner_margin(0.0);
 
@@ -516,4 +1029,13 @@ impl eframe::App for App {
                 self.tree.ui(&mut self.tree_ctx, ui);
             });
     }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        let context = self.tree_ctx.context.read().expect("Lock poisoned");
+        eframe::set_value(storage, KEYMAP_STORAGE_KEY, &context.keymap);
+        eframe::set_value(storage, LOCALE_STORAGE_KEY, &i18n::locale());
+        eframe::set_value(storage, PROJECTS_STORAGE_KEY, &context.saved_projects);
+        eframe::set_value(storage, DATASET_ARGS_STORAGE_KEY, &context.dataset_args);
+        eframe::set_value(storage, SHARE_STORAGE_KEY, &context.share);
+    }
 }
@@ -10,8 +10,6 @@ use tokio::sync::oneshot::error::RecvError;
 
 #[allow(clippy::unnecessary_wraps)] // Error isn't need on wasm but that's ok.
 fn main() -> Result<(), anyhow::Error> {
-    let wgpu_options = brush_ui::create_egui_options();
-
     #[allow(unused)]
     let (send, rec) = tokio::sync::oneshot::channel();
 
@@ -28,11 +26,32 @@ fn main() -> Result<(), anyhow::Error> {
             .expect("Failed to initialize tokio runtime");
 
         runtime.block_on(async move {
-            env_logger::builder()
-                .target(env_logger::Target::Stdout)
-                .init();
-
-            if args.with_viewer {
+            brush_app::crash::init_logging();
+            brush_app::crash::install_panic_hook();
+
+            let post_process = args.render_post_process();
+            if let Some(port) = args.session_relay {
+                brush_cli::session_relay::run_relay(port).await?;
+            } else if let Some(path) = args.validate {
+                let max_anisotropy = args.process.train_config.max_anisotropy;
+                let max_anisotropy = (max_anisotropy > 0.0).then_some(max_anisotropy);
+                brush_cli::validate::validate_file(&path, args.fix, max_anisotropy).await?;
+            } else if let Some(out_path) = args.render_equirect {
+                let Some(source) = args.source else {
+                    panic!("Validation of args failed?");
+                };
+                let device = brush_render::burn_init_setup().await;
+                brush_cli::render::render_equirect_to_file(
+                    source,
+                    args.process,
+                    device,
+                    args.render_position,
+                    args.render_width,
+                    &post_process,
+                    &out_path,
+                )
+                .await?;
+            } else if args.with_viewer {
                 let icon = eframe::icon_data::from_png_bytes(
                     &include_bytes!("../../assets/icon-256.png")[..],
                 )
@@ -44,7 +63,7 @@ fn main() -> Result<(), anyhow::Error> {
                         .with_inner_size(egui::Vec2::new(1450.0, 1200.0))
                         .with_active(true)
                         .with_icon(std::sync::Arc::new(icon)),
-                    wgpu_options,
+                    wgpu_options: brush_ui::create_egui_options(!args.no_vsync),
                     ..Default::default()
                 };
 
@@ -79,8 +98,9 @@ fn main() -> Result<(), anyhow::Error> {
                 let Some(source) = args.source else {
                     panic!("Validation of args failed?");
                 };
-                let device = brush_render::burn_init_setup().await;
-                brush_cli::ui::process_ui(source, args.process, device).await?;
+                let (device, limits) = brush_render::burn_init_setup_with_limits().await;
+                let budget = brush_train::memory::memory_budget(&limits);
+                brush_cli::ui::process_ui(source, args.process, device, budget).await?;
             }
 
             anyhow::Result::<(), anyhow::Error>::Ok(())
@@ -93,13 +113,17 @@ fn main() -> Result<(), anyhow::Error> {
         use wasm_bindgen::JsCast;
 
         if cfg!(debug_assertions) {
-            eframe::WebLogger::init(log::LevelFilter::Debug).ok();
+            let logger = eframe::WebLogger::new(log::LevelFilter::Debug);
+            brush_app::log_capture::init(Some(Box::new(logger)), log::LevelFilter::Debug);
+        } else {
+            brush_app::log_capture::init(None, log::LevelFilter::Off);
         }
 
-        let document = web_sys::window()
-            .expect("Failed to find web window (not running in a browser?")
-            .document()
-            .expect("Failed to find document body");
+        let window =
+            web_sys::window().expect("Failed to find web window (not running in a browser?");
+        let search = window.location().search().unwrap_or_default();
+        let wgpu_options = brush_ui::create_egui_options(vsync_from_search(&search));
+        let document = window.document().expect("Failed to find document body");
 
         if let Some(canvas) = document
             .get_element_by_id("main_canvas")
@@ -127,9 +151,17 @@ fn main() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Vsync can only be turned off at startup, with `vsync=false` in the URL.
+#[cfg(target_family = "wasm")]
+fn vsync_from_search(search: &str) -> bool {
+    brush_app::parse_search(search)
+        .get("vsync")
+        .is_none_or(|v| v != "false")
+}
+
 #[cfg(target_family = "wasm")]
 mod embedded {
-    use super::start_process;
+    use super::{start_process, vsync_from_search};
     use brush_app::App;
     use brush_process::{data_source::DataSource, process_loop::ProcessArgs};
     use glam::Quat;
@@ -194,7 +226,7 @@ mod embedded {
     impl EmbeddedApp {
         #[wasm_bindgen(constructor)]
         pub fn new(canvas_name: &str, start_uri: &str) -> Self {
-            let wgpu_options = brush_ui::create_egui_options();
+            let wgpu_options = brush_ui::create_egui_options(vsync_from_search(start_uri));
             let document = web_sys::window()
                 .expect("Failed to get winow")
                 .document()
@@ -1,7 +1,9 @@
 use core::f32;
 
 use egui::Response;
-use glam::{Quat, Vec2, Vec3};
+use glam::{Mat3, Quat, Vec2, Vec3};
+
+use crate::keymap::{Action, Keymap};
 
 #[derive(Clone, Default)]
 pub struct CameraClamping {
@@ -15,10 +17,47 @@ pub struct CameraClamping {
     pub max_yaw: Option<f32>,
 }
 
+/// Walking over the floor of the scene, instead of flying through it.
+///
+/// The controller doesn't know the scene, distances to it are measured elsewhere and
+/// passed in with [`CameraController::set_walk_probe`].
+#[derive(Clone, Copy, Debug, Default)]
+struct Walk {
+    /// Height of the camera above the floor, measured when walking starts.
+    eye_height: Option<f32>,
+    /// Distance to the floor below the camera.
+    floor: Option<f32>,
+    /// Distance to the nearest surface in the direction of walking.
+    wall: Option<f32>,
+}
+
+/// A smooth move from another view to where the camera is, see
+/// [`CameraController::transition_from`].
+#[derive(Clone, Copy, Debug)]
+struct Transition {
+    from: (Vec3, Quat),
+    to: (Vec3, Quat),
+    from_fov: (f64, f64),
+    to_fov: (f64, f64),
+    elapsed: f32,
+}
+
+// Seconds a transition takes.
+const TRANSITION_TIME: f32 = 0.5;
+
+// Distance to the scene at which the camera moves at the speed set by the speed scale. Closer
+// to surfaces it slows down, and further away it speeds up.
+const REFERENCE_DISTANCE: f32 = 4.0;
+
+// Walls stop the camera this far away from them, relative to the eye height.
+const WALL_MARGIN: f32 = 0.25;
+
 pub struct CameraController {
     pub position: Vec3,
     pub rotation: Quat,
     pub focus_distance: f32,
+    /// Keep the camera up aligned with the up of the scene, so it never rolls.
+    pub lock_horizon: bool,
 
     clamping: CameraClamping,
 
@@ -26,6 +65,12 @@ pub struct CameraController {
     fly_velocity: Vec3,
     orbit_velocity: Vec2,
     speed_scale: f32,
+    walk: Option<Walk>,
+    // Position to smoothly move to, with the focus distance there.
+    fly_to: Option<(Vec3, f32)>,
+    transition: Option<Transition>,
+    // Distance to the splats in front of the camera, to scale the movement speed by.
+    surface_distance: Option<f32>,
 }
 pub fn smooth_orbit(
     position: Vec3,
@@ -101,6 +146,12 @@ fn exp_lerp3(a: Vec3, b: Vec3, dt: f32, lambda: f32) -> Vec3 {
     )
 }
 
+// Eases in and out of t in [0, 1].
+fn smoothstep(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
 fn smooth_clamp(val: f32, min: Option<f32>, max: Option<f32>, dt: f32, lambda: f32) -> f32 {
     let mut target = val;
     if let Some(min) = min {
@@ -127,20 +178,28 @@ impl CameraController {
             fly_velocity: Vec3::ZERO,
             orbit_velocity: Vec2::ZERO,
             focus_distance,
+            lock_horizon: false,
             clamping,
             speed_scale,
+            walk: None,
+            fly_to: None,
+            transition: None,
+            surface_distance: None,
         }
     }
 
-    pub fn tick(&mut self, response: &Response, ui: &egui::Ui) {
+    pub fn tick(&mut self, response: &Response, ui: &egui::Ui, keymap: &Keymap) {
         let delta_time = ui.input(|r| r.predicted_dt);
+        let down = |action| keymap.down(ui.ctx(), action);
 
         let lmb = response.dragged_by(egui::PointerButton::Primary);
         let rmb = response.dragged_by(egui::PointerButton::Secondary);
         let mmb = response.dragged_by(egui::PointerButton::Middle);
 
-        let look_pan = mmb || lmb && ui.input(|r| r.modifiers.ctrl);
-        let look_fps = rmb || lmb && ui.input(|r| r.key_down(egui::Key::Space));
+        // Walking looks around instead of orbiting, there's nothing to orbit around.
+        let walking = self.walk.is_some();
+        let look_pan = !walking && (mmb || lmb && ui.input(|r| r.modifiers.ctrl));
+        let look_fps = rmb || lmb && (walking || down(Action::LookAround));
         let look_orbit = lmb;
 
         let mouselook_speed = 0.002;
@@ -152,13 +211,28 @@ impl CameraController {
         if response.hovered() {
             if ui.input(|r| r.modifiers.ctrl) {
                 ui.ctx().set_cursor_icon(egui::CursorIcon::Move);
-            } else if ui.input(|r| r.key_down(egui::Key::Space)) {
+            } else if down(Action::LookAround) {
                 ui.ctx().set_cursor_icon(egui::CursorIcon::Crosshair);
             } else {
                 ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
             }
         }
 
+        if response.dragged() {
+            self.fly_to = None;
+            self.transition = None;
+        }
+
+        if let Some(transition) = self.transition.as_mut() {
+            transition.elapsed += delta_time;
+            let t = smoothstep(transition.elapsed / TRANSITION_TIME);
+            self.position = transition.from.0.lerp(transition.to.0, t);
+            self.rotation = transition.from.1.slerp(transition.to.1, t);
+            if t >= 1.0 {
+                self.transition = None;
+            }
+        }
+
         if look_pan {
             let drag_mult = self.focus_distance / response.rect.width().max(response.rect.height());
             self.position -= right * response.drag_delta().x * drag_mult;
@@ -197,35 +271,40 @@ impl CameraController {
             } else {
                 1.0
             };
+        // Move slower near surfaces, and faster through large scenes.
+        let proximity = self
+            .surface_distance
+            .map_or(1.0, |d| (d / REFERENCE_DISTANCE).clamp(0.02, 50.0));
+        let fly_speed = move_speed * proximity;
 
-        if ui.input(|r| r.key_down(egui::Key::W) || r.key_down(egui::Key::ArrowUp)) {
+        if down(Action::MoveForward) {
             self.fly_velocity = exp_lerp3(
                 self.fly_velocity,
-                Vec3::Z * move_speed,
+                Vec3::Z * fly_speed,
                 delta_time,
                 fly_moment_lambda,
             );
         }
-        if ui.input(|r| r.key_down(egui::Key::A) || r.key_down(egui::Key::ArrowLeft)) {
+        if down(Action::MoveLeft) {
             self.fly_velocity = exp_lerp3(
                 self.fly_velocity,
-                -Vec3::X * move_speed,
+                -Vec3::X * fly_speed,
                 delta_time,
                 fly_moment_lambda,
             );
         }
-        if ui.input(|r| r.key_down(egui::Key::S) || r.key_down(egui::Key::ArrowDown)) {
+        if down(Action::MoveBackward) {
             self.fly_velocity = exp_lerp3(
                 self.fly_velocity,
-                -Vec3::Z * move_speed,
+                -Vec3::Z * fly_speed,
                 delta_time,
                 fly_moment_lambda,
             );
         }
-        if ui.input(|r| r.key_down(egui::Key::D) || r.key_down(egui::Key::ArrowRight)) {
+        if down(Action::MoveRight) {
             self.fly_velocity = exp_lerp3(
                 self.fly_velocity,
-                Vec3::X * move_speed,
+                Vec3::X * fly_speed,
                 delta_time,
                 fly_moment_lambda,
             );
@@ -233,20 +312,20 @@ impl CameraController {
 
         if ui.input(|r| r.modifiers.alt) {
         } else {
-            // Move _down_ with Q
-            if ui.input(|r| r.key_down(egui::Key::Q)) {
+            // Move down, Q by default.
+            if down(Action::MoveDown) {
                 self.fly_velocity = exp_lerp3(
                     self.fly_velocity,
-                    -Vec3::Y * move_speed,
+                    -Vec3::Y * fly_speed,
                     delta_time,
                     fly_moment_lambda,
                 );
             }
-            // Move up with E
-            if ui.input(|r| r.key_down(egui::Key::E)) {
+            // Move up, E by default.
+            if down(Action::MoveUp) {
                 self.fly_velocity = exp_lerp3(
                     self.fly_velocity,
-                    Vec3::Y * move_speed,
+                    Vec3::Y * fly_speed,
                     delta_time,
                     fly_moment_lambda,
                 );
@@ -254,23 +333,39 @@ impl CameraController {
         }
 
         // Roll with alt + Q&E.
-        if ui.input(|r| r.key_down(egui::Key::Z)) {
+        if down(Action::RollLeft) {
             let roll = Quat::from_axis_angle(forward, move_speed * 0.025 * delta_time);
             self.rotation = roll * self.rotation;
             self.roll = roll * self.roll;
         }
-        if ui.input(|r| r.key_down(egui::Key::X)) {
+        if down(Action::ResetRoll) {
             self.rotation = self.roll.inverse() * self.rotation;
             self.roll = Quat::IDENTITY;
         }
-        if ui.input(|r| r.key_down(egui::Key::C)) {
+        if down(Action::RollRight) {
             let roll = Quat::from_axis_angle(forward, -move_speed * 0.025 * delta_time);
             self.rotation = roll * self.rotation;
             self.roll = roll * self.roll;
         }
 
+        if self.lock_horizon {
+            self.level();
+        }
+
         let delta = self.fly_velocity * delta_time;
-        self.position += delta.x * right + delta.y * up + delta.z * forward;
+        if let Some(walk) = self.walk {
+            self.walk_step(walk, delta, delta_time);
+        } else {
+            self.position += delta.x * right + delta.y * up + delta.z * forward;
+        }
+
+        if let Some((target, focus_distance)) = self.fly_to {
+            self.position = exp_lerp3(self.position, target, delta_time, 8.0);
+            self.focus_distance = exp_lerp(self.focus_distance, focus_distance, delta_time, 8.0);
+            if self.position.distance(target) < focus_distance * 1e-3 {
+                self.fly_to = None;
+            }
+        }
 
         // Damp velocities towards zero.
         self.orbit_velocity = exp_lerp2(self.orbit_velocity, Vec2::ZERO, delta_time, 8.0);
@@ -278,6 +373,13 @@ impl CameraController {
 
         // Handle scroll wheel: move back, and adjust focus distance.
         let scrolled = ui.input(|r| r.smooth_scroll_delta.y);
+        if walking {
+            // Scrolling would fly through walls, only the focus point moves.
+            self.focus_distance *= 1.0 - scrolled * 0.001;
+            self.focus_distance = self.focus_distance.max(0.01);
+            return;
+        }
+
         let scroll_speed = 0.001;
 
         let old_pivot = self.position + self.rotation * Vec3::Z * self.focus_distance;
@@ -301,8 +403,206 @@ impl CameraController {
         glam::Affine3A::from_rotation_translation(self.rotation, self.position)
     }
 
+    /// Orbit around the focus point, eg. to slowly turn around the scene.
+    pub(crate) fn orbit(&mut self, delta_yaw: f32, dt: f32) {
+        (self.position, self.rotation) = smooth_orbit(
+            self.position,
+            self.rotation,
+            self.roll,
+            delta_yaw,
+            0.0,
+            &self.clamping,
+            dt,
+            self.focus_distance,
+        );
+        if self.lock_horizon {
+            self.level();
+        }
+    }
+
+    /// Turn the camera around its view direction, so its up is the up of the scene.
+    fn level(&mut self) {
+        let forward = self.rotation * Vec3::Z;
+        // Looking straight up or down, there's no horizon to level with.
+        let Some(right) = forward.cross(Vec3::NEG_Y).try_normalize() else {
+            return;
+        };
+        let down = forward.cross(right);
+        self.rotation = Quat::from_mat3(&Mat3::from_cols(right, down, forward));
+        self.roll = Quat::IDENTITY;
+    }
+
+    /// Move over the floor by `delta`, in the camera space, without going through walls, and
+    /// settle at the eye height above the floor.
+    fn walk_step(&mut self, walk: Walk, delta: Vec3, dt: f32) {
+        let forward = self.walk_forward();
+        let right = forward.cross(Vec3::NEG_Y);
+        let mut step = right * delta.x + forward * delta.z;
+
+        if let (Some(eye_height), Some(wall)) = (walk.eye_height, walk.wall) {
+            // The wall is measured along the direction of walking, only stop moving into it.
+            let direction = self.walk_direction();
+            let towards = step.dot(direction);
+            if towards > 0.0 && wall - towards < eye_height * WALL_MARGIN {
+                step -= direction * towards;
+            }
+        }
+        self.position += step;
+
+        // Gravity, or stepping up, to keep the floor at eye height.
+        if let (Some(eye_height), Some(floor)) = (walk.eye_height, walk.floor) {
+            let target = floor - eye_height;
+            if target.abs() < eye_height * 1e-3 {
+                return;
+            }
+            let settled = exp_lerp(0.0, target, dt, 10.0);
+            self.position += Vec3::Y * settled;
+            // The measurement is relative to where the camera was, use it up.
+            if let Some(walk) = self.walk.as_mut() {
+                walk.floor = Some(floor - settled);
+            }
+        }
+    }
+
+    /// Horizontal direction the camera looks in.
+    fn walk_forward(&self) -> Vec3 {
+        let forward = self.rotation * Vec3::Z;
+        Vec3::new(forward.x, 0.0, forward.z)
+            .try_normalize()
+            .unwrap_or(Vec3::Z)
+    }
+
+    pub fn walking(&self) -> bool {
+        self.walk.is_some()
+    }
+
+    /// Start or stop walking. The height above the floor is kept from the first
+    /// measurement of the floor.
+    pub fn set_walking(&mut self, walking: bool) {
+        self.walk = walking.then(Walk::default);
+        self.stop_movement();
+    }
+
+    /// Horizontal direction the camera moves in while walking, to measure walls along.
+    /// This is where it's looking when standing still.
+    pub fn walk_direction(&self) -> Vec3 {
+        let forward = self.walk_forward();
+        let right = forward.cross(Vec3::NEG_Y);
+        (right * self.fly_velocity.x + forward * self.fly_velocity.z)
+            .try_normalize()
+            .unwrap_or(forward)
+    }
+
+    /// Distances from the camera to the floor below it and to the wall along
+    /// [`Self::walk_direction`], `None` if there's nothing there.
+    pub fn set_walk_probe(&mut self, floor: Option<f32>, wall: Option<f32>) {
+        if let Some(walk) = self.walk.as_mut() {
+            walk.floor = floor;
+            walk.wall = wall;
+            if walk.eye_height.is_none() {
+                walk.eye_height = floor;
+            }
+        }
+    }
+
+    /// Move the camera to `point`. The camera keeps looking in the same direction, and
+    /// turns around the point from then on. When walking, the camera jumps to stand in
+    /// front of the point instead.
+    pub(crate) fn go_to(&mut self, point: Vec3) {
+        self.stop_movement();
+        if let Some(walk) = self.walk.as_mut() {
+            let eye_height = walk.eye_height.unwrap_or(0.0);
+            // Stay out of the wall that was clicked.
+            let towards = Vec3::new(point.x - self.position.x, 0.0, point.z - self.position.z);
+            let back = towards.normalize_or_zero() * eye_height * WALL_MARGIN * 2.0;
+            self.position = point - back - Vec3::Y * eye_height;
+            // Wait for the floor to be measured at the new place.
+            walk.floor = None;
+            walk.wall = None;
+        } else {
+            let distance = self.position.distance(point);
+            self.fly_to = Some((point - self.rotation * Vec3::Z * distance, distance));
+        }
+    }
+
+    /// Move the camera back or forward, keeping its direction, until the sphere at `center`
+    /// with `radius` fits in view, and turn around its center from then on. Does nothing
+    /// while walking, as the camera stays on the floor.
+    pub(crate) fn frame(&mut self, center: Vec3, radius: f32, fov_y: f64) {
+        if self.walking() {
+            return;
+        }
+        self.stop_movement();
+        let distance = radius / (fov_y as f32 * 0.5).sin().max(1e-3);
+        let distance = distance.max(0.01);
+        self.fly_to = Some((center - self.rotation * Vec3::Z * distance, distance));
+    }
+
+    /// Animate from the view at `position` and `rotation` to where the camera is now, rather
+    /// than cutting to it. The field of view goes from `fov` to `target_fov` meanwhile, see
+    /// [`Self::transition_fov`].
+    pub fn transition_from(
+        &mut self,
+        position: Vec3,
+        rotation: Quat,
+        fov: (f64, f64),
+        target_fov: (f64, f64),
+    ) {
+        self.transition = Some(Transition {
+            from: (position, rotation),
+            to: (self.position, self.rotation),
+            from_fov: fov,
+            to_fov: target_fov,
+            elapsed: 0.0,
+        });
+        self.position = position;
+        self.rotation = rotation;
+    }
+
+    /// The field of view (x, y) part way through a transition.
+    pub fn transition_fov(&self) -> Option<(f64, f64)> {
+        let transition = self.transition.as_ref()?;
+        let t = smoothstep(transition.elapsed / TRANSITION_TIME) as f64;
+        let lerp = |a: f64, b: f64| a + (b - a) * t;
+        Some((
+            lerp(transition.from_fov.0, transition.to_fov.0),
+            lerp(transition.from_fov.1, transition.to_fov.1),
+        ))
+    }
+
+    /// Distance to the splats in front of the camera, to move faster or slower by. `None`
+    /// moves at the set speed.
+    pub fn set_surface_distance(&mut self, distance: Option<f32>) {
+        self.surface_distance = distance;
+    }
+
     pub(crate) fn stop_movement(&mut self) {
         self.orbit_velocity = Vec2::ZERO;
         self.fly_velocity = Vec3::ZERO;
+        self.fly_to = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Quat, Vec3};
+
+    use super::{CameraClamping, CameraController};
+
+    #[test]
+    fn level_removes_roll() {
+        let rotation = Quat::from_rotation_z(0.4) * Quat::from_rotation_x(0.3);
+        let mut controller =
+            CameraController::new(Vec3::ZERO, rotation, 1.0, 1.0, CameraClamping::default());
+        let forward = rotation * Vec3::Z;
+
+        controller.level();
+        assert!((controller.rotation * Vec3::Z).abs_diff_eq(forward, 1e-5));
+        // The camera's right stays in the horizontal plane.
+        assert!((controller.rotation * Vec3::X).y.abs() < 1e-5);
+        assert!(
+            (controller.rotation * Vec3::NEG_Y).y < 0.0,
+            "Up should stay up"
+        );
     }
 }
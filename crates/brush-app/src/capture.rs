@@ -0,0 +1,45 @@
+use std::path::PathBuf;
+
+use brush_dataset::capture::CaptureWriter;
+use glam::{Mat4, UVec2, Vec2};
+
+/// Record a dataset with the AR capture activity, and write it to a zip in app storage.
+///
+/// Returns the path of the written dataset.
+pub(crate) async fn capture_dataset() -> anyhow::Result<PathBuf> {
+    let mut capture = rrfd::android::start_capture()?;
+
+    // Pick a name that doesn't clash with earlier captures.
+    let path = (0..)
+        .map(|i| capture.output_dir.join(format!("capture_{i}.zip")))
+        .find(|p| !p.exists())
+        .expect("Ran out of capture names");
+
+    let file = std::fs::File::create(&path)?;
+    let mut writer = CaptureWriter::new(file);
+
+    while let Some(frame) = capture.frames.recv().await {
+        writer.add_frame(
+            &frame.jpeg,
+            "jpg",
+            Mat4::from_cols_array(&frame.cam_to_world),
+            Vec2::from_array(frame.focal),
+            Vec2::from_array(frame.principal_point),
+            UVec2::new(frame.width, frame.height),
+        )?;
+    }
+
+    if writer.frame_count() == 0 {
+        drop(writer);
+        let _ = std::fs::remove_file(&path);
+        anyhow::bail!("No frames were captured.");
+    }
+
+    log::info!(
+        "Captured {} frames to {}",
+        writer.frame_count(),
+        path.display()
+    );
+    writer.finish()?;
+    Ok(path)
+}
@@ -0,0 +1,116 @@
+//! Planes to cut away part of the scene, to look at the structure inside it.
+
+use brush_render::camera::MAX_CLIP_PLANES;
+use glam::{Affine3A, Vec3, Vec3A, Vec4};
+
+use crate::i18n::tr;
+
+/// A plane along one of the world axes. Splats on the low side of `offset` are cut away,
+/// or on the high side when flipped.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct ClipPlane {
+    enabled: bool,
+    axis: usize,
+    offset: f32,
+    flip: bool,
+}
+
+impl ClipPlane {
+    /// The plane as (normal, offset) in world space, zero when disabled.
+    fn world_plane(&self) -> Vec4 {
+        if !self.enabled {
+            return Vec4::ZERO;
+        }
+        let sign = if self.flip { -1.0 } else { 1.0 };
+        (Vec3::AXES[self.axis] * sign).extend(-self.offset * sign)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub(crate) struct ClipPlanes {
+    planes: [ClipPlane; MAX_CLIP_PLANES],
+}
+
+impl ClipPlanes {
+    /// The planes in the space of the splats, for [`brush_render::camera::Camera::clip_planes`].
+    pub(crate) fn local_planes(&self, local_to_world: Affine3A) -> [Vec4; MAX_CLIP_PLANES] {
+        self.planes.map(|p| {
+            // n · (A p + t) + d = (Aᵀ n) · p + (n · t + d)
+            let plane = p.world_plane();
+            let normal = plane.truncate();
+            let local_normal = local_to_world.matrix3.transpose() * Vec3A::from(normal);
+            Vec3::from(local_normal).extend(plane.w + normal.dot(local_to_world.translation.into()))
+        })
+    }
+
+    /// Edit the planes. Planes that get enabled start out through `focus`, the point the
+    /// camera looks at.
+    pub(crate) fn menu_ui(&mut self, ui: &mut egui::Ui, focus: Vec3, step: f32) {
+        const AXES: [&str; 3] = ["X", "Y", "Z"];
+
+        for (i, plane) in self.planes.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                let label = tr("clip.plane").replace("{index}", &(i + 1).to_string());
+                if ui.checkbox(&mut plane.enabled, label).changed() && plane.enabled {
+                    plane.offset = focus[plane.axis];
+                }
+                ui.add_enabled_ui(plane.enabled, |ui| {
+                    egui::ComboBox::from_id_salt(("clip_axis", i))
+                        .width(40.0)
+                        .selected_text(AXES[plane.axis])
+                        .show_ui(ui, |ui| {
+                            for (axis, name) in AXES.iter().enumerate() {
+                                ui.selectable_value(&mut plane.axis, axis, *name);
+                            }
+                        });
+                    ui.add(egui::DragValue::new(&mut plane.offset).speed(step))
+                        .on_hover_text(tr("clip.offset_hover"));
+                    ui.checkbox(&mut plane.flip, tr("clip.flip"))
+                        .on_hover_text(tr("clip.flip_hover"));
+                });
+            });
+        }
+
+        if ui.button(tr("clip.reset")).clicked() {
+            *self = Self::default();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Affine3A, Quat, Vec3, Vec4};
+
+    use super::{ClipPlane, ClipPlanes};
+
+    fn behind(plane: Vec4, point: Vec3) -> bool {
+        plane.truncate().dot(point) + plane.w < 0.0
+    }
+
+    #[test]
+    fn planes_follow_model_transform() {
+        let mut planes = ClipPlanes::default();
+        planes.planes[0] = ClipPlane {
+            enabled: true,
+            axis: 1,
+            offset: 2.0,
+            flip: false,
+        };
+        let local_to_world = Affine3A::from_scale_rotation_translation(
+            Vec3::splat(2.0),
+            Quat::from_rotation_x(0.5),
+            Vec3::new(1.0, -3.0, 0.5),
+        );
+        let local = planes.local_planes(local_to_world);
+        assert_eq!(local[1], Vec4::ZERO, "Disabled planes don't cut anything");
+
+        for point in [
+            Vec3::ZERO,
+            Vec3::new(0.3, 2.0, -1.0),
+            Vec3::new(-1.0, 4.0, 2.0),
+        ] {
+            let world = local_to_world.transform_point3(point);
+            assert_eq!(behind(local[0], point), world.y < 2.0);
+        }
+    }
+}
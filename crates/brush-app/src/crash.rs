@@ -0,0 +1,253 @@
+//! Crash reports.
+//!
+//! A panic hook writes the panic, the GPU adapter and the last few process messages to a file
+//! in the app data folder. The next time the app starts, it offers to open or copy the report.
+
+use std::collections::VecDeque;
+use std::sync::{LazyLock, Mutex};
+
+use brush_process::error::ProcessError;
+use brush_process::process_loop::ProcessMessage;
+
+/// How many process messages to include in a crash report.
+const RECENT_MESSAGES: usize = 32;
+
+#[derive(Default)]
+struct CrashState {
+    adapter: Option<String>,
+    recent: VecDeque<String>,
+}
+
+static STATE: LazyLock<Mutex<CrashState>> = LazyLock::new(Mutex::default);
+
+impl CrashState {
+    fn push_recent(&mut self, line: String) {
+        if self.recent.len() == RECENT_MESSAGES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(line);
+    }
+}
+
+/// Remember which GPU we're running on, to include in crash reports.
+pub fn set_adapter(info: &wgpu::AdapterInfo) {
+    let desc = format!(
+        "{} ({:?}, {:?}), driver {} {}",
+        info.name, info.backend, info.device_type, info.driver, info.driver_info
+    );
+    log::info!("Using adapter {desc}");
+    if let Ok(mut state) = STATE.lock() {
+        state.adapter = Some(desc);
+    }
+}
+
+/// Remember a process message, to include in crash reports.
+///
+/// Only a short summary is kept, not the message data.
+pub fn record_message(message: &ProcessMessage) {
+    let line = match message {
+        ProcessMessage::NewSource => "NewSource".to_owned(),
+        ProcessMessage::StartLoading { training } => format!("StartLoading (training: {training})"),
+        ProcessMessage::ViewSplats {
+            splats,
+            frame,
+            total_frames,
+            ..
+        } => format!(
+            "ViewSplats (frame {frame}/{total_frames}, {} splats)",
+            splats.num_splats()
+        ),
+        ProcessMessage::ViewChunks { index, .. } => {
+            format!("ViewChunks ({} chunks)", index.chunks.len())
+        }
+        ProcessMessage::Dataset { dataset } => format!(
+            "Dataset ({} train views, {} eval views)",
+            dataset.train.views.len(),
+            dataset.eval.as_ref().map_or(0, |e| e.views.len())
+        ),
+        ProcessMessage::DoneLoading { training } => format!("DoneLoading (training: {training})"),
+        // Sent many times while loading, and not much help in finding what went wrong.
+        ProcessMessage::LoadProgress { .. } => return,
+        ProcessMessage::SplatsTruncated { loaded, skipped } => {
+            format!("SplatsTruncated ({loaded} loaded, {skipped} skipped)")
+        }
+        ProcessMessage::TrainChunks { count, .. } => format!("TrainChunks ({count} chunks)"),
+        ProcessMessage::EvalAvailable { .. } => "EvalAvailable".to_owned(),
+        ProcessMessage::TrainStep { iter, .. } => format!("TrainStep (iter {iter})"),
+        // Sent along with every train step that updates the splats.
+        ProcessMessage::Appearances { .. } | ProcessMessage::EnvLight { .. } => return,
+        ProcessMessage::RefineStep {
+            cur_splat_count,
+            iter,
+            ..
+        } => format!("RefineStep (iter {iter}, {cur_splat_count} splats)"),
+        ProcessMessage::RunDir { path } => format!("RunDir ({})", path.display()),
+        ProcessMessage::EvalResult {
+            iter,
+            avg_psnr,
+            avg_ssim,
+        } => format!("EvalResult (iter {iter}, {avg_psnr:.2} PSNR, {avg_ssim:.3} SSIM)"),
+    };
+
+    let Ok(mut state) = STATE.lock() else {
+        return;
+    };
+    // Consecutive train steps would push out everything else, only keep the latest.
+    let train_step = matches!(message, ProcessMessage::TrainStep { .. });
+    if train_step
+        && state
+            .recent
+            .back()
+            .is_some_and(|l| l.starts_with("TrainStep"))
+    {
+        state.recent.pop_back();
+    }
+    state.push_recent(line);
+}
+
+/// Remember a process error, to include in crash reports.
+pub fn record_error(error: &ProcessError) {
+    if let Ok(mut state) = STATE.lock() {
+        state.push_recent(format!("Error: {error:#}"));
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+pub use native::*;
+
+#[cfg(not(target_family = "wasm"))]
+mod native {
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use std::time::SystemTime;
+
+    use super::STATE;
+
+    const LOG_FILE: &str = "brush.log";
+    const PENDING_REPORT: &str = "last_crash.txt";
+
+    /// Folder that logs and crash reports are written to.
+    pub fn log_dir() -> Option<PathBuf> {
+        eframe::storage_dir("Brush").map(|d| d.join("logs"))
+    }
+
+    /// Log to stdout, to a log file in the log folder, and to the log panel.
+    ///
+    /// The log file is overwritten on each start, the previous one is kept next to it.
+    pub fn init_logging() {
+        let file = log_dir().and_then(|dir| {
+            std::fs::create_dir_all(&dir).ok()?;
+            let path = dir.join(LOG_FILE);
+            let _ = std::fs::rename(&path, path.with_extension("prev.log"));
+            std::fs::File::create(path).ok()
+        });
+
+        let mut builder = env_logger::builder();
+        builder.format_timestamp_millis();
+        if let Some(file) = file {
+            builder.target(env_logger::Target::Pipe(Box::new(TeeWriter { file })));
+        } else {
+            builder.target(env_logger::Target::Stdout);
+        }
+        let logger = builder.build();
+        let level = logger.filter();
+        crate::log_capture::init(Some(Box::new(logger)), level);
+    }
+
+    struct TeeWriter {
+        file: std::fs::File,
+    }
+
+    impl Write for TeeWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            std::io::stdout().write_all(buf)?;
+            self.file.write_all(buf)?;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            std::io::stdout().flush()?;
+            self.file.flush()
+        }
+    }
+
+    /// Write a crash report on panic, before running the default panic handling.
+    pub fn install_panic_hook() {
+        let Some(dir) = log_dir() else {
+            log::warn!("No data folder available, crash reports are disabled.");
+            return;
+        };
+
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let report = format_report(&info.to_string());
+            if std::fs::create_dir_all(&dir).is_ok()
+                && std::fs::write(dir.join(PENDING_REPORT), &report).is_ok()
+            {
+                eprintln!(
+                    "Crash report written to {}",
+                    dir.join(PENDING_REPORT).display()
+                );
+            }
+            previous(info);
+        }));
+    }
+
+    fn format_report(panic: &str) -> String {
+        let mut report = format!(
+            "Brush {} crashed at {}\n\nPlatform: {} {}\n",
+            env!("CARGO_PKG_VERSION"),
+            humantime::format_rfc3339_seconds(SystemTime::now()),
+            std::env::consts::OS,
+            std::env::consts::ARCH,
+        );
+
+        // The panic might have happened while holding the lock, don't wait for it.
+        if let Ok(state) = STATE.try_lock() {
+            let adapter = state.adapter.as_deref().unwrap_or("unknown");
+            report += &format!("Adapter: {adapter}\n\nPanic: {panic}\n\nLast messages:\n");
+            for line in &state.recent {
+                report += &format!("  {line}\n");
+            }
+        } else {
+            report += &format!("\nPanic: {panic}\n");
+        }
+
+        let backtrace = std::backtrace::Backtrace::force_capture();
+        report += &format!("\nBacktrace:\n{backtrace}\n");
+        report
+    }
+
+    /// A crash report left behind by an earlier run.
+    pub struct CrashReport {
+        pub path: PathBuf,
+        pub text: String,
+    }
+
+    impl CrashReport {
+        pub fn dir(&self) -> &Path {
+            self.path.parent().unwrap_or(&self.path)
+        }
+    }
+
+    /// Take the report of the last crash, if there is one that hasn't been shown yet.
+    ///
+    /// The report is moved to a timestamped file, so it's only shown once.
+    pub fn take_last_crash() -> Option<CrashReport> {
+        let pending = log_dir()?.join(PENDING_REPORT);
+        let text = std::fs::read_to_string(&pending).ok()?;
+
+        let secs = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let path = pending.with_file_name(format!("crash_{secs}.txt"));
+        if std::fs::rename(&pending, &path).is_err() {
+            let _ = std::fs::remove_file(&pending);
+            return Some(CrashReport {
+                path: pending,
+                text,
+            });
+        }
+        Some(CrashReport { path, text })
+    }
+}
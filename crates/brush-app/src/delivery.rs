@@ -0,0 +1,148 @@
+//! Exporting a splat in all the versions it's usually delivered in, in one go.
+
+use std::io::Cursor;
+
+use anyhow::Context;
+use brush_dataset::splat_export::{splat_to_ply, splat_to_spz};
+use brush_render::{
+    SplatForward, camera::Camera, gaussian_splats::Splats, post_process::PostProcess,
+};
+use burn::prelude::Backend;
+use image::{DynamicImage, ImageFormat, Rgba32FImage};
+use serde::Serialize;
+
+/// Name all files of a delivery start with.
+pub const DELIVERY_NAME: &str = "scene";
+
+/// Width of the thumbnail, its height follows the aspect of the camera.
+const THUMBNAIL_WIDTH: u32 = 512;
+
+/// The mobile version keeps some view dependent color, at a fraction of the size.
+const MOBILE_SH_DEGREE: u32 = 1;
+
+#[derive(Serialize)]
+struct FileEntry {
+    file: String,
+    bytes: usize,
+    description: &'static str,
+}
+
+#[derive(Serialize)]
+struct Metadata {
+    exported_with: String,
+    splats: u32,
+    sh_degree: u32,
+    files: Vec<FileEntry>,
+}
+
+/// Render the splats from `camera` for a preview image of the delivery, as a PNG.
+pub async fn render_thumbnail<B: Backend + SplatForward<B>>(
+    splats: &Splats<B>,
+    camera: &Camera,
+    post_process: &PostProcess,
+) -> anyhow::Result<Vec<u8>> {
+    let aspect = (camera.fov_y * 0.5).tan() / (camera.fov_x * 0.5).tan();
+    let height = ((THUMBNAIL_WIDTH as f64 * aspect).round() as u32).max(1);
+    let img =
+        splats.render_post_processed(camera, glam::uvec2(THUMBNAIL_WIDTH, height), post_process);
+    let data = img
+        .into_data_async()
+        .await
+        .into_vec::<f32>()
+        .map_err(|e| anyhow::anyhow!("Failed to read render: {e:?}"))?;
+    let image = Rgba32FImage::from_raw(THUMBNAIL_WIDTH, height, data)
+        .context("Render has unexpected size")?;
+
+    let mut png = vec![];
+    DynamicImage::ImageRgba32F(image)
+        .into_rgba8()
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    Ok(png)
+}
+
+/// The files of a delivery, as names and their contents: the full quality ply, a compressed
+/// .spz, a ply with less view dependent color for mobile viewers, the thumbnail, and a JSON
+/// file describing them.
+pub async fn delivery_files<B: Backend>(
+    splats: Splats<B>,
+    thumbnail_png: Vec<u8>,
+) -> anyhow::Result<Vec<(String, Vec<u8>)>> {
+    let sh_degree = splats.sh_degree();
+    let mobile = splats
+        .clone()
+        .with_sh_degree(sh_degree.min(MOBILE_SH_DEGREE));
+
+    let files = [
+        (
+            format!("{DELIVERY_NAME}.ply"),
+            splat_to_ply(splats.clone()).await?,
+            "Full quality splats",
+        ),
+        (
+            format!("{DELIVERY_NAME}.spz"),
+            splat_to_spz(splats.clone()).await?,
+            "Compressed splats, for streaming on the web",
+        ),
+        (
+            format!("{DELIVERY_NAME}_mobile.ply"),
+            splat_to_ply(mobile).await?,
+            "Splats with view dependent color up to SH degree 1, for mobile viewers",
+        ),
+        (
+            format!("{DELIVERY_NAME}_thumbnail.png"),
+            thumbnail_png,
+            "Preview image",
+        ),
+    ];
+
+    let metadata = Metadata {
+        exported_with: format!("Brush {}", env!("CARGO_PKG_VERSION")),
+        splats: splats.num_splats(),
+        sh_degree,
+        files: files
+            .iter()
+            .map(|(file, data, description)| FileEntry {
+                file: file.clone(),
+                bytes: data.len(),
+                description: *description,
+            })
+            .collect(),
+    };
+    let metadata = serde_json::to_vec_pretty(&metadata)?;
+
+    Ok(files
+        .into_iter()
+        .map(|(file, data, _)| (file, data))
+        .chain([(format!("{DELIVERY_NAME}.json"), metadata)])
+        .collect())
+}
+
+/// Save the files of a delivery. Natively they're written to a picked folder, on the web
+/// they're downloaded as a zip.
+pub async fn save_delivery(files: Vec<(String, Vec<u8>)>) -> anyhow::Result<()> {
+    #[cfg(not(target_family = "wasm"))]
+    {
+        let dir = rrfd::pick_directory().await?;
+        for (name, data) in files {
+            let path = dir.join(&name);
+            std::fs::write(&path, data).with_context(|| format!("Failed to write {path:?}"))?;
+        }
+    }
+
+    #[cfg(target_family = "wasm")]
+    {
+        use std::io::Write;
+        use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+        let file = rrfd::save_file(&format!("{DELIVERY_NAME}_delivery.zip")).await?;
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        for (name, data) in files {
+            zip.start_file(name, options)?;
+            zip.write_all(&data)?;
+        }
+        file.write(&zip.finish()?.into_inner()).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use brush_render::error::Suggestion;
+use serde::{Deserialize, Serialize};
+
+pub(crate) const LOCALE_STORAGE_KEY: &str = "brush_locale";
+
+/// Languages the UI is translated to.
+///
+/// Each locale is a simple `key = value` catalog in the locales folder. To add a
+/// language, copy `en.txt`, translate the values, and add it here.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Locale {
+    #[default]
+    English,
+    German,
+}
+
+impl Locale {
+    pub const ALL: [Self; 2] = [Self::English, Self::German];
+
+    /// Name of the language, in that language.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::English => "English",
+            Self::German => "Deutsch",
+        }
+    }
+
+    fn catalog(self) -> &'static str {
+        match self {
+            Self::English => include_str!("../locales/en.txt"),
+            Self::German => include_str!("../locales/de.txt"),
+        }
+    }
+}
+
+static CURRENT: AtomicU8 = AtomicU8::new(0);
+
+static CATALOGS: LazyLock<Vec<HashMap<&'static str, String>>> =
+    LazyLock::new(|| Locale::ALL.iter().map(|l| parse(l.catalog())).collect());
+
+fn parse(catalog: &'static str) -> HashMap<&'static str, String> {
+    catalog
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| l.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim().replace("\\n", "\n")))
+        .collect()
+}
+
+pub fn set_locale(locale: Locale) {
+    let index = Locale::ALL.iter().position(|l| *l == locale).unwrap_or(0);
+    CURRENT.store(index as u8, Ordering::Relaxed);
+}
+
+pub fn locale() -> Locale {
+    Locale::ALL[CURRENT.load(Ordering::Relaxed) as usize]
+}
+
+/// Look up a UI string in the current locale.
+///
+/// Falls back to English for missing translations, and to the key itself for missing strings.
+pub fn tr(key: &'static str) -> &'static str {
+    let catalogs = &*CATALOGS;
+    catalogs[CURRENT.load(Ordering::Relaxed) as usize]
+        .get(key)
+        .or_else(|| catalogs[0].get(key))
+        .map_or(key, String::as_str)
+}
+
+/// Key of the suggestion for an error, phrased for the app, eg. referring to its settings
+/// instead of command line flags.
+pub fn suggestion_key(suggestion: Suggestion) -> &'static str {
+    match suggestion {
+        Suggestion::UnsupportedFormat => "error.unsupported_format",
+        Suggestion::DownloadFailed => "error.download_failed",
+        Suggestion::UnknownLayout => "error.unknown_layout",
+        Suggestion::InitFileCount => "error.init_file_count",
+        Suggestion::ArchiveTooLarge => "error.archive_too_large",
+        Suggestion::TooManyFiles => "error.too_many_files",
+        Suggestion::ImageTooLarge => "error.image_too_large",
+        Suggestion::OutOfMemory => "error.out_of_memory",
+        Suggestion::SplatsFile => "error.splats_file",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catalogs_have_same_keys() {
+        let english = parse(Locale::English.catalog());
+        for locale in Locale::ALL {
+            let catalog = parse(locale.catalog());
+            for key in english.keys() {
+                assert!(catalog.contains_key(key), "{locale:?} is missing {key}");
+            }
+        }
+    }
+
+    #[test]
+    fn suggestions_are_translated() {
+        let english = parse(Locale::English.catalog());
+        for suggestion in [
+            Suggestion::UnsupportedFormat,
+            Suggestion::DownloadFailed,
+            Suggestion::UnknownLayout,
+            Suggestion::InitFileCount,
+            Suggestion::ArchiveTooLarge,
+            Suggestion::TooManyFiles,
+            Suggestion::ImageTooLarge,
+            Suggestion::OutOfMemory,
+            Suggestion::SplatsFile,
+        ] {
+            let key = suggestion_key(suggestion);
+            assert!(english.contains_key(key), "Missing {key}");
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Training jobs to run one after the other.
+
+use brush_process::{data_source::DataSource, process_loop::ProcessArgs};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum JobStatus {
+    Waiting,
+    Running,
+    Done,
+    Failed(String),
+    Cancelled,
+}
+
+pub(crate) struct Job {
+    pub id: u64,
+    pub source: DataSource,
+    pub args: ProcessArgs,
+    pub status: JobStatus,
+}
+
+impl Job {
+    /// Short name to show, the last part of the path or URL.
+    pub fn name(&self) -> String {
+        match &self.source {
+            DataSource::Url(s) | DataSource::Path(s) => s
+                .trim_end_matches(['/', '\\'])
+                .rsplit(['/', '\\'])
+                .next()
+                .unwrap_or(s)
+                .to_owned(),
+            DataSource::PickFile | DataSource::PickDirectory => "?".to_owned(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct JobQueue {
+    jobs: Vec<Job>,
+    next_id: u64,
+    running: Option<u64>,
+    /// Whether to start the next waiting job when the device is free.
+    pub active: bool,
+}
+
+impl JobQueue {
+    pub fn jobs(&self) -> &[Job] {
+        &self.jobs
+    }
+
+    /// Add a job at the end of the queue. Jobs that pick a file can't run unattended, so
+    /// only sources that can be loaded again are queued.
+    pub fn push(&mut self, source: DataSource, args: ProcessArgs) {
+        if matches!(source, DataSource::PickFile | DataSource::PickDirectory) {
+            return;
+        }
+        self.jobs.push(Job {
+            id: self.next_id,
+            source,
+            args,
+            status: JobStatus::Waiting,
+        });
+        self.next_id += 1;
+    }
+
+    /// Swap a job with its neighbour, `up` towards the front of the queue.
+    pub fn reorder(&mut self, id: u64, up: bool) {
+        let Some(index) = self.jobs.iter().position(|j| j.id == id) else {
+            return;
+        };
+        let other = if up {
+            index.checked_sub(1)
+        } else {
+            Some(index + 1).filter(|&i| i < self.jobs.len())
+        };
+        if let Some(other) = other {
+            self.jobs.swap(index, other);
+        }
+    }
+
+    /// Take a job out of the queue. The running job is only marked cancelled, stopping its
+    /// process is up to the caller.
+    pub fn cancel(&mut self, id: u64) {
+        if self.running == Some(id) {
+            self.finish(JobStatus::Cancelled);
+        } else {
+            self.jobs.retain(|j| j.id != id);
+        }
+    }
+
+    /// Remove jobs that are done, failed or cancelled.
+    pub fn clear_finished(&mut self) {
+        self.jobs
+            .retain(|j| matches!(j.status, JobStatus::Waiting | JobStatus::Running));
+    }
+
+    pub fn running(&self) -> Option<u64> {
+        self.running
+    }
+
+    /// Mark the next waiting job as running, and return what to start it with.
+    ///
+    /// Stops the queue when there's nothing left to run.
+    pub fn start_next(&mut self) -> Option<(DataSource, ProcessArgs)> {
+        if !self.active || self.running.is_some() {
+            return None;
+        }
+        let Some(job) = self
+            .jobs
+            .iter_mut()
+            .find(|j| j.status == JobStatus::Waiting)
+        else {
+            self.active = false;
+            return None;
+        };
+        job.status = JobStatus::Running;
+        self.running = Some(job.id);
+        Some((job.source.clone(), job.args.clone()))
+    }
+
+    /// The running job stopped with `status`.
+    pub fn finish(&mut self, status: JobStatus) {
+        let Some(id) = self.running.take() else {
+            return;
+        };
+        if let Some(job) = self.jobs.iter_mut().find(|j| j.id == id) {
+            job.status = status;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use brush_process::{data_source::DataSource, process_loop::ProcessArgs};
+
+    use super::{JobQueue, JobStatus};
+
+    fn queue(names: &[&str]) -> JobQueue {
+        let mut queue = JobQueue::default();
+        for name in names {
+            queue.push(DataSource::Path((*name).to_owned()), ProcessArgs::default());
+        }
+        queue
+    }
+
+    fn names(queue: &JobQueue) -> Vec<String> {
+        queue.jobs().iter().map(|j| j.name()).collect()
+    }
+
+    #[test]
+    fn runs_in_order() {
+        let mut queue = queue(&["data/a", "data/b.zip"]);
+        assert!(queue.start_next().is_none(), "Queue isn't started yet");
+
+        queue.active = true;
+        assert!(queue.start_next().is_some());
+        assert!(queue.start_next().is_none(), "One job at a time");
+        queue.finish(JobStatus::Done);
+
+        let (source, _) = queue.start_next().expect("Second job should start");
+        assert!(matches!(source, DataSource::Path(p) if p == "data/b.zip"));
+        queue.finish(JobStatus::Failed("Out of memory".to_owned()));
+
+        assert!(queue.start_next().is_none());
+        assert!(!queue.active, "Queue stops when it's done");
+        queue.clear_finished();
+        assert!(queue.jobs().is_empty());
+    }
+
+    #[test]
+    fn reorder_and_cancel() {
+        let mut queue = queue(&["a", "b", "c"]);
+        let ids: Vec<_> = queue.jobs().iter().map(|j| j.id).collect();
+
+        queue.reorder(ids[2], true);
+        assert_eq!(names(&queue), ["a", "c", "b"]);
+        queue.reorder(ids[0], true);
+        queue.reorder(ids[1], false);
+        assert_eq!(names(&queue), ["a", "c", "b"]);
+
+        queue.cancel(ids[2]);
+        assert_eq!(names(&queue), ["a", "b"]);
+
+        queue.active = true;
+        queue.start_next();
+        queue.cancel(ids[0]);
+        assert_eq!(queue.jobs()[0].status, JobStatus::Cancelled);
+        assert_eq!(queue.running(), None);
+    }
+}
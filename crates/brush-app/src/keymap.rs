@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+
+use egui::Key;
+use serde::{Deserialize, Serialize};
+
+use crate::i18n::tr;
+
+pub(crate) const KEYMAP_STORAGE_KEY: &str = "brush_keymap";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    RollLeft,
+    RollRight,
+    ResetRoll,
+    LookAround,
+    PauseTraining,
+    Export,
+    Frame,
+    ToggleQuadView,
+    ToggleTrainingView,
+    ToggleLowPower,
+    TogglePanels,
+    PreviousModel,
+}
+
+impl Action {
+    pub const ALL: [Self; 18] = [
+        Self::MoveForward,
+        Self::MoveBackward,
+        Self::MoveLeft,
+        Self::MoveRight,
+        Self::MoveUp,
+        Self::MoveDown,
+        Self::RollLeft,
+        Self::RollRight,
+        Self::ResetRoll,
+        Self::LookAround,
+        Self::PauseTraining,
+        Self::Export,
+        Self::Frame,
+        Self::ToggleQuadView,
+        Self::ToggleTrainingView,
+        Self::ToggleLowPower,
+        Self::TogglePanels,
+        Self::PreviousModel,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::MoveForward => tr("action.move_forward"),
+            Self::MoveBackward => tr("action.move_backward"),
+            Self::MoveLeft => tr("action.move_left"),
+            Self::MoveRight => tr("action.move_right"),
+            Self::MoveUp => tr("action.move_up"),
+            Self::MoveDown => tr("action.move_down"),
+            Self::RollLeft => tr("action.roll_left"),
+            Self::RollRight => tr("action.roll_right"),
+            Self::ResetRoll => tr("action.reset_roll"),
+            Self::LookAround => tr("action.look_around"),
+            Self::PauseTraining => tr("action.pause_training"),
+            Self::Export => tr("action.export"),
+            Self::Frame => tr("action.frame"),
+            Self::ToggleQuadView => tr("action.toggle_quad_view"),
+            Self::ToggleTrainingView => tr("action.toggle_training_view"),
+            Self::ToggleLowPower => tr("action.toggle_low_power"),
+            Self::TogglePanels => tr("action.toggle_panels"),
+            Self::PreviousModel => tr("action.previous_model"),
+        }
+    }
+
+    fn default_keys(self) -> Vec<Key> {
+        match self {
+            Self::MoveForward => vec![Key::W, Key::ArrowUp],
+            Self::MoveBackward => vec![Key::S, Key::ArrowDown],
+            Self::MoveLeft => vec![Key::A, Key::ArrowLeft],
+            Self::MoveRight => vec![Key::D, Key::ArrowRight],
+            Self::MoveUp => vec![Key::E],
+            Self::MoveDown => vec![Key::Q],
+            Self::RollLeft => vec![Key::Z],
+            Self::RollRight => vec![Key::C],
+            Self::ResetRoll => vec![Key::X],
+            Self::LookAround => vec![Key::Space],
+            Self::PauseTraining => vec![Key::P],
+            Self::Export => vec![],
+            Self::Frame => vec![Key::F],
+            Self::ToggleQuadView => vec![Key::F2],
+            Self::ToggleTrainingView => vec![Key::F3],
+            Self::ToggleLowPower => vec![],
+            // Not Tab, egui uses that to move focus between widgets.
+            Self::TogglePanels => vec![Key::F4],
+            Self::PreviousModel => vec![],
+        }
+    }
+}
+
+/// Keyboard shortcuts for the viewer, saved with the rest of the app state.
+///
+/// Actions that aren't in the map use their default keys, so shortcuts added
+/// later still work with an older saved keymap.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Keymap {
+    #[serde(default)]
+    bindings: BTreeMap<Action, Vec<Key>>,
+}
+
+impl Keymap {
+    pub fn keys(&self, action: Action) -> Vec<Key> {
+        self.bindings
+            .get(&action)
+            .cloned()
+            .unwrap_or_else(|| action.default_keys())
+    }
+
+    pub fn set_keys(&mut self, action: Action, keys: Vec<Key>) {
+        self.bindings.insert(action, keys);
+    }
+
+    pub fn reset(&mut self) {
+        self.bindings.clear();
+    }
+
+    /// Whether any key for this action is held down.
+    pub fn down(&self, ctx: &egui::Context, action: Action) -> bool {
+        let keys = self.keys(action);
+        !ctx.wants_keyboard_input() && ctx.input(|r| keys.iter().any(|k| r.key_down(*k)))
+    }
+
+    /// Whether any key for this action was pressed this frame.
+    pub fn pressed(&self, ctx: &egui::Context, action: Action) -> bool {
+        let keys = self.keys(action);
+        !ctx.wants_keyboard_input() && ctx.input(|r| keys.iter().any(|k| r.key_pressed(*k)))
+    }
+
+    pub fn describe(&self, action: Action) -> String {
+        let keys = self.keys(action);
+        if keys.is_empty() {
+            tr("keymap.none").to_owned()
+        } else {
+            keys.iter()
+                .map(|k| k.name())
+                .collect::<Vec<_>>()
+                .join(" / ")
+        }
+    }
+}
+
+/// Editor for the keymap. Click an action to rebind it to the next pressed key.
+#[derive(Default)]
+pub(crate) struct KeymapEditor {
+    rebinding: Option<Action>,
+}
+
+impl KeymapEditor {
+    pub(crate) fn ui(&mut self, ui: &mut egui::Ui, keymap: &mut Keymap) {
+        if let Some(action) = self.rebinding {
+            let pressed = ui.input(|r| {
+                r.events.iter().find_map(|e| match e {
+                    egui::Event::Key {
+                        key, pressed: true, ..
+                    } => Some(*key),
+                    _ => None,
+                })
+            });
+
+            match pressed {
+                Some(Key::Escape) => self.rebinding = None,
+                Some(Key::Backspace | Key::Delete) => {
+                    keymap.set_keys(action, vec![]);
+                    self.rebinding = None;
+                }
+                Some(key) => {
+                    keymap.set_keys(action, vec![key]);
+                    self.rebinding = None;
+                }
+                None => {}
+            }
+        }
+
+        egui::Grid::new("keymap_grid")
+            .num_columns(2)
+            .spacing([40.0, 4.0])
+            .striped(true)
+            .show(ui, |ui| {
+                for action in Action::ALL {
+                    ui.label(action.label());
+
+                    let text = if self.rebinding == Some(action) {
+                        tr("keymap.press_key").to_owned()
+                    } else {
+                        keymap.describe(action)
+                    };
+                    if ui
+                        .button(text)
+                        .on_hover_text(tr("keymap.rebind_hover"))
+                        .clicked()
+                    {
+                        self.rebinding = Some(action);
+                    }
+                    ui.end_row();
+                }
+            });
+
+        if ui.button(tr("keymap.reset")).clicked() {
+            keymap.reset();
+            self.rebinding = None;
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! Kiosk mode, for unattended screens in showrooms and museums.
+//!
+//! The viewer hides all UI, slowly orbits the scene while nobody is interacting, and moves
+//! the camera back to its start after a while without input. With a playlist, it cycles
+//! through the splats in it.
+
+use std::collections::HashMap;
+
+use web_time::{Duration, Instant};
+
+use crate::app::AppContext;
+
+/// How long to wait after the last input before orbiting again.
+const ORBIT_DELAY: Duration = Duration::from_secs(3);
+
+pub(crate) struct Kiosk {
+    playlist: Vec<String>,
+    current: usize,
+    /// Time each splat of the playlist is shown.
+    interval: Duration,
+    /// Time without input after which the camera is reset.
+    reset_after: Duration,
+    /// Orbit speed in radians per second.
+    orbit_speed: f32,
+    last_input: Instant,
+    last_switch: Instant,
+    was_reset: bool,
+}
+
+/// Splats to cycle through, the `url` parameter followed by the comma separated `playlist`.
+fn playlist_from_search(params: &HashMap<String, String>) -> Vec<String> {
+    params
+        .get("url")
+        .map(String::as_str)
+        .into_iter()
+        .chain(
+            params
+                .get("playlist")
+                .into_iter()
+                .flat_map(|p| p.split(',')),
+        )
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+fn has_input(ctx: &egui::Context) -> bool {
+    ctx.input(|i| {
+        i.pointer.any_down()
+            || i.raw_scroll_delta != egui::Vec2::ZERO
+            || i.events.iter().any(|e| {
+                matches!(
+                    e,
+                    egui::Event::Key { .. }
+                        | egui::Event::PointerButton { .. }
+                        | egui::Event::Touch { .. }
+                        | egui::Event::Zoom(_)
+                )
+            })
+    })
+}
+
+impl Kiosk {
+    /// Kiosk settings from the search parameters, or `None` if kiosk mode is off.
+    pub(crate) fn from_search(params: &HashMap<String, String>) -> Option<Self> {
+        if !params.get("kiosk").is_some_and(|k| k == "true") {
+            return None;
+        }
+        let secs = |key: &str, default: f32| {
+            let secs = params
+                .get(key)
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(default);
+            Duration::from_secs_f32(f32::max(secs, 1.0))
+        };
+        let orbit_speed: f32 = params
+            .get("orbit_speed")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(6.0);
+
+        Some(Self {
+            playlist: playlist_from_search(params),
+            current: 0,
+            interval: secs("kiosk_interval", 60.0),
+            reset_after: secs("kiosk_reset", 30.0),
+            orbit_speed: orbit_speed.to_radians(),
+            last_input: Instant::now(),
+            last_switch: Instant::now(),
+            was_reset: true,
+        })
+    }
+
+    /// The splat to show first.
+    pub(crate) fn first_url(&self) -> Option<&str> {
+        self.playlist.first().map(String::as_str)
+    }
+
+    /// Orbit and reset the camera. Returns the next splat to load when it's time to switch.
+    pub(crate) fn tick(&mut self, ctx: &egui::Context, context: &mut AppContext) -> Option<String> {
+        let now = Instant::now();
+        if has_input(ctx) {
+            self.last_input = now;
+            self.was_reset = false;
+        }
+
+        let idle = now - self.last_input;
+        if idle < ORBIT_DELAY {
+            ctx.request_repaint_after(ORBIT_DELAY - idle);
+            return None;
+        }
+
+        if !self.was_reset && idle >= self.reset_after {
+            context.reset_camera();
+            self.was_reset = true;
+        }
+        // Clamp the time step, so the camera doesn't jump after the tab was hidden.
+        let dt = ctx.input(|i| i.stable_dt).min(0.1);
+        context.controls.orbit(self.orbit_speed * dt, dt);
+        ctx.request_repaint();
+
+        if self.playlist.len() > 1 && now - self.last_switch >= self.interval {
+            self.last_switch = now;
+            self.current = (self.current + 1) % self.playlist.len();
+            return Some(self.playlist[self.current].clone());
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::playlist_from_search;
+
+    #[test]
+    fn playlist_starts_with_url() {
+        let params = HashMap::from([
+            ("url".to_owned(), "https://example.com/a.ply".to_owned()),
+            (
+                "playlist".to_owned(),
+                "https://example.com/b.ply, https://example.com/c.spz,".to_owned(),
+            ),
+        ]);
+        assert_eq!(
+            playlist_from_search(&params),
+            [
+                "https://example.com/a.ply",
+                "https://example.com/b.ply",
+                "https://example.com/c.spz"
+            ]
+        );
+        assert!(playlist_from_search(&HashMap::new()).is_empty());
+    }
+}
@@ -1,10 +1,26 @@
 #![recursion_limit = "256"]
 
+pub mod annotations;
 pub mod camera_controls;
+pub mod crash;
+pub mod keymap;
+mod kiosk;
+pub mod log_capture;
 mod panels;
 
+#[cfg(target_os = "android")]
+mod capture;
+
 mod app;
+mod clip_planes;
+mod delivery;
+pub mod i18n;
+mod job_queue;
 pub mod running_process;
+mod session;
+mod share;
+mod storage;
+mod web_export;
 
 pub use app::*;
 use burn::backend::Autodiff;
@@ -0,0 +1,237 @@
+//! Keeps recent log records and tracing events in memory, so they can be shown in the log
+//! panel.
+
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::{LazyLock, Mutex};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use tracing_subscriber::Layer;
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::layer::Context;
+use web_time::{Duration, Instant};
+
+/// How many records to keep around. Older records are dropped.
+const MAX_RECORDS: usize = 10_000;
+
+/// How long new records can wait to be shown, so a burst of them only repaints once.
+const NEW_RECORD_REPAINT: Duration = Duration::from_millis(250);
+
+/// Rough grouping of log targets, to filter them in the UI.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LogModule {
+    App,
+    Process,
+    Dataset,
+    Train,
+    Render,
+    Other,
+}
+
+impl LogModule {
+    pub const ALL: [Self; 6] = [
+        Self::App,
+        Self::Process,
+        Self::Dataset,
+        Self::Train,
+        Self::Render,
+        Self::Other,
+    ];
+
+    pub fn from_target(target: &str) -> Self {
+        let krate = target.split("::").next().unwrap_or(target);
+        match krate {
+            "brush_app" | "brush_ui" | "brush_cli" => Self::App,
+            "brush_process" | "brush_rerun" => Self::Process,
+            "brush_dataset" | "rrfd" => Self::Dataset,
+            "brush_train" => Self::Train,
+            "brush_render" | "brush_kernel" | "brush_sort" | "brush_prefix_sum" => Self::Render,
+            _ => Self::Other,
+        }
+    }
+
+    /// Most verbose level that is captured for this module.
+    ///
+    /// Dependencies like wgpu are very chatty at debug level, so only keep their info logs.
+    fn capture_level(self) -> Level {
+        match self {
+            Self::Other => Level::Info,
+            _ => Level::Debug,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LogRecord {
+    /// Time since the app started.
+    pub elapsed: web_time::Duration,
+    pub level: Level,
+    pub module: LogModule,
+    pub target: String,
+    pub message: String,
+}
+
+struct Captured {
+    start: Instant,
+    records: VecDeque<LogRecord>,
+    /// Total number of records captured, including dropped ones.
+    total: u64,
+}
+
+static CAPTURED: LazyLock<Mutex<Captured>> = LazyLock::new(|| {
+    Mutex::new(Captured {
+        start: Instant::now(),
+        records: VecDeque::new(),
+        total: 0,
+    })
+});
+
+/// UI to repaint when new records come in.
+static REPAINT: Mutex<Option<egui::Context>> = Mutex::new(None);
+
+fn capture(level: Level, target: &str, message: String) {
+    let module = LogModule::from_target(target);
+    if level > module.capture_level() {
+        return;
+    }
+
+    {
+        let Ok(mut captured) = CAPTURED.lock() else {
+            return;
+        };
+        if captured.records.len() == MAX_RECORDS {
+            captured.records.pop_front();
+        }
+        let elapsed = captured.start.elapsed();
+        captured.records.push_back(LogRecord {
+            elapsed,
+            level,
+            module,
+            target: target.to_owned(),
+            message,
+        });
+        captured.total += 1;
+    }
+
+    // egui might log while it holds its own lock, and would deadlock on the repaint. Its
+    // records show up on the next repaint instead.
+    if ["egui", "epaint", "eframe"]
+        .iter()
+        .any(|krate| target.starts_with(krate))
+    {
+        return;
+    }
+    let ctx = REPAINT.lock().ok().and_then(|ctx| ctx.clone());
+    if let Some(ctx) = ctx {
+        ctx.request_repaint_after(NEW_RECORD_REPAINT);
+    }
+}
+
+struct CaptureLogger {
+    inner: Option<Box<dyn Log>>,
+}
+
+impl Log for CaptureLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= LogModule::from_target(metadata.target()).capture_level()
+            || self.inner.as_ref().is_some_and(|l| l.enabled(metadata))
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if let Some(inner) = self.inner.as_ref().filter(|l| l.enabled(record.metadata())) {
+            inner.log(record);
+        }
+        capture(record.level(), record.target(), record.args().to_string());
+    }
+
+    fn flush(&self) {
+        if let Some(inner) = &self.inner {
+            inner.flush();
+        }
+    }
+}
+
+/// Install a logger that captures records for the log panel, and forwards them to `inner`.
+///
+/// `inner` still decides for itself which records it prints, `inner_level` is the most
+/// verbose level it might print.
+pub fn init(inner: Option<Box<dyn Log>>, inner_level: LevelFilter) {
+    if log::set_boxed_logger(Box::new(CaptureLogger { inner })).is_ok() {
+        log::set_max_level(inner_level.max(LevelFilter::Debug));
+    }
+}
+
+/// Collects the message and fields of a tracing event into one line.
+#[derive(Default)]
+struct EventMessage(String);
+
+impl tracing::field::Visit for EventMessage {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        if field.name() == "message" {
+            let _ = write!(self.0, "{value:?}");
+        } else {
+            let _ = write!(self.0, "{}={value:?}", field.name());
+        }
+    }
+}
+
+struct CaptureLayer;
+
+impl<S: tracing::Subscriber> Layer<S> for CaptureLayer {
+    fn on_event(&self, event: &tracing::Event<'_>, _: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut message = EventMessage::default();
+        event.record(&mut message);
+        capture(
+            to_log_level(*metadata.level()),
+            metadata.target(),
+            message.0,
+        );
+    }
+}
+
+fn to_log_level(level: tracing::Level) -> Level {
+    match level {
+        tracing::Level::ERROR => Level::Error,
+        tracing::Level::WARN => Level::Warn,
+        tracing::Level::INFO => Level::Info,
+        tracing::Level::DEBUG => Level::Debug,
+        tracing::Level::TRACE => Level::Trace,
+    }
+}
+
+/// Tracing layer that captures events for the log panel, like the logger does for log
+/// records. Spans are left to other layers.
+pub fn tracing_layer<S>() -> impl Layer<S>
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    CaptureLayer.with_filter(filter_fn(|metadata| {
+        metadata.is_event()
+            && to_log_level(*metadata.level())
+                <= LogModule::from_target(metadata.target()).capture_level()
+    }))
+}
+
+/// Repaint this UI when new records come in.
+pub fn repaint_on_new_records(ctx: &egui::Context) {
+    if let Ok(mut repaint) = REPAINT.lock() {
+        *repaint = Some(ctx.clone());
+    }
+}
+
+/// Total number of records captured so far, to cheaply check for new records.
+pub fn total_records() -> u64 {
+    CAPTURED.lock().map_or(0, |c| c.total)
+}
+
+/// Copy of the records that pass the given filter.
+pub fn records(filter: impl Fn(&LogRecord) -> bool) -> Vec<LogRecord> {
+    CAPTURED.lock().map_or_else(
+        |_| vec![],
+        |c| c.records.iter().filter(|r| filter(r)).cloned().collect(),
+    )
+}
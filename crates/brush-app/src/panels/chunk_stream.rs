@@ -0,0 +1,189 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Context;
+use brush_dataset::{brush_vfs::BrushVfs, chunks::ChunkIndex, splat_import};
+use brush_render::gaussian_splats::Splats;
+use brush_train::train::TrainBack;
+use burn::tensor::{Tensor, backend::AutodiffBackend};
+use burn_wgpu::WgpuDevice;
+use glam::Vec3;
+use tokio::sync::oneshot::{Receiver, error::TryRecvError};
+use tokio_stream::StreamExt;
+use web_time::{Duration, Instant};
+
+type InnerBackend = <TrainBack as AutodiffBackend>::InnerBackend;
+
+// Chunks are loaded once the camera is this many times their size away, and dropped again
+// a bit further out, so moving back and forth at the edge doesn't keep reloading them.
+const LOAD_RANGE: f32 = 1.0;
+const UNLOAD_RANGE: f32 = 1.5;
+// Most chunks kept loaded at once, the closest ones win.
+const MAX_RESIDENT: usize = 8;
+// Time a newly loaded chunk takes to fade in, rather than popping in.
+const FADE_IN: Duration = Duration::from_millis(500);
+
+struct Resident {
+    splats: Splats<InnerBackend>,
+    loaded_at: Instant,
+}
+
+/// Shows a merged scene saved as chunks, keeping only the chunks near the camera loaded, so
+/// scenes too big to fit in memory at once can still be explored.
+pub(crate) struct ChunkStreamer {
+    vfs: Arc<BrushVfs>,
+    dir: PathBuf,
+    index: ChunkIndex,
+    device: WgpuDevice,
+    resident: HashMap<usize, Resident>,
+    loading: Option<(usize, Receiver<anyhow::Result<Splats<InnerBackend>>>)>,
+    // Chunks that failed to load, not tried again.
+    failed: HashSet<usize>,
+    // The resident chunks appended together.
+    merged: Option<Splats<InnerBackend>>,
+    fading: bool,
+}
+
+async fn load_chunk(
+    vfs: Arc<BrushVfs>,
+    path: PathBuf,
+    device: WgpuDevice,
+) -> anyhow::Result<Splats<InnerBackend>> {
+    let reader = vfs.reader_at_path(&path).await?;
+    let stream = splat_import::load_splat_from_ply(reader, None, false, device);
+    let mut stream = std::pin::pin!(stream);
+    // Each message has all splats read so far, the last one has them all.
+    let mut splats = None;
+    while let Some(message) = stream.next().await {
+        splats = Some(message?.splats);
+    }
+    splats.with_context(|| format!("No splats in chunk {path:?}"))
+}
+
+impl ChunkStreamer {
+    pub(crate) fn new(
+        vfs: Arc<BrushVfs>,
+        dir: PathBuf,
+        index: ChunkIndex,
+        device: WgpuDevice,
+    ) -> Self {
+        Self {
+            vfs,
+            dir,
+            index,
+            device,
+            resident: HashMap::new(),
+            loading: None,
+            failed: HashSet::new(),
+            merged: None,
+            fading: false,
+        }
+    }
+
+    fn poll_loading(&mut self, ctx: &egui::Context) -> bool {
+        let Some((chunk, receiver)) = &mut self.loading else {
+            return false;
+        };
+        let chunk = *chunk;
+        let loaded = match receiver.try_recv() {
+            Ok(Ok(splats)) => Some(splats),
+            Ok(Err(e)) => {
+                log::error!("Failed to load chunk {chunk}: {e:#}");
+                self.failed.insert(chunk);
+                None
+            }
+            Err(TryRecvError::Empty) => return false,
+            Err(TryRecvError::Closed) => None,
+        };
+        self.loading = None;
+        let Some(splats) = loaded else {
+            return false;
+        };
+        self.resident.insert(
+            chunk,
+            Resident {
+                splats,
+                loaded_at: Instant::now(),
+            },
+        );
+        ctx.request_repaint();
+        true
+    }
+
+    fn start_loading(&mut self, chunk: usize, ctx: &egui::Context) {
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        let vfs = self.vfs.clone();
+        let path = self.dir.join(&self.index.chunks[chunk].file);
+        let device = self.device.clone();
+        let ctx = ctx.clone();
+        tokio_with_wasm::alias::spawn(async move {
+            let _ = sender.send(load_chunk(vfs, path, device).await);
+            ctx.request_repaint();
+        });
+        self.loading = Some((chunk, receiver));
+    }
+
+    /// Load and drop chunks for a camera at `position`, in the space of the splats. Gives the
+    /// splats to show, and whether they changed since the last call.
+    pub(crate) fn update(
+        &mut self,
+        ctx: &egui::Context,
+        position: Vec3,
+    ) -> (Option<Splats<InnerBackend>>, bool) {
+        let mut changed = self.poll_loading(ctx);
+
+        let keep: Vec<usize> = self
+            .index
+            .near(position, UNLOAD_RANGE)
+            .into_iter()
+            .take(MAX_RESIDENT)
+            .collect();
+        let count = self.resident.len();
+        self.resident.retain(|chunk, _| keep.contains(chunk));
+        changed |= self.resident.len() != count;
+
+        if self.loading.is_none() {
+            let next = self
+                .index
+                .near(position, LOAD_RANGE)
+                .into_iter()
+                .take(MAX_RESIDENT)
+                .find(|c| !self.resident.contains_key(c) && !self.failed.contains(c));
+            if let Some(chunk) = next {
+                self.start_loading(chunk, ctx);
+            }
+        }
+
+        // Rebuild once more after the last fade is done, to show the chunks at full opacity.
+        let was_fading = self.fading;
+        self.fading = self
+            .resident
+            .values()
+            .any(|r| r.loaded_at.elapsed() < FADE_IN);
+        if changed || self.fading || was_fading {
+            let mut chunks: Vec<_> = self.resident.iter().collect();
+            chunks.sort_by_key(|(chunk, _)| **chunk);
+            self.merged = chunks
+                .into_iter()
+                .map(|(_, resident)| {
+                    let fade = resident.loaded_at.elapsed().as_secs_f32() / FADE_IN.as_secs_f32();
+                    let splats = resident.splats.clone();
+                    if fade < 1.0 {
+                        let weights = Tensor::ones([splats.num_splats() as usize], &self.device)
+                            * fade.max(0.0);
+                        splats.with_opacity_scaled(weights)
+                    } else {
+                        splats
+                    }
+                })
+                .reduce(Splats::append);
+            changed = true;
+        }
+        if self.fading {
+            ctx.request_repaint();
+        }
+
+        (self.merged.clone(), changed)
+    }
+}
@@ -1,5 +1,11 @@
-use crate::app::{AppContext, AppPanel};
-use brush_dataset::scene::{Scene, SceneView, ViewType};
+use crate::{
+    app::{AppContext, AppPanel},
+    i18n::tr,
+};
+use brush_dataset::{
+    Dataset, anonymize,
+    scene::{Scene, SceneView, ViewType},
+};
 use brush_process::process_loop::ProcessMessage;
 use egui::{Color32, Slider, TextureHandle, TextureOptions, pos2};
 use tokio::sync::oneshot::Receiver;
@@ -43,9 +49,36 @@ impl DatasetPanel {
     }
 }
 
+fn export_anonymized(dataset: Dataset) {
+    let fut = async move {
+        let file = match rrfd::save_file("anonymized_dataset.zip").await {
+            Ok(file) => file,
+            Err(e) => {
+                log::error!("Failed to save file: {e}");
+                return;
+            }
+        };
+
+        let data = match anonymize::export_anonymized(&dataset, std::io::Cursor::new(vec![])).await
+        {
+            Ok(cursor) => cursor.into_inner(),
+            Err(e) => {
+                log::error!("Failed to export anonymized dataset: {e}");
+                return;
+            }
+        };
+
+        if let Err(e) = file.write(&data).await {
+            log::error!("Failed to write file: {e}");
+        }
+    };
+
+    tokio_with_wasm::alias::spawn(fut);
+}
+
 impl AppPanel for DatasetPanel {
     fn title(&self) -> String {
-        "Dataset".to_owned()
+        tr("panel.dataset").to_owned()
     }
 
     fn on_message(&mut self, message: &ProcessMessage, context: &mut AppContext) {
@@ -222,12 +255,12 @@ impl AppPanel for DatasetPanel {
                     let selected_view = selected.get_view(context);
                     let mask_info = if selected_view.image.has_alpha() {
                         if !selected_view.image.is_masked() {
-                            "rgb + alpha transparency"
+                            tr("dataset.rgb_alpha")
                         } else {
-                            "rgb, masked"
+                            tr("dataset.rgb_masked")
                         }
                     } else {
-                        "rgb"
+                        tr("dataset.rgb")
                     };
 
                     let info = format!(
@@ -238,12 +271,37 @@ impl AppPanel for DatasetPanel {
                         mask_info
                     );
                     ui.label(info);
+
+                    ui.add_space(10.0);
+
+                    let any_masked = context
+                        .dataset
+                        .train
+                        .views
+                        .iter()
+                        .any(|v| v.image.is_masked());
+                    let hover = if any_masked {
+                        tr("dataset.export_anonymized_hover")
+                    } else {
+                        tr("dataset.export_anonymized_no_masks")
+                    };
+                    if ui
+                        .add_enabled(
+                            any_masked,
+                            egui::Button::new(tr("dataset.export_anonymized")),
+                        )
+                        .on_hover_text(hover)
+                        .on_disabled_hover_text(hover)
+                        .clicked()
+                    {
+                        export_anonymized(context.dataset.clone());
+                    }
                 });
             }
         }
 
         if context.loading() && context.training() {
-            ui.label("Loading...");
+            ui.label(tr("common.loading"));
         }
     }
 
@@ -0,0 +1,147 @@
+use brush_render::{camera::Camera, gaussian_splats::Splats};
+use brush_train::train::TrainBack;
+use burn::tensor::backend::AutodiffBackend;
+use glam::{Affine3A, Quat, Vec3};
+use tokio::sync::oneshot::{Receiver, error::TryRecvError};
+
+use crate::app::AppContext;
+
+type InnerBackend = <TrainBack as AutodiffBackend>::InnerBackend;
+
+// Distances are measured with a tiny depth render in a narrow cone.
+const PROBE_SIZE: u32 = 8;
+const PROBE_FOV: f64 = 0.4;
+
+// Pixels need to be this opaque to count as a surface, so the camera can walk through
+// thin floaters.
+const SOLID_ALPHA: f32 = 0.5;
+
+/// The nearest solid surface in a depth render, see [`Splats::render_depth`].
+pub(super) fn nearest_surface(rgba: &[f32]) -> Option<f32> {
+    rgba.chunks_exact(4)
+        .filter(|p| p[3] > SOLID_ALPHA)
+        .map(|p| p[0] / p[3])
+        .min_by(f32::total_cmp)
+}
+
+/// Distance to the splats along `direction` from `position`, in the camera controls space.
+async fn probe(
+    splats: &Splats<InnerBackend>,
+    model_local_to_world: Affine3A,
+    position: Vec3,
+    direction: Vec3,
+) -> Option<f32> {
+    let rotation = Quat::from_rotation_arc(Vec3::Z, direction);
+    let transform = model_local_to_world * Affine3A::from_rotation_translation(rotation, position);
+    let camera = Camera::new(
+        transform.translation.into(),
+        Quat::from_mat3a(&transform.matrix3),
+        PROBE_FOV,
+        PROBE_FOV,
+        glam::vec2(0.5, 0.5),
+    );
+    let (depth, _) = splats.render_depth(&camera, glam::uvec2(PROBE_SIZE, PROBE_SIZE));
+    let depth = depth.into_data_async().await.to_vec::<f32>().ok()?;
+    let scale = model_local_to_world.matrix3.determinant().abs().cbrt();
+    nearest_surface(&depth).map(|d| d / scale.max(1e-6))
+}
+
+/// Distances from the camera to the splats around it.
+#[derive(Clone, Copy, Debug, Default)]
+struct Distances {
+    ahead: Option<f32>,
+    floor: Option<f32>,
+    wall: Option<f32>,
+}
+
+/// Measures how far the splats are from the camera, to scale the camera speed by, and to
+/// walk over the floor.
+#[derive(Default)]
+pub(crate) struct DepthProbe {
+    pending: Option<Receiver<Distances>>,
+    // Where the last measurement was taken, the view direction and the walking direction, to
+    // only measure again after moving.
+    last: Option<(Vec3, Vec3, Vec3)>,
+}
+
+impl DepthProbe {
+    pub(crate) fn update(
+        &mut self,
+        ctx: &egui::Context,
+        context: &mut AppContext,
+        splats: Option<&Splats<InnerBackend>>,
+    ) {
+        let walking = context.controls.walking();
+        let auto_speed = context.auto_speed;
+        let (Some(splats), true) = (splats, walking || auto_speed) else {
+            self.pending = None;
+            self.last = None;
+            context.controls.set_surface_distance(None);
+            return;
+        };
+
+        if let Some(receiver) = self.pending.as_mut() {
+            match receiver.try_recv() {
+                Ok(distances) => {
+                    self.pending = None;
+                    context.controls.set_surface_distance(distances.ahead);
+                    context
+                        .controls
+                        .set_walk_probe(distances.floor, distances.wall);
+                }
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Closed) => self.pending = None,
+            }
+        }
+
+        let controls = &context.controls;
+        let position = controls.position;
+        let view_dir = controls.rotation * Vec3::Z;
+        let walk_dir = controls.walk_direction();
+        let min_move = controls.focus_distance * 1e-3;
+        if self.last.is_some_and(|(p, v, w)| {
+            p.distance(position) < min_move && v.dot(view_dir) > 0.999 && w.dot(walk_dir) > 0.999
+        }) {
+            return;
+        }
+        self.last = Some((position, view_dir, walk_dir));
+
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        let splats = splats.clone();
+        let model_local_to_world = context.model_local_to_world;
+        let ctx = ctx.clone();
+        tokio_with_wasm::alias::spawn(async move {
+            let measure = |dir| probe(&splats, model_local_to_world, position, dir);
+            let mut distances = Distances::default();
+            if auto_speed {
+                distances.ahead = measure(view_dir).await;
+            }
+            if walking {
+                // Down is +Y in the camera controls space.
+                distances.floor = measure(Vec3::Y).await;
+                distances.wall = measure(walk_dir).await;
+            }
+            let _ = sender.send(distances);
+            ctx.request_repaint();
+        });
+        self.pending = Some(receiver);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::nearest_surface;
+
+    #[test]
+    fn nearest_solid_surface() {
+        // Depth is blended like a color, weighted by alpha.
+        let pixels = [
+            [0.0, 0.0, 0.0, 0.0],
+            [1.5, 1.5, 1.5, 0.3],
+            [1.6, 1.6, 1.6, 0.8],
+            [5.0, 5.0, 5.0, 1.0],
+        ];
+        assert_eq!(nearest_surface(pixels.as_flattened()), Some(2.0));
+        assert_eq!(nearest_surface(pixels[..2].as_flattened()), None);
+    }
+}
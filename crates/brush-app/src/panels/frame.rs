@@ -0,0 +1,79 @@
+use brush_render::{bounding_box::BoundingBox, gaussian_splats::Splats};
+use brush_train::train::TrainBack;
+use burn::tensor::{Tensor, backend::AutodiffBackend};
+use glam::{Affine3A, Vec3};
+use tokio::sync::oneshot::{Receiver, error::TryRecvError};
+
+type InnerBackend = <TrainBack as AutodiffBackend>::InnerBackend;
+
+// Fraction of the splats left out on each side of the bounds, so a few far away floaters
+// don't make the model tiny.
+const OUTLIER_FRACTION: f32 = 0.01;
+
+/// Bounds of the splat centers, in the space of the splats.
+async fn splat_bounds(splats: &Splats<InnerBackend>) -> Option<BoundingBox> {
+    let count = splats.num_splats() as usize;
+    if count == 0 {
+        return None;
+    }
+    // Each axis is sorted on its own, so the rows are the quantiles of each axis.
+    let sorted = splats.means.val().sort(0);
+    let low = ((count as f32 * OUTLIER_FRACTION) as usize).min(count - 1);
+    let high = count - 1 - low;
+    let rows = Tensor::cat(
+        vec![
+            sorted.clone().slice([low..low + 1, 0..3]),
+            sorted.slice([high..high + 1, 0..3]),
+        ],
+        0,
+    );
+    let data = rows.into_data_async().await.to_vec::<f32>().ok()?;
+    Some(BoundingBox::from_min_max(
+        Vec3::from_slice(&data[0..3]),
+        Vec3::from_slice(&data[3..6]),
+    ))
+}
+
+/// Finds the bounds of the model, to fit the camera to.
+#[derive(Default)]
+pub(crate) struct Framer {
+    pending: Option<Receiver<Option<BoundingBox>>>,
+}
+
+impl Framer {
+    pub(crate) fn frame(&mut self, ctx: &egui::Context, splats: &Splats<InnerBackend>) {
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        let splats = splats.clone();
+        let ctx = ctx.clone();
+        tokio_with_wasm::alias::spawn(async move {
+            let _ = sender.send(splat_bounds(&splats).await);
+            ctx.request_repaint();
+        });
+        self.pending = Some(receiver);
+    }
+
+    /// The center and radius of the bounds in the camera controls space, once they're found.
+    pub(crate) fn framed(&mut self, model_local_to_world: Affine3A) -> Option<(Vec3, f32)> {
+        let receiver = self.pending.as_mut()?;
+        let bounds = match receiver.try_recv() {
+            Ok(bounds) => bounds,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Closed) => None,
+        };
+        self.pending = None;
+        let bounds = bounds?;
+
+        let to_controls = model_local_to_world.inverse();
+        let corners = (0..8).map(|i| {
+            let sign = Vec3::new(
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { -1.0 } else { 1.0 },
+            );
+            to_controls.transform_point3(bounds.center + bounds.extent * sign)
+        });
+        let center = to_controls.transform_point3(bounds.center);
+        let radius = corners.map(|c| c.distance(center)).fold(0.0, f32::max);
+        Some((center, radius))
+    }
+}
@@ -0,0 +1,199 @@
+use std::sync::Arc;
+
+use crate::{
+    app::{AppContext, AppPanel},
+    i18n::tr,
+    running_process::start_process,
+    storage::{DatasetStorage, StoredDataset, default_storage, format_bytes},
+};
+use egui::{Button, Color32};
+use tokio::sync::oneshot::Receiver;
+use web_time::{Duration, Instant};
+
+/// How often the list is read again, to pick up datasets added or removed elsewhere.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+pub(crate) struct LocalDatasetsPanel {
+    storage: Option<Arc<dyn DatasetStorage>>,
+    datasets: Vec<StoredDataset>,
+    last_refresh: Option<Instant>,
+    import: Option<Receiver<anyhow::Result<()>>>,
+    error: Option<String>,
+    // Deleting asks for a second click.
+    confirm_delete: Option<String>,
+}
+
+impl LocalDatasetsPanel {
+    pub(crate) fn new() -> Self {
+        Self {
+            storage: default_storage().map(Arc::from),
+            datasets: vec![],
+            last_refresh: None,
+            import: None,
+            error: None,
+            confirm_delete: None,
+        }
+    }
+
+    fn refresh(&mut self, storage: &dyn DatasetStorage) {
+        self.last_refresh = Some(Instant::now());
+        match storage.list() {
+            Ok(datasets) => self.datasets = datasets,
+            Err(e) => self.error = Some(format!("{e:#}")),
+        }
+    }
+
+    fn start_import(&mut self, ctx: &egui::Context, storage: Arc<dyn DatasetStorage>, dir: bool) {
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        let ctx = ctx.clone();
+        tokio_with_wasm::alias::task::spawn(async move {
+            let result = if dir {
+                match rrfd::pick_directory().await {
+                    Ok(dir) => storage.import_dir(&dir),
+                    Err(e) => Err(e),
+                }
+            } else {
+                match rrfd::pick_file().await {
+                    Ok(file) => {
+                        let name = file.file_name();
+                        storage.import_file(&name, &file.read().await)
+                    }
+                    Err(e) => Err(e),
+                }
+            };
+            let _ = sender.send(result);
+            ctx.request_repaint();
+        });
+        self.import = Some(receiver);
+        self.error = None;
+    }
+}
+
+impl AppPanel for LocalDatasetsPanel {
+    fn title(&self) -> String {
+        tr("panel.local_datasets").to_owned()
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, context: &mut AppContext) {
+        let Some(storage) = self.storage.clone() else {
+            ui.label(tr("local.unavailable"));
+            return;
+        };
+
+        if let Some(receiver) = self.import.as_mut() {
+            if let Ok(result) = receiver.try_recv() {
+                self.import = None;
+                self.last_refresh = None;
+                if let Err(e) = result {
+                    self.error = Some(format!("{e:#}"));
+                }
+            }
+        }
+
+        if self
+            .last_refresh
+            .is_none_or(|last| last.elapsed() > REFRESH_INTERVAL)
+        {
+            self.refresh(storage.as_ref());
+        }
+        ui.ctx().request_repaint_after(REFRESH_INTERVAL);
+
+        ui.label(
+            egui::RichText::new(tr("local.location").replace("{path}", &storage.location())).weak(),
+        );
+
+        ui.horizontal(|ui| {
+            let idle = self.import.is_none();
+            if ui
+                .add_enabled(idle, Button::new(tr("local.import_file")))
+                .clicked()
+            {
+                self.start_import(ui.ctx(), storage.clone(), false);
+            }
+            let can_pick_dir = !cfg!(target_family = "wasm") && !cfg!(target_os = "android");
+            if can_pick_dir
+                && ui
+                    .add_enabled(idle, Button::new(tr("local.import_folder")))
+                    .clicked()
+            {
+                self.start_import(ui.ctx(), storage.clone(), true);
+            }
+            if !idle {
+                ui.spinner();
+            }
+        });
+
+        if let Some(error) = &self.error {
+            ui.colored_label(Color32::LIGHT_RED, error);
+        }
+
+        ui.add_space(10.0);
+
+        if self.datasets.is_empty() {
+            ui.label(tr("local.empty"));
+            return;
+        }
+
+        let mut train = None;
+        let mut queue = None;
+        let mut delete = None;
+        egui::Grid::new("local_datasets")
+            .num_columns(5)
+            .striped(true)
+            .show(ui, |ui| {
+                for dataset in &self.datasets {
+                    ui.label(&dataset.name);
+                    ui.label(format_bytes(dataset.size));
+                    if ui
+                        .button(tr("local.train"))
+                        .on_hover_text(tr("local.train_hover"))
+                        .clicked()
+                    {
+                        train = Some(dataset.name.clone());
+                    }
+                    if ui
+                        .button(tr("local.queue"))
+                        .on_hover_text(tr("local.queue_hover"))
+                        .clicked()
+                    {
+                        queue = Some(dataset.name.clone());
+                    }
+                    if self.confirm_delete.as_ref() == Some(&dataset.name) {
+                        if ui.button(tr("local.confirm_delete")).clicked() {
+                            delete = Some(dataset.name.clone());
+                        }
+                    } else if ui.button(tr("local.delete")).clicked() {
+                        self.confirm_delete = Some(dataset.name.clone());
+                    }
+                    ui.end_row();
+                }
+            });
+
+        if let Some(name) = train {
+            let source = storage.source(&name);
+            let args = context.dataset_args(&source);
+            context.connect_to(start_process(
+                source,
+                args.clone().unwrap_or_default(),
+                context.device.clone(),
+                ui.ctx().clone(),
+            ));
+            // Show the remembered args, to tweak them for the next run.
+            context.prefill_args = args;
+        }
+
+        if let Some(name) = queue {
+            let source = storage.source(&name);
+            let args = context.dataset_args(&source).unwrap_or_default();
+            context.queue.push(source, args);
+        }
+
+        if let Some(name) = delete {
+            self.confirm_delete = None;
+            if let Err(e) = storage.delete(&name) {
+                self.error = Some(format!("{e:#}"));
+            }
+            self.refresh(storage.as_ref());
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+
+use egui::{Color32, RichText};
+use log::{Level, LevelFilter};
+
+use crate::{
+    app::{AppContext, AppPanel},
+    i18n::tr,
+    log_capture::{self, LogModule, LogRecord},
+};
+
+// Records are captured up to debug level, trace would never show anything.
+const LEVELS: [LevelFilter; 5] = [
+    LevelFilter::Off,
+    LevelFilter::Error,
+    LevelFilter::Warn,
+    LevelFilter::Info,
+    LevelFilter::Debug,
+];
+
+fn module_label(module: LogModule) -> &'static str {
+    match module {
+        LogModule::App => tr("log.module.app"),
+        LogModule::Process => tr("log.module.process"),
+        LogModule::Dataset => tr("log.module.dataset"),
+        LogModule::Train => tr("log.module.train"),
+        LogModule::Render => tr("log.module.render"),
+        LogModule::Other => tr("log.module.other"),
+    }
+}
+
+fn level_color(level: Level) -> Color32 {
+    match level {
+        Level::Error => Color32::LIGHT_RED,
+        Level::Warn => Color32::YELLOW,
+        Level::Info => Color32::LIGHT_GRAY,
+        Level::Debug | Level::Trace => Color32::GRAY,
+    }
+}
+
+fn format_record(record: &LogRecord) -> String {
+    format!(
+        "[{:>8.3}s {:<5} {}] {}",
+        record.elapsed.as_secs_f32(),
+        record.level,
+        record.target,
+        record.message
+    )
+}
+
+/// Shows the log of this session, filtered per module.
+pub(crate) struct LogPanel {
+    levels: HashMap<LogModule, LevelFilter>,
+    search: String,
+    /// Records that pass the filters, and the state they were filtered with.
+    filtered: Vec<LogRecord>,
+    filtered_key: Option<(u64, Vec<LevelFilter>, String)>,
+    watching: bool,
+}
+
+impl LogPanel {
+    pub(crate) fn new() -> Self {
+        let levels = LogModule::ALL
+            .into_iter()
+            .map(|m| {
+                let level = if m == LogModule::Other {
+                    LevelFilter::Warn
+                } else {
+                    LevelFilter::Info
+                };
+                (m, level)
+            })
+            .collect();
+
+        Self {
+            levels,
+            search: String::new(),
+            filtered: vec![],
+            filtered_key: None,
+            watching: false,
+        }
+    }
+
+    fn update_filtered(&mut self) {
+        let key = (
+            log_capture::total_records(),
+            LogModule::ALL.map(|m| self.levels[&m]).to_vec(),
+            self.search.clone(),
+        );
+        if self.filtered_key.as_ref() == Some(&key) {
+            return;
+        }
+
+        let search = self.search.to_lowercase();
+        self.filtered = log_capture::records(|r| {
+            r.level <= self.levels[&r.module]
+                && (search.is_empty()
+                    || r.message.to_lowercase().contains(&search)
+                    || r.target.contains(&search))
+        });
+        self.filtered_key = Some(key);
+    }
+
+    fn export(&self) -> String {
+        self.filtered
+            .iter()
+            .map(format_record)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl AppPanel for LogPanel {
+    fn title(&self) -> String {
+        tr("panel.log").to_owned()
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, _: &mut AppContext) {
+        if !self.watching {
+            self.watching = true;
+            log_capture::repaint_on_new_records(ui.ctx());
+        }
+
+        egui::CollapsingHeader::new(tr("log.filters")).show(ui, |ui| {
+            egui::Grid::new("log_levels")
+                .num_columns(2)
+                .spacing([20.0, 4.0])
+                .show(ui, |ui| {
+                    for module in LogModule::ALL {
+                        ui.label(module_label(module));
+                        let level = self.levels.entry(module).or_insert(LevelFilter::Info);
+                        egui::ComboBox::from_id_salt(("log_level", module))
+                            .selected_text(level.as_str())
+                            .show_ui(ui, |ui| {
+                                for l in LEVELS {
+                                    ui.selectable_value(level, l, l.as_str());
+                                }
+                            });
+                        ui.end_row();
+                    }
+                });
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(tr("log.search"));
+            ui.text_edit_singleline(&mut self.search);
+        });
+
+        self.update_filtered();
+
+        ui.horizontal(|ui| {
+            if ui.button(tr("log.copy")).clicked() {
+                ui.ctx().copy_text(self.export());
+            }
+
+            if ui.button(tr("log.save")).clicked() {
+                let text = self.export();
+                tokio_with_wasm::alias::task::spawn(async move {
+                    match rrfd::save_file("brush_log.txt").await {
+                        Ok(file) => {
+                            if let Err(e) = file.write(text.as_bytes()).await {
+                                log::error!("Failed to write log: {e}");
+                            }
+                        }
+                        Err(e) => log::error!("Failed to save log: {e}"),
+                    }
+                });
+            }
+
+            ui.label(format!(
+                "{} / {}",
+                self.filtered.len(),
+                log_capture::total_records()
+            ));
+        });
+
+        ui.separator();
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        egui::ScrollArea::both()
+            .auto_shrink(false)
+            .stick_to_bottom(true)
+            .show_rows(ui, row_height, self.filtered.len(), |ui, rows| {
+                for record in &self.filtered[rows] {
+                    ui.label(
+                        RichText::new(format_record(record))
+                            .monospace()
+                            .color(level_color(record.level)),
+                    );
+                }
+            });
+    }
+}
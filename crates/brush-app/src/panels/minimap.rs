@@ -0,0 +1,184 @@
+use std::sync::Arc;
+
+use brush_render::gaussian_splats::Splats;
+use brush_train::train::TrainBack;
+use brush_ui::burn_texture::BurnTexture;
+use burn::tensor::{Tensor, backend::AutodiffBackend};
+use eframe::egui_wgpu::Renderer;
+use egui::{Area, Color32, Rect, Stroke, epaint::mutex::RwLock as EguiRwLock};
+use glam::{Quat, UVec2, Vec3};
+use tokio::sync::oneshot::{Receiver, error::TryRecvError};
+
+use super::ortho_view::{OrthoAxis, ortho_camera};
+use crate::app::AppContext;
+
+const MAP_SIZE: f32 = 160.0;
+
+// How many standard deviations of the splat positions the map shows around their mean. This
+// covers the bulk of a scene, without zooming out for a few far away floaters.
+const MAP_SPREAD: f32 = 2.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct MapState {
+    size: UVec2,
+    center: Vec3,
+    half_size: f32,
+    model_rotation: Quat,
+    splats_generation: u64,
+}
+
+/// Small top down overview of the scene, with the main camera drawn on it.
+pub(crate) struct Minimap {
+    backbuffer: BurnTexture,
+    /// Mean and standard deviation of the splat positions.
+    spread: Option<(Vec3, Vec3)>,
+    spread_generation: Option<u64>,
+    spread_loading: Option<Receiver<(Vec3, Vec3)>>,
+    last_state: Option<MapState>,
+}
+
+impl Minimap {
+    pub(crate) fn new(
+        renderer: Arc<EguiRwLock<Renderer>>,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+    ) -> Self {
+        Self {
+            backbuffer: BurnTexture::new(renderer, device, queue),
+            spread: None,
+            spread_generation: None,
+            spread_loading: None,
+            last_state: None,
+        }
+    }
+
+    fn update_spread(
+        &mut self,
+        ui: &egui::Ui,
+        splats: &Splats<<TrainBack as AutodiffBackend>::InnerBackend>,
+        splats_generation: u64,
+    ) {
+        if self.spread_generation != Some(splats_generation)
+            && self.spread_loading.is_none()
+            && splats.num_splats() > 0
+        {
+            self.spread_generation = Some(splats_generation);
+
+            let (sender, receiver) = tokio::sync::oneshot::channel();
+            let ctx = ui.ctx().clone();
+            let means = splats.means.val();
+
+            tokio_with_wasm::alias::spawn(async move {
+                let stats = Tensor::cat(vec![means.clone().mean_dim(0), means.var(0)], 0);
+                let Ok(stats) = stats.into_data_async().await.to_vec::<f32>() else {
+                    return;
+                };
+                let mean = Vec3::from_slice(&stats[0..3]);
+                let std = Vec3::from_slice(&stats[3..6]).max(Vec3::ZERO).powf(0.5);
+                let _ = sender.send((mean, std));
+                ctx.request_repaint();
+            });
+
+            self.spread_loading = Some(receiver);
+        }
+
+        if let Some(receiver) = self.spread_loading.as_mut() {
+            match receiver.try_recv() {
+                Ok(spread) => {
+                    self.spread = Some(spread);
+                    self.spread_loading = None;
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Closed) => self.spread_loading = None,
+            }
+        }
+    }
+
+    /// Draw the map in the bottom left corner of the given rect.
+    pub(crate) fn draw(
+        &mut self,
+        ui: &mut egui::Ui,
+        rect: Rect,
+        context: &AppContext,
+        splats: Option<&Splats<<TrainBack as AutodiffBackend>::InnerBackend>>,
+        splats_generation: u64,
+    ) {
+        let Some(splats) = splats else {
+            return;
+        };
+        self.update_spread(ui, splats, splats_generation);
+        let Some((mean, std)) = self.spread else {
+            return;
+        };
+
+        // The map is in the camera controls space, the splats might be transformed from it.
+        let model_local_to_world = context.model_local_to_world;
+        let center = model_local_to_world.inverse().transform_point3(mean);
+        let scale = model_local_to_world.matrix3.determinant().abs().cbrt();
+        let half_size = (MAP_SPREAD * std.max_element() / scale.max(1e-6)).max(1e-3);
+
+        let pixels_per_point = context.power_settings().pixels_per_point(ui.ctx());
+        let size = brush_ui::render_size(egui::vec2(MAP_SIZE, MAP_SIZE), pixels_per_point);
+        let state = MapState {
+            size,
+            center,
+            half_size,
+            model_rotation: Quat::from_affine3(&model_local_to_world),
+            splats_generation,
+        };
+
+        if self.last_state != Some(state) && size.x > 8 && size.y > 8 {
+            self.last_state = Some(state);
+            let camera = ortho_camera(OrthoAxis::Top, center, half_size, 1.0, model_local_to_world);
+            let (img, _) = splats.render(&camera, size, false);
+            self.backbuffer.update_texture(img);
+        }
+
+        // Position on the map of a point in the camera controls space.
+        let rotation = OrthoAxis::Top.rotation().inverse();
+        let to_map = |rect: Rect, point: Vec3| {
+            let local = rotation * (point - center) / half_size;
+            rect.center() + egui::vec2(local.x, local.y) * MAP_SIZE * 0.5
+        };
+
+        let margin = 8.0;
+        let pos = egui::pos2(rect.min.x + margin, rect.max.y - MAP_SIZE - margin);
+
+        Area::new(ui.auto_id_with("minimap"))
+            .order(egui::Order::Foreground)
+            .fixed_pos(pos)
+            .interactable(false)
+            .show(ui.ctx(), |ui| {
+                let (map_rect, _) =
+                    ui.allocate_exact_size(egui::vec2(MAP_SIZE, MAP_SIZE), egui::Sense::hover());
+                let painter = ui.painter_at(map_rect);
+                painter.rect_filled(map_rect, 0.0, Color32::BLACK);
+                if let Some(id) = self.backbuffer.id() {
+                    let uv = Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                    painter.image(id, map_rect, uv, Color32::WHITE);
+                }
+
+                // The camera, with lines along the sides of its view up to the focus point.
+                let controls = &context.controls;
+                let half_fov = (context.camera.fov_x as f32 * 0.5).tan();
+                let side = |dir: f32| {
+                    let ray = controls.rotation * Vec3::new(dir * half_fov, 0.0, 1.0);
+                    to_map(map_rect, controls.position + ray * controls.focus_distance)
+                };
+                let eye = to_map(map_rect, controls.position);
+                let (left, right) = (side(-1.0), side(1.0));
+                let stroke = Stroke::new(1.5, Color32::YELLOW);
+                painter.line_segment([eye, left], stroke);
+                painter.line_segment([eye, right], stroke);
+                painter.line_segment([left, right], Stroke::new(1.0, Color32::YELLOW));
+                painter.circle_filled(eye, 3.5, Color32::YELLOW);
+
+                painter.rect_stroke(
+                    map_rect,
+                    0.0,
+                    (1.0, Color32::GRAY),
+                    egui::StrokeKind::Inside,
+                );
+            });
+    }
+}
@@ -1,13 +1,26 @@
 mod datasets;
+mod local_datasets;
+mod log_view;
 mod settings;
 
+mod chunk_stream;
+mod depth_probe;
+mod frame;
+mod minimap;
+mod ortho_view;
+mod pick;
 mod presets;
+mod queue;
 mod scene;
 mod stats;
 mod tracing_debug;
+mod training_view;
 
 pub(crate) use datasets::*;
+pub(crate) use local_datasets::*;
+pub(crate) use log_view::*;
 pub(crate) use presets::*;
+pub(crate) use queue::*;
 pub(crate) use scene::*;
 pub(crate) use settings::*;
 pub(crate) use stats::*;
@@ -0,0 +1,188 @@
+use std::sync::Arc;
+
+use brush_render::{camera::Camera, gaussian_splats::Splats};
+use brush_train::train::TrainBack;
+use brush_ui::burn_texture::BurnTexture;
+use burn::tensor::backend::AutodiffBackend;
+use eframe::egui_wgpu::Renderer;
+use egui::{Color32, Rect, epaint::mutex::RwLock as EguiRwLock};
+use glam::{Affine3A, Quat, UVec2, Vec3};
+
+use crate::{app::AppContext, i18n::tr};
+
+// Orthographic views are approximated with a very narrow field of view camera placed far away.
+const ORTHO_FOV: f64 = 0.02;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum OrthoAxis {
+    Top,
+    Front,
+    Side,
+}
+
+impl OrthoAxis {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Top => tr("ortho.top"),
+            Self::Front => tr("ortho.front"),
+            Self::Side => tr("ortho.side"),
+        }
+    }
+
+    // Rotation in the camera controls space, where -Y is up and cameras look along +Z.
+    pub(crate) fn rotation(self) -> Quat {
+        match self {
+            Self::Top => Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2),
+            Self::Front => Quat::IDENTITY,
+            Self::Side => Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+        }
+    }
+}
+
+/// Camera looking at `center` along `axis`, seeing `half_height` above and below it.
+/// `center` is in the camera controls space.
+pub(crate) fn ortho_camera(
+    axis: OrthoAxis,
+    center: Vec3,
+    half_height: f32,
+    aspect: f64,
+    model_local_to_world: Affine3A,
+) -> Camera {
+    let rotation = axis.rotation();
+    let distance = half_height / (ORTHO_FOV as f32 * 0.5).tan();
+    let position = center - rotation * Vec3::Z * distance;
+    let transform = model_local_to_world * Affine3A::from_rotation_translation(rotation, position);
+    Camera::new(
+        transform.translation.into(),
+        Quat::from_mat3a(&transform.matrix3),
+        ORTHO_FOV * aspect,
+        ORTHO_FOV,
+        glam::vec2(0.5, 0.5),
+    )
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct OrthoState {
+    size: UVec2,
+    center: Vec3,
+    half_height: f32,
+    model_rotation: Quat,
+    splats_generation: u64,
+}
+
+/// An extra viewport looking at the scene along one of the axes.
+pub(crate) struct OrthoView {
+    axis: OrthoAxis,
+    /// Whether to follow the focus point and zoom of the main camera.
+    synced: bool,
+    center: Vec3,
+    half_height: f32,
+    backbuffer: BurnTexture,
+    last_state: Option<OrthoState>,
+}
+
+impl OrthoView {
+    pub(crate) fn new(
+        axis: OrthoAxis,
+        renderer: Arc<EguiRwLock<Renderer>>,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+    ) -> Self {
+        Self {
+            axis,
+            synced: true,
+            center: Vec3::ZERO,
+            half_height: 1.0,
+            backbuffer: BurnTexture::new(renderer, device, queue),
+            last_state: None,
+        }
+    }
+
+    pub(crate) fn draw(
+        &mut self,
+        ui: &mut egui::Ui,
+        size: egui::Vec2,
+        context: &AppContext,
+        splats: Option<&Splats<<TrainBack as AutodiffBackend>::InnerBackend>>,
+        splats_generation: u64,
+    ) {
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::drag());
+
+        if self.synced {
+            let controls = &context.controls;
+            self.center = controls.position + controls.rotation * Vec3::Z * controls.focus_distance;
+            self.half_height = controls.focus_distance * 0.5;
+        }
+
+        // Dragging or scrolling in this view makes it independent of the main camera.
+        let rotation = self.axis.rotation();
+        if response.dragged() {
+            let delta = response.drag_delta();
+            let world_per_pixel = 2.0 * self.half_height / rect.height().max(1.0);
+            self.center -= rotation * Vec3::new(delta.x, delta.y, 0.0) * world_per_pixel;
+            self.synced = false;
+        }
+        if response.hovered() {
+            let scrolled = ui.input(|r| r.smooth_scroll_delta.y);
+            if scrolled != 0.0 {
+                self.half_height *= 1.0 - scrolled * 0.001;
+                self.half_height = self.half_height.max(0.001);
+                self.synced = false;
+            }
+        }
+
+        let pixels_per_point = context.power_settings().pixels_per_point(ui.ctx());
+        let size = brush_ui::render_size(rect.size(), pixels_per_point);
+        let state = OrthoState {
+            size,
+            center: self.center,
+            half_height: self.half_height,
+            model_rotation: Quat::from_affine3(&context.model_local_to_world),
+            splats_generation,
+        };
+
+        if let Some(splats) = splats {
+            if self.last_state != Some(state) && size.x > 8 && size.y > 8 {
+                self.last_state = Some(state);
+
+                let aspect = size.x as f64 / size.y as f64;
+                let camera = ortho_camera(
+                    self.axis,
+                    self.center,
+                    self.half_height,
+                    aspect,
+                    context.model_local_to_world,
+                );
+                let (img, _) = splats.render(&camera, size, false);
+                self.backbuffer.update_texture(img);
+            }
+        }
+
+        ui.painter().rect_filled(rect, 0.0, Color32::BLACK);
+        if let Some(id) = self.backbuffer.id() {
+            ui.painter().image(
+                id,
+                rect,
+                Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                Color32::WHITE,
+            );
+        }
+        ui.painter().rect_stroke(
+            rect,
+            0.0,
+            (1.0, Color32::DARK_GRAY),
+            egui::StrokeKind::Inside,
+        );
+
+        // Overlay with the view name, and a toggle to sync with the main camera again.
+        let overlay = Rect::from_min_size(rect.min + egui::vec2(6.0, 4.0), egui::vec2(150.0, 20.0));
+        ui.put(overlay, |ui: &mut egui::Ui| {
+            ui.horizontal(|ui| {
+                ui.label(self.axis.label());
+                ui.selectable_value(&mut self.synced, true, tr("ortho.synced"))
+                    .on_hover_text(tr("ortho.synced_hover"));
+            })
+            .response
+        });
+    }
+}
@@ -0,0 +1,74 @@
+use brush_render::{camera::Camera, gaussian_splats::Splats};
+use brush_train::train::TrainBack;
+use burn::tensor::backend::AutodiffBackend;
+use glam::{Affine3A, UVec2, Vec3, uvec2, vec3};
+use tokio::sync::oneshot::{Receiver, error::TryRecvError};
+
+use super::depth_probe::nearest_surface;
+use crate::app::AppContext;
+
+type InnerBackend = <TrainBack as AutodiffBackend>::InnerBackend;
+
+/// Point of the splats under a pixel, in the space of the splats.
+async fn pick_point(
+    splats: &Splats<InnerBackend>,
+    camera: &Camera,
+    img_size: UVec2,
+    pixel: UVec2,
+) -> Option<Vec3> {
+    // Only render the one pixel.
+    let crop = camera.crop(img_size, pixel, uvec2(1, 1));
+    let (depth, _) = splats.render_depth(&crop, uvec2(1, 1));
+    let depth = depth.into_data_async().await.to_vec::<f32>().ok()?;
+    let depth = nearest_surface(&depth)?;
+
+    // The depth is along the view direction, not along the ray.
+    let ray = (pixel.as_vec2() + 0.5 - camera.center(img_size)) / camera.focal(img_size);
+    Some(camera.position + camera.rotation * vec3(ray.x, ray.y, 1.0) * depth)
+}
+
+/// Finds the point that was double clicked, to move the camera to.
+#[derive(Default)]
+pub(crate) struct Picker {
+    pending: Option<Receiver<Option<Vec3>>>,
+}
+
+impl Picker {
+    /// Look for the point under `pixel` of the current view, of an image of `img_size`.
+    pub(crate) fn pick(
+        &mut self,
+        ctx: &egui::Context,
+        context: &AppContext,
+        splats: &Splats<InnerBackend>,
+        img_size: UVec2,
+        pixel: UVec2,
+    ) {
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        let splats = splats.clone();
+        let camera = context.camera.clone();
+        let pixel = pixel.min(img_size - 1);
+        let ctx = ctx.clone();
+        tokio_with_wasm::alias::spawn(async move {
+            let point = pick_point(&splats, &camera, img_size, pixel).await;
+            let _ = sender.send(point);
+            ctx.request_repaint();
+        });
+        self.pending = Some(receiver);
+    }
+
+    /// The picked point in the camera controls space, once it's found.
+    pub(crate) fn picked(&mut self, model_local_to_world: Affine3A) -> Option<Vec3> {
+        let receiver = self.pending.as_mut()?;
+        match receiver.try_recv() {
+            Ok(point) => {
+                self.pending = None;
+                point.map(|p| model_local_to_world.inverse().transform_point3(p))
+            }
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Closed) => {
+                self.pending = None;
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{
+    app::{AppContext, AppPanel},
+    i18n::tr,
+    job_queue::JobStatus,
+};
+use egui::{Button, Color32};
+use web_time::Duration;
+
+pub(crate) struct QueuePanel;
+
+impl QueuePanel {
+    pub(crate) fn new() -> Self {
+        Self
+    }
+}
+
+fn status_text(status: &JobStatus) -> (String, Option<Color32>) {
+    match status {
+        JobStatus::Waiting => (tr("queue.waiting").to_owned(), None),
+        JobStatus::Running => (tr("queue.running").to_owned(), Some(Color32::LIGHT_BLUE)),
+        JobStatus::Done => (tr("queue.done").to_owned(), Some(Color32::LIGHT_GREEN)),
+        JobStatus::Failed(_) => (tr("queue.failed").to_owned(), Some(Color32::LIGHT_RED)),
+        JobStatus::Cancelled => (tr("queue.cancelled").to_owned(), None),
+    }
+}
+
+impl AppPanel for QueuePanel {
+    fn title(&self) -> String {
+        tr("panel.queue").to_owned()
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, context: &mut AppContext) {
+        let queue = &mut context.queue;
+        if queue.jobs().is_empty() {
+            ui.label(tr("queue.empty"));
+            return;
+        }
+
+        // The running job stopping doesn't always repaint, check back now and then.
+        if queue.active {
+            ui.ctx().request_repaint_after(Duration::from_secs(1));
+        }
+
+        ui.horizontal(|ui| {
+            let waiting = queue.jobs().iter().any(|j| j.status == JobStatus::Waiting);
+            if queue.active {
+                if ui.button(tr("queue.pause")).clicked() {
+                    queue.active = false;
+                }
+            } else if ui
+                .add_enabled(waiting, Button::new(tr("queue.start")))
+                .on_hover_text(tr("queue.start_hover"))
+                .clicked()
+            {
+                queue.active = true;
+            }
+            if ui.button(tr("queue.clear")).clicked() {
+                queue.clear_finished();
+            }
+        });
+
+        ui.add_space(10.0);
+
+        let mut reorder = None;
+        let mut cancel = None;
+        egui::Grid::new("job_queue")
+            .num_columns(5)
+            .striped(true)
+            .show(ui, |ui| {
+                for job in queue.jobs() {
+                    ui.label(job.name());
+                    let (text, color) = status_text(&job.status);
+                    let status = match color {
+                        Some(color) => ui.colored_label(color, text),
+                        None => ui.label(text),
+                    };
+                    if let JobStatus::Failed(error) = &job.status {
+                        status.on_hover_text(error);
+                    }
+                    if ui.small_button("⬆").clicked() {
+                        reorder = Some((job.id, true));
+                    }
+                    if ui.small_button("⬇").clicked() {
+                        reorder = Some((job.id, false));
+                    }
+                    let finished = matches!(
+                        job.status,
+                        JobStatus::Done | JobStatus::Failed(_) | JobStatus::Cancelled
+                    );
+                    if ui
+                        .add_enabled(!finished, Button::new(tr("queue.cancel")).small())
+                        .clicked()
+                    {
+                        cancel = Some(job.id);
+                    }
+                    ui.end_row();
+                }
+            });
+
+        if let Some((id, up)) = reorder {
+            context.queue.reorder(id, up);
+        }
+        if let Some(id) = cancel {
+            context.cancel_job(id);
+        }
+    }
+}
@@ -1,7 +1,8 @@
-use brush_dataset::splat_export;
-use brush_process::process_loop::ProcessMessage;
+use brush_dataset::{pano::render_equirect, splat_export};
+use brush_process::error::ProcessError;
+use brush_process::process_loop::{ChunkFocus, ProcessMessage};
 
-use brush_train::train::TrainBack;
+use brush_train::{appearance::Appearance, env_light::EnvLight, train::TrainBack};
 use brush_ui::burn_texture::BurnTexture;
 use burn::tensor::backend::AutodiffBackend;
 use core::f32;
@@ -11,46 +12,165 @@ use std::sync::Arc;
 use brush_render::{
     camera::{focal_to_fov, fov_to_focal},
     gaussian_splats::Splats,
+    post_process::PostProcess,
 };
 use eframe::egui_wgpu::Renderer;
 use egui::{Color32, Rect};
 use glam::{Quat, UVec2, Vec3};
 use tokio_with_wasm::alias as tokio_wasm;
 use tracing::trace_span;
-use web_time::Instant;
-
+use web_time::{Duration, Instant};
+
+use super::chunk_stream::ChunkStreamer;
+use super::depth_probe::DepthProbe;
+use super::frame::Framer;
+use super::minimap::Minimap;
+use super::ortho_view::{OrthoAxis, OrthoView};
+use super::pick::Picker;
+use super::training_view::TrainingViewPip;
 use crate::{
+    annotations::Annotation,
     app::{AppContext, AppPanel},
+    clip_planes::ClipPlanes,
+    delivery::{delivery_files, render_thumbnail, save_delivery},
+    i18n::{suggestion_key, tr},
+    keymap::Action,
     running_process::ControlMessage,
+    session::{Session, SessionView},
+    share::ShareMenu,
+    web_export::{SCENE_FILE, export_web_page},
 };
 
+/// Width of 360° images saved from the viewer.
+const EQUIRECT_WIDTH: u32 = 4096;
+
+/// What the main view shows of the splats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum ViewMode {
+    #[default]
+    Color,
+    Normals,
+    // Grey splats lit from the camera, to judge the geometry.
+    Shaded,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 struct RenderState {
     size: UVec2,
     cam_pos: Vec3,
     cam_rot: Quat,
+    post_process: PostProcess,
+    view_mode: ViewMode,
+    dc_only: bool,
+    clip_planes: ClipPlanes,
 
     frame: f32,
 }
 
+struct LoadProgress {
+    loaded_bytes: u64,
+    total_bytes: Option<u64>,
+    eta: Option<Duration>,
+}
+
+impl LoadProgress {
+    fn ui(&self, ui: &mut egui::Ui) {
+        let mb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+        let Some(total) = self.total_bytes.filter(|&t| t > 0) else {
+            ui.label(format!("{:.1} MB", mb(self.loaded_bytes)));
+            return;
+        };
+        let mut text = format!("{:.1} / {:.1} MB", mb(self.loaded_bytes), mb(total));
+        if let Some(eta) = self.eta {
+            let time = format!("{}s", eta.as_secs() + 1);
+            text += &format!(", {}", tr("scene.load_eta").replace("{time}", &time));
+        }
+        ui.add(
+            egui::ProgressBar::new(self.loaded_bytes as f32 / total as f32)
+                .desired_width(300.0)
+                .text(text),
+        );
+    }
+}
+
+/// Splats of a scene that isn't shown right now, with all frames of animated ones.
+#[derive(Default)]
+struct LoadedScene {
+    splats: Vec<Splats<<TrainBack as AutodiffBackend>::InnerBackend>>,
+    frame_count: u32,
+    up_axis: Option<Vec3>,
+}
+
 struct ErrorDisplay {
     headline: String,
     context: Vec<String>,
+    suggestion: Option<&'static str>,
 }
 
 pub(crate) struct ScenePanel {
     pub(crate) backbuffer: BurnTexture,
     pub(crate) last_draw: Option<Instant>,
+    last_render: Option<Instant>,
 
     view_splats: Vec<Splats<<TrainBack as AutodiffBackend>::InnerBackend>>,
     frame_count: u32,
+    /// A new scene that's loading in the background, while the current one is still shown.
+    pending: Option<LoadedScene>,
+    /// The scene shown before the current process started, to go back to.
+    previous: Option<LoadedScene>,
     frame: f32,
+    // Bumped whenever new splats come in.
+    splats_generation: u64,
+    // A merged scene saved as chunks, streamed in around the camera.
+    chunks: Option<ChunkStreamer>,
+    // Tells training which chunks are being looked at, when training in chunks.
+    chunk_focus: Option<ChunkFocus>,
+
+    // Extra axis aligned views shown in quad view.
+    quad_view: bool,
+    ortho_views: Vec<OrthoView>,
+    view_mode: ViewMode,
+    // Show and export only the base colors, with the view dependent colors baked in.
+    dc_only: bool,
+    // Names and color transforms of the capture sessions, when training learns them.
+    appearances: Option<(
+        Vec<String>,
+        Appearance<<TrainBack as AutodiffBackend>::InnerBackend>,
+    )>,
+    // The session whose look is shown and exported, or the splats' own colors if none.
+    appearance: Option<usize>,
+    // The shown splats as lit in that session, with the generation, frame and session they
+    // were baked for, so they're only baked again when one of those changes.
+    baked: Option<(
+        (u64, usize, usize),
+        Splats<<TrainBack as AutodiffBackend>::InnerBackend>,
+    )>,
+    // The light the splats are trained in, when training decomposes the lighting.
+    env_light: Option<EnvLight<<TrainBack as AutodiffBackend>::InnerBackend>>,
+    clip_planes: ClipPlanes,
+
+    show_training_view: bool,
+    training_view: TrainingViewPip,
+    show_minimap: bool,
+    minimap: Minimap,
+    depth_probe: DepthProbe,
+    picker: Picker,
+    framer: Framer,
 
     // Ui state.
     live_update: bool,
     paused: bool,
     err: Option<ErrorDisplay>,
+    load_progress: Option<LoadProgress>,
+    // Splats loaded and skipped, when the ply file was cut short.
+    truncated: Option<(u32, u32)>,
     zen: bool,
+    // Hide all controls, only show the splats.
+    kiosk: bool,
+    // Index of the note being edited.
+    editing_note: Option<usize>,
+    share_menu: ShareMenu,
+    session: Session,
 
     // Keep track of what was last rendered.
     last_state: Option<RenderState>,
@@ -62,18 +182,55 @@ impl ScenePanel {
         queue: wgpu::Queue,
         renderer: Arc<EguiRwLock<Renderer>>,
         zen: bool,
+        kiosk: bool,
     ) -> Self {
+        let ortho_views = [OrthoAxis::Top, OrthoAxis::Front, OrthoAxis::Side]
+            .into_iter()
+            .map(|axis| OrthoView::new(axis, renderer.clone(), device.clone(), queue.clone()))
+            .collect();
+
+        let training_view = TrainingViewPip::new(renderer.clone(), device.clone(), queue.clone());
+        let minimap = Minimap::new(renderer.clone(), device.clone(), queue.clone());
+
         Self {
             backbuffer: BurnTexture::new(renderer, device, queue),
             last_draw: None,
+            last_render: None,
             err: None,
+            load_progress: None,
+            truncated: None,
             view_splats: vec![],
             live_update: true,
             paused: false,
             last_state: None,
             zen,
+            kiosk,
+            editing_note: None,
+            share_menu: ShareMenu::default(),
+            session: Session::default(),
             frame_count: 0,
+            pending: None,
+            previous: None,
             frame: 0.0,
+            splats_generation: 0,
+            chunks: None,
+            chunk_focus: None,
+            quad_view: false,
+            ortho_views,
+            view_mode: ViewMode::default(),
+            clip_planes: ClipPlanes::default(),
+            dc_only: false,
+            appearances: None,
+            appearance: None,
+            baked: None,
+            env_light: None,
+            show_training_view: true,
+            training_view,
+            show_minimap: false,
+            minimap,
+            depth_probe: DepthProbe::default(),
+            picker: Picker::default(),
+            framer: Framer::default(),
         }
     }
 
@@ -82,11 +239,15 @@ impl ScenePanel {
         ui: &mut egui::Ui,
         context: &mut AppContext,
         splats: Option<Splats<<TrainBack as AutodiffBackend>::InnerBackend>>,
+        size: egui::Vec2,
     ) -> egui::Rect {
-        let size = brush_ui::size_for_splat_view(ui);
-
         let mut size = size.floor();
 
+        if let Some((fov_x, fov_y)) = context.controls.transition_fov() {
+            context.camera.fov_x = fov_x;
+            context.camera.fov_y = fov_y;
+        }
+
         if let Some(aspect_ratio) = context.view_aspect {
             if size.x / size.y > aspect_ratio {
                 size.x = size.y * aspect_ratio;
@@ -101,10 +262,43 @@ impl ScenePanel {
 
         let (rect, response) = ui.allocate_exact_size(
             egui::Vec2::new(size.x as f32, size.y as f32),
-            egui::Sense::drag(),
+            egui::Sense::click_and_drag(),
         );
+        let pixels_per_point = context.power_settings().pixels_per_point(ui.ctx());
+        let size = brush_ui::render_size(rect.size(), pixels_per_point);
 
-        context.controls.tick(&response, ui);
+        context.controls.tick(&response, ui, &context.keymap);
+        if let Some(point) = self.picker.picked(context.model_local_to_world) {
+            context.controls.go_to(point);
+        }
+        if let Some((center, radius)) = self.framer.framed(context.model_local_to_world) {
+            context.controls.frame(center, radius, context.camera.fov_y);
+        }
+        self.depth_probe.update(ui.ctx(), context, splats.as_ref());
+
+        let view = SessionView {
+            position: context.controls.position,
+            rotation: context.controls.rotation,
+            focus_distance: context.controls.focus_distance,
+            frame: self.frame,
+        };
+        if let Some(view) = self.session.sync(ui.ctx(), view) {
+            context.controls.position = view.position;
+            context.controls.rotation = view.rotation;
+            context.controls.focus_distance = view.focus_distance;
+            context.controls.stop_movement();
+            self.frame = view.frame;
+        }
+
+        // Double click to go to the point under the cursor, as seen in the last render.
+        if let (Some(pos), Some(splats)) = (response.interact_pointer_pos(), &splats) {
+            if response.double_clicked() && size.x > 8 && size.y > 8 {
+                let uv = (pos - rect.min) / rect.size();
+                let pixel = glam::vec2(uv.x, uv.y) * size.as_vec2();
+                self.picker
+                    .pick(ui.ctx(), context, splats, size, pixel.as_uvec2());
+            }
+        }
 
         let camera = &mut context.camera;
 
@@ -113,29 +307,79 @@ impl ScenePanel {
 
         camera.position = total_transform.translation.into();
         camera.rotation = Quat::from_mat3a(&total_transform.matrix3);
+        camera.clip_planes = self.clip_planes.local_planes(context.model_local_to_world);
 
         let state = RenderState {
             size,
             cam_pos: camera.position,
             cam_rot: camera.rotation,
+            post_process: context.post_process(),
+            view_mode: self.view_mode,
+            dc_only: self.dc_only,
+            clip_planes: self.clip_planes,
             frame: self.frame,
         };
 
-        let dirty = self.last_state != Some(state);
+        let mut dirty = self.last_state != Some(state);
+        let frame_interval = context.power_settings().frame_interval();
+
+        if dirty {
+            if let (Some(interval), Some(last)) = (frame_interval, self.last_render) {
+                let elapsed = last.elapsed();
+                if elapsed < interval {
+                    // Too soon to render again, try again once the interval has passed.
+                    ui.ctx()
+                        .request_repaint_after(interval.saturating_sub(elapsed));
+                    dirty = false;
+                }
+            }
+        }
 
         if dirty {
             self.last_state = Some(state);
+            self.last_render = Some(Instant::now());
 
             // Check again next frame, as there might be more to animate.
-            ui.ctx().request_repaint();
+            match frame_interval {
+                Some(interval) => ui.ctx().request_repaint_after(interval),
+                None => ui.ctx().request_repaint(),
+            }
         }
 
         if let Some(splats) = splats {
             // If this viewport is re-rendering.
             if size.x > 8 && size.y > 8 && dirty {
                 let _span = trace_span!("Render splats").entered();
-                let (img, _) = splats.render(&context.camera, size, false);
-                self.backbuffer.update_texture(img);
+                // Post-processing needs the float image.
+                let post_process = context.post_process();
+                let camera = &context.camera;
+                let float_buffer = post_process.is_enabled();
+                // The splats only hold the albedo, light them as seen from this view.
+                let splats = match &self.env_light {
+                    Some(light) => light.relight(splats, camera),
+                    None => splats,
+                };
+                let splats = if self.dc_only {
+                    splats.bake_to_dc(&bake_positions(context))
+                } else {
+                    splats
+                };
+                let (img, _) = match self.view_mode {
+                    ViewMode::Color => splats.render(camera, size, float_buffer),
+                    ViewMode::Normals => splats.render_normals(camera, size, float_buffer),
+                    ViewMode::Shaded => {
+                        // Light from over the left shoulder of the camera.
+                        let light_dir = camera.rotation * Vec3::new(-0.4, -0.7, -1.0);
+                        splats.render_shaded(camera, size, light_dir, float_buffer)
+                    }
+                };
+                let img = if post_process.ambient_occlusion > 0.0 {
+                    let (depth, _) = splats.render_depth(camera, size);
+                    post_process.occlude(img, depth)
+                } else {
+                    img
+                };
+                self.backbuffer.update_texture(post_process.apply(img));
             }
         }
 
@@ -168,25 +412,367 @@ impl ScenePanel {
             }
         });
 
+        self.draw_annotations(ui, context, rect, size);
+
         rect
     }
+
+    // Notes are drawn on top of the view, at their anchors as seen through the camera.
+    fn draw_annotations(
+        &mut self,
+        ui: &egui::Ui,
+        context: &mut AppContext,
+        rect: Rect,
+        size: UVec2,
+    ) {
+        let mut notes = context.annotations().to_vec();
+        let mut changed = false;
+        let mut delete = None;
+
+        for (i, note) in notes.iter_mut().enumerate() {
+            let Some(pixel) = note.project(&context.camera, size) else {
+                continue;
+            };
+            let scale = rect.size() / egui::vec2(size.x as f32, size.y as f32);
+            let pos = rect.min + egui::vec2(pixel.x, pixel.y) * scale;
+            if !rect.contains(pos) {
+                continue;
+            }
+
+            ui.painter()
+                .circle_filled(pos, 4.0, Color32::from_rgb(255, 200, 60));
+
+            Area::new(ui.id().with(("note", i)))
+                .fixed_pos(pos + egui::vec2(6.0, -6.0))
+                .constrain_to(rect)
+                .show(ui.ctx(), |ui| {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        if self.editing_note == Some(i) {
+                            changed |= ui
+                                .add(
+                                    egui::TextEdit::multiline(&mut note.text)
+                                        .desired_rows(2)
+                                        .hint_text(tr("scene.note_hint")),
+                                )
+                                .changed();
+                            ui.horizontal(|ui| {
+                                if ui.button(tr("common.done")).clicked() {
+                                    self.editing_note = None;
+                                }
+                                if ui.button(tr("scene.delete_note")).clicked() {
+                                    delete = Some(i);
+                                }
+                            });
+                        } else {
+                            ui.set_max_width(250.0);
+                            let text = if note.text.is_empty() {
+                                "…"
+                            } else {
+                                note.text.as_str()
+                            };
+                            if ui
+                                .add(egui::Label::new(text).sense(egui::Sense::click()))
+                                .on_hover_text(tr("scene.edit_note_hover"))
+                                .clicked()
+                            {
+                                self.editing_note = Some(i);
+                            }
+                        }
+                    });
+                });
+        }
+
+        if let Some(i) = delete {
+            notes.remove(i);
+            self.editing_note = None;
+            changed = true;
+        }
+        if changed {
+            context.set_annotations(notes);
+        }
+    }
+
+    // Exports match the preview, so they look the same in viewers without view dependent colors.
+    fn export_colors(
+        &self,
+        context: &AppContext,
+        splats: Splats<<TrainBack as AutodiffBackend>::InnerBackend>,
+    ) -> Splats<<TrainBack as AutodiffBackend>::InnerBackend> {
+        if self.dc_only {
+            splats.bake_to_dc(&bake_positions(context))
+        } else {
+            splats
+        }
+    }
+
+    // Main view in the top left, with the axis aligned views around it.
+    fn draw_quad(
+        &mut self,
+        ui: &mut egui::Ui,
+        context: &mut AppContext,
+        splats: Option<Splats<<TrainBack as AutodiffBackend>::InnerBackend>>,
+    ) -> egui::Rect {
+        let cell = (brush_ui::size_for_splat_view(ui) / 2.0).floor();
+        let generation = self.splats_generation;
+
+        ui.scope(|ui| {
+            ui.spacing_mut().item_spacing = egui::Vec2::ZERO;
+
+            let main_rect = ui
+                .horizontal(|ui| {
+                    let rect = self.draw_splats(ui, context, splats.clone(), cell);
+                    self.ortho_views[0].draw(ui, cell, context, splats.as_ref(), generation);
+                    rect
+                })
+                .inner;
+
+            ui.horizontal(|ui| {
+                for view in &mut self.ortho_views[1..] {
+                    view.draw(ui, cell, context, splats.as_ref(), generation);
+                }
+            });
+
+            main_rect
+        })
+        .inner
+    }
+}
+
+/// Where view dependent colors are baked from. Without training views there's no average
+/// direction, and only the base colors are kept.
+fn bake_positions(context: &AppContext) -> Vec<Vec3> {
+    let views = &context.dataset.train.views;
+    views.iter().map(|v| v.camera.position).collect()
+}
+
+fn export_splats(splats: Splats<<TrainBack as AutodiffBackend>::InnerBackend>) {
+    let fut = async move {
+        let file = rrfd::save_file("export.ply").await;
+
+        // Not sure where/how to show this error if any.
+        match file {
+            Err(e) => {
+                log::error!("Failed to save file: {e}");
+            }
+            Ok(file) => {
+                // Raw parameters for use in eg. PyTorch, when saved as .safetensors.
+                let data = if file.file_name().ends_with(".safetensors") {
+                    splats.to_safetensors().await.map_err(anyhow::Error::from)
+                } else {
+                    splat_export::splat_to_ply(splats).await
+                };
+
+                let data = match data {
+                    Ok(data) => data,
+                    Err(e) => {
+                        log::error!("Failed to serialize file: {e}");
+                        return;
+                    }
+                };
+
+                if let Err(e) = file.write(&data).await {
+                    log::error!("Failed to write file: {e}");
+                }
+            }
+        }
+    };
+
+    tokio_wasm::task::spawn(fut);
+}
+
+fn export_web(splats: Splats<<TrainBack as AutodiffBackend>::InnerBackend>, query: String) {
+    let fut = async move {
+        let file = match rrfd::save_file("brush_web.zip").await {
+            Ok(file) => file,
+            Err(e) => {
+                log::error!("Failed to save file: {e}");
+                return;
+            }
+        };
+
+        let bundle = match splat_export::splat_to_ply(splats).await {
+            Ok(ply) => export_web_page(ply, &query).await,
+            Err(e) => Err(e),
+        };
+        let data = match bundle {
+            Ok(data) => data,
+            Err(e) => {
+                log::error!("Failed to export web page: {e}");
+                return;
+            }
+        };
+
+        if let Err(e) = file.write(&data).await {
+            log::error!("Failed to write file: {e}");
+        }
+    };
+
+    tokio_wasm::task::spawn(fut);
+}
+
+// The thumbnail is rendered from `view_splats` as they're shown, the files are of `splats`.
+fn export_delivery(
+    splats: Splats<<TrainBack as AutodiffBackend>::InnerBackend>,
+    view_splats: Splats<<TrainBack as AutodiffBackend>::InnerBackend>,
+    camera: brush_render::camera::Camera,
+    post_process: PostProcess,
+) {
+    let fut = async move {
+        let files = match render_thumbnail(&view_splats, &camera, &post_process).await {
+            Ok(thumbnail) => delivery_files(splats, thumbnail).await,
+            Err(e) => Err(e),
+        };
+        let files = match files {
+            Ok(files) => files,
+            Err(e) => {
+                log::error!("Failed to export delivery: {e}");
+                return;
+            }
+        };
+
+        if let Err(e) = save_delivery(files).await {
+            log::error!("Failed to save delivery: {e}");
+        }
+    };
+
+    tokio_wasm::task::spawn(fut);
+}
+
+fn export_equirect(
+    splats: Splats<<TrainBack as AutodiffBackend>::InnerBackend>,
+    camera: brush_render::camera::Camera,
+    post_process: PostProcess,
+) {
+    let fut = async move {
+        let file = match rrfd::save_file("render_360.png").await {
+            Ok(file) => file,
+            Err(e) => {
+                log::error!("Failed to save file: {e}");
+                return;
+            }
+        };
+
+        let image = match render_equirect(&splats, &camera, EQUIRECT_WIDTH, &post_process).await {
+            Ok(image) => image,
+            Err(e) => {
+                log::error!("Failed to render 360° image: {e}");
+                return;
+            }
+        };
+
+        let mut data = vec![];
+        if let Err(e) = image.into_rgba8().write_to(
+            &mut std::io::Cursor::new(&mut data),
+            image::ImageFormat::Png,
+        ) {
+            log::error!("Failed to encode image: {e}");
+            return;
+        }
+
+        if let Err(e) = file.write(&data).await {
+            log::error!("Failed to write file: {e}");
+        }
+    };
+
+    tokio_wasm::task::spawn(fut);
+}
+
+impl ScenePanel {
+    /// Swap in the scene that was loading in the background.
+    fn show_pending(&mut self, context: &mut AppContext) {
+        let Some(pending) = self.pending.take() else {
+            return;
+        };
+        if let Some(up_axis) = pending.up_axis {
+            context.set_model_up(up_axis);
+        }
+        self.view_splats = pending.splats;
+        self.frame_count = pending.frame_count;
+        self.frame = 0.0;
+        self.last_state = None;
+        self.splats_generation += 1;
+    }
+
+    /// Stop the running process, and show the scene from before it again.
+    fn show_previous(&mut self, context: &mut AppContext) {
+        let Some(previous) = self.previous.take() else {
+            return;
+        };
+        if !context.restore_previous_view() {
+            return;
+        }
+        self.view_splats = previous.splats;
+        self.frame_count = previous.frame_count;
+        self.frame = 0.0;
+        self.pending = None;
+        self.err = None;
+        self.paused = false;
+        self.last_state = None;
+        self.splats_generation += 1;
+    }
 }
 
 impl AppPanel for ScenePanel {
     fn title(&self) -> String {
-        "Scene".to_owned()
+        tr("panel.scene").to_owned()
     }
 
     fn on_message(&mut self, message: &ProcessMessage, context: &mut AppContext) {
         match message {
             ProcessMessage::NewSource => {
-                self.view_splats = vec![];
-                self.frame_count = 0;
+                if !self.view_splats.is_empty() {
+                    self.previous = Some(LoadedScene {
+                        splats: self.view_splats.clone(),
+                        frame_count: self.frame_count,
+                        up_axis: None,
+                    });
+                }
+                if context.keep_scene_while_loading && !self.view_splats.is_empty() {
+                    self.pending = Some(LoadedScene::default());
+                } else {
+                    self.pending = None;
+                    self.view_splats = vec![];
+                    self.frame_count = 0;
+                }
                 self.live_update = true;
                 self.paused = false;
                 self.err = None;
+                self.load_progress = None;
+                self.truncated = None;
                 self.last_state = None;
                 self.frame = 0.0;
+                self.chunks = None;
+                self.appearances = None;
+                self.appearance = None;
+                self.env_light = None;
+                if let Some(focus) = self.chunk_focus.take() {
+                    focus.set(None);
+                }
+                self.training_view.reset();
+            }
+            ProcessMessage::Appearances { names, appearance } => {
+                if self.appearance.is_some_and(|i| i >= names.len()) {
+                    self.appearance = None;
+                }
+                self.appearances = Some((names.clone(), appearance.clone()));
+                self.baked = None;
+            }
+            ProcessMessage::EnvLight { light } => {
+                self.env_light = Some(light.clone());
+            }
+            ProcessMessage::TrainChunks { focus, .. } => {
+                self.chunk_focus = Some(focus.clone());
+            }
+            ProcessMessage::ViewChunks { vfs, dir, index } => {
+                self.pending = None;
+                self.view_splats = vec![];
+                self.frame_count = 0;
+                self.chunks = Some(ChunkStreamer::new(
+                    vfs.clone(),
+                    dir.clone(),
+                    index.clone(),
+                    context.device.clone(),
+                ));
             }
             ProcessMessage::ViewSplats {
                 up_axis,
@@ -194,6 +780,14 @@ impl AppPanel for ScenePanel {
                 frame,
                 total_frames,
             } => {
+                if let Some(pending) = &mut self.pending {
+                    pending.splats.truncate(*frame as usize);
+                    pending.splats.push(*splats.clone());
+                    pending.frame_count = *total_frames;
+                    pending.up_axis = up_axis.or(pending.up_axis);
+                    return;
+                }
+
                 if let Some(up_axis) = up_axis {
                     context.set_model_up(*up_axis);
                 }
@@ -204,26 +798,59 @@ impl AppPanel for ScenePanel {
                 }
                 self.frame_count = *total_frames;
                 self.last_state = None;
+                self.splats_generation += 1;
             }
-            ProcessMessage::TrainStep { splats, .. } => {
-                self.last_state = None;
-                let splats = *splats.clone();
-                if self.live_update {
-                    self.view_splats = vec![splats];
+            ProcessMessage::LoadProgress {
+                loaded_bytes,
+                total_bytes,
+                eta,
+            } => {
+                self.load_progress = Some(LoadProgress {
+                    loaded_bytes: *loaded_bytes,
+                    total_bytes: *total_bytes,
+                    eta: *eta,
+                });
+            }
+            ProcessMessage::SplatsTruncated { loaded, skipped } => {
+                self.truncated = Some((*loaded, *skipped));
+            }
+            ProcessMessage::DoneLoading { .. } => {
+                self.show_pending(context);
+            }
+            ProcessMessage::TrainStep {
+                splats,
+                camera,
+                view_index,
+                gt_image,
+                ..
+            } => {
+                self.show_pending(context);
+                self.training_view
+                    .set_step(camera.clone(), *view_index, gt_image.clone());
+                if let Some(splats) = splats.as_ref().and_then(|s| s.front()) {
+                    self.last_state = None;
+                    self.splats_generation += 1;
+                    if self.live_update {
+                        self.view_splats = vec![splats];
+                    }
                 }
             }
             _ => {}
         }
     }
 
-    fn on_error(&mut self, error: &anyhow::Error, _: &mut AppContext) {
+    fn on_error(&mut self, error: &ProcessError, _: &mut AppContext) {
+        self.pending = None;
         let headline = error.to_string();
-        let context = error
-            .chain()
-            .skip(1)
-            .map(|cause| format!("{cause}"))
-            .collect();
-        self.err = Some(ErrorDisplay { headline, context });
+        let context =
+            std::iter::successors(std::error::Error::source(error), |cause| cause.source())
+                .map(|cause| format!("{cause}"))
+                .collect();
+        self.err = Some(ErrorDisplay {
+            headline,
+            context,
+            suggestion: error.suggestion().map(|s| tr(suggestion_key(s))),
+        });
     }
 
     fn ui(&mut self, ui: &mut egui::Ui, context: &mut AppContext) {
@@ -232,26 +859,23 @@ impl AppPanel for ScenePanel {
         self.last_draw = Some(cur_time);
 
         // Empty scene, nothing to show.
-        if !context.training() && self.view_splats.is_empty() && self.err.is_none() && !self.zen {
-            ui.heading("Load a ply file or dataset to get started.");
+        if !context.training()
+            && !context.loading()
+            && self.view_splats.is_empty()
+            && self.chunks.is_none()
+            && self.err.is_none()
+            && !self.zen
+        {
+            ui.heading(tr("scene.empty_heading"));
             ui.add_space(5.0);
-            ui.label(
-                r#"
-Load a pretrained .ply file to view it
-
-Or load a dataset to train on. These are zip files with:
-    - a transforms.json and images, like the nerfstudio dataset format.
-    - COLMAP data, containing the `images` & `sparse` folder."#,
-            );
+            ui.label(tr("scene.empty_body"));
 
             ui.add_space(10.0);
 
             if cfg!(debug_assertions) {
                 ui.scope(|ui| {
                     ui.visuals_mut().override_text_color = Some(Color32::LIGHT_BLUE);
-                    ui.heading(
-                        "Note: running in debug mode, compile with --release for best performance",
-                    );
+                    ui.heading(tr("scene.debug_note"));
                 });
 
                 ui.add_space(10.0);
@@ -260,14 +884,9 @@ Or load a dataset to train on. These are zip files with:
             #[cfg(target_family = "wasm")]
             ui.scope(|ui| {
                 ui.visuals_mut().override_text_color = Some(Color32::YELLOW);
-                ui.heading("Note: Running in browser is still experimental");
+                ui.heading(tr("scene.wasm_note"));
 
-                ui.label(
-                    r#"
-In browser training is slower, and lower quality than the native app.
-
-For bigger training runs consider using the native app."#,
-                );
+                ui.label(tr("scene.wasm_body"));
             });
 
             return;
@@ -282,6 +901,11 @@ For bigger training runs consider using the native app."#,
                     ui.add_space(2.0);
                 }
             });
+
+            if let Some(suggestion) = &err.suggestion {
+                ui.add_space(10.0);
+                ui.colored_label(Color32::LIGHT_BLUE, format!("💡 {suggestion}"));
+            }
         } else {
             const FPS: f32 = 24.0;
 
@@ -296,8 +920,44 @@ For bigger training runs consider using the native app."#,
                 .rem_euclid(self.frame_count as f32)
                 .floor() as usize;
 
-            let splats = self.view_splats.get(frame).cloned();
-            let rect = self.draw_splats(ui, context, splats.clone());
+            let mut splats = self.view_splats.get(frame).cloned();
+            let position = context
+                .model_local_to_world
+                .transform_point3(context.camera.position);
+            if let Some(focus) = &self.chunk_focus {
+                focus.set(Some(position));
+            }
+            if let Some(chunks) = &mut self.chunks {
+                let (chunk_splats, changed) = chunks.update(ui.ctx(), position);
+                if changed {
+                    self.last_state = None;
+                    self.splats_generation += 1;
+                }
+                splats = chunk_splats;
+            }
+            if let (Some(session), Some((_, appearance))) = (self.appearance, &self.appearances) {
+                let key = (self.splats_generation, frame, session);
+                if !matches!(&self.baked, Some((baked_key, _)) if *baked_key == key) {
+                    self.baked = splats.map(|splats| (key, appearance.bake(splats, session)));
+                }
+                splats = self.baked.as_ref().map(|(_, baked)| baked.clone());
+            }
+            let rect = if self.quad_view {
+                self.draw_quad(ui, context, splats.clone())
+            } else {
+                let size = brush_ui::size_for_splat_view(ui);
+                self.draw_splats(ui, context, splats.clone(), size)
+            };
+
+            if context.training() && self.show_training_view {
+                self.training_view.draw(ui, rect, splats.as_ref());
+            }
+
+            if self.show_minimap {
+                let generation = self.splats_generation;
+                self.minimap
+                    .draw(ui, rect, context, splats.as_ref(), generation);
+            }
 
             if context.loading() {
                 let id = ui.auto_id_with("loading_bar");
@@ -309,18 +969,40 @@ For bigger training runs consider using the native app."#,
                             .fill(egui::Color32::from_rgba_premultiplied(20, 20, 20, 150))
                             .show(ui, |ui| {
                                 ui.horizontal(|ui| {
-                                    ui.label(egui::RichText::new("Loading...").heading());
+                                    ui.label(egui::RichText::new(tr("common.loading")).heading());
                                     ui.spinner();
                                 });
+                                if let Some(progress) = &self.load_progress {
+                                    progress.ui(ui);
+                                }
+                            });
+                    });
+            } else if let Some((loaded, skipped)) = self.truncated {
+                let id = ui.auto_id_with("truncated");
+                let text = tr("scene.truncated")
+                    .replace("{loaded}", &loaded.to_string())
+                    .replace("{skipped}", &skipped.to_string());
+                Area::new(id)
+                    .order(egui::Order::Foreground)
+                    .fixed_pos(rect.min)
+                    .show(ui.ctx(), |ui| {
+                        egui::Frame::new()
+                            .fill(egui::Color32::from_rgba_premultiplied(20, 20, 20, 150))
+                            .show(ui, |ui| {
+                                ui.label(egui::RichText::new(text).color(egui::Color32::YELLOW));
                             });
                     });
             }
 
+            if self.kiosk {
+                return;
+            }
+
             if self.view_splats.len() > 1 && self.view_splats.len() as u32 == self.frame_count {
                 let label = if self.paused {
-                    "⏸ paused"
+                    tr("scene.paused")
                 } else {
-                    "⏵ playing"
+                    tr("scene.playing")
                 };
 
                 if ui.selectable_label(!self.paused, label).clicked() {
@@ -328,17 +1010,49 @@ For bigger training runs consider using the native app."#,
                 }
             }
 
+            let keymap = context.keymap.clone();
+            let egui_ctx = ui.ctx().clone();
+            let pressed = |action| keymap.pressed(&egui_ctx, action);
+            let export_pressed = pressed(Action::Export);
+
+            if pressed(Action::ToggleQuadView) {
+                self.quad_view = !self.quad_view;
+                self.last_state = None;
+            }
+            if pressed(Action::ToggleTrainingView) {
+                self.show_training_view = !self.show_training_view;
+            }
+            if pressed(Action::ToggleLowPower) {
+                let mut power = context.power_settings();
+                power.low_power = !power.low_power;
+                context.set_power_settings(power);
+            }
+
+            let mut go_back = pressed(Action::PreviousModel);
+            let mut frame_model = pressed(Action::Frame);
+
             ui.horizontal(|ui| {
+                if self.previous.is_some()
+                    && ui
+                        .button(tr("scene.previous_model"))
+                        .on_hover_text(tr("scene.previous_model_hover"))
+                        .clicked()
+                {
+                    go_back = true;
+                }
+
                 if context.training() {
                     ui.add_space(15.0);
 
                     let label = if self.paused {
-                        "⏸ paused"
+                        tr("scene.paused")
                     } else {
-                        "⏵ training"
+                        tr("scene.training")
                     };
 
-                    if ui.selectable_label(!self.paused, label).clicked() {
+                    if ui.selectable_label(!self.paused, label).clicked()
+                        || pressed(Action::PauseTraining)
+                    {
                         self.paused = !self.paused;
                         context.control_message(ControlMessage::Paused(self.paused));
                     }
@@ -348,7 +1062,7 @@ For bigger training runs consider using the native app."#,
                     ui.scope(|ui| {
                         ui.style_mut().visuals.selection.bg_fill = Color32::DARK_RED;
                         if ui
-                            .selectable_label(self.live_update, "🔴 Live update splats")
+                            .selectable_label(self.live_update, tr("scene.live_update"))
                             .clicked()
                         {
                             self.live_update = !self.live_update;
@@ -357,54 +1071,302 @@ For bigger training runs consider using the native app."#,
 
                     ui.add_space(15.0);
 
-                    if let Some(splats) = splats {
-                        if ui.button("⬆ Export").clicked() {
-                            let fut = async move {
-                                let file = rrfd::save_file("export.ply").await;
-
-                                // Not sure where/how to show this error if any.
-                                match file {
-                                    Err(e) => {
-                                        log::error!("Failed to save file: {e}");
-                                    }
-                                    Ok(file) => {
-                                        let data = splat_export::splat_to_ply(splats).await;
-
-                                        let data = match data {
-                                            Ok(data) => data,
-                                            Err(e) => {
-                                                log::error!("Failed to serialize file: {e}");
-                                                return;
-                                            }
-                                        };
-
-                                        if let Err(e) = file.write(&data).await {
-                                            log::error!("Failed to write file: {e}");
-                                        }
-                                    }
-                                }
-                            };
+                    if ui
+                        .selectable_label(self.show_training_view, tr("scene.training_view"))
+                        .on_hover_text(tr("scene.training_view_hover"))
+                        .clicked()
+                    {
+                        self.show_training_view = !self.show_training_view;
+                    }
+
+                    ui.add_space(15.0);
+
+                    if let Some(splats) = splats.clone() {
+                        if ui.button(tr("scene.export")).clicked() || export_pressed {
+                            let splats = self.export_colors(context, splats);
+                            export_splats(context.splats_in_world(splats));
+                        }
+                    }
+                }
+
+                if ui
+                    .selectable_label(self.show_minimap, tr("scene.minimap"))
+                    .on_hover_text(tr("scene.minimap_hover"))
+                    .clicked()
+                {
+                    self.show_minimap = !self.show_minimap;
+                }
+
+                if ui
+                    .selectable_label(self.quad_view, tr("scene.quad_view"))
+                    .on_hover_text(tr("scene.quad_view_hover"))
+                    .clicked()
+                {
+                    self.quad_view = !self.quad_view;
+                    self.last_state = None;
+                }
+
+                if ui
+                    .selectable_label(
+                        context.camera.exact_projection,
+                        tr("scene.exact_projection"),
+                    )
+                    .on_hover_text(tr("scene.exact_projection_hover"))
+                    .clicked()
+                {
+                    context.camera.exact_projection = !context.camera.exact_projection;
+                    self.last_state = None;
+                }
+
+                let locked = context.controls.lock_horizon;
+                if ui
+                    .selectable_label(locked, tr("scene.lock_horizon"))
+                    .on_hover_text(tr("scene.lock_horizon_hover"))
+                    .clicked()
+                {
+                    context.controls.lock_horizon = !locked;
+                }
+
+                let walking = context.controls.walking();
+                if ui
+                    .selectable_label(walking, tr("scene.walk"))
+                    .on_hover_text(tr("scene.walk_hover"))
+                    .clicked()
+                {
+                    context.controls.set_walking(!walking);
+                }
+
+                if ui
+                    .selectable_label(self.dc_only, tr("scene.dc_only"))
+                    .on_hover_text(tr("scene.dc_only_hover"))
+                    .clicked()
+                {
+                    self.dc_only = !self.dc_only;
+                    self.last_state = None;
+                }
+
+                if let Some((names, _)) = &self.appearances {
+                    let mut selected = self.appearance;
+                    ui.menu_button(tr("scene.appearance"), |ui| {
+                        ui.radio_value(&mut selected, None, tr("scene.appearance_own"));
+                        for (i, name) in names.iter().enumerate() {
+                            ui.radio_value(&mut selected, Some(i), name);
+                        }
+                    })
+                    .response
+                    .on_hover_text(tr("scene.appearance_hover"));
+                    if selected != self.appearance {
+                        self.appearance = selected;
+                        self.last_state = None;
+                    }
+                }
+
+                ui.add_space(15.0);
+
+                if let Some(splats) = splats.clone() {
+                    if ui
+                        .button(tr("scene.render_360"))
+                        .on_hover_text(tr("scene.render_360_hover"))
+                        .clicked()
+                    {
+                        export_equirect(splats, context.camera.clone(), context.post_process());
+                    }
+                    ui.add_space(15.0);
+                }
+
+                if splats.is_some()
+                    && ui
+                        .button(tr("scene.frame"))
+                        .on_hover_text(tr("scene.frame_hover"))
+                        .clicked()
+                {
+                    frame_model = true;
+                }
+
+                if let Some(splats) = splats.clone() {
+                    if ui
+                        .button(tr("scene.add_note"))
+                        .on_hover_text(tr("scene.add_note_hover"))
+                        .clicked()
+                    {
+                        // Pin the note to the point the camera orbits around.
+                        let camera = &context.camera;
+                        let position = camera.position
+                            + camera.rotation * Vec3::Z * context.controls.focus_distance;
+                        let mut notes = context.annotations().to_vec();
+                        notes.push(Annotation {
+                            position,
+                            text: String::new(),
+                        });
+                        self.editing_note = Some(notes.len() - 1);
+                        context.set_annotations(notes);
+                    }
 
-                            tokio_wasm::task::spawn(fut);
+                    if let Some(link) = context.share_link() {
+                        if ui
+                            .button(tr("scene.copy_link"))
+                            .on_hover_text(tr("scene.copy_link_hover"))
+                            .clicked()
+                        {
+                            ui.ctx().copy_text(link);
                         }
                     }
+
+                    ui.menu_button(tr("scene.share"), |ui| {
+                        self.share_menu.ui(ui, context, splats.clone());
+                    });
+
+                    if ui
+                        .button(tr("scene.export_web"))
+                        .on_hover_text(tr("scene.export_web_hover"))
+                        .clicked()
+                    {
+                        let query = context.export_query(&format!("./{SCENE_FILE}"));
+                        let splats = self.export_colors(context, splats.clone());
+                        export_web(context.splats_in_world(splats), query);
+                    }
+
+                    if ui
+                        .button(tr("scene.export_delivery"))
+                        .on_hover_text(tr("scene.export_delivery_hover"))
+                        .clicked()
+                    {
+                        let splats = self.export_colors(context, splats);
+                        export_delivery(
+                            context.splats_in_world(splats.clone()),
+                            splats,
+                            context.camera.clone(),
+                            context.post_process(),
+                        );
+                    }
+                    ui.add_space(15.0);
+                }
+
+                ui.menu_button(tr("scene.session"), |ui| {
+                    self.session.menu_ui(ui);
+                })
+                .response
+                .on_hover_text(tr("scene.session_hover"));
+
+                ui.menu_button(tr("scene.view_mode"), |ui| {
+                    ui.radio_value(&mut self.view_mode, ViewMode::Color, tr("scene.view_color"));
+                    ui.radio_value(
+                        &mut self.view_mode,
+                        ViewMode::Normals,
+                        tr("scene.view_normals"),
+                    );
+                    ui.radio_value(
+                        &mut self.view_mode,
+                        ViewMode::Shaded,
+                        tr("scene.view_shaded"),
+                    )
+                    .on_hover_text(tr("scene.view_shaded_hover"));
+                });
+
+                ui.menu_button(tr("scene.clip"), |ui| {
+                    let controls = &context.controls;
+                    let focus =
+                        controls.position + controls.rotation * Vec3::Z * controls.focus_distance;
+                    self.clip_planes
+                        .menu_ui(ui, focus, controls.focus_distance * 0.005);
+                })
+                .response
+                .on_hover_text(tr("scene.clip_hover"));
+
+                let mut post_process = context.post_process();
+                ui.menu_button(tr("scene.post_process"), |ui| {
+                    ui.add(
+                        egui::Slider::new(&mut post_process.exposure, -4.0..=4.0)
+                            .text(tr("scene.exposure")),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut post_process.bloom, 0.0..=2.0)
+                            .text(tr("scene.bloom")),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut post_process.bloom_threshold, 0.0..=2.0)
+                            .text(tr("scene.bloom_threshold")),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut post_process.vignette, 0.0..=1.0)
+                            .text(tr("scene.vignette")),
+                    );
+                    ui.checkbox(&mut post_process.tonemap, tr("scene.tonemap"));
+                    ui.add(
+                        egui::Slider::new(&mut post_process.ambient_occlusion, 0.0..=1.0)
+                            .text(tr("scene.ambient_occlusion")),
+                    )
+                    .on_hover_text(tr("scene.ambient_occlusion_hover"));
+                    if ui.button(tr("scene.post_process_reset")).clicked() {
+                        post_process = PostProcess::default();
+                    }
+                });
+                if post_process != context.post_process() {
+                    context.set_post_process(post_process);
                 }
 
-                ui.selectable_label(false, "Controls")
+                ui.add_space(15.0);
+
+                let mut power = context.power_settings();
+                if ui
+                    .selectable_label(power.low_power, tr("scene.low_power"))
+                    .on_hover_text(tr("scene.low_power_hover"))
+                    .clicked()
+                {
+                    power.low_power = !power.low_power;
+                }
+                if power.low_power {
+                    ui.add(
+                        egui::DragValue::new(&mut power.max_fps)
+                            .range(1..=60)
+                            .suffix(" fps"),
+                    );
+                }
+                if power != context.power_settings() {
+                    context.set_power_settings(power);
+                }
+
+                ui.add_space(15.0);
+
+                ui.selectable_label(false, tr("scene.controls"))
                     .on_hover_ui_at_pointer(|ui| {
-                        ui.heading("Controls");
+                        ui.heading(tr("scene.controls"));
 
-                        ui.label("• Left click and drag to orbit");
+                        ui.label(tr("controls.orbit"));
                         ui.label(
-                            "• Right click, or left click + spacebar, and drag to look around.",
+                            tr("controls.look")
+                                .replace("{key}", &keymap.describe(Action::LookAround)),
                         );
-                        ui.label("• Middle click, or left click + control, and drag to pan");
-                        ui.label("• Scroll to zoom");
-                        ui.label("• WASD to fly, Q&E to move up & down.");
-                        ui.label("• Z&C to roll, X to reset roll");
-                        ui.label("• Shift to move faster");
+                        ui.label(tr("controls.pan"));
+                        ui.label(tr("controls.zoom"));
+                        ui.label(tr("controls.go_to"));
+                        for action in [
+                            Action::MoveForward,
+                            Action::MoveBackward,
+                            Action::MoveLeft,
+                            Action::MoveRight,
+                            Action::MoveUp,
+                            Action::MoveDown,
+                            Action::RollLeft,
+                            Action::RollRight,
+                            Action::ResetRoll,
+                            Action::Frame,
+                        ] {
+                            ui.label(format!("• {}: {}", action.label(), keymap.describe(action)));
+                        }
+                        ui.label(tr("controls.faster"));
+                        ui.label(tr("controls.rebind_note"));
                     });
             });
+
+            if go_back {
+                self.show_previous(context);
+            }
+            if frame_model {
+                if let Some(splats) = &splats {
+                    self.framer.frame(ui.ctx(), splats);
+                }
+            }
         }
     }
 
@@ -1,18 +1,43 @@
 use crate::{
     app::{AppContext, AppPanel},
+    i18n::{self, Locale, tr},
+    keymap::KeymapEditor,
     running_process::start_process,
 };
 use brush_dataset::{LoadDataseConfig, ModelConfig};
 use brush_process::{
     data_source::DataSource,
-    process_loop::{ProcessArgs, ProcessConfig, RerunConfig},
+    process_loop::{ProcessArgs, ProcessConfig, ProcessMessage, RerunConfig},
 };
-use brush_train::config::TrainConfig;
+use brush_train::{config::TrainConfig, memory};
 use egui::Slider;
+use std::path::PathBuf;
 
 pub(crate) struct SettingsPanel {
     args: ProcessArgs,
     url: String,
+    keymap_editor: KeymapEditor,
+    quick_preview: bool,
+    // Source of the last quick preview, and the folder it writes to once it's training.
+    preview: Option<(DataSource, Option<PathBuf>)>,
+    #[cfg(target_os = "android")]
+    capture: Option<tokio::sync::oneshot::Receiver<anyhow::Result<std::path::PathBuf>>>,
+    #[cfg(target_os = "android")]
+    capture_preview: bool,
+}
+
+/// File the splats of a quick preview are exported to, to continue from.
+const PREVIEW_EXPORT: &str = "preview.ply";
+
+/// Cheaper settings to quickly check if the poses and coverage of a dataset are any good.
+fn quick_preview_args(mut args: ProcessArgs) -> ProcessArgs {
+    args.train_config.total_steps = 2000;
+    args.train_config.max_splats = args.train_config.max_splats.min(250_000);
+    args.load_config.max_resolution = (args.load_config.max_resolution / 4).max(256);
+    // Only export the end result.
+    args.process_config.export_every = args.train_config.total_steps;
+    args.process_config.export_name = PREVIEW_EXPORT.to_owned();
+    args
 }
 
 impl SettingsPanel {
@@ -27,28 +52,63 @@ impl SettingsPanel {
                 RerunConfig::new(),
             ),
             url: "splat.com/example.ply".to_owned(),
+            keymap_editor: KeymapEditor::default(),
+            quick_preview: false,
+            preview: None,
+            #[cfg(target_os = "android")]
+            capture: None,
+            #[cfg(target_os = "android")]
+            capture_preview: true,
         }
     }
 }
 
 impl AppPanel for SettingsPanel {
     fn title(&self) -> String {
-        "Settings".to_owned()
+        tr("panel.settings").to_owned()
+    }
+
+    fn on_message(&mut self, message: &ProcessMessage, _: &mut AppContext) {
+        if let ProcessMessage::RunDir { path } = message {
+            match &mut self.preview {
+                Some((_, dir @ None)) => *dir = Some(path.clone()),
+                // Something else started training since.
+                _ => self.preview = None,
+            }
+        }
     }
 
     fn ui(&mut self, ui: &mut egui::Ui, context: &mut AppContext) {
+        if let Some(args) = context.prefill_args.take() {
+            self.args = args;
+        }
+
         egui::ScrollArea::vertical().show(ui, |ui| {
-            ui.heading("Model Settings");
-            ui.label("Spherical Harmonics Degree:");
+            let mut locale = i18n::locale();
+            egui::ComboBox::from_label(tr("settings.language"))
+                .selected_text(locale.name())
+                .show_ui(ui, |ui| {
+                    for l in Locale::ALL {
+                        ui.selectable_value(&mut locale, l, l.name());
+                    }
+                });
+            if locale != i18n::locale() {
+                i18n::set_locale(locale);
+            }
+
+            ui.add_space(10.0);
+
+            ui.heading(tr("settings.model"));
+            ui.label(tr("settings.sh_degree"));
             ui.add(Slider::new(&mut self.args.model_config.sh_degree, 0..=4));
 
-            ui.label("Max image resolution");
+            ui.label(tr("settings.max_resolution"));
             ui.add(
                 Slider::new(&mut self.args.load_config.max_resolution, 32..=2048)
                     .clamping(egui::SliderClamping::Never),
             );
 
-            ui.label("Max Splats");
+            ui.label(tr("settings.max_splats"));
             ui.add(
                 Slider::new(&mut self.args.train_config.max_splats, 1000000..=10000000)
                     .custom_formatter(|n, _| {
@@ -58,8 +118,28 @@ impl AppPanel for SettingsPanel {
                     .clamping(egui::SliderClamping::Never),
             );
 
+            ui.checkbox(
+                &mut self.args.train_config.budget_pruning,
+                tr("settings.budget_pruning"),
+            )
+            .on_hover_text(tr("settings.budget_pruning_hover"));
+
+            let mut adaptive_growth = self.args.train_config.growth_target_splats > 0;
+            ui.checkbox(&mut adaptive_growth, tr("settings.adaptive_growth"))
+                .on_hover_text(tr("settings.adaptive_growth_hover"));
+            self.args.train_config.growth_target_splats = if adaptive_growth {
+                self.args.train_config.max_splats
+            } else {
+                0
+            };
+
+            self.memory_ui(ui, context.memory_budget);
+
             let mut limit_frames = self.args.load_config.max_frames.is_some();
-            if ui.checkbox(&mut limit_frames, "Limit max frames").clicked() {
+            if ui
+                .checkbox(&mut limit_frames, tr("settings.limit_frames"))
+                .clicked()
+            {
                 self.args.load_config.max_frames = if limit_frames { Some(32) } else { None };
             }
 
@@ -69,7 +149,7 @@ impl AppPanel for SettingsPanel {
 
             let mut use_eval_split = self.args.load_config.eval_split_every.is_some();
             if ui
-                .checkbox(&mut use_eval_split, "Split dataset for evaluation")
+                .checkbox(&mut use_eval_split, tr("settings.eval_split"))
                 .clicked()
             {
                 self.args.load_config.eval_split_every =
@@ -85,10 +165,10 @@ impl AppPanel for SettingsPanel {
                 );
             }
 
-            ui.heading("Training Settings");
+            ui.heading(tr("settings.training"));
 
             ui.horizontal(|ui| {
-                ui.label("Train");
+                ui.label(tr("settings.train"));
 
                 ui.add(
                     egui::Slider::new(&mut self.args.train_config.total_steps, 1..=50000)
@@ -97,10 +177,19 @@ impl AppPanel for SettingsPanel {
                 );
             });
 
-            ui.heading("Process Settings");
+            let mut stop_early = self.args.train_config.stop_min_improvement > 0.0;
+            if ui
+                .checkbox(&mut stop_early, tr("settings.stop_early"))
+                .on_hover_text(tr("settings.stop_early_hover"))
+                .clicked()
+            {
+                self.args.train_config.stop_min_improvement = if stop_early { 0.005 } else { 0.0 };
+            }
+
+            ui.heading(tr("settings.process"));
 
             ui.horizontal(|ui| {
-                ui.label("Evaluate");
+                ui.label(tr("settings.evaluate"));
                 ui.add(
                     egui::Slider::new(&mut self.args.process_config.eval_every, 1..=5000)
                         .clamping(egui::SliderClamping::Never)
@@ -109,10 +198,66 @@ impl AppPanel for SettingsPanel {
                 );
             });
 
+            ui.horizontal(|ui| {
+                ui.label(tr("settings.send_splats"));
+                ui.add(
+                    egui::Slider::new(
+                        &mut self.args.process_config.max_splat_updates_per_sec,
+                        0.1..=60.0,
+                    )
+                    .clamping(egui::SliderClamping::Never)
+                    .prefix("max ")
+                    .suffix(" times per second"),
+                );
+            });
+
+            let mut power = context.power_settings();
+            ui.horizontal(|ui| {
+                ui.label(tr("settings.update_ui"));
+                ui.add(
+                    egui::Slider::new(&mut power.ui_update_hz, 1..=60).suffix(" times per second"),
+                );
+            });
+            ui.horizontal(|ui| {
+                let mut capped = power.fps_cap.is_some();
+                ui.checkbox(&mut capped, tr("settings.fps_cap"))
+                    .on_hover_text(tr("settings.fps_cap_hover"));
+                let mut fps = power.fps_cap.unwrap_or(60);
+                if capped {
+                    ui.add(egui::DragValue::new(&mut fps).range(1..=500).suffix(" fps"));
+                }
+                power.fps_cap = capped.then_some(fps);
+            });
+            ui.checkbox(
+                &mut context.keep_scene_while_loading,
+                tr("settings.keep_scene"),
+            )
+            .on_hover_text(tr("settings.keep_scene_hover"));
+            ui.checkbox(&mut context.keep_view, tr("settings.keep_view"))
+                .on_hover_text(tr("settings.keep_view_hover"));
+            ui.checkbox(
+                &mut context.smooth_transitions,
+                tr("settings.smooth_transitions"),
+            )
+            .on_hover_text(tr("settings.smooth_transitions_hover"));
+            ui.checkbox(&mut context.auto_speed, tr("settings.auto_speed"))
+                .on_hover_text(tr("settings.auto_speed_hover"));
+            ui.checkbox(&mut power.hidpi, tr("settings.hidpi"))
+                .on_hover_text(tr("settings.hidpi_hover"));
+            ui.add(
+                egui::Slider::new(&mut power.render_scale, 0.5..=2.0)
+                    .text(tr("settings.render_scale"))
+                    .suffix("x"),
+            )
+            .on_hover_text(tr("settings.render_scale_hover"));
+            if power != context.power_settings() {
+                context.set_power_settings(power);
+            }
+
             #[cfg(not(target_family = "wasm"))]
             {
                 ui.horizontal(|ui| {
-                    ui.label("Export");
+                    ui.label(tr("settings.export"));
                     ui.add(
                         egui::Slider::new(&mut self.args.process_config.export_every, 1..=15000)
                             .clamping(egui::SliderClamping::Never)
@@ -124,7 +269,7 @@ impl AppPanel for SettingsPanel {
 
             #[cfg(all(not(target_family = "wasm"), not(target_os = "android")))]
             {
-                ui.heading("Rerun Settings");
+                ui.heading(tr("settings.rerun"));
 
                 ui.horizontal(|ui| {
                     ui.spacing_mut().item_spacing.x = 0.0;
@@ -132,15 +277,16 @@ impl AppPanel for SettingsPanel {
                     ui.label(" settings");
                 });
                 let rerun_config = &mut self.args.rerun_config;
-                ui.checkbox(&mut rerun_config.rerun_enabled, "Enable rerun");
+                ui.checkbox(
+                    &mut rerun_config.rerun_enabled,
+                    tr("settings.rerun_enabled"),
+                );
 
                 if rerun_config.rerun_enabled {
-                    ui.label(
-                    "Open the brush_blueprint.rbl in the rerun viewer for a good default layout.",
-                );
+                    ui.label(tr("settings.rerun_blueprint"));
 
                     ui.horizontal(|ui| {
-                        ui.label("Log train stats");
+                        ui.label(tr("settings.rerun_train_stats"));
                         ui.add(
                             egui::Slider::new(
                                 &mut rerun_config.rerun_log_train_stats_every,
@@ -153,7 +299,7 @@ impl AppPanel for SettingsPanel {
                     });
 
                     let mut visualize_splats = rerun_config.rerun_log_splats_every.is_some();
-                    ui.checkbox(&mut visualize_splats, "Visualize splats");
+                    ui.checkbox(&mut visualize_splats, tr("settings.rerun_visualize"));
                     if visualize_splats != rerun_config.rerun_log_splats_every.is_some() {
                         rerun_config.rerun_log_splats_every =
                             if visualize_splats { Some(500) } else { None };
@@ -163,25 +309,40 @@ impl AppPanel for SettingsPanel {
                         ui.add(
                             egui::Slider::new(every, 1..=5000)
                                 .clamping(egui::SliderClamping::Never)
-                                .text("Visualize splats every"),
+                                .text(tr("settings.rerun_visualize_every")),
                         );
                     }
                 }
             }
 
+            egui::CollapsingHeader::new(tr("settings.shortcuts")).show(ui, |ui| {
+                self.keymap_editor.ui(ui, &mut context.keymap);
+            });
+
             ui.add_space(20.0);
 
-            ui.label("Select a .ply to visualize, or a .zip with training data.");
+            ui.label(tr("settings.load_hint"));
+            ui.checkbox(&mut self.quick_preview, tr("settings.quick_preview"))
+                .on_hover_text(tr("settings.quick_preview_hover"));
 
-            let file = ui.button("Load file").clicked();
+            let file = ui.button(tr("settings.load_file")).clicked();
 
             let can_pick_dir = !cfg!(target_family = "wasm") && !cfg!(target_os = "android");
-            let dir = can_pick_dir && ui.button("Load directory").clicked();
+            let dir = can_pick_dir && ui.button(tr("settings.load_directory")).clicked();
 
             ui.add_space(10.0);
             ui.text_edit_singleline(&mut self.url);
 
-            let url = ui.button("Load URL").clicked();
+            let url = ui.button(tr("settings.load_url")).clicked();
+            if ui
+                .button(tr("settings.queue_url"))
+                .on_hover_text(tr("settings.queue_hover"))
+                .clicked()
+            {
+                context
+                    .queue
+                    .push(DataSource::Url(self.url.clone()), self.args.clone());
+            }
 
             ui.add_space(10.0);
 
@@ -193,15 +354,177 @@ impl AppPanel for SettingsPanel {
                 } else {
                     DataSource::Url(self.url.clone())
                 };
+                if self.estimated_train_bytes() > context.memory_budget {
+                    log::warn!(
+                        "Training is predicted to use {} MB of GPU memory, which might not fit \
+                         on this device. Consider lowering max splats or resolution.",
+                        self.estimated_train_bytes() / 1_000_000
+                    );
+                }
+                self.start(context, ui.ctx(), source);
+            }
+
+            // Iterate on the same dataset, without picking it again.
+            if let Some(source) = context.reloadable_source() {
+                if ui
+                    .button(tr("settings.train_again"))
+                    .on_hover_text(tr("settings.train_again_hover"))
+                    .clicked()
+                {
+                    self.start(context, ui.ctx(), source.clone());
+                }
+                if ui
+                    .button(tr("settings.queue_again"))
+                    .on_hover_text(tr("settings.queue_hover"))
+                    .clicked()
+                {
+                    context.queue.push(source, self.args.clone());
+                }
+            }
+
+            self.preview_ui(ui, context);
+
+            ui.add_space(10.0);
+
+            #[cfg(target_os = "android")]
+            self.capture_ui(ui, context);
+        });
+    }
+}
+
+impl SettingsPanel {
+    /// Train on `source` with the current settings, or a quick preview of them.
+    fn start(&mut self, context: &mut AppContext, ctx: &egui::Context, source: DataSource) {
+        let args = if self.quick_preview {
+            self.preview = Some((source.clone(), None));
+            quick_preview_args(self.args.clone())
+        } else {
+            self.preview = None;
+            self.args.clone()
+        };
+        context.connect_to(start_process(
+            source,
+            args,
+            context.device.clone(),
+            ctx.clone(),
+        ));
+    }
+
+    /// Offer to continue a finished quick preview with the full settings.
+    fn preview_ui(&mut self, ui: &mut egui::Ui, context: &mut AppContext) {
+        let Some((source, Some(dir))) = &self.preview else {
+            return;
+        };
+        // The preview only exports at the end, so it's done once this exists.
+        let init = dir.join(PREVIEW_EXPORT);
+        if !init.is_file() {
+            return;
+        }
+        if ui
+            .button(tr("settings.continue_preview"))
+            .on_hover_text(tr("settings.continue_preview_hover"))
+            .clicked()
+        {
+            let mut args = self.args.clone();
+            args.load_config.init_ply = Some(init.to_string_lossy().into_owned());
+            context.connect_to(start_process(
+                source.clone(),
+                args,
+                context.device.clone(),
+                ui.ctx().clone(),
+            ));
+            self.preview = None;
+        }
+    }
+
+    fn estimated_train_bytes(&self) -> u64 {
+        memory::estimate_train_bytes(
+            self.args.train_config.max_splats,
+            self.args.model_config.sh_degree,
+            self.args.load_config.max_resolution,
+        )
+    }
+
+    /// Show the predicted memory use, and offer limits that should fit.
+    fn memory_ui(&mut self, ui: &mut egui::Ui, budget: u64) {
+        let estimate = self.estimated_train_bytes();
+        ui.label(
+            tr("settings.memory_estimate")
+                .replace("{estimate}", &format!("{:.1}", estimate as f64 / 1e9))
+                .replace("{budget}", &format!("{:.1}", budget as f64 / 1e9)),
+        );
+
+        if estimate > budget {
+            ui.colored_label(egui::Color32::YELLOW, tr("settings.memory_warning"));
+
+            let suggestion = memory::suggest_limits(
+                budget,
+                self.args.model_config.sh_degree,
+                self.args.load_config.max_resolution,
+            );
+            let text = tr("settings.memory_suggest")
+                .replace("{splats}", &format!("{}k", suggestion.max_splats / 1000))
+                .replace("{resolution}", &suggestion.max_resolution.to_string());
+            if ui.button(text).clicked() {
+                self.args.train_config.max_splats = suggestion.max_splats;
+                self.args.load_config.max_resolution = suggestion.max_resolution;
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "android")]
+impl SettingsPanel {
+    fn capture_ui(&mut self, ui: &mut egui::Ui, context: &mut AppContext) {
+        use tokio::sync::oneshot::error::TryRecvError;
+
+        ui.heading(tr("capture.heading"));
+        ui.label(tr("capture.hint"));
+        ui.checkbox(&mut self.capture_preview, tr("capture.preview"));
+
+        if self.capture.is_some() {
+            ui.horizontal(|ui| {
+                ui.label(tr("capture.capturing"));
+                ui.spinner();
+            });
+        } else if ui.button(tr("capture.start")).clicked() {
+            let (sender, receiver) = tokio::sync::oneshot::channel();
+            let ctx = ui.ctx().clone();
+            tokio_with_wasm::alias::task::spawn(async move {
+                let _ = sender.send(crate::capture::capture_dataset().await);
+                ctx.request_repaint();
+            });
+            self.capture = Some(receiver);
+        }
+
+        let Some(receiver) = self.capture.as_mut() else {
+            return;
+        };
+
+        match receiver.try_recv() {
+            Ok(Ok(path)) => {
+                self.capture = None;
+                let source = DataSource::Path(path.to_string_lossy().into_owned());
+                let args = if self.capture_preview {
+                    self.preview = Some((source.clone(), None));
+                    quick_preview_args(self.args.clone())
+                } else {
+                    self.preview = None;
+                    self.args.clone()
+                };
                 context.connect_to(start_process(
                     source,
-                    self.args.clone(),
+                    args,
                     context.device.clone(),
                     ui.ctx().clone(),
                 ));
             }
-
-            ui.add_space(10.0);
-        });
+            Ok(Err(e)) => {
+                self.capture = None;
+                log::error!("Failed to capture dataset: {e}");
+            }
+            Err(TryRecvError::Closed) => self.capture = None,
+            Err(TryRecvError::Empty) => {}
+        }
     }
 }
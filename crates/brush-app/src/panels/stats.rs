@@ -1,5 +1,12 @@
-use crate::app::{AppContext, AppPanel};
-use brush_process::process_loop::ProcessMessage;
+use std::path::PathBuf;
+
+use crate::{
+    app::{AppContext, AppPanel},
+    i18n::tr,
+};
+use brush_dataset::scene_loader::CacheStats;
+use brush_process::process_loop::{EvalRequest, ProcessMessage};
+use brush_train::{memory::MemoryUse, train::RefineStats};
 
 use burn_cubecl::cubecl::Runtime;
 use burn_wgpu::{WgpuDevice, WgpuRuntime};
@@ -12,12 +19,24 @@ pub(crate) struct StatsPanel {
     last_train_step: (Duration, u32),
     train_iter_per_s: f32,
     last_eval: Option<String>,
+    /// Set while training with eval views, to eval right away.
+    eval_request: Option<EvalRequest>,
     cur_sh_degree: u32,
 
     training_started: bool,
     num_splats: u32,
     frames: u32,
     adapter_info: AdapterInfo,
+
+    /// Highest GPU memory use seen so far.
+    peak_bytes: u64,
+    /// Memory use of training, as last measured.
+    memory_use: Option<MemoryUse>,
+    image_cache: CacheStats,
+
+    refine_history: Vec<(u32, RefineStats)>,
+    /// Folder the current run writes its files to.
+    run_dir: Option<PathBuf>,
 }
 
 impl StatsPanel {
@@ -27,11 +46,17 @@ impl StatsPanel {
             last_train_step: (Duration::from_secs(0), 0),
             train_iter_per_s: 0.0,
             last_eval: None,
+            eval_request: None,
             training_started: false,
             num_splats: 0,
             frames: 0,
             cur_sh_degree: 0,
             adapter_info,
+            peak_bytes: 0,
+            memory_use: None,
+            image_cache: CacheStats::default(),
+            refine_history: vec![],
+            run_dir: None,
         }
     }
 }
@@ -56,9 +81,101 @@ fn bytes_format(bytes: u64) -> String {
     }
 }
 
+impl StatsPanel {
+    fn refine_ui(&self, ui: &mut egui::Ui) {
+        let Some((iter, last)) = self.refine_history.last() else {
+            return;
+        };
+
+        ui.label(tr("stats.splat_count_plot"));
+        splat_count_plot(ui, &self.refine_history);
+
+        egui::Grid::new("refine_grid")
+            .num_columns(2)
+            .spacing([40.0, 4.0])
+            .striped(true)
+            .show(ui, |ui| {
+                let rows = [
+                    (tr("stats.refine_iter"), iter.to_string()),
+                    (tr("stats.refine_added"), last.num_added.to_string()),
+                    (tr("stats.refine_pruned"), last.num_pruned.to_string()),
+                    (
+                        tr("stats.refine_transparent"),
+                        last.num_transparent.to_string(),
+                    ),
+                    (tr("stats.refine_invisible"), last.num_invisible.to_string()),
+                    (
+                        tr("stats.refine_budget"),
+                        last.num_budget_pruned.to_string(),
+                    ),
+                    (
+                        tr("stats.refine_above_threshold"),
+                        last.num_above_threshold.to_string(),
+                    ),
+                    (
+                        tr("stats.refine_threshold"),
+                        format!("{:.6}", last.growth_grad_threshold),
+                    ),
+                ];
+                for (label, value) in rows {
+                    ui.label(label);
+                    ui.label(value);
+                    ui.end_row();
+                }
+            });
+    }
+}
+
+/// Line plot of the splat count at each refine.
+fn splat_count_plot(ui: &mut egui::Ui, history: &[(u32, RefineStats)]) {
+    let size = egui::vec2(ui.available_width(), 80.0);
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+    let max_iter = history.iter().map(|(i, _)| *i).max().unwrap_or(1).max(1) as f32;
+    let max_count = history
+        .iter()
+        .map(|(_, s)| s.num_splats)
+        .max()
+        .unwrap_or(1)
+        .max(1) as f32;
+
+    let points: Vec<_> = history
+        .iter()
+        .map(|(iter, stats)| {
+            egui::pos2(
+                rect.left() + rect.width() * *iter as f32 / max_iter,
+                rect.bottom() - rect.height() * stats.num_splats as f32 / max_count,
+            )
+        })
+        .collect();
+    painter.add(egui::Shape::line(
+        points,
+        egui::Stroke::new(1.5, ui.visuals().selection.bg_fill),
+    ));
+    painter.text(
+        rect.left_top() + egui::vec2(4.0, 2.0),
+        egui::Align2::LEFT_TOP,
+        format!("{}", max_count as u32),
+        egui::FontId::monospace(10.0),
+        ui.visuals().weak_text_color(),
+    );
+
+    if let Some(pos) = response.hover_pos() {
+        let hover_iter = (pos.x - rect.left()) / rect.width() * max_iter;
+        if let Some((iter, stats)) = history
+            .iter()
+            .min_by_key(|(i, _)| (*i as f32 - hover_iter).abs() as u32)
+        {
+            response.on_hover_text(format!("{iter}: {}", stats.num_splats));
+        }
+    }
+}
+
 impl AppPanel for StatsPanel {
     fn title(&self) -> String {
-        "Stats".to_owned()
+        tr("panel.stats").to_owned()
     }
 
     fn on_message(&mut self, message: &ProcessMessage, _: &mut AppContext) {
@@ -71,7 +188,11 @@ impl AppPanel for StatsPanel {
                 self.num_splats = 0;
                 self.cur_sh_degree = 0;
                 self.last_eval = None;
+                self.eval_request = None;
+                self.refine_history.clear();
+                self.run_dir = None;
                 self.training_started = *training;
+                self.memory_use = None;
             }
             ProcessMessage::ViewSplats {
                 up_axis: _,
@@ -85,17 +206,36 @@ impl AppPanel for StatsPanel {
             }
             ProcessMessage::TrainStep {
                 splats,
-                stats: _,
+                stats,
+                camera: _,
+                view_index: _,
+                gt_image: _,
                 iter,
                 total_elapsed,
+                image_cache,
             } => {
-                self.cur_sh_degree = splats.sh_degree();
-                self.num_splats = splats.num_splats();
+                self.image_cache = *image_cache;
+                if let Some(splats) = splats.as_ref().and_then(|s| s.front()) {
+                    self.cur_sh_degree = splats.sh_degree();
+                    self.num_splats = splats.num_splats();
+                }
+                if let Some(memory) = stats.memory {
+                    self.memory_use = Some(memory);
+                }
                 let current_iter_per_s = (iter - self.last_train_step.1) as f32
                     / (*total_elapsed - self.last_train_step.0).as_secs_f32();
                 self.train_iter_per_s = 0.95 * self.train_iter_per_s + 0.05 * current_iter_per_s;
                 self.last_train_step = (*total_elapsed, *iter);
             }
+            ProcessMessage::RefineStep { stats, iter, .. } => {
+                self.refine_history.push((*iter, stats.as_ref().clone()));
+            }
+            ProcessMessage::RunDir { path } => {
+                self.run_dir = Some(path.clone());
+            }
+            ProcessMessage::EvalAvailable { request } => {
+                self.eval_request = Some(request.clone());
+            }
             ProcessMessage::EvalResult {
                 iter: _,
                 avg_psnr,
@@ -113,38 +253,45 @@ impl AppPanel for StatsPanel {
             .spacing([40.0, 4.0])
             .striped(true)
             .show(ui, |ui| {
-                ui.label("Splats");
+                ui.label(tr("stats.splats"));
                 ui.label(format!("{}", self.num_splats));
                 ui.end_row();
 
-                ui.label("SH Degree");
+                ui.label(tr("stats.sh_degree"));
                 ui.label(format!("{}", self.cur_sh_degree));
                 ui.end_row();
 
                 if self.frames > 0 {
-                    ui.label("Frames");
+                    ui.label(tr("stats.frames"));
                     ui.label(format!("{}", self.frames));
                     ui.end_row();
                 }
 
                 if self.training_started {
-                    ui.label("Train step");
+                    ui.label(tr("stats.train_step"));
                     ui.label(format!("{}", self.last_train_step.1));
                     ui.end_row();
 
-                    ui.label("Steps/s");
+                    ui.label(tr("stats.steps_per_sec"));
                     ui.label(format!("{:.1}", self.train_iter_per_s));
                     ui.end_row();
 
-                    ui.label("Last eval:");
-                    ui.label(if let Some(eval) = self.last_eval.as_ref() {
-                        eval
-                    } else {
-                        "--"
+                    ui.label(tr("stats.last_eval"));
+                    ui.horizontal(|ui| {
+                        ui.label(if let Some(eval) = self.last_eval.as_ref() {
+                            eval
+                        } else {
+                            "--"
+                        });
+                        if let Some(request) = &self.eval_request {
+                            if ui.small_button(tr("stats.eval_now")).clicked() {
+                                request.request();
+                            }
+                        }
                     });
                     ui.end_row();
 
-                    ui.label("Training time");
+                    ui.label(tr("stats.training_time"));
                     ui.label(format!(
                         "{}",
                         // Format in at most whole seconds.
@@ -153,27 +300,85 @@ impl AppPanel for StatsPanel {
                         ))
                     ));
                     ui.end_row();
+
+                    let cache = &self.image_cache;
+                    let lookups = (cache.hits + cache.misses).max(1);
+                    ui.label(tr("stats.image_cache"));
+                    ui.label(
+                        tr("stats.image_cache_value")
+                            .replace("{images}", &cache.images.to_string())
+                            .replace("{used}", &bytes_format(cache.bytes))
+                            .replace("{budget}", &bytes_format(cache.budget_bytes))
+                            .replace("{hits}", &(100 * cache.hits / lookups).to_string()),
+                    );
+                    ui.end_row();
                 }
 
                 let client = WgpuRuntime::client(&self.device);
                 let memory = client.memory_usage();
 
-                ui.label("GPU memory");
+                ui.label(tr("stats.gpu_memory"));
                 ui.end_row();
 
-                ui.label("Bytes in use");
+                ui.label(tr("stats.bytes_in_use"));
                 ui.label(bytes_format(memory.bytes_in_use));
                 ui.end_row();
 
-                ui.label("Bytes reserved");
+                ui.label(tr("stats.bytes_reserved"));
                 ui.label(bytes_format(memory.bytes_reserved));
                 ui.end_row();
 
-                ui.label("Active allocations");
+                ui.label(tr("stats.active_allocations"));
                 ui.label(format!("{}", memory.number_allocs));
                 ui.end_row();
+
+                self.peak_bytes = self.peak_bytes.max(memory.bytes_in_use);
+                ui.label(tr("stats.peak_in_use"));
+                ui.label(bytes_format(self.peak_bytes));
+                ui.end_row();
+
+                // Training measures the buffers of each part every so often. Whatever
+                // else is in use is the viewer, caches and other buffers.
+                if let Some(used) = self.memory_use {
+                    let rows = [
+                        ("stats.mem_splats", used.splats),
+                        ("stats.mem_optimizer", used.optimizer),
+                        ("stats.mem_render", used.render),
+                        ("stats.mem_images", used.images),
+                    ];
+                    for (key, bytes) in rows {
+                        ui.label(tr(key));
+                        ui.label(bytes_format(bytes));
+                        ui.end_row();
+                    }
+
+                    let known = used.splats + used.optimizer + used.images;
+                    ui.label(tr("stats.mem_other"));
+                    ui.label(bytes_format(memory.bytes_in_use.saturating_sub(known)));
+                    ui.end_row();
+                }
             });
 
+        // There's no file browser to show the folder in on the web or android.
+        let can_open = !cfg!(target_family = "wasm") && !cfg!(target_os = "android");
+        if let Some(run_dir) = self.run_dir.as_ref().filter(|_| can_open) {
+            if ui
+                .button(tr("stats.open_output"))
+                .on_hover_text(run_dir.display().to_string())
+                .clicked()
+            {
+                let dir = std::path::absolute(run_dir).unwrap_or_else(|_| run_dir.clone());
+                ui.ctx()
+                    .open_url(egui::OpenUrl::same_tab(format!("file://{}", dir.display())));
+            }
+        }
+
+        if !self.refine_history.is_empty() {
+            egui::CollapsingHeader::new(tr("stats.refine_history")).show(ui, |ui| {
+                self.refine_ui(ui);
+            });
+        }
+
         // On WASM, adapter info is mostly private, not worth showing.
         if !cfg!(target_family = "wasm") {
             egui::Grid::new("gpu_grid")
@@ -181,18 +386,18 @@ impl AppPanel for StatsPanel {
                 .spacing([40.0, 4.0])
                 .striped(true)
                 .show(ui, |ui| {
-                    ui.label("GPU");
+                    ui.label(tr("stats.gpu"));
                     ui.end_row();
 
-                    ui.label("Name");
+                    ui.label(tr("stats.gpu_name"));
                     ui.label(&self.adapter_info.name);
                     ui.end_row();
 
-                    ui.label("Type");
+                    ui.label(tr("stats.gpu_type"));
                     ui.label(format!("{:?}", self.adapter_info.device_type));
                     ui.end_row();
 
-                    ui.label("Driver");
+                    ui.label(tr("stats.gpu_driver"));
                     ui.label(format!(
                         "{}, {}",
                         self.adapter_info.driver, self.adapter_info.driver_info
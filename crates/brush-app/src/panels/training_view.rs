@@ -0,0 +1,152 @@
+use std::sync::Arc;
+
+use brush_render::{camera::Camera, gaussian_splats::Splats};
+use brush_train::train::TrainBack;
+use brush_ui::burn_texture::BurnTexture;
+use burn::tensor::Tensor;
+use burn::tensor::backend::AutodiffBackend;
+use burn::tensor::module::adaptive_avg_pool2d;
+use eframe::egui_wgpu::Renderer;
+use egui::{Area, Color32, Rect, epaint::mutex::RwLock as EguiRwLock};
+
+const THUMB_HEIGHT: f32 = 120.0;
+
+type InnerBackend = <TrainBack as AutodiffBackend>::InnerBackend;
+
+/// Small picture in picture overlay, showing the splats from the camera currently being
+/// trained next to the ground truth image of that view.
+pub(crate) struct TrainingViewPip {
+    backbuffer: BurnTexture,
+    camera: Option<Camera>,
+    dirty: bool,
+
+    gt_backbuffer: BurnTexture,
+    gt_index: Option<usize>,
+    /// Ground truth image of a new view, still to be copied to its texture.
+    gt_pending: Option<Tensor<InnerBackend, 3>>,
+    gt_aspect: f32,
+}
+
+impl TrainingViewPip {
+    pub(crate) fn new(
+        renderer: Arc<EguiRwLock<Renderer>>,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+    ) -> Self {
+        Self {
+            backbuffer: BurnTexture::new(renderer.clone(), device.clone(), queue.clone()),
+            camera: None,
+            dirty: false,
+            gt_backbuffer: BurnTexture::new(renderer, device, queue),
+            gt_index: None,
+            gt_pending: None,
+            gt_aspect: 1.0,
+        }
+    }
+
+    /// Show the step trained on view `index`, with the image it was trained on.
+    pub(crate) fn set_step(
+        &mut self,
+        camera: Camera,
+        index: usize,
+        gt_image: Tensor<InnerBackend, 3>,
+    ) {
+        self.camera = Some(camera);
+        self.dirty = true;
+
+        // The image is already on the GPU for training, only copy it over when the view changes.
+        if self.gt_index != Some(index) {
+            self.gt_index = Some(index);
+            let [height, width, _] = gt_image.dims();
+            self.gt_aspect = width as f32 / height as f32;
+            self.gt_pending = Some(gt_image);
+        }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.camera = None;
+        self.gt_index = None;
+        self.gt_pending = None;
+    }
+
+    /// Draw the overlay in the bottom right corner of the given rect.
+    pub(crate) fn draw(
+        &mut self,
+        ui: &mut egui::Ui,
+        rect: Rect,
+        splats: Option<&Splats<InnerBackend>>,
+    ) {
+        let Some(camera) = self.camera.clone() else {
+            return;
+        };
+
+        if let Some(gt_image) = self.gt_pending.take() {
+            self.gt_backbuffer.update_texture(thumbnail(gt_image));
+        }
+
+        let thumb_size = egui::vec2(THUMB_HEIGHT * self.gt_aspect, THUMB_HEIGHT);
+
+        if let Some(splats) = splats {
+            if self.dirty {
+                self.dirty = false;
+                let size = glam::uvec2(thumb_size.x as u32, thumb_size.y as u32);
+                let (img, _) = splats.render(&camera, size, false);
+                self.backbuffer.update_texture(img);
+            }
+        }
+
+        let margin = 8.0;
+        let total = egui::vec2(thumb_size.x * 2.0 + margin, thumb_size.y);
+        let pos = rect.max - total - egui::vec2(margin, margin);
+
+        Area::new(ui.auto_id_with("training_view_pip"))
+            .order(egui::Order::Foreground)
+            .fixed_pos(pos)
+            .interactable(false)
+            .show(ui.ctx(), |ui| {
+                ui.horizontal(|ui| {
+                    ui.spacing_mut().item_spacing.x = margin;
+
+                    let uv = Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                    for id in [
+                        self.backbuffer.id(),
+                        self.gt_index.and(self.gt_backbuffer.id()),
+                    ] {
+                        let (thumb_rect, _) =
+                            ui.allocate_exact_size(thumb_size, egui::Sense::hover());
+                        ui.painter().rect_filled(thumb_rect, 0.0, Color32::BLACK);
+                        if let Some(id) = id {
+                            ui.painter().image(id, thumb_rect, uv, Color32::WHITE);
+                        }
+                        ui.painter().rect_stroke(
+                            thumb_rect,
+                            0.0,
+                            (1.0, Color32::GRAY),
+                            egui::StrokeKind::Outside,
+                        );
+                    }
+                });
+            });
+    }
+}
+
+/// Scale an image down to about the size it's shown at, as an RGBA image. Stays on the GPU.
+fn thumbnail(image: Tensor<InnerBackend, 3>) -> Tensor<InnerBackend, 3> {
+    let [height, width, channels] = image.dims();
+    let scale = (2.0 * THUMB_HEIGHT / height as f32).min(1.0);
+    let size = [
+        ((height as f32 * scale) as usize).max(1),
+        ((width as f32 * scale) as usize).max(1),
+    ];
+    let image = adaptive_avg_pool2d(image.permute([2, 0, 1]).unsqueeze::<4>(), size)
+        .squeeze::<3>(0)
+        .permute([1, 2, 0]);
+
+    // Images without alpha are opaque.
+    if channels == 3 {
+        let alpha = Tensor::ones([size[0], size[1], 1], &image.device());
+        Tensor::cat(vec![image, alpha], 2)
+    } else {
+        image
+    }
+}
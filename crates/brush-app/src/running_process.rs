@@ -1,10 +1,18 @@
 use brush_process::{
     data_source::DataSource,
+    error::ProcessError,
     process_loop::{ProcessArgs, ProcessMessage, process_stream},
 };
 use burn_wgpu::WgpuDevice;
-use tokio::sync::mpsc::{Receiver, UnboundedSender};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use web_time::{Duration, Instant};
+
+use crate::app::PowerSettings;
+use tokio::sync::mpsc::{Receiver, Sender, UnboundedReceiver, UnboundedSender};
 use tokio_stream::StreamExt;
+use tokio_util::sync::CancellationToken;
 use tokio_with_wasm::alias as tokio_wasm;
 
 #[derive(Debug, Clone)]
@@ -13,62 +21,265 @@ pub enum ControlMessage {
 }
 
 pub struct RunningProcess {
+    pub source: DataSource,
     pub start_args: ProcessArgs,
-    pub messages: Receiver<Result<ProcessMessage, anyhow::Error>>,
+    pub messages: Receiver<Result<ProcessMessage, ProcessError>>,
     pub control: UnboundedSender<ControlMessage>,
+    limits: Arc<UpdateLimits>,
+    cancel: CancellationToken,
 }
 
-pub fn start_process(
+// Limits shared with the message pump, so they can be changed while the process runs.
+struct UpdateLimits {
+    // Minimum time between repaint requests in ms, 0 repaints on every message.
+    repaint_interval_ms: AtomicU64,
+    // Max number of train steps per second forwarded to the UI.
+    train_updates_hz: AtomicU32,
+}
+
+impl UpdateLimits {
+    fn train_update_interval(&self) -> Duration {
+        let hz = self.train_updates_hz.load(Ordering::Relaxed).max(1);
+        Duration::from_secs_f32(1.0 / hz as f32)
+    }
+}
+
+impl RunningProcess {
+    pub fn set_power_settings(&self, power: &PowerSettings) {
+        let ms = power.frame_interval().map_or(0, |d| d.as_millis() as u64);
+        self.limits.repaint_interval_ms.store(ms, Ordering::Relaxed);
+        self.limits
+            .train_updates_hz
+            .store(power.ui_update_hz, Ordering::Relaxed);
+    }
+}
+
+impl Drop for RunningProcess {
+    fn drop(&mut self) {
+        // Stop right away, rather than when the process next has a message for the UI. This
+        // drops the process stream, and with it any downloads and file reads in flight.
+        self.cancel.cancel();
+    }
+}
+
+type Message = Result<ProcessMessage, ProcessError>;
+
+fn request_repaint(ctx: &egui::Context, limits: &UpdateLimits) {
+    // Mark egui as needing a repaint. When throttled, egui keeps the earliest
+    // requested repaint, so this caps the repaint rate while messages keep coming in.
+    match limits.repaint_interval_ms.load(Ordering::Relaxed) {
+        0 => ctx.request_repaint(),
+        ms => ctx.request_repaint_after(Duration::from_millis(ms)),
+    }
+}
+
+// Send a message to the UI. Returns false if noone is listening anymore.
+async fn forward(
+    sender: &Sender<Message>,
+    ctx: &egui::Context,
+    limits: &UpdateLimits,
+    msg: Message,
+) -> bool {
+    request_repaint(ctx, limits);
+    sender.send(msg).await.is_ok()
+}
+
+/// Drive the process, handing its messages to the message pump. This runs in a task of its
+/// own, so training never waits on the UI, only on being paused.
+async fn run_process(
     source: DataSource,
     args: ProcessArgs,
     device: WgpuDevice,
-    ctx: egui::Context,
-) -> RunningProcess {
-    let (sender, receiver) = tokio::sync::mpsc::channel(1);
-    let (train_sender, mut train_receiver) = tokio::sync::mpsc::unbounded_channel();
+    messages: UnboundedSender<Pumped>,
+    mut control: UnboundedReceiver<ControlMessage>,
+    cancel: CancellationToken,
+) {
+    let stream = process_stream(source, args, device);
+    let mut stream = std::pin::pin!(stream);
 
-    let args_loop = args.clone();
+    while let Some(Some(msg)) = cancel.run_until_cancelled(stream.next()).await {
+        let is_train_step = matches!(msg, Ok(ProcessMessage::TrainStep { .. }));
 
-    tokio_with_wasm::alias::task::spawn(async move {
-        let stream = process_stream(source, args_loop, device);
-        let mut stream = std::pin::pin!(stream);
-
-        while let Some(msg) = stream.next().await {
-            // Mark egui as needing a repaint.
-            ctx.request_repaint();
-
-            let is_train_step = matches!(msg, Ok(ProcessMessage::TrainStep { .. }));
+        // Stop the process if noone is listening anymore.
+        if messages.send(Pumped::Message(msg)).is_err() {
+            break;
+        }
 
-            // Stop the process if noone is listening anymore.
-            if sender.send(msg).await.is_err() {
+        // Check if training is paused. Don't care about other messages as pausing loading
+        // doesn't make much sense.
+        if is_train_step && matches!(control.try_recv(), Ok(ControlMessage::Paused(true))) {
+            // Show the latest state while paused.
+            if messages.send(Pumped::Paused).is_err() {
                 break;
             }
 
-            // Check if training is paused. Don't care about other messages as pausing loading
-            // doesn't make much sense.
-            if is_train_step
-                && matches!(train_receiver.try_recv(), Ok(ControlMessage::Paused(true)))
-            {
-                // Pause if needed.
-                while !matches!(
-                    train_receiver.recv().await,
-                    Some(ControlMessage::Paused(false))
-                ) {}
+            // Pause if needed.
+            loop {
+                match cancel.run_until_cancelled(control.recv()).await {
+                    Some(Some(ControlMessage::Paused(false))) => break,
+                    Some(Some(ControlMessage::Paused(true))) => {}
+                    // Closed or cancelled, stop the process.
+                    Some(None) | None => return,
+                }
             }
+        }
 
-            // Give back control to the runtime.
-            // This only really matters in the browser:
-            // on native, receiving also yields. In the browser that doesn't yield
-            // back control fully though whereas yield_now() does.
-            if cfg!(target_family = "wasm") {
-                tokio_wasm::task::yield_now().await;
+        // Give back control to the runtime.
+        // This only really matters in the browser:
+        // on native, receiving also yields. In the browser that doesn't yield
+        // back control fully though whereas yield_now() does.
+        if cfg!(target_family = "wasm") {
+            tokio_wasm::task::yield_now().await;
+        }
+    }
+}
+
+/// What the process task hands to the message pump.
+enum Pumped {
+    Message(Message),
+    /// Training was paused, show the latest step right away.
+    Paused,
+}
+
+/// Replace the pending train step with a newer one. Splats are only sent along with some
+/// steps, so keep the latest ones when the step they came with is skipped.
+fn coalesce(pending: Option<Message>, mut step: Message) -> Message {
+    if let (
+        Some(Ok(ProcessMessage::TrainStep {
+            splats: pending_splats,
+            ..
+        })),
+        Ok(ProcessMessage::TrainStep { splats, .. }),
+    ) = (pending, &mut step)
+    {
+        if splats.is_none() {
+            *splats = pending_splats;
+        }
+    }
+    step
+}
+
+/// Queue a train step to be sent to the UI. A step still waiting to be sent is replaced, so
+/// steps don't pile up while the UI is busy.
+fn queue_step(outbox: &mut VecDeque<Message>, step: Message) {
+    let waiting = outbox
+        .iter()
+        .position(|msg| matches!(msg, Ok(ProcessMessage::TrainStep { .. })))
+        .and_then(|i| outbox.remove(i));
+    outbox.push_back(coalesce(waiting, step));
+}
+
+/// Forward messages of the process to the UI, at the rate the UI wants them.
+async fn pump_messages(
+    mut messages: UnboundedReceiver<Pumped>,
+    sender: Sender<Message>,
+    ctx: egui::Context,
+    limits: Arc<UpdateLimits>,
+    cancel: CancellationToken,
+) {
+    // Messages waiting for the UI to take them, in order.
+    let mut outbox = VecDeque::new();
+
+    // Train steps come in much faster than is useful to show. Only the latest
+    // step is kept around until it's time to update the UI again.
+    let mut pending_step = None;
+    let mut last_step_sent: Option<Instant> = None;
+
+    loop {
+        tokio::select! {
+            () = cancel.cancelled() => return,
+            permit = sender.reserve(), if !outbox.is_empty() => {
+                // Stop the process if noone is listening anymore.
+                let Ok(permit) = permit else {
+                    return;
+                };
+                if let Some(msg) = outbox.pop_front() {
+                    request_repaint(&ctx, &limits);
+                    permit.send(msg);
+                }
             }
+            pumped = messages.recv() => match pumped {
+                // The process is done.
+                None => break,
+                Some(Pumped::Paused) => {
+                    if let Some(step) = pending_step.take() {
+                        queue_step(&mut outbox, step);
+                    }
+                }
+                Some(Pumped::Message(msg)) => {
+                    if matches!(msg, Ok(ProcessMessage::TrainStep { .. })) {
+                        pending_step = Some(coalesce(pending_step.take(), msg));
+                        let due = last_step_sent
+                            .is_none_or(|t| t.elapsed() >= limits.train_update_interval());
+                        if let Some(step) = pending_step.take_if(|_| due) {
+                            queue_step(&mut outbox, step);
+                            last_step_sent = Some(Instant::now());
+                        }
+                    } else {
+                        // Keep messages in order, the UI should see the latest step first.
+                        if let Some(step) = pending_step.take() {
+                            queue_step(&mut outbox, step);
+                        }
+                        outbox.push_back(msg);
+                    }
+                }
+            },
+        }
+    }
+
+    if let Some(step) = pending_step {
+        queue_step(&mut outbox, step);
+    }
+    for msg in outbox {
+        match cancel
+            .run_until_cancelled(forward(&sender, &ctx, &limits, msg))
+            .await
+        {
+            Some(true) => {}
+            Some(false) | None => break,
         }
+    }
+}
+
+pub fn start_process(
+    source: DataSource,
+    args: ProcessArgs,
+    device: WgpuDevice,
+    ctx: egui::Context,
+) -> RunningProcess {
+    let (sender, receiver) = tokio::sync::mpsc::channel(1);
+    let (train_sender, train_receiver) = tokio::sync::mpsc::unbounded_channel();
+    let (pump_sender, pump_receiver) = tokio::sync::mpsc::unbounded_channel();
+
+    let default_power = PowerSettings::default();
+    let limits = Arc::new(UpdateLimits {
+        repaint_interval_ms: AtomicU64::new(0),
+        train_updates_hz: AtomicU32::new(default_power.ui_update_hz),
     });
+    let cancel = CancellationToken::new();
+
+    tokio_wasm::task::spawn(run_process(
+        source.clone(),
+        args.clone(),
+        device,
+        pump_sender,
+        train_receiver,
+        cancel.clone(),
+    ));
+    tokio_wasm::task::spawn(pump_messages(
+        pump_receiver,
+        sender,
+        ctx,
+        limits.clone(),
+        cancel.clone(),
+    ));
 
     RunningProcess {
+        source,
         start_args: args,
         messages: receiver,
         control: train_sender,
+        limits,
+        cancel,
     }
 }
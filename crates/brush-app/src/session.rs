@@ -0,0 +1,209 @@
+//! Viewing sessions, where one viewer presents and others follow along.
+//!
+//! Viewers connect to a relay (see `brush_app --session-relay`) that forwards messages
+//! between them. The presenter sends its camera and timeline position, followers apply
+//! whatever they receive.
+
+use ewebsock::{WsEvent, WsMessage, WsReceiver, WsSender};
+use glam::{Quat, Vec3};
+use serde::{Deserialize, Serialize};
+use web_time::{Duration, Instant};
+
+use crate::i18n::tr;
+
+/// Don't flood the relay while the presenter is moving around.
+const SEND_INTERVAL: Duration = Duration::from_millis(33);
+
+/// What followers see of the presenter's viewer.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SessionView {
+    pub position: Vec3,
+    pub rotation: Quat,
+    pub focus_distance: f32,
+    /// Time in the timeline of animated splats, in seconds.
+    pub frame: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum SessionMessage {
+    /// Sent by followers when they join, so the presenter sends its current view.
+    Join,
+    View(SessionView),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionRole {
+    Presenter,
+    Follower,
+}
+
+struct Connection {
+    sender: WsSender,
+    receiver: WsReceiver,
+    open: bool,
+    last_sent: Option<(SessionView, Instant)>,
+}
+
+pub(crate) struct Session {
+    url: String,
+    role: SessionRole,
+    connection: Option<Connection>,
+    error: Option<String>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            url: "ws://localhost:9876".to_owned(),
+            role: SessionRole::Follower,
+            connection: None,
+            error: None,
+        }
+    }
+}
+
+impl Session {
+    pub(crate) fn is_connected(&self) -> bool {
+        self.connection.as_ref().is_some_and(|c| c.open)
+    }
+
+    pub(crate) fn menu_ui(&mut self, ui: &mut egui::Ui) {
+        let connected = self.connection.is_some();
+
+        ui.add_enabled_ui(!connected, |ui| {
+            ui.label(tr("session.relay_url"));
+            ui.add(egui::TextEdit::singleline(&mut self.url).desired_width(250.0))
+                .on_hover_text(tr("session.relay_url_hover"));
+            ui.horizontal(|ui| {
+                ui.radio_value(
+                    &mut self.role,
+                    SessionRole::Presenter,
+                    tr("session.present"),
+                );
+                ui.radio_value(&mut self.role, SessionRole::Follower, tr("session.follow"));
+            });
+        });
+
+        ui.add_space(5.0);
+
+        if connected {
+            if self.is_connected() {
+                ui.label(tr("session.connected"));
+            } else {
+                ui.horizontal(|ui| {
+                    ui.label(tr("session.connecting"));
+                    ui.spinner();
+                });
+            }
+            if ui.button(tr("session.leave")).clicked() {
+                self.connection = None;
+            }
+        } else if ui.button(tr("session.join")).clicked() {
+            let ctx = ui.ctx().clone();
+            let wakeup = move || ctx.request_repaint();
+            match ewebsock::connect_with_wakeup(&self.url, ewebsock::Options::default(), wakeup) {
+                Ok((sender, receiver)) => {
+                    self.connection = Some(Connection {
+                        sender,
+                        receiver,
+                        open: false,
+                        last_sent: None,
+                    });
+                    self.error = None;
+                }
+                Err(e) => self.error = Some(e),
+            }
+        }
+
+        if let Some(error) = &self.error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+    }
+
+    /// Exchange the current view with the session.
+    ///
+    /// As the presenter this sends `view` when it changed. As a follower, returns the
+    /// latest view of the presenter, if a new one came in.
+    pub(crate) fn sync(&mut self, ctx: &egui::Context, view: SessionView) -> Option<SessionView> {
+        let connection = self.connection.as_mut()?;
+
+        let mut received = None;
+        while let Some(event) = connection.receiver.try_recv() {
+            match event {
+                WsEvent::Opened => {
+                    connection.open = true;
+                    if self.role == SessionRole::Follower {
+                        send(&mut connection.sender, &SessionMessage::Join);
+                    }
+                }
+                WsEvent::Message(WsMessage::Text(text)) => {
+                    match serde_json::from_str::<SessionMessage>(&text) {
+                        Ok(SessionMessage::Join) => connection.last_sent = None,
+                        Ok(SessionMessage::View(view)) => received = Some(view),
+                        Err(e) => log::warn!("Invalid session message: {e}"),
+                    }
+                }
+                WsEvent::Message(_) => {}
+                WsEvent::Error(e) => {
+                    log::error!("Session error: {e}");
+                    self.error = Some(e);
+                    self.connection = None;
+                    return None;
+                }
+                WsEvent::Closed => {
+                    self.error = Some(tr("session.closed").to_owned());
+                    self.connection = None;
+                    return None;
+                }
+            }
+        }
+
+        match self.role {
+            SessionRole::Follower => received,
+            SessionRole::Presenter => {
+                if !connection.open {
+                    return None;
+                }
+                match connection.last_sent {
+                    Some((last, _)) if last == view => {}
+                    Some((_, time)) if time.elapsed() < SEND_INTERVAL => {
+                        // Make sure the final view is sent once the presenter stops moving.
+                        ctx.request_repaint_after(SEND_INTERVAL.saturating_sub(time.elapsed()));
+                    }
+                    _ => {
+                        send(&mut connection.sender, &SessionMessage::View(view));
+                        connection.last_sent = Some((view, Instant::now()));
+                    }
+                }
+                None
+            }
+        }
+    }
+}
+
+fn send(sender: &mut WsSender, msg: &SessionMessage) {
+    let text = serde_json::to_string(msg).expect("Session messages are always serializable");
+    sender.send(WsMessage::Text(text));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn view_message_round_trip() {
+        let msg = SessionMessage::View(SessionView {
+            position: Vec3::new(1.0, 2.0, -3.0),
+            rotation: Quat::from_rotation_y(0.5),
+            focus_distance: 4.0,
+            frame: 1.5,
+        });
+        let text = serde_json::to_string(&msg).expect("Serializable");
+        assert!(text.contains("\"type\":\"View\""));
+        assert_eq!(
+            serde_json::from_str::<SessionMessage>(&text).ok(),
+            Some(msg)
+        );
+    }
+}
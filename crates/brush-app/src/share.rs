@@ -0,0 +1,158 @@
+use brush_dataset::splat_export;
+use brush_render::gaussian_splats::Splats;
+use brush_train::train::TrainBack;
+use burn::tensor::backend::AutodiffBackend;
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot::{Receiver, error::TryRecvError};
+
+use crate::{app::AppContext, i18n::tr};
+
+pub(crate) const SHARE_STORAGE_KEY: &str = "brush_share";
+
+/// Where splats are uploaded to, to share them.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShareSettings {
+    /// Endpoint the splat is PUT to. Either a presigned S3 URL, or any server that accepts
+    /// PUT requests and serves the file back from the same URL.
+    pub upload_url: String,
+    /// Web viewer that shared links open.
+    pub viewer_url: String,
+}
+
+impl Default for ShareSettings {
+    fn default() -> Self {
+        Self {
+            upload_url: String::new(),
+            viewer_url: "https://arthurbrussee.github.io/brush-demo/".to_owned(),
+        }
+    }
+}
+
+/// URL an uploaded file can be read back from. That's the upload URL without its
+/// query, which for presigned URLs holds the signature.
+pub fn public_url(upload_url: &str) -> &str {
+    upload_url
+        .split_once('?')
+        .map_or(upload_url, |(base, _)| base)
+}
+
+async fn upload(upload_url: &str, data: Vec<u8>) -> anyhow::Result<()> {
+    reqwest::Client::new()
+        .put(upload_url)
+        .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+        .body(data)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Menu to upload the current splats, and get a viewer link to them.
+#[derive(Default)]
+pub(crate) struct ShareMenu {
+    upload: Option<Receiver<anyhow::Result<String>>>,
+    link: Option<String>,
+    error: Option<String>,
+}
+
+impl ShareMenu {
+    pub(crate) fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        context: &mut AppContext,
+        splats: Splats<<TrainBack as AutodiffBackend>::InnerBackend>,
+    ) {
+        if let Some(receiver) = self.upload.as_mut() {
+            match receiver.try_recv() {
+                Ok(Ok(link)) => {
+                    ui.ctx().copy_text(link.clone());
+                    self.link = Some(link);
+                    self.upload = None;
+                }
+                Ok(Err(e)) => {
+                    log::error!("Failed to share splat: {e}");
+                    self.error = Some(e.to_string());
+                    self.upload = None;
+                }
+                Err(TryRecvError::Closed) => self.upload = None,
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+
+        let mut settings = context.share_settings().clone();
+        ui.label(tr("share.upload_url"));
+        ui.add(
+            egui::TextEdit::singleline(&mut settings.upload_url)
+                .hint_text("https://…")
+                .desired_width(300.0),
+        )
+        .on_hover_text(tr("share.upload_url_hover"));
+        ui.label(tr("share.viewer_url"));
+        ui.add(egui::TextEdit::singleline(&mut settings.viewer_url).desired_width(300.0));
+        if &settings != context.share_settings() {
+            context.set_share_settings(settings.clone());
+        }
+
+        ui.add_space(5.0);
+
+        if self.upload.is_some() {
+            ui.horizontal(|ui| {
+                ui.label(tr("share.uploading"));
+                ui.spinner();
+            });
+        } else if ui
+            .add_enabled(
+                !settings.upload_url.is_empty(),
+                egui::Button::new(tr("share.upload")),
+            )
+            .clicked()
+        {
+            let query = context.export_query(public_url(&settings.upload_url));
+            let splats = context.splats_in_world(splats);
+            let link = format!("{}{query}", settings.viewer_url);
+            let (sender, receiver) = tokio::sync::oneshot::channel();
+            let ctx = ui.ctx().clone();
+            tokio_with_wasm::alias::task::spawn(async move {
+                let result = async {
+                    let data = splat_export::splat_to_ply(splats).await?;
+                    upload(&settings.upload_url, data).await?;
+                    Ok::<_, anyhow::Error>(link)
+                }
+                .await;
+                let _ = sender.send(result);
+                ctx.request_repaint();
+            });
+            self.upload = Some(receiver);
+            self.link = None;
+            self.error = None;
+        }
+
+        if let Some(link) = &self.link {
+            ui.label(tr("share.copied"));
+            ui.hyperlink(link);
+        }
+        if let Some(error) = &self.error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::public_url;
+
+    #[test]
+    fn presigned_signature_is_stripped() {
+        assert_eq!(
+            public_url(
+                "https://bucket.s3.amazonaws.com/scene.ply?X-Amz-Signature=abc&X-Amz-Expires=60"
+            ),
+            "https://bucket.s3.amazonaws.com/scene.ply"
+        );
+        assert_eq!(
+            public_url("https://example.com/uploads/scene.ply"),
+            "https://example.com/uploads/scene.ply"
+        );
+    }
+}
@@ -0,0 +1,203 @@
+//! Datasets kept by the app, to train on again without picking them each time.
+
+use std::path::Path;
+
+use brush_process::data_source::DataSource;
+
+/// A dataset in storage.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct StoredDataset {
+    pub name: String,
+    /// Size on disk, in bytes.
+    pub size: u64,
+}
+
+pub(crate) trait DatasetStorage: Send + Sync {
+    /// Where the datasets are kept, to show to the user.
+    fn location(&self) -> String;
+
+    /// All stored datasets, sorted by name.
+    fn list(&self) -> anyhow::Result<Vec<StoredDataset>>;
+
+    /// Store a dataset file, eg. a zip, under `name`.
+    fn import_file(&self, name: &str, data: &[u8]) -> anyhow::Result<()>;
+
+    /// Store a copy of a dataset folder.
+    fn import_dir(&self, dir: &Path) -> anyhow::Result<()>;
+
+    fn delete(&self, name: &str) -> anyhow::Result<()>;
+
+    /// Source to load a stored dataset from.
+    fn source(&self, name: &str) -> DataSource;
+}
+
+/// The storage for this platform, if it has one.
+#[cfg(not(target_family = "wasm"))]
+pub(crate) fn default_storage() -> Option<Box<dyn DatasetStorage>> {
+    let root = eframe::storage_dir("Brush")?.join("datasets");
+    Some(Box::new(native::DirStorage { root }))
+}
+
+// There's no storage backend for the browser yet.
+#[cfg(target_family = "wasm")]
+pub(crate) fn default_storage() -> Option<Box<dyn DatasetStorage>> {
+    None
+}
+
+/// Sizes in a human readable unit, eg. "12.3 MB".
+pub(crate) fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+#[cfg(not(target_family = "wasm"))]
+mod native {
+    use std::path::{Path, PathBuf};
+
+    use brush_process::data_source::DataSource;
+
+    use super::{DatasetStorage, StoredDataset};
+
+    /// Datasets as files and folders in a folder on disk.
+    pub(super) struct DirStorage {
+        pub(super) root: PathBuf,
+    }
+
+    impl DirStorage {
+        fn path(&self, name: &str) -> anyhow::Result<PathBuf> {
+            // Names come from file names, but don't let them point outside of the storage.
+            anyhow::ensure!(
+                !name.is_empty() && Path::new(name).file_name() == Some(name.as_ref()),
+                "Invalid dataset name {name}"
+            );
+            Ok(self.root.join(name))
+        }
+
+        /// Name for a new dataset that doesn't clash with the stored ones.
+        fn free_name(&self, name: &str) -> anyhow::Result<String> {
+            let path = Path::new(name);
+            let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(name);
+            let ext = path.extension().and_then(|s| s.to_str());
+            (0..)
+                .map(|i| {
+                    let stem = if i == 0 {
+                        stem.to_owned()
+                    } else {
+                        format!("{stem}_{i}")
+                    };
+                    ext.map_or(stem.clone(), |ext| format!("{stem}.{ext}"))
+                })
+                .find(|n| !self.root.join(n).exists())
+                .ok_or_else(|| anyhow::anyhow!("Ran out of dataset names"))
+        }
+    }
+
+    fn dir_size(path: &Path) -> std::io::Result<u64> {
+        let meta = std::fs::metadata(path)?;
+        if !meta.is_dir() {
+            return Ok(meta.len());
+        }
+        std::fs::read_dir(path)?.try_fold(0, |total, entry| Ok(total + dir_size(&entry?.path())?))
+    }
+
+    fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            let target = to.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                copy_dir(&entry.path(), &target)?;
+            } else {
+                std::fs::copy(entry.path(), target)?;
+            }
+        }
+        Ok(())
+    }
+
+    impl DatasetStorage for DirStorage {
+        fn location(&self) -> String {
+            self.root.display().to_string()
+        }
+
+        fn list(&self) -> anyhow::Result<Vec<StoredDataset>> {
+            if !self.root.exists() {
+                return Ok(vec![]);
+            }
+            let mut datasets = std::fs::read_dir(&self.root)?
+                .map(|entry| {
+                    let entry = entry?;
+                    Ok(StoredDataset {
+                        name: entry.file_name().to_string_lossy().into_owned(),
+                        size: dir_size(&entry.path())?,
+                    })
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            datasets.sort_by(|a, b| a.name.cmp(&b.name));
+            Ok(datasets)
+        }
+
+        fn import_file(&self, name: &str, data: &[u8]) -> anyhow::Result<()> {
+            std::fs::create_dir_all(&self.root)?;
+            let path = self.path(&self.free_name(name)?)?;
+            std::fs::write(path, data)?;
+            Ok(())
+        }
+
+        fn import_dir(&self, dir: &Path) -> anyhow::Result<()> {
+            std::fs::create_dir_all(&self.root)?;
+            let name = dir
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("dataset");
+
+            // Nerfstudio style folders become a single zip, anything else is copied as is.
+            if dir.join(brush_dataset::archive::TRANSFORMS_FILE).is_file() {
+                let path = self.path(&self.free_name(&format!("{name}.zip"))?)?;
+                let file = std::fs::File::create(&path)?;
+                if let Err(e) = brush_dataset::archive::pack_dir(dir, file) {
+                    let _ = std::fs::remove_file(&path);
+                    return Err(e);
+                }
+            } else {
+                let path = self.path(&self.free_name(name)?)?;
+                copy_dir(dir, &path)?;
+            }
+            Ok(())
+        }
+
+        fn delete(&self, name: &str) -> anyhow::Result<()> {
+            let path = self.path(name)?;
+            if path.is_dir() {
+                std::fs::remove_dir_all(path)?;
+            } else {
+                std::fs::remove_file(path)?;
+            }
+            Ok(())
+        }
+
+        fn source(&self, name: &str) -> DataSource {
+            DataSource::Path(self.root.join(name).to_string_lossy().into_owned())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_bytes;
+
+    #[test]
+    fn formats_sizes() {
+        assert_eq!(format_bytes(999), "999 B");
+        assert_eq!(format_bytes(12_300_000), "12.3 MB");
+        assert_eq!(format_bytes(4_500_000_000_000_000), "4500.0 TB");
+    }
+}
@@ -0,0 +1,125 @@
+use std::io::{Cursor, Write};
+
+use anyhow::Context;
+use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+/// Name of the splat file in an exported web page.
+pub const SCENE_FILE: &str = "scene.ply";
+
+/// Files of a web build of the viewer, as output by trunk.
+const VIEWER_FILES: [&str; 10] = [
+    "index.html",
+    "brush_app.js",
+    "brush_app_bg.wasm",
+    "sw.js",
+    "manifest.json",
+    "favicon.ico",
+    "icon-1024.png",
+    "icon-256.png",
+    "icon_ios_touch_192.png",
+    "maskable_icon_x512.png",
+];
+
+/// Make the viewer page work from any folder, and open `query` when loaded without one.
+fn prepare_index(html: &str, query: &str) -> anyhow::Result<String> {
+    // Trunk bakes in the public URL the viewer was built for, point it at the page's folder.
+    let html = match html.find("<base href=\"") {
+        Some(start) => {
+            let value_start = start + "<base href=\"".len();
+            let value_end = value_start
+                + html[value_start..]
+                    .find('"')
+                    .context("Unterminated base tag")?;
+            format!("{}./{}", &html[..value_start], &html[value_end..])
+        }
+        None => html.to_owned(),
+    };
+
+    // The viewer reads its settings from the search params on startup, fill in the
+    // defaults before it loads.
+    let head = html.find("<head>").context("Not a viewer index.html")? + "<head>".len();
+    let script = format!(
+        "\n    <script>if (!location.search) history.replaceState(null, \"\", location.pathname + \"{query}\");</script>"
+    );
+    Ok(format!("{}{script}{}", &html[..head], &html[head..]))
+}
+
+/// Read the files of the web viewer.
+async fn read_viewer_files() -> anyhow::Result<Vec<(&'static str, Vec<u8>)>> {
+    let mut files = vec![];
+
+    #[cfg(target_family = "wasm")]
+    {
+        // Already running in the browser, use the files this page was loaded from.
+        let location = web_sys::window().context("No window")?.location();
+        let origin = location
+            .origin()
+            .map_err(|_| anyhow::anyhow!("No origin"))?;
+        let path = location
+            .pathname()
+            .map_err(|_| anyhow::anyhow!("No path"))?;
+        let dir = &path[..path.rfind('/').map_or(0, |i| i + 1)];
+
+        for name in VIEWER_FILES {
+            let data = reqwest::get(format!("{origin}{dir}{name}"))
+                .await?
+                .error_for_status()?
+                .bytes()
+                .await?;
+            files.push((name, data.to_vec()));
+        }
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    {
+        // Needs a web build of the viewer, eg. the output of `trunk build --release`.
+        let dir = rrfd::pick_directory().await?;
+        for name in VIEWER_FILES {
+            let data = std::fs::read(dir.join(name)).with_context(|| {
+                format!("{name} not found, pick the folder of a web build of Brush")
+            })?;
+            files.push((name, data));
+        }
+    }
+
+    Ok(files)
+}
+
+/// Bundle the web viewer and a splat into a zip, that can be put on any static host.
+///
+/// Opening the page shows the splat with the view and notes of `query`.
+pub async fn export_web_page(splat_ply: Vec<u8>, query: &str) -> anyhow::Result<Vec<u8>> {
+    let viewer_files = read_viewer_files().await?;
+
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for (name, data) in viewer_files {
+        zip.start_file(name, options)?;
+        if name == "index.html" {
+            let html = String::from_utf8(data).context("Invalid index.html")?;
+            zip.write_all(prepare_index(&html, query)?.as_bytes())?;
+        } else {
+            zip.write_all(&data)?;
+        }
+    }
+
+    zip.start_file(SCENE_FILE, options)?;
+    zip.write_all(&splat_ply)?;
+
+    Ok(zip.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::prepare_index;
+
+    #[test]
+    fn index_is_relocated() {
+        let html = "<html>\n<head>\n    <base href=\"/brush-demo/\" />\n</head>\n</html>";
+        let out = prepare_index(html, "?url=.%2Fscene.ply").expect("Valid index");
+        assert!(out.contains("<base href=\"./\" />"));
+        assert!(out.contains("location.pathname + \"?url=.%2Fscene.ply\""));
+        assert!(out.find("<script>") < out.find("<base"));
+    }
+}
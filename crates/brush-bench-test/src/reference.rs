@@ -13,7 +13,7 @@ use burn::{
 use safetensors::SafeTensors;
 use std::{fs::File, io::Read};
 
-use crate::safetensor_utils::{safetensor_to_burn, splats_from_safetensors};
+use crate::safetensor_utils::safetensor_to_burn;
 
 type DiffBack = Autodiff<Wgpu>;
 
@@ -103,7 +103,7 @@ async fn test_reference() -> Result<()> {
         let _ = File::open(format!("./test_cases/{path}.safetensors"))?.read_to_end(&mut buffer)?;
 
         let tensors = SafeTensors::deserialize(&buffer)?;
-        let splats: Splats<DiffBack> = splats_from_safetensors(&tensors, &device)?;
+        let splats: Splats<DiffBack> = Splats::from_safetensors(&buffer, &device)?;
 
         let img_ref = safetensor_to_burn::<DiffBack, 3>(&tensors.tensor("out_img")?, &device);
         let [h, w, _] = img_ref.dims();
@@ -1,8 +1,6 @@
 #![allow(clippy::single_range_in_vec_init)]
 #![recursion_limit = "256"]
 
-mod safetensor_utils;
-
 use std::collections::HashMap;
 use std::path::Path;
 use std::{fs::File, io::Read};
@@ -16,8 +14,6 @@ use burn::backend::wgpu::WgpuDevice;
 use burn::backend::{Autodiff, Wgpu};
 use burn::module::AutodiffModule;
 use burn::tensor::{Tensor, TensorPrimitive};
-use safetensor_utils::splats_from_safetensors;
-use safetensors::SafeTensors;
 
 fn main() {
     divan::main();
@@ -146,9 +142,8 @@ fn bench_general(
         .expect("Failed to open bench data")
         .read_to_end(&mut buffer)
         .expect("Failed to read bench data");
-    let tensors = SafeTensors::deserialize(&buffer).expect("Failed to deserialize bench data");
     let splats: Splats<DiffBack> =
-        splats_from_safetensors(&tensors, &device).expect("Failed to load bench data");
+        Splats::from_safetensors(&buffer, &device).expect("Failed to load bench data");
     let num_points = (splats.num_splats() as f32 * dens) as usize;
     let splats = Splats::from_tensor_data(
         (splats.means.val() * mean_mult).slice([0..num_points]),
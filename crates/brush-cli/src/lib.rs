@@ -1,8 +1,14 @@
 #![recursion_limit = "256"]
 
+pub mod render;
+pub mod session_relay;
 pub mod ui;
+pub mod validate;
+
+use std::path::PathBuf;
 
 use brush_process::{data_source::DataSource, process_loop::ProcessArgs};
+use brush_render::post_process::PostProcess;
 use clap::{Error, Parser, builder::ArgPredicate, error::ErrorKind};
 
 #[derive(Parser)]
@@ -25,11 +31,83 @@ pub struct Cli {
     )]
     pub with_viewer: bool,
 
+    /// Render a 360° equirectangular image of the splats to this file, instead of viewing them.
+    #[arg(long, value_name = "IMAGE", help_heading = "Render options")]
+    pub render_equirect: Option<PathBuf>,
+
+    /// Width of the rendered 360° image. The height is half of this.
+    #[arg(long, help_heading = "Render options", default_value = "4096")]
+    pub render_width: u32,
+
+    /// Position to render the 360° image from, as x,y,z.
+    #[arg(
+        long,
+        help_heading = "Render options",
+        default_value = "0,0,0",
+        value_parser = render::parse_vec3,
+        allow_hyphen_values = true
+    )]
+    pub render_position: glam::Vec3,
+
+    /// Exposure adjustment of the render, in stops.
+    #[arg(
+        long,
+        help_heading = "Render options",
+        default_value = "0.0",
+        allow_hyphen_values = true
+    )]
+    pub render_exposure: f32,
+
+    /// Strength of the glow around bright areas, 0 disables bloom.
+    #[arg(long, help_heading = "Render options", default_value = "0.0")]
+    pub render_bloom: f32,
+
+    /// How much the corners of the render are darkened, 0 disables the vignette.
+    #[arg(long, help_heading = "Render options", default_value = "0.0")]
+    pub render_vignette: f32,
+
+    /// Map the render colors with the ACES filmic curve.
+    #[arg(long, help_heading = "Render options", default_value = "false")]
+    pub render_tonemap: bool,
+
+    /// Run a relay for viewing sessions on this port, instead of viewing or training.
+    ///
+    /// Viewers connect to it at ws://<host>:<PORT>, one presents and the others follow.
+    #[arg(long, value_name = "PORT")]
+    pub session_relay: Option<u16>,
+
+    /// Check a .ply file for broken splats, instead of viewing or training.
+    ///
+    /// Reports NaNs, out of range opacities, degenerate scales and rotations, duplicate
+    /// splats, and incomplete SH coefficients.
+    #[arg(long, value_name = "PLY", help_heading = "Validation options")]
+    pub validate: Option<PathBuf>,
+
+    /// Write a fixed copy of the validated file as <name>_fixed.ply, with broken splats
+    /// removed and out of range values clamped.
+    #[arg(long, help_heading = "Validation options", requires = "validate")]
+    pub fix: bool,
+
+    /// Present viewer frames as soon as they're rendered, eg. to benchmark the frame rate.
+    #[arg(long, help_heading = "Viewer options")]
+    pub no_vsync: bool,
+
     #[clap(flatten)]
     pub process: ProcessArgs,
 }
 
 impl Cli {
+    /// Post-processing to apply to renders.
+    pub fn render_post_process(&self) -> PostProcess {
+        PostProcess {
+            exposure: self.render_exposure,
+            bloom: self.render_bloom,
+            vignette: self.render_vignette,
+            tonemap: self.render_tonemap,
+            ..Default::default()
+        }
+    }
+
     pub fn validate(self) -> Result<Self, Error> {
         if !self.with_viewer && self.source.is_none() {
             return Err(Error::raw(
@@ -37,6 +115,12 @@ impl Cli {
                 "When --with-viewer is false, --source must be provided",
             ));
         }
+        if self.render_equirect.is_some() && self.source.is_none() {
+            return Err(Error::raw(
+                ErrorKind::MissingRequiredArgument,
+                "When --render-equirect is set, a source must be provided",
+            ));
+        }
         Ok(self)
     }
 }
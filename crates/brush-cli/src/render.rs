@@ -0,0 +1,70 @@
+use std::path::Path;
+
+use brush_dataset::pano::render_equirect;
+use brush_process::{
+    data_source::DataSource,
+    process_loop::{ProcessArgs, ProcessMessage, process_stream},
+};
+use brush_render::{camera::Camera, post_process::PostProcess};
+use burn_wgpu::WgpuDevice;
+use glam::{Quat, Vec3};
+use tokio_stream::StreamExt;
+
+/// Parse a position given as `x,y,z`.
+pub fn parse_vec3(s: &str) -> Result<Vec3, String> {
+    let values: Vec<f32> = s
+        .split(',')
+        .map(|v| v.trim().parse::<f32>().map_err(|e| e.to_string()))
+        .collect::<Result<_, _>>()?;
+    let [x, y, z] = values[..] else {
+        return Err(format!(
+            "Expected 3 comma separated values, got {}",
+            values.len()
+        ));
+    };
+    Ok(Vec3::new(x, y, z))
+}
+
+/// Load a splat file and render a 360° equirectangular image of it to `out_path`.
+pub async fn render_equirect_to_file(
+    source: DataSource,
+    process_args: ProcessArgs,
+    device: WgpuDevice,
+    position: Vec3,
+    width: u32,
+    post_process: &PostProcess,
+    out_path: &Path,
+) -> anyhow::Result<()> {
+    let stream = process_stream(source, process_args, device);
+    let mut stream = std::pin::pin!(stream);
+
+    let mut last_splats = None;
+    while let Some(msg) = stream.next().await {
+        match msg? {
+            ProcessMessage::StartLoading { training: true } => {
+                anyhow::bail!("Rendering needs a .ply file, not a dataset.");
+            }
+            ProcessMessage::ViewSplats {
+                up_axis, splats, ..
+            } => {
+                last_splats = Some((up_axis, splats));
+            }
+            _ => {}
+        }
+    }
+
+    let Some((up_axis, splats)) = last_splats else {
+        anyhow::bail!("No splats found to render.");
+    };
+
+    // Keep the horizon level: the camera looks down +Z with -Y up.
+    let rotation = up_axis.map_or(Quat::IDENTITY, |up| {
+        Quat::from_rotation_arc(Vec3::NEG_Y, up.normalize())
+    });
+    let camera = Camera::new(position, rotation, 0.5, 0.5, glam::vec2(0.5, 0.5));
+
+    log::info!("Rendering {width}px 360° image to {out_path:?}");
+    let image = render_equirect(&splats, &camera, width, post_process).await?;
+    image.into_rgba8().save(out_path)?;
+    Ok(())
+}
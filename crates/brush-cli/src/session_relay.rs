@@ -0,0 +1,68 @@
+use std::net::SocketAddr;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::broadcast::{self, error::RecvError},
+};
+use tokio_tungstenite::tungstenite::Message;
+
+/// Run a relay for viewing sessions on `port`.
+///
+/// Every message a client sends is forwarded to all other connected clients. The relay
+/// doesn't look at the messages, presenting and following is up to the viewers.
+pub async fn run_relay(port: u16) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+    log::info!("Session relay listening on ws://{}", listener.local_addr()?);
+
+    let (sender, _) = broadcast::channel(64);
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            if let Err(e) = relay_client(stream, addr, sender).await {
+                log::warn!("Session client {addr} disconnected: {e}");
+            }
+        });
+    }
+}
+
+async fn relay_client(
+    stream: TcpStream,
+    addr: SocketAddr,
+    sender: broadcast::Sender<(SocketAddr, Message)>,
+) -> anyhow::Result<()> {
+    let socket = tokio_tungstenite::accept_async(stream).await?;
+    log::info!("Session client {addr} connected");
+
+    let (mut write, mut read) = socket.split();
+    let mut others = sender.subscribe();
+
+    loop {
+        tokio::select! {
+            msg = read.next() => {
+                match msg {
+                    Some(Ok(msg @ (Message::Text(_) | Message::Binary(_)))) => {
+                        // Fails only when nobody else is connected, which is fine.
+                        let _ = sender.send((addr, msg));
+                    }
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => return Err(e.into()),
+                }
+            }
+            msg = others.recv() => {
+                match msg {
+                    Ok((from, msg)) if from != addr => write.send(msg).await?,
+                    Ok(_) => {}
+                    // Followers only care about the latest view, skipping some is fine.
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        }
+    }
+
+    log::info!("Session client {addr} left");
+    Ok(())
+}
@@ -4,15 +4,39 @@ use brush_process::{
     data_source::DataSource,
     process_loop::{ProcessArgs, ProcessMessage, process_stream},
 };
+use brush_train::memory;
 use burn_wgpu::WgpuDevice;
 use indicatif::{ProgressBar, ProgressStyle};
 use tokio_stream::StreamExt;
 
+/// Warn before training starts when the limits are predicted to need more memory than the
+/// device has, and suggest limits that should fit.
+fn warn_over_memory_budget(args: &ProcessArgs, budget: u64) {
+    let sh_degree = args.model_config.sh_degree;
+    let max_resolution = args.load_config.max_resolution;
+    let estimate =
+        memory::estimate_train_bytes(args.train_config.max_splats, sh_degree, max_resolution);
+    if estimate <= budget {
+        return;
+    }
+    let suggestion = memory::suggest_limits(budget, sh_degree, max_resolution);
+    log::warn!(
+        "Training with these limits is predicted to need {:.1} GB of GPU memory, but only about {:.1} GB is available. Try --max-splats {} --max-resolution {}.",
+        estimate as f64 / 1e9,
+        budget as f64 / 1e9,
+        suggestion.max_splats,
+        suggestion.max_resolution,
+    );
+}
+
 pub async fn process_ui(
     source: DataSource,
     process_args: ProcessArgs,
     device: WgpuDevice,
+    memory_budget: u64,
 ) -> Result<(), anyhow::Error> {
+    warn_over_memory_budget(&process_args, memory_budget);
+
     let main_spinner = ProgressBar::new_spinner().with_style(
         ProgressStyle::with_template("{spinner:.blue} {msg}")
             .expect("Invalid indacitif config")
@@ -69,10 +93,11 @@ pub async fn process_ui(
     let stats_spinner = sp.add(stats_spinner);
 
     main_spinner.enable_steady_tick(Duration::from_millis(120));
-    eval_spinner.set_message(format!(
-        "evaluating every {} steps",
-        process_args.process_config.eval_every,
-    ));
+    let eval_when = match &process_args.process_config.eval_schedule {
+        Some(schedule) => format!("on schedule {schedule}"),
+        None => format!("every {} steps", process_args.process_config.eval_every),
+    };
+    eval_spinner.set_message(format!("evaluating {eval_when}"));
     stats_spinner.set_message("Starting up");
 
     if cfg!(debug_assertions) {
@@ -92,7 +117,10 @@ pub async fn process_ui(
             Err(error) => {
                 // Don't print the error here. It'll bubble up and be printed as output.
                 let _ = sp.println("❌ Encountered an error");
-                return Err(error);
+                if let Some(suggestion) = error.suggestion() {
+                    let _ = sp.println(format!("💡 {}", suggestion.text()));
+                }
+                return Err(error.into());
             }
         };
 
@@ -108,7 +136,7 @@ pub async fn process_ui(
                 }
                 main_spinner.set_message("Loading data...");
             }
-            ProcessMessage::ViewSplats { .. } => {
+            ProcessMessage::ViewSplats { .. } | ProcessMessage::ViewChunks { .. } => {
                 // I guess we're already showing a warning.
             }
             ProcessMessage::Dataset { dataset } => {
@@ -119,13 +147,30 @@ pub async fn process_ui(
                     "Loading data... {train_views} training, {eval_views} eval views",
                 ));
                 if let Some(val) = dataset.eval.as_ref() {
-                    eval_spinner.set_message(format!(
-                        "evaluating {} views every {} steps",
-                        val.views.len(),
-                        process_args.process_config.eval_every,
-                    ));
+                    eval_spinner
+                        .set_message(format!("evaluating {} views {eval_when}", val.views.len(),));
                 }
             }
+            ProcessMessage::LoadProgress {
+                loaded_bytes,
+                total_bytes,
+                ..
+            } => {
+                let mb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+                main_spinner.set_message(match total_bytes {
+                    Some(total) => format!(
+                        "Loading data... {:.1} / {:.1} MB",
+                        mb(loaded_bytes),
+                        mb(total)
+                    ),
+                    None => format!("Loading data... {:.1} MB", mb(loaded_bytes)),
+                });
+            }
+            ProcessMessage::SplatsTruncated { loaded, skipped } => {
+                let _ = sp.println(format!(
+                    "⚠️ The ply file is cut short, showing {loaded} splats, {skipped} more couldn't be read"
+                ));
+            }
             ProcessMessage::DoneLoading { .. } => {
                 log::info!("Dataset loaded.");
                 main_spinner.set_message("Dataset loaded");
@@ -149,6 +194,15 @@ pub async fn process_ui(
                 //
                 log::info!("Refine iter {iter}, {cur_splat_count} splats.");
             }
+            ProcessMessage::EvalAvailable { .. }
+            | ProcessMessage::Appearances { .. }
+            | ProcessMessage::EnvLight { .. } => {}
+            ProcessMessage::TrainChunks { count, .. } => {
+                let _ = sp.println(format!("🧩 Training in {count} chunks"));
+            }
+            ProcessMessage::RunDir { path } => {
+                let _ = sp.println(format!("📁 Writing output to {}", path.display()));
+            }
             ProcessMessage::EvalResult {
                 iter,
                 avg_psnr,
@@ -0,0 +1,34 @@
+use std::path::Path;
+
+use brush_dataset::{
+    splat_export::{estimate_splat_up, splat_to_ply_oriented},
+    splat_validate::validate_ply,
+};
+use brush_render::gaussian_splats::Splats;
+use burn_wgpu::Wgpu;
+
+/// Check a .ply file for broken splats and print what's wrong. With `fix`, the fixed splats
+/// are written next to it as `<name>_fixed.ply`.
+pub async fn validate_file(
+    path: &Path,
+    fix: bool,
+    max_anisotropy: Option<f32>,
+) -> anyhow::Result<()> {
+    let file = tokio::fs::File::open(path).await?;
+    let (report, fixed) = validate_ply(file, max_anisotropy).await?;
+    print!("{}: {report}", path.display());
+
+    if !fix || report.is_valid() {
+        return Ok(());
+    }
+
+    let device = brush_render::burn_init_setup().await;
+    let splats: Splats<Wgpu> = fixed.into_splats(&device);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let out_path = path.with_file_name(format!("{stem}_fixed.ply"));
+    // There are no cameras to tell which way is up, so guess it from the splats.
+    let up = estimate_splat_up(&splats).await?;
+    tokio::fs::write(&out_path, splat_to_ply_oriented(splats, up, false).await?).await?;
+    println!("Wrote fixed splats to {}", out_path.display());
+    Ok(())
+}
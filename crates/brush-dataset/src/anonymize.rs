@@ -0,0 +1,147 @@
+//! Exporting a dataset with the masked parts of its images blurred, so it can be shared.
+//!
+//! Faces and license plates have to be masked first, with mask images or motion masks. The
+//! masks are exported along with the images, so training on the export still leaves the
+//! blurred parts out.
+
+use std::io::{Cursor, Seek, Write};
+
+use anyhow::Result;
+use image::{
+    ColorType, DynamicImage, GrayImage, ImageFormat, RgbImage, RgbaImage,
+    codecs::jpeg::JpegEncoder,
+    imageops::{self, FilterType},
+};
+
+use crate::{Dataset, capture::CaptureWriter, hdr::linear_to_srgb};
+
+/// Masked parts are blurred down to this fraction of the image size.
+const BLUR_SCALE: u32 = 32;
+const JPEG_QUALITY: u8 = 90;
+
+/// Blur the parts of `img` where `mask` is below half. The image is scaled down to a few
+/// pixels across and back up there, so no detail is left to recover.
+pub fn blur_masked(img: &RgbImage, mask: &GrayImage) -> RgbImage {
+    let (width, height) = img.dimensions();
+    let small = imageops::resize(
+        img,
+        (width / BLUR_SCALE).max(1),
+        (height / BLUR_SCALE).max(1),
+        FilterType::Triangle,
+    );
+    let blurred = imageops::resize(&small, width, height, FilterType::Triangle);
+    RgbImage::from_fn(width, height, |x, y| {
+        if mask.get_pixel(x, y)[0] < 128 {
+            *blurred.get_pixel(x, y)
+        } else {
+            *img.get_pixel(x, y)
+        }
+    })
+}
+
+/// The colors of a loaded image as 8 bit sRGB, and its alpha. Linear images are encoded
+/// back to sRGB.
+fn to_srgb8(img: DynamicImage) -> (RgbImage, GrayImage) {
+    let linear = matches!(img.color(), ColorType::Rgb32F | ColorType::Rgba32F);
+    let img = img.into_rgba32f();
+    let to_byte = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let rgb = RgbImage::from_fn(img.width(), img.height(), |x, y| {
+        let pixel = img.get_pixel(x, y);
+        image::Rgb(std::array::from_fn(|c| {
+            let v = pixel[c].clamp(0.0, 1.0);
+            to_byte(if linear { linear_to_srgb(v) } else { v })
+        }))
+    });
+    let alpha = GrayImage::from_fn(img.width(), img.height(), |x, y| {
+        image::Luma([to_byte(img.get_pixel(x, y)[3])])
+    });
+    (rgb, alpha)
+}
+
+fn encode_png(img: &DynamicImage) -> Result<Vec<u8>> {
+    let mut data = vec![];
+    img.write_to(&mut Cursor::new(&mut data), ImageFormat::Png)?;
+    Ok(data)
+}
+
+fn encode_jpeg(img: &RgbImage) -> Result<Vec<u8>> {
+    let mut data = vec![];
+    JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY).encode_image(img)?;
+    Ok(data)
+}
+
+/// Write all views of the dataset to a dataset zip, see [`crate::archive`], with the masked
+/// parts of the images blurred. Images are written as they're trained on, at the max
+/// resolution, and the eval views are written as training views.
+pub async fn export_anonymized<W: Write + Seek>(dataset: &Dataset, writer: W) -> Result<W> {
+    let mut capture = CaptureWriter::new(writer);
+
+    let eval_views = dataset.eval.iter().flat_map(|e| e.views.iter());
+    for view in dataset.train.views.iter().chain(eval_views) {
+        let img = view.image.load().await?;
+        let has_alpha = img.color().has_alpha();
+        let size = glam::uvec2(img.width(), img.height());
+        let (rgb, alpha) = to_srgb8(img);
+
+        let (data, extension) = if view.image.is_masked() {
+            (encode_jpeg(&blur_masked(&rgb, &alpha))?, "jpg")
+        } else if has_alpha {
+            // Transparent images keep their alpha, it's part of what's trained on.
+            let rgba = RgbaImage::from_fn(size.x, size.y, |x, y| {
+                let [r, g, b] = rgb.get_pixel(x, y).0;
+                image::Rgba([r, g, b, alpha.get_pixel(x, y)[0]])
+            });
+            (encode_png(&DynamicImage::ImageRgba8(rgba))?, "png")
+        } else {
+            (encode_jpeg(&rgb)?, "jpg")
+        };
+
+        // Back to the OpenGL convention of the transforms file.
+        let mut cam_to_world = glam::Mat4::from(view.camera.local_to_world());
+        cam_to_world.y_axis *= -1.0;
+        cam_to_world.z_axis *= -1.0;
+
+        capture.add_frame(
+            &data,
+            extension,
+            cam_to_world,
+            view.camera.focal(size),
+            view.camera.center(size),
+            size,
+        )?;
+        if view.image.is_masked() {
+            capture.add_mask(&encode_png(&DynamicImage::ImageLuma8(alpha))?, "png")?;
+        }
+    }
+
+    log::info!("Exported {} anonymized images", capture.frame_count());
+    capture.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GrayImage, Luma, Rgb, RgbImage};
+
+    use super::blur_masked;
+
+    #[test]
+    fn only_masked_parts_are_blurred() {
+        let img = RgbImage::from_fn(128, 64, |x, y| {
+            if (x + y) % 2 == 0 {
+                Rgb([255; 3])
+            } else {
+                Rgb([0; 3])
+            }
+        });
+        let mask = GrayImage::from_fn(128, 64, |x, _| Luma([if x < 64 { 0 } else { 255 }]));
+
+        let blurred = blur_masked(&img, &mask);
+        for (x, y, pixel) in blurred.enumerate_pixels() {
+            if x < 48 {
+                assert!(pixel[0].abs_diff(128) < 16, "Masked detail is gone");
+            } else if x >= 64 {
+                assert_eq!(pixel, img.get_pixel(x, y), "Unmasked pixels are kept");
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Brush dataset zips.
+//!
+//! A canonical Brush zip has a nerfstudio `transforms.json` at the root, with the images and
+//! masks it refers to next to it. Images are stored as is, as they're compressed already,
+//! everything else is deflated.
+
+use std::{
+    io::{Read, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use zip::{CompressionMethod, ZipArchive, ZipWriter, write::SimpleFileOptions};
+
+use crate::scene::is_image_path;
+
+pub const TRANSFORMS_FILE: &str = "transforms.json";
+
+/// Zip options for a file of a dataset.
+pub fn entry_options(path: &Path) -> SimpleFileOptions {
+    let method = if is_image_path(path) {
+        CompressionMethod::Stored
+    } else {
+        CompressionMethod::Deflated
+    };
+    SimpleFileOptions::default().compression_method(method)
+}
+
+/// Package a folder with a `transforms.json` into a dataset zip, with all images and masks in
+/// the folder. Other files are left out.
+pub fn pack_dir<W: Write + Seek>(dir: &Path, writer: W) -> Result<W> {
+    anyhow::ensure!(
+        dir.join(TRANSFORMS_FILE).is_file(),
+        "No {TRANSFORMS_FILE} in {}",
+        dir.display()
+    );
+
+    let mut images = vec![];
+    collect_images(dir, Path::new(""), &mut images)?;
+    // Keep zips the same for the same folder.
+    images.sort();
+
+    let mut zip = ZipWriter::new(writer);
+    for path in std::iter::once(PathBuf::from(TRANSFORMS_FILE)).chain(images) {
+        // Zips always use forward slashes.
+        let name: Vec<_> = path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect();
+        zip.start_file(name.join("/"), entry_options(&path))?;
+        std::io::copy(&mut std::fs::File::open(dir.join(&path))?, &mut zip)?;
+    }
+    Ok(zip.finish()?)
+}
+
+fn collect_images(root: &Path, dir: &Path, images: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(root.join(dir))? {
+        let entry = entry?;
+        let path = dir.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            collect_images(root, &path, images)?;
+        } else if is_image_path(&path) {
+            images.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Unpack a dataset zip into `dir`. Entries that would end up outside of `dir` are refused.
+pub fn unpack<R: Read + Seek>(reader: R, dir: &Path) -> Result<()> {
+    let mut archive = ZipArchive::new(reader)?;
+    anyhow::ensure!(
+        archive.index_for_name(TRANSFORMS_FILE).is_some(),
+        "Not a dataset zip, no {TRANSFORMS_FILE} at the root"
+    );
+    archive.extract(dir)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Cursor};
+
+    use super::{TRANSFORMS_FILE, pack_dir, unpack};
+
+    #[test]
+    fn pack_and_unpack() {
+        let root = std::env::temp_dir().join(format!("brush_archive_{}", std::process::id()));
+        let src = root.join("src");
+        fs::create_dir_all(src.join("images")).expect("Failed to create dir");
+        fs::create_dir_all(src.join("masks")).expect("Failed to create dir");
+        fs::write(src.join(TRANSFORMS_FILE), "{}").expect("Failed to write");
+        fs::write(src.join("images/a.png"), "a").expect("Failed to write");
+        fs::write(src.join("masks/a.png"), "mask").expect("Failed to write");
+        fs::write(src.join("notes.txt"), "left out").expect("Failed to write");
+
+        let zip = pack_dir(&src, Cursor::new(vec![])).expect("Failed to pack");
+        let dst = root.join("dst");
+        unpack(Cursor::new(zip.into_inner()), &dst).expect("Failed to unpack");
+
+        assert_eq!(
+            fs::read_to_string(dst.join("images/a.png")).expect("Missing image"),
+            "a"
+        );
+        assert_eq!(
+            fs::read_to_string(dst.join("masks/a.png")).expect("Missing mask"),
+            "mask"
+        );
+        assert!(dst.join(TRANSFORMS_FILE).is_file());
+        assert!(!dst.join("notes.txt").exists());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn unpack_needs_transforms() {
+        let zip = zip::ZipWriter::new(Cursor::new(vec![]))
+            .finish()
+            .expect("Failed to write zip");
+        let dir = std::env::temp_dir().join("brush_archive_empty");
+        assert!(unpack(Cursor::new(zip.into_inner()), &dir).is_err());
+    }
+}
@@ -7,6 +7,7 @@
 use std::{
     collections::HashMap,
     io::{Cursor, Read},
+    ops::Range,
     path::{Path, PathBuf},
     sync::Arc,
 };
@@ -18,12 +19,56 @@ use tokio::{
     sync::Mutex,
 };
 
-use zip::{
-    ZipArchive,
-    result::{ZipError, ZipResult},
-};
+use zip::{CompressionMethod, ZipArchive, result::ZipError};
+
+use crate::{WasmNotSend, error::DatasetError};
+
+/// Limits on the data a VFS is allowed to hold, so a malicious or broken archive can't use up
+/// all memory, eg. a zip bomb that unpacks to terabytes.
+#[derive(Debug, Clone)]
+pub struct VfsLimits {
+    /// Max total size of the files of an archive, in bytes, when unpacked.
+    pub max_unpacked_bytes: u64,
+    pub max_files: usize,
+    /// Max width or height of images, in pixels.
+    pub max_image_size: u32,
+}
+
+impl Default for VfsLimits {
+    fn default() -> Self {
+        // Everything has to fit in the memory of a tab on the web.
+        let max_unpacked_gb = if cfg!(target_family = "wasm") { 2 } else { 32 };
+        Self {
+            max_unpacked_bytes: max_unpacked_gb << 30,
+            max_files: 100_000,
+            max_image_size: 16384,
+        }
+    }
+}
 
-use crate::WasmNotSend;
+impl VfsLimits {
+    fn check_file_count(&self, count: usize) -> Result<(), DatasetError> {
+        if count > self.max_files {
+            return Err(DatasetError::TooManyFiles {
+                count,
+                max: self.max_files,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check the size of an image before decoding it.
+    pub fn check_image_size(&self, size: glam::UVec2) -> Result<(), DatasetError> {
+        if size.max_element() > self.max_image_size {
+            return Err(DatasetError::ImageTooLarge {
+                width: size.x,
+                height: size.y,
+                max: self.max_image_size,
+            });
+        }
+        Ok(())
+    }
+}
 
 pub trait DynRead: AsyncRead + WasmNotSend + Unpin {}
 impl<T: AsyncRead + WasmNotSend + Unpin> DynRead for T {}
@@ -31,15 +76,70 @@ impl<T: AsyncRead + WasmNotSend + Unpin> DynRead for T {}
 // Sometimes rust is beautiful - sometimes it's ArcMutexOptionBox
 type SharedRead = Arc<Mutex<Option<Box<dyn DynRead>>>>;
 
+enum ZipBytes {
+    Memory(Vec<u8>),
+    // Local zips are mapped, so they don't have to fit in memory, and stored entries can be
+    // read without copying the whole archive.
+    #[cfg(not(target_family = "wasm"))]
+    Mapped(memmap2::Mmap),
+}
+
 #[derive(Clone)]
 pub struct ZipData {
-    data: Arc<Vec<u8>>,
+    data: Arc<ZipBytes>,
 }
 
 impl AsRef<[u8]> for ZipData {
     fn as_ref(&self) -> &[u8] {
-        &self.data
+        match self.data.as_ref() {
+            ZipBytes::Memory(bytes) => bytes,
+            #[cfg(not(target_family = "wasm"))]
+            ZipBytes::Mapped(map) => map,
+        }
+    }
+}
+
+/// Part of the zip data, for entries that are stored without compression.
+struct ZipSlice {
+    data: ZipData,
+    range: Range<usize>,
+}
+
+impl AsRef<[u8]> for ZipSlice {
+    fn as_ref(&self) -> &[u8] {
+        &self.data.as_ref()[self.range.clone()]
+    }
+}
+
+type Archive = ZipArchive<Cursor<ZipData>>;
+
+/// Open entry `index` of the archive. Stored entries are read straight from the zip data,
+/// compressed ones are decompressed into memory.
+fn open_zip_entry(archive: &Archive, index: usize) -> anyhow::Result<Box<dyn DynRead>> {
+    // Archive is cheap to clone, as the data and the central directory are shared.
+    let mut archive = archive.clone();
+    let entry = archive.by_index_raw(index)?;
+    if entry.compression() == CompressionMethod::Stored {
+        let start = entry.data_start() as usize;
+        let range = start..start + entry.compressed_size() as usize;
+        drop(entry);
+        let data = archive.into_inner().into_inner();
+        return Ok(Box::new(Cursor::new(ZipSlice { data, range })));
+    }
+    drop(entry);
+
+    // The unpacked sizes were checked against the limits when mounting, don't unpack more
+    // than the entry claims.
+    let entry = archive.by_index(index)?;
+    let size = entry.size();
+    let mut buffer = vec![];
+    entry
+        .take(size.saturating_add(1))
+        .read_to_end(&mut buffer)?;
+    if buffer.len() as u64 > size {
+        anyhow::bail!("Zip entry {index} unpacks to more than its recorded size");
     }
+    Ok(Box::new(Cursor::new(buffer)))
 }
 
 #[derive(Clone, Default)]
@@ -67,43 +167,100 @@ impl PathReader {
     }
 }
 
-pub enum BrushVfs {
-    Zip(ZipArchive<Cursor<ZipData>>),
+enum VfsFiles {
+    /// A zip archive, with the index of each entry by its path.
+    Zip(Archive, HashMap<PathBuf, usize>),
     Manual(PathReader),
     #[cfg(not(target_family = "wasm"))]
     Directory(PathBuf, Vec<PathBuf>),
 }
 
+pub struct BrushVfs {
+    files: VfsFiles,
+    limits: VfsLimits,
+}
+
 impl BrushVfs {
-    pub async fn from_zip_reader(reader: impl AsyncRead + Unpin) -> ZipResult<Self> {
+    pub async fn from_zip_reader(
+        reader: impl AsyncRead + Unpin,
+        limits: VfsLimits,
+    ) -> Result<Self, DatasetError> {
+        // A zip is never bigger than what it unpacks to, so this also stops endless downloads.
         let mut bytes = vec![];
-        let mut reader = reader;
-        reader.read_to_end(&mut bytes).await?;
+        reader
+            .take(limits.max_unpacked_bytes.saturating_add(1))
+            .read_to_end(&mut bytes)
+            .await?;
+        if bytes.len() as u64 > limits.max_unpacked_bytes {
+            return Err(DatasetError::ArchiveTooLarge {
+                max_bytes: limits.max_unpacked_bytes,
+            });
+        }
+        Self::from_zip_data(
+            ZipData {
+                data: Arc::new(ZipBytes::Memory(bytes)),
+            },
+            limits,
+        )
+    }
 
-        let zip_data = ZipData {
-            data: Arc::new(bytes),
-        };
-        let archive = ZipArchive::new(Cursor::new(zip_data))?;
-        Ok(Self::Zip(archive))
+    /// Open a local zip by mapping it into memory, instead of reading all of it.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn from_zip_file(path: &Path, limits: VfsLimits) -> Result<Self, DatasetError> {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: The file must not be changed while it's mapped. Like any other program
+        // reading it, we can't prevent that, but a dataset changing while training is
+        // already unsupported.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        Self::from_zip_data(
+            ZipData {
+                data: Arc::new(ZipBytes::Mapped(map)),
+            },
+            limits,
+        )
+    }
+
+    fn from_zip_data(data: ZipData, limits: VfsLimits) -> Result<Self, DatasetError> {
+        let mut archive = ZipArchive::new(Cursor::new(data))?;
+        limits.check_file_count(archive.len())?;
+        // Sizes as recorded in the archive, reading entries checks they don't unpack to more.
+        let mut unpacked_bytes = 0u64;
+        for i in 0..archive.len() {
+            unpacked_bytes = unpacked_bytes.saturating_add(archive.by_index_raw(i)?.size());
+        }
+        if unpacked_bytes > limits.max_unpacked_bytes {
+            return Err(DatasetError::ArchiveTooLarge {
+                max_bytes: limits.max_unpacked_bytes,
+            });
+        }
+        let index = (0..archive.len())
+            .filter_map(|i| Some((Path::new(archive.name_for_index(i)?).clean(), i)))
+            .collect();
+        Ok(Self {
+            files: VfsFiles::Zip(archive, index),
+            limits,
+        })
     }
 
-    pub fn from_paths(paths: PathReader) -> Self {
-        Self::Manual(paths)
+    pub fn from_paths(paths: PathReader, limits: VfsLimits) -> Self {
+        Self {
+            files: VfsFiles::Manual(paths),
+            limits,
+        }
     }
 
-    pub async fn from_directory(dir: &Path) -> anyhow::Result<Self> {
+    pub async fn from_directory(dir: &Path, limits: VfsLimits) -> Result<Self, DatasetError> {
         #[cfg(not(target_family = "wasm"))]
         {
             if dir.is_file() {
-                let file = tokio::fs::File::open(dir).await?;
-
                 if dir.extension().is_some_and(|e| e == "zip") {
-                    Ok(Self::from_zip_reader(file).await?)
+                    Self::from_zip_file(dir, limits)
                 } else {
                     // Make a VFS with just this file.
+                    let file = tokio::fs::File::open(dir).await?;
                     let mut paths = PathReader::default();
                     paths.add(dir, file);
-                    Ok(Self::from_paths(paths))
+                    Ok(Self::from_paths(paths, limits))
                 }
             } else {
                 // Make a VFS with all files contained in the directory.
@@ -131,23 +288,33 @@ impl BrushVfs {
                     Ok(paths)
                 }
 
-                Ok(Self::Directory(dir.to_path_buf(), walk_dir(dir).await?))
+                let paths = walk_dir(dir).await?;
+                limits.check_file_count(paths.len())?;
+                Ok(Self {
+                    files: VfsFiles::Directory(dir.to_path_buf(), paths),
+                    limits,
+                })
             }
         }
 
         #[cfg(target_family = "wasm")]
         {
-            let _ = dir;
+            let _ = (dir, limits);
             panic!("Cannot read paths on wasm");
         }
     }
 
+    /// The limits the data of this VFS is held to.
+    pub fn limits(&self) -> &VfsLimits {
+        &self.limits
+    }
+
     pub fn file_names(&self) -> impl Iterator<Item = PathBuf> + '_ {
-        let iterator: Box<dyn Iterator<Item = &Path>> = match self {
-            Self::Zip(archive) => Box::new(archive.file_names().map(Path::new)),
-            Self::Manual(map) => Box::new(map.paths().map(|p| p.as_path())),
+        let iterator: Box<dyn Iterator<Item = &Path>> = match &self.files {
+            VfsFiles::Zip(archive, _) => Box::new(archive.file_names().map(Path::new)),
+            VfsFiles::Manual(map) => Box::new(map.paths().map(|p| p.as_path())),
             #[cfg(not(target_family = "wasm"))]
-            Self::Directory(_, paths) => Box::new(paths.iter().map(|p| p.as_path())),
+            VfsFiles::Directory(_, paths) => Box::new(paths.iter().map(|p| p.as_path())),
         };
         iterator.filter_map(|p| {
             // stupic macOS.
@@ -160,21 +327,14 @@ impl BrushVfs {
     }
 
     pub async fn reader_at_path(&self, path: &Path) -> anyhow::Result<Box<dyn DynRead>> {
-        match self {
-            Self::Zip(archive) => {
-                let name = archive
-                    .file_names()
-                    .find(|name| path == Path::new(name))
-                    .ok_or(ZipError::FileNotFound)?;
-                let name = name.to_owned();
-                let mut buffer = vec![];
-                // Archive is cheap to clone, as the data is an Arc<[u8]>.
-                archive.clone().by_name(&name)?.read_to_end(&mut buffer)?;
-                Ok(Box::new(Cursor::new(buffer)))
+        match &self.files {
+            VfsFiles::Zip(archive, index) => {
+                let index = *index.get(path).ok_or(ZipError::FileNotFound)?;
+                open_zip_entry(archive, index)
             }
-            Self::Manual(map) => map.open(path).await,
+            VfsFiles::Manual(map) => map.open(path).await,
             #[cfg(not(target_family = "wasm"))]
-            Self::Directory(dir, _) => {
+            VfsFiles::Directory(dir, _) => {
                 let total_path = dir.join(path);
                 let file = tokio::fs::File::open(total_path).await?;
                 let file = tokio::io::BufReader::new(file);
@@ -183,3 +343,86 @@ impl BrushVfs {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Cursor, Write},
+        path::Path,
+    };
+
+    use tokio::io::AsyncReadExt;
+    use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+    use super::{BrushVfs, VfsLimits};
+    use crate::error::DatasetError;
+
+    fn test_zip() -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        for (name, method) in [
+            ("images/stored.png", CompressionMethod::Stored),
+            ("images/deflated.txt", CompressionMethod::Deflated),
+        ] {
+            let options = SimpleFileOptions::default().compression_method(method);
+            zip.start_file(name, options).expect("Failed to add file");
+            zip.write_all(name.as_bytes()).expect("Failed to write");
+        }
+        zip.finish().expect("Failed to finish zip").into_inner()
+    }
+
+    #[test]
+    fn stored_and_compressed_entries() {
+        let bytes = test_zip();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("Failed to make runtime");
+        runtime.block_on(async {
+            let vfs = BrushVfs::from_zip_reader(Cursor::new(bytes), VfsLimits::default())
+                .await
+                .expect("Valid zip");
+            for name in ["images/stored.png", "images/deflated.txt"] {
+                let mut data = String::new();
+                vfs.reader_at_path(Path::new(name))
+                    .await
+                    .expect("Entry exists")
+                    .read_to_string(&mut data)
+                    .await
+                    .expect("Readable entry");
+                assert_eq!(data, name);
+            }
+            assert!(vfs.reader_at_path(Path::new("missing.png")).await.is_err());
+        });
+    }
+
+    #[test]
+    fn archives_over_the_limits_are_refused() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("Failed to make runtime");
+        let mount = |limits: VfsLimits| {
+            runtime
+                .block_on(BrushVfs::from_zip_reader(Cursor::new(test_zip()), limits))
+                .err()
+        };
+
+        let few_files = VfsLimits {
+            max_files: 1,
+            ..Default::default()
+        };
+        assert!(matches!(
+            mount(few_files),
+            Some(DatasetError::TooManyFiles { count: 2, max: 1 })
+        ));
+        // Less than the names of the entries, which is all they hold.
+        let small = VfsLimits {
+            max_unpacked_bytes: 20,
+            ..Default::default()
+        };
+        assert!(matches!(
+            mount(small),
+            Some(DatasetError::ArchiveTooLarge { .. })
+        ));
+        assert!(mount(VfsLimits::default()).is_none());
+    }
+}
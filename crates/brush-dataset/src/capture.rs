@@ -0,0 +1,108 @@
+use std::io::{Seek, Write};
+use std::path::Path;
+
+use glam::{Mat4, UVec2, Vec2};
+use zip::{ZipWriter, result::ZipResult};
+
+use crate::archive::{TRANSFORMS_FILE, entry_options};
+
+#[derive(serde::Serialize)]
+struct CaptureFrame {
+    file_path: String,
+    fl_x: f64,
+    fl_y: f64,
+    cx: f64,
+    cy: f64,
+    w: f64,
+    h: f64,
+    transform_matrix: Vec<Vec<f32>>,
+}
+
+#[derive(serde::Serialize)]
+struct CaptureScene {
+    camera_model: String,
+    frames: Vec<CaptureFrame>,
+}
+
+/// Writes captured images with known poses to a dataset zip, see [`crate::archive`], so
+/// a capture can be loaded back as a regular dataset.
+pub struct CaptureWriter<W: Write + Seek> {
+    zip: ZipWriter<W>,
+    frames: Vec<CaptureFrame>,
+}
+
+impl<W: Write + Seek> CaptureWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            zip: ZipWriter::new(writer),
+            frames: vec![],
+        }
+    }
+
+    /// Add an already encoded image.
+    ///
+    /// The camera to world transform is expected in the OpenGL convention (-Z forward, +Y up),
+    /// like the nerfstudio format. Focal length and principal point are in pixels.
+    pub fn add_frame(
+        &mut self,
+        image: &[u8],
+        extension: &str,
+        cam_to_world: Mat4,
+        focal: Vec2,
+        principal_point: Vec2,
+        size: UVec2,
+    ) -> ZipResult<()> {
+        let file_path = format!("images/frame_{:05}.{extension}", self.frames.len());
+
+        self.zip
+            .start_file(&file_path, entry_options(Path::new(&file_path)))?;
+        self.zip.write_all(image)?;
+
+        // The json stores the matrix as a list of rows.
+        let rows = cam_to_world.transpose().to_cols_array_2d();
+
+        self.frames.push(CaptureFrame {
+            file_path,
+            fl_x: focal.x as f64,
+            fl_y: focal.y as f64,
+            cx: principal_point.x as f64,
+            cy: principal_point.y as f64,
+            w: size.x as f64,
+            h: size.y as f64,
+            transform_matrix: rows.iter().map(|r| r.to_vec()).collect(),
+        });
+
+        Ok(())
+    }
+
+    /// Add a mask for the frame that was added last. It's named after the image with a
+    /// `_mask` suffix, which is where masks are looked for when loading.
+    pub fn add_mask(&mut self, mask: &[u8], extension: &str) -> ZipResult<()> {
+        let index = self
+            .frames
+            .len()
+            .checked_sub(1)
+            .expect("A mask must be added after its frame");
+        let file_path = format!("images/frame_{index:05}_mask.{extension}");
+        self.zip
+            .start_file(&file_path, entry_options(Path::new(&file_path)))?;
+        self.zip.write_all(mask)
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Write out the transforms file and finish the archive.
+    pub fn finish(mut self) -> anyhow::Result<W> {
+        let scene = CaptureScene {
+            camera_model: "PINHOLE".to_owned(),
+            frames: self.frames,
+        };
+        let json = serde_json::to_vec(&scene)?;
+        self.zip
+            .start_file(TRANSFORMS_FILE, entry_options(Path::new(TRANSFORMS_FILE)))?;
+        self.zip.write_all(&json)?;
+        Ok(self.zip.finish()?)
+    }
+}
@@ -0,0 +1,247 @@
+//! Splitting large scenes into overlapping chunks, to train them one at a time.
+//!
+//! Each chunk is trained on the views with their camera in or near it, so only one chunk has
+//! to fit in memory at a time. The trained chunks are then cut back to their own part of the
+//! scene, and blended with their neighbors where they overlap.
+//!
+//! The merged chunks can also be saved to their own ply files, listed in a [`ChunkIndex`], so
+//! a viewer only has to load the chunks near the camera.
+
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::scene::{Scene, SceneView};
+
+/// Part of a scene that's trained on its own.
+#[derive(Clone)]
+pub struct Chunk {
+    /// Corners of the part of the scene this chunk makes up. Chunks at the edge of the scene
+    /// extend infinitely outwards.
+    pub min: Vec3,
+    pub max: Vec3,
+    /// Width of the band around the bounds where this chunk blends with its neighbors.
+    pub overlap: Vec3,
+    /// Views with their camera in the bounds grown by the overlap.
+    pub scene: Scene,
+}
+
+impl Chunk {
+    /// Whether `point` is in the part of the scene the chunk is trained on, its own part
+    /// grown by the overlap on all sides.
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.cmpge(self.min - self.overlap).all() && point.cmple(self.max + self.overlap).all()
+    }
+
+    /// Distance from `point` to the chunk's own part of the scene, zero inside of it.
+    pub fn distance(&self, point: Vec3) -> f32 {
+        (self.min - point)
+            .max(point - self.max)
+            .max(Vec3::ZERO)
+            .length()
+    }
+}
+
+fn quantile(values: &mut [f32], q: f32) -> f32 {
+    values.sort_by(f32::total_cmp);
+    let index = ((values.len() - 1) as f32 * q).round() as usize;
+    values[index]
+}
+
+/// Bounds and overlap of about `count` chunks, on a grid along the two axes the cameras
+/// spread out most along. Splits are placed so each chunk has about as many cameras.
+fn chunk_bounds(positions: &[Vec3], count: u32, overlap: f32) -> Vec<(Vec3, Vec3, Vec3)> {
+    let unbounded = vec![(Vec3::NEG_INFINITY, Vec3::INFINITY, Vec3::ZERO)];
+    if count <= 1 || positions.len() < 2 {
+        return unbounded;
+    }
+
+    let (low, high) = positions
+        .iter()
+        .fold((Vec3::INFINITY, Vec3::NEG_INFINITY), |(low, high), &p| {
+            (low.min(p), high.max(p))
+        });
+    let size = high - low;
+    let mut axes = [0, 1, 2];
+    axes.sort_by(|&a, &b| size[b].total_cmp(&size[a]));
+    let (wide, narrow) = (axes[0], axes[1]);
+
+    // Make the chunks about square.
+    let ratio = size[wide] / size[narrow].max(1e-6);
+    let columns = ((count as f32 * ratio).sqrt().round() as u32).clamp(1, count);
+    let rows = (count / columns).max(1);
+
+    let edges = |axis: usize, n: u32| {
+        let mut values: Vec<f32> = positions.iter().map(|p| p[axis]).collect();
+        let splits = (1..n).map(|i| quantile(&mut values, i as f32 / n as f32));
+        let edges: Vec<f32> = std::iter::once(f32::NEG_INFINITY)
+            .chain(splits)
+            .chain(std::iter::once(f32::INFINITY))
+            .collect();
+        edges.windows(2).map(|w| (w[0], w[1])).collect::<Vec<_>>()
+    };
+
+    let mut overlap_size = Vec3::ZERO;
+    overlap_size[wide] = overlap * size[wide] / columns as f32;
+    overlap_size[narrow] = overlap * size[narrow] / rows as f32;
+
+    let mut chunks = vec![];
+    for (col_min, col_max) in edges(wide, columns) {
+        for &(row_min, row_max) in &edges(narrow, rows) {
+            let (mut min, mut max) = (Vec3::NEG_INFINITY, Vec3::INFINITY);
+            min[wide] = col_min;
+            max[wide] = col_max;
+            min[narrow] = row_min;
+            max[narrow] = row_max;
+            chunks.push((min, max, overlap_size));
+        }
+    }
+    chunks
+}
+
+/// Split the scene into about `count` chunks, that overlap by `overlap` times their size.
+/// Chunks without any views are left out.
+pub fn partition(scene: &Scene, count: u32, overlap: f32) -> Vec<Chunk> {
+    let positions: Vec<Vec3> = scene.views.iter().map(|v| v.camera.position).collect();
+
+    chunk_bounds(&positions, count, overlap)
+        .into_iter()
+        .map(|(min, max, overlap)| {
+            let mut chunk = Chunk {
+                min,
+                max,
+                overlap,
+                scene: Scene::new(vec![]),
+            };
+            let views = scene
+                .views
+                .iter()
+                .filter(|v| chunk.contains(v.camera.position))
+                .map(|v| SceneView {
+                    image: v.image.clone(),
+                    camera: v.camera.clone(),
+                })
+                .collect();
+            chunk.scene = Scene::new(views);
+            chunk
+        })
+        .filter(|chunk| !chunk.scene.views.is_empty())
+        .collect()
+}
+
+/// Name of the file listing the merged chunks, next to their ply files.
+pub const CHUNK_INDEX_FILE: &str = "chunk_index.json";
+
+/// A merged chunk saved to its own ply file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChunkFile {
+    /// Name of the ply file, relative to the index.
+    pub file: String,
+    /// Bounds of the splat centers in the file.
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl ChunkFile {
+    /// Distance from `point` to the bounds of the chunk, zero inside of them.
+    pub fn distance(&self, point: Vec3) -> f32 {
+        (self.min - point)
+            .max(point - self.max)
+            .max(Vec3::ZERO)
+            .length()
+    }
+
+    /// Length of the diagonal of the bounds.
+    pub fn size(&self) -> f32 {
+        (self.max - self.min).length()
+    }
+}
+
+/// The chunks a merged scene was saved as.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChunkIndex {
+    pub chunks: Vec<ChunkFile>,
+}
+
+impl ChunkIndex {
+    /// Chunks within `range` times their own size of `point`, closest first. The closest chunk
+    /// is always included, so there's something to see from far away.
+    pub fn near(&self, point: Vec3, range: f32) -> Vec<usize> {
+        let mut near: Vec<(usize, f32)> = self
+            .chunks
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| (i, chunk.distance(point)))
+            .collect();
+        near.sort_by(|a, b| a.1.total_cmp(&b.1));
+        near.iter()
+            .enumerate()
+            .filter(|(rank, (i, dist))| *rank == 0 || *dist <= range * self.chunks[*i].size())
+            .map(|(_, (i, _))| *i)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Vec3, vec3};
+
+    use super::{ChunkFile, ChunkIndex, chunk_bounds};
+
+    #[test]
+    fn chunks_split_along_widest_axes() {
+        // A street of cameras along x, a little wide along z.
+        let positions: Vec<Vec3> = (0..100)
+            .map(|i| vec3(i as f32, 0.1 * (i % 3) as f32, (i % 5) as f32 * 5.0))
+            .collect();
+        let chunks = chunk_bounds(&positions, 4, 0.2);
+        assert_eq!(chunks.len(), 4);
+
+        for (min, max, overlap) in &chunks {
+            // Never split along the narrowest axis.
+            assert!(min.y.is_infinite() && max.y.is_infinite());
+            assert_eq!(overlap.y, 0.0);
+            assert!(overlap.x > 0.0);
+        }
+
+        // Every camera is in exactly one chunk.
+        for p in &positions {
+            let count = chunks
+                .iter()
+                .filter(|(min, max, _)| p.cmpge(*min).all() && p.cmplt(*max).all())
+                .count();
+            assert_eq!(count, 1);
+        }
+    }
+
+    #[test]
+    fn one_chunk_is_unbounded() {
+        let chunks = chunk_bounds(&[Vec3::ZERO, Vec3::ONE], 1, 0.2);
+        assert_eq!(
+            chunks,
+            vec![(Vec3::NEG_INFINITY, Vec3::INFINITY, Vec3::ZERO)]
+        );
+    }
+
+    #[test]
+    fn near_chunks_closest_first() {
+        // A row of unit chunks along x.
+        let index = ChunkIndex {
+            chunks: (0..5)
+                .map(|i| ChunkFile {
+                    file: format!("chunk_{i}.ply"),
+                    min: vec3(i as f32, 0.0, 0.0),
+                    max: vec3(i as f32 + 1.0, 1.0, 1.0),
+                })
+                .collect(),
+        };
+        let size = index.chunks[0].size();
+
+        let near = index.near(vec3(2.5, 0.5, 0.5), 1.0 / size);
+        assert_eq!(near[0], 2);
+        assert_eq!(near.len(), 3);
+
+        // Far away from all of them, only the closest is kept.
+        let near = index.near(vec3(100.0, 0.5, 0.5), 1.0);
+        assert_eq!(near, vec![4]);
+    }
+}
@@ -0,0 +1,591 @@
+//! Import of nerfstudio splatfacto checkpoints (`.ckpt`).
+//!
+//! These are PyTorch checkpoints: a zip with a pickled state dict in `data.pkl`, and the
+//! raw bytes of each tensor storage in `data/<key>`. Only the small subset of pickle
+//! used by `torch.save` is supported, and only float32 tensors are read.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context, Result};
+use brush_render::{gaussian_splats::Splats, sh::rgb_to_sh};
+use burn::prelude::Backend;
+use glam::{Quat, Vec3};
+use tokio::io::AsyncReadExt;
+
+use crate::{
+    brush_vfs::BrushVfs,
+    splat_import::{ParseMetadata, SplatMessage},
+};
+
+/// Whether the vfs holds a torch checkpoint, or its unpacked contents.
+pub fn is_checkpoint(vfs: &BrushVfs) -> bool {
+    vfs.file_names()
+        .any(|p| p.file_name().is_some_and(|n| n == "data.pkl") || is_ckpt_file(&p))
+}
+
+fn is_ckpt_file(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "ckpt")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct TensorRef {
+    storage_type: String,
+    storage_key: String,
+    offset: usize,
+    shape: Vec<usize>,
+    stride: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[allow(dead_code)] // Not all values are read, but they show up in errors.
+enum Value {
+    None,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bytes(Vec<u8>),
+    Tuple(Vec<Value>),
+    List(Vec<Value>),
+    Dict(Vec<(Value, Value)>),
+    Global(String, String),
+    /// A call to rebuild an object that isn't understood.
+    Object(Box<Value>, Vec<Value>),
+    PersistentId(Box<Value>),
+    Tensor(TensorRef),
+}
+
+impl Value {
+    fn as_usize(&self) -> Result<usize> {
+        match self {
+            Self::Int(i) => usize::try_from(*i).context("Negative size"),
+            _ => anyhow::bail!("Expected an int, got {self:?}"),
+        }
+    }
+
+    fn as_usizes(&self) -> Result<Vec<usize>> {
+        match self {
+            Self::Tuple(values) | Self::List(values) => values.iter().map(Self::as_usize).collect(),
+            _ => anyhow::bail!("Expected a tuple, got {self:?}"),
+        }
+    }
+
+    /// The number of values this is made of, including itself.
+    fn node_count(&self) -> usize {
+        1 + match self {
+            Self::Tuple(values) | Self::List(values) => values.iter().map(Self::node_count).sum(),
+            Self::Dict(entries) => entries
+                .iter()
+                .map(|(k, v)| k.node_count() + v.node_count())
+                .sum(),
+            Self::Object(callable, args) => {
+                callable.node_count() + args.iter().map(Self::node_count).sum::<usize>()
+            }
+            Self::PersistentId(value) => value.node_count(),
+            _ => 0,
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&Self> {
+        match self {
+            Self::Dict(entries) => entries
+                .iter()
+                .find(|(k, _)| *k == Self::Str(key.to_owned()))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+fn rebuild_tensor(args: &[Value]) -> Result<Value> {
+    let [Value::PersistentId(pid), offset, shape, stride, ..] = args else {
+        anyhow::bail!("Unexpected tensor arguments {args:?}");
+    };
+    let Value::Tuple(pid) = pid.as_ref() else {
+        anyhow::bail!("Unexpected storage {pid:?}");
+    };
+    let [
+        Value::Str(kind),
+        Value::Global(_, storage_type),
+        Value::Str(storage_key),
+        ..,
+    ] = pid.as_slice()
+    else {
+        anyhow::bail!("Unexpected storage {pid:?}");
+    };
+    anyhow::ensure!(kind == "storage", "Unexpected storage kind {kind}");
+
+    Ok(Value::Tensor(TensorRef {
+        storage_type: storage_type.clone(),
+        storage_key: storage_key.clone(),
+        offset: offset.as_usize()?,
+        shape: shape.as_usizes()?,
+        stride: stride.as_usizes()?,
+    }))
+}
+
+fn call(callable: Value, args: Vec<Value>) -> Result<Value> {
+    let Value::Global(module, name) = &callable else {
+        return Ok(Value::Object(Box::new(callable), args));
+    };
+    Ok(match (module.as_str(), name.as_str()) {
+        ("collections", "OrderedDict") => Value::Dict(vec![]),
+        ("torch._utils", "_rebuild_tensor_v2") => rebuild_tensor(&args)?,
+        // Parameters are tensors as far as we're concerned.
+        ("torch._utils", "_rebuild_parameter") => {
+            args.into_iter().next().context("Missing parameter")?
+        }
+        _ => Value::Object(Box::new(callable), args),
+    })
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        // Lengths come from the pickle itself, so they can be anything.
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|&end| end <= self.data.len())
+            .context("Unexpected end of pickle")?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.bytes(N)?.try_into().expect("Read exactly N bytes"))
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn line(&mut self) -> Result<String> {
+        let len = self.data[self.pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .context("Unterminated line")?;
+        let line = String::from_utf8_lossy(self.bytes(len)?).into_owned();
+        self.pos += 1;
+        Ok(line)
+    }
+
+    fn string(&mut self, len: usize) -> Result<Value> {
+        Ok(Value::Str(
+            String::from_utf8_lossy(self.bytes(len)?).into_owned(),
+        ))
+    }
+}
+
+/// Limit on the number of values a pickle can copy out of its memo.
+const MAX_COPIED_VALUES: usize = 1 << 20;
+
+/// Run the pickle program, returning the object it builds.
+fn unpickle(data: &[u8]) -> Result<Value> {
+    let mut reader = Reader { data, pos: 0 };
+    let mut stack: Vec<Value> = vec![];
+    let mut marks: Vec<usize> = vec![];
+    let mut memo: HashMap<u32, Value> = HashMap::new();
+
+    let pop = |stack: &mut Vec<Value>| stack.pop().context("Pickle stack underflow");
+    let pop_mark = |stack: &mut Vec<Value>, marks: &mut Vec<usize>| -> Result<Vec<Value>> {
+        let mark = marks.pop().context("Missing pickle mark")?;
+        anyhow::ensure!(mark <= stack.len(), "Pickle stack underflow");
+        Ok(stack.split_off(mark))
+    };
+    // Memo entries can be copied into themselves, which grows exponentially. Real
+    // checkpoints copy little more than globals, so put a limit on it.
+    let mut copied = 0;
+    let mut copy = |value: &Value| -> Result<Value> {
+        copied += value.node_count();
+        anyhow::ensure!(copied <= MAX_COPIED_VALUES, "Pickle copies too many values");
+        Ok(value.clone())
+    };
+
+    loop {
+        let op = reader.u8()?;
+        match op {
+            // PROTO
+            0x80 => {
+                reader.u8()?;
+            }
+            // FRAME
+            0x95 => {
+                reader.bytes(8)?;
+            }
+            b'}' => stack.push(Value::Dict(vec![])),
+            b']' => stack.push(Value::List(vec![])),
+            b')' => stack.push(Value::Tuple(vec![])),
+            b'N' => stack.push(Value::None),
+            0x88 => stack.push(Value::Bool(true)),
+            0x89 => stack.push(Value::Bool(false)),
+            b'(' => marks.push(stack.len()),
+            // Ints.
+            b'J' => stack.push(Value::Int(i32::from_le_bytes(reader.array()?) as i64)),
+            b'K' => stack.push(Value::Int(reader.u8()? as i64)),
+            b'M' => stack.push(Value::Int(u16::from_le_bytes(reader.array()?) as i64)),
+            // LONG1
+            0x8a => {
+                let len = reader.u8()? as usize;
+                anyhow::ensure!(len <= 8, "Int too large");
+                let bytes = reader.bytes(len)?;
+                let negative = bytes.last().is_some_and(|b| b & 0x80 != 0);
+                let mut buf = if negative { [0xff; 8] } else { [0; 8] };
+                buf[..len].copy_from_slice(bytes);
+                stack.push(Value::Int(i64::from_le_bytes(buf)));
+            }
+            b'G' => stack.push(Value::Float(f64::from_be_bytes(reader.array()?))),
+            // Strings.
+            b'X' | b'T' => {
+                let len = reader.u32()? as usize;
+                stack.push(reader.string(len)?);
+            }
+            0x8c | b'U' => {
+                let len = reader.u8()? as usize;
+                stack.push(reader.string(len)?);
+            }
+            0x8d => {
+                let len = u64::from_le_bytes(reader.array()?) as usize;
+                stack.push(reader.string(len)?);
+            }
+            b'B' => {
+                let len = reader.u32()? as usize;
+                stack.push(Value::Bytes(reader.bytes(len)?.to_vec()));
+            }
+            b'C' => {
+                let len = reader.u8()? as usize;
+                stack.push(Value::Bytes(reader.bytes(len)?.to_vec()));
+            }
+            // Memo.
+            b'q' => {
+                let index = reader.u8()? as u32;
+                memo.insert(index, copy(stack.last().context("Empty stack")?)?);
+            }
+            b'r' => {
+                let index = reader.u32()?;
+                memo.insert(index, copy(stack.last().context("Empty stack")?)?);
+            }
+            0x94 => {
+                let value = copy(stack.last().context("Empty stack")?)?;
+                memo.insert(memo.len() as u32, value);
+            }
+            b'h' | b'j' => {
+                let index = if op == b'h' {
+                    reader.u8()? as u32
+                } else {
+                    reader.u32()?
+                };
+                stack.push(copy(memo.get(&index).context("Missing memo entry")?)?);
+            }
+            // Globals.
+            b'c' => {
+                let module = reader.line()?;
+                let name = reader.line()?;
+                stack.push(Value::Global(module, name));
+            }
+            0x93 => {
+                let (Value::Str(name), Value::Str(module)) = (pop(&mut stack)?, pop(&mut stack)?)
+                else {
+                    anyhow::bail!("Invalid global");
+                };
+                stack.push(Value::Global(module, name));
+            }
+            // Containers.
+            b't' => {
+                let items = pop_mark(&mut stack, &mut marks)?;
+                stack.push(Value::Tuple(items));
+            }
+            0x85..=0x87 => {
+                let len = (op - 0x84) as usize;
+                anyhow::ensure!(stack.len() >= len, "Pickle stack underflow");
+                let items = stack.split_off(stack.len() - len);
+                stack.push(Value::Tuple(items));
+            }
+            b'l' => {
+                let items = pop_mark(&mut stack, &mut marks)?;
+                stack.push(Value::List(items));
+            }
+            b'd' => {
+                let items = pop_mark(&mut stack, &mut marks)?;
+                let mut entries = vec![];
+                let mut items = items.into_iter();
+                while let (Some(k), Some(v)) = (items.next(), items.next()) {
+                    entries.push((k, v));
+                }
+                stack.push(Value::Dict(entries));
+            }
+            b'a' => {
+                let item = pop(&mut stack)?;
+                if let Some(Value::List(list)) = stack.last_mut() {
+                    list.push(item);
+                }
+            }
+            b'e' => {
+                let items = pop_mark(&mut stack, &mut marks)?;
+                if let Some(Value::List(list)) = stack.last_mut() {
+                    list.extend(items);
+                }
+            }
+            b's' => {
+                let value = pop(&mut stack)?;
+                let key = pop(&mut stack)?;
+                if let Some(Value::Dict(entries)) = stack.last_mut() {
+                    entries.push((key, value));
+                }
+            }
+            b'u' => {
+                let items = pop_mark(&mut stack, &mut marks)?;
+                if let Some(Value::Dict(entries)) = stack.last_mut() {
+                    let mut items = items.into_iter();
+                    while let (Some(k), Some(v)) = (items.next(), items.next()) {
+                        entries.push((k, v));
+                    }
+                }
+            }
+            // Objects.
+            b'R' | 0x81 => {
+                let args = match pop(&mut stack)? {
+                    Value::Tuple(args) => args,
+                    args => anyhow::bail!("Expected argument tuple, got {args:?}"),
+                };
+                let callable = pop(&mut stack)?;
+                stack.push(call(callable, args)?);
+            }
+            // BUILD, the state of objects isn't needed.
+            b'b' => {
+                pop(&mut stack)?;
+            }
+            b'Q' => {
+                let pid = pop(&mut stack)?;
+                stack.push(Value::PersistentId(Box::new(pid)));
+            }
+            b'0' => {
+                pop(&mut stack)?;
+            }
+            b'1' => {
+                pop_mark(&mut stack, &mut marks)?;
+            }
+            b'2' => {
+                let top = copy(stack.last().context("Empty stack")?)?;
+                stack.push(top);
+            }
+            b'.' => return pop(&mut stack),
+            _ => anyhow::bail!("Unsupported pickle opcode {op:#x}"),
+        }
+    }
+}
+
+/// Find a tensor of the state dict, by the end of its name.
+fn find_tensor<'a>(value: &'a Value, suffix: &str) -> Option<&'a TensorRef> {
+    let Value::Dict(entries) = value else {
+        return None;
+    };
+    entries.iter().find_map(|(k, v)| match (k, v) {
+        (Value::Str(name), Value::Tensor(tensor)) if name.ends_with(suffix) => Some(tensor),
+        (_, Value::Dict(_)) => find_tensor(v, suffix),
+        _ => None,
+    })
+}
+
+async fn read_tensor(vfs: &BrushVfs, archive_dir: &Path, tensor: &TensorRef) -> Result<Vec<f32>> {
+    anyhow::ensure!(
+        tensor.storage_type == "FloatStorage",
+        "Only float32 tensors are supported, found {}",
+        tensor.storage_type
+    );
+
+    // Must be contiguous, which is what torch saves parameters as.
+    let mut expected_stride = 1usize;
+    for (&size, &stride) in tensor.shape.iter().zip(&tensor.stride).rev() {
+        anyhow::ensure!(
+            size == 1 || stride == expected_stride,
+            "Non contiguous tensors aren't supported"
+        );
+        expected_stride = expected_stride.saturating_mul(size);
+    }
+    let numel = tensor
+        .shape
+        .iter()
+        .try_fold(1usize, |n, &size| n.checked_mul(size))
+        .context("Tensor too large")?;
+
+    let path = archive_dir.join("data").join(&tensor.storage_key);
+    let mut bytes = vec![];
+    vfs.reader_at_path(&path)
+        .await
+        .with_context(|| format!("Missing tensor data {}", path.display()))?
+        .read_to_end(&mut bytes)
+        .await?;
+
+    let range = tensor
+        .offset
+        .checked_mul(4)
+        .and_then(|start| Some(start..start.checked_add(numel.checked_mul(4)?)?));
+    let bytes = range
+        .and_then(|range| bytes.get(range))
+        .context("Tensor data too short")?;
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes(b.try_into().expect("Chunks of 4")))
+        .collect())
+}
+
+/// Read a splatfacto parameter, returning its shape and values.
+async fn read_param(
+    vfs: &BrushVfs,
+    archive_dir: &Path,
+    state: &Value,
+    name: &str,
+) -> Result<(Vec<usize>, Vec<f32>)> {
+    let tensor = find_tensor(state, &format!("gauss_params.{name}"))
+        .with_context(|| format!("No gauss_params.{name} in checkpoint, is this splatfacto?"))?;
+    let data = read_tensor(vfs, archive_dir, tensor).await?;
+    Ok((tensor.shape.clone(), data))
+}
+
+/// Load the splats of a nerfstudio splatfacto checkpoint.
+///
+/// The vfs can either be the checkpoint itself, or a directory with `.ckpt` files, eg. a
+/// nerfstudio output directory. Then the last checkpoint is loaded.
+pub async fn load_splat_from_ckpt<B: Backend>(
+    vfs: Arc<BrushVfs>,
+    device: &B::Device,
+) -> Result<SplatMessage<B>> {
+    // Checkpoints are named by their step, the last one sorts last.
+    let ckpt: Option<PathBuf> = vfs.file_names().filter(|p| is_ckpt_file(p)).max();
+    let vfs = if let Some(ckpt) = ckpt {
+        log::info!("Loading checkpoint {ckpt:?}");
+        let reader = vfs.reader_at_path(&ckpt).await?;
+        Arc::new(BrushVfs::from_zip_reader(reader, vfs.limits().clone()).await?)
+    } else {
+        vfs
+    };
+
+    let pickle_path = vfs
+        .file_names()
+        .find(|p| p.file_name().is_some_and(|n| n == "data.pkl"))
+        .context("Not a PyTorch checkpoint, missing data.pkl")?;
+    let archive_dir = pickle_path.parent().unwrap_or(Path::new("")).to_owned();
+
+    let mut pickle = vec![];
+    vfs.reader_at_path(&pickle_path)
+        .await?
+        .read_to_end(&mut pickle)
+        .await?;
+    let checkpoint = unpickle(&pickle)?;
+    let state = checkpoint.get("pipeline").unwrap_or(&checkpoint);
+
+    let read = |name| read_param(&vfs, &archive_dir, state, name);
+    let (_, means) = read("means").await?;
+    let (_, quats) = read("quats").await?;
+    let (_, scales) = read("scales").await?;
+    let (_, opacities) = read("opacities").await?;
+    let (_, features_dc) = read("features_dc").await?;
+    let (rest_shape, features_rest) = read("features_rest").await?;
+
+    let n_splats = means.len() / 3;
+    let n_rest = rest_shape.get(1).copied().unwrap_or(0);
+    anyhow::ensure!(
+        quats.len() == n_splats * 4
+            && scales.len() == n_splats * 3
+            && opacities.len() == n_splats
+            && features_dc.len() == n_splats * 3
+            && Some(features_rest.len()) == n_rest.checked_mul(n_splats * 3),
+        "Splat parameters have mismatched sizes"
+    );
+
+    let means: Vec<Vec3> = means.chunks_exact(3).map(Vec3::from_slice).collect();
+    // Stored as w, x, y, z.
+    let rotations: Vec<Quat> = quats
+        .chunks_exact(4)
+        .map(|q| Quat::from_xyzw(q[1], q[2], q[3], q[0]).normalize())
+        .collect();
+    let log_scales: Vec<Vec3> = scales.chunks_exact(3).map(Vec3::from_slice).collect();
+
+    let mut sh_coeffs = Vec::with_capacity(features_dc.len() + features_rest.len());
+    for (i, dc) in features_dc.chunks_exact(3).enumerate() {
+        if n_rest == 0 {
+            // Without spherical harmonics, splatfacto stores colors as logits.
+            let sigmoid = |x: f32| 1.0 / (1.0 + (-x).exp());
+            let sh = rgb_to_sh(Vec3::from_slice(dc).map(sigmoid));
+            sh_coeffs.extend([sh.x, sh.y, sh.z]);
+        } else {
+            sh_coeffs.extend_from_slice(dc);
+            sh_coeffs.extend_from_slice(&features_rest[i * n_rest * 3..(i + 1) * n_rest * 3]);
+        }
+    }
+
+    let splats = Splats::from_raw(
+        &means,
+        Some(&rotations),
+        Some(&log_scales),
+        Some(&sh_coeffs),
+        Some(&opacities),
+        device,
+    );
+
+    Ok(SplatMessage {
+        meta: ParseMetadata {
+            up_axis: None,
+            total_splats: n_splats as u32,
+            frame_count: 1,
+            current_frame: 0,
+            ..Default::default()
+        },
+        splats,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // torch.save({"pipeline": {"_model.gauss_params.means": torch.zeros(2, 3)}}) in its
+    // pickle protocol 2 form.
+    const STATE_DICT: &[u8] = b"\x80\x02}q\x00X\x08\x00\x00\x00pipelineq\x01}q\x02X\x19\x00\x00\x00_model.gauss_params.meansq\x03ctorch._utils\n_rebuild_tensor_v2\nq\x04((X\x07\x00\x00\x00storageq\x05ctorch\nFloatStorage\nq\x06X\x01\x00\x00\x000q\x07X\x03\x00\x00\x00cpuq\x08K\x06tq\tQK\x00K\x02K\x03\x86q\nK\x03K\x01\x86q\x0b\x89ccollections\nOrderedDict\nq\x0c)Rq\rtq\x0eRq\x0fss.";
+
+    #[test]
+    fn unpickle_state_dict() {
+        let value = unpickle(STATE_DICT).expect("Valid pickle");
+        let state = value.get("pipeline").expect("Has pipeline");
+        let tensor = find_tensor(state, "gauss_params.means").expect("Has means");
+        assert_eq!(
+            tensor,
+            &TensorRef {
+                storage_type: "FloatStorage".to_owned(),
+                storage_key: "0".to_owned(),
+                offset: 0,
+                shape: vec![2, 3],
+                stride: vec![3, 1],
+            }
+        );
+    }
+
+    #[test]
+    fn unpickle_rejects_malformed_programs() {
+        // A string as long as memory can address.
+        assert!(unpickle(b"\x8d\xff\xff\xff\xff\xff\xff\xff\xff").is_err());
+        // A tuple reaching below its mark.
+        assert!(unpickle(b"N(0t.").is_err());
+        // A tuple that holds two copies of itself, over and over.
+        let mut bomb = b"N\x94".to_vec();
+        for i in 0..64u8 {
+            bomb.extend([b'h', i, b'h', i, 0x86, 0x94]);
+        }
+        bomb.push(b'.');
+        assert!(unpickle(&bomb).is_err());
+    }
+}
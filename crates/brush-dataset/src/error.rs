@@ -0,0 +1,50 @@
+//! Errors of loading data that the user can do something about.
+
+use brush_render::error::Suggestion;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum DatasetError {
+    /// The data isn't in any of the formats Brush reads.
+    #[error("Unsupported format: {found}")]
+    UnsupportedFormat { found: String },
+    /// A download returned a web page, eg. an error or login page, instead of the data.
+    #[error("Failed to download data, the server sent a web page instead")]
+    DownloadFailed,
+    /// There are files, but not laid out as any dataset format Brush knows.
+    #[error("Dataset is neither in nerfstudio nor COLMAP format")]
+    UnknownLayout,
+    /// Training starts from a point cloud only if there is a single one.
+    #[error("Expected exactly one .ply or LiDAR file, found {found}")]
+    InitFileCount { found: usize },
+    /// Unpacking the archive would take more than the limit, eg. a zip bomb.
+    #[error("Archive unpacks to more than {} MB", max_bytes >> 20)]
+    ArchiveTooLarge { max_bytes: u64 },
+    #[error("Dataset has {count} files, more than the limit of {max}")]
+    TooManyFiles { count: usize, max: usize },
+    #[error("Image of {width}x{height} pixels is larger than the limit of {max} pixels")]
+    ImageTooLarge { width: u32, height: u32, max: u32 },
+    #[error("Failed to read data: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid zip archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    /// Any other failure, eg. of parsing the files of a format.
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl DatasetError {
+    /// What the user can do to fix the error, if anything.
+    pub fn suggestion(&self) -> Option<Suggestion> {
+        match self {
+            Self::UnsupportedFormat { .. } => Some(Suggestion::UnsupportedFormat),
+            Self::DownloadFailed => Some(Suggestion::DownloadFailed),
+            Self::UnknownLayout => Some(Suggestion::UnknownLayout),
+            Self::InitFileCount { .. } => Some(Suggestion::InitFileCount),
+            Self::ArchiveTooLarge { .. } => Some(Suggestion::ArchiveTooLarge),
+            Self::TooManyFiles { .. } => Some(Suggestion::TooManyFiles),
+            Self::ImageTooLarge { .. } => Some(Suggestion::ImageTooLarge),
+            Self::Io(_) | Self::Zip(_) | Self::Other(_) => None,
+        }
+    }
+}
@@ -3,7 +3,7 @@ use std::{
     sync::Arc,
 };
 
-use super::DataStream;
+use super::{DataStream, DatasetFormat, LoadFuture};
 use crate::{
     Dataset, LoadDataseConfig,
     brush_vfs::BrushVfs,
@@ -61,22 +61,41 @@ fn find_mask_and_img(vfs: &BrushVfs, paths: &[PathBuf]) -> Result<(PathBuf, Opti
         .context("No candidates found")
 }
 
-pub(crate) async fn load_dataset<B: Backend>(
-    vfs: Arc<BrushVfs>,
-    load_args: &LoadDataseConfig,
-    device: &B::Device,
-) -> Option<Result<(DataStream<SplatMessage<B>>, Dataset)>> {
-    log::info!("Loading colmap dataset");
-
-    let (cam_path, img_path) = if let Some(path) = find_base_path(&vfs, "cameras.bin") {
-        (path.join("cameras.bin"), path.join("images.bin"))
-    } else if let Some(path) = find_base_path(&vfs, "cameras.txt") {
-        (path.join("cameras.txt"), path.join("images.txt"))
+/// Paths of the cameras and images files of the reconstruction, binary or text.
+fn find_colmap_files(vfs: &BrushVfs) -> Option<(PathBuf, PathBuf)> {
+    if let Some(path) = find_base_path(vfs, "cameras.bin") {
+        Some((path.join("cameras.bin"), path.join("images.bin")))
     } else {
-        return None;
-    };
+        find_base_path(vfs, "cameras.txt")
+            .map(|path| (path.join("cameras.txt"), path.join("images.txt")))
+    }
+}
+
+/// A COLMAP reconstruction, with the cameras and images files next to the images.
+pub struct ColmapFormat;
 
-    Some(load_dataset_inner(vfs, load_args, device, cam_path, img_path).await)
+impl<B: Backend> DatasetFormat<B> for ColmapFormat {
+    fn name(&self) -> &'static str {
+        "COLMAP dataset"
+    }
+
+    fn detect(&self, vfs: &BrushVfs) -> bool {
+        find_colmap_files(vfs).is_some()
+    }
+
+    fn load<'a>(
+        &'a self,
+        vfs: Arc<BrushVfs>,
+        load_args: &'a LoadDataseConfig,
+        device: &'a B::Device,
+    ) -> LoadFuture<'a, B> {
+        Box::pin(async move {
+            log::info!("Loading colmap dataset");
+            let (cam_path, img_path) =
+                find_colmap_files(&vfs).context("No COLMAP cameras file found")?;
+            load_dataset_inner(vfs, load_args, device, cam_path, img_path).await
+        })
+    }
 }
 
 async fn load_dataset_inner<B: Backend>(
@@ -113,7 +132,10 @@ async fn load_dataset_inner<B: Backend>(
         .step_by(load_args.subsample_frames.unwrap_or(1) as usize)
         .enumerate()
     {
-        let cam_data = cam_model_data[&img_info.camera_id].clone();
+        let cam_data = cam_model_data
+            .get(&img_info.camera_id)
+            .with_context(|| format!("No camera {} for image", img_info.camera_id))?
+            .clone();
         let vfs = vfs.clone();
 
         // Create a future to handle loading the image.
@@ -225,6 +247,7 @@ async fn load_dataset_inner<B: Backend>(
                             total_splats: init_splat.num_splats(),
                             frame_count: 1,
                             current_frame: 0,
+                            ..Default::default()
                         },
                         splats: init_splat,
                     })
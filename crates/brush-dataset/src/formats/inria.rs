@@ -0,0 +1,247 @@
+//! Output directories of the reference 3DGS implementation by INRIA.
+//!
+//! These hold the trained splats at each saved iteration in
+//! `point_cloud/iteration_<N>/point_cloud.ply`, the training cameras in `cameras.json`, and
+//! optionally the per image exposure compensation in `exposure.json`. The images themselves
+//! aren't part of the output, without them the splats can only be viewed.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use super::{DataStream, DatasetFormat, LoadFuture, find_mask_path};
+use crate::{
+    Dataset, LoadDataseConfig,
+    brush_vfs::BrushVfs,
+    scene::{LoadImage, SceneView, is_image_path},
+    splat_import::{SplatMessage, load_splat_from_ply},
+};
+use anyhow::{Context, Result};
+use async_fn_stream::try_fn_stream;
+use brush_render::camera::{Camera, focal_to_fov};
+use burn::prelude::Backend;
+use glam::{Affine3A, Mat3, Quat, Vec3};
+use tokio::io::AsyncReadExt;
+use tokio_stream::StreamExt;
+
+#[derive(serde::Deserialize)]
+struct JsonCamera {
+    img_name: String,
+    width: u32,
+    height: u32,
+    position: [f32; 3],
+    /// Camera to world rotation, as rows.
+    rotation: [[f32; 3]; 3],
+    fx: f64,
+    fy: f64,
+}
+
+/// Iteration of a `point_cloud/iteration_<N>/point_cloud.ply` checkpoint.
+fn checkpoint_iteration(path: &Path) -> Option<u32> {
+    if path.file_name()? != "point_cloud.ply" {
+        return None;
+    }
+    let iter_dir = path.parent()?;
+    if iter_dir.parent()?.file_name()? != "point_cloud" {
+        return None;
+    }
+    iter_dir
+        .file_name()?
+        .to_str()?
+        .strip_prefix("iteration_")?
+        .parse()
+        .ok()
+}
+
+/// The splats of the last saved iteration, if this is an INRIA output directory.
+pub fn find_checkpoint(vfs: &BrushVfs) -> Option<PathBuf> {
+    vfs.file_names()
+        .filter_map(|p| checkpoint_iteration(&p).map(|iter| (iter, p)))
+        .max_by_key(|(iter, _)| *iter)
+        .map(|(_, path)| path)
+}
+
+async fn read_json<T: serde::de::DeserializeOwned>(vfs: &BrushVfs, path: &Path) -> Result<T> {
+    let mut buf = String::new();
+    vfs.reader_at_path(path)
+        .await?
+        .read_to_string(&mut buf)
+        .await?;
+    serde_json::from_str(&buf).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Parse a 3x4 exposure matrix, mapping rendered colors to the colors of the image.
+fn exposure_transform(rows: &[[f32; 4]; 3]) -> Affine3A {
+    let linear = Mat3::from_cols_array_2d(&rows.map(|r| [r[0], r[1], r[2]])).transpose();
+    let offset = Vec3::new(rows[0][3], rows[1][3], rows[2][3]);
+    Affine3A::from_mat3_translation(linear, offset)
+}
+
+/// The output directory of the INRIA 3DGS implementation, with the images it was trained on.
+pub struct InriaFormat;
+
+impl<B: Backend> DatasetFormat<B> for InriaFormat {
+    fn name(&self) -> &'static str {
+        "INRIA 3DGS output"
+    }
+
+    fn detect(&self, vfs: &BrushVfs) -> bool {
+        find_checkpoint(vfs).is_some()
+    }
+
+    fn load<'a>(
+        &'a self,
+        vfs: Arc<BrushVfs>,
+        load_args: &'a LoadDataseConfig,
+        device: &'a B::Device,
+    ) -> LoadFuture<'a, B> {
+        Box::pin(async move {
+            let checkpoint = find_checkpoint(&vfs).context("No INRIA checkpoint found")?;
+            log::info!("Loading INRIA 3DGS output");
+            load_dataset_inner(vfs, load_args, device, checkpoint).await
+        })
+    }
+}
+
+async fn load_dataset_inner<B: Backend>(
+    vfs: Arc<BrushVfs>,
+    load_args: &LoadDataseConfig,
+    device: &B::Device,
+    checkpoint: PathBuf,
+) -> Result<(DataStream<SplatMessage<B>>, Dataset)> {
+    let base = checkpoint
+        .ancestors()
+        .nth(3)
+        .context("Checkpoint must be in point_cloud/iteration_<N>")?
+        .to_owned();
+
+    let mut cameras: Vec<JsonCamera> = read_json(&vfs, &base.join("cameras.json")).await?;
+    cameras.sort_by(|a, b| a.img_name.cmp(&b.img_name));
+
+    let exposure_path = base.join("exposure.json");
+    let exposures: HashMap<String, [[f32; 4]; 3]> = if vfs.file_names().any(|p| p == exposure_path)
+    {
+        read_json(&vfs, &exposure_path).await?
+    } else {
+        HashMap::new()
+    };
+
+    let mut train_views = vec![];
+    let mut eval_views = vec![];
+
+    for (i, cam) in cameras
+        .iter()
+        .take(load_args.max_frames.unwrap_or(usize::MAX))
+        .step_by(load_args.subsample_frames.unwrap_or(1) as usize)
+        .enumerate()
+    {
+        // Only the name of the image is stored, search for it anywhere.
+        let img_paths: Vec<_> = vfs
+            .file_names()
+            .filter(|p| {
+                p.file_stem().is_some_and(|s| s == cam.img_name.as_str()) && is_image_path(p)
+            })
+            .collect();
+        let path = img_paths
+            .iter()
+            .min()
+            .with_context(|| format!("Failed to find image {}", cam.img_name))?
+            .clone();
+        let mask_path = find_mask_path(&vfs, &path);
+
+        let rotation = Mat3::from_cols_array_2d(&cam.rotation).transpose();
+        let camera = Camera::new(
+            Vec3::from(cam.position),
+            Quat::from_mat3(&rotation),
+            focal_to_fov(cam.fx, cam.width),
+            focal_to_fov(cam.fy, cam.height),
+            glam::vec2(0.5, 0.5),
+        );
+
+        let mut image =
+            LoadImage::new(vfs.clone(), path, mask_path, load_args.max_resolution).await?;
+
+        // The splats were trained to match the image after exposure compensation. Brush
+        // doesn't optimize exposures, so undo it on the image instead.
+        if let Some(exposure) = exposures.get(&cam.img_name) {
+            image = image.with_color_transform(exposure_transform(exposure).inverse());
+        }
+
+        let view = SceneView { camera, image };
+        if load_args
+            .eval_split_every
+            .is_some_and(|every| i % every == 0)
+        {
+            eval_views.push(view);
+        } else {
+            train_views.push(view);
+        }
+    }
+
+    // Continue from the trained splats, or when asked to, the points the training started from.
+    let init_path = if load_args.ignore_dataset_ply {
+        Some(base.join("input.ply")).filter(|p| vfs.file_names().any(|f| f == *p))
+    } else {
+        Some(checkpoint)
+    };
+
+    let device = device.clone();
+    let subsample_points = load_args.subsample_points;
+    let init_stream = try_fn_stream(|emitter| async move {
+        let Some(init_path) = init_path else {
+            return Ok(());
+        };
+        log::info!("Starting from {init_path:?}");
+        let splat_stream = load_splat_from_ply(
+            vfs.reader_at_path(&init_path).await?,
+            subsample_points,
+            false,
+            device,
+        );
+        let mut splat_stream = std::pin::pin!(splat_stream);
+        while let Some(message) = splat_stream.next().await {
+            emitter.emit(message?).await;
+        }
+        Ok(())
+    });
+
+    Ok((
+        Box::pin(init_stream),
+        Dataset::from_views(train_views, eval_views),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkpoint_iterations() {
+        assert_eq!(
+            checkpoint_iteration(Path::new("out/point_cloud/iteration_30000/point_cloud.ply")),
+            Some(30000)
+        );
+        assert_eq!(
+            checkpoint_iteration(Path::new("point_cloud/iteration_7000/point_cloud.ply")),
+            Some(7000)
+        );
+        assert_eq!(checkpoint_iteration(Path::new("out/input.ply")), None);
+        assert_eq!(
+            checkpoint_iteration(Path::new("out/iteration_7000/point_cloud.ply")),
+            None
+        );
+    }
+
+    #[test]
+    fn exposure_is_affine() {
+        let exposure = exposure_transform(&[
+            [2.0, 0.0, 0.0, 0.1],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.5, 1.0, -0.2],
+        ]);
+        let color = exposure.transform_point3(Vec3::new(0.5, 0.2, 0.4));
+        assert!(color.abs_diff_eq(Vec3::new(1.1, 0.2, 0.3), 1e-6));
+    }
+}
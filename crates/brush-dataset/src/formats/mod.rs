@@ -1,12 +1,18 @@
 use crate::{
-    Dataset, LoadDataseConfig, WasmNotSend,
+    ColorSpace, Dataset, LoadDataseConfig, WasmNotSend,
     brush_vfs::BrushVfs,
+    error::DatasetError,
+    hdr, lens,
+    lidar_import::{is_lidar_extension, load_splat_from_lidar},
+    motion::neighbor_frames,
+    scene::{LoadImage, Scene, SceneView},
     splat_import::{SplatMessage, load_splat_from_ply},
 };
 use anyhow::Context;
 use burn::prelude::Backend;
 use path_clean::PathClean;
 use std::{
+    future::Future,
     path::{Path, PathBuf},
     pin::Pin,
     sync::Arc,
@@ -14,49 +20,166 @@ use std::{
 use tokio_stream::Stream;
 
 pub mod colmap;
+pub mod inria;
 pub mod nerfstudio;
 
 pub trait DynStream<Item>: Stream<Item = Item> + WasmNotSend {}
 impl<Item, T: Stream<Item = Item> + WasmNotSend> DynStream<Item> for T {}
 pub type DataStream<T> = Pin<Box<dyn DynStream<anyhow::Result<T>> + 'static>>;
 
+pub trait DynFuture<Output>: Future<Output = Output> + WasmNotSend {}
+impl<Output, T: Future<Output = Output> + WasmNotSend> DynFuture<Output> for T {}
+/// Loading of a dataset, with the splats to start training from and the views.
+pub type LoadFuture<'a, B> =
+    Pin<Box<dyn DynFuture<anyhow::Result<(DataStream<SplatMessage<B>>, Dataset)>> + 'a>>;
+
+/// A layout of dataset that can be trained on, eg. COLMAP.
+///
+/// [`load_dataset`] loads the dataset with the first of its formats that detects it, so new
+/// formats can be added on their own, without changing the others.
+pub trait DatasetFormat<B: Backend>: Send + Sync {
+    /// Name of the format, eg. for errors.
+    fn name(&self) -> &'static str;
+
+    /// Whether the files in `vfs` are laid out in this format.
+    fn detect(&self, vfs: &BrushVfs) -> bool;
+
+    /// Load the dataset, only called when [`Self::detect`] found the format.
+    fn load<'a>(
+        &'a self,
+        vfs: Arc<BrushVfs>,
+        load_args: &'a LoadDataseConfig,
+        device: &'a B::Device,
+    ) -> LoadFuture<'a, B>;
+}
+
+/// The formats built into Brush, in the order they're tried.
+pub fn builtin_formats<B: Backend>() -> Vec<Arc<dyn DatasetFormat<B>>> {
+    // INRIA outputs have json files too, check for them first.
+    vec![
+        Arc::new(inria::InriaFormat),
+        Arc::new(nerfstudio::NerfstudioFormat),
+        Arc::new(colmap::ColmapFormat),
+    ]
+}
+
+/// Load the dataset in `vfs`, with the first of `formats` that detects it.
 pub async fn load_dataset<B: Backend>(
     vfs: Arc<BrushVfs>,
+    formats: &[Arc<dyn DatasetFormat<B>>],
     load_args: &LoadDataseConfig,
     device: &B::Device,
-) -> anyhow::Result<(DataStream<SplatMessage<B>>, Dataset)> {
-    let data_read = nerfstudio::read_dataset(vfs.clone(), load_args, device).await;
+) -> Result<(DataStream<SplatMessage<B>>, Dataset), DatasetError> {
+    let format = formats
+        .iter()
+        .find(|format| format.detect(&vfs))
+        .ok_or(DatasetError::UnknownLayout)?;
+    let data_read = format
+        .load(vfs.clone(), load_args, device)
+        .await
+        .with_context(|| format!("Failed to load {}.", format.name()))?;
+
+    // If there's an initial ply file or LiDAR scan, override the init stream with that.
+    let init_count = vfs.file_names().filter(|p| is_init_file(p)).count();
 
-    let data_read = if let Some(data_read) = data_read {
-        data_read.context("Failed to load as json format.")?
+    let init_stream = if init_count == 1 && !load_args.ignore_dataset_ply {
+        load_init_ply(vfs.clone(), load_args, device).await?
     } else {
-        let stream = colmap::load_dataset::<B>(vfs.clone(), load_args, device)
-            .await
-            .context("Dataset was neither in nerfstudio or COLMAP format.")?;
-        stream.context("Failed to load as COLMAP format.")?
+        data_read.0
+    };
+
+    let mut dataset = data_read.1;
+    if let Some(merged) = hdr::merge_brackets(&dataset).await {
+        dataset = merged;
+    }
+
+    if load_args.color_space == ColorSpace::Linear {
+        dataset = map_images(&dataset, |image| image.with_color_space(ColorSpace::Linear));
+    }
+
+    if let Some(option) = &load_args.lens_profile {
+        let profile = if option == lens::ESTIMATE_PROFILE {
+            lens::estimate_profile(&dataset.train.views).await?
+        } else {
+            lens::read_profile(&vfs, option).await?
+        };
+        dataset = map_images(&dataset, |image| image.with_lens(profile.clone()));
+    }
+
+    if load_args.motion_masks {
+        let frames = neighbor_frames(&dataset);
+        dataset = map_images(&dataset, |image| match frames.get(&image.path) {
+            Some(motion) if image.pano_face.is_none() && image.hdr.is_none() => {
+                image.with_motion_mask(motion.clone())
+            }
+            _ => image.clone(),
+        });
+    }
+
+    Ok((init_stream, dataset))
+}
+
+/// Copy of the dataset with the loading of every image changed by `f`.
+fn map_images(dataset: &Dataset, f: impl Fn(&LoadImage) -> LoadImage) -> Dataset {
+    let map_scene = |scene: &Scene| {
+        Scene::new(
+            scene
+                .views
+                .iter()
+                .map(|view| SceneView {
+                    image: f(&view.image),
+                    camera: view.camera.clone(),
+                })
+                .collect(),
+        )
     };
+    Dataset {
+        train: map_scene(&dataset.train),
+        eval: dataset.eval.as_ref().map(map_scene),
+    }
+}
+
+fn is_init_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("ply") || is_lidar_extension(ext))
+}
+
+/// Load the splats of the only .ply file or LiDAR scan in the vfs, to start training from.
+pub async fn load_init_ply<B: Backend>(
+    vfs: Arc<BrushVfs>,
+    load_args: &LoadDataseConfig,
+    device: &B::Device,
+) -> Result<DataStream<SplatMessage<B>>, DatasetError> {
+    let paths: Vec<_> = vfs.file_names().filter(|p| is_init_file(p)).collect();
 
-    // If there's an initial ply file, override the init stream with that.
-    let path: Vec<_> = vfs
-        .file_names()
-        .filter(|x| x.extension().is_some_and(|ext| ext == "ply"))
-        .collect();
+    let [main_path] = paths.as_slice() else {
+        return Err(DatasetError::InitFileCount { found: paths.len() });
+    };
+    log::info!("Using {main_path:?} as initial point cloud.");
 
-    let init_stream = if path.len() == 1 {
-        let main_path = path.first().expect("unreachable");
-        log::info!("Using ply {main_path:?} as initial point cloud.");
+    let reader = vfs.reader_at_path(main_path).await?;
+    let extension = main_path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_owned();
 
-        let reader = vfs.reader_at_path(main_path).await?;
-        Box::pin(load_splat_from_ply(
+    if is_lidar_extension(&extension) {
+        Ok(Box::pin(load_splat_from_lidar(
             reader,
+            extension,
             load_args.subsample_points,
             device.clone(),
-        ))
+        )))
     } else {
-        data_read.0
-    };
-
-    Ok((init_stream, data_read.1))
+        Ok(Box::pin(load_splat_from_ply(
+            reader,
+            load_args.subsample_points,
+            false,
+            device.clone(),
+        )))
+    }
 }
 
 fn find_mask_path(vfs: &BrushVfs, path: &Path) -> Option<PathBuf> {
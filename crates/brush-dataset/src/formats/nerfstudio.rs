@@ -1,18 +1,20 @@
 use super::DataStream;
 use super::find_mask_path;
+use super::{DatasetFormat, LoadFuture};
 use crate::Dataset;
 use crate::LoadDataseConfig;
 use crate::brush_vfs::BrushVfs;
+use crate::pano::{PanoFaces, pano_faces};
 use crate::scene::LoadImage;
 use crate::scene::SceneView;
 use crate::splat_import::SplatMessage;
 use crate::splat_import::load_splat_from_ply;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use async_fn_stream::try_fn_stream;
 use brush_render::camera::fov_to_focal;
 use brush_render::camera::{Camera, focal_to_fov};
 use burn::prelude::Backend;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::io::AsyncReadExt;
 use tokio_stream::StreamExt;
@@ -30,7 +32,7 @@ struct JsonScene {
     /// Focal length y
     fl_y: Option<f64>,
 
-    // Not really used atm.
+    /// Only EQUIRECTANGULAR is treated specially, as 360° panoramas.
     camera_model: Option<String>,
     // Nerfstudio doesn't mention this in their format? But fine to include really.
     ply_file_path: Option<String>,
@@ -100,12 +102,99 @@ struct FrameData {
     file_path: String,
 }
 
+/// Position and rotation of a frame, from its camera to world matrix.
+fn frame_pose(frame: &FrameData) -> Result<(glam::Quat, glam::Vec3)> {
+    // NeRF 'transform_matrix' is a camera-to-world transform
+    let transform_matrix: Vec<f32> = frame.transform_matrix.iter().flatten().copied().collect();
+    if transform_matrix.len() != 16 {
+        anyhow::bail!(
+            "Transform matrix of {} should be 4x4, got {} values",
+            frame.file_path,
+            transform_matrix.len()
+        );
+    }
+    let mut transform = glam::Mat4::from_cols_slice(&transform_matrix).transpose();
+    // Swap basis to match camera format and reconstrunstion ply (if included).
+    transform.y_axis *= -1.0;
+    transform.z_axis *= -1.0;
+    let (_, rotation, translation) = transform.to_scale_rotation_translation();
+    Ok((rotation, translation))
+}
+
+/// Camera of a frame that isn't a panorama. The image size is used for intrinsics that
+/// aren't in the file.
+fn frame_camera(scene: &JsonScene, frame: &FrameData, image_size: glam::UVec2) -> Result<Camera> {
+    let (rotation, translation) = frame_pose(frame)?;
+
+    let w = frame.w.or(scene.w).unwrap_or(image_size.x as f64) as u32;
+    let h = frame.h.or(scene.h).unwrap_or(image_size.y as f64) as u32;
+
+    let fovx = frame
+        .camera_angle_x
+        .or(frame.fl_x.map(|fx| focal_to_fov(fx, w)))
+        .or(scene.camera_angle_x)
+        .or(scene.fl_x.map(|fx| focal_to_fov(fx, w)));
+
+    let fovy = frame
+        .camera_angle_y
+        .or(frame.fl_y.map(|fy| focal_to_fov(fy, h)))
+        .or(scene.camera_angle_y)
+        .or(scene.fl_y.map(|fy| focal_to_fov(fy, h)));
+
+    let (fovx, fovy) = match (fovx, fovy) {
+        (None, None) => anyhow::bail!("Must have some kind of focal length"),
+        (None, Some(fovy)) => {
+            let fovx = focal_to_fov(fov_to_focal(fovy, h), w);
+            (fovx, fovy)
+        }
+        (Some(fovx), None) => {
+            let fovy = focal_to_fov(fov_to_focal(fovx, w), h);
+            (fovx, fovy)
+        }
+        (Some(fovx), Some(fovy)) => (fovx, fovy),
+    };
+
+    let cx = frame.cx.or(scene.cx).unwrap_or(w as f64 / 2.0);
+    let cy = frame.cy.or(scene.cy).unwrap_or(h as f64 / 2.0);
+
+    let cuv = glam::vec2((cx / w as f64) as f32, (cy / h as f64) as f32);
+
+    Ok(Camera::new(translation, rotation, fovx, fovy, cuv))
+}
+
+/// Cameras of the frames of a transforms file, without loading their images. Intrinsics that
+/// aren't in the file are taken from `image_size`. Panorama frames give no cameras.
+///
+/// Meant for checking transforms files on their own, eg. when fuzzing the parser.
+pub fn parse_cameras(json: &[u8], image_size: glam::UVec2) -> Result<Vec<Camera>> {
+    let scene: JsonScene = serde_json::from_slice(json)?;
+    if scene
+        .camera_model
+        .as_deref()
+        .is_some_and(|m| m.eq_ignore_ascii_case("EQUIRECTANGULAR"))
+    {
+        return Ok(vec![]);
+    }
+    scene
+        .frames
+        .iter()
+        .map(|frame| frame_camera(&scene, frame, image_size))
+        .collect()
+}
+
+/// Read the views of each frame. Panorama frames are split up into multiple views.
 async fn read_transforms_file(
     scene: JsonScene,
     transforms_path: &Path,
     vfs: Arc<BrushVfs>,
     load_args: &LoadDataseConfig,
-) -> anyhow::Result<Vec<SceneView>> {
+) -> anyhow::Result<Vec<Vec<SceneView>>> {
+    let is_pano = scene
+        .camera_model
+        .as_deref()
+        .is_some_and(|m| m.eq_ignore_ascii_case("EQUIRECTANGULAR"));
+    let pano_faces = pano_faces(load_args.pano_views, load_args.pano_fov);
+
     let mut results = vec![];
     for frame in scene
         .frames
@@ -113,14 +202,6 @@ async fn read_transforms_file(
         .take(load_args.max_frames.unwrap_or(usize::MAX))
         .step_by(load_args.subsample_frames.unwrap_or(1) as usize)
     {
-        // NeRF 'transform_matrix' is a camera-to-world transform
-        let transform_matrix: Vec<f32> = frame.transform_matrix.iter().flatten().copied().collect();
-        let mut transform = glam::Mat4::from_cols_slice(&transform_matrix).transpose();
-        // Swap basis to match camera format and reconstrunstion ply (if included).
-        transform.y_axis *= -1.0;
-        transform.z_axis *= -1.0;
-        let (_, rotation, translation) = transform.to_scale_rotation_translation();
-
         // Read the imageat the specified path, fallback to default .png extension.
         let mut path = transforms_path
             .parent()
@@ -135,55 +216,38 @@ async fn read_transforms_file(
 
         let image = LoadImage::new(vfs.clone(), path, mask_path, load_args.max_resolution).await?;
 
-        let w = frame.w.or(scene.w).unwrap_or(image.width() as f64) as u32;
-        let h = frame.h.or(scene.h).unwrap_or(image.height() as f64) as u32;
-
-        let fovx = frame
-            .camera_angle_x
-            .or(frame.fl_x.map(|fx| focal_to_fov(fx, w)))
-            .or(scene.camera_angle_x)
-            .or(scene.fl_x.map(|fx| focal_to_fov(fx, w)));
-
-        let fovy = frame
-            .camera_angle_y
-            .or(frame.fl_y.map(|fy| focal_to_fov(fy, h)))
-            .or(scene.camera_angle_y)
-            .or(scene.fl_y.map(|fy| focal_to_fov(fy, h)));
-
-        let (fovx, fovy) = match (fovx, fovy) {
-            (None, None) => anyhow::bail!("Must have some kind of focal length"),
-            (None, Some(fovy)) => {
-                let fovx = focal_to_fov(fov_to_focal(fovy, h), w);
-                (fovx, fovy)
-            }
-            (Some(fovx), None) => {
-                let fovy = focal_to_fov(fov_to_focal(fovx, w), h);
-                (fovx, fovy)
-            }
-            (Some(fovx), Some(fovy)) => (fovx, fovy),
-        };
-
-        let cx = frame.cx.or(scene.cx).unwrap_or(w as f64 / 2.0);
-        let cy = frame.cy.or(scene.cy).unwrap_or(h as f64 / 2.0);
-
-        let cuv = glam::vec2((cx / w as f64) as f32, (cy / h as f64) as f32);
+        if is_pano {
+            let (rotation, translation) = frame_pose(frame)?;
+            let shared = Arc::new(PanoFaces::new(pano_faces.clone()));
+            let views = pano_faces
+                .iter()
+                .map(|face| SceneView {
+                    image: image.with_pano_face(*face, &shared),
+                    camera: Camera::new(
+                        translation,
+                        rotation * face.rotation,
+                        face.fov,
+                        face.fov,
+                        glam::vec2(0.5, 0.5),
+                    ),
+                })
+                .collect();
+            results.push(views);
+            continue;
+        }
 
+        let image_size = glam::uvec2(image.width(), image.height());
         let view = SceneView {
+            camera: frame_camera(&scene, frame, image_size)?,
             image,
-            camera: Camera::new(translation, rotation, fovx, fovy, cuv),
         };
-        results.push(view);
+        results.push(vec![view]);
     }
     Ok(results)
 }
 
-pub async fn read_dataset<B: Backend>(
-    vfs: Arc<BrushVfs>,
-    load_args: &LoadDataseConfig,
-    device: &B::Device,
-) -> Option<Result<(DataStream<SplatMessage<B>>, Dataset)>> {
-    log::info!("Loading nerfstudio dataset");
-
+/// All json files, and the transforms file of the views to train on, if there is one.
+fn find_transforms(vfs: &BrushVfs) -> Option<(Vec<PathBuf>, PathBuf)> {
     let json_files: Vec<_> = vfs
         .file_names()
         .filter(|n| n.extension().is_some_and(|p| p == "json"))
@@ -200,8 +264,34 @@ pub async fn read_dataset<B: Backend>(
             })?
             .clone()
     };
+    Some((json_files, transforms_path))
+}
+
+/// A nerfstudio or blender dataset, with the cameras in a `transforms.json` file.
+pub struct NerfstudioFormat;
+
+impl<B: Backend> DatasetFormat<B> for NerfstudioFormat {
+    fn name(&self) -> &'static str {
+        "nerfstudio dataset"
+    }
 
-    Some(read_dataset_inner(vfs, load_args, device, json_files, transforms_path).await)
+    fn detect(&self, vfs: &BrushVfs) -> bool {
+        find_transforms(vfs).is_some()
+    }
+
+    fn load<'a>(
+        &'a self,
+        vfs: Arc<BrushVfs>,
+        load_args: &'a LoadDataseConfig,
+        device: &'a B::Device,
+    ) -> LoadFuture<'a, B> {
+        Box::pin(async move {
+            log::info!("Loading nerfstudio dataset");
+            let (json_files, transforms_path) =
+                find_transforms(&vfs).context("No transforms json file found")?;
+            read_dataset_inner(vfs, load_args, device, json_files, transforms_path).await
+        })
+    }
 }
 
 async fn read_dataset_inner<B: Backend>(
@@ -246,23 +336,25 @@ async fn read_dataset_inner<B: Backend>(
             .read_to_string(&mut json_str)
             .await?;
         let val_scene = serde_json::from_str(&json_str)?;
-        Some(read_transforms_file(val_scene, eval_trans_path, vfs.clone(), load_args).await?)
+        let val_views = read_transforms_file(val_scene, eval_trans_path, vfs.clone(), load_args);
+        Some(val_views.await?.into_iter().flatten().collect::<Vec<_>>())
     } else {
         None
     };
 
     let mut train_views = vec![];
     let mut eval_views = vec![];
-    for (i, view) in train_handles.into_iter().enumerate() {
+    // Split by frame, so all views of a panorama end up on the same side.
+    for (i, views) in train_handles.into_iter().enumerate() {
         if let Some(eval_period) = load_args.eval_split_every {
             // Include extra eval images only when the dataset doesn't have them.
             if i % eval_period == 0 && val_views.is_none() {
-                eval_views.push(view);
+                eval_views.extend(views);
             } else {
-                train_views.push(view);
+                train_views.extend(views);
             }
         } else {
-            train_views.push(view);
+            train_views.extend(views);
         }
     }
 
@@ -285,8 +377,12 @@ async fn read_dataset_inner<B: Backend>(
             let ply_data = vfs.reader_at_path(&init_path).await;
 
             if let Ok(ply_data) = ply_data {
-                let splat_stream =
-                    load_splat_from_ply(ply_data, load_args.subsample_points, device.clone());
+                let splat_stream = load_splat_from_ply(
+                    ply_data,
+                    load_args.subsample_points,
+                    false,
+                    device.clone(),
+                );
 
                 let mut splat_stream = std::pin::pin!(splat_stream);
 
@@ -301,3 +397,26 @@ async fn read_dataset_inner<B: Backend>(
 
     Ok((Box::pin(splat_stream), dataset))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::parse_cameras;
+
+    #[test]
+    fn malformed_transforms_are_errors() {
+        let size = glam::uvec2(64, 48);
+        let json = |matrix: &str| {
+            format!(
+                r#"{{"camera_angle_x": 0.8, "frames": [{{"file_path": "a.png", "transform_matrix": {matrix}}}]}}"#
+            )
+        };
+
+        let identity = "[[1,0,0,0],[0,1,0,0],[0,0,1,0],[0,0,0,1]]";
+        let cameras = parse_cameras(json(identity).as_bytes(), size).expect("Valid transforms");
+        assert_eq!(cameras.len(), 1);
+
+        assert!(parse_cameras(json("[[1,0,0],[0,1,0]]").as_bytes(), size).is_err());
+        assert!(parse_cameras(json("[]").as_bytes(), size).is_err());
+        assert!(parse_cameras(b"{\"frames\": 3}", size).is_err());
+    }
+}
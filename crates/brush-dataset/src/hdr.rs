@@ -0,0 +1,292 @@
+//! Merging exposure brackets into linear HDR training images.
+//!
+//! Brackets are images shot from the same pose at different exposures. Merged, they keep
+//! detail in both the shadows and the highlights, eg. of an interior with windows.
+
+use std::{io::Cursor, path::PathBuf};
+
+use anyhow::Result;
+use image::{DynamicImage, GenericImageView, Rgba32FImage};
+use tokio::io::AsyncReadExt;
+
+use crate::{
+    Dataset,
+    brush_vfs::BrushVfs,
+    scene::{Scene, SceneView, decode_image},
+};
+
+/// EXIF data of JPEGs is stored in the first segment, which is at most 64kb.
+const EXIF_READ_SIZE: u64 = 96 * 1024;
+
+/// Views closer than this, relative to the size of the camera bounds, can be brackets.
+const MAX_BRACKET_DISTANCE: f32 = 1e-3;
+const MAX_BRACKET_ANGLE_DEG: f32 = 2.0;
+
+/// One exposure of a bracketed set.
+#[derive(Clone, Debug)]
+pub struct Bracket {
+    pub path: PathBuf,
+    /// Relative amount of light the image captured.
+    pub exposure: f32,
+}
+
+/// Images to merge into one linear HDR image when loading.
+#[derive(Clone, Debug)]
+pub struct HdrMerge {
+    pub brackets: Vec<Bracket>,
+    /// Exposure that maps to the usual [0, 1] range. Brighter parts go above 1.
+    pub reference_exposure: f32,
+}
+
+impl HdrMerge {
+    pub async fn load(&self, vfs: &BrushVfs) -> Result<DynamicImage> {
+        let mut images = vec![];
+        for bracket in &self.brackets {
+            let mut bytes = vec![];
+            vfs.reader_at_path(&bracket.path)
+                .await?
+                .read_to_end(&mut bytes)
+                .await?;
+            images.push((decode_image(&bytes, vfs.limits())?, bracket.exposure));
+        }
+        Ok(merge_exposures(&images, self.reference_exposure))
+    }
+}
+
+/// Relative amount of light an image captured, going by its EXIF data: the exposure time
+/// times the ISO, divided by the f-number squared.
+pub fn exposure_from_exif(data: &[u8]) -> Option<f32> {
+    let exif = exif::Reader::new()
+        .read_from_container(&mut Cursor::new(data))
+        .ok()?;
+    let rational = |tag| match &exif.get_field(tag, exif::In::PRIMARY)?.value {
+        exif::Value::Rational(v) => v.first().map(|r| r.to_f64()),
+        _ => None,
+    };
+
+    let time = rational(exif::Tag::ExposureTime)?;
+    let f_number = rational(exif::Tag::FNumber).unwrap_or(1.0);
+    let iso = exif
+        .get_field(exif::Tag::PhotographicSensitivity, exif::In::PRIMARY)
+        .and_then(|f| f.value.get_uint(0))
+        .unwrap_or(100) as f64;
+
+    let exposure = time * iso / (f_number * f_number);
+    (exposure.is_finite() && exposure > 0.0).then_some(exposure as f32)
+}
+
+async fn read_exposure(vfs: &BrushVfs, path: &std::path::Path) -> Option<f32> {
+    let mut data = vec![];
+    vfs.reader_at_path(path)
+        .await
+        .ok()?
+        .take(EXIF_READ_SIZE)
+        .read_to_end(&mut data)
+        .await
+        .ok()?;
+    exposure_from_exif(&data)
+}
+
+pub(crate) fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub(crate) fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// Trust mid tones most, and ignore clipped values.
+fn bracket_weight(v: f32) -> f32 {
+    if v <= 0.002 || v >= 0.998 {
+        0.0
+    } else {
+        1.0 - (2.0 * v - 1.0).powi(12)
+    }
+}
+
+/// Merge images of the same view at different exposures into one linear image, scaled so
+/// `reference_exposure` maps to the usual [0, 1] range. Alpha is taken from the first image.
+pub fn merge_exposures(images: &[(DynamicImage, f32)], reference_exposure: f32) -> DynamicImage {
+    let (first, _) = &images[0];
+    let (width, height) = first.dimensions();
+    let has_alpha = first.color().has_alpha();
+
+    let layers: Vec<_> = images
+        .iter()
+        .map(|(img, exposure)| {
+            let img = if img.dimensions() == (width, height) {
+                img.to_rgba32f()
+            } else {
+                img.resize_exact(width, height, image::imageops::FilterType::Triangle)
+                    .to_rgba32f()
+            };
+            (img, *exposure)
+        })
+        .collect();
+    let darkest = layers
+        .iter()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .expect("Need at least one image");
+    let brightest = layers
+        .iter()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .expect("Need at least one image");
+
+    let merged = Rgba32FImage::from_fn(width, height, |x, y| {
+        let mut out = [0.0, 0.0, 0.0, layers[0].0.get_pixel(x, y)[3]];
+        for (c, out) in out.iter_mut().take(3).enumerate() {
+            let (mut sum, mut weight) = (0.0, 0.0);
+            for (layer, exposure) in &layers {
+                let v = layer.get_pixel(x, y)[c];
+                let w = bracket_weight(v);
+                sum += w * srgb_to_linear(v) / exposure;
+                weight += w;
+            }
+
+            let radiance = if weight > 0.0 {
+                sum / weight
+            } else {
+                // Clipped in every exposure. Bright pixels are closest in the darkest
+                // exposure, dark ones in the brightest.
+                let (layer, exposure) = if layers[0].0.get_pixel(x, y)[c] > 0.5 {
+                    darkest
+                } else {
+                    brightest
+                };
+                srgb_to_linear(layer.get_pixel(x, y)[c]) / exposure
+            };
+            *out = radiance * reference_exposure;
+        }
+        image::Rgba(out)
+    });
+
+    if has_alpha {
+        DynamicImage::ImageRgba32F(merged)
+    } else {
+        DynamicImage::ImageRgb32F(DynamicImage::ImageRgba32F(merged).into_rgb32f())
+    }
+}
+
+fn is_same_pose(a: &SceneView, b: &SceneView, max_distance: f32) -> bool {
+    a.camera.position.distance(b.camera.position) <= max_distance
+        && a.camera.rotation.angle_between(b.camera.rotation) <= MAX_BRACKET_ANGLE_DEG.to_radians()
+}
+
+/// If the dataset has exposure brackets, merge each set of brackets into one HDR view.
+///
+/// All views are then loaded as linear colors, scaled to the median exposure, so the splats
+/// are trained in linear space. Returns `None` if there are no brackets, or the images have
+/// no exposure info.
+pub async fn merge_brackets(dataset: &Dataset) -> Option<Dataset> {
+    let eval_views = dataset.eval.iter().flat_map(|e| e.views.iter());
+    let views: Vec<(&SceneView, bool)> = dataset
+        .train
+        .views
+        .iter()
+        .map(|v| (v, false))
+        .chain(eval_views.map(|v| (v, true)))
+        .collect();
+
+    let mut exposures = vec![];
+    for (view, _) in &views {
+        exposures.push(read_exposure(&view.image.vfs, &view.image.path).await?);
+    }
+
+    let bounds = dataset.train.bounds();
+    let max_distance = bounds.extent.length() * 2.0 * MAX_BRACKET_DISTANCE;
+
+    // Group views that are shot from the same pose, at different exposures.
+    let mut groups: Vec<Vec<usize>> = vec![];
+    for (i, (view, _)) in views.iter().enumerate() {
+        let group = groups.iter_mut().find(|group| {
+            is_same_pose(views[group[0]].0, view, max_distance)
+                && group.iter().all(|&j| {
+                    let ratio = exposures[i] / exposures[j];
+                    !(0.8..1.25).contains(&ratio)
+                })
+        });
+        match group {
+            Some(group) => group.push(i),
+            None => groups.push(vec![i]),
+        }
+    }
+
+    let num_bracketed = groups.iter().filter(|g| g.len() > 1).count();
+    if num_bracketed == 0 {
+        return None;
+    }
+    log::info!("Merging {num_bracketed} sets of exposure brackets, training in linear color");
+
+    let mut sorted = exposures.clone();
+    sorted.sort_by(f32::total_cmp);
+    let reference_exposure = sorted[sorted.len() / 2];
+
+    let (mut train, mut eval) = (vec![], vec![]);
+    for mut group in groups {
+        // The exposure closest to the reference is the main view of the set.
+        group.sort_by(|&a, &b| {
+            let dist = |i: usize| (exposures[i] / reference_exposure).ln().abs();
+            dist(a).total_cmp(&dist(b))
+        });
+        let (main, is_eval) = views[group[0]];
+
+        let hdr = HdrMerge {
+            brackets: group
+                .iter()
+                .map(|&i| Bracket {
+                    path: views[i].0.image.path.clone(),
+                    exposure: exposures[i],
+                })
+                .collect(),
+            reference_exposure,
+        };
+        let view = SceneView {
+            image: main.image.with_hdr(hdr),
+            camera: main.camera.clone(),
+        };
+        if is_eval {
+            eval.push(view);
+        } else {
+            train.push(view);
+        }
+    }
+
+    Some(Dataset {
+        train: Scene::new(train),
+        eval: (!eval.is_empty()).then(|| Scene::new(eval)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Rgb32FImage};
+
+    use super::{linear_to_srgb, merge_exposures, srgb_to_linear};
+
+    #[test]
+    fn brackets_recover_highlights() {
+        // A dark pixel and one 3x brighter than what the reference exposure can hold.
+        let radiance = [0.02, 3.0];
+        let shoot = |exposure: f32| {
+            let img = Rgb32FImage::from_fn(2, 1, |x, _| {
+                let v = linear_to_srgb((radiance[x as usize] * exposure).min(1.0));
+                image::Rgb([v; 3])
+            });
+            (DynamicImage::ImageRgb32F(img), exposure)
+        };
+
+        let merged = merge_exposures(&[shoot(1.0), shoot(0.25), shoot(4.0)], 1.0).into_rgb32f();
+        for (pixel, expected) in merged.pixels().zip(radiance) {
+            assert!((pixel[0] - expected).abs() < expected * 0.01);
+        }
+        assert!((srgb_to_linear(linear_to_srgb(0.5)) - 0.5).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,102 @@
+//! HEIC/HEIF images, as exported by iPhones.
+//!
+//! The `image` crate can't read these. Decoding uses libheif, behind the `heif` feature, as
+//! it needs the library installed.
+
+use anyhow::Result;
+use image::{ColorType, DynamicImage};
+use std::path::Path;
+
+/// Brands of the `ftyp` box of HEIF images and image sequences.
+const BRANDS: [&[u8; 4]; 8] = [
+    b"heic", b"heix", b"hevc", b"hevx", b"heim", b"heis", b"mif1", b"msf1",
+];
+
+/// Whether the file starts like a HEIF image.
+pub fn is_heif(bytes: &[u8]) -> bool {
+    bytes.len() >= 12
+        && &bytes[4..8] == b"ftyp"
+        && BRANDS.iter().any(|brand| bytes[8..12] == brand[..])
+}
+
+pub fn is_heif_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| ["heic", "heif"].contains(&e.to_lowercase().as_str()))
+}
+
+#[cfg(feature = "heif")]
+pub(crate) fn image_data(bytes: &[u8]) -> Result<(glam::UVec2, ColorType)> {
+    let ctx = libheif_rs::HeifContext::read_from_bytes(bytes)?;
+    let handle = ctx.primary_image_handle()?;
+    let color = if handle.has_alpha_channel() {
+        ColorType::Rgba8
+    } else {
+        ColorType::Rgb8
+    };
+    Ok((glam::uvec2(handle.width(), handle.height()), color))
+}
+
+#[cfg(feature = "heif")]
+pub(crate) fn decode(bytes: &[u8]) -> Result<DynamicImage> {
+    use anyhow::Context;
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let ctx = HeifContext::read_from_bytes(bytes)?;
+    let handle = ctx.primary_image_handle()?;
+    let has_alpha = handle.has_alpha_channel();
+    let chroma = if has_alpha {
+        RgbChroma::Rgba
+    } else {
+        RgbChroma::Rgb
+    };
+    let image = LibHeif::new().decode(&handle, ColorSpace::Rgb(chroma), None)?;
+    let plane = image
+        .planes()
+        .interleaved
+        .context("Decoded HEIF image has no pixels")?;
+
+    // Rows can be padded, copy out just the pixels.
+    let row_bytes = plane.width as usize * if has_alpha { 4 } else { 3 };
+    let data: Vec<u8> = plane
+        .data
+        .chunks(plane.stride)
+        .flat_map(|row| &row[..row_bytes])
+        .copied()
+        .collect();
+
+    let img = if has_alpha {
+        image::RgbaImage::from_raw(plane.width, plane.height, data).map(DynamicImage::from)
+    } else {
+        image::RgbImage::from_raw(plane.width, plane.height, data).map(DynamicImage::from)
+    };
+    img.context("Decoded HEIF image has the wrong size")
+}
+
+#[cfg(not(feature = "heif"))]
+pub(crate) fn image_data(_bytes: &[u8]) -> Result<(glam::UVec2, ColorType)> {
+    anyhow::bail!("HEIC images need Brush to be built with the `heif` feature");
+}
+
+#[cfg(not(feature = "heif"))]
+pub(crate) fn decode(_bytes: &[u8]) -> Result<DynamicImage> {
+    anyhow::bail!("HEIC images need Brush to be built with the `heif` feature");
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{is_heif, is_heif_path};
+
+    #[test]
+    fn detects_heif() {
+        assert!(is_heif(b"\0\0\0\x18ftypheic\0\0\0\0mif1heic"));
+        assert!(is_heif(b"\0\0\0\x18ftypmif1\0\0\0\0"));
+        assert!(!is_heif(b"\0\0\0\x1cftypavif\0\0\0\0"));
+        assert!(!is_heif(b"\x89PNG\r\n\x1a\n"));
+
+        assert!(is_heif_path(Path::new("images/IMG_0001.HEIC")));
+        assert!(!is_heif_path(Path::new("images/IMG_0001.jpg")));
+    }
+}
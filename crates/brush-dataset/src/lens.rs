@@ -0,0 +1,232 @@
+//! Correcting lens vignetting and lateral chromatic aberration when loading images.
+//!
+//! Without this, the darker corners and color fringes of a lens differ per view, and the
+//! splats can only explain them with view dependent colors.
+
+use anyhow::{Context, Result};
+use image::{DynamicImage, GenericImageView, Rgba32FImage};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+
+use crate::{
+    brush_vfs::BrushVfs,
+    hdr::{linear_to_srgb, srgb_to_linear},
+    scene::SceneView,
+};
+
+/// Value of the lens profile option to estimate the vignetting from the images.
+pub const ESTIMATE_PROFILE: &str = "estimate";
+
+/// Number of views the vignetting is estimated from.
+const ESTIMATE_VIEWS: usize = 32;
+const ESTIMATE_BINS: usize = 16;
+
+/// Lens distortions to correct. The radius `r` is 0 in the center of the image, and 1 in
+/// the corners.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LensProfile {
+    /// Brightness falls off by `1 + k1 r² + k2 r⁴ + k3 r⁶`, as in the lensfun `pa` model.
+    pub vignetting: [f32; 3],
+    /// Size of the red channel relative to green, eg. 1.001 if red is magnified.
+    pub ca_red: f32,
+    /// Size of the blue channel relative to green.
+    pub ca_blue: f32,
+}
+
+impl Default for LensProfile {
+    fn default() -> Self {
+        Self {
+            vignetting: [0.0; 3],
+            ca_red: 1.0,
+            ca_blue: 1.0,
+        }
+    }
+}
+
+/// Bilinear sample of one channel, clamping to the edges.
+fn sample(img: &Rgba32FImage, x: f32, y: f32, c: usize) -> f32 {
+    let x = x.clamp(0.0, (img.width() - 1) as f32);
+    let y = y.clamp(0.0, (img.height() - 1) as f32);
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = (
+        (x0 + 1).min(img.width() - 1),
+        (y0 + 1).min(img.height() - 1),
+    );
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+    let top = img.get_pixel(x0, y0)[c] * (1.0 - fx) + img.get_pixel(x1, y0)[c] * fx;
+    let bottom = img.get_pixel(x0, y1)[c] * (1.0 - fx) + img.get_pixel(x1, y1)[c] * fx;
+    top * (1.0 - fy) + bottom * fy
+}
+
+impl LensProfile {
+    fn vignetting_at(&self, r2: f32) -> f32 {
+        let [k1, k2, k3] = self.vignetting;
+        1.0 + r2 * (k1 + r2 * (k2 + r2 * k3))
+    }
+
+    /// Remove the distortions from an image. `linear` images are corrected as is, others
+    /// are taken to be sRGB encoded.
+    pub fn correct(&self, img: DynamicImage, linear: bool) -> DynamicImage {
+        if *self == Self::default() {
+            return img;
+        }
+
+        let color = img.color();
+        let (width, height) = img.dimensions();
+        let mut src = img.into_rgba32f();
+        if !linear {
+            for pixel in src.pixels_mut() {
+                pixel.0[..3]
+                    .iter_mut()
+                    .for_each(|c| *c = srgb_to_linear(*c));
+            }
+        }
+
+        let center = glam::vec2(width as f32, height as f32) / 2.0;
+        let inv_corner = 1.0 / center.length();
+        let scales = [self.ca_red, 1.0, self.ca_blue];
+
+        let corrected = Rgba32FImage::from_fn(width, height, |x, y| {
+            let offset = glam::vec2(x as f32 + 0.5, y as f32 + 0.5) - center;
+            let gain = 1.0 / self.vignetting_at((offset * inv_corner).length_squared());
+            let mut out = src.get_pixel(x, y).0;
+            for (c, scale) in scales.into_iter().enumerate() {
+                // A magnified channel shows this point further out from the center.
+                let p = center + offset * scale - 0.5;
+                let value = if scale == 1.0 {
+                    out[c]
+                } else {
+                    sample(&src, p.x, p.y, c)
+                };
+                let value = value * gain;
+                out[c] = if linear { value } else { linear_to_srgb(value) };
+            }
+            image::Rgba(out)
+        });
+
+        let corrected = DynamicImage::ImageRgba32F(corrected);
+        match (linear, color.has_alpha()) {
+            (true, true) => corrected,
+            (true, false) => DynamicImage::ImageRgb32F(corrected.into_rgb32f()),
+            (false, true) => DynamicImage::ImageRgba8(corrected.into_rgba8()),
+            (false, false) => DynamicImage::ImageRgb8(corrected.into_rgb8()),
+        }
+    }
+}
+
+/// Read a lens profile from a JSON file. The file is looked up in the dataset first, then
+/// on disk.
+pub async fn read_profile(vfs: &BrushVfs, path: &str) -> Result<LensProfile> {
+    let mut json = String::new();
+    if let Some(file) = vfs.file_names().find(|p| p.ends_with(path)) {
+        vfs.reader_at_path(&file)
+            .await?
+            .read_to_string(&mut json)
+            .await?;
+    } else {
+        #[cfg(not(target_family = "wasm"))]
+        {
+            json = tokio::fs::read_to_string(path).await?;
+        }
+        #[cfg(target_family = "wasm")]
+        anyhow::bail!("Lens profile {path} not found in the dataset");
+    }
+    serde_json::from_str(&json).with_context(|| format!("Failed to parse lens profile {path}"))
+}
+
+/// Estimate the vignetting of the lens, from how the brightness of the views falls off on
+/// average towards the corners. Chromatic aberration can't be estimated, and is left as is.
+pub async fn estimate_profile(views: &[SceneView]) -> Result<LensProfile> {
+    let step = views.len().div_ceil(ESTIMATE_VIEWS).max(1);
+    let mut sums = [0.0f64; ESTIMATE_BINS];
+    let mut counts = [0u64; ESTIMATE_BINS];
+
+    for view in views.iter().step_by(step) {
+        let img = view
+            .image
+            .load()
+            .await?
+            .resize(256, 256, image::imageops::FilterType::Triangle);
+        let linear =
+            img.color() == image::ColorType::Rgb32F || img.color() == image::ColorType::Rgba32F;
+        let img = img.into_rgb32f();
+        let center = glam::vec2(img.width() as f32, img.height() as f32) / 2.0;
+        for (x, y, pixel) in img.enumerate_pixels() {
+            let r =
+                (glam::vec2(x as f32 + 0.5, y as f32 + 0.5) - center).length() / center.length();
+            let bin = ((r * ESTIMATE_BINS as f32) as usize).min(ESTIMATE_BINS - 1);
+            let luma = pixel.0.iter().sum::<f32>() / 3.0;
+            sums[bin] += if linear { luma } else { srgb_to_linear(luma) } as f64;
+            counts[bin] += 1;
+        }
+    }
+
+    let center_level = sums[0] / counts[0].max(1) as f64;
+    anyhow::ensure!(
+        center_level > 0.0,
+        "Images are too dark to estimate vignetting"
+    );
+
+    // Least squares fit of the falloff, the polynomial is linear in k1, k2 and k3.
+    let mut ata = [[0.0f64; 3]; 3];
+    let mut atb = [0.0f64; 3];
+    for (bin, (sum, count)) in sums.iter().zip(counts).enumerate() {
+        if count == 0 {
+            continue;
+        }
+        let r2 = ((bin as f64 + 0.5) / ESTIMATE_BINS as f64).powi(2);
+        let row = [r2, r2 * r2, r2 * r2 * r2];
+        let falloff = sum / count as f64 / center_level - 1.0;
+        for i in 0..3 {
+            for j in 0..3 {
+                ata[i][j] += row[i] * row[j];
+            }
+            atb[i] += row[i] * falloff;
+        }
+    }
+
+    let k = glam::DMat3::from_cols_array_2d(&ata).inverse() * glam::DVec3::from_array(atb);
+    anyhow::ensure!(k.is_finite(), "Failed to estimate vignetting");
+    let profile = LensProfile {
+        vignetting: k.as_vec3().to_array(),
+        ..Default::default()
+    };
+    log::info!("Estimated vignetting {:?}", profile.vignetting);
+    Ok(profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Rgb32FImage};
+
+    use super::LensProfile;
+
+    #[test]
+    fn vignetting_is_removed() {
+        let profile = LensProfile {
+            vignetting: [-0.3, 0.05, 0.0],
+            ..Default::default()
+        };
+        let size = 64;
+        let center = size as f32 / 2.0;
+        let img = Rgb32FImage::from_fn(size, size, |x, y| {
+            let offset = glam::vec2(x as f32 + 0.5, y as f32 + 0.5) - center;
+            let r2 = offset.length_squared() / (2.0 * center * center);
+            image::Rgb([0.5 * (1.0 - 0.3 * r2 + 0.05 * r2 * r2); 3])
+        });
+
+        let corrected = profile
+            .correct(DynamicImage::ImageRgb32F(img), true)
+            .into_rgb32f();
+        for pixel in corrected.pixels() {
+            assert!((pixel[0] - 0.5).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn default_profile_is_identity() {
+        let img = DynamicImage::new_rgb8(4, 4);
+        assert_eq!(LensProfile::default().correct(img.clone(), false), img);
+    }
+}
@@ -2,19 +2,51 @@ mod formats;
 mod parsed_gaussian;
 mod quant;
 
+pub mod anonymize;
+pub mod archive;
 pub mod brush_vfs;
+pub mod capture;
+pub mod chunks;
+pub mod ckpt_import;
+pub mod error;
+pub mod hdr;
+pub mod heif;
+pub mod lens;
+pub mod lidar_import;
+pub mod motion;
+pub mod pano;
+pub mod progress;
 pub mod scene;
 pub mod scene_loader;
 pub mod splat_export;
 pub mod splat_import;
+pub mod splat_validate;
+pub mod threads;
+pub mod view_scheduler;
 
+use brush_vfs::VfsLimits;
 use burn::config::Config;
 use clap::Args;
 use core::f32;
-pub use formats::load_dataset;
+pub use formats::{
+    DataStream, DatasetFormat, LoadFuture, builtin_formats, inria, load_dataset, load_init_ply,
+    nerfstudio,
+};
 use glam::{Mat3, Mat4, Vec3};
 use scene::Scene;
 use scene::SceneView;
+use serde::{Deserialize, Serialize};
+use view_scheduler::ViewSchedule;
+
+/// Color space the splats are trained in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum ColorSpace {
+    /// Train on the sRGB encoded colors of the images, as most implementations do.
+    #[default]
+    Srgb,
+    /// Decode the images to linear light, so colors blend as light does.
+    Linear,
+}
 
 #[derive(Config, Debug, Args)]
 pub struct LoadDataseConfig {
@@ -25,6 +57,27 @@ pub struct LoadDataseConfig {
     #[arg(long, help_heading = "Dataset Options", default_value = "1920")]
     #[config(default = 1920)]
     pub max_resolution: u32,
+    /// Train on a crop of the full resolution image every nth step, for images bigger than the
+    /// max resolution. Crops are loaded on demand, so only downscaled images are kept in memory.
+    #[arg(long, help_heading = "Dataset Options")]
+    pub full_res_crop_every: Option<u32>,
+    /// Max size of the decoded images kept in memory, in MB. The least recently used images
+    /// are dropped first. Defaults to 6GB on desktop, and 1GB on the web.
+    #[arg(long, help_heading = "Dataset Options")]
+    pub image_cache_mb: Option<u32>,
+    /// Max size of a zip archive when unpacked, in MB. Larger archives are refused before
+    /// unpacking anything. Defaults to 32GB on desktop, and 2GB on the web.
+    #[arg(long, help_heading = "Dataset Options")]
+    pub max_archive_mb: Option<u64>,
+    /// Max number of files in a dataset.
+    #[arg(long, help_heading = "Dataset Options", default_value = "100000")]
+    #[config(default = 100000)]
+    pub max_archive_files: usize,
+    /// Max width or height of images, in pixels. Larger images are refused before they're
+    /// decoded.
+    #[arg(long, help_heading = "Dataset Options", default_value = "16384")]
+    #[config(default = 16384)]
+    pub max_image_size: u32,
     /// Create an eval dataset by selecting every nth image
     #[arg(long, help_heading = "Dataset Options")]
     pub eval_split_every: Option<usize>,
@@ -34,6 +87,68 @@ pub struct LoadDataseConfig {
     /// Load only every nth point from the initial sfm data
     #[arg(long, help_heading = "Dataset Options")]
     pub subsample_points: Option<u32>,
+    /// Number of views around the horizon that each 360° panorama is split into. Views
+    /// straight up and down are always added.
+    #[arg(long, help_heading = "Dataset Options", default_value = "4")]
+    #[config(default = 4)]
+    pub pano_views: u32,
+    /// Field of view of the views a 360° panorama is split into, in degrees.
+    #[arg(long, help_heading = "Dataset Options", default_value = "90")]
+    #[config(default = 90.0)]
+    pub pano_fov: f32,
+    /// Start training from the splats in this .ply file or LiDAR scan (las, laz or e57),
+    /// instead of the dataset's points. Can be a path or a URL, so the initial point cloud
+    /// doesn't have to be part of the dataset. Combine with start-iter to continue a previous run.
+    #[arg(long, help_heading = "Dataset Options")]
+    pub init_ply: Option<String>,
+    /// Don't start from a .ply file or LiDAR scan included with the dataset, use the
+    /// dataset's own points instead.
+    #[arg(long, help_heading = "Dataset Options", default_value = "false")]
+    #[config(default = false)]
+    pub ignore_dataset_ply: bool,
+    /// Remove lens vignetting and chromatic aberration from the images. Either a path to a
+    /// JSON lens profile, or "estimate" to estimate the vignetting from the images.
+    #[arg(long, help_heading = "Dataset Options")]
+    pub lens_profile: Option<String>,
+    /// Mask out moving objects, like cars and people, so they don't leave ghosts in the
+    /// splats. For datasets of video frames, named in order: moving parts are found from the
+    /// motion to the frames before and after each image.
+    #[arg(long, help_heading = "Dataset Options", default_value = "false")]
+    #[config(default = false)]
+    pub motion_masks: bool,
+    /// Color space to train in. Metrics are always measured in sRGB, and training exports
+    /// are converted back to sRGB.
+    #[arg(
+        long,
+        help_heading = "Dataset Options",
+        value_enum,
+        default_value = "srgb"
+    )]
+    #[config(default = "ColorSpace::Srgb")]
+    pub color_space: ColorSpace,
+    /// Order the views are trained in.
+    #[arg(
+        long,
+        help_heading = "Dataset Options",
+        value_enum,
+        default_value = "random"
+    )]
+    #[config(default = "ViewSchedule::Random")]
+    pub view_schedule: ViewSchedule,
+}
+
+impl LoadDataseConfig {
+    /// Limits on the data mounted for this config.
+    pub fn vfs_limits(&self) -> VfsLimits {
+        let defaults = VfsLimits::default();
+        VfsLimits {
+            max_unpacked_bytes: self
+                .max_archive_mb
+                .map_or(defaults.max_unpacked_bytes, |mb| mb.saturating_mul(1 << 20)),
+            max_files: self.max_archive_files,
+            max_image_size: self.max_image_size,
+        }
+    }
 }
 
 #[derive(Config, Debug, Args)]
@@ -0,0 +1,265 @@
+//! Import of LiDAR scans (LAS, LAZ and E57) as initial splats.
+//!
+//! Scans are used as is, so they need to be in the same coordinate frame as the dataset
+//! cameras.
+
+use std::io::Cursor;
+
+use anyhow::{Context, Result};
+use async_fn_stream::try_fn_stream;
+use brush_render::{gaussian_splats::Splats, sh::rgb_to_sh};
+use burn::prelude::Backend;
+use glam::Vec3;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_stream::Stream;
+
+use crate::splat_import::{ParseMetadata, SplatMessage};
+use crate::threads;
+
+/// Whether this file extension is a LiDAR format that can be imported.
+pub fn is_lidar_extension(ext: &str) -> bool {
+    matches!(ext.to_lowercase().as_str(), "las" | "laz" | "e57")
+}
+
+/// Points of a scan, with colors in 0-1 if the scan has them.
+struct PointCloud {
+    positions: Vec<Vec3>,
+    colors: Option<Vec<Vec3>>,
+}
+
+fn read_las(data: Vec<u8>, step: usize) -> Result<PointCloud> {
+    let mut reader = las::Reader::new(Cursor::new(data)).context("Invalid LAS file")?;
+    let has_color = reader.header().point_format().has_color;
+
+    let mut positions = vec![];
+    let mut colors = vec![];
+    for point in reader.points().step_by(step) {
+        let point = point?;
+        positions.push(glam::dvec3(point.x, point.y, point.z).as_vec3());
+        if let Some(color) = point.color {
+            colors.push(Vec3::new(
+                color.red as f32,
+                color.green as f32,
+                color.blue as f32,
+            ));
+        }
+    }
+
+    // The spec says colors are 16 bit, but plenty of writers store 8 bit values.
+    let colors = (has_color && colors.len() == positions.len()).then(|| {
+        let max = colors.iter().map(|c| c.max_element()).fold(0.0, f32::max);
+        let scale = if max <= 255.0 { 255.0 } else { 65535.0 };
+        colors.into_iter().map(|c| c / scale).collect()
+    });
+
+    Ok(PointCloud { positions, colors })
+}
+
+fn read_e57(data: Vec<u8>, step: usize) -> Result<PointCloud> {
+    let mut reader = e57::E57Reader::new(Cursor::new(data)).context("Invalid E57 file")?;
+
+    let mut positions = vec![];
+    let mut colors = vec![];
+    let mut all_colored = true;
+
+    // A file can hold multiple scans, each with their own pose. The reader moves
+    // all points into the shared world frame.
+    for cloud in reader.pointclouds() {
+        all_colored &= cloud.has_color();
+        let mut iter = reader.pointcloud_simple(&cloud)?;
+        iter.spherical_to_cartesian(true);
+
+        for point in iter.step_by(step) {
+            let point = point?;
+            let e57::CartesianCoordinate::Valid { x, y, z } = point.cartesian else {
+                continue;
+            };
+            positions.push(glam::dvec3(x, y, z).as_vec3());
+            if let Some(color) = point.color {
+                colors.push(Vec3::new(color.red, color.green, color.blue));
+            }
+        }
+    }
+
+    let colors = (all_colored && colors.len() == positions.len()).then_some(colors);
+    Ok(PointCloud { positions, colors })
+}
+
+/// Read every `step`th point of a scan in the format of `extension`.
+fn read_cloud(data: Vec<u8>, extension: &str, step: usize) -> Result<PointCloud> {
+    match extension.to_lowercase().as_str() {
+        "las" | "laz" => read_las(data, step),
+        "e57" => read_e57(data, step),
+        _ => anyhow::bail!("Unknown LiDAR format {extension}"),
+    }
+}
+
+/// Load a LiDAR scan as splats, with the point colors if the scan has them.
+///
+/// Every `subsample_points`th point is kept, as scans easily have far more points
+/// than is useful to start training from.
+pub fn load_splat_from_lidar<T: AsyncRead + Unpin + 'static, B: Backend>(
+    mut reader: T,
+    extension: String,
+    subsample_points: Option<u32>,
+    device: B::Device,
+) -> impl Stream<Item = Result<SplatMessage<B>>> + 'static {
+    try_fn_stream(|emitter| async move {
+        // The readers need to seek, so read the whole file first.
+        let mut data = vec![];
+        reader.read_to_end(&mut data).await?;
+
+        let step = subsample_points.unwrap_or(1).max(1) as usize;
+        // Scans are large, and the readers decode all of it at once.
+        let cloud = threads::spawn_blocking(move || read_cloud(data, &extension, step)).await?;
+
+        anyhow::ensure!(!cloud.positions.is_empty(), "LiDAR scan has no points");
+        log::info!("Starting from {} LiDAR points", cloud.positions.len());
+
+        let sh_coeffs: Option<Vec<f32>> = cloud.colors.map(|colors| {
+            colors
+                .into_iter()
+                .flat_map(|c| {
+                    let sh = rgb_to_sh(c.clamp(Vec3::ZERO, Vec3::ONE));
+                    [sh.x, sh.y, sh.z]
+                })
+                .collect()
+        });
+
+        let splats = Splats::from_raw(
+            &cloud.positions,
+            None,
+            None,
+            sh_coeffs.as_deref(),
+            None,
+            &device,
+        );
+
+        emitter
+            .emit(SplatMessage {
+                meta: ParseMetadata {
+                    up_axis: None,
+                    total_splats: splats.num_splats(),
+                    frame_count: 1,
+                    current_frame: 0,
+                    ..Default::default()
+                },
+                splats,
+            })
+            .await;
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use glam::Vec3;
+
+    use super::read_cloud;
+
+    fn las_bytes(points: &[(Vec3, [u16; 3])], compressed: bool) -> Vec<u8> {
+        let mut builder = las::Builder::from((1, 2));
+        builder.point_format = las::point::Format::new(2).expect("Invalid point format");
+        builder.point_format.is_compressed = compressed;
+        let header = builder.into_header().expect("Invalid LAS header");
+
+        let mut writer =
+            las::Writer::new(Cursor::new(Vec::new()), header).expect("Failed to create writer");
+        for (pos, [red, green, blue]) in points {
+            writer
+                .write_point(las::Point {
+                    x: pos.x as f64,
+                    y: pos.y as f64,
+                    z: pos.z as f64,
+                    color: Some(las::Color::new(*red, *green, *blue)),
+                    ..Default::default()
+                })
+                .expect("Failed to write point");
+        }
+        writer
+            .into_inner()
+            .expect("Failed to finish LAS file")
+            .into_inner()
+    }
+
+    fn e57_bytes(points: &[(Vec3, [u8; 3])]) -> Vec<u8> {
+        use e57::{Record, RecordValue};
+
+        let mut data = Cursor::new(Vec::new());
+        let mut writer =
+            e57::E57Writer::new(&mut data, "file_guid").expect("Failed to create writer");
+        let prototype = vec![
+            Record::CARTESIAN_X_F32,
+            Record::CARTESIAN_Y_F32,
+            Record::CARTESIAN_Z_F32,
+            Record::COLOR_RED_U8,
+            Record::COLOR_GREEN_U8,
+            Record::COLOR_BLUE_U8,
+        ];
+        let mut cloud = writer
+            .add_pointcloud("cloud_guid", prototype)
+            .expect("Failed to add point cloud");
+        for (pos, color) in points {
+            let mut values = vec![
+                RecordValue::Single(pos.x),
+                RecordValue::Single(pos.y),
+                RecordValue::Single(pos.z),
+            ];
+            values.extend(color.map(|c| RecordValue::Integer(c as i64)));
+            cloud.add_point(values).expect("Failed to write point");
+        }
+        cloud.finalize().expect("Failed to finish point cloud");
+        writer.finalize().expect("Failed to finish E57 file");
+        data.into_inner()
+    }
+
+    #[test]
+    fn las_points_and_8_bit_colors() {
+        let points = [
+            (Vec3::new(1.0, 2.0, 3.0), [255, 0, 0]),
+            (Vec3::new(-1.5, 0.25, 8.0), [0, 51, 255]),
+        ];
+        let cloud = read_cloud(las_bytes(&points, false), "LAS", 1).expect("Failed to read LAS");
+
+        assert_eq!(cloud.positions.len(), 2);
+        assert!(cloud.positions[1].abs_diff_eq(points[1].0, 1e-3));
+        let colors = cloud.colors.expect("Scan has colors");
+        assert!(colors[1].abs_diff_eq(Vec3::new(0.0, 0.2, 1.0), 1e-6));
+    }
+
+    #[test]
+    fn laz_16_bit_colors_and_subsampling() {
+        let points: Vec<_> = (0..10)
+            .map(|i| (Vec3::splat(i as f32), [65535, 32768, i as u16 * 1000]))
+            .collect();
+        let cloud = read_cloud(las_bytes(&points, true), "laz", 3).expect("Failed to read LAZ");
+
+        // Every third point: 0, 3, 6 and 9.
+        assert_eq!(cloud.positions.len(), 4);
+        assert!(cloud.positions[1].abs_diff_eq(Vec3::splat(3.0), 1e-3));
+        let colors = cloud.colors.expect("Scan has colors");
+        assert!(colors[0].abs_diff_eq(Vec3::new(1.0, 32768.0 / 65535.0, 0.0), 1e-6));
+    }
+
+    #[test]
+    fn e57_points_and_colors() {
+        let points = [
+            (Vec3::new(1.0, 2.0, 3.0), [255, 0, 0]),
+            (Vec3::new(4.0, 5.0, 6.0), [0, 255, 0]),
+            (Vec3::new(7.0, 8.0, 9.0), [0, 0, 255]),
+        ];
+        let cloud = read_cloud(e57_bytes(&points), "e57", 2).expect("Failed to read E57");
+
+        assert_eq!(cloud.positions, vec![points[0].0, points[2].0]);
+        let colors = cloud.colors.expect("Scan has colors");
+        assert!(colors[1].abs_diff_eq(Vec3::Z, 1e-6));
+    }
+
+    #[test]
+    fn unknown_format_is_an_error() {
+        assert!(read_cloud(vec![], "xyz", 1).is_err());
+    }
+}
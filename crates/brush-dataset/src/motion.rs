@@ -0,0 +1,327 @@
+//! Masking out moving objects in datasets of video frames.
+//!
+//! Cars and people that move while the camera passes by can't be explained by static splats,
+//! and end up as ghosts. They're found from how each part of a frame moves to the frames next
+//! to it: the background moves along with the camera, moving objects don't.
+
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::Result;
+use glam::{DMat3, DVec3, Vec2, vec2};
+use image::{DynamicImage, ImageBuffer, Luma};
+use tokio::io::AsyncReadExt;
+
+use crate::{Dataset, brush_vfs::BrushVfs, scene::decode_image};
+
+type GrayImage = ImageBuffer<Luma<f32>, Vec<f32>>;
+
+/// Width the motion is estimated at. Smaller is faster, but misses small objects.
+const WORK_WIDTH: u32 = 320;
+const BLOCK_SIZE: u32 = 8;
+/// How far a block is searched for in the neighboring frames, in pixels at the work width.
+const SEARCH_RADIUS: i32 = 8;
+/// Blocks that move this many pixels differently than the camera motion are moving.
+const MOTION_THRESHOLD: f32 = 1.5;
+/// Blocks with less contrast than this match anywhere, so their motion isn't trusted.
+const MIN_CONTRAST: f32 = 0.02;
+
+/// Frames next to an image in the video, to find the moving objects in it from.
+#[derive(Clone, Debug)]
+pub struct MotionMask {
+    pub neighbors: Vec<PathBuf>,
+}
+
+impl MotionMask {
+    /// Mask of the static parts of `img`, one byte per pixel. Moving parts are 0.
+    pub async fn load(&self, vfs: &BrushVfs, img: &DynamicImage) -> Result<Vec<u8>> {
+        let mut neighbors = vec![];
+        for path in &self.neighbors {
+            let mut bytes = vec![];
+            vfs.reader_at_path(path)
+                .await?
+                .read_to_end(&mut bytes)
+                .await?;
+            neighbors.push(decode_image(&bytes, vfs.limits())?);
+        }
+        Ok(motion_mask(img, &neighbors))
+    }
+}
+
+/// Frames of a video are named in order, so the neighbors of each image are the images
+/// named just before and after it. Panorama faces and exposure brackets aren't frames of a
+/// video, and are left out.
+pub fn neighbor_frames(dataset: &Dataset) -> HashMap<PathBuf, MotionMask> {
+    let eval_views = dataset.eval.iter().flat_map(|e| e.views.iter());
+    let mut paths: Vec<_> = dataset
+        .train
+        .views
+        .iter()
+        .chain(eval_views)
+        .filter(|v| v.image.pano_face.is_none() && v.image.hdr.is_none())
+        .map(|v| v.image.path.clone())
+        .collect();
+    paths.sort();
+    paths.dedup();
+    if paths.len() < 2 {
+        return HashMap::new();
+    }
+
+    paths
+        .iter()
+        .enumerate()
+        .map(|(i, path)| {
+            let neighbors = [i.checked_sub(1), Some(i + 1)]
+                .into_iter()
+                .flatten()
+                .filter_map(|j| paths.get(j).cloned())
+                .collect();
+            (path.clone(), MotionMask { neighbors })
+        })
+        .collect()
+}
+
+fn to_work_size(img: &DynamicImage, size: (u32, u32)) -> GrayImage {
+    if (img.width(), img.height()) == size {
+        img.to_luma32f()
+    } else {
+        img.resize_exact(size.0, size.1, image::imageops::FilterType::Triangle)
+            .to_luma32f()
+    }
+}
+
+struct BlockMotion {
+    center: Vec2,
+    flow: Vec2,
+    textured: bool,
+}
+
+/// Where each block of `frame` moved to in `neighbor`, by the offset with the smallest
+/// difference.
+fn block_motion(frame: &GrayImage, neighbor: &GrayImage) -> Vec<BlockMotion> {
+    let (width, height) = frame.dimensions();
+    let (blocks_x, blocks_y) = (width / BLOCK_SIZE, height / BLOCK_SIZE);
+    let pixel_count = (BLOCK_SIZE * BLOCK_SIZE) as f32;
+
+    let mut blocks = vec![];
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            let (x0, y0) = (bx * BLOCK_SIZE, by * BLOCK_SIZE);
+            let pixels = || {
+                (0..BLOCK_SIZE).flat_map(move |y| (0..BLOCK_SIZE).map(move |x| (x0 + x, y0 + y)))
+            };
+
+            let mean = pixels().map(|(x, y)| frame.get_pixel(x, y)[0]).sum::<f32>() / pixel_count;
+            let variance = pixels()
+                .map(|(x, y)| (frame.get_pixel(x, y)[0] - mean).powi(2))
+                .sum::<f32>()
+                / pixel_count;
+
+            let mut best = (f32::INFINITY, 0, 0);
+            for dy in -SEARCH_RADIUS..=SEARCH_RADIUS {
+                for dx in -SEARCH_RADIUS..=SEARCH_RADIUS {
+                    let (nx, ny) = (x0 as i32 + dx, y0 as i32 + dy);
+                    if nx < 0
+                        || ny < 0
+                        || nx as u32 + BLOCK_SIZE > width
+                        || ny as u32 + BLOCK_SIZE > height
+                    {
+                        continue;
+                    }
+                    let difference: f32 = pixels()
+                        .map(|(x, y)| {
+                            let moved = neighbor
+                                .get_pixel((x as i32 + dx) as u32, (y as i32 + dy) as u32)[0];
+                            (frame.get_pixel(x, y)[0] - moved).abs()
+                        })
+                        .sum();
+                    // Prefer the smaller offset of equally good matches.
+                    let better = difference < best.0
+                        || (difference == best.0
+                            && dx.abs() + dy.abs() < best.1.abs() + best.2.abs());
+                    if better {
+                        best = (difference, dx, dy);
+                    }
+                }
+            }
+
+            blocks.push(BlockMotion {
+                center: vec2(x0 as f32, y0 as f32) + BLOCK_SIZE as f32 / 2.0,
+                flow: vec2(best.1 as f32, best.2 as f32),
+                textured: best.0.is_finite() && variance.sqrt() > MIN_CONTRAST,
+            });
+        }
+    }
+    blocks
+}
+
+/// Least squares fit of an affine motion to the blocks, for each axis the coefficients of
+/// `x`, `y` and 1.
+fn fit_affine(blocks: &[&BlockMotion], scale: f32) -> Option<[DVec3; 2]> {
+    if blocks.len() < 6 {
+        return None;
+    }
+    let mut ata = DMat3::ZERO;
+    let mut atb = [DVec3::ZERO; 2];
+    for block in blocks {
+        let p = (block.center / scale).as_dvec2();
+        let row = DVec3::new(p.x, p.y, 1.0);
+        ata += DMat3::from_cols(row * row.x, row * row.y, row * row.z);
+        atb[0] += row * block.flow.x as f64;
+        atb[1] += row * block.flow.y as f64;
+    }
+    let inverse = ata.inverse();
+    let coeffs = [inverse * atb[0], inverse * atb[1]];
+    coeffs.iter().all(|c| c.is_finite()).then_some(coeffs)
+}
+
+/// Which blocks move differently than the camera. The camera motion is taken to be the
+/// affine motion most blocks agree on, which holds when the background is far away compared
+/// to how far the camera moves between frames.
+fn moving_blocks(blocks: &[BlockMotion], size: Vec2) -> Vec<bool> {
+    let scale = size.max_element();
+    let predict = |coeffs: &[DVec3; 2], center: Vec2| {
+        let p = (center / scale).as_dvec2();
+        let row = DVec3::new(p.x, p.y, 1.0);
+        vec2(coeffs[0].dot(row) as f32, coeffs[1].dot(row) as f32)
+    };
+
+    // Fit again without the blocks that are far off, which are likely moving.
+    let mut coeffs = None;
+    for max_error in [
+        f32::INFINITY,
+        4.0 * MOTION_THRESHOLD,
+        2.0 * MOTION_THRESHOLD,
+    ] {
+        let inliers: Vec<_> = blocks
+            .iter()
+            .filter(|b| b.textured)
+            .filter(|b| coeffs.is_none_or(|c| predict(&c, b.center).distance(b.flow) < max_error))
+            .collect();
+        coeffs = fit_affine(&inliers, scale).or(coeffs);
+    }
+    let Some(coeffs) = coeffs else {
+        return vec![false; blocks.len()];
+    };
+
+    // Blocks the camera motion takes out of the frame can't be found in the neighbor, so
+    // their motion says nothing.
+    let half_block = Vec2::splat(BLOCK_SIZE as f32 / 2.0);
+    blocks
+        .iter()
+        .map(|b| {
+            let flow = predict(&coeffs, b.center);
+            let moved = b.center + flow;
+            let in_frame = moved.cmpge(half_block).all() && moved.cmple(size - half_block).all();
+            b.textured && in_frame && flow.distance(b.flow) > MOTION_THRESHOLD
+        })
+        .collect()
+}
+
+/// Mask of the parts of `frame` that stay put relative to the background in all of its
+/// neighboring frames, one byte per pixel of the frame. Moving parts are 0.
+pub fn motion_mask(frame: &DynamicImage, neighbors: &[DynamicImage]) -> Vec<u8> {
+    let (width, height) = (frame.width(), frame.height());
+    let work_width = width.min(WORK_WIDTH);
+    let work_height = ((height as u64 * work_width as u64) / width.max(1) as u64).max(1) as u32;
+    let work_size = (work_width, work_height);
+    let (blocks_x, blocks_y) = (work_width / BLOCK_SIZE, work_height / BLOCK_SIZE);
+
+    let static_mask = vec![255; (width * height) as usize];
+    if neighbors.is_empty() || blocks_x == 0 || blocks_y == 0 {
+        return static_mask;
+    }
+
+    let frame_gray = to_work_size(frame, work_size);
+    let size = vec2(work_width as f32, work_height as f32);
+
+    // A block only counts as moving if it moves against the background in every neighbor,
+    // so a bad match in one of them doesn't mask it.
+    let mut moving = vec![true; (blocks_x * blocks_y) as usize];
+    for neighbor in neighbors {
+        let neighbor_gray = to_work_size(neighbor, work_size);
+        let blocks = block_motion(&frame_gray, &neighbor_gray);
+        for (m, moves) in moving.iter_mut().zip(moving_blocks(&blocks, size)) {
+            *m &= moves;
+        }
+    }
+
+    // Grow the moving parts by a block, to cover the edges of the objects as well.
+    let is_moving = |bx: i64, by: i64| {
+        (-1..=1).any(|oy| {
+            (-1..=1).any(|ox| {
+                let (x, y) = (bx + ox, by + oy);
+                x >= 0
+                    && y >= 0
+                    && x < blocks_x as i64
+                    && y < blocks_y as i64
+                    && moving[(y * blocks_x as i64 + x) as usize]
+            })
+        })
+    };
+    let grown: Vec<bool> = (0..blocks_y as i64)
+        .flat_map(|by| (0..blocks_x as i64).map(move |bx| (bx, by)))
+        .map(|(bx, by)| is_moving(bx, by))
+        .collect();
+    if !grown.contains(&true) {
+        return static_mask;
+    }
+
+    let block_of = |pixel: u32, size: u32, work: u32, blocks: u32| {
+        ((pixel as u64 * work as u64 / size as u64) as u32 / BLOCK_SIZE).min(blocks - 1)
+    };
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let bx = block_of(x, width, work_width, blocks_x);
+            let by = block_of(y, height, work_height, blocks_y);
+            if grown[(by * blocks_x + bx) as usize] {
+                0
+            } else {
+                255
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, GrayImage, Luma};
+
+    use super::motion_mask;
+
+    fn noise(x: i32, y: i32, seed: i32) -> u8 {
+        let h = (x.wrapping_mul(73_856_093)
+            ^ y.wrapping_mul(19_349_663)
+            ^ seed.wrapping_mul(83_492_791)) as u32;
+        (h.wrapping_mul(2_654_435_761) >> 24) as u8
+    }
+
+    /// A frame of a camera panning over a textured wall, with a square driving past it.
+    fn frame(index: i32) -> DynamicImage {
+        let object = (64 + 6 * index, 32);
+        DynamicImage::ImageLuma8(GrayImage::from_fn(160, 96, |x, y| {
+            let (x, y) = (x as i32, y as i32);
+            let (ox, oy) = (x - object.0, y - object.1);
+            if (0..32).contains(&ox) && (0..32).contains(&oy) {
+                Luma([noise(ox, oy, 1)])
+            } else {
+                Luma([noise(x + 2 * index, y + index, 0)])
+            }
+        }))
+    }
+
+    #[test]
+    fn moving_object_is_masked() {
+        let mask = motion_mask(&frame(0), &[frame(-1), frame(1)]);
+        let at = |x: usize, y: usize| mask[y * 160 + x];
+        assert_eq!(at(80, 48), 0, "The moving square is masked");
+        assert_eq!(at(8, 8), 255, "The background is kept");
+        assert_eq!(at(150, 88), 255, "The background is kept");
+    }
+
+    #[test]
+    fn static_scene_is_kept() {
+        let mask = motion_mask(&frame(0), &[frame(0)]);
+        assert!(mask.iter().all(|&m| m == 255));
+    }
+}
@@ -0,0 +1,398 @@
+//! Splitting 360° equirectangular panoramas into pinhole views that can be trained on,
+//! and rendering splats back out as panoramas.
+
+use std::future::Future;
+
+use anyhow::Context;
+use brush_render::{
+    SplatForward, camera::Camera, gaussian_splats::Splats, post_process::PostProcess,
+};
+use burn::{
+    prelude::Backend,
+    tensor::{Tensor, TensorData},
+};
+use glam::{Quat, Vec3};
+use image::{DynamicImage, Rgba32FImage};
+use tokio::sync::Mutex;
+
+use crate::threads;
+
+/// A pinhole view looking out from the center of a panorama.
+#[derive(Debug, Clone, Copy)]
+pub struct PanoFace {
+    /// Rotation of the view relative to the panorama camera.
+    pub rotation: Quat,
+    /// Field of view in both directions, in radians.
+    pub fov: f64,
+    pub index: u32,
+}
+
+/// Views around the horizon, and one straight up and down. With 4 horizontal views
+/// at 90° this gives the faces of a cube map.
+pub fn pano_faces(horizontal_views: u32, fov_deg: f32) -> Vec<PanoFace> {
+    let fov = (fov_deg as f64).to_radians();
+    let n = horizontal_views.max(1);
+
+    // Camera convention is +Z forward, +Y down.
+    let around = (0..n).map(|i| Quat::from_rotation_y(i as f32 / n as f32 * std::f32::consts::TAU));
+    let poles = [
+        Quat::from_rotation_x(std::f32::consts::FRAC_PI_2),
+        Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2),
+    ];
+
+    around
+        .chain(poles)
+        .enumerate()
+        .map(|(index, rotation)| PanoFace {
+            rotation,
+            fov,
+            index: index as u32,
+        })
+        .collect()
+}
+
+/// The faces of a panorama that are trained on as separate views. The first face that's
+/// loaded samples all of them, so the panorama is only decoded once. The other faces are
+/// kept until they're loaded.
+pub struct PanoFaces {
+    faces: Vec<PanoFace>,
+    sampled: Mutex<Vec<Option<DynamicImage>>>,
+}
+
+impl PanoFaces {
+    pub fn new(faces: Vec<PanoFace>) -> Self {
+        let sampled = Mutex::new(vec![None; faces.len()]);
+        Self { faces, sampled }
+    }
+
+    /// Load `face` at `size`, sampling all faces from the panorama `load_pano` decodes if
+    /// it isn't sampled already.
+    pub async fn load(
+        &self,
+        face: PanoFace,
+        size: u32,
+        load_pano: impl Future<Output = anyhow::Result<DynamicImage>>,
+    ) -> anyhow::Result<DynamicImage> {
+        // Hold the lock while decoding, so faces loading at the same time wait for it.
+        let mut sampled = self.sampled.lock().await;
+        if let Some(img) = sampled[face.index as usize].take() {
+            return Ok(img);
+        }
+
+        let pano = load_pano.await?;
+        let faces = self.faces.clone();
+        *sampled = threads::run_blocking(move || {
+            faces.iter().map(|f| Some(f.sample(&pano, size))).collect()
+        })
+        .await;
+        Ok(sampled[face.index as usize]
+            .take()
+            .expect("All faces were just sampled"))
+    }
+}
+
+impl PanoFace {
+    /// Size of the face that keeps about the same pixel density as the panorama.
+    pub fn size_for_pano(&self, pano_width: u32) -> u32 {
+        let size = pano_width as f64 * self.fov / std::f64::consts::TAU;
+        (size.round() as u32).max(1)
+    }
+
+    /// Resample this face from an equirectangular panorama.
+    ///
+    /// The center of the panorama looks down +Z, the top row straight up.
+    pub fn sample(&self, pano: &DynamicImage, size: u32) -> DynamicImage {
+        let has_alpha = pano.color().has_alpha();
+        let is_float = matches!(
+            pano,
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+        );
+        let pano = pano.to_rgba32f();
+        let (pano_w, pano_h) = (pano.width() as f32, pano.height() as f32);
+        let focal = (0.5 * size as f64 / (self.fov * 0.5).tan()) as f32;
+        let half = size as f32 / 2.0;
+
+        let face = Rgba32FImage::from_fn(size, size, |x, y| {
+            let ray = Vec3::new(x as f32 + 0.5 - half, y as f32 + 0.5 - half, focal);
+            let dir = (self.rotation * ray).normalize();
+
+            let lon = dir.x.atan2(dir.z);
+            let lat = (-dir.y).clamp(-1.0, 1.0).asin();
+            let u = (lon / std::f32::consts::TAU + 0.5) * pano_w - 0.5;
+            let v = (0.5 - lat / std::f32::consts::PI) * pano_h - 0.5;
+            sample_wrapped(&pano, u, v)
+        });
+
+        // Linear images stay float, other images are 8 bit as they were loaded.
+        let face = DynamicImage::ImageRgba32F(face);
+        match (is_float, has_alpha) {
+            (true, true) => face,
+            (true, false) => DynamicImage::ImageRgb32F(face.into_rgb32f()),
+            (false, true) => DynamicImage::ImageRgba8(face.into_rgba8()),
+            (false, false) => DynamicImage::ImageRgb8(face.into_rgb8()),
+        }
+    }
+}
+
+/// Bilinear sample, wrapping around horizontally and clamping vertically.
+fn sample_wrapped(img: &Rgba32FImage, x: f32, y: f32) -> image::Rgba<f32> {
+    let (w, h) = (img.width() as i64, img.height() as i64);
+    let y = y.clamp(0.0, (h - 1) as f32);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+
+    let px = |xi: i64, yi: i64| img.get_pixel(xi.rem_euclid(w) as u32, yi.clamp(0, h - 1) as u32);
+    let (x0, y0) = (x0 as i64, y0 as i64);
+    let corners = [
+        (px(x0, y0), (1.0 - fx) * (1.0 - fy)),
+        (px(x0 + 1, y0), fx * (1.0 - fy)),
+        (px(x0, y0 + 1), (1.0 - fx) * fy),
+        (px(x0 + 1, y0 + 1), fx * fy),
+    ];
+
+    let mut out = [0.0f32; 4];
+    for (pixel, weight) in corners {
+        for (o, c) in out.iter_mut().zip(pixel.0) {
+            *o += c * weight;
+        }
+    }
+    image::Rgba(out)
+}
+
+/// Render a 360° equirectangular image of the splats, looking out from `camera`.
+///
+/// The six faces of a cube are rendered and reprojected. The faces are a bit wider than
+/// 90° so there's room for filtering at the seams. The camera field of view is ignored.
+/// The post-processing is applied as the viewer does, with ambient occlusion per face.
+pub async fn render_equirect<B: Backend + SplatForward<B>>(
+    splats: &Splats<B>,
+    camera: &Camera,
+    width: u32,
+    post_process: &PostProcess,
+) -> anyhow::Result<DynamicImage> {
+    let faces = pano_faces(4, 95.0);
+    let face_size = faces[0].size_for_pano(width);
+    let focal = (0.5 * face_size as f64 / (faces[0].fov * 0.5).tan()) as f32;
+    let half = face_size as f32 / 2.0;
+
+    let mut face_images = vec![];
+    for face in &faces {
+        let face_cam = Camera::new(
+            camera.position,
+            camera.rotation * face.rotation,
+            face.fov,
+            face.fov,
+            glam::vec2(0.5, 0.5),
+        );
+        let face_dims = glam::uvec2(face_size, face_size);
+        let (img, _) = splats.render(&face_cam, face_dims, true);
+        // Occlusion needs the depth of each face, the other effects are applied to the
+        // panorama as a whole.
+        let img = if post_process.ambient_occlusion > 0.0 {
+            let (depth, _) = splats.render_depth(&face_cam, face_dims);
+            post_process.occlude(img, depth)
+        } else {
+            img
+        };
+        let data = img
+            .into_data_async()
+            .await
+            .into_vec::<f32>()
+            .map_err(|e| anyhow::anyhow!("Failed to read render: {e:?}"))?;
+        let face_img = Rgba32FImage::from_raw(face_dims.x, face_dims.y, data)
+            .context("Render has unexpected size")?;
+        face_images.push(face_img);
+    }
+
+    let height = (width / 2).max(1);
+    let forwards: Vec<Vec3> = faces.iter().map(|f| f.rotation * Vec3::Z).collect();
+
+    let pano = Rgba32FImage::from_fn(width, height, |x, y| {
+        let lon = ((x as f32 + 0.5) / width as f32 - 0.5) * std::f32::consts::TAU;
+        let lat = (0.5 - (y as f32 + 0.5) / height as f32) * std::f32::consts::PI;
+        let dir = Vec3::new(lat.cos() * lon.sin(), -lat.sin(), lat.cos() * lon.cos());
+
+        // The face looking most towards the direction contains it.
+        let (index, _) = forwards
+            .iter()
+            .map(|f| f.dot(dir))
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .expect("Must have faces");
+        let local = faces[index].rotation.inverse() * dir;
+        let px = focal * local.x / local.z + half - 0.5;
+        let py = focal * local.y / local.z + half - 0.5;
+        sample_clamped(&face_images[index], px, py)
+    });
+
+    // Occlusion is done already.
+    let post_process = PostProcess {
+        ambient_occlusion: 0.0,
+        ..*post_process
+    };
+    if !post_process.is_enabled() {
+        return Ok(DynamicImage::ImageRgba32F(pano));
+    }
+
+    let shape = [height as usize, width as usize, 4];
+    let img = Tensor::<B, 3>::from_data(TensorData::new(pano.into_raw(), shape), &splats.device());
+    let data = post_process
+        .apply(img)
+        .into_data_async()
+        .await
+        .into_vec::<f32>()
+        .map_err(|e| anyhow::anyhow!("Failed to read post-processed render: {e:?}"))?;
+    let pano = Rgba32FImage::from_raw(width, height, data).context("Render has unexpected size")?;
+    Ok(DynamicImage::ImageRgba32F(pano))
+}
+
+/// Bilinear sample, clamping to the edges.
+fn sample_clamped(img: &Rgba32FImage, x: f32, y: f32) -> image::Rgba<f32> {
+    let (w, h) = (img.width() as f32, img.height() as f32);
+    let x = x.clamp(0.0, w - 1.0);
+    let y = y.clamp(0.0, h - 1.0);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (x0, y0) = (x0 as u32, y0 as u32);
+    let (x1, y1) = (
+        (x0 + 1).min(img.width() - 1),
+        (y0 + 1).min(img.height() - 1),
+    );
+
+    let corners = [
+        (img.get_pixel(x0, y0), (1.0 - fx) * (1.0 - fy)),
+        (img.get_pixel(x1, y0), fx * (1.0 - fy)),
+        (img.get_pixel(x0, y1), (1.0 - fx) * fy),
+        (img.get_pixel(x1, y1), fx * fy),
+    ];
+    let mut out = [0.0f32; 4];
+    for (pixel, weight) in corners {
+        for (o, c) in out.iter_mut().zip(pixel.0) {
+            *o += c * weight;
+        }
+    }
+    image::Rgba(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use glam::Vec3;
+    use image::{DynamicImage, Rgba32FImage};
+
+    use brush_render::{
+        camera::Camera, gaussian_splats::Splats, post_process::PostProcess, sh::rgb_to_sh,
+    };
+    use burn::backend::{Wgpu, wgpu::WgpuDevice};
+
+    use super::{PanoFaces, pano_faces, render_equirect};
+
+    // Red goes from 0 to 1 from left to right, green from top to bottom.
+    fn gradient_pano() -> DynamicImage {
+        DynamicImage::ImageRgba32F(Rgba32FImage::from_fn(64, 32, |x, y| {
+            image::Rgba([x as f32 / 63.0, y as f32 / 31.0, 0.0, 1.0])
+        }))
+    }
+
+    #[test]
+    fn faces_look_around_and_up_and_down() {
+        let faces = pano_faces(4, 90.0);
+        let forwards: Vec<_> = faces.iter().map(|f| f.rotation * Vec3::Z).collect();
+        // +Y is down.
+        let expected = [Vec3::Z, Vec3::X, -Vec3::Z, -Vec3::X, -Vec3::Y, Vec3::Y];
+        assert_eq!(forwards.len(), expected.len());
+        for (forward, expected) in forwards.iter().zip(expected) {
+            assert!(
+                forward.abs_diff_eq(expected, 1e-6),
+                "{forward} != {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn face_centers_sample_their_direction() {
+        let pano = gradient_pano();
+        // With an odd size the center pixel looks straight ahead.
+        let center = |index: usize| {
+            let face = pano_faces(4, 90.0)[index].sample(&pano, 9);
+            face.to_rgba32f().get_pixel(4, 4).0
+        };
+
+        // Looking ahead is the middle column, +X a quarter turn to the right. Looking
+        // back is the seam, which is blended from both edges.
+        let columns = [31.5, 47.5, 31.5, 15.5];
+        for (index, column) in columns.into_iter().enumerate() {
+            let [r, g, _, _] = center(index);
+            assert!((r - column / 63.0).abs() < 1e-4, "Face {index} red {r}");
+            assert!((g - 0.5).abs() < 1e-4, "Face {index} green {g}");
+        }
+        // Straight up is the top row, straight down the bottom row.
+        assert!(center(4)[1].abs() < 1e-4);
+        assert!((center(5)[1] - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn pano_is_decoded_once_for_all_faces() {
+        let faces = pano_faces(4, 90.0);
+        let shared = PanoFaces::new(faces.clone());
+        let decodes = AtomicU32::new(0);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("Failed to create runtime");
+
+        for face in &faces {
+            let img = runtime
+                .block_on(shared.load(*face, 8, async {
+                    decodes.fetch_add(1, Ordering::Relaxed);
+                    Ok(gradient_pano())
+                }))
+                .expect("Failed to load face");
+            assert_eq!((img.width(), img.height()), (8, 8));
+        }
+        assert_eq!(decodes.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn equirect_shows_splats_in_their_direction() {
+        let device = WgpuDevice::DefaultDevice;
+        // A single white splat to the right of the camera.
+        let white = rgb_to_sh(Vec3::ONE);
+        let splats = Splats::<Wgpu>::from_raw(
+            &[Vec3::new(5.0, 0.0, 0.0)],
+            None,
+            Some(&[Vec3::ZERO]),
+            Some(&[white.x, white.y, white.z]),
+            Some(&[10.0]),
+            &device,
+        );
+        let camera = Camera::new(
+            Vec3::ZERO,
+            glam::Quat::IDENTITY,
+            0.5,
+            0.5,
+            glam::vec2(0.5, 0.5),
+        );
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("Failed to create runtime");
+        let pano = runtime
+            .block_on(render_equirect(
+                &splats,
+                &camera,
+                64,
+                &PostProcess::default(),
+            ))
+            .expect("Failed to render panorama")
+            .to_rgba32f();
+        assert_eq!(pano.dimensions(), (64, 32));
+
+        // +X is three quarters across, on the horizon. Behind it, at -X, is nothing.
+        let [r, g, b, a] = pano.get_pixel(48, 16).0;
+        assert!(a > 0.9, "Splat should cover +X, alpha {a}");
+        assert!(r > 0.9 && g > 0.9 && b > 0.9, "Splat should be white");
+        assert!(pano.get_pixel(16, 16).0[3] < 1e-3);
+        assert!(pano.get_pixel(48, 0).0[3] < 1e-3);
+    }
+}
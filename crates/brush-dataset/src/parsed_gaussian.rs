@@ -1,9 +1,16 @@
-use brush_render::sh::channel_to_sh;
+use brush_render::sh::{channel_to_sh, sh_coeffs_for_degree};
 use glam::{Quat, Vec3};
 use ply_rs::ply::{Property, PropertyAccess};
 
 use crate::quant::{decode_quat, decode_vec_8_8_8_8, decode_vec_11_10_11};
 
+/// Highest SH degree the renderer supports.
+pub(crate) const MAX_SH_DEGREE: u32 = 4;
+
+// Rest coefficients of all channels at the highest SH degree. Higher `f_rest_` properties are
+// ignored, so a property name can't make each splat allocate a huge vector.
+pub(crate) const MAX_SH_REST: u32 = (sh_coeffs_for_degree(MAX_SH_DEGREE) - 1) * 3;
+
 /// A gaussian as it parsed in the ply.
 ///
 /// Nb that this is somewhat abused and the values are mostly what _directly_ comes out of the ply,
@@ -71,6 +78,9 @@ impl PropertyAccess for ParsedGaussian<false> {
             b"blue" => self.sh_dc[2] = channel_to_sh(value),
             _ if ascii.starts_with(b"f_rest_") => {
                 if let Ok(idx) = key["f_rest_".len()..].parse::<u32>() {
+                    if idx >= MAX_SH_REST {
+                        return;
+                    }
                     if idx >= self.sh_coeffs_rest.len() as u32 {
                         self.sh_coeffs_rest.resize(idx as usize + 1, 0.0);
                     }
@@ -0,0 +1,125 @@
+//! Tracking how far along reading a file or download is, to show a progress bar.
+
+use std::{
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, ready},
+};
+
+use tokio::{
+    io::{AsyncRead, ReadBuf},
+    sync::watch,
+};
+use web_time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BytesRead {
+    pub loaded: u64,
+    /// Size of the whole file, if known.
+    pub total: Option<u64>,
+}
+
+/// Bytes read so far, shared between the reader and whoever shows the progress.
+#[derive(Clone)]
+pub struct ByteProgress {
+    bytes: Arc<watch::Sender<BytesRead>>,
+    started: Instant,
+}
+
+impl Default for ByteProgress {
+    fn default() -> Self {
+        Self {
+            bytes: Arc::new(watch::Sender::new(BytesRead::default())),
+            started: Instant::now(),
+        }
+    }
+}
+
+impl ByteProgress {
+    pub fn set_total(&self, total: Option<u64>) {
+        self.bytes.send_modify(|b| b.total = total);
+    }
+
+    pub fn loaded_bytes(&self) -> u64 {
+        self.bytes.borrow().loaded
+    }
+
+    pub fn total_bytes(&self) -> Option<u64> {
+        self.bytes.borrow().total
+    }
+
+    /// Time left until all bytes are read, going by the average speed so far.
+    pub fn eta(&self) -> Option<Duration> {
+        let BytesRead { loaded, total } = *self.bytes.borrow();
+        let total = total?;
+        if loaded == 0 {
+            return None;
+        }
+        let remaining = total.saturating_sub(loaded) as f64 / loaded as f64;
+        Some(self.started.elapsed().mul_f64(remaining))
+    }
+
+    /// Receive a notification whenever more bytes are read.
+    pub fn subscribe(&self) -> watch::Receiver<BytesRead> {
+        self.bytes.subscribe()
+    }
+
+    /// Wrap a reader, counting the bytes read through it.
+    pub fn reader<R: AsyncRead + Unpin>(&self, inner: R) -> ProgressReader<R> {
+        ProgressReader {
+            inner,
+            progress: self.clone(),
+        }
+    }
+}
+
+pub struct ProgressReader<R> {
+    inner: R,
+    progress: ByteProgress,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for ProgressReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        let read = (buf.filled().len() - before) as u64;
+        if read > 0 {
+            this.progress.bytes.send_modify(|b| b.loaded += read);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use tokio::io::AsyncReadExt;
+
+    use super::ByteProgress;
+
+    #[test]
+    fn counts_bytes_read() {
+        let progress = ByteProgress::default();
+        progress.set_total(Some(1000));
+        assert_eq!(progress.eta(), None);
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("Failed to make runtime");
+        let mut reader = progress.reader(Cursor::new(vec![0u8; 400]));
+        let mut data = vec![];
+        runtime
+            .block_on(reader.read_to_end(&mut data))
+            .expect("Failed to read");
+
+        assert_eq!(progress.loaded_bytes(), 400);
+        assert_eq!(progress.total_bytes(), Some(1000));
+        assert!(progress.eta().is_some());
+    }
+}
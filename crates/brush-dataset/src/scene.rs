@@ -5,11 +5,22 @@ use burn::{
     tensor::{Tensor, TensorData},
 };
 use glam::{Affine3A, Vec3, vec3};
-use image::{ColorType, DynamicImage, ImageDecoder, ImageReader};
-use std::{io::Cursor, path::PathBuf, sync::Arc};
+use image::{ColorType, DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use tokio::io::{AsyncRead, AsyncReadExt};
 
-use crate::brush_vfs::BrushVfs;
+use crate::ColorSpace;
+use crate::brush_vfs::{BrushVfs, VfsLimits};
+use crate::hdr::{HdrMerge, srgb_to_linear};
+use crate::heif;
+use crate::lens::LensProfile;
+use crate::motion::MotionMask;
+use crate::pano::{PanoFace, PanoFaces};
+use crate::threads;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum ViewType {
@@ -18,15 +29,74 @@ pub enum ViewType {
     Test,
 }
 
+/// A region of an image, in pixels of the image at full resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageCrop {
+    pub min: glam::UVec2,
+    pub size: glam::UVec2,
+}
+
+#[derive(Clone)]
 pub struct LoadImage {
     pub vfs: Arc<BrushVfs>,
     pub path: PathBuf,
     pub mask_path: Option<PathBuf>,
+    /// If set, the image is an equirectangular panorama and this is the part of it to load.
+    pub pano_face: Option<PanoFace>,
+    /// The faces of the panorama, shared by the views of each face.
+    pano_faces: Option<Arc<PanoFaces>>,
+    /// If set, colors of the image are mapped with this transform when loading.
+    pub color_transform: Option<Affine3A>,
+    /// If set, the image is merged from these exposure brackets, into linear HDR colors.
+    pub hdr: Option<HdrMerge>,
+    /// If set, vignetting and chromatic aberration of the lens are removed when loading.
+    pub lens: Option<LensProfile>,
+    /// If set, moving objects are masked out, found from the motion to these frames.
+    pub motion: Option<MotionMask>,
+    /// Color space the image is decoded to. Linear images are loaded as floats.
+    pub color_space: ColorSpace,
     color: image::ColorType,
     size: glam::UVec2,
     max_resolution: u32,
 }
 
+/// Fail early on formats this build can't decode, rather than with a generic decoding error.
+fn check_format(format: Option<ImageFormat>) -> Result<()> {
+    if format == Some(ImageFormat::Avif) && !cfg!(feature = "avif") {
+        anyhow::bail!("AVIF images need Brush to be built with the `avif` feature");
+    }
+    Ok(())
+}
+
+/// Whether the file is an image, going by its extension.
+pub fn is_image_path(path: &Path) -> bool {
+    ImageFormat::from_path(path).is_ok() || heif::is_heif_path(path)
+}
+
+/// Decode a whole image file, in any of the supported formats. Images larger than the
+/// `limits` are refused before decoding them.
+pub(crate) fn decode_image(bytes: &[u8], limits: &VfsLimits) -> Result<DynamicImage> {
+    if heif::is_heif(bytes) {
+        limits.check_image_size(heif::image_data(bytes)?.0)?;
+        return heif::decode(bytes);
+    }
+    let mut reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+    check_format(reader.format())?;
+    let mut image_limits = image::Limits::default();
+    image_limits.max_image_width = Some(limits.max_image_size);
+    image_limits.max_image_height = Some(limits.max_image_size);
+    reader.limits(image_limits);
+    let decoder = reader.into_decoder()?;
+    limits.check_image_size(decoder.dimensions().into())?;
+    Ok(DynamicImage::from_decoder(decoder)?)
+}
+
+/// Decode an image with [`decode_image`], on a worker thread if there are any.
+async fn decode_image_async(bytes: Vec<u8>, limits: &VfsLimits) -> Result<DynamicImage> {
+    let limits = limits.clone();
+    threads::run_blocking(move || decode_image(&bytes, &limits)).await
+}
+
 /// Gets the dimensions of an image from an [`AsyncRead`] source
 pub async fn get_image_data<R>(reader: &mut R) -> Result<(glam::UVec2, ColorType)>
 where
@@ -39,8 +109,9 @@ where
 
     let mut n = 0;
     loop {
+        // Small files, eg. well compressed WebP images, can end before the buffer is full.
         let read = reader
-            .read_exact(&mut temp_buf[n..])
+            .read(&mut temp_buf[n..])
             .await
             .context("Failed to read from buffer")?;
 
@@ -51,16 +122,28 @@ where
 
         n += read;
 
+        // HEIF images are read through libheif, which wants the whole file.
+        if heif::is_heif(&temp_buf[..n]) {
+            temp_buf.truncate(n);
+            reader
+                .read_to_end(&mut temp_buf)
+                .await
+                .context("Failed to read from buffer")?;
+            return heif::image_data(&temp_buf);
+        }
+
         // Try to decode with what we have (nb, no copying happens here).
-        if let Ok(decoder) = ImageReader::new(Cursor::new(&temp_buf[..n]))
+        let reader = ImageReader::new(Cursor::new(&temp_buf[..n]))
             .with_guessed_format()
-            .context("Failed to guess format")?
-            .into_decoder()
-        {
+            .context("Failed to guess format")?;
+        check_format(reader.format())?;
+        if let Ok(decoder) = reader.into_decoder() {
             return Ok((decoder.dimensions().into(), decoder.color_type()));
         }
         // Try reading up to double the size.
-        temp_buf.resize(temp_buf.len() * 2, 0);
+        if n == temp_buf.len() {
+            temp_buf.resize(temp_buf.len() * 2, 0);
+        }
     }
 }
 
@@ -78,23 +161,100 @@ impl LoadImage {
         let data = get_image_data(reader)
             .await
             .context("Failed to get image data.")?;
+        vfs.limits()
+            .check_image_size(data.0)
+            .with_context(|| format!("Failed to load {}", path.display()))?;
 
         Ok(Self {
             vfs,
             path,
             mask_path,
+            pano_face: None,
+            pano_faces: None,
+            color_transform: None,
+            hdr: None,
+            lens: None,
+            motion: None,
+            color_space: ColorSpace::Srgb,
             max_resolution,
             size: data.0,
             color: data.1,
         })
     }
 
+    /// Load only this face of the image, treating it as an equirectangular panorama. The
+    /// views of all `faces` share them, so the panorama is decoded once for all of them.
+    pub fn with_pano_face(&self, face: PanoFace, faces: &Arc<PanoFaces>) -> Self {
+        Self {
+            pano_face: Some(face),
+            pano_faces: Some(faces.clone()),
+            ..self.clone()
+        }
+    }
+
+    /// Map the colors of the image with `transform` when loading.
+    pub fn with_color_transform(&self, transform: Affine3A) -> Self {
+        Self {
+            color_transform: Some(transform),
+            ..self.clone()
+        }
+    }
+
+    /// Merge the image from exposure brackets when loading. Merged images are linear.
+    pub fn with_hdr(&self, hdr: HdrMerge) -> Self {
+        Self {
+            hdr: Some(hdr),
+            color_space: ColorSpace::Linear,
+            ..self.clone()
+        }
+    }
+
+    /// Decode the image to `color_space` when loading.
+    pub fn with_color_space(&self, color_space: ColorSpace) -> Self {
+        Self {
+            color_space,
+            ..self.clone()
+        }
+    }
+
+    /// Correct the distortions of this lens when loading.
+    pub fn with_lens(&self, lens: LensProfile) -> Self {
+        Self {
+            lens: Some(lens),
+            ..self.clone()
+        }
+    }
+
+    /// Mask out the parts of the image that move relative to the neighboring frames.
+    pub fn with_motion_mask(&self, motion: MotionMask) -> Self {
+        Self {
+            motion: Some(motion),
+            ..self.clone()
+        }
+    }
+
+    /// Name of the image for display and saving, unique per panorama face.
+    pub fn name(&self) -> String {
+        let stem = self
+            .path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        match self.pano_face {
+            Some(face) => format!("{stem}_face{}", face.index),
+            None => stem,
+        }
+    }
+
     pub fn has_alpha(&self) -> bool {
         self.color.has_alpha() || self.is_masked()
     }
 
     pub fn dimensions(&self) -> glam::UVec2 {
-        if self.size.x <= self.max_resolution && self.size.y <= self.max_resolution {
+        if let Some(face) = self.pano_face {
+            let size = face.size_for_pano(self.size.x).min(self.max_resolution);
+            glam::uvec2(size, size)
+        } else if self.size.x <= self.max_resolution && self.size.y <= self.max_resolution {
             self.size
         } else {
             // Take from image crate, just to be sure logic here matches exactly.
@@ -115,52 +275,169 @@ impl LoadImage {
         self.dimensions().y
     }
 
+    /// Size of the image as stored, before it's downscaled to the max resolution.
+    pub fn full_dimensions(&self) -> glam::UVec2 {
+        self.size
+    }
+
+    /// Whether the image is loaded at a lower resolution than it's stored at.
+    pub fn is_downscaled(&self) -> bool {
+        self.pano_face.is_none() && self.dimensions() != self.size
+    }
+
+    /// Load the part of the image inside `crop` at full resolution, without downscaling it.
+    pub async fn load_crop(&self, crop: ImageCrop) -> Result<DynamicImage> {
+        let img = self.load_full().await?;
+        Ok(img.crop_imm(crop.min.x, crop.min.y, crop.size.x, crop.size.y))
+    }
+
     pub async fn load(&self) -> Result<DynamicImage> {
-        let mut img_bytes = vec![];
-        self.vfs
-            .reader_at_path(&self.path)
-            .await?
-            .read_to_end(&mut img_bytes)
-            .await?;
-        let mut img = image::load_from_memory(&img_bytes)?;
+        // Panoramas are resampled at full resolution, straight to the size of the face.
+        if let Some(face) = self.pano_face {
+            return match &self.pano_faces {
+                Some(faces) => faces.load(face, self.width(), self.load_full()).await,
+                None => Ok(face.sample(&self.load_full().await?, self.width())),
+            };
+        }
+
+        let img = self.load_full().await?;
+
+        if img.width() <= self.max_resolution && img.height() <= self.max_resolution {
+            return Ok(img);
+        }
+        let max_resolution = self.max_resolution;
+        Ok(threads::run_blocking(move || {
+            img.resize(
+                max_resolution,
+                max_resolution,
+                image::imageops::FilterType::Triangle,
+            )
+        })
+        .await)
+    }
+
+    async fn load_full(&self) -> Result<DynamicImage> {
+        let mut img = if let Some(hdr) = &self.hdr {
+            hdr.load(&self.vfs).await?
+        } else {
+            let mut img_bytes = vec![];
+            self.vfs
+                .reader_at_path(&self.path)
+                .await?
+                .read_to_end(&mut img_bytes)
+                .await?;
+            decode_image_async(img_bytes, self.vfs.limits()).await?
+        };
+
+        let motion_mask = match &self.motion {
+            Some(motion) => Some(motion.load(&self.vfs, &img).await?),
+            None => None,
+        };
+
+        if let Some(lens) = &self.lens {
+            img = lens.correct(img, self.hdr.is_some());
+        }
+
+        // Merged brackets are linear already.
+        if self.color_space == ColorSpace::Linear && self.hdr.is_none() {
+            img = decode_srgb(img);
+        }
 
         // Copy over mask.
         // TODO: Interleave this work better & speed things up here.
+        let mut mask = None;
         if let Some(mask_path) = &self.mask_path {
-            // Add in alpha channel if needed to the image to copy the mask into.
-            let mut masked_img = img.into_rgba8();
             let mut mask_bytes = vec![];
             self.vfs
                 .reader_at_path(mask_path)
                 .await?
                 .read_to_end(&mut mask_bytes)
                 .await?;
-            let mask_img = image::load_from_memory(&mask_bytes)?;
-            if mask_img.color().has_alpha() {
-                let mask_img = mask_img.into_rgba8();
-                for (pixel, mask_pixel) in masked_img.pixels_mut().zip(mask_img.pixels()) {
-                    pixel[3] = mask_pixel[3];
+            let mask_img = decode_image_async(mask_bytes, self.vfs.limits()).await?;
+            mask = Some(if mask_img.color().has_alpha() {
+                mask_img
+                    .into_rgba8()
+                    .pixels()
+                    .map(|p| p[3])
+                    .collect::<Vec<u8>>()
+            } else {
+                mask_img.into_rgb8().pixels().map(|p| p[0]).collect()
+            });
+        }
+        // Moving parts are masked out on top of the mask of the dataset.
+        if let Some(motion_mask) = motion_mask {
+            mask = Some(match mask {
+                Some(mask) => mask
+                    .iter()
+                    .zip(motion_mask)
+                    .map(|(&a, b)| a.min(b))
+                    .collect(),
+                None => motion_mask,
+            });
+        }
+
+        if let Some(mask) = mask {
+            // Add in alpha channel if needed to the image to copy the mask into. Linear
+            // images stay float, to keep their precision and colors above 1.
+            if self.color_space == ColorSpace::Linear {
+                let mut masked_img = img.into_rgba32f();
+                for (pixel, mask) in masked_img.pixels_mut().zip(mask) {
+                    pixel[3] = mask as f32 / 255.0;
                 }
+                img = masked_img.into();
             } else {
-                let mask_img = mask_img.into_rgb8();
-                for (pixel, mask_pixel) in masked_img.pixels_mut().zip(mask_img.pixels()) {
-                    pixel[3] = mask_pixel[0];
+                let mut masked_img = img.into_rgba8();
+                for (pixel, mask) in masked_img.pixels_mut().zip(mask) {
+                    pixel[3] = mask;
                 }
+                img = masked_img.into();
             }
-            img = masked_img.into();
         }
-        if img.width() <= self.max_resolution && img.height() <= self.max_resolution {
-            return Ok(img);
+
+        if let Some(transform) = self.color_transform {
+            img = transform_colors(img, transform, self.color_space == ColorSpace::Linear);
         }
-        Ok(img.resize(
-            self.max_resolution,
-            self.max_resolution,
-            image::imageops::FilterType::Triangle,
-        ))
+        Ok(img)
     }
 
     pub fn is_masked(&self) -> bool {
-        self.mask_path.is_some()
+        self.mask_path.is_some() || self.motion.is_some()
+    }
+}
+
+/// Convert the colors of an sRGB encoded image to linear floats. Alpha is kept as is.
+fn decode_srgb(img: DynamicImage) -> DynamicImage {
+    let has_alpha = img.color().has_alpha();
+    let mut rgba = img.into_rgba32f();
+    for pixel in rgba.pixels_mut() {
+        pixel.0[..3]
+            .iter_mut()
+            .for_each(|c| *c = srgb_to_linear(*c));
+    }
+    let img = DynamicImage::ImageRgba32F(rgba);
+    if has_alpha {
+        img
+    } else {
+        DynamicImage::ImageRgb32F(img.into_rgb32f())
+    }
+}
+
+/// Map the colors of an image with `transform`. Linear images stay float, and can go above 1.
+fn transform_colors(img: DynamicImage, transform: Affine3A, linear: bool) -> DynamicImage {
+    let has_alpha = img.color().has_alpha();
+    let max = if linear { Vec3::INFINITY } else { Vec3::ONE };
+    let mut img = img.into_rgba32f();
+    for pixel in img.pixels_mut() {
+        let color = transform.transform_point3(vec3(pixel[0], pixel[1], pixel[2]));
+        let color = color.clamp(Vec3::ZERO, max);
+        pixel.0[..3].copy_from_slice(&color.to_array());
+    }
+    let img = DynamicImage::ImageRgba32F(img);
+    match (linear, has_alpha) {
+        (true, true) => img,
+        (true, false) => DynamicImage::ImageRgb32F(img.into_rgb32f()),
+        (false, true) => DynamicImage::ImageRgba8(img.into_rgba8()),
+        (false, false) => DynamicImage::ImageRgb8(img.into_rgb8()),
     }
 }
 
@@ -176,6 +453,35 @@ pub struct Scene {
     pub views: Arc<Vec<SceneView>>,
 }
 
+/// Views grouped into the sessions they were captured in, eg. in the morning and in the
+/// evening, going by the name of the folder their image is in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureSessions {
+    /// Name of each session.
+    pub names: Vec<String>,
+    /// Index of the session of each view.
+    pub of_view: Vec<usize>,
+}
+
+impl CaptureSessions {
+    fn from_paths<'a>(paths: impl Iterator<Item = &'a Path>) -> Self {
+        let mut names: Vec<String> = vec![];
+        let of_view = paths
+            .map(|path| {
+                let name = path
+                    .parent()
+                    .and_then(|dir| dir.file_name())
+                    .map_or_else(|| "root".to_owned(), |n| n.to_string_lossy().into_owned());
+                names.iter().position(|n| *n == name).unwrap_or_else(|| {
+                    names.push(name);
+                    names.len() - 1
+                })
+            })
+            .collect();
+        Self { names, of_view }
+    }
+}
+
 fn camera_distance_penalty(cam_local_to_world: Affine3A, reference: Affine3A) -> f32 {
     let mut penalty = 0.0;
     for off_x in [-1.0, 0.0, 1.0] {
@@ -236,6 +542,10 @@ impl Scene {
             .map(|(index, _)| index) // We return the index instead of the camera
     }
 
+    pub fn capture_sessions(&self) -> CaptureSessions {
+        CaptureSessions::from_paths(self.views.iter().map(|view| view.image.path.as_path()))
+    }
+
     pub fn estimate_extent(&self) -> Option<f32> {
         if self.views.len() < 5 {
             None
@@ -252,6 +562,17 @@ impl Scene {
 //
 // This assume the input image has un-premultiplied alpha, whereas the output has pre-multiplied alpha.
 pub fn view_to_sample_image(image: DynamicImage, alpha_is_mask: bool) -> DynamicImage {
+    if let DynamicImage::ImageRgba32F(mut rgba) = image {
+        // HDR images go above 1, so keep them as floats.
+        if !alpha_is_mask {
+            for pixel in rgba.pixels_mut() {
+                let a = pixel[3];
+                pixel.0[..3].iter_mut().for_each(|c| *c *= a);
+            }
+        }
+        return DynamicImage::ImageRgba32F(rgba);
+    }
+
     if image.color().has_alpha() && !alpha_is_mask {
         let mut rgba_bytes = image.to_rgba8();
 
@@ -289,6 +610,8 @@ pub struct SceneBatch<B: Backend> {
     pub img_tensor: Tensor<B, 3>,
     pub alpha_is_mask: bool,
     pub camera: Camera,
+    /// Index of the view in the scene this batch is of.
+    pub view_index: usize,
 }
 
 impl<B: Backend> SceneBatch<B> {
@@ -296,3 +619,56 @@ impl<B: Backend> SceneBatch<B> {
         self.img_tensor.shape().dims[2] == 4
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use image::{ColorType, DynamicImage, ImageFormat, RgbaImage};
+    use std::io::Cursor;
+
+    use std::path::Path;
+
+    use super::{CaptureSessions, decode_image, get_image_data};
+    use crate::brush_vfs::VfsLimits;
+
+    #[test]
+    fn sessions_by_folder() {
+        let paths = [
+            "images/morning/a.jpg",
+            "images/evening/b.jpg",
+            "images/morning/c.jpg",
+            "d.jpg",
+        ];
+        let sessions = CaptureSessions::from_paths(paths.iter().map(Path::new));
+        assert_eq!(sessions.names, ["morning", "evening", "root"]);
+        assert_eq!(sessions.of_view, [0, 1, 0, 2]);
+    }
+
+    #[test]
+    fn webp_keeps_alpha() {
+        let img = RgbaImage::from_fn(16, 8, |x, _| image::Rgba([255, 0, 0, (x * 16) as u8]));
+        let mut bytes = vec![];
+        DynamicImage::ImageRgba8(img)
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::WebP)
+            .expect("Failed to encode WebP");
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("Failed to create runtime");
+        let (size, color) = runtime
+            .block_on(get_image_data(&mut Cursor::new(&bytes)))
+            .expect("Failed to read WebP header");
+        assert_eq!(size, glam::uvec2(16, 8));
+        assert_eq!(color, ColorType::Rgba8);
+
+        let decoded = decode_image(&bytes, &VfsLimits::default())
+            .expect("Failed to decode WebP")
+            .into_rgba8();
+        assert_eq!(decoded.get_pixel(3, 0).0, [255, 0, 0, 48]);
+
+        let small = VfsLimits {
+            max_image_size: 8,
+            ..Default::default()
+        };
+        assert!(decode_image(&bytes, &small).is_err());
+    }
+}
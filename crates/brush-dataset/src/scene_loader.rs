@@ -1,118 +1,281 @@
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
+use brush_render::camera::Camera;
 use burn::prelude::Backend;
 use image::DynamicImage;
-use rand::{SeedableRng, seq::SliceRandom};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use tokio::sync::mpsc::Receiver;
-use tokio::sync::{RwLock, mpsc};
+use tokio::sync::{Mutex, Semaphore, mpsc};
 use tokio_with_wasm::alias as tokio_wasm;
 
-use crate::scene::{Scene, SceneBatch, sample_to_tensor, view_to_sample_image};
+use crate::LoadDataseConfig;
+use crate::scene::{
+    ImageCrop, Scene, SceneBatch, SceneView, sample_to_tensor, view_to_sample_image,
+};
+use crate::threads;
+use crate::view_scheduler::ViewScheduler;
 
 pub struct SceneLoader<B: Backend> {
     receiver: Receiver<SceneBatch<B>>,
+    cache: Arc<Mutex<ImageCache>>,
+    permits: Arc<Semaphore>,
+    order: Arc<std::sync::Mutex<SampleOrder>>,
 }
 
+/// How the cache of decoded images is doing.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheStats {
+    pub images: usize,
+    pub bytes: u64,
+    pub budget_bytes: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Decoded images, up to a budget. When full, the least recently used images are dropped.
 struct ImageCache {
-    states: Vec<Option<Arc<DynamicImage>>>,
-    max_size: usize,
-    size: usize,
+    /// Each cached image, with when it was last used.
+    states: Vec<Option<(Arc<DynamicImage>, u64)>>,
+    max_bytes: u64,
+    bytes: u64,
+    clock: u64,
+    hits: u64,
+    misses: u64,
 }
 
-// Cache at most some nr. of gigs of data.
-// TODO: Not sure if this should be configurable or not.
+// Cache at most some nr. of gigs of data by default.
 #[cfg(not(target_family = "wasm"))]
-const MAX_CACHE_MB: usize = 6 * 1024;
+const DEFAULT_CACHE_MB: u32 = 6 * 1024;
 
-// On WASM, not much hope a big dataset will work anyway but let's not
-// cache more than what fits in memory.
+// On WASM the dataset itself has to fit in memory too, and a tab that uses too much
+// memory is killed, so keep the cache small.
 #[cfg(target_family = "wasm")]
-const MAX_CACHE_MB: usize = 2 * 1024;
+const DEFAULT_CACHE_MB: u32 = 1024;
+
+fn image_bytes(image: &DynamicImage) -> u64 {
+    image.as_bytes().len() as u64
+}
 
 impl ImageCache {
-    fn new(max_size: usize, n_images: usize) -> Self {
+    fn new(max_mb: u32, n_images: usize) -> Self {
         Self {
             states: vec![None; n_images],
-            max_size,
-            size: 0,
+            max_bytes: max_mb as u64 * 1024 * 1024,
+            bytes: 0,
+            clock: 0,
+            hits: 0,
+            misses: 0,
         }
     }
 
-    fn try_get(&self, index: usize) -> Option<Arc<DynamicImage>> {
-        self.states[index].clone()
+    fn get(&mut self, index: usize) -> Option<Arc<DynamicImage>> {
+        self.clock += 1;
+        match &mut self.states[index] {
+            Some((image, last_used)) => {
+                *last_used = self.clock;
+                self.hits += 1;
+                Some(image.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
     }
 
-    fn insert(&mut self, index: usize, data: Arc<DynamicImage>) {
-        let data_size_mb = data.as_bytes().len() / (1024 * 1024);
+    fn insert(&mut self, index: usize, image: Arc<DynamicImage>) {
+        let size = image_bytes(&image);
+        if size > self.max_bytes || self.states[index].is_some() {
+            return;
+        }
+
+        while self.bytes + size > self.max_bytes {
+            let (oldest, _) = self
+                .states
+                .iter()
+                .enumerate()
+                .filter_map(|(i, state)| state.as_ref().map(|(_, last_used)| (i, *last_used)))
+                .min_by_key(|(_, last_used)| *last_used)
+                .expect("Cache over budget must hold images");
+            if let Some((evicted, _)) = self.states[oldest].take() {
+                self.bytes -= image_bytes(&evicted);
+            }
+        }
+
+        self.clock += 1;
+        self.states[index] = Some((image, self.clock));
+        self.bytes += size;
+    }
 
-        if self.size + data_size_mb < self.max_size && self.states[index].is_none() {
-            self.states[index] = Some(data);
-            self.size += data_size_mb;
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            images: self.states.iter().filter(|s| s.is_some()).count(),
+            bytes: self.bytes,
+            budget_bytes: self.max_bytes,
+            hits: self.hits,
+            misses: self.misses,
         }
     }
 }
 
+/// Random crop of a downscaled view at full resolution, as big as the view is loaded at.
+fn random_crop(view: &SceneView, rng: &mut impl Rng) -> ImageCrop {
+    let full = view.image.full_dimensions();
+    let size = view.image.dimensions().min(full);
+    let min = glam::uvec2(
+        rng.random_range(0..=full.x - size.x),
+        rng.random_range(0..=full.y - size.y),
+    );
+    ImageCrop { min, size }
+}
+
+/// One step of the training order.
+struct Sample {
+    position: u64,
+    index: usize,
+    crop: Option<ImageCrop>,
+}
+
+/// The order views are trained in: the views picked by the scheduler, with the occasional
+/// full resolution crop. Shared by all loading tasks, so the order only depends on the seed.
+struct SampleOrder {
+    views: Arc<Vec<SceneView>>,
+    scheduler: Box<dyn ViewScheduler>,
+    rng: StdRng,
+    position: u64,
+    full_res_crop_every: Option<u32>,
+}
+
+impl SampleOrder {
+    fn next(&mut self) -> Sample {
+        let index = self.scheduler.next_view(&mut self.rng);
+
+        let view = &self.views[index];
+        let crop = self
+            .full_res_crop_every
+            .filter(|&every| view.image.is_downscaled() && self.rng.random_ratio(1, every.max(1)))
+            .map(|_| random_crop(view, &mut self.rng));
+
+        self.position += 1;
+        Sample {
+            position: self.position - 1,
+            index,
+            crop,
+        }
+    }
+}
+
+async fn load_sample(
+    view: &SceneView,
+    sample: &Sample,
+    cache: &Mutex<ImageCache>,
+) -> (Arc<DynamicImage>, Camera) {
+    if let Some(crop) = sample.crop {
+        // Full resolution crops aren't cached, they're only needed once.
+        let image = view
+            .image
+            .load_crop(crop)
+            .await
+            .expect("Scene loader encountered an error while loading an image");
+        let image = Arc::new(view_to_sample_image(image, view.image.is_masked()));
+        let full_size = view.image.full_dimensions();
+        return (image, view.camera.crop(full_size, crop.min, crop.size));
+    }
+
+    if let Some(image) = cache.lock().await.get(sample.index) {
+        return (image, view.camera.clone());
+    }
+
+    let image = view
+        .image
+        .load()
+        .await
+        .expect("Scene loader encountered an error while loading an image");
+    // Don't premultiply the image if it's a mask - treat as fully opaque.
+    let image = Arc::new(view_to_sample_image(image, view.image.is_masked()));
+    cache.lock().await.insert(sample.index, image.clone());
+    (image, view.camera.clone())
+}
+
 impl<B: Backend> SceneLoader<B> {
-    pub fn new(scene: &Scene, seed: u64, device: &B::Device) -> Self {
+    /// Load batches of the views of `scene`, in the order of the configured view schedule.
+    pub fn new(
+        scene: &Scene,
+        seed: u64,
+        load_config: &LoadDataseConfig,
+        device: &B::Device,
+    ) -> Self {
+        let positions: Vec<_> = scene.views.iter().map(|v| v.camera.position).collect();
+        let scheduler = load_config.view_schedule.scheduler(&positions);
+        Self::with_scheduler(scene, seed, load_config, scheduler, device)
+    }
+
+    /// Load batches of the views of `scene`, in the order picked by `scheduler`.
+    ///
+    /// Images are decoded in parallel, in the order they're trained on, so the images needed
+    /// next are always decoded first. Decoded images are cached up to the configured budget.
+    ///
+    /// With `full_res_crop_every`, every nth batch of a view that's downscaled is instead a
+    /// crop of the full resolution image, loaded on demand. Only the downscaled images are
+    /// cached, so memory stays bounded for datasets of very large images.
+    pub fn with_scheduler(
+        scene: &Scene,
+        seed: u64,
+        load_config: &LoadDataseConfig,
+        scheduler: Box<dyn ViewScheduler>,
+        device: &B::Device,
+    ) -> Self {
+        // Number of batches to prefetch.
         let num_img_queue = 32;
 
-        // The bounded size == number of batches to prefetch.
         let (send_img, mut rec_imag) = mpsc::channel(num_img_queue);
 
-        // On wasm, there is little point to spawning multiple of these. In theory there would be
-        // IF file reading truly was async, but since the zip archive is just in memory it isn't really
-        // any faster.
-        let parallelism = if cfg!(target_family = "wasm") {
-            1
-        } else {
-            std::thread::available_parallelism()
-                .map(|x| x.get())
-                .unwrap_or(8)
-                // Don't need more threads than the image queue can hold, most
-                // threads would just sit around idling!
-                .min(num_img_queue) as u64
-        };
+        // On wasm without threads there is little point to spawning multiple of these, as
+        // decoding all happens on the main thread anyway. With threads, images are decoded on
+        // web workers.
+        let parallelism = threads::available()
+            // Don't need more threads than the image queue can hold, most
+            // threads would just sit around idling!
+            .min(num_img_queue);
         let num_views = scene.views.len();
 
-        let load_cache = Arc::new(RwLock::new(ImageCache::new(MAX_CACHE_MB, num_views)));
+        let cache_mb = load_config.image_cache_mb.unwrap_or(DEFAULT_CACHE_MB);
+        let load_cache = Arc::new(Mutex::new(ImageCache::new(cache_mb, num_views)));
+        let order = Arc::new(std::sync::Mutex::new(SampleOrder {
+            views: scene.views.clone(),
+            scheduler,
+            rng: StdRng::seed_from_u64(seed),
+            position: 0,
+            full_res_crop_every: load_config.full_res_crop_every,
+        }));
+        // A permit per sample that's loading or waiting for its turn. This keeps the loading
+        // tasks from running further ahead than the prefetch queue.
+        let permits = Arc::new(Semaphore::new(num_img_queue));
 
-        for i in 0..parallelism {
-            let mut rng = rand::rngs::StdRng::seed_from_u64(seed + i);
+        for _ in 0..parallelism {
             let send_img = send_img.clone();
             let views = scene.views.clone();
-
             let load_cache = load_cache.clone();
+            let order = order.clone();
+            let permits = permits.clone();
 
             tokio_wasm::spawn(async move {
-                let mut shuf_indices = vec![];
-
                 loop {
-                    let index = shuf_indices.pop().unwrap_or_else(|| {
-                        shuf_indices = (0..num_views).collect();
-                        shuf_indices.shuffle(&mut rng);
-                        shuf_indices
-                            .pop()
-                            .expect("Need at least one view in dataset")
-                    });
-
-                    let view = &views[index];
-
-                    let sample = if let Some(image) = load_cache.read().await.try_get(index) {
-                        image
-                    } else {
-                        let image = view
-                            .image
-                            .load()
-                            .await
-                            .expect("Scene loader encountered an error while loading an image");
-                        // Don't premultiply the image if it's a mask - treat as fully opaque.
-                        let sample = Arc::new(view_to_sample_image(image, view.image.is_masked()));
-                        load_cache.write().await.insert(index, sample.clone());
-                        sample
+                    let Ok(permit) = permits.clone().acquire_owned().await else {
+                        break;
                     };
+                    let sample = order.lock().expect("Poisoned sample order").next();
+                    let view = &views[sample.index];
+                    let (image, camera) = load_sample(view, &sample, &load_cache).await;
 
                     if send_img
-                        .send((sample, view.image.is_masked(), view.camera.clone()))
+                        .send((
+                            sample.position,
+                            permit,
+                            (sample.index, image, view.image.is_masked(), camera),
+                        ))
                         .await
                         .is_err()
                     {
@@ -125,29 +288,64 @@ impl<B: Backend> SceneLoader<B> {
 
         let device = device.clone();
         tokio_wasm::spawn(async move {
-            while let Some(rec) = rec_imag.recv().await {
-                let (sample, alpha_is_mask, camera) = rec;
-                let img_tensor = sample_to_tensor(&sample, &device);
-
-                if send_batch
-                    .send(SceneBatch {
-                        img_tensor,
-                        alpha_is_mask,
-                        camera,
-                    })
-                    .await
-                    .is_err()
-                {
-                    break;
+            // Samples finish loading out of order, pass them on in the training order.
+            let mut loaded = BTreeMap::new();
+            let mut next_position = 0;
+
+            while let Some((position, permit, rec)) = rec_imag.recv().await {
+                loaded.insert(position, (permit, rec));
+
+                while let Some((_permit, rec)) = loaded.remove(&next_position) {
+                    next_position += 1;
+                    let (view_index, sample, alpha_is_mask, camera) = rec;
+                    let img_tensor = sample_to_tensor(&sample, &device);
+
+                    if send_batch
+                        .send(SceneBatch {
+                            img_tensor,
+                            alpha_is_mask,
+                            camera,
+                            view_index,
+                        })
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
                 }
             }
         });
 
         Self {
             receiver: rec_batch,
+            cache: load_cache,
+            permits,
+            order,
         }
     }
 
+    /// Whether the view scheduler uses the loss of each step, see [`Self::report_loss`].
+    pub fn wants_loss(&self) -> bool {
+        self.order
+            .lock()
+            .expect("Poisoned sample order")
+            .scheduler
+            .wants_loss()
+    }
+
+    /// Pass the loss of a step on the batch of view `view_index` to the view scheduler.
+    pub fn report_loss(&self, view_index: usize, loss: f32) {
+        self.order
+            .lock()
+            .expect("Poisoned sample order")
+            .scheduler
+            .report_loss(view_index, loss);
+    }
+
+    pub async fn cache_stats(&self) -> CacheStats {
+        self.cache.lock().await.stats()
+    }
+
     pub async fn next_batch(&mut self) -> SceneBatch<B> {
         self.receiver
             .recv()
@@ -155,3 +353,39 @@ impl<B: Backend> SceneLoader<B> {
             .expect("Somehow lost data loading channel!")
     }
 }
+
+impl<B: Backend> Drop for SceneLoader<B> {
+    fn drop(&mut self) {
+        // Stop the loading tasks from starting on more images, so the cache is freed as soon
+        // as the images they're decoding are done.
+        self.permits.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use image::DynamicImage;
+
+    use super::ImageCache;
+
+    #[test]
+    fn least_recently_used_is_evicted() {
+        // Room for two 512x512 RGBA images.
+        let mut cache = ImageCache::new(2, 3);
+        let image = || Arc::new(DynamicImage::new_rgba8(512, 512));
+
+        cache.insert(0, image());
+        cache.insert(1, image());
+        assert!(cache.get(0).is_some());
+        cache.insert(2, image());
+
+        assert!(cache.get(1).is_none());
+        assert!(cache.get(0).is_some());
+        assert!(cache.get(2).is_some());
+        let stats = cache.stats();
+        assert_eq!(stats.images, 2);
+        assert_eq!((stats.hits, stats.misses), (3, 1));
+    }
+}
@@ -1,8 +1,12 @@
+use std::io::Write;
+
 use crate::parsed_gaussian::ParsedGaussian;
 use anyhow::anyhow;
 use brush_render::gaussian_splats::Splats;
+use brush_render::sh::{sh_coeffs_for_degree, sh_degree_from_coeffs};
 use burn::{prelude::Backend, tensor::DataError};
-use glam::{Quat, Vec3};
+use flate2::{Compression, write::GzEncoder};
+use glam::{Affine3A, Mat3, Quat, Vec3};
 use ply_rs::{
     ply::{self, Ply, PropertyDef, PropertyType, ScalarType},
     writer::Writer,
@@ -67,7 +71,90 @@ async fn read_splat_data<B: Backend>(
     Ok(splats)
 }
 
+/// Serialize the splats to a ply file, with -Y as their vertical axis.
 pub async fn splat_to_ply<B: Backend>(splats: Splats<B>) -> anyhow::Result<Vec<u8>> {
+    write_ply(splats, Some("y")).await
+}
+
+/// Serialize splats whose up direction is `up` to a ply file. With `upright`, the splats are
+/// rotated so `up` points along -Y, as most viewers expect. Otherwise the file names the axis
+/// closest to `up` as its vertical axis, if `up` is close to one it can name.
+pub async fn splat_to_ply_oriented<B: Backend>(
+    splats: Splats<B>,
+    up: Vec3,
+    upright: bool,
+) -> anyhow::Result<Vec<u8>> {
+    if upright {
+        let rotation = Quat::from_rotation_arc(up.normalize(), Vec3::NEG_Y);
+        write_ply(splats.transform(Affine3A::from_quat(rotation)), Some("y")).await
+    } else {
+        write_ply(splats, vertical_axis(up)).await
+    }
+}
+
+/// Name of the axis in the "Vertical axis" comment of a ply file for splats with `up` as their
+/// up direction. The comment names +X, -Y or +Z, so other directions have none.
+fn vertical_axis(up: Vec3) -> Option<&'static str> {
+    // Within about 25 degrees of the axis.
+    const MIN_COS: f32 = 0.9;
+    let up = up.normalize_or_zero();
+    [(Vec3::X, "x"), (Vec3::NEG_Y, "y"), (Vec3::Z, "z")]
+        .into_iter()
+        .find(|(axis, _)| up.dot(*axis) > MIN_COS)
+        .map(|(_, name)| name)
+}
+
+/// Estimate the up direction of splats without a dataset, from their positions. Scenes are
+/// mostly spread out horizontally, so up is along the axis the positions vary least in.
+/// Of the two directions along it, up is the one away from the bulk of the splats, which
+/// tend to sit on the ground.
+pub async fn estimate_splat_up<B: Backend>(splats: &Splats<B>) -> anyhow::Result<Vec3> {
+    let means = splats
+        .means
+        .val()
+        .into_data_async()
+        .await
+        .into_vec::<f32>()
+        .map_err(|e| anyhow!("Failed to read splat positions {e:?}"))?;
+    let points: Vec<Vec3> = means.chunks_exact(3).map(Vec3::from_slice).collect();
+    Ok(estimate_up_from_points(&points))
+}
+
+fn estimate_up_from_points(points: &[Vec3]) -> Vec3 {
+    if points.len() < 3 {
+        return Vec3::NEG_Y;
+    }
+    let mean = points.iter().sum::<Vec3>() / points.len() as f32;
+    let cov = points.iter().map(|&p| p - mean).fold(Mat3::ZERO, |acc, p| {
+        acc + Mat3::from_cols(p * p.x, p * p.y, p * p.z)
+    });
+
+    // The axis of least variance is the largest of the covariance subtracted from its trace,
+    // found by power iteration. Start off the axes, so none of them is missed.
+    let trace = cov.col(0).x + cov.col(1).y + cov.col(2).z;
+    let shifted = Mat3::from_diagonal(Vec3::splat(trace)) - cov;
+    let mut normal = Vec3::new(0.3, -0.9, 0.3).normalize();
+    for _ in 0..100 {
+        normal = (shifted * normal).normalize_or_zero();
+    }
+    if !normal.is_finite() || normal == Vec3::ZERO {
+        return Vec3::NEG_Y;
+    }
+
+    // The splats on the ground are on one side, with a long tail away from it, upwards.
+    let skew: f32 = points.iter().map(|&p| (p - mean).dot(normal).powi(3)).sum();
+    if skew == 0.0 {
+        // No way to tell, lean towards the up axis Brush uses.
+        normal * normal.dot(Vec3::NEG_Y).signum()
+    } else {
+        normal * skew.signum()
+    }
+}
+
+async fn write_ply<B: Backend>(
+    splats: Splats<B>,
+    vertical_axis: Option<&str>,
+) -> anyhow::Result<Vec<u8>> {
     let splats = splats.with_normed_rotations();
 
     let data = read_splat_data(splats.clone())
@@ -101,7 +188,9 @@ pub async fn splat_to_ply<B: Backend>(splats: Splats<B>) -> anyhow::Result<Vec<u
     ply.header.elements.push(vertex);
     ply.header.encoding = ply::Encoding::BinaryLittleEndian;
     ply.header.comments.push("Exported from Brush".to_owned());
-    ply.header.comments.push("Vertical axis: y".to_owned());
+    if let Some(axis) = vertical_axis {
+        ply.header.comments.push(format!("Vertical axis: {axis}"));
+    }
     ply.payload.insert("vertex".to_owned(), data);
 
     let mut buf = vec![];
@@ -109,3 +198,223 @@ pub async fn splat_to_ply<B: Backend>(splats: Splats<B>) -> anyhow::Result<Vec<u
     writer.write_ply(&mut buf, &mut ply)?;
     Ok(buf)
 }
+
+// The packed format of .spz files, see https://github.com/nianticlabs/spz.
+const SPZ_MAGIC: u32 = 0x5053_474e;
+const SPZ_VERSION: u32 = 2;
+const SPZ_MAX_SH_DEGREE: u32 = 3;
+// Bits of positions after the point, giving about 0.25mm precision in a scene of ±2km.
+const SPZ_FRACTIONAL_BITS: u8 = 12;
+const SPZ_COLOR_SCALE: f32 = 0.15;
+
+/// Sign of each SH coefficient when flipping the Y and Z axes, in the order of the coefficients.
+const SPZ_SH_FLIPS: [f32; 16] = [
+    1.0, -1.0, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, 1.0, -1.0, 1.0, -1.0, -1.0, 1.0, -1.0, 1.0,
+];
+
+fn spz_u8(value: f32) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+/// SH coefficients are quantized to `bits` bits, the rest of the byte is rounded away so it
+/// compresses well.
+fn spz_sh(value: f32, bits: u32) -> u8 {
+    let bucket = 1 << (8 - bits);
+    let quantized = (value * 128.0).round() as i32 + 128;
+    let quantized = (quantized + bucket / 2) / bucket * bucket;
+    quantized.clamp(0, 255) as u8
+}
+
+/// Pack splat parameters, in the layout of the [`Splats`] tensors, to an uncompressed .spz.
+///
+/// Splats use the axes of the ply format, with Y down and Z forward, while .spz files have
+/// Y up and Z backward, so the Y and Z axes are flipped.
+fn pack_spz(
+    means: &[f32],
+    rotations: &[f32],
+    log_scales: &[f32],
+    raw_opacities: &[f32],
+    sh_coeffs: &[f32],
+    sh_degree: u32,
+) -> Vec<u8> {
+    let num_points = raw_opacities.len();
+    let coeffs = sh_coeffs.len() / (num_points * 3).max(1);
+    let sh_degree = sh_degree.min(SPZ_MAX_SH_DEGREE);
+    let rest_coeffs = sh_coeffs_for_degree(sh_degree) as usize - 1;
+    let flip = Vec3::new(1.0, -1.0, -1.0);
+
+    let mut data = Vec::with_capacity(16 + num_points * (9 + 1 + 3 + 3 + 3 + rest_coeffs * 3));
+    data.extend(SPZ_MAGIC.to_le_bytes());
+    data.extend(SPZ_VERSION.to_le_bytes());
+    data.extend((num_points as u32).to_le_bytes());
+    data.extend([sh_degree as u8, SPZ_FRACTIONAL_BITS, 0, 0]);
+
+    let scale = (1 << SPZ_FRACTIONAL_BITS) as f32;
+    for mean in means.chunks_exact(3) {
+        let mean = Vec3::from_slice(mean) * flip;
+        for v in mean.to_array() {
+            let fixed = (v * scale).round() as i32;
+            data.extend(&fixed.to_le_bytes()[..3]);
+        }
+    }
+    for &raw in raw_opacities {
+        let alpha = 1.0 / (1.0 + (-raw).exp());
+        data.push(spz_u8(alpha * 255.0));
+    }
+    for i in 0..num_points {
+        for c in 0..3 {
+            let dc = sh_coeffs[i * coeffs * 3 + c];
+            data.push(spz_u8((dc * SPZ_COLOR_SCALE + 0.5) * 255.0));
+        }
+    }
+    for log_scale in log_scales {
+        data.push(spz_u8((log_scale + 10.0) * 16.0));
+    }
+    for rot in rotations.chunks_exact(4) {
+        // Stored as w, x, y, z. Only x, y, z are kept, with w positive.
+        let rot = Quat::from_xyzw(rot[1], -rot[2], -rot[3], rot[0]).normalize();
+        let rot = if rot.w < 0.0 { -rot } else { rot };
+        for v in [rot.x, rot.y, rot.z] {
+            data.push(spz_u8(v * 127.5 + 127.5));
+        }
+    }
+    for i in 0..num_points {
+        for k in 1..=rest_coeffs {
+            // Degree 1 keeps a bit more precision, as it's most visible.
+            let bits = if k <= 3 { 5 } else { 4 };
+            for c in 0..3 {
+                let value = if k < coeffs {
+                    sh_coeffs[(i * coeffs + k) * 3 + c] * SPZ_SH_FLIPS[k]
+                } else {
+                    0.0
+                };
+                data.push(spz_sh(value, bits));
+            }
+        }
+    }
+    data
+}
+
+/// Write the splats as a .spz file, a compressed format about a tenth the size of a ply.
+///
+/// Spherical harmonics above degree 3 are dropped, as .spz doesn't support them.
+pub async fn splat_to_spz<B: Backend>(splats: Splats<B>) -> anyhow::Result<Vec<u8>> {
+    let splats = splats.with_normed_rotations();
+    let read = |e: DataError| anyhow!("Failed to read data from splat {e:?}");
+
+    let means = splats
+        .means
+        .val()
+        .into_data_async()
+        .await
+        .to_vec()
+        .map_err(read)?;
+    let rotations = splats
+        .rotation
+        .val()
+        .into_data_async()
+        .await
+        .to_vec()
+        .map_err(read)?;
+    let log_scales = splats
+        .log_scales
+        .val()
+        .into_data_async()
+        .await
+        .to_vec()
+        .map_err(read)?;
+    let opacities = splats
+        .raw_opacity
+        .val()
+        .into_data_async()
+        .await
+        .to_vec()
+        .map_err(read)?;
+    let sh_coeffs = splats
+        .sh_coeffs
+        .val()
+        .into_data_async()
+        .await
+        .to_vec()
+        .map_err(read)?;
+    let sh_degree = sh_degree_from_coeffs(splats.sh_coeffs.dims()[1] as u32);
+
+    let packed = pack_spz(
+        &means,
+        &rotations,
+        &log_scales,
+        &opacities,
+        &sh_coeffs,
+        sh_degree,
+    );
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(&packed)?;
+    Ok(encoder.finish()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::{estimate_up_from_points, pack_spz, vertical_axis};
+
+    #[test]
+    fn up_of_ground_and_tree() {
+        // A wide patch of ground at y = 0, with a thin tree growing up towards -Y.
+        let mut points = vec![];
+        for x in -10..=10 {
+            for z in -10..=10 {
+                points.push(Vec3::new(x as f32, 0.0, z as f32 * 0.8));
+            }
+        }
+        for y in 1..=8 {
+            points.push(Vec3::new(0.5, -(y as f32), 0.0));
+        }
+        let up = estimate_up_from_points(&points);
+        assert!(up.dot(Vec3::NEG_Y) > 0.99, "{up}");
+
+        // Flipped upside down, up flips too.
+        let flipped: Vec<_> = points.iter().map(|p| Vec3::new(p.x, -p.y, p.z)).collect();
+        let up = estimate_up_from_points(&flipped);
+        assert!(up.dot(Vec3::Y) > 0.99, "{up}");
+    }
+
+    #[test]
+    fn vertical_axis_names() {
+        assert_eq!(vertical_axis(Vec3::new(0.1, -1.0, 0.0)), Some("y"));
+        assert_eq!(vertical_axis(Vec3::Z), Some("z"));
+        assert_eq!(vertical_axis(Vec3::Y), None);
+        assert_eq!(vertical_axis(Vec3::new(1.0, -1.0, 0.0)), None);
+    }
+
+    #[test]
+    fn spz_layout() {
+        let packed = pack_spz(
+            &[1.0, 2.0, -0.5],
+            &[1.0, 0.0, 0.0, 0.0],
+            &[-10.0, -10.0, -10.0],
+            &[0.0],
+            &[
+                0.0, 0.0, 0.0, 0.5, 0.5, 0.5, 0.0, 0.0, 0.0, 0.25, 0.25, 0.25,
+            ],
+            1,
+        );
+        assert_eq!(&packed[..4], b"NGSP");
+        assert_eq!(packed[12], 1);
+        assert_eq!(packed.len(), 16 + 9 + 1 + 3 + 3 + 3 + 9);
+
+        let body = &packed[16..];
+        // Y and Z are flipped, in 24 bit fixed point.
+        let fixed: Vec<i32> = body[..9]
+            .chunks_exact(3)
+            .map(|b| {
+                i32::from_le_bytes([b[0], b[1], b[2], if b[2] & 0x80 != 0 { 0xff } else { 0 }])
+            })
+            .collect();
+        assert_eq!(fixed, [4096, -8192, 2048]);
+        // Alpha, base color, scale and rotation.
+        assert_eq!(&body[9..19], &[128, 128, 128, 128, 0, 0, 0, 128, 128, 128]);
+        // The Y coefficient flips sign, the X coefficient doesn't.
+        assert_eq!(&body[19..], &[64, 64, 64, 128, 128, 128, 160, 160, 160]);
+    }
+}
@@ -1,7 +1,11 @@
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
+use std::io::Cursor;
 
 use async_fn_stream::try_fn_stream;
-use brush_render::{gaussian_splats::inverse_sigmoid, sh::rgb_to_sh};
+use brush_render::{
+    gaussian_splats::inverse_sigmoid,
+    sh::{rgb_to_sh, sh_coeffs_for_degree},
+};
 use burn::{
     prelude::Backend,
     tensor::{Tensor, TensorData},
@@ -9,23 +13,41 @@ use burn::{
 use glam::{Quat, Vec3, Vec4};
 use ply_rs::{
     parser::Parser,
-    ply::{DefaultElement, ElementDef, Encoding, Header, Property, PropertyAccess},
+    ply::{
+        DefaultElement, ElementDef, Encoding, Header, Property, PropertyAccess, PropertyType,
+        ScalarType,
+    },
 };
-use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
 use tokio_stream::{Stream, StreamExt};
 use tokio_with_wasm::alias as tokio_wasm;
 use tracing::trace_span;
+use web_time::Duration;
 
 use anyhow::{Context, Result};
 use brush_render::gaussian_splats::Splats;
 
-use crate::parsed_gaussian::ParsedGaussian;
+use crate::{
+    parsed_gaussian::{MAX_SH_DEGREE, ParsedGaussian},
+    progress::ByteProgress,
+    threads,
+};
 
+#[derive(Default)]
 pub struct ParseMetadata {
     pub up_axis: Option<Vec3>,
     pub total_splats: u32,
     pub frame_count: u32,
     pub current_frame: u32,
+    /// Bytes of the file read so far.
+    pub loaded_bytes: u64,
+    /// Size of the file, if it's known before reading all of it.
+    pub total_bytes: Option<u64>,
+    /// Estimated time left until the whole file is read.
+    pub eta: Option<Duration>,
+    /// Splats that couldn't be read, as the file was cut short or corrupt before them. Only
+    /// set when parsing tolerantly.
+    pub skipped_splats: u32,
 }
 
 pub struct SplatMessage<B: Backend> {
@@ -39,19 +61,35 @@ enum PlyFormat {
     SuperSplatCompressed,
 }
 
-fn interleave_coeffs(sh_dc: Vec3, sh_rest: &[f32], result: &mut Vec<f32>) {
+// Element counts in the header could be anything in a corrupt or malicious file. Only this
+// many splats are allocated for up front, past that vectors grow as splats are actually read.
+const MAX_PREALLOC: usize = 1 << 20;
+
+pub(crate) fn preallocated<T>(count: usize) -> Vec<T> {
+    Vec::with_capacity(count.min(MAX_PREALLOC))
+}
+
+/// Append the SH coefficients of a splat, from the per channel layout of the ply to the per
+/// coefficient layout of [`Splats`]. Rest coefficients that don't make up a full SH degree are
+/// dropped.
+pub(crate) fn interleave_coeffs(sh_dc: Vec3, sh_rest: &[f32], result: &mut Vec<f32>) {
     let channels = 3;
-    let coeffs_per_channel = sh_rest.len() / channels;
+    let rest_per_channel = sh_rest.len() / channels;
+    let coeffs_per_channel = (1..=MAX_SH_DEGREE)
+        .map(|degree| sh_coeffs_for_degree(degree) as usize - 1)
+        .take_while(|&coeffs| coeffs <= rest_per_channel)
+        .last()
+        .unwrap_or(0);
     result.extend([sh_dc.x, sh_dc.y, sh_dc.z]);
     for i in 0..coeffs_per_channel {
         for j in 0..channels {
-            let index = j * coeffs_per_channel + i;
+            let index = j * rest_per_channel + i;
             result.push(sh_rest[index]);
         }
     }
 }
 
-async fn parse_elem<T: AsyncBufRead + Unpin + 'static, E: PropertyAccess>(
+pub(crate) async fn parse_elem<T: AsyncBufRead + Unpin + 'static, E: PropertyAccess>(
     reader: &mut T,
     parser: &Parser<E>,
     encoding: Encoding,
@@ -70,6 +108,107 @@ async fn parse_elem<T: AsyncBufRead + Unpin + 'static, E: PropertyAccess>(
     }
 }
 
+fn scalar_size(scalar: &ScalarType) -> u64 {
+    match scalar {
+        ScalarType::Char | ScalarType::UChar => 1,
+        ScalarType::Short | ScalarType::UShort => 2,
+        ScalarType::Int | ScalarType::UInt | ScalarType::Float => 4,
+        ScalarType::Double => 8,
+    }
+}
+
+/// Size of one row of a binary element. Only known for elements without lists.
+fn row_size(element: &ElementDef) -> Option<u64> {
+    element
+        .properties
+        .iter()
+        .map(|p| match &p.data_type {
+            PropertyType::Scalar(scalar) => Some(scalar_size(scalar)),
+            PropertyType::List(..) => None,
+        })
+        .sum()
+}
+
+/// Size of the data after the header. Only known for binary files without lists.
+fn body_size(header: &Header) -> Option<u64> {
+    if matches!(header.encoding, Encoding::Ascii) {
+        return None;
+    }
+    header
+        .elements
+        .iter()
+        .map(|element| Some(row_size(element)? * element.count as u64))
+        .sum()
+}
+
+// Rows parsed on a worker at once, when parsing in batches.
+const MAX_BATCH_ROWS: usize = 1 << 16;
+
+/// Reads the rows of an element one by one, like [`parse_elem`].
+///
+/// On threaded wasm builds, rows of binary elements are instead read in batches, and parsed on
+/// a worker so parsing doesn't block the browser.
+struct RowReader<E> {
+    parser: Parser<E>,
+    batch: VecDeque<E>,
+    // Set when rows are parsed in batches.
+    row_size: Option<u64>,
+    batch_rows: usize,
+    remaining: usize,
+}
+
+impl<E: PropertyAccess + Send + 'static> RowReader<E> {
+    fn new(encoding: Encoding, element: &ElementDef, batch_rows: usize) -> Self {
+        let batched = cfg!(target_family = "wasm") && threads::ENABLED;
+        Self {
+            parser: Parser::new(),
+            batch: VecDeque::new(),
+            row_size: row_size(element).filter(|_| batched && !matches!(encoding, Encoding::Ascii)),
+            batch_rows: batch_rows.max(1),
+            remaining: element.count,
+        }
+    }
+
+    async fn next<T: AsyncBufRead + Unpin + 'static>(
+        &mut self,
+        reader: &mut T,
+        encoding: Encoding,
+        element: &ElementDef,
+    ) -> tokio::io::Result<E> {
+        let Some(row_size) = self.row_size else {
+            return parse_elem(reader, &self.parser, encoding, element).await;
+        };
+
+        if self.batch.is_empty() {
+            let rows = self.batch_rows.min(self.remaining);
+            let mut bytes = vec![0; rows * row_size as usize];
+            reader.read_exact(&mut bytes).await?;
+            self.remaining -= rows;
+
+            let element = element.clone();
+            self.batch = threads::run_blocking(move || {
+                let parser = Parser::<E>::new();
+                let mut rows_reader = Cursor::new(bytes);
+                (0..rows)
+                    .map(|_| {
+                        threads::poll_ready(parse_elem(
+                            &mut rows_reader,
+                            &parser,
+                            encoding,
+                            &element,
+                        ))
+                    })
+                    .collect::<tokio::io::Result<_>>()
+            })
+            .await?;
+        }
+
+        self.batch
+            .pop_front()
+            .ok_or_else(|| std::io::ErrorKind::UnexpectedEof.into())
+    }
+}
+
 struct TimeYield {
     last_yield: web_time::Instant,
     tick: usize,
@@ -98,13 +237,19 @@ impl TimeYield {
     }
 }
 
+/// Load the splats of a .ply file, streaming in partial splats while parsing.
+///
+/// With `tolerant`, a file that's truncated or corrupt part way through, eg. by an interrupted
+/// download, gives the splats read until then and logs a warning, instead of failing.
 pub fn load_splat_from_ply<T: AsyncRead + Unpin + 'static, B: Backend>(
     reader: T,
     subsample_points: Option<u32>,
+    tolerant: bool,
     device: B::Device,
 ) -> impl Stream<Item = Result<SplatMessage<B>>> + 'static {
     // set up a reader, in this case a file.
-    let mut reader = BufReader::new(reader);
+    let progress = ByteProgress::default();
+    let mut reader = BufReader::new(progress.reader(reader));
 
     let _span = trace_span!("Read splats").entered();
 
@@ -113,6 +258,16 @@ pub fn load_splat_from_ply<T: AsyncRead + Unpin + 'static, B: Backend>(
             .read_header(&mut reader)
             .await?;
 
+        // Whatever is still buffered was read past the header.
+        let header_size = progress.loaded_bytes() - reader.buffer().len() as u64;
+        progress.set_total(body_size(&header).map(|body| header_size + body));
+        let with_progress = |mut message: SplatMessage<B>| {
+            message.meta.loaded_bytes = progress.loaded_bytes();
+            message.meta.total_bytes = progress.total_bytes();
+            message.meta.eta = progress.eta();
+            message
+        };
+
         // Parse some metadata.
         let up_axis = header
             .comments
@@ -141,10 +296,16 @@ pub fn load_splat_from_ply<T: AsyncRead + Unpin + 'static, B: Backend>(
 
         match ply_type {
             PlyFormat::Ply => {
-                let mut stream =
-                    std::pin::pin!(parse_ply(reader, subsample_points, device, header, up_axis));
+                let mut stream = std::pin::pin!(parse_ply(
+                    reader,
+                    subsample_points,
+                    tolerant,
+                    device,
+                    header,
+                    up_axis
+                ));
                 while let Some(splat) = stream.next().await {
-                    emitter.emit(splat?).await;
+                    emitter.emit(with_progress(splat?)).await;
                 }
             }
             PlyFormat::Brush4DCompressed => {
@@ -156,7 +317,7 @@ pub fn load_splat_from_ply<T: AsyncRead + Unpin + 'static, B: Backend>(
                     up_axis
                 ));
                 while let Some(splat) = stream.next().await {
-                    emitter.emit(splat?).await;
+                    emitter.emit(with_progress(splat?)).await;
                 }
             }
             PlyFormat::SuperSplatCompressed => {
@@ -168,7 +329,7 @@ pub fn load_splat_from_ply<T: AsyncRead + Unpin + 'static, B: Backend>(
                     up_axis
                 ));
                 while let Some(splat) = stream.next().await {
-                    emitter.emit(splat?).await;
+                    emitter.emit(with_progress(splat?)).await;
                 }
             }
         };
@@ -180,6 +341,7 @@ pub fn load_splat_from_ply<T: AsyncRead + Unpin + 'static, B: Backend>(
 fn parse_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
     mut reader: T,
     subsample_points: Option<u32>,
+    tolerant: bool,
     device: B::Device,
     header: Header,
     up_axis: Option<Vec3>,
@@ -190,27 +352,45 @@ fn parse_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
             anyhow::bail!("First element must be 'vertex'")
         }
 
-        let parser = Parser::<ParsedGaussian<false>>::new();
-
         let properties: HashSet<_> = vertex.properties.iter().map(|x| x.name.clone()).collect();
-        let mut means = Vec::with_capacity(vertex.count);
+        let mut means = preallocated(vertex.count);
         let mut log_scales = properties
             .contains("scale_0")
-            .then(|| Vec::with_capacity(vertex.count));
+            .then(|| preallocated(vertex.count));
         let mut rotations = properties
             .contains("rot_0")
-            .then(|| Vec::with_capacity(vertex.count));
+            .then(|| preallocated(vertex.count));
         let mut sh_coeffs = (properties.contains("f_dc_0") || properties.contains("red"))
-            .then(|| Vec::with_capacity(vertex.count * 24));
+            .then(|| preallocated(vertex.count.saturating_mul(24)));
         let mut opacity = properties
             .contains("opacity")
-            .then(|| Vec::with_capacity(vertex.count));
+            .then(|| preallocated(vertex.count));
+
+        let message = |means: &[Vec3],
+                       rotations: Option<&[Quat]>,
+                       log_scales: Option<&[Vec3]>,
+                       sh_coeffs: Option<&[f32]>,
+                       opacity: Option<&[f32]>| SplatMessage {
+            meta: ParseMetadata {
+                total_splats: vertex.count as u32,
+                up_axis,
+                frame_count: 0,
+                current_frame: 0,
+                ..Default::default()
+            },
+            splats: Splats::from_raw(means, rotations, log_scales, sh_coeffs, opacity, &device),
+        };
 
         let update_every = vertex.count.div_ceil(20);
 
         let mut last_update = 0;
 
         let mut yielder = TimeYield::new();
+        let mut rows = RowReader::<ParsedGaussian<false>>::new(
+            header.encoding,
+            vertex,
+            update_every.min(MAX_BATCH_ROWS),
+        );
 
         for i in 0..vertex.count {
             yielder.try_yield().await;
@@ -223,7 +403,28 @@ fn parse_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
                 }
             }
 
-            let splat = parse_elem(&mut reader, &parser, header.encoding, vertex).await?;
+            let splat = match rows.next(&mut reader, header.encoding, vertex).await {
+                Ok(splat) => splat,
+                // Nothing to show without any splats, then the error is more use.
+                Err(e) if tolerant && !means.is_empty() => {
+                    log::warn!(
+                        "Ply file is truncated or corrupt after {i} of {} splats, showing the splats read so far: {e}",
+                        vertex.count
+                    );
+                    let mut truncated = message(
+                        &means,
+                        rotations.as_deref(),
+                        log_scales.as_deref(),
+                        sh_coeffs.as_deref(),
+                        opacity.as_deref(),
+                    );
+                    truncated.meta.total_splats = means.len() as u32;
+                    truncated.meta.skipped_splats = (vertex.count - i) as u32;
+                    emitter.emit(truncated).await;
+                    return Ok(());
+                }
+                Err(e) => return Err(e.into()),
+            };
 
             if !splat.is_finite() {
                 continue;
@@ -244,24 +445,14 @@ fn parse_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
             }
 
             if (i - last_update) >= update_every || i == vertex.count - 1 {
-                let splats = Splats::from_raw(
-                    &means,
-                    rotations.as_deref(),
-                    log_scales.as_deref(),
-                    sh_coeffs.as_deref(),
-                    opacity.as_deref(),
-                    &device,
-                );
                 emitter
-                    .emit(SplatMessage {
-                        meta: ParseMetadata {
-                            total_splats: vertex.count as u32,
-                            up_axis,
-                            frame_count: 0,
-                            current_frame: 0,
-                        },
-                        splats,
-                    })
+                    .emit(message(
+                        &means,
+                        rotations.as_deref(),
+                        log_scales.as_deref(),
+                        sh_coeffs.as_deref(),
+                        opacity.as_deref(),
+                    ))
                     .await;
 
                 last_update = i;
@@ -365,17 +556,17 @@ fn parse_compressed_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
         }
 
         let parser = Parser::<ParsedGaussian<true>>::new();
-        let mut means = Vec::with_capacity(vertex.count);
+        let mut means = preallocated(vertex.count);
         // Atm, unlike normal plys, these values aren't optional.
-        let mut log_scales = Vec::with_capacity(vertex.count);
-        let mut rotations = Vec::with_capacity(vertex.count);
-        let mut sh_coeffs = Vec::with_capacity(vertex.count);
-        let mut opacity = Vec::with_capacity(vertex.count);
+        let mut log_scales = preallocated(vertex.count);
+        let mut rotations = preallocated(vertex.count);
+        let mut sh_coeffs = preallocated(vertex.count.saturating_mul(3));
+        let mut opacity = preallocated(vertex.count);
 
         let update_every = vertex.count.div_ceil(20);
         let mut last_update = 0;
 
-        let mut valid = vec![true; vertex.count];
+        let mut invalid = HashSet::new();
 
         for i in 0..vertex.count {
             // Occasionally yield.
@@ -397,7 +588,7 @@ fn parse_compressed_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
 
             // Don't add invalid splats.
             if !splat.is_finite() {
-                valid[i] = false;
+                invalid.insert(i);
                 continue;
             }
 
@@ -422,6 +613,7 @@ fn parse_compressed_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
                             up_axis,
                             frame_count: 0,
                             current_frame: 0,
+                            ..Default::default()
                         },
                         splats: Splats::from_raw(
                             &means,
@@ -445,6 +637,9 @@ fn parse_compressed_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
             if sh_vals.name != "sh" {
                 anyhow::bail!("Second element should be SH compression metadata!");
             }
+            if sh_vals.count != vertex.count {
+                anyhow::bail!("SH compression metadata should have a value for each splat");
+            }
 
             let mut splat_index = 0;
 
@@ -452,21 +647,20 @@ fn parse_compressed_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
             for i in 0..sh_vals.count {
                 yielder.try_yield().await;
 
-                if !valid[i] {
+                // Parse a splat - though nb only SH values will be used. Skipped splats still
+                // need to be read to advance the reader.
+                let mut splat = parse_elem(&mut reader, &parser, header.encoding, sh_vals).await?;
+                if invalid.contains(&i) {
                     continue;
                 }
-
-                // Parse a splat - though nb only SH values will be used.
-                let mut splat = parse_elem(&mut reader, &parser, header.encoding, sh_vals).await?;
                 for coeff in &mut splat.sh_coeffs_rest {
                     *coeff = 8.0 * (*coeff - 0.5);
                 }
 
-                let dc = glam::vec3(
-                    sh_coeffs[splat_index * 3],
-                    sh_coeffs[splat_index * 3 + 1],
-                    sh_coeffs[splat_index * 3 + 2],
-                );
+                let dc = sh_coeffs
+                    .get(splat_index * 3..splat_index * 3 + 3)
+                    .context("More SH values than splats in compressed ply")?;
+                let dc = Vec3::from_slice(dc);
                 interleave_coeffs(dc, &splat.sh_coeffs_rest, &mut total_coeffs);
                 splat_index += 1;
             }
@@ -478,6 +672,7 @@ fn parse_compressed_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
                         up_axis,
                         frame_count: 0,
                         current_frame: 0,
+                        ..Default::default()
                     },
                     splats: Splats::from_raw(
                         &means,
@@ -538,18 +733,18 @@ fn parse_delta_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
             let properties: HashSet<_> =
                 element.properties.iter().map(|x| x.name.clone()).collect();
 
-            let mut means = Vec::with_capacity(element.count);
+            let mut means = preallocated(element.count);
             let mut log_scales = properties
                 .contains("scale_0")
-                .then(|| Vec::with_capacity(element.count));
+                .then(|| preallocated(element.count));
             let mut rotations = properties
                 .contains("rot_0")
-                .then(|| Vec::with_capacity(element.count));
+                .then(|| preallocated(element.count));
             let mut sh_coeffs = (properties.contains("f_dc_0") || properties.contains("red"))
-                .then(|| Vec::with_capacity(element.count * 24));
+                .then(|| preallocated(element.count.saturating_mul(24)));
             let mut opacity = properties
                 .contains("opacity")
-                .then(|| Vec::with_capacity(element.count));
+                .then(|| preallocated(element.count));
 
             if element.name == "vertex" {
                 let update_every = element.count.div_ceil(20);
@@ -566,6 +761,7 @@ fn parse_delta_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
                                     up_axis,
                                     frame_count,
                                     current_frame: frame,
+                                    ..Default::default()
                                 },
                                 splats: Splats::from_raw(
                                     &means,
@@ -619,6 +815,7 @@ fn parse_delta_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
                             up_axis,
                             frame_count,
                             current_frame: frame,
+                            ..Default::default()
                         },
                         splats,
                     })
@@ -665,6 +862,13 @@ fn parse_delta_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
                 }
 
                 let n_splats = splats.num_splats() as usize;
+                if means.len() != n_splats {
+                    anyhow::bail!(
+                        "Frame {} has {} splats, but the base splat has {n_splats}",
+                        element.name,
+                        means.len()
+                    );
+                }
                 let means_tensor: Vec<f32> = means.iter().flat_map(|v| [v.x, v.y, v.z]).collect();
                 let means =
                     Tensor::from_data(TensorData::new(means_tensor, [n_splats, 3]), &device)
@@ -702,6 +906,7 @@ fn parse_delta_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
                             up_axis,
                             frame_count,
                             current_frame: frame,
+                            ..Default::default()
                         },
                         splats: Splats::from_tensor_data(
                             means,
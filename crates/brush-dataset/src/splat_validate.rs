@@ -0,0 +1,391 @@
+//! Checking splat files for values that break rendering and training, and fixing them.
+//!
+//! Files written by other tools can have NaNs, splats without any size, or opacities far
+//! outside what the sigmoid can tell apart. Each kind of problem is counted, and can be fixed
+//! by removing or clamping the splats.
+
+use std::{collections::HashSet, fmt};
+
+use anyhow::Result;
+use brush_render::gaussian_splats::Splats;
+use burn::prelude::Backend;
+use glam::{Quat, Vec3};
+use ply_rs::parser::Parser;
+use ply_rs::ply::DefaultElement;
+use tokio::io::{AsyncRead, BufReader};
+
+use crate::parsed_gaussian::{MAX_SH_DEGREE, MAX_SH_REST, ParsedGaussian};
+use crate::splat_import::{interleave_coeffs, parse_elem, preallocated};
+
+/// Raw opacities are logits, beyond this the activated opacity is 0 or 1 to float precision.
+const MAX_OPACITY_LOGIT: f32 = 20.0;
+/// Range of the log of the scales. Beyond this splats are too small to ever be visible, or
+/// larger than any scene.
+const MIN_LOG_SCALE: f32 = -20.0;
+const MAX_LOG_SCALE: f32 = 20.0;
+
+/// Problems found in a splat file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub total: usize,
+    /// Splats with NaN or infinite values. Removed when fixing.
+    pub non_finite: usize,
+    /// Splats at exactly the same position as an earlier one. Removed when fixing.
+    pub duplicates: usize,
+    /// Clamped when fixing.
+    pub opacity_out_of_range: usize,
+    /// Splats with a scale that's practically zero or infinite. Clamped when fixing.
+    pub degenerate_scales: usize,
+    /// Splats with a zero rotation quaternion. Reset when fixing.
+    pub degenerate_rotations: usize,
+    /// Splats more stretched than the max anisotropy, if one was given. Widened when fixing.
+    pub too_anisotropic: usize,
+    /// What's wrong with the SH coefficient properties, if anything. Coefficients that don't
+    /// make up a full SH degree are dropped when fixing.
+    pub sh_layout: Option<String>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        *self
+            == Self {
+                total: self.total,
+                ..Default::default()
+            }
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} splats", self.total)?;
+        if self.is_valid() {
+            return writeln!(f, "✅ No problems found");
+        }
+        let problems = [
+            (
+                self.non_finite,
+                "with NaN or infinite values (removed by --fix)",
+            ),
+            (
+                self.duplicates,
+                "at the same position as another (removed by --fix)",
+            ),
+            (
+                self.opacity_out_of_range,
+                "with opacities out of range (clamped by --fix)",
+            ),
+            (
+                self.degenerate_scales,
+                "with zero or infinite scales (clamped by --fix)",
+            ),
+            (
+                self.degenerate_rotations,
+                "with a zero rotation (reset by --fix)",
+            ),
+            (
+                self.too_anisotropic,
+                "more stretched than --max-anisotropy (widened by --fix)",
+            ),
+        ];
+        for (count, problem) in problems {
+            if count > 0 {
+                writeln!(f, "❌ {count} splats {problem}")?;
+            }
+        }
+        if let Some(sh_layout) = &self.sh_layout {
+            writeln!(f, "❌ {sh_layout}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The splats of a validated file, with the problems fixed.
+pub struct FixedSplats {
+    means: Vec<Vec3>,
+    rotations: Option<Vec<Quat>>,
+    log_scales: Option<Vec<Vec3>>,
+    sh_coeffs: Option<Vec<f32>>,
+    opacities: Option<Vec<f32>>,
+}
+
+impl FixedSplats {
+    pub fn into_splats<B: Backend>(self, device: &B::Device) -> Splats<B> {
+        Splats::from_raw(
+            &self.means,
+            self.rotations.as_deref(),
+            self.log_scales.as_deref(),
+            self.sh_coeffs.as_deref(),
+            self.opacities.as_deref(),
+            device,
+        )
+    }
+}
+
+/// Number of SH coefficients per channel to keep of `rest` higher order coefficients. Returns
+/// what's wrong with the layout, if anything.
+fn check_sh_layout(rest_indices: &[usize], has_dc: bool) -> (usize, Option<String>) {
+    let count = rest_indices.len();
+    if count == 0 {
+        return (0, None);
+    }
+    if !has_dc {
+        return (
+            0,
+            Some("Has f_rest_* but no f_dc_* coefficients".to_owned()),
+        );
+    }
+    if rest_indices.iter().enumerate().any(|(i, &idx)| i != idx) {
+        return (0, Some("The f_rest_* coefficients have gaps".to_owned()));
+    }
+    if count > MAX_SH_REST as usize {
+        let message =
+            format!("{count} f_rest_* coefficients are more than SH degree {MAX_SH_DEGREE} has");
+        return (0, Some(message));
+    }
+    if count % 3 != 0 {
+        let message = format!("{count} f_rest_* coefficients don't split into 3 color channels");
+        return (0, Some(message));
+    }
+
+    let per_channel = count / 3;
+    // The largest full degree that fits, (degree + 1)² - 1 coefficients per channel.
+    let degree = ((per_channel + 1) as f32).sqrt().floor() as usize - 1;
+    let full = (degree + 1).pow(2) - 1;
+    if full == per_channel {
+        (per_channel, None)
+    } else {
+        let message = format!(
+            "{count} f_rest_* coefficients aren't a full SH degree, only degree {degree} is kept"
+        );
+        (full, Some(message))
+    }
+}
+
+/// Widen the smaller axes of a splat, so its largest axis is at most `max_anisotropy` times
+/// as long as the smallest.
+pub fn clamp_anisotropy(log_scale: Vec3, max_anisotropy: f32) -> Vec3 {
+    let min_allowed = log_scale.max_element() - max_anisotropy.max(1.0).ln();
+    log_scale.max(Vec3::splat(min_allowed))
+}
+
+/// Check the splats of a .ply file, and fix the problems found. With `max_anisotropy`, splats
+/// that are more stretched than that are reported and widened too.
+pub async fn validate_ply<T: AsyncRead + Unpin + 'static>(
+    reader: T,
+    max_anisotropy: Option<f32>,
+) -> Result<(ValidationReport, FixedSplats)> {
+    let mut reader = BufReader::new(reader);
+    let header = Parser::<DefaultElement>::new()
+        .read_header(&mut reader)
+        .await?;
+
+    let Some(vertex) = header.elements.first().filter(|e| e.name == "vertex") else {
+        anyhow::bail!("Only uncompressed .ply files with splats as vertices can be validated");
+    };
+
+    let properties: HashSet<_> = vertex.properties.iter().map(|p| p.name.as_str()).collect();
+    let mut rest_indices: Vec<usize> = vertex
+        .properties
+        .iter()
+        .filter_map(|p| p.name.strip_prefix("f_rest_")?.parse().ok())
+        .collect();
+    rest_indices.sort_unstable();
+    let has_dc = properties.contains("f_dc_0") || properties.contains("red");
+    let (keep_rest, sh_layout) = check_sh_layout(&rest_indices, has_dc);
+    let rest_per_channel = rest_indices.len() / 3;
+
+    let mut report = ValidationReport {
+        total: vertex.count,
+        sh_layout,
+        ..Default::default()
+    };
+    let mut fixed = FixedSplats {
+        means: preallocated(vertex.count),
+        rotations: properties.contains("rot_0").then(Vec::new),
+        log_scales: properties.contains("scale_0").then(Vec::new),
+        sh_coeffs: has_dc.then(Vec::new),
+        opacities: properties.contains("opacity").then(Vec::new),
+    };
+
+    let parser = Parser::<ParsedGaussian<false>>::new();
+    let mut positions = HashSet::new();
+    let mut rest = vec![];
+
+    for _ in 0..vertex.count {
+        let splat = parse_elem(&mut reader, &parser, header.encoding, vertex).await?;
+
+        if !splat.is_finite() {
+            report.non_finite += 1;
+            continue;
+        }
+        if !positions.insert(splat.mean.to_array().map(f32::to_bits)) {
+            report.duplicates += 1;
+            continue;
+        }
+        fixed.means.push(splat.mean);
+
+        if let Some(opacities) = &mut fixed.opacities {
+            if splat.opacity.abs() > MAX_OPACITY_LOGIT {
+                report.opacity_out_of_range += 1;
+            }
+            opacities.push(splat.opacity.clamp(-MAX_OPACITY_LOGIT, MAX_OPACITY_LOGIT));
+        }
+
+        if let Some(log_scales) = &mut fixed.log_scales {
+            let clamped = splat
+                .log_scale
+                .clamp(Vec3::splat(MIN_LOG_SCALE), Vec3::splat(MAX_LOG_SCALE));
+            if clamped != splat.log_scale {
+                report.degenerate_scales += 1;
+            }
+            let clamped = match max_anisotropy {
+                Some(max_anisotropy) => {
+                    let widened = clamp_anisotropy(clamped, max_anisotropy);
+                    if widened != clamped {
+                        report.too_anisotropic += 1;
+                    }
+                    widened
+                }
+                None => clamped,
+            };
+            log_scales.push(clamped);
+        }
+
+        if let Some(rotations) = &mut fixed.rotations {
+            let rotation = if splat.rotation.length_squared() < 1e-12 {
+                report.degenerate_rotations += 1;
+                Quat::IDENTITY
+            } else {
+                splat.rotation.normalize()
+            };
+            rotations.push(rotation);
+        }
+
+        if let Some(sh_coeffs) = &mut fixed.sh_coeffs {
+            // Rest coefficients are stored per channel, keep the first of each channel.
+            rest.clear();
+            for channel in 0..3 {
+                let start = channel * rest_per_channel;
+                if let Some(coeffs) = splat.sh_coeffs_rest.get(start..start + keep_rest) {
+                    rest.extend_from_slice(coeffs);
+                }
+            }
+            interleave_coeffs(splat.sh_dc, &rest, sh_coeffs);
+        }
+    }
+
+    Ok((report, fixed))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{ValidationReport, check_sh_layout, clamp_anisotropy, validate_ply};
+
+    const HEADER: &str = "ply
+format ascii 1.0
+element vertex 6
+property float x
+property float y
+property float z
+property float scale_0
+property float scale_1
+property float scale_2
+property float rot_0
+property float rot_1
+property float rot_2
+property float rot_3
+property float opacity
+property float f_dc_0
+property float f_dc_1
+property float f_dc_2
+end_header
+";
+
+    #[test]
+    fn problems_are_found_and_fixed() {
+        let rows = [
+            "0 0 0 -3 -3 -3 1 0 0 0 0.5 0.1 0.2 0.3",
+            "1 0 0 -3 -3 -3 1 0 0 0 nan 0.1 0.2 0.3",
+            "0 0 0 -3 -3 -3 1 0 0 0 0.5 0.1 0.2 0.3",
+            "2 0 0 -3 -3 -3 1 0 0 0 100 0.1 0.2 0.3",
+            "3 0 0 -50 -3 -3 1 0 0 0 0.5 0.1 0.2 0.3",
+            "4 0 0 -3 -3 -3 0 0 0 0 0.5 0.1 0.2 0.3",
+        ];
+        let ply = format!("{HEADER}{}\n", rows.join("\n"));
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("Failed to make runtime");
+        let (report, fixed) = runtime
+            .block_on(validate_ply(Cursor::new(ply.into_bytes()), None))
+            .expect("Failed to validate");
+
+        assert_eq!(
+            report,
+            ValidationReport {
+                total: 6,
+                non_finite: 1,
+                duplicates: 1,
+                opacity_out_of_range: 1,
+                degenerate_scales: 1,
+                degenerate_rotations: 1,
+                too_anisotropic: 0,
+                sh_layout: None,
+            }
+        );
+        assert_eq!(fixed.means.len(), 4);
+        assert_eq!(fixed.opacities.expect("Has opacities")[1], 20.0);
+    }
+
+    #[test]
+    fn anisotropic_splats_are_widened() {
+        let ply = format!(
+            "{}0 0 0 -3 -3 -3 1 0 0 0 0.5 0.1 0.2 0.3\n1 0 0 0 -3 -6 1 0 0 0 0.5 0.1 0.2 0.3\n",
+            HEADER.replace("vertex 6", "vertex 2")
+        );
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("Failed to make runtime");
+        let (report, fixed) = runtime
+            .block_on(validate_ply(Cursor::new(ply.into_bytes()), Some(10.0)))
+            .expect("Failed to validate");
+        assert_eq!(report.too_anisotropic, 1);
+
+        let widened = fixed.log_scales.expect("Has scales")[1];
+        let expected = clamp_anisotropy(glam::vec3(0.0, -3.0, -6.0), 10.0);
+        assert_eq!(widened, expected);
+        assert!((widened.max_element() - widened.min_element() - 10f32.ln()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn partial_sh_degrees_are_truncated() {
+        let indices: Vec<_> = (0..45).collect();
+        assert_eq!(check_sh_layout(&indices, true), (15, None));
+        // Degree 2 has 8 coefficients per channel, 10 is part way to degree 3.
+        let indices: Vec<_> = (0..30).collect();
+        assert_eq!(check_sh_layout(&indices, true).0, 8);
+        assert!(check_sh_layout(&[0, 1, 3], true).1.is_some());
+        assert_eq!(check_sh_layout(&[], false), (0, None));
+    }
+
+    #[test]
+    fn declared_counts_are_not_trusted() {
+        // A huge splat count and SH property index, with a single splat of data.
+        let ply = format!(
+            "{}0 0 0 -3 -3 -3 1 0 0 0 0.5 0.1 0.2 0.3 1\n",
+            HEADER
+                .replace("vertex 6", "vertex 4000000000000")
+                .replace("end_header", "property float f_rest_4000000000\nend_header")
+        );
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("Failed to make runtime");
+        assert!(
+            runtime
+                .block_on(validate_ply(Cursor::new(ply.into_bytes()), None))
+                .is_err()
+        );
+    }
+}
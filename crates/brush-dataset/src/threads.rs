@@ -0,0 +1,95 @@
+//! Running CPU heavy loading work off the main thread.
+//!
+//! Natively loading runs on the multi threaded tokio runtime already. On the web, work only
+//! leaves the main thread when the wasm is built with atomics (and so `SharedArrayBuffer`),
+//! in which case `tokio_with_wasm` runs blocking work on web workers. Without atomics,
+//! everything here runs inline as before.
+
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+
+/// Whether this build can run work on other threads than the one loading.
+pub const ENABLED: bool = cfg!(any(not(target_family = "wasm"), target_feature = "atomics"));
+
+/// Number of tasks worth running in parallel to load data.
+pub fn available() -> usize {
+    #[cfg(not(target_family = "wasm"))]
+    {
+        std::thread::available_parallelism()
+            .map(|x| x.get())
+            .unwrap_or(8)
+    }
+
+    #[cfg(all(target_family = "wasm", target_feature = "atomics"))]
+    {
+        web_sys::window()
+            .map(|w| w.navigator().hardware_concurrency() as usize)
+            .unwrap_or(4)
+            .max(1)
+    }
+
+    #[cfg(all(target_family = "wasm", not(target_feature = "atomics")))]
+    {
+        1
+    }
+}
+
+/// Run `f` on a web worker on threaded wasm builds, so it doesn't block the browser. Elsewhere
+/// `f` just runs inline, natively loading tasks are on their own threads already.
+pub async fn run_blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    #[cfg(all(target_family = "wasm", target_feature = "atomics"))]
+    {
+        tokio_with_wasm::alias::task::spawn_blocking(f)
+            .await
+            .expect("Loading worker panicked")
+    }
+
+    #[cfg(not(all(target_family = "wasm", target_feature = "atomics")))]
+    {
+        f()
+    }
+}
+
+/// Run blocking work that takes a while, like parsing a whole file at once, without holding
+/// up the loading task. Natively this runs on a blocking thread, elsewhere like
+/// [`run_blocking`].
+pub async fn spawn_blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    #[cfg(not(target_family = "wasm"))]
+    {
+        tokio_with_wasm::alias::task::spawn_blocking(f)
+            .await
+            .expect("Loading worker panicked")
+    }
+
+    #[cfg(target_family = "wasm")]
+    {
+        run_blocking(f).await
+    }
+}
+
+/// Get the output of a future that never waits, like reading from an in memory buffer.
+pub(crate) fn poll_ready<F: Future>(fut: F) -> F::Output {
+    let fut = pin!(fut);
+    match fut.poll(&mut Context::from_waker(Waker::noop())) {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("Future unexpectedly had to wait"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use tokio::io::AsyncReadExt;
+
+    use super::poll_ready;
+
+    #[test]
+    fn in_memory_reads_are_ready() {
+        let mut reader = Cursor::new(vec![1u8, 2, 3, 4]);
+        let mut buf = [0; 4];
+        poll_ready(reader.read_exact(&mut buf)).expect("Read failed");
+        assert_eq!(buf, [1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,278 @@
+//! Which view is trained on next.
+//!
+//! The scene loader asks a [`ViewScheduler`] for the index of each view it loads, so the
+//! sampling policy can be swapped out without touching the training loop. The built in
+//! schedulers are picked with [`ViewSchedule`], custom ones can be passed to
+//! [`crate::scene_loader::SceneLoader::with_scheduler`].
+
+use glam::Vec3;
+use rand::rngs::StdRng;
+use rand::{Rng, seq::SliceRandom};
+use serde::{Deserialize, Serialize};
+
+use crate::WasmNotSend;
+
+pub trait ViewScheduler: WasmNotSend {
+    /// Index of the view to train on next.
+    fn next_view(&mut self, rng: &mut StdRng) -> usize;
+
+    /// Whether the scheduler uses the loss of each step. Reading back the loss syncs with the
+    /// GPU, so it's only reported when needed.
+    fn wants_loss(&self) -> bool {
+        false
+    }
+
+    /// The loss of a step trained on view `index`. As views are loaded ahead of time, this
+    /// arrives a few views after the view was picked.
+    fn report_loss(&mut self, _index: usize, _loss: f32) {}
+}
+
+/// The built in view schedulers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum ViewSchedule {
+    /// All views in the order of the dataset.
+    Sequential,
+    /// Shuffled epochs, each view is trained on once before any view is repeated.
+    #[default]
+    Random,
+    /// Views with a higher loss are trained on more often.
+    LossWeighted,
+    /// Views are grouped by position, and each group takes a turn, so consecutive steps are
+    /// spread around the scene.
+    Spatial,
+}
+
+impl ViewSchedule {
+    /// Create the scheduler for views at the given camera positions.
+    pub fn scheduler(self, positions: &[Vec3]) -> Box<dyn ViewScheduler> {
+        let num_views = positions.len();
+        match self {
+            Self::Sequential => Box::new(Sequential { num_views, next: 0 }),
+            Self::Random => Box::new(Shuffled::new((0..num_views).collect())),
+            Self::LossWeighted => Box::new(LossWeighted {
+                losses: vec![None; num_views],
+            }),
+            Self::Spatial => Box::new(SpatialRoundRobin::new(positions)),
+        }
+    }
+}
+
+struct Sequential {
+    num_views: usize,
+    next: usize,
+}
+
+impl ViewScheduler for Sequential {
+    fn next_view(&mut self, _rng: &mut StdRng) -> usize {
+        let index = self.next;
+        self.next = (self.next + 1) % self.num_views;
+        index
+    }
+}
+
+/// Shuffled epochs of a set of views.
+struct Shuffled {
+    views: Vec<usize>,
+    epoch: Vec<usize>,
+}
+
+impl Shuffled {
+    fn new(views: Vec<usize>) -> Self {
+        Self {
+            views,
+            epoch: vec![],
+        }
+    }
+}
+
+impl ViewScheduler for Shuffled {
+    fn next_view(&mut self, rng: &mut StdRng) -> usize {
+        if self.epoch.is_empty() {
+            self.epoch = self.views.clone();
+            self.epoch.shuffle(rng);
+        }
+        self.epoch.pop().expect("Need at least one view in dataset")
+    }
+}
+
+/// How quickly the loss of a view follows new losses.
+const LOSS_SMOOTHING: f32 = 0.5;
+/// Share of the steps that pick a view uniformly, so views with a low loss aren't forgotten.
+const UNIFORM_SHARE: f64 = 0.2;
+
+struct LossWeighted {
+    /// Smoothed loss of each view, if it has been trained on yet.
+    losses: Vec<Option<f32>>,
+}
+
+impl ViewScheduler for LossWeighted {
+    fn next_view(&mut self, rng: &mut StdRng) -> usize {
+        let num_views = self.losses.len();
+        assert!(num_views > 0, "Need at least one view in dataset");
+        if rng.random_bool(UNIFORM_SHARE) {
+            return rng.random_range(0..num_views);
+        }
+
+        // Views that haven't been trained on yet count as the worst view.
+        let max_loss = self.losses.iter().flatten().copied().fold(1e-6, f32::max);
+        let weight = |loss: &Option<f32>| loss.unwrap_or(max_loss).max(1e-6);
+        let total: f32 = self.losses.iter().map(weight).sum();
+
+        let mut pick = rng.random_range(0.0..total);
+        for (index, loss) in self.losses.iter().enumerate() {
+            pick -= weight(loss);
+            if pick < 0.0 {
+                return index;
+            }
+        }
+        num_views - 1
+    }
+
+    fn wants_loss(&self) -> bool {
+        true
+    }
+
+    fn report_loss(&mut self, index: usize, loss: f32) {
+        if !loss.is_finite() {
+            return;
+        }
+        let smoothed = &mut self.losses[index];
+        *smoothed = Some(match *smoothed {
+            Some(old) => old + (loss - old) * LOSS_SMOOTHING,
+            None => loss,
+        });
+    }
+}
+
+/// Max number of groups of nearby views.
+const SPATIAL_GROUPS: usize = 8;
+
+struct SpatialRoundRobin {
+    groups: Vec<Shuffled>,
+    next_group: usize,
+}
+
+impl SpatialRoundRobin {
+    fn new(positions: &[Vec3]) -> Self {
+        let groups = group_by_position(positions, SPATIAL_GROUPS)
+            .into_iter()
+            .map(Shuffled::new)
+            .collect();
+        Self {
+            groups,
+            next_group: 0,
+        }
+    }
+}
+
+impl ViewScheduler for SpatialRoundRobin {
+    fn next_view(&mut self, rng: &mut StdRng) -> usize {
+        let group = &mut self.groups[self.next_group];
+        self.next_group = (self.next_group + 1) % self.groups.len();
+        group.next_view(rng)
+    }
+}
+
+/// Split views into up to `max_groups` groups of nearby positions, with a few rounds of
+/// k-means starting from far apart views. No group is empty.
+fn group_by_position(positions: &[Vec3], max_groups: usize) -> Vec<Vec<usize>> {
+    assert!(!positions.is_empty(), "Need at least one view in dataset");
+
+    // Start each group at the view furthest from the groups so far.
+    let mut centers = vec![positions[0]];
+    while centers.len() < max_groups.min(positions.len()) {
+        let furthest = positions
+            .iter()
+            .map(|p| {
+                centers
+                    .iter()
+                    .map(|c| c.distance_squared(*p))
+                    .fold(f32::MAX, f32::min)
+            })
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| i)
+            .expect("Positions aren't empty");
+        centers.push(positions[furthest]);
+    }
+
+    let assign = |centers: &[Vec3]| {
+        let mut groups = vec![vec![]; centers.len()];
+        for (i, p) in positions.iter().enumerate() {
+            let nearest = centers
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| a.distance_squared(*p).total_cmp(&b.distance_squared(*p)))
+                .map(|(i, _)| i)
+                .expect("Need at least one group");
+            groups[nearest].push(i);
+        }
+        groups
+    };
+
+    for _ in 0..10 {
+        let groups = assign(&centers);
+        for (center, group) in centers.iter_mut().zip(&groups) {
+            if !group.is_empty() {
+                *center = group.iter().map(|&i| positions[i]).sum::<Vec3>() / group.len() as f32;
+            }
+        }
+    }
+    let mut groups = assign(&centers);
+    groups.retain(|g| !g.is_empty());
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::{ViewSchedule, group_by_position};
+
+    fn take(schedule: ViewSchedule, positions: &[Vec3], steps: usize) -> Vec<usize> {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut scheduler = schedule.scheduler(positions);
+        (0..steps).map(|_| scheduler.next_view(&mut rng)).collect()
+    }
+
+    #[test]
+    fn sequential_and_random_visit_all_views() {
+        let positions = vec![Vec3::ZERO; 4];
+        assert_eq!(
+            take(ViewSchedule::Sequential, &positions, 6),
+            [0, 1, 2, 3, 0, 1]
+        );
+
+        let mut epoch = take(ViewSchedule::Random, &positions, 4);
+        epoch.sort_unstable();
+        assert_eq!(epoch, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn loss_weighted_prefers_high_loss() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut scheduler = ViewSchedule::LossWeighted.scheduler(&[Vec3::ZERO; 2]);
+        assert!(scheduler.wants_loss());
+        scheduler.report_loss(0, 0.01);
+        scheduler.report_loss(1, 1.0);
+
+        let picks = (0..1000).filter(|_| scheduler.next_view(&mut rng) == 1);
+        assert!(picks.count() > 800);
+    }
+
+    #[test]
+    fn spatial_alternates_between_groups() {
+        let left = Vec3::new(-10.0, 0.0, 0.0);
+        let right = Vec3::new(10.0, 0.0, 0.0);
+        let positions = [left, left, left, right, right, right];
+
+        let groups = group_by_position(&positions, 2);
+        assert_eq!(groups, [vec![0, 1, 2], vec![3, 4, 5]]);
+
+        let order = take(ViewSchedule::Spatial, &positions, 6);
+        let on_left: Vec<_> = order.iter().map(|&i| i < 3).collect();
+        assert_eq!(on_left, [true, false, true, false, true, false]);
+    }
+}
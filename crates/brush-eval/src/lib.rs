@@ -1,6 +1,8 @@
 use anyhow::Result;
+use brush_dataset::ColorSpace;
 use brush_dataset::scene::{SceneView, sample_to_tensor, view_to_sample_image};
 use brush_render::gaussian_splats::Splats;
+use brush_render::post_process::encode_srgb;
 use brush_render::{RenderAux, SplatForward};
 use brush_ssim::Ssim;
 use burn::prelude::Backend;
@@ -30,10 +32,17 @@ pub async fn eval_stats<B: Backend + SplatForward<B>>(
         device,
     );
 
-    let gt_rgb = gt_tensor.slice([0..res.y as usize, 0..res.x as usize, 0..3]);
+    let mut gt_rgb = gt_tensor.slice([0..res.y as usize, 0..res.x as usize, 0..3]);
 
     let (rendered, aux) = splats.render(&eval_view.camera, res, true);
-    let render_rgb = rendered.slice([0..res.y as usize, 0..res.x as usize, 0..3]);
+    let mut render_rgb = rendered.slice([0..res.y as usize, 0..res.x as usize, 0..3]);
+
+    // Always compare in sRGB, so metrics match other implementations whatever the
+    // color space the splats are trained in.
+    if eval_view.image.color_space == ColorSpace::Linear {
+        render_rgb = encode_srgb(render_rgb);
+        gt_rgb = encode_srgb(gt_rgb);
+    }
 
     // Simulate an 8-bit roundtrip for fair comparison.
     let render_rgb = (render_rgb * 255.0).round() / 255.0;
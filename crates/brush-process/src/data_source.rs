@@ -5,7 +5,9 @@ use std::{path::Path, str::FromStr};
 use anyhow::anyhow;
 
 use brush_dataset::WasmNotSend;
-use brush_dataset::brush_vfs::{BrushVfs, PathReader};
+use brush_dataset::brush_vfs::{BrushVfs, PathReader, VfsLimits};
+use brush_dataset::error::DatasetError;
+use brush_dataset::progress::ByteProgress;
 use tokio::io::{AsyncRead, AsyncReadExt, BufReader};
 use tokio_stream::StreamExt;
 use tokio_util::io::StreamReader;
@@ -23,14 +25,16 @@ impl FromStr for DataSource {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Match keywords and schemes case insensitively, but keep the original
+        // string as URLs and paths can be case sensitive.
         match s.to_lowercase().as_str() {
             "pick-file" => Ok(Self::PickFile),
             "pick-directory" | "dir" => Ok(Self::PickDirectory),
-            s if s.starts_with("http://") || s.starts_with("https://") => {
+            lower if lower.starts_with("http://") || lower.starts_with("https://") => {
                 Ok(Self::Url(s.to_owned()))
             }
-            s if std::fs::exists(s).is_ok() => Ok(Self::Path(s.to_owned())),
-            s => Err(format!("Invalid data source. Can't find {s}")),
+            _ if std::fs::exists(s).is_ok() => Ok(Self::Path(s.to_owned())),
+            _ => Err(format!("Invalid data source. Can't find {s}")),
         }
     }
 }
@@ -46,9 +50,12 @@ async fn read_at_most<R: AsyncRead + Unpin>(
 }
 
 impl DataSource {
+    /// Mount a single file, or a zip. `name` is only used to report errors.
     async fn vfs_from_reader(
+        name: &str,
         reader: impl AsyncRead + WasmNotSend + Unpin + 'static,
-    ) -> anyhow::Result<BrushVfs> {
+        limits: VfsLimits,
+    ) -> Result<BrushVfs, DatasetError> {
         // Small hack to peek some bytes: Read them
         // and add them at the start again.
         let mut data = BufReader::new(reader);
@@ -58,33 +65,54 @@ impl DataSource {
         if peek.as_slice().starts_with(b"ply") {
             let mut path_reader = PathReader::default();
             path_reader.add(Path::new("input.ply"), reader);
-            Ok(BrushVfs::from_paths(path_reader))
+            Ok(BrushVfs::from_paths(path_reader, limits))
+        } else if peek.starts_with(b"LASF") {
+            // LAZ files have the same signature, the LAS reader handles both.
+            let mut path_reader = PathReader::default();
+            path_reader.add(Path::new("input.las"), reader);
+            Ok(BrushVfs::from_paths(path_reader, limits))
+        } else if peek.starts_with(b"ASTM-E57") {
+            let mut path_reader = PathReader::default();
+            path_reader.add(Path::new("input.e57"), reader);
+            Ok(BrushVfs::from_paths(path_reader, limits))
+        } else if peek.get(8..10) == Some(b"{\"".as_slice()) {
+            // Safetensors start with the length of their json header.
+            let mut path_reader = PathReader::default();
+            path_reader.add(Path::new("input.safetensors"), reader);
+            Ok(BrushVfs::from_paths(path_reader, limits))
         } else if peek.starts_with(b"PK") {
-            BrushVfs::from_zip_reader(reader)
-                .await
-                .map_err(|e| anyhow::anyhow!(e))
+            BrushVfs::from_zip_reader(reader, limits).await
         } else if peek.starts_with(b"<!DOCTYPE html>") {
-            anyhow::bail!("Failed to download data.")
+            Err(DatasetError::DownloadFailed)
         } else if let Some(path_bytes) = peek.strip_prefix(b"BRUSH_PATH") {
-            let string = String::from_utf8(path_bytes.to_vec())?;
+            let string = String::from_utf8(path_bytes.to_vec()).map_err(|e| anyhow!(e))?;
             let path = Path::new(&string);
-            BrushVfs::from_directory(path).await
+            BrushVfs::from_directory(path, limits).await
         } else {
-            anyhow::bail!("only zip and ply files are supported.")
+            Err(DatasetError::UnsupportedFormat {
+                found: name.to_owned(),
+            })
         }
     }
 
-    pub async fn into_vfs(self) -> anyhow::Result<BrushVfs> {
+    /// Mount the source, holding its data to `limits`. Bytes downloaded are counted in
+    /// `progress`.
+    pub async fn into_vfs(
+        self,
+        progress: &ByteProgress,
+        limits: VfsLimits,
+    ) -> Result<BrushVfs, DatasetError> {
         match self {
             Self::PickFile => {
                 let picked = rrfd::pick_file().await.map_err(|e| anyhow!(e))?;
+                let name = picked.file_name();
                 let data = picked.read().await;
                 let reader = Cursor::new(data);
-                Self::vfs_from_reader(reader).await
+                Self::vfs_from_reader(&name, reader, limits).await
             }
             Self::PickDirectory => {
                 let picked = rrfd::pick_directory().await.map_err(|e| anyhow!(e))?;
-                BrushVfs::from_directory(&picked).await
+                BrushVfs::from_directory(&picked, limits).await
             }
             Self::Url(url) => {
                 let mut url = url.clone();
@@ -106,22 +134,49 @@ impl DataSource {
                     }
 
                     // On non-wasm... not much we can do here, what server would we ask?
+                } else if url.starts_with("./") {
+                    #[cfg(target_family = "wasm")]
+                    {
+                        // Relative to the current page, eg. for a splat exported next to the viewer.
+                        let location = web_sys::window()
+                            .expect("No window object available")
+                            .location();
+                        let origin = location.origin().expect("Coultn't figure out origin");
+                        let path = location.pathname().expect("Couldn't figure out path");
+                        let dir = &path[..path.rfind('/').map_or(0, |i| i + 1)];
+                        url = format!("{origin}{dir}{}", &url[2..]);
+                    }
                 } else {
                     // Just try to add https:// and hope for the best. Eg. if someone specifies google.com/splat.ply.
                     url = format!("https://{url}");
                 }
 
-                let response = reqwest::get(url)
-                    .await
-                    .map_err(|e| anyhow!(e))?
-                    .bytes_stream();
+                let response = reqwest::get(&url).await.map_err(|e| anyhow!(e))?;
+                progress.set_total(response.content_length());
 
-                let response =
-                    response.map(|b| b.map_err(|_e| std::io::ErrorKind::ConnectionAborted));
-                let reader = StreamReader::new(response);
-                Self::vfs_from_reader(reader).await
+                let response = response
+                    .bytes_stream()
+                    .map(|b| b.map_err(|_e| std::io::ErrorKind::ConnectionAborted));
+                let reader = progress.reader(StreamReader::new(response));
+                Self::vfs_from_reader(&url, reader, limits).await
+            }
+            Self::Path(path) => {
+                let path = PathBuf::from(path);
+                if path.is_file() && path.extension().is_some_and(|e| e == "zip") {
+                    // Local zips are mapped instead of read.
+                    BrushVfs::from_directory(&path, limits).await
+                } else if path.is_file() {
+                    // Read a ply just like a picked file, without blocking the runtime.
+                    #[cfg(not(target_family = "wasm"))]
+                    let reader = tokio::fs::File::open(&path).await?;
+                    // There's no file system on the web, so this is never reached there.
+                    #[cfg(target_family = "wasm")]
+                    let reader = Cursor::new(std::fs::read(&path)?);
+                    Self::vfs_from_reader(&path.to_string_lossy(), reader, limits).await
+                } else {
+                    BrushVfs::from_directory(&path, limits).await
+                }
             }
-            Self::Path(path) => BrushVfs::from_directory(&PathBuf::from(path)).await,
         }
     }
 }
@@ -0,0 +1,31 @@
+//! Errors of a process, and what the user can do about them.
+
+use brush_dataset::error::DatasetError;
+use brush_render::error::{SplatsFileError, Suggestion};
+use brush_train::error::TrainError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ProcessError {
+    #[error(transparent)]
+    Dataset(#[from] DatasetError),
+    #[error(transparent)]
+    Train(#[from] TrainError),
+    #[error(transparent)]
+    SplatsFile(#[from] SplatsFileError),
+    /// Any other failure, which there is nothing to suggest for.
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl ProcessError {
+    /// What the user can do to fix the error, if anything.
+    pub fn suggestion(&self) -> Option<Suggestion> {
+        match self {
+            Self::Dataset(e) => e.suggestion(),
+            Self::Train(e) => Some(e.suggestion()),
+            Self::SplatsFile(e) => Some(e.suggestion()),
+            Self::Other(_) => None,
+        }
+    }
+}
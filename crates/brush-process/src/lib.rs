@@ -3,4 +3,31 @@
 pub mod rerun_tools;
 
 pub mod data_source;
+pub mod error;
 pub mod process_loop;
+
+use std::sync::{Arc, Mutex};
+
+use brush_dataset::DatasetFormat;
+use brush_train::train::TrainBack;
+
+static DATASET_FORMATS: Mutex<Vec<Arc<dyn DatasetFormat<TrainBack>>>> = Mutex::new(vec![]);
+
+/// Add a format of dataset to train on. Added formats are tried in the order they were added,
+/// before the built in formats.
+pub fn register_dataset_format(format: impl DatasetFormat<TrainBack> + 'static) {
+    DATASET_FORMATS
+        .lock()
+        .expect("Format registry lock poisoned")
+        .push(Arc::new(format));
+}
+
+/// All formats datasets are loaded with, in the order they're tried.
+pub(crate) fn dataset_formats() -> Vec<Arc<dyn DatasetFormat<TrainBack>>> {
+    let mut formats = DATASET_FORMATS
+        .lock()
+        .expect("Format registry lock poisoned")
+        .clone();
+    formats.extend(brush_dataset::builtin_formats());
+    formats
+}
@@ -0,0 +1,35 @@
+use std::sync::{Arc, Mutex};
+
+use glam::Vec3;
+use tokio::sync::Notify;
+
+/// Where the viewer is looking while a scene is trained in chunks.
+///
+/// Shared with the training loop, so it trains the chunks near the camera first, and can
+/// refine the chunk being looked at once all chunks are done.
+#[derive(Clone, Default)]
+pub struct ChunkFocus {
+    position: Arc<Mutex<Option<Vec3>>>,
+    changed: Arc<Notify>,
+}
+
+impl ChunkFocus {
+    /// Set the camera position, in the space of the splats. `None` when nobody is looking.
+    pub fn set(&self, position: Option<Vec3>) {
+        let mut current = self.position.lock().expect("Focus lock poisoned");
+        if *current != position {
+            *current = position;
+            self.changed.notify_one();
+        }
+    }
+
+    pub fn get(&self) -> Option<Vec3> {
+        *self.position.lock().expect("Focus lock poisoned")
+    }
+
+    /// Wait for the position to change. Returns right away if it changed since this last
+    /// returned.
+    pub async fn changed(&self) {
+        self.changed.notified().await;
+    }
+}
@@ -0,0 +1,101 @@
+//! Distilling a large trained splat into a smaller one.
+//!
+//! The student is trained on renders of the teacher from the training cameras, rather than on
+//! the images, and starts from the most important splats of the teacher. This does better
+//! than only pruning the teacher, as the kept splats are optimized to cover for the rest.
+
+use std::io::{Cursor, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::Context;
+use brush_dataset::LoadDataseConfig;
+use brush_dataset::brush_vfs::BrushVfs;
+use brush_dataset::load_init_ply;
+use brush_dataset::progress::ByteProgress;
+use brush_dataset::scene::{LoadImage, Scene, SceneView};
+use brush_render::SplatForward;
+use brush_render::gaussian_splats::Splats;
+use burn::prelude::Backend;
+use image::{DynamicImage, ImageFormat};
+use tokio_stream::StreamExt;
+use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+use crate::data_source::DataSource;
+use crate::process_loop::tensor_into_image;
+
+/// Load the splats to distill from. For animated splats, this is the last frame.
+pub(crate) async fn load_teacher<B: Backend>(
+    path: &str,
+    load_config: &LoadDataseConfig,
+    device: &B::Device,
+) -> anyhow::Result<Splats<B>> {
+    let vfs = DataSource::from_str(path)
+        .map_err(|e| anyhow::anyhow!(e))?
+        .into_vfs(&ByteProgress::default(), load_config.vfs_limits())
+        .await?;
+    let mut stream = load_init_ply(Arc::new(vfs), load_config, device)
+        .await
+        .with_context(|| format!("Failed to load splats to distill from {path}"))?;
+
+    let mut teacher = None;
+    while let Some(message) = stream.next().await {
+        teacher = Some(message?.splats);
+    }
+    teacher.with_context(|| format!("No splats in {path}"))
+}
+
+/// The views of `scene`, with renders of `teacher` as their images.
+///
+/// The renders are kept in memory, as a zip of PNGs.
+pub(crate) async fn teacher_scene<B: Backend + SplatForward<B>>(
+    teacher: &Splats<B>,
+    scene: &Scene,
+    max_resolution: u32,
+) -> anyhow::Result<Scene> {
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    // PNGs are compressed already.
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+    let mut paths = vec![];
+    for (i, view) in scene.views.iter().enumerate() {
+        let (render, _) = teacher.render(&view.camera, view.image.dimensions(), true);
+        let render = tensor_into_image(render.into_data_async().await);
+        // Only keep alpha if the images had it, otherwise it's the splats' coverage.
+        let render = if view.image.has_alpha() {
+            DynamicImage::ImageRgba8(render.into_rgba8())
+        } else {
+            DynamicImage::ImageRgb8(render.into_rgb8())
+        };
+
+        let mut png = Cursor::new(vec![]);
+        render.write_to(&mut png, ImageFormat::Png)?;
+        let path = PathBuf::from(format!("teacher_{i:05}.png"));
+        zip.start_file(path.to_string_lossy(), options)?;
+        zip.write_all(png.get_ref())?;
+        paths.push(path);
+    }
+
+    let data = zip.finish()?.into_inner();
+    // Held to the limits of the dataset the views are of.
+    let limits = scene
+        .views
+        .first()
+        .map(|view| view.image.vfs.limits().clone())
+        .unwrap_or_default();
+    let vfs = Arc::new(BrushVfs::from_zip_reader(Cursor::new(data), limits).await?);
+
+    let mut views = vec![];
+    for (view, path) in scene.views.iter().zip(paths) {
+        let image = LoadImage::new(vfs.clone(), path, None, max_resolution)
+            .await?
+            // Exported splats are in sRGB, train on them like on the original images.
+            .with_color_space(view.image.color_space);
+        views.push(SceneView {
+            image,
+            camera: view.camera.clone(),
+        });
+    }
+    Ok(Scene::new(views))
+}
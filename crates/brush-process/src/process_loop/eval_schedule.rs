@@ -0,0 +1,131 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Which steps of training are evaluated.
+///
+/// Parsed from a comma separated list of:
+/// - `N` to evaluate every N steps.
+/// - `N@S` to evaluate every N steps from step S on, instead of the interval before it. An
+///   interval of 0 stops evaluating.
+/// - `refine` to also evaluate before each refine, while splats are still growing.
+///
+/// Eg. `100,500@2000,2000@10000` evaluates often early on, when the splats change quickly,
+/// and rarely later, when evals mostly cost training time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvalSchedule {
+    // Step each interval starts at, and the interval, sorted by start.
+    intervals: Vec<(u32, u32)>,
+    at_refine: bool,
+}
+
+impl EvalSchedule {
+    /// Evaluate every `steps` steps.
+    pub fn every(steps: u32) -> Self {
+        Self {
+            intervals: vec![(0, steps)],
+            at_refine: false,
+        }
+    }
+
+    /// Whether to evaluate after step `iter`. Refines happen every `refine_every` steps,
+    /// with splats growing until `growth_stop_iter`.
+    pub fn should_eval(&self, iter: u32, refine_every: u32, growth_stop_iter: u32) -> bool {
+        let every = self
+            .intervals
+            .iter()
+            .rev()
+            .find(|(start, _)| *start <= iter)
+            .map_or(0, |(_, every)| *every);
+        if every > 0 && iter % every == 0 {
+            return true;
+        }
+        self.at_refine && refine_every > 0 && iter % refine_every == 0 && iter <= growth_stop_iter
+    }
+}
+
+impl FromStr for EvalSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut schedule = Self {
+            intervals: vec![],
+            at_refine: false,
+        };
+        let parse = |num: &str| {
+            num.trim()
+                .parse::<u32>()
+                .map_err(|_| format!("Invalid step count '{num}' in eval schedule '{s}'"))
+        };
+
+        for part in s.split(',').map(str::trim) {
+            if part.eq_ignore_ascii_case("refine") {
+                schedule.at_refine = true;
+                continue;
+            }
+            let (every, start) = match part.split_once('@') {
+                Some((every, start)) => (parse(every)?, parse(start)?),
+                None => (parse(part)?, 0),
+            };
+            if schedule.intervals.iter().any(|(s, _)| *s == start) {
+                return Err(format!(
+                    "Eval schedule '{s}' has more than one interval starting at step {start}"
+                ));
+            }
+            schedule.intervals.push((start, every));
+        }
+        schedule.intervals.sort_unstable();
+        Ok(schedule)
+    }
+}
+
+/// Asks the training loop to evaluate after the step it's on, eg. from an eval button.
+///
+/// Shared with the training loop, which checks it every step.
+#[derive(Clone, Default)]
+pub struct EvalRequest {
+    requested: Arc<AtomicBool>,
+}
+
+impl EvalRequest {
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether an eval was requested since the last call.
+    pub(crate) fn take(&self) -> bool {
+        self.requested.swap(false, Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EvalSchedule;
+
+    #[test]
+    fn intervals_change_with_progress() {
+        let schedule: EvalSchedule = "100, 500@2000,0@10000".parse().expect("Valid schedule");
+        let evals: Vec<_> = (1..=12000)
+            .filter(|&iter| schedule.should_eval(iter, 150, 12500))
+            .collect();
+        assert_eq!(&evals[..3], &[100, 200, 300]);
+        assert!(evals.contains(&1900) && !evals.contains(&2100));
+        assert_eq!(evals.last(), Some(&9500));
+    }
+
+    #[test]
+    fn refine_steps_while_growing() {
+        let schedule: EvalSchedule = "refine,0".parse().expect("Valid schedule");
+        assert!(schedule.should_eval(300, 150, 450));
+        assert!(schedule.should_eval(450, 150, 450));
+        assert!(!schedule.should_eval(600, 150, 450));
+        assert!(!schedule.should_eval(301, 150, 450));
+    }
+
+    #[test]
+    fn invalid_schedules() {
+        assert!("".parse::<EvalSchedule>().is_err());
+        assert!("100,fast".parse::<EvalSchedule>().is_err());
+        assert!("100@50,200@50".parse::<EvalSchedule>().is_err());
+    }
+}
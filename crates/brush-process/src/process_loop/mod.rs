@@ -1,13 +1,22 @@
+mod chunk_focus;
+mod distill;
+mod eval_schedule;
 mod process;
 mod process_args;
+#[cfg(not(target_family = "wasm"))]
+mod report;
+mod splat_snapshot;
 
 mod train_stream;
 mod view_stream;
 
 use burn::tensor::{DType, TensorData};
+pub use chunk_focus::ChunkFocus;
+pub use eval_schedule::{EvalRequest, EvalSchedule};
 use image::{DynamicImage, Rgb32FImage, Rgba32FImage};
 pub use process::*;
 pub use process_args::*;
+pub use splat_snapshot::SplatSnapshot;
 
 pub trait TensorDataToImage {
     fn into_image(self) -> DynamicImage;
@@ -1,12 +1,30 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 use async_fn_stream::try_fn_stream;
+use burn::tensor::Tensor;
 use burn::tensor::backend::AutodiffBackend;
-use web_time::Duration;
+use web_time::{Duration, Instant};
 
-use crate::{data_source::DataSource, process_loop::view_stream::view_stream};
-use brush_dataset::Dataset;
+use crate::{
+    data_source::DataSource,
+    error::ProcessError,
+    process_loop::view_stream::{
+        view_checkpoint_stream, view_chunks_stream, view_safetensors_stream, view_stream,
+    },
+};
+use brush_dataset::{
+    Dataset,
+    brush_vfs::BrushVfs,
+    chunks::{CHUNK_INDEX_FILE, ChunkIndex},
+    ckpt_import, inria,
+    progress::ByteProgress,
+    scene,
+    scene_loader::CacheStats,
+};
+use brush_render::camera::Camera;
 use brush_render::gaussian_splats::Splats;
+use brush_train::appearance::Appearance;
+use brush_train::env_light::EnvLight;
 use brush_train::train::{RefineStats, TrainBack, TrainStepStats};
 use burn_wgpu::WgpuDevice;
 use glam::Vec3;
@@ -15,7 +33,7 @@ use tokio_stream::Stream;
 #[allow(unused)]
 use brush_dataset::splat_export;
 
-use super::{ProcessArgs, train_stream::train_stream};
+use super::{ChunkFocus, EvalRequest, ProcessArgs, SplatSnapshot, train_stream::train_stream};
 
 pub enum ProcessMessage {
     NewSource,
@@ -32,22 +50,75 @@ pub enum ProcessMessage {
         frame: u32,
         total_frames: u32,
     },
+    /// A ply file was cut short or corrupt part way through, only the splats before that
+    /// are shown.
+    SplatsTruncated {
+        /// Number of splats that were read.
+        loaded: u32,
+        /// Number of splats the file should have had after those.
+        skipped: u32,
+    },
+    /// A merged scene saved as chunks, to stream in the chunks near the camera from `vfs`.
+    ViewChunks {
+        vfs: Arc<BrushVfs>,
+        /// Folder of the index, the chunk files are relative to.
+        dir: PathBuf,
+        index: ChunkIndex,
+    },
     /// Loaded a bunch of viewpoints to train on.
     Dataset {
         dataset: Dataset,
     },
+    /// More of the source was read, while downloading it or parsing splats.
+    LoadProgress {
+        loaded_bytes: u64,
+        /// Size of the source, if known.
+        total_bytes: Option<u64>,
+        /// Estimated time left until it's fully read.
+        eta: Option<Duration>,
+    },
     /// Splat, or dataset and initial splat, are done loading.
     #[allow(unused)]
     DoneLoading {
         training: bool,
     },
+    /// The scene is trained in chunks, the viewer can tell the training where it's looking.
+    TrainChunks {
+        count: u32,
+        focus: ChunkFocus,
+    },
+    /// Training evaluates on held out views, the viewer can ask for an eval at any time.
+    EvalAvailable {
+        request: EvalRequest,
+    },
     /// Some number of training steps are done.
     #[allow(unused)]
     TrainStep {
-        splats: Box<Splats<<TrainBack as AutodiffBackend>::InnerBackend>>,
+        /// Set when new splats were swapped to the front of the snapshot, only every so often
+        /// to limit how often the viewer redraws them.
+        splats: Option<SplatSnapshot>,
         stats: Box<TrainStepStats<TrainBack>>,
+        /// Camera of the view trained on in this step.
+        camera: Camera,
+        /// Index of the training view trained on in this step.
+        view_index: usize,
+        /// The image of that view as it was trained on, already on the GPU.
+        gt_image: Tensor<<TrainBack as AutodiffBackend>::InnerBackend, 3>,
         iter: u32,
         total_elapsed: Duration,
+        /// How the cache of decoded training images is doing.
+        image_cache: CacheStats,
+    },
+    /// Training learns the look of each capture session, the viewer can show any of them.
+    Appearances {
+        /// Name of each session.
+        names: Vec<String>,
+        appearance: Appearance<<TrainBack as AutodiffBackend>::InnerBackend>,
+    },
+    /// Training decomposes the lighting, so the splats only hold the albedo. The viewer
+    /// lights them with this to show them as they're trained.
+    EnvLight {
+        light: EnvLight<<TrainBack as AutodiffBackend>::InnerBackend>,
     },
     /// Some number of training steps are done.
     #[allow(unused)]
@@ -56,6 +127,10 @@ pub enum ProcessMessage {
         cur_splat_count: u32,
         iter: u32,
     },
+    /// Training started, and writes all its files to this folder.
+    RunDir {
+        path: PathBuf,
+    },
     /// Eval was run successfully with these results.
     #[allow(unused)]
     EvalResult {
@@ -65,33 +140,78 @@ pub enum ProcessMessage {
     },
 }
 
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
 pub fn process_stream(
     source: DataSource,
     process_args: ProcessArgs,
     device: WgpuDevice,
-) -> impl Stream<Item = Result<ProcessMessage, anyhow::Error>> + 'static {
+) -> impl Stream<Item = Result<ProcessMessage, ProcessError>> + 'static {
     try_fn_stream(|emitter| async move {
         log::info!("Starting process with source {source:?}");
 
         emitter.emit(ProcessMessage::NewSource).await;
 
-        let vfs = source.into_vfs().await;
-
-        let vfs = match vfs {
-            Ok(vfs) => Arc::new(vfs),
-            Err(e) => {
-                anyhow::bail!(e);
+        // Sources like zips are downloaded in full before they can be used, report on how
+        // that's going.
+        let progress = ByteProgress::default();
+        let mut bytes_read = progress.subscribe();
+        let limits = process_args.load_config.vfs_limits();
+        let mut mount = std::pin::pin!(source.into_vfs(&progress, limits));
+        let mut last_report = Instant::now();
+        let vfs = loop {
+            tokio::select! {
+                vfs = &mut mount => break vfs,
+                Ok(()) = bytes_read.changed() => {
+                    // Every chunk read notifies, don't flood the UI with those.
+                    if last_report.elapsed() < PROGRESS_INTERVAL {
+                        continue;
+                    }
+                    last_report = Instant::now();
+                    emitter
+                        .emit(ProcessMessage::LoadProgress {
+                            loaded_bytes: progress.loaded_bytes(),
+                            total_bytes: progress.total_bytes(),
+                            eta: progress.eta(),
+                        })
+                        .await;
+                }
             }
         };
 
+        let vfs = Arc::new(vfs?);
+
         let paths: Vec<_> = vfs.file_names().collect();
         log::info!("Mounted VFS with {} files", paths.len());
 
-        if paths
+        // An INRIA 3DGS output without the images it was trained on can only be viewed.
+        let inria_checkpoint =
+            inria::find_checkpoint(&vfs).filter(|_| !paths.iter().any(|p| scene::is_image_path(p)));
+
+        let safetensors_file = match paths.as_slice() {
+            [path] if path.extension().is_some_and(|e| e == "safetensors") => Some(path.clone()),
+            _ => None,
+        };
+
+        let chunk_index = paths
+            .iter()
+            .find(|p| p.file_name().is_some_and(|n| n == CHUNK_INDEX_FILE))
+            .cloned();
+
+        if let Some(index_path) = chunk_index {
+            view_chunks_stream(vfs, index_path, emitter).await?;
+        } else if ckpt_import::is_checkpoint(&vfs) {
+            view_checkpoint_stream(vfs, device, emitter).await?;
+        } else if let Some(path) = safetensors_file {
+            view_safetensors_stream(vfs, path, device, emitter).await?;
+        } else if let Some(checkpoint) = inria_checkpoint {
+            log::info!("Viewing INRIA 3DGS output {checkpoint:?}");
+            view_stream(vfs, vec![checkpoint], device, emitter).await?;
+        } else if paths
             .iter()
             .all(|p| p.extension().is_some_and(|p| p == "ply"))
         {
-            view_stream(vfs, device, emitter).await?;
+            view_stream(vfs, paths, device, emitter).await?;
         } else {
             train_stream(vfs, process_args, device, emitter).await?;
         };
@@ -2,6 +2,10 @@ use brush_dataset::{LoadDataseConfig, ModelConfig};
 use brush_train::config::TrainConfig;
 use burn::config::Config;
 use clap::Args;
+use std::path::{Path, PathBuf};
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use super::EvalSchedule;
 
 #[derive(Config, Args)]
 pub struct ProcessConfig {
@@ -13,7 +17,14 @@ pub struct ProcessConfig {
     #[arg(long, help_heading = "Process options", default_value = "1000")]
     #[config(default = 1000)]
     pub eval_every: u32,
-    /// Save the rendered eval images to disk. Uses export-path for the file location.
+    /// When to eval, instead of every eval-every steps.
+    ///
+    /// A comma separated list of `N` to eval every N steps, `N@S` to eval every N steps from
+    /// step S on, and `refine` to also eval before each refine while splats are growing. Eg.
+    /// `100,500@2000,2000@10000,refine` evals often early on and rarely later.
+    #[arg(long, help_heading = "Process options")]
+    pub eval_schedule: Option<String>,
+    /// Save the rendered eval images to disk, in the run folder.
     #[arg(long, help_heading = "Process options", default_value = "false")]
     #[config(default = false)]
     pub eval_save_to_disk: bool,
@@ -23,11 +34,17 @@ pub struct ProcessConfig {
     #[config(default = 5000)]
     pub export_every: u32,
 
-    /// Location to put exported files. By default uses the cwd.
+    /// Folder to put runs in. By default uses the cwd.
     ///
-    /// This path can be set to be relative to the CWD.
+    /// Each run gets its own folder in here, named by run-name, with all its exports, eval
+    /// images and logs. This path can be set to be relative to the CWD.
+    #[arg(long, help_heading = "Process options", alias = "export-path")]
+    pub output_dir: Option<String>,
+
+    /// Name of the folder of this run in the output dir. By default uses the time the run
+    /// started, eg. `run_2025-01-31_14-05-09`.
     #[arg(long, help_heading = "Process options")]
-    pub export_path: Option<String>,
+    pub run_name: Option<String>,
 
     /// Filename of exported ply file
     #[arg(
@@ -38,12 +55,105 @@ pub struct ProcessConfig {
     #[config(default = "String::from(\"./export_{iter}.ply\")")]
     pub export_name: String,
 
+    /// Rotate exported ply files so the up direction of the dataset is -Y, as most viewers
+    /// expect. Otherwise exports keep the orientation of the dataset, and only name their
+    /// vertical axis if up is close to one of the axes.
+    #[arg(long, help_heading = "Process options", default_value = "false")]
+    #[config(default = false)]
+    pub export_upright: bool,
+
+    /// Distill this trained splat file into a model of at most max-splats splats.
+    ///
+    /// The model is trained on renders of these splats from the training cameras rather than on
+    /// the images, starting from its most important splats. This gives smaller models, eg. for
+    /// the web or mobile, that look better than pruning alone.
+    #[arg(long, help_heading = "Process options")]
+    pub distill_from: Option<String>,
+
+    /// Max number of times per second the training splats are sent to the viewer.
+    ///
+    /// Each update snapshots the splats, so at high step rates this keeps the
+    /// viewer from slowing down training.
+    #[arg(long, help_heading = "Process options", default_value = "10")]
+    #[config(default = 10.0)]
+    pub max_splat_updates_per_sec: f32,
+
+    /// Split the scene into about this many chunks, and train them one after the other.
+    ///
+    /// For scenes too big to train at once, eg. a whole city. Each chunk is trained on the
+    /// views with their camera in or near it, for the full number of steps. The chunks are
+    /// then merged, blending them where they overlap.
+    #[arg(long, help_heading = "Process options", default_value = "1")]
+    #[config(default = 1)]
+    pub train_chunks: u32,
+
+    /// How much neighboring chunks overlap, as a fraction of their size.
+    #[arg(long, help_heading = "Process options", default_value = "0.2")]
+    #[config(default = 0.2)]
+    pub chunk_overlap: f32,
+
+    /// Once all chunks are trained, refine the chunk the viewer is looking at.
+    ///
+    /// Refines for a short round at the end of the schedule each time the viewer looks at
+    /// another chunk, for as long as the scene is viewed.
+    #[arg(long, help_heading = "Process options", default_value = "false")]
+    #[config(default = false)]
+    pub refine_viewed_chunk: bool,
+
     /// Iteration to resume from
     #[config(default = 0)]
     #[arg(long, help_heading = "Process options", default_value = "0")]
     pub start_iter: u32,
 }
 
+impl ProcessConfig {
+    /// The eval schedule, or evaluating every eval-every steps without one.
+    pub fn parse_eval_schedule(&self) -> anyhow::Result<EvalSchedule> {
+        match &self.eval_schedule {
+            Some(schedule) => schedule.parse().map_err(|e: String| anyhow::anyhow!(e)),
+            None => Ok(EvalSchedule::every(self.eval_every)),
+        }
+    }
+
+    /// Folder all files of this run are written to.
+    ///
+    /// Without a run name this is based on the current time, so only resolve it once per run.
+    pub fn run_dir(&self) -> PathBuf {
+        let name = self
+            .run_name
+            .clone()
+            .unwrap_or_else(|| run_name_at(SystemTime::now()));
+        Path::new(self.output_dir.as_deref().unwrap_or(".")).join(name)
+    }
+}
+
+/// Name of a run started at `time`, with the date and time in UTC.
+fn run_name_at(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // Days to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "run_{year:04}-{month:02}-{day:02}_{:02}-{:02}-{:02}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
 #[derive(Config, Args)]
 pub struct RerunConfig {
     /// Whether to enable rerun.io logging for this run.
@@ -88,3 +198,17 @@ impl Default for ProcessArgs {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use web_time::{Duration, UNIX_EPOCH};
+
+    use super::run_name_at;
+
+    #[test]
+    fn run_names_are_utc_times() {
+        assert_eq!(run_name_at(UNIX_EPOCH), "run_1970-01-01_00-00-00");
+        let time = UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+        assert_eq!(run_name_at(time), "run_2024-02-29_12-34-56");
+    }
+}
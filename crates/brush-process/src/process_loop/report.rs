@@ -0,0 +1,312 @@
+//! A single-file HTML report of a training run, to share the results with people who don't
+//! run Brush themselves.
+
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageFormat};
+use web_time::Duration;
+
+/// Name of the report in the run folder.
+pub(crate) const REPORT_FILE: &str = "report.html";
+
+/// Number of eval views shown next to their render.
+pub(crate) const MAX_COMPARISONS: usize = 4;
+
+// Longest side of the images in the report, to keep its size down.
+const COMPARISON_SIZE: u32 = 512;
+
+struct Comparison {
+    name: String,
+    ground_truth: String,
+    rendered: String,
+    psnr: f32,
+}
+
+/// What's recorded of a training run for the report.
+#[derive(Default)]
+pub(crate) struct RunReport {
+    // Each time training starts over at an earlier iteration, eg. for the next chunk, the
+    // losses start a new line in the plot.
+    losses: Vec<Vec<(u32, f32)>>,
+    psnr: Vec<(u32, f32)>,
+    ssim: Vec<(u32, f32)>,
+    comparisons: Vec<Comparison>,
+    peak_memory: u64,
+    train_time: Duration,
+    splat_count: u32,
+}
+
+fn base64(data: &[u8]) -> String {
+    const CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let byte = |i: usize| chunk.get(i).copied().unwrap_or(0) as u32;
+        let bits = (byte(0) << 16) | (byte(1) << 8) | byte(2);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(CHARS[((bits >> (18 - 6 * i)) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn png_data_uri(image: &DynamicImage) -> anyhow::Result<String> {
+    let image = image
+        .thumbnail(COMPARISON_SIZE, COMPARISON_SIZE)
+        .into_rgb8();
+    let mut png = Cursor::new(vec![]);
+    image.write_to(&mut png, ImageFormat::Png)?;
+    Ok(format!("data:image/png;base64,{}", base64(png.get_ref())))
+}
+
+/// An SVG plot of `series` of (iteration, value) points, one line each.
+fn line_plot(title: &str, series: &[Vec<(u32, f32)>]) -> String {
+    const WIDTH: f32 = 640.0;
+    const HEIGHT: f32 = 260.0;
+    const MARGIN: f32 = 48.0;
+
+    let points: Vec<(u32, f32)> = series.iter().flatten().copied().collect();
+    if points.is_empty() {
+        return format!("<p>No {} recorded.</p>", escape(title));
+    }
+    let (min_x, max_x) = points
+        .iter()
+        .fold((u32::MAX, 0), |(lo, hi), &(x, _)| (lo.min(x), hi.max(x)));
+    let (min_y, max_y) = points
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &(_, y)| {
+            (lo.min(y), hi.max(y))
+        });
+    let range_x = (max_x - min_x).max(1) as f32;
+    let range_y = (max_y - min_y).max(1e-6);
+    let to_svg = |(x, y): (u32, f32)| {
+        (
+            MARGIN + (x - min_x) as f32 / range_x * (WIDTH - 2.0 * MARGIN),
+            HEIGHT - MARGIN - (y - min_y) / range_y * (HEIGHT - 2.0 * MARGIN),
+        )
+    };
+
+    let mut svg = format!(
+        r#"<svg viewBox="0 0 {WIDTH} {HEIGHT}" width="{WIDTH}" height="{HEIGHT}">
+<text x="{MARGIN}" y="20" class="title">{title}</text>
+<line x1="{MARGIN}" y1="{bottom}" x2="{right}" y2="{bottom}" class="axis"/>
+<line x1="{MARGIN}" y1="{MARGIN}" x2="{MARGIN}" y2="{bottom}" class="axis"/>
+<text x="{MARGIN}" y="{below}">{min_x}</text>
+<text x="{right}" y="{below}" text-anchor="end">{max_x}</text>
+<text x="{left}" y="{bottom}" text-anchor="end">{min_y:.4}</text>
+<text x="{left}" y="{MARGIN}" text-anchor="end">{max_y:.4}</text>
+"#,
+        title = escape(title),
+        bottom = HEIGHT - MARGIN,
+        right = WIDTH - MARGIN,
+        below = HEIGHT - MARGIN + 18.0,
+        left = MARGIN - 4.0,
+    );
+    for line in series {
+        let coords: Vec<String> = line
+            .iter()
+            .map(|&p| {
+                let (x, y) = to_svg(p);
+                format!("{x:.1},{y:.1}")
+            })
+            .collect();
+        svg += &format!(r#"<polyline points="{}" class="line"/>"#, coords.join(" "));
+        svg += "\n";
+    }
+    svg += "</svg>";
+    svg
+}
+
+impl RunReport {
+    pub(crate) fn add_loss(&mut self, iter: u32, loss: f32) {
+        let restarted = self
+            .losses
+            .last()
+            .and_then(|line| line.last())
+            .is_none_or(|&(last, _)| iter <= last);
+        if restarted {
+            self.losses.push(vec![]);
+        }
+        if let Some(line) = self.losses.last_mut() {
+            line.push((iter, loss));
+        }
+    }
+
+    pub(crate) fn add_eval(&mut self, iter: u32, psnr: f32, ssim: f32) {
+        self.psnr.push((iter, psnr));
+        self.ssim.push((iter, ssim));
+    }
+
+    /// Keep the `index`th eval view to show next to its render. Views of earlier evals are
+    /// replaced, so the report shows the latest one.
+    pub(crate) fn add_comparison(
+        &mut self,
+        index: usize,
+        name: &str,
+        ground_truth: &DynamicImage,
+        rendered: &DynamicImage,
+        psnr: f32,
+    ) -> anyhow::Result<()> {
+        if index >= MAX_COMPARISONS {
+            return Ok(());
+        }
+        self.comparisons.truncate(index);
+        self.comparisons.push(Comparison {
+            name: name.to_owned(),
+            ground_truth: png_data_uri(ground_truth)?,
+            rendered: png_data_uri(rendered)?,
+            psnr,
+        });
+        Ok(())
+    }
+
+    pub(crate) fn add_memory(&mut self, bytes_reserved: u64) {
+        self.peak_memory = self.peak_memory.max(bytes_reserved);
+    }
+
+    pub(crate) fn add_train_time(&mut self, time: Duration) {
+        self.train_time += time;
+    }
+
+    pub(crate) fn set_splat_count(&mut self, count: u32) {
+        self.splat_count = count;
+    }
+
+    /// The report as a standalone HTML page, with the `config` of the run as JSON and a list
+    /// of facts about the `hardware` it ran on.
+    pub(crate) fn to_html(&self, config: &str, hardware: &[(&str, String)]) -> String {
+        let mut metrics = vec![
+            ("Splats", self.splat_count.to_string()),
+            (
+                "Training time",
+                format!("{:.1} minutes", self.train_time.as_secs_f32() / 60.0),
+            ),
+        ];
+        if let (Some((iter, psnr)), Some((_, ssim))) = (self.psnr.last(), self.ssim.last()) {
+            metrics.push(("Eval PSNR", format!("{psnr:.2} (iteration {iter})")));
+            metrics.push(("Eval SSIM", format!("{ssim:.4} (iteration {iter})")));
+        }
+        let mut hardware = hardware.to_vec();
+        hardware.push((
+            "Peak GPU memory",
+            format!("{:.2} GB", self.peak_memory as f64 / 1e9),
+        ));
+
+        let table = |rows: &[(&str, String)]| {
+            let rows: String = rows
+                .iter()
+                .map(|(key, value)| {
+                    format!(
+                        "<tr><th>{}</th><td>{}</td></tr>\n",
+                        escape(key),
+                        escape(value)
+                    )
+                })
+                .collect();
+            format!("<table>\n{rows}</table>")
+        };
+
+        let comparisons: String = self
+            .comparisons
+            .iter()
+            .map(|c| {
+                format!(
+                    r#"<figure><img src="{}"><img src="{}"><figcaption>{} &mdash; {:.2} PSNR (ground truth, render)</figcaption></figure>
+"#,
+                    c.ground_truth,
+                    c.rendered,
+                    escape(&c.name),
+                    c.psnr
+                )
+            })
+            .collect();
+        let comparisons = if comparisons.is_empty() {
+            "<p>No eval views.</p>".to_owned()
+        } else {
+            comparisons
+        };
+
+        format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Brush training report</title>
+<style>
+body {{ font-family: sans-serif; max-width: 1100px; margin: 2em auto; color: #222; }}
+table {{ border-collapse: collapse; }}
+th, td {{ text-align: left; padding: 2px 12px 2px 0; }}
+svg text {{ font-size: 12px; fill: #444; }}
+svg .title {{ font-size: 14px; font-weight: bold; }}
+svg .axis {{ stroke: #888; }}
+svg .line {{ fill: none; stroke: #2a7ab0; stroke-width: 1.5; }}
+figure img {{ max-width: 49%; margin-right: 1%; }}
+pre {{ background: #f4f4f4; padding: 1em; overflow-x: auto; }}
+</style>
+</head>
+<body>
+<h1>Brush training report</h1>
+<h2>Results</h2>
+{metrics}
+<h2>Training</h2>
+{loss}
+{psnr}
+<h2>Eval views</h2>
+{comparisons}
+<h2>Hardware</h2>
+{hardware}
+<h2>Config</h2>
+<pre>{config}</pre>
+</body>
+</html>
+"#,
+            metrics = table(&metrics),
+            loss = line_plot("Loss", &self.losses),
+            psnr = line_plot("Eval PSNR", std::slice::from_ref(&self.psnr)),
+            hardware = table(&hardware),
+            config = escape(config),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RunReport, base64, line_plot};
+
+    #[test]
+    fn base64_pads() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn losses_restart_a_line() {
+        let mut report = RunReport::default();
+        for iter in [50, 100, 150, 50, 100] {
+            report.add_loss(iter, 0.1);
+        }
+        assert_eq!(report.losses.len(), 2);
+        let svg = line_plot("Loss", &report.losses);
+        assert_eq!(svg.matches("<polyline").count(), 2);
+    }
+
+    #[test]
+    fn report_escapes_config() {
+        let html = RunReport::default().to_html("<script>", &[]);
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(!html.contains("<script>"));
+    }
+}
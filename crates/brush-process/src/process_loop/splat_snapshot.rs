@@ -0,0 +1,52 @@
+use std::sync::{Arc, Mutex};
+
+use brush_render::gaussian_splats::Splats;
+use brush_train::train::TrainBack;
+use burn::tensor::backend::AutodiffBackend;
+
+type InnerSplats = Splats<<TrainBack as AutodiffBackend>::InnerBackend>;
+
+#[derive(Default)]
+struct Buffers {
+    front: Option<InnerSplats>,
+    back: Option<InnerSplats>,
+}
+
+/// The latest splats from training, double buffered on the training device.
+///
+/// Training copies its splats into the back buffer and swaps it to the front, the viewer only
+/// reads the front buffer. The copy stays on the GPU, and the viewer never holds on to the
+/// tensors training is updating. The previous front stays alive as the back buffer until the
+/// next swap, so a frame still drawing it isn't pulled out from under.
+#[derive(Clone, Default)]
+pub struct SplatSnapshot {
+    buffers: Arc<Mutex<Buffers>>,
+}
+
+impl SplatSnapshot {
+    /// Copy `splats` into the back buffer, and swap it to the front.
+    pub fn publish(&self, splats: &InnerSplats) {
+        // Adding zero makes new tensors on the same device, without a round trip to the host.
+        let copy = Splats::from_tensor_data(
+            splats.means.val().add_scalar(0.0),
+            splats.rotation.val().add_scalar(0.0),
+            splats.log_scales.val().add_scalar(0.0),
+            splats.sh_coeffs.val().add_scalar(0.0),
+            splats.raw_opacity.val().add_scalar(0.0),
+        );
+
+        let mut guard = self.buffers.lock().expect("Snapshot lock poisoned");
+        let buffers = &mut *guard;
+        buffers.back = Some(copy);
+        std::mem::swap(&mut buffers.front, &mut buffers.back);
+    }
+
+    /// The front buffer, if anything was published yet.
+    pub fn front(&self) -> Option<InnerSplats> {
+        self.buffers
+            .lock()
+            .expect("Snapshot lock poisoned")
+            .front
+            .clone()
+    }
+}
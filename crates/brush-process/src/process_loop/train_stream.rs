@@ -1,41 +1,73 @@
+use std::ops::Range;
 use std::path::Path;
+use std::str::FromStr;
 use std::sync::Arc;
 
 use anyhow::Context;
 /// A default training loop for Brush.
 use async_fn_stream::TryStreamEmitter;
 
+use brush_dataset::ColorSpace;
 use brush_dataset::brush_vfs::BrushVfs;
+use brush_dataset::chunks::{Chunk, partition};
+use brush_dataset::load_init_ply;
+use brush_dataset::progress::ByteProgress;
+use brush_dataset::scene::Scene;
 use brush_dataset::scene_loader::SceneLoader;
 use brush_eval::eval_stats;
 use brush_render::gaussian_splats::{RandomSplatsConfig, Splats};
+use brush_train::convergence::ConvergenceMonitor;
+use brush_train::env_light::EnvLight;
 use brush_train::train::SplatTrainer;
 use brush_train::train::TrainBack;
 
 use burn::module::AutodiffModule;
 use burn::prelude::Backend;
+use burn::tensor::backend::AutodiffBackend;
+use burn::tensor::{Int, Tensor, TensorData};
 use burn_cubecl::cubecl::Runtime;
 use burn_wgpu::{WgpuDevice, WgpuRuntime};
+use glam::Vec3;
 use rand::SeedableRng;
 use tokio_stream::StreamExt;
 use web_time::{Duration, Instant};
 
+use crate::data_source::DataSource;
+use crate::error::ProcessError;
 use crate::rerun_tools::VisualizeTools;
 
-use super::{ProcessArgs, ProcessMessage};
+#[cfg(not(target_family = "wasm"))]
+use super::report::{self, RunReport};
+use super::{
+    ChunkFocus, EvalRequest, EvalSchedule, ProcessArgs, ProcessMessage, SplatSnapshot, distill,
+};
+
+/// How often the loss is read back to check for convergence.
+const LOSS_SAMPLE_EVERY: u32 = 50;
 
 pub(crate) async fn train_stream(
     vfs: Arc<BrushVfs>,
-    process_args: ProcessArgs,
+    mut process_args: ProcessArgs,
     device: WgpuDevice,
-    emitter: TryStreamEmitter<ProcessMessage, anyhow::Error>,
-) -> anyhow::Result<()> {
+    emitter: TryStreamEmitter<ProcessMessage, ProcessError>,
+) -> Result<(), ProcessError> {
     log::info!("Start of training stream");
 
+    let max_buffer = WgpuRuntime::client(&device)
+        .properties()
+        .memory_properties()
+        .max_page_size;
+    process_args.train_config.max_splats = brush_train::memory::clamp_to_buffer_limit(
+        process_args.train_config.max_splats,
+        process_args.model_config.sh_degree,
+        max_buffer,
+    );
+
     log::info!("Create rerun {}", process_args.rerun_config.rerun_enabled);
     let visualize = VisualizeTools::new(process_args.rerun_config.rerun_enabled);
 
     let process_config = &process_args.process_config;
+    let eval_schedule = process_config.parse_eval_schedule()?;
     emitter
         .emit(ProcessMessage::StartLoading { training: true })
         .await;
@@ -45,9 +77,47 @@ pub(crate) async fn train_stream(
     let mut rng = rand::rngs::StdRng::from_seed([process_config.seed as u8; 32]);
 
     log::info!("Loading dataset");
-    let (mut splat_stream, dataset) =
-        brush_dataset::load_dataset(vfs.clone(), &process_args.load_config, &device).await?;
+    let (mut splat_stream, mut dataset) = brush_dataset::load_dataset(
+        vfs.clone(),
+        &crate::dataset_formats(),
+        &process_args.load_config,
+        &device,
+    )
+    .await?;
     log::info!("Dataset loaded");
+
+    if let Some(init_ply) = &process_args.load_config.init_ply {
+        log::info!("Loading initial splats from {init_ply}");
+        let init_vfs = DataSource::from_str(init_ply)
+            .map_err(|e| anyhow::anyhow!(e))?
+            .into_vfs(
+                &ByteProgress::default(),
+                process_args.load_config.vfs_limits(),
+            )
+            .await?;
+        splat_stream = load_init_ply(Arc::new(init_vfs), &process_args.load_config, &device)
+            .await
+            .inspect_err(|_| log::error!("Failed to load initial splats from {init_ply}"))?;
+    }
+
+    // When distilling, train on renders of the teacher, starting from its most important
+    // splats unless there are initial splats already.
+    let mut teacher_init = None;
+    if let Some(teacher_path) = &process_args.process_config.distill_from {
+        log::info!("Distilling from {teacher_path}");
+        let teacher =
+            distill::load_teacher::<TrainBack>(teacher_path, &process_args.load_config, &device)
+                .await?;
+        dataset.train = distill::teacher_scene(
+            &teacher.valid(),
+            &dataset.train,
+            process_args.load_config.max_resolution,
+        )
+        .await?;
+        if process_args.load_config.init_ply.is_none() {
+            teacher_init = Some(teacher.keep_most_important(process_args.train_config.max_splats));
+        }
+    }
     emitter
         .emit(ProcessMessage::Dataset {
             dataset: dataset.clone(),
@@ -56,7 +126,7 @@ pub(crate) async fn train_stream(
 
     visualize.log_scene(&dataset.train, process_args.rerun_config.rerun_max_img_size)?;
 
-    let estimated_up = dataset.estimate_up();
+    let mut up_axis = dataset.estimate_up();
 
     log::info!("Loading initial splats if any.");
     // Read initial splats if any.
@@ -64,10 +134,13 @@ pub(crate) async fn train_stream(
 
     while let Some(message) = splat_stream.next().await {
         let message = message?;
+        // If the metadata has an up axis prefer that, otherwise estimate
+        // the up direction.
+        if let Some(up) = message.meta.up_axis {
+            up_axis = up;
+        }
         let msg = ProcessMessage::ViewSplats {
-            // If the metadata has an up axis prefer that, otherwise estimate
-            // the up direction.
-            up_axis: message.meta.up_axis.or(Some(estimated_up)),
+            up_axis: Some(up_axis),
             splats: Box::new(message.splats.valid()),
             frame: 0,
             total_frames: 0,
@@ -80,135 +153,790 @@ pub(crate) async fn train_stream(
         .emit(ProcessMessage::DoneLoading { training: true })
         .await;
 
-    let splats = if let Some(splats) = initial_splats {
-        splats
-    } else {
-        log::info!("Starting with random splat config.");
-
-        // By default, spawn the splats in bounds.
-        let bounds = dataset.train.bounds();
-        let bounds_extent = bounds.extent.length();
-        // Arbitrarily assume area of interest is 0.2 - 0.75 of scene bounds.
-        // Somewhat specific to the blender scenes
-        let adjusted_bounds = dataset
-            .train
-            .adjusted_bounds(bounds_extent * 0.25, bounds_extent);
-        let config = RandomSplatsConfig::new();
-
-        Splats::from_random_config(&config, adjusted_bounds, &mut rng, &device)
+    // Chunks are trained without evaluating, only the merged chunks are.
+    let eval_request = EvalRequest::default();
+    if dataset.eval.is_some() && process_config.train_chunks <= 1 {
+        emitter
+            .emit(ProcessMessage::EvalAvailable {
+                request: eval_request.clone(),
+            })
+            .await;
+    }
+
+    let initial_splats = teacher_init.or(initial_splats);
+    if let Some(splats) = &initial_splats {
+        brush_train::memory::check_buffer_limit(
+            splats.num_splats(),
+            process_args.model_config.sh_degree,
+            max_buffer,
+        )?;
+    }
+
+    let scene_extent = process_args
+        .train_config
+        .scene_extent
+        .or_else(|| dataset.train.estimate_extent())
+        .unwrap_or(1.0);
+    log::info!("Scene extent: {scene_extent}");
+    // Splats trained on linear colors are converted back to sRGB when exported.
+    let export_srgb = dataset
+        .train
+        .views
+        .first()
+        .is_some_and(|view| view.image.color_space == ColorSpace::Linear);
+
+    // Everything this run writes ends up in here.
+    let run_dir = process_config.run_dir();
+    #[cfg(not(target_family = "wasm"))]
+    {
+        tokio::fs::create_dir_all(&run_dir)
+            .await
+            .with_context(|| format!("Failed to create run folder {run_dir:?}"))?;
+        log::info!("Writing run to {run_dir:?}");
+        emitter
+            .emit(ProcessMessage::RunDir {
+                path: run_dir.clone(),
+            })
+            .await;
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    let report = std::sync::Mutex::new(RunReport::default());
+    let run = TrainRun {
+        process_args: &process_args,
+        device: &device,
+        emitter: &emitter,
+        visualize: &visualize,
+        run_dir: &run_dir,
+        export_srgb,
+        up_axis,
+        eval_schedule: &eval_schedule,
+        eval_request: &eval_request,
+        #[cfg(not(target_family = "wasm"))]
+        report: &report,
     };
 
-    let mut splats = splats.with_sh_degree(process_args.model_config.sh_degree);
+    if process_config.train_chunks <= 1 {
+        let splats = match initial_splats {
+            Some(splats) => splats,
+            None => random_splats(&dataset.train, &mut rng, &device),
+        };
+        let trained = train_scene(
+            &run,
+            &dataset.train,
+            dataset.eval.as_ref(),
+            splats,
+            scene_extent,
+            process_config.start_iter..process_args.train_config.total_steps,
+            None,
+            "",
+        )
+        .await?;
+        #[cfg(not(target_family = "wasm"))]
+        write_report(&run, trained.num_splats()).await?;
+        return Ok(());
+    }
+
+    let chunks = partition(
+        &dataset.train,
+        process_config.train_chunks,
+        process_config.chunk_overlap,
+    );
+    let focus = ChunkFocus::default();
+    emitter
+        .emit(ProcessMessage::TrainChunks {
+            count: chunks.len() as u32,
+            focus: focus.clone(),
+        })
+        .await;
 
-    let mut eval_scene = dataset.eval;
-    let scene_extent = dataset.train.estimate_extent().unwrap_or(1.0);
+    let total_steps = process_args.train_config.total_steps;
+    let chunk_extent = |chunk: &Chunk| {
+        process_args
+            .train_config
+            .scene_extent
+            .or_else(|| chunk.scene.estimate_extent())
+            .unwrap_or(scene_extent)
+    };
 
-    let mut train_duration = Duration::from_secs(0);
-    let mut dataloader = SceneLoader::new(&dataset.train, 42, &device);
-    let mut trainer = SplatTrainer::new(&process_args.train_config, &device);
+    // Each trained chunk, cut back to its own part of the scene.
+    let mut trained_chunks: Vec<TrainedChunk> = vec![];
+    let mut remaining: Vec<usize> = (0..chunks.len()).collect();
+    while !remaining.is_empty() {
+        // Train the chunk closest to where the viewer is looking first, if anyone is.
+        let next = focus.get().map_or(0, |position| {
+            (0..remaining.len())
+                .min_by(|&a, &b| {
+                    let dist = |k: usize| chunks[remaining[k]].distance(position);
+                    dist(a).total_cmp(&dist(b))
+                })
+                .unwrap_or(0)
+        });
+        let i = remaining.remove(next);
+        let chunk = &chunks[i];
+        log::info!(
+            "Training chunk {} ({} of {}), with {} views",
+            i,
+            trained_chunks.len() + 1,
+            chunks.len(),
+            chunk.scene.views.len()
+        );
 
-    log::info!("Start training loop.");
-    for iter in process_args.process_config.start_iter..process_args.train_config.total_steps {
-        let step_time = Instant::now();
+        // Start from the initial splats in the part of the scene the chunk is trained on.
+        let mut splats = None;
+        if let Some(initial) = &initial_splats {
+            let weights = initial.blend_weights(
+                chunk.min - chunk.overlap,
+                chunk.max + chunk.overlap,
+                Vec3::ZERO,
+            );
+            splats = keep_weighted(initial.clone(), weights).await?;
+        }
+        let splats = splats.unwrap_or_else(|| random_splats(&chunk.scene, &mut rng, &device));
 
-        let batch = dataloader.next_batch().await;
-        let (new_splats, stats) = trainer.step(scene_extent, iter, &batch, splats);
-        splats = new_splats;
-        let (new_splats, refine) = trainer.refine_if_needed(iter, splats).await;
-        splats = new_splats;
+        let shown_with = merge_chunks(&trained_chunks, None, Some(PREVIEW_SPLATS), &device)?;
+        let trained = train_scene(
+            &run,
+            &chunk.scene,
+            None,
+            splats,
+            chunk_extent(chunk),
+            process_config.start_iter..total_steps,
+            shown_with.as_ref(),
+            &format!("chunk_{i}_"),
+        )
+        .await?;
 
-        #[allow(unused)]
-        let export_path =
-            Path::new(process_config.export_path.as_deref().unwrap_or(".")).to_owned();
+        // Cut the chunk back to its own part of the scene, fading out across the overlap so
+        // it blends with its neighbors.
+        let trained = trained.valid();
+        let weights = trained.blend_weights(chunk.min, chunk.max, chunk.overlap);
+        if let Some(blended) = keep_weighted(trained, weights).await? {
+            trained_chunks.push(TrainedChunk::new(i, &blended).await?);
+        }
+    }
 
-        // We just finished iter 'iter', now starting iter + 1.
-        let iter = iter + 1;
-        let is_last_step = iter == process_args.train_config.total_steps;
+    // Nothing is training anymore, so the whole scene can be on the GPU now.
+    let Some(merged) = merge_chunks(&trained_chunks, None, None, &device)? else {
+        return Err(anyhow::anyhow!("None of the chunks have any splats left").into());
+    };
+    log::info!(
+        "Merged {} chunks into {} splats",
+        chunks.len(),
+        merged.num_splats()
+    );
 
-        // Check if we want to evaluate _next iteration_. Small detail, but this ensures we evaluate
-        // before doing a refine.
-        if iter % process_config.eval_every == 0 || is_last_step {
-            if let Some(eval_scene) = eval_scene.as_mut() {
-                let mut psnr = 0.0;
-                let mut ssim = 0.0;
-                let mut count = 0;
+    if let Some(eval_scene) = &dataset.eval {
+        evaluate(&run, eval_scene, &merged, None, total_steps).await?;
+    }
+    #[cfg(not(target_family = "wasm"))]
+    {
+        export_merged(&run, &trained_chunks, merged.clone()).await?;
+        write_report(&run, merged.num_splats()).await?;
+    }
+
+    emitter
+        .emit(ProcessMessage::ViewSplats {
+            up_axis: None,
+            splats: Box::new(merged),
+            frame: 0,
+            total_frames: 0,
+        })
+        .await;
 
-                log::info!("Running evaluation for iteration {iter}");
+    if !process_config.refine_viewed_chunk {
+        return Ok(());
+    }
 
-                for (i, view) in eval_scene.views.iter().enumerate() {
-                    let sample = eval_stats(splats.valid(), view, &device)
-                        .await
-                        .context("Failed to run eval for sample.")?;
+    // Refine the chunk the viewer is looking at in a short round at the end of the schedule,
+    // each time the viewer looks at another chunk, until nobody is looking anymore.
+    let mut last_refined = None;
+    while let Some(position) = focus.get() {
+        let Some(k) = (0..trained_chunks.len()).min_by(|&a, &b| {
+            let dist = |k: usize| chunks[trained_chunks[k].index].distance(position);
+            dist(a).total_cmp(&dist(b))
+        }) else {
+            break;
+        };
+        if last_refined == Some(k) {
+            focus.changed().await;
+            continue;
+        }
+        last_refined = Some(k);
 
-                    count += 1;
-                    psnr += sample.psnr.clone().into_scalar_async().await;
-                    ssim += sample.ssim.clone().into_scalar_async().await;
+        let i = trained_chunks[k].index;
+        let splats = trained_chunks[k].load(&device)?;
+        let chunk = &chunks[i];
+        log::info!("Refining chunk {i}");
 
-                    #[cfg(not(target_family = "wasm"))]
-                    if process_args.process_config.eval_save_to_disk {
-                        log::info!("Saving eval image to disk.");
+        // Only refine the splats in the chunk's own part of the scene, the ones faded out
+        // across the overlap are left as they are.
+        let own = splats.blend_weights(chunk.min, chunk.max, Vec3::ZERO);
+        let rim = keep_weighted(splats.clone(), own.clone().neg() + 1.0).await?;
+        let Some(core) = keep_weighted(splats, own).await? else {
+            break;
+        };
 
-                        let eval_render = crate::process_loop::tensor_into_image(
-                            sample.rendered.clone().into_data_async().await,
-                        );
-                        let rendered: image::DynamicImage = eval_render.into_rgb8().into();
+        let shown_with = merge_chunks(&trained_chunks, Some(k), Some(PREVIEW_SPLATS), &device)?
+            .into_iter()
+            .chain(rim.clone())
+            .reduce(Splats::append);
+        let refined = train_scene(
+            &run,
+            &chunk.scene,
+            None,
+            to_autodiff(core),
+            chunk_extent(chunk),
+            total_steps.saturating_sub(REFINE_ROUND_STEPS)..total_steps,
+            shown_with.as_ref(),
+            &format!("chunk_{i}_"),
+        )
+        .await?;
 
-                        let img_name = Path::new(&view.image.path)
-                            .file_stem()
-                            .expect("No file name for eval view.")
-                            .to_string_lossy();
+        let refined = refined.valid();
+        let own = refined.blend_weights(chunk.min, chunk.max, Vec3::ZERO);
+        let refined = keep_weighted(refined, own).await?;
+        let Some(splats) = [rim, refined].into_iter().flatten().reduce(Splats::append) else {
+            break;
+        };
+        trained_chunks[k] = TrainedChunk::new(i, &splats).await?;
 
-                        let path = Path::new(&export_path)
-                            .join(format!("eval_{iter}"))
-                            .join(format!("{img_name}.png"));
+        let Some(merged) = merge_chunks(&trained_chunks, None, None, &device)? else {
+            break;
+        };
+        #[cfg(not(target_family = "wasm"))]
+        export_merged(&run, &trained_chunks, merged.clone()).await?;
+        emitter
+            .emit(ProcessMessage::ViewSplats {
+                up_axis: None,
+                splats: Box::new(merged),
+                frame: 0,
+                total_frames: 0,
+            })
+            .await;
+    }
 
-                        let parent = path.parent().expect("Eval must have a filename");
-                        tokio::fs::create_dir_all(parent).await?;
+    Ok(())
+}
 
-                        log::info!("Saving eval view to {path:?}");
+/// Steps in one round of refining the chunk being viewed.
+const REFINE_ROUND_STEPS: u32 = 500;
 
-                        rendered.save(path)?;
-                    }
+/// Most splats of the other chunks shown while training a chunk. They're only there to show
+/// the rest of the scene, so they're cut down to keep them from taking much GPU memory.
+const PREVIEW_SPLATS: u32 = 1_000_000;
 
-                    visualize.log_eval_sample(iter, i as u32, sample).await?;
-                }
+/// A trained chunk. These are kept in host memory, so only the chunk being trained has to fit
+/// on the GPU.
+struct TrainedChunk {
+    /// Index of the chunk in the partition.
+    index: usize,
+    num_splats: u32,
+    /// The splats, as saved by [`Splats::to_safetensors`].
+    data: Vec<u8>,
+}
 
-                psnr /= count as f32;
-                ssim /= count as f32;
+impl TrainedChunk {
+    async fn new(index: usize, splats: &Splats<InnerBackend>) -> anyhow::Result<Self> {
+        Ok(Self {
+            index,
+            num_splats: splats.num_splats(),
+            data: splats.to_safetensors().await?,
+        })
+    }
 
-                visualize.log_eval_stats(iter, psnr, ssim)?;
+    fn load(&self, device: &WgpuDevice) -> anyhow::Result<Splats<InnerBackend>> {
+        Ok(Splats::from_safetensors(&self.data, device)?)
+    }
+}
 
-                let message = ProcessMessage::EvalResult {
-                    iter,
-                    avg_psnr: psnr,
-                    avg_ssim: ssim,
-                };
+/// All trained chunks appended together on the GPU, except the one at `skip`. With
+/// `max_splats`, each chunk is cut down to its share of them, keeping its most important
+/// splats.
+fn merge_chunks(
+    chunks: &[TrainedChunk],
+    skip: Option<usize>,
+    max_splats: Option<u32>,
+    device: &WgpuDevice,
+) -> anyhow::Result<Option<Splats<InnerBackend>>> {
+    let merged: Vec<_> = chunks
+        .iter()
+        .enumerate()
+        .filter(|(k, _)| Some(*k) != skip)
+        .map(|(_, chunk)| chunk)
+        .collect();
+    let total: u64 = merged.iter().map(|chunk| chunk.num_splats as u64).sum();
 
-                emitter.emit(message).await;
-            }
+    let mut splats: Option<Splats<InnerBackend>> = None;
+    for chunk in merged {
+        let mut chunk_splats = chunk.load(device)?;
+        if let Some(max_splats) = max_splats {
+            let share = max_splats as u64 * chunk.num_splats as u64 / total.max(1);
+            chunk_splats = chunk_splats.keep_most_important((share as u32).max(1));
         }
+        splats = Some(match splats {
+            Some(splats) => splats.append(chunk_splats),
+            None => chunk_splats,
+        });
+    }
+    Ok(splats)
+}
+
+/// Splats that can be trained again, from ones that were trained before.
+fn to_autodiff(splats: Splats<InnerBackend>) -> Splats<TrainBack> {
+    Splats::from_tensor_data(
+        Tensor::from_inner(splats.means.val()),
+        Tensor::from_inner(splats.rotation.val()),
+        Tensor::from_inner(splats.log_scales.val()),
+        Tensor::from_inner(splats.sh_coeffs.val()),
+        Tensor::from_inner(splats.raw_opacity.val()),
+    )
+}
+
+type InnerBackend = <TrainBack as AutodiffBackend>::InnerBackend;
+
+/// Everything a training run needs besides the scene it trains on.
+struct TrainRun<'a> {
+    process_args: &'a ProcessArgs,
+    device: &'a WgpuDevice,
+    emitter: &'a TryStreamEmitter<ProcessMessage, ProcessError>,
+    visualize: &'a VisualizeTools,
+    #[allow(unused)]
+    run_dir: &'a Path,
+    #[allow(unused)]
+    export_srgb: bool,
+    /// Up direction of the scene, for orienting exports.
+    #[allow(unused)]
+    up_axis: Vec3,
+    eval_schedule: &'a EvalSchedule,
+    /// Set when the viewer asks for an eval.
+    eval_request: &'a EvalRequest,
+    #[cfg(not(target_family = "wasm"))]
+    report: &'a std::sync::Mutex<RunReport>,
+}
+
+/// Random splats in the bounds of the cameras of `scene`.
+fn random_splats(
+    scene: &Scene,
+    rng: &mut rand::rngs::StdRng,
+    device: &WgpuDevice,
+) -> Splats<TrainBack> {
+    log::info!("Starting with random splat config.");
+
+    // By default, spawn the splats in bounds.
+    let bounds = scene.bounds();
+    let bounds_extent = bounds.extent.length();
+    // Arbitrarily assume area of interest is 0.2 - 0.75 of scene bounds.
+    // Somewhat specific to the blender scenes
+    let adjusted_bounds = scene.adjusted_bounds(bounds_extent * 0.25, bounds_extent);
+    let config = RandomSplatsConfig::new();
+
+    Splats::from_random_config(&config, adjusted_bounds, rng, device)
+}
+
+/// Keep the splats with a weight above zero, with their opacity scaled by it. `None` if
+/// no splats are left.
+async fn keep_weighted<B: Backend>(
+    splats: Splats<B>,
+    weights: Tensor<B, 1>,
+) -> anyhow::Result<Option<Splats<B>>> {
+    let values = weights
+        .clone()
+        .into_data_async()
+        .await
+        .to_vec::<f32>()
+        .map_err(|e| anyhow::anyhow!("Failed to read blend weights: {e:?}"))?;
+    let keep: Vec<i32> = values
+        .iter()
+        .enumerate()
+        .filter(|(_, w)| **w > 0.0)
+        .map(|(i, _)| i as i32)
+        .collect();
+    if keep.is_empty() {
+        return Ok(None);
+    }
+    let len = keep.len();
+    let keep = Tensor::<B, 1, Int>::from_data(TensorData::new(keep, [len]), &splats.device());
+    Ok(Some(splats.with_opacity_scaled(weights).select(keep)))
+}
+
+/// Average PSNR and SSIM of the splats over the eval views. With a decomposed lighting, the
+/// splats are lit by `env_light` as they are while training.
+async fn evaluate(
+    run: &TrainRun<'_>,
+    eval_scene: &Scene,
+    splats: &Splats<InnerBackend>,
+    env_light: Option<&EnvLight<InnerBackend>>,
+    iter: u32,
+) -> anyhow::Result<()> {
+    let mut psnr = 0.0;
+    let mut ssim = 0.0;
+    let mut count = 0;
+
+    log::info!("Running evaluation for iteration {iter}");
+
+    for (i, view) in eval_scene.views.iter().enumerate() {
+        let splats = match env_light {
+            Some(light) => light.relight(splats.clone(), &view.camera),
+            None => splats.clone(),
+        };
+        let sample = eval_stats(splats, view, run.device)
+            .await
+            .context("Failed to run eval for sample.")?;
 
-        let client = WgpuRuntime::client(&device);
-        visualize.log_memory(iter, &client.memory_usage())?;
+        count += 1;
+        let sample_psnr = sample.psnr.clone().into_scalar_async().await;
+        psnr += sample_psnr;
+        ssim += sample.ssim.clone().into_scalar_async().await;
 
-        // TODO: Support this on WASM somehow. Maybe have user pick a file once,
-        // and write to it repeatedly?
         #[cfg(not(target_family = "wasm"))]
-        if iter % process_config.export_every == 0 || is_last_step {
-            let total_steps = process_args.train_config.total_steps;
+        if i < report::MAX_COMPARISONS {
+            let rendered = crate::process_loop::tensor_into_image(
+                sample.rendered.clone().into_data_async().await,
+            );
+            run.report
+                .lock()
+                .expect("Report lock poisoned")
+                .add_comparison(
+                    i,
+                    &view.image.name(),
+                    &sample.gt_img,
+                    &rendered,
+                    sample_psnr,
+                )?;
+        }
+
+        #[cfg(not(target_family = "wasm"))]
+        if run.process_args.process_config.eval_save_to_disk {
+            log::info!("Saving eval image to disk.");
+
+            let eval_render = crate::process_loop::tensor_into_image(
+                sample.rendered.clone().into_data_async().await,
+            );
+            let rendered: image::DynamicImage = eval_render.into_rgb8().into();
+
+            let img_name = view.image.name();
+
+            let path = run
+                .run_dir
+                .join(format!("eval_{iter}"))
+                .join(format!("{img_name}.png"));
+
+            let parent = path.parent().expect("Eval must have a filename");
+            tokio::fs::create_dir_all(parent).await?;
 
-            // Ad-hoc format string.
-            let digits = (total_steps as f64).log10().ceil() as usize;
-            let export_name = process_config
-                .export_name
-                .replace("{iter}", &format!("{iter:0digits$}"));
+            log::info!("Saving eval view to {path:?}");
 
-            tokio::fs::create_dir_all(&export_path).await?;
+            rendered.save(path)?;
+        }
+
+        run.visualize
+            .log_eval_sample(iter, i as u32, sample)
+            .await?;
+    }
+
+    psnr /= count as f32;
+    ssim /= count as f32;
+
+    run.visualize.log_eval_stats(iter, psnr, ssim)?;
+    #[cfg(not(target_family = "wasm"))]
+    run.report
+        .lock()
+        .expect("Report lock poisoned")
+        .add_eval(iter, psnr, ssim);
+
+    let message = ProcessMessage::EvalResult {
+        iter,
+        avg_psnr: psnr,
+        avg_ssim: ssim,
+    };
+
+    run.emitter.emit(message).await;
+    Ok(())
+}
+
+/// Write the splats to the run folder, named after the export name with `prefix` in front.
+// TODO: Support this on WASM somehow. Maybe have user pick a file once,
+// and write to it repeatedly?
+#[cfg(not(target_family = "wasm"))]
+async fn export_splats(
+    run: &TrainRun<'_>,
+    splats: Splats<InnerBackend>,
+    env_light: Option<&EnvLight<TrainBack>>,
+    appearance: Option<(&brush_train::appearance::Appearance<TrainBack>, &[String])>,
+    iter: u32,
+    prefix: &str,
+) -> anyhow::Result<()> {
+    let process_config = &run.process_args.process_config;
+    let total_steps = run.process_args.train_config.total_steps;
+    let run_dir = run.run_dir;
 
-            let splat_data = brush_dataset::splat_export::splat_to_ply(splats.valid()).await?;
-            tokio::fs::write(export_path.join(&export_name), splat_data)
+    // Ad-hoc format string.
+    let digits = (total_steps as f64).log10().ceil() as usize;
+    let export_name = process_config
+        .export_name
+        .replace("{iter}", &format!("{iter:0digits$}"));
+    let export_name = match export_name.rsplit_once('/') {
+        Some((dir, file)) => format!("{dir}/{prefix}{file}"),
+        None => format!("{prefix}{export_name}"),
+    };
+
+    tokio::fs::create_dir_all(run_dir).await?;
+
+    let encode = |splats: Splats<InnerBackend>| {
+        if run.export_srgb {
+            splats.encode_srgb()
+        } else {
+            splats
+        }
+    };
+    let upright = process_config.export_upright;
+    let to_ply = |splats: Splats<InnerBackend>| {
+        brush_dataset::splat_export::splat_to_ply_oriented(encode(splats), run.up_axis, upright)
+    };
+    let splat_data = to_ply(splats.clone()).await?;
+    tokio::fs::write(run_dir.join(&export_name), splat_data)
+        .await
+        .with_context(|| format!("Failed to export ply {run_dir:?}"))?;
+
+    // Save the splats in the look of each capture session too.
+    if let Some((appearance, names)) = appearance {
+        let appearance = appearance.valid();
+        for (session, name) in names.iter().enumerate() {
+            let splat_data = to_ply(appearance.bake(splats.clone(), session)).await?;
+            let path = run_dir.join(Path::new(&export_name).with_extension(format!("{name}.ply")));
+            tokio::fs::write(&path, splat_data)
                 .await
-                .with_context(|| format!("Failed to export ply {export_path:?}"))?;
+                .with_context(|| format!("Failed to export ply {path:?}"))?;
+        }
+    }
+
+    // The splats only hold the albedo, save the light they were lit by next to them.
+    if let Some(light) = env_light {
+        let light = serde_json::json!({
+            "irradiance_sh_coeffs": light.to_coeffs().await,
+        });
+        let light_path = run_dir.join(Path::new(&export_name).with_extension("light.json"));
+        tokio::fs::write(&light_path, serde_json::to_vec_pretty(&light)?)
+            .await
+            .with_context(|| format!("Failed to export light {light_path:?}"))?;
+    }
+    Ok(())
+}
+
+/// Folder of the run the merged chunks are saved to one by one, for viewing them streamed in.
+#[cfg(not(target_family = "wasm"))]
+const MERGED_CHUNKS_DIR: &str = "merged_chunks";
+
+/// Save a merged chunk to its own ply file, to list it in the chunk index.
+#[cfg(not(target_family = "wasm"))]
+async fn export_chunk(
+    run: &TrainRun<'_>,
+    splats: Splats<InnerBackend>,
+    index: usize,
+) -> anyhow::Result<brush_dataset::chunks::ChunkFile> {
+    let read_vec = |t: Tensor<InnerBackend, 2>| async move {
+        let values = t
+            .into_data_async()
+            .await
+            .to_vec::<f32>()
+            .map_err(|e| anyhow::anyhow!("Failed to read chunk bounds: {e:?}"))?;
+        anyhow::Ok(Vec3::from_slice(&values))
+    };
+    let means = splats.means.val();
+    let min = read_vec(means.clone().min_dim(0)).await?;
+    let max = read_vec(means.max_dim(0)).await?;
+
+    let dir = run.run_dir.join(MERGED_CHUNKS_DIR);
+    tokio::fs::create_dir_all(&dir).await?;
+    let file = format!("chunk_{index}.ply");
+    let export_splats = if run.export_srgb {
+        splats.encode_srgb()
+    } else {
+        splats
+    };
+    // Chunks stay in the orientation of their index, rotating them would move them off it.
+    let splat_data =
+        brush_dataset::splat_export::splat_to_ply_oriented(export_splats, run.up_axis, false)
+            .await?;
+    tokio::fs::write(dir.join(&file), splat_data)
+        .await
+        .with_context(|| format!("Failed to export chunk {file}"))?;
+    Ok(brush_dataset::chunks::ChunkFile { file, min, max })
+}
+
+/// Write the report of the run to the run folder.
+#[cfg(not(target_family = "wasm"))]
+async fn write_report(run: &TrainRun<'_>, splat_count: u32) -> anyhow::Result<()> {
+    let config = serde_json::to_string_pretty(run.process_args)?;
+    let threads = std::thread::available_parallelism().map_or(0, |n| n.get());
+    let hardware = [
+        ("GPU device", format!("{:?}", run.device)),
+        (
+            "OS",
+            format!("{} ({})", std::env::consts::OS, std::env::consts::ARCH),
+        ),
+        ("CPU threads", threads.to_string()),
+    ];
+    let html = {
+        let mut report = run.report.lock().expect("Report lock poisoned");
+        report.set_splat_count(splat_count);
+        report.to_html(&config, &hardware)
+    };
+    let path = run.run_dir.join(report::REPORT_FILE);
+    tokio::fs::write(&path, html)
+        .await
+        .with_context(|| format!("Failed to write report {path:?}"))?;
+    log::info!("Wrote training report to {path:?}");
+    Ok(())
+}
+
+/// Export the merged model, and each chunk on its own along with an index of them.
+#[cfg(not(target_family = "wasm"))]
+async fn export_merged(
+    run: &TrainRun<'_>,
+    chunks: &[TrainedChunk],
+    merged: Splats<InnerBackend>,
+) -> anyhow::Result<()> {
+    let total_steps = run.process_args.train_config.total_steps;
+    export_splats(run, merged, None, None, total_steps, "").await?;
+
+    let mut index = brush_dataset::chunks::ChunkIndex::default();
+    for chunk in chunks {
+        let splats = chunk.load(run.device)?;
+        index
+            .chunks
+            .push(export_chunk(run, splats, chunk.index).await?);
+    }
+    let index_path = run
+        .run_dir
+        .join(MERGED_CHUNKS_DIR)
+        .join(brush_dataset::chunks::CHUNK_INDEX_FILE);
+    tokio::fs::write(&index_path, serde_json::to_vec_pretty(&index)?)
+        .await
+        .with_context(|| format!("Failed to export chunk index {index_path:?}"))?;
+    Ok(())
+}
+
+/// Train the splats on `train_scene` for the iterations in `steps`, evaluating on
+/// `eval_scene` if any. The viewer is sent the splats along with `shown_with`, eg. the rest
+/// of the scene when training part of it. Exports are named with `export_prefix` in front.
+async fn train_scene(
+    run: &TrainRun<'_>,
+    train_scene: &Scene,
+    eval_scene: Option<&Scene>,
+    splats: Splats<TrainBack>,
+    scene_extent: f32,
+    steps: Range<u32>,
+    shown_with: Option<&Splats<InnerBackend>>,
+    #[allow(unused)] export_prefix: &str,
+) -> anyhow::Result<Splats<TrainBack>> {
+    let process_args = run.process_args;
+    let process_config = &process_args.process_config;
+    let device = run.device;
+    let visualize = run.visualize;
+
+    let mut splats = splats.with_sh_degree(process_args.model_config.sh_degree);
+
+    let mut train_duration = Duration::from_secs(0);
+    let mut last_splat_update: Option<Instant> = None;
+    let snapshot = SplatSnapshot::default();
+    let splat_update_interval =
+        Duration::from_secs_f32(1.0 / process_config.max_splat_updates_per_sec.max(0.01));
+    let mut dataloader = SceneLoader::new(train_scene, 42, &process_args.load_config, device);
+    let mut trainer = SplatTrainer::new(&process_args.train_config, device);
+    let sessions = train_scene.capture_sessions();
+    trainer.learn_appearance(sessions.of_view.clone(), device);
+    if trainer.appearance().is_some() {
+        log::info!("Learning the look of sessions {:?}", sessions.names);
+    }
+    let mut convergence = ConvergenceMonitor::new(process_args.train_config.stop_min_improvement);
+
+    log::info!("Start training loop.");
+    for iter in steps.clone() {
+        let step_time = Instant::now();
+
+        let mut batches = vec![];
+        for _ in 0..process_args.train_config.grad_accum.max(1) {
+            batches.push(dataloader.next_batch().await);
+        }
+        let (new_splats, stats) = trainer.step(scene_extent, iter, &batches, splats);
+        splats = new_splats;
+        let (new_splats, refine) = trainer.refine_if_needed(iter, splats).await;
+        splats = new_splats;
+
+        // We just finished iter 'iter', now starting iter + 1.
+        let iter = iter + 1;
+
+        // Reading back the loss syncs with the GPU, so only sample it now and then, for the
+        // report and to check for convergence, unless the view scheduler needs the loss of
+        // every step.
+        let check_convergence = process_args.train_config.stop_min_improvement > 0.0;
+        let sample_loss =
+            iter % LOSS_SAMPLE_EVERY == 0 && (check_convergence || !cfg!(target_family = "wasm"));
+        if dataloader.wants_loss() {
+            for (batch, loss) in batches.iter().zip(&stats.batch_losses) {
+                dataloader.report_loss(batch.view_index, loss.clone().into_scalar_async().await);
+            }
+        }
+
+        let mut converged = false;
+        if sample_loss {
+            let loss = stats.loss.clone().into_scalar_async().await;
+            #[cfg(not(target_family = "wasm"))]
+            run.report
+                .lock()
+                .expect("Report lock poisoned")
+                .add_loss(iter, loss);
+            if check_convergence {
+                convergence.add_loss(iter, loss);
+                converged =
+                    iter >= process_args.train_config.growth_stop_iter && convergence.converged();
+                if converged {
+                    log::info!("Loss stopped improving, ending training at iteration {iter}");
+                }
+            }
+        }
+        let is_last_step = iter == steps.end || converged;
+
+        // Check if we want to evaluate _next iteration_. Small detail, but this ensures we evaluate
+        // before doing a refine.
+        let train_config = &process_args.train_config;
+        let scheduled = run.eval_schedule.should_eval(
+            iter,
+            train_config.refine_every,
+            train_config.growth_stop_iter,
+        );
+        let requested = run.eval_request.take();
+        if scheduled || requested || is_last_step {
+            if let Some(eval_scene) = eval_scene {
+                let env_light = trainer.env_light().map(|light| light.valid());
+                evaluate(run, eval_scene, &splats.valid(), env_light.as_ref(), iter).await?;
+            }
+        }
+
+        let client = WgpuRuntime::client(device);
+        let memory = client.memory_usage();
+        visualize.log_memory(iter, &memory)?;
+        #[cfg(not(target_family = "wasm"))]
+        run.report
+            .lock()
+            .expect("Report lock poisoned")
+            .add_memory(memory.bytes_reserved);
+
+        #[cfg(not(target_family = "wasm"))]
+        if iter % process_config.export_every == 0 || is_last_step {
+            export_splats(
+                run,
+                splats.valid(),
+                trainer.env_light(),
+                trainer
+                    .appearance()
+                    .map(|appearance| (appearance, sessions.names.as_slice())),
+                iter,
+                export_prefix,
+            )
+            .await?;
         }
 
         if let Some(every) = process_args.rerun_config.rerun_log_splats_every {
@@ -230,7 +958,10 @@ pub(crate) async fn train_stream(
         // Emit some messages. Important to not count these in the training time (as this might pause).
         if let Some(stats) = refine {
             visualize.log_refine_stats(iter, &stats)?;
-            emitter
+
+            #[cfg(not(target_family = "wasm"))]
+            append_refine_log(run.run_dir, iter, &stats).await?;
+            run.emitter
                 .emit(ProcessMessage::RefineStep {
                     stats: Box::new(stats),
                     cur_splat_count: splats.num_splats(),
@@ -239,18 +970,88 @@ pub(crate) async fn train_stream(
                 .await;
         }
 
-        // How frequently to update the UI after a training step.
-        const UPDATE_EVERY: u32 = 5;
-        if iter % UPDATE_EVERY == 0 || is_last_step {
-            let message = ProcessMessage::TrainStep {
-                splats: Box::new(splats.valid()),
-                stats: Box::new(stats),
-                iter,
-                total_elapsed: train_duration,
-            };
-            emitter.emit(message).await;
+        // Stats are sent every step, but limit how often the splats are sent off to be
+        // uploaded, independent of the step rate.
+        let update_due = last_splat_update.is_none_or(|t| t.elapsed() >= splat_update_interval);
+        let upload = update_due || is_last_step;
+        if upload {
+            last_splat_update = Some(Instant::now());
+            snapshot.publish(&match shown_with {
+                Some(shown_with) => shown_with.clone().append(splats.valid()),
+                None => splats.valid(),
+            });
+        }
+        // The predicted image in the stats is of the last batch.
+        let batch = batches.last().expect("Trained on a batch");
+        let message = ProcessMessage::TrainStep {
+            splats: upload.then(|| snapshot.clone()),
+            stats: Box::new(stats),
+            camera: batch.camera.clone(),
+            view_index: batch.view_index,
+            gt_image: batch.img_tensor.clone().inner(),
+            iter,
+            total_elapsed: train_duration,
+            image_cache: dataloader.cache_stats().await,
+        };
+        run.emitter.emit(message).await;
+
+        if upload {
+            if let Some(appearance) = trainer.appearance() {
+                run.emitter
+                    .emit(ProcessMessage::Appearances {
+                        names: sessions.names.clone(),
+                        appearance: appearance.valid(),
+                    })
+                    .await;
+            }
+            if let Some(light) = trainer.env_light() {
+                run.emitter
+                    .emit(ProcessMessage::EnvLight {
+                        light: light.valid(),
+                    })
+                    .await;
+            }
+        }
+
+        if is_last_step {
+            break;
         }
     }
 
+    #[cfg(not(target_family = "wasm"))]
+    run.report
+        .lock()
+        .expect("Report lock poisoned")
+        .add_train_time(train_duration);
+
+    Ok(splats)
+}
+
+/// Append the refine stats as a line of JSON to `refine_stats.jsonl` next to the exports.
+#[cfg(not(target_family = "wasm"))]
+async fn append_refine_log(
+    run_dir: &Path,
+    iter: u32,
+    stats: &brush_train::train::RefineStats,
+) -> anyhow::Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    #[derive(serde::Serialize)]
+    struct Entry<'a> {
+        iter: u32,
+        #[serde(flatten)]
+        stats: &'a brush_train::train::RefineStats,
+    }
+
+    let mut line = serde_json::to_string(&Entry { iter, stats })?;
+    line.push('\n');
+
+    tokio::fs::create_dir_all(run_dir).await?;
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(run_dir.join("refine_stats.jsonl"))
+        .await?;
+    file.write_all(line.as_bytes()).await?;
     Ok(())
 }
@@ -1,18 +1,26 @@
-use std::sync::Arc;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use super::ProcessMessage;
+use crate::error::ProcessError;
+use anyhow::Context;
 use async_fn_stream::TryStreamEmitter;
-use brush_dataset::{brush_vfs::BrushVfs, splat_import};
+use brush_dataset::{brush_vfs::BrushVfs, chunks::ChunkIndex, ckpt_import, splat_import};
+use brush_render::gaussian_splats::Splats;
+use brush_train::train::TrainBack;
+use burn::tensor::backend::AutodiffBackend;
 use burn_wgpu::WgpuDevice;
+use tokio::io::AsyncReadExt;
 use tokio_stream::StreamExt;
 
 pub(crate) async fn view_stream(
     vfs: Arc<BrushVfs>,
+    paths: Vec<PathBuf>,
     device: WgpuDevice,
-    emitter: TryStreamEmitter<ProcessMessage, anyhow::Error>,
-) -> anyhow::Result<()> {
-    let paths: Vec<_> = vfs.file_names().collect();
-
+    emitter: TryStreamEmitter<ProcessMessage, ProcessError>,
+) -> Result<(), ProcessError> {
     for (i, path) in paths.iter().enumerate() {
         log::info!("Loading single ply file");
 
@@ -24,6 +32,8 @@ pub(crate) async fn view_stream(
         let splat_stream = splat_import::load_splat_from_ply(
             vfs.reader_at_path(path).await?,
             sub_sample,
+            // Show what's there of plys that were cut short, eg. by an interrupted export.
+            true,
             device.clone(),
         );
 
@@ -32,6 +42,14 @@ pub(crate) async fn view_stream(
         while let Some(message) = splat_stream.next().await {
             let message = message?;
 
+            emitter
+                .emit(ProcessMessage::LoadProgress {
+                    loaded_bytes: message.meta.loaded_bytes,
+                    total_bytes: message.meta.total_bytes,
+                    eta: message.meta.eta,
+                })
+                .await;
+
             // If there's multiple ply files in a zip, don't support animated plys, that would
             // get rather mind bending.
             let (frame, total_frames) = if paths.len() == 1 {
@@ -48,6 +66,15 @@ pub(crate) async fn view_stream(
             };
 
             emitter.emit(view_splat_msg).await;
+
+            if message.meta.skipped_splats > 0 {
+                emitter
+                    .emit(ProcessMessage::SplatsTruncated {
+                        loaded: message.meta.total_splats,
+                        skipped: message.meta.skipped_splats,
+                    })
+                    .await;
+            }
         }
     }
 
@@ -57,3 +84,92 @@ pub(crate) async fn view_stream(
 
     Ok(())
 }
+
+/// Show splats that are loaded in one go, rather than streamed in.
+async fn view_loaded(
+    emitter: &TryStreamEmitter<ProcessMessage, ProcessError>,
+    load: impl Future<
+        Output = Result<Splats<<TrainBack as AutodiffBackend>::InnerBackend>, ProcessError>,
+    >,
+) -> Result<(), ProcessError> {
+    emitter
+        .emit(ProcessMessage::StartLoading { training: false })
+        .await;
+
+    let splats = load.await?;
+    emitter
+        .emit(ProcessMessage::ViewSplats {
+            up_axis: None,
+            splats: Box::new(splats),
+            frame: 0,
+            total_frames: 1,
+        })
+        .await;
+
+    emitter
+        .emit(ProcessMessage::DoneLoading { training: false })
+        .await;
+    Ok(())
+}
+
+/// View the splats of a nerfstudio checkpoint.
+pub(crate) async fn view_checkpoint_stream(
+    vfs: Arc<BrushVfs>,
+    device: WgpuDevice,
+    emitter: TryStreamEmitter<ProcessMessage, ProcessError>,
+) -> Result<(), ProcessError> {
+    view_loaded(&emitter, async {
+        let message = ckpt_import::load_splat_from_ckpt(vfs, &device).await?;
+        Ok(message.splats)
+    })
+    .await
+}
+
+/// View splat parameters saved as safetensors.
+pub(crate) async fn view_safetensors_stream(
+    vfs: Arc<BrushVfs>,
+    path: PathBuf,
+    device: WgpuDevice,
+    emitter: TryStreamEmitter<ProcessMessage, ProcessError>,
+) -> Result<(), ProcessError> {
+    view_loaded(&emitter, async {
+        let data = read_file(&vfs, &path).await?;
+        Ok(Splats::from_safetensors(&data, &device)?)
+    })
+    .await
+}
+
+/// View a merged scene saved as chunks. Only the index is read here, the viewer loads the
+/// chunks near the camera as it moves around.
+pub(crate) async fn view_chunks_stream(
+    vfs: Arc<BrushVfs>,
+    index_path: PathBuf,
+    emitter: TryStreamEmitter<ProcessMessage, ProcessError>,
+) -> Result<(), ProcessError> {
+    emitter
+        .emit(ProcessMessage::StartLoading { training: false })
+        .await;
+
+    let data = read_file(&vfs, &index_path).await?;
+    let index: ChunkIndex = serde_json::from_slice(&data).context("Invalid chunk index")?;
+    log::info!("Viewing {} chunks from {index_path:?}", index.chunks.len());
+
+    let dir = index_path.parent().unwrap_or(Path::new("")).to_path_buf();
+    emitter
+        .emit(ProcessMessage::ViewChunks { vfs, dir, index })
+        .await;
+
+    emitter
+        .emit(ProcessMessage::DoneLoading { training: false })
+        .await;
+    Ok(())
+}
+
+async fn read_file(vfs: &BrushVfs, path: &Path) -> anyhow::Result<Vec<u8>> {
+    let mut data = vec![];
+    vfs.reader_at_path(path)
+        .await?
+        .read_to_end(&mut data)
+        .await?;
+    Ok(data)
+}
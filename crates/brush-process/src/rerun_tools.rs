@@ -285,6 +285,22 @@ impl VisualizeTools {
                     "refine/effective_growth",
                     &rerun::Scalar::new(refine.num_added as f64 - refine.num_pruned as f64),
                 );
+                let _ = rec.log(
+                    "refine/num_transparent",
+                    &rerun::Scalar::new(refine.num_transparent as f64),
+                );
+                let _ = rec.log(
+                    "refine/num_invisible",
+                    &rerun::Scalar::new(refine.num_invisible as f64),
+                );
+                let _ = rec.log(
+                    "refine/num_budget_pruned",
+                    &rerun::Scalar::new(refine.num_budget_pruned as f64),
+                );
+                let _ = rec.log(
+                    "refine/num_above_threshold",
+                    &rerun::Scalar::new(refine.num_above_threshold as f64),
+                );
             }
         }
 
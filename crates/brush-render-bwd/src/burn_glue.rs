@@ -330,7 +330,7 @@ impl<BT: BoolElement> SplatBackwardOps<Self> for Fusion<BBase<BT>> {
             v_scales: client.tensor_uninitialized(vec![num_points, 3], DType::F32),
             v_coeffs: client.tensor_uninitialized(vec![num_points, coeffs, 3], DType::F32),
             v_raw_opac: client.tensor_uninitialized(vec![num_points], DType::F32),
-            v_refine_weight: client.tensor_uninitialized(vec![num_points, 2], DType::F32),
+            v_refine_weight: client.tensor_uninitialized(vec![num_points, 3], DType::F32),
         };
 
         let desc = CustomOpIr::new(
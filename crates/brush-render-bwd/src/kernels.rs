@@ -74,7 +74,7 @@ pub(crate) fn render_backward<BT: BoolElement>(
 
     // These gradients are atomically added to so important to zero them.
     let v_grads = BBase::<BT>::float_zeros([num_points, 9].into(), device);
-    let v_refine_weight = BBase::<BT>::float_zeros([num_points, 2].into(), device);
+    let v_refine_weight = BBase::<BT>::float_zeros([num_points, 3].into(), device);
 
     let hard_floats =
         client
@@ -154,7 +154,8 @@ fn main(
             var v_xy = vec2f(0.0);
             var v_conic = vec3f(0.0);
             var v_colors = vec4f(0.0);
-            var v_refine = vec2f(0.0);
+            // xy gradient magnitude, and the blend weight of this splat in this pixel.
+            var v_refine = vec3f(0.0);
 
             var splat_active = false;
 
@@ -203,7 +204,7 @@ fn main(
                     let v_rgb = select(vec3f(0.0), fac * v_out.rgb, color.rgb > vec3f(0.0));
                     v_colors = vec4f(v_rgb, vis * v_alpha);
 
-                    v_refine = abs(v_xy);
+                    v_refine = vec3f(abs(v_xy), fac);
                 }
             }
 
@@ -232,14 +233,16 @@ fn main(
                         // all the gaussian fields. The next size (16) is fine.
                         if subgroup_size == 8u {
                             write_grads_atomic(compact_gid * 9 + 8, v_colors_sum.w);
-                            write_refine_atomic(compact_gid * 2 + 0, v_refine_sum.x);
-                            write_refine_atomic(compact_gid * 2 + 1, v_refine_sum.y);
+                            write_refine_atomic(compact_gid * 3 + 0, v_refine_sum.x);
+                            write_refine_atomic(compact_gid * 3 + 1, v_refine_sum.y);
+                            write_refine_atomic(compact_gid * 3 + 2, v_refine_sum.z);
                         }
                     }
 
                     case 8u:  { write_grads_atomic(compact_gid * 9 + 8, v_colors_sum.w); }
-                    case 9u:  { write_refine_atomic(compact_gid * 2 + 0, v_refine_sum.x); }
-                    case 10u: { write_refine_atomic(compact_gid * 2 + 1, v_refine_sum.y); }
+                    case 9u:  { write_refine_atomic(compact_gid * 3 + 0, v_refine_sum.x); }
+                    case 10u: { write_refine_atomic(compact_gid * 3 + 1, v_refine_sum.y); }
+                    case 11u: { write_refine_atomic(compact_gid * 3 + 2, v_refine_sum.z); }
                     default: {}
                 }
             }
@@ -1,5 +1,8 @@
 use glam::Affine3A;
 
+/// How many planes a camera can cut the splats with, see [`Camera::clip_planes`].
+pub const MAX_CLIP_PLANES: usize = 3;
+
 #[derive(Debug, Default, Clone)]
 pub struct Camera {
     pub fov_x: f64,
@@ -7,6 +10,16 @@ pub struct Camera {
     pub center_uv: glam::Vec2,
     pub position: glam::Vec3,
     pub rotation: glam::Quat,
+    /// Evaluate splats exactly along each pixel ray, instead of through their projected
+    /// 2D footprint. Slower, but stays correct at very wide fields of view where the
+    /// projection approximation breaks down. Only applies to renders without gradients:
+    /// the backward pass differentiates the projected footprint, so training, and anything
+    /// else that renders with `bwd_info`, always uses the approximation.
+    pub exact_projection: bool,
+    /// Planes as (normal, offset) that cut away the splats behind them, to look inside a
+    /// scene. A splat is hidden when its center is on the negative side of any plane.
+    /// Planes that are all zero don't cut anything.
+    pub clip_planes: [glam::Vec4; MAX_CLIP_PLANES],
 }
 
 impl Camera {
@@ -23,9 +36,16 @@ impl Camera {
             center_uv,
             position,
             rotation,
+            exact_projection: false,
+            clip_planes: [glam::Vec4::ZERO; MAX_CLIP_PLANES],
         }
     }
 
+    pub fn with_exact_projection(mut self, exact_projection: bool) -> Self {
+        self.exact_projection = exact_projection;
+        self
+    }
+
     pub fn focal(&self, img_size: glam::UVec2) -> glam::Vec2 {
         glam::vec2(
             fov_to_focal(self.fov_x, img_size.x) as f32,
@@ -40,6 +60,18 @@ impl Camera {
         )
     }
 
+    /// Camera that sees only the `size` pixels starting at `min`, of an image of `img_size`.
+    pub fn crop(&self, img_size: glam::UVec2, min: glam::UVec2, size: glam::UVec2) -> Self {
+        let focal = self.focal(img_size);
+        let center = self.center(img_size) - min.as_vec2();
+        Self {
+            fov_x: focal_to_fov(focal.x as f64, size.x),
+            fov_y: focal_to_fov(focal.y as f64, size.y),
+            center_uv: center / size.as_vec2(),
+            ..self.clone()
+        }
+    }
+
     pub fn local_to_world(&self) -> Affine3A {
         Affine3A::from_rotation_translation(self.rotation, self.position)
     }
@@ -57,3 +89,26 @@ pub fn fov_to_focal(fov_rad: f64, pixels: u32) -> f64 {
 pub fn focal_to_fov(focal: f64, pixels: u32) -> f64 {
     2.0 * f64::atan((pixels as f64) / (2.0 * focal))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Camera;
+    use glam::{Quat, Vec3, uvec2, vec2};
+
+    #[test]
+    fn crop_keeps_projection() {
+        let camera = Camera::new(Vec3::ZERO, Quat::IDENTITY, 1.2, 0.9, vec2(0.52, 0.48));
+        let size = uvec2(6000, 4000);
+        let (min, crop_size) = (uvec2(1000, 2500), uvec2(1500, 1000));
+        let crop = camera.crop(size, min, crop_size);
+
+        assert!(
+            (crop.focal(crop_size) - camera.focal(size))
+                .abs()
+                .max_element()
+                < 1e-2
+        );
+        let center = crop.center(crop_size) + min.as_vec2();
+        assert!((center - camera.center(size)).abs().max_element() < 1e-2);
+    }
+}
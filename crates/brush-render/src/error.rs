@@ -0,0 +1,99 @@
+//! Errors that the user can do something about, and what to suggest for them.
+//!
+//! There is no error type for rendering itself: the render ops of the backend only fail on
+//! bugs, which are asserted. What users can get wrong is the splats they load.
+
+use safetensors::{Dtype, SafeTensorError};
+use thiserror::Error;
+
+/// What the user can do about an error.
+///
+/// Frontends phrase these themselves, eg. the app refers to its settings where the command line
+/// refers to its flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Suggestion {
+    /// Load data in a format Brush reads.
+    UnsupportedFormat,
+    /// Link to the data itself, not to a page showing it.
+    DownloadFailed,
+    /// Lay the dataset out as COLMAP or nerfstudio.
+    UnknownLayout,
+    /// Keep a single point cloud to start training from.
+    InitFileCount,
+    /// Raise the limit on archive size.
+    ArchiveTooLarge,
+    /// Raise the limit on the number of files.
+    TooManyFiles,
+    /// Downscale the images, or raise the limit on image size.
+    ImageTooLarge,
+    /// Start training from fewer splats.
+    OutOfMemory,
+    /// Save the splats in the layout Brush expects.
+    SplatsFile,
+}
+
+impl Suggestion {
+    /// The suggestion on the command line, which refers to its flags.
+    pub fn text(self) -> &'static str {
+        match self {
+            Self::UnsupportedFormat => {
+                "Load a .ply, .safetensors, .las, .laz or .e57 file, a .zip of a dataset, or a dataset directory."
+            }
+            Self::DownloadFailed => {
+                "Check that the URL links to the file itself, not to a page showing it, and that it doesn't need a login."
+            }
+            Self::UnknownLayout => {
+                "A dataset needs COLMAP sparse/0/cameras.bin and images.bin files, or a nerfstudio transforms.json, next to its images."
+            }
+            Self::InitFileCount => {
+                "Keep only the point cloud to start from in the dataset, or pass it with --init-ply."
+            }
+            Self::ArchiveTooLarge => {
+                "If the archive is trusted, raise the limit with --max-archive-mb, or load it as a directory."
+            }
+            Self::TooManyFiles => {
+                "Remove files the dataset doesn't need, or raise the limit with --max-archive-files."
+            }
+            Self::ImageTooLarge => {
+                "Downscale the images, or raise the limit with --max-image-size."
+            }
+            Self::OutOfMemory => "Start from fewer splats, or lower the SH degree (--sh-degree).",
+            Self::SplatsFile => {
+                "Save the splats with Brush, or as safetensors with means, quats, scales, coeffs and opacities laid out the same way."
+            }
+        }
+    }
+}
+
+/// Why splats couldn't be loaded from a safetensors file.
+#[derive(Debug, Error)]
+pub enum SplatsFileError {
+    #[error("Invalid safetensors file: {0}")]
+    Safetensors(#[from] SafeTensorError),
+    #[error("Tensor {name} is {dtype:?}, only F32 tensors are supported")]
+    WrongDtype { name: &'static str, dtype: Dtype },
+    #[error("Tensor {name} has shape {shape:?}, expected {expected}")]
+    WrongShape {
+        name: &'static str,
+        shape: Vec<usize>,
+        expected: &'static str,
+    },
+    #[error("Tensor {name} has {count} splats, but means has {expected}")]
+    MismatchedCount {
+        name: &'static str,
+        count: usize,
+        expected: usize,
+    },
+}
+
+impl SplatsFileError {
+    /// What the user can do to fix the error.
+    pub fn suggestion(&self) -> Suggestion {
+        match self {
+            Self::Safetensors(_)
+            | Self::WrongDtype { .. }
+            | Self::WrongShape { .. }
+            | Self::MismatchedCount { .. } => Suggestion::SplatsFile,
+        }
+    }
+}
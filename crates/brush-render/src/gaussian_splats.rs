@@ -2,17 +2,21 @@ use crate::{
     RenderAux, SplatForward,
     bounding_box::BoundingBox,
     camera::Camera,
-    sh::{sh_coeffs_for_degree, sh_degree_from_coeffs},
+    error::SplatsFileError,
+    post_process::PostProcess,
+    sh::{SH_C0, rgb_to_sh, sh_coeffs_for_degree, sh_degree_from_coeffs, sh_rotation},
 };
 use ball_tree::BallTree;
 use burn::{
     config::Config,
     module::{Module, Param, ParamId},
     prelude::Backend,
-    tensor::{Tensor, TensorData, TensorPrimitive, activation::sigmoid},
+    tensor::{Int, Tensor, TensorData, TensorPrimitive, activation::sigmoid},
 };
-use glam::{Quat, Vec3};
+use glam::{Affine3A, Quat, Vec3};
 use rand::Rng;
+use safetensors::{Dtype, SafeTensorError, SafeTensors, tensor::TensorView};
+use std::{collections::HashMap, ops::Range};
 
 #[derive(Config)]
 pub struct RandomSplatsConfig {
@@ -41,6 +45,102 @@ pub fn inverse_sigmoid(x: f32) -> f32 {
     (x / (1.0 - x)).ln()
 }
 
+/// Rotate SH coefficients of shape [N, coeffs, 3] so colors follow a rotation of the scene.
+fn rotate_sh<B: Backend>(sh_coeffs: Tensor<B, 3>, rotation: Quat) -> Tensor<B, 3> {
+    let [n, coeffs, _] = sh_coeffs.dims();
+    let device = sh_coeffs.device();
+
+    let mut bands = vec![sh_coeffs.clone().slice([0..n, 0..1])];
+    for (l, matrix) in sh_rotation(sh_degree_from_coeffs(coeffs as u32), rotation)
+        .into_iter()
+        .enumerate()
+    {
+        let (start, size) = ((l + 1) * (l + 1), 2 * l + 3);
+        let matrix = Tensor::<B, 2>::from_data(TensorData::new(matrix, [size, size]), &device);
+        // Coefficients of each channel as rows, times the transposed rotation.
+        let band = sh_coeffs
+            .clone()
+            .slice([0..n, start..start + size])
+            .swap_dims(1, 2)
+            .reshape([n * 3, size])
+            .matmul(matrix.transpose())
+            .reshape([n, 3, size])
+            .swap_dims(1, 2);
+        bands.push(band);
+    }
+    Tensor::cat(bands, 1)
+}
+
+/// Values of the SH bases up to `degree` for each direction, as [N, coeffs]. Same as
+/// [`crate::sh::sh_basis`], on the GPU.
+fn sh_basis_tensor<B: Backend>(degree: u32, dirs: Tensor<B, 2>) -> Tensor<B, 2> {
+    let n = dirs.dims()[0];
+    let col = |i: usize| dirs.clone().slice([0..n, i..i + 1]);
+    let (x, y, z) = (col(0), col(1), col(2));
+    let mut basis = vec![Tensor::ones([n, 1], &dirs.device()) * SH_C0];
+
+    if degree >= 1 {
+        let c = 0.48860251190292;
+        basis.extend([y.clone() * -c, z.clone() * c, x.clone() * -c]);
+    }
+
+    if degree >= 2 {
+        let z2 = z.clone() * z.clone();
+        let fc1 = x.clone() * x.clone() - y.clone() * y.clone();
+        let fs1 = x.clone() * y.clone() * 2.0;
+        let sh6 = z2.clone() * 0.9461746957575601 - 0.3153915652525201;
+        let tmp0 = z.clone() * -1.092548430592079;
+        let tmp1 = 0.5462742152960395;
+        basis.extend([
+            fs1.clone() * tmp1,
+            tmp0.clone() * y.clone(),
+            sh6.clone(),
+            tmp0 * x.clone(),
+            fc1.clone() * tmp1,
+        ]);
+
+        if degree >= 3 {
+            let fc2 = x.clone() * fc1.clone() - y.clone() * fs1.clone();
+            let fs2 = x.clone() * fs1.clone() + y.clone() * fc1.clone();
+            let sh12 = z.clone() * (z2.clone() * 1.865881662950577 - 1.119528997770346);
+            let tmp0 = z2.clone() * -2.285228997322329 + 0.4570457994644658;
+            let tmp1 = z.clone() * 1.445305721320277;
+            let tmp2 = -0.5900435899266435;
+            basis.extend([
+                fs2.clone() * tmp2,
+                tmp1.clone() * fs1.clone(),
+                tmp0.clone() * y.clone(),
+                sh12.clone(),
+                tmp0 * x.clone(),
+                tmp1 * fc1.clone(),
+                fc2.clone() * tmp2,
+            ]);
+
+            if degree >= 4 {
+                let tmp0 = z.clone() * (z2.clone() * -4.683325804901025 + 2.007139630671868);
+                let tmp1 = z2 * 3.31161143515146 - 0.47308734787878;
+                let tmp2 = z.clone() * -1.770130769779931;
+                let tmp3 = 0.6258357354491763;
+                let fc3 = x.clone() * fc2.clone() - y.clone() * fs2.clone();
+                let fs3 = x.clone() * fs2.clone() + y.clone() * fc2.clone();
+                basis.extend([
+                    fs3 * tmp3,
+                    tmp2.clone() * fs2,
+                    tmp1.clone() * fs1,
+                    tmp0.clone() * y,
+                    sh12 * 1.984313483298443 - sh6 * 1.006230589874905,
+                    tmp0 * x,
+                    tmp1 * fc1,
+                    tmp2 * fc2,
+                    fc3 * tmp3,
+                ]);
+            }
+        }
+    }
+
+    Tensor::cat(basis, 1)
+}
+
 impl<B: Backend> Splats<B> {
     pub fn from_random_config(
         config: &RandomSplatsConfig,
@@ -188,6 +288,267 @@ impl<B: Backend> Splats<B> {
         self
     }
 
+    /// Create splats from plain colors, eg. for procedural content.
+    ///
+    /// Scales are the size of each splat along its axes. Without scales, splats are sized by
+    /// the distance to their neighbours. Opacities default to fully opaque.
+    pub fn from_colors(
+        means: &[Vec3],
+        colors: &[Vec3],
+        scales: Option<&[Vec3]>,
+        opacities: Option<&[f32]>,
+        device: &B::Device,
+    ) -> Self {
+        assert_eq!(means.len(), colors.len(), "Need a color for each splat");
+
+        let sh_coeffs: Vec<f32> = colors
+            .iter()
+            .flat_map(|&c| rgb_to_sh(c).to_array())
+            .collect();
+        let log_scales: Option<Vec<Vec3>> = scales.map(|s| {
+            s.iter()
+                .map(|s| {
+                    let s = s.max(Vec3::splat(1e-12));
+                    Vec3::new(s.x.ln(), s.y.ln(), s.z.ln())
+                })
+                .collect()
+        });
+        let raw_opacities: Vec<f32> = match opacities {
+            Some(opacities) => opacities
+                .iter()
+                .map(|&o| inverse_sigmoid(o.clamp(1e-4, 1.0 - 1e-4)))
+                .collect(),
+            None => vec![inverse_sigmoid(1.0 - 1e-4); means.len()],
+        };
+        let rotations = vec![Quat::IDENTITY; means.len()];
+
+        Self::from_raw(
+            means,
+            Some(&rotations),
+            log_scales.as_deref(),
+            Some(&sh_coeffs),
+            Some(&raw_opacities),
+            device,
+        )
+    }
+
+    /// Add the splats of `other` after these splats. If the SH degrees differ, the result
+    /// has the highest of the two.
+    pub fn append(self, other: Self) -> Self {
+        let sh_degree = self.sh_degree().max(other.sh_degree());
+        let (a, b) = (
+            self.with_sh_degree(sh_degree),
+            other.with_sh_degree(sh_degree),
+        );
+        Self::from_tensor_data(
+            Tensor::cat(vec![a.means.val(), b.means.val()], 0),
+            Tensor::cat(vec![a.rotation.val(), b.rotation.val()], 0),
+            Tensor::cat(vec![a.log_scales.val(), b.log_scales.val()], 0),
+            Tensor::cat(vec![a.sh_coeffs.val(), b.sh_coeffs.val()], 0),
+            Tensor::cat(vec![a.raw_opacity.val(), b.raw_opacity.val()], 0),
+        )
+    }
+
+    /// Remove the splats at the indices in `range`.
+    pub fn remove_range(self, range: Range<usize>) -> Self {
+        let n = self.num_splats() as usize;
+        assert!(
+            range.start <= range.end && range.end <= n,
+            "Range {range:?} out of bounds for {n} splats"
+        );
+
+        let device = self.device();
+        let keep: Vec<i32> = (0..range.start)
+            .chain(range.end..n)
+            .map(|i| i as i32)
+            .collect();
+        let len = keep.len();
+        let keep = Tensor::<B, 1, Int>::from_data(TensorData::new(keep, [len]), &device);
+
+        Self::from_tensor_data(
+            self.means.val().select(0, keep.clone()),
+            self.rotation.val().select(0, keep.clone()),
+            self.log_scales.val().select(0, keep.clone()),
+            self.sh_coeffs.val().select(0, keep.clone()),
+            self.raw_opacity.val().select(0, keep),
+        )
+    }
+
+    /// Keep only the `count` splats that contribute most to the image, going by their opacity
+    /// and size.
+    pub fn keep_most_important(self, count: u32) -> Self {
+        let n = self.num_splats();
+        if count >= n {
+            return self;
+        }
+
+        // Splats end up as flat discs, so weigh them by their area rather than volume.
+        let area = self.scales().powf_scalar(2.0).sum_dim(1).squeeze(1);
+        let keep = (self.opacities() * area)
+            .argsort_descending(0)
+            .slice([0..count as usize]);
+
+        Self::from_tensor_data(
+            self.means.val().select(0, keep.clone()),
+            self.rotation.val().select(0, keep.clone()),
+            self.log_scales.val().select(0, keep.clone()),
+            self.sh_coeffs.val().select(0, keep.clone()),
+            self.raw_opacity.val().select(0, keep),
+        )
+    }
+
+    /// Keep only the splats at `indices`, in that order.
+    pub fn select(self, indices: Tensor<B, 1, Int>) -> Self {
+        Self::from_tensor_data(
+            self.means.val().select(0, indices.clone()),
+            self.rotation.val().select(0, indices.clone()),
+            self.log_scales.val().select(0, indices.clone()),
+            self.sh_coeffs.val().select(0, indices.clone()),
+            self.raw_opacity.val().select(0, indices),
+        )
+    }
+
+    /// Weight of each splat for blending it with splats of neighboring regions. The weight
+    /// is 1 up to `fade / 2` inside of the box from `min` to `max`, and falls off linearly
+    /// to 0 at `fade / 2` outside of it. Bounds can be infinite, to not fade on that side.
+    pub fn blend_weights(&self, min: Vec3, max: Vec3, fade: Vec3) -> Tensor<B, 1> {
+        let device = self.device();
+        let row = |v: Vec3| Tensor::<B, 1>::from_floats(v.to_array(), &device).unsqueeze::<2>();
+        let means = self.means.val();
+        let inside = (means.clone() - row(min)).min_pair(row(max) - means);
+        let weights = (inside / row(fade.max(Vec3::splat(1e-6))) + 0.5).clamp(0.0, 1.0);
+        // Fading along each axis multiplies, so corners of the box blend four ways.
+        weights.prod_dim(1).squeeze(1)
+    }
+
+    /// Scale the opacity of each splat by `weights`.
+    pub fn with_opacity_scaled(self, weights: Tensor<B, 1>) -> Self {
+        let opacity = (self.opacities() * weights).clamp(1e-6, 1.0 - 1e-6);
+        let raw_opacity = (opacity.clone() / (opacity.neg() + 1.0)).log();
+        Self::from_tensor_data(
+            self.means.val(),
+            self.rotation.val(),
+            self.log_scales.val(),
+            self.sh_coeffs.val(),
+            raw_opacity,
+        )
+    }
+
+    /// Move the splats by `transform`, on the GPU.
+    ///
+    /// Means, rotations, scales and the view dependent colors are all transformed. Only
+    /// uniform scaling can be represented exactly, a non-uniform scale is averaged.
+    pub fn transform(self, transform: Affine3A) -> Self {
+        let device = self.device();
+        let (scale, rotation, _) = transform.to_scale_rotation_translation();
+
+        // Splats are rows, so multiply by the transpose. Reading the columns as rows gives that.
+        let matrix =
+            Tensor::<B, 1>::from_floats(transform.matrix3.to_cols_array(), &device).reshape([3, 3]);
+        let translation =
+            Tensor::<B, 1>::from_floats(transform.translation.to_array(), &device).unsqueeze::<2>();
+        let means = self.means.val().matmul(matrix) + translation;
+
+        // Left multiply each (w, x, y, z) quaternion by the rotation, as a linear map.
+        let [x, y, z, w] = rotation.to_array();
+        #[rustfmt::skip]
+        let quat_mul = [
+            w, -x, -y, -z,
+            x,  w, -z,  y,
+            y,  z,  w, -x,
+            z, -y,  x,  w,
+        ];
+        let quat_mul = Tensor::<B, 1>::from_floats(quat_mul, &device)
+            .reshape([4, 4])
+            .transpose();
+        let rotations = self.rotation.val().matmul(quat_mul);
+
+        let log_scale = (scale.x * scale.y * scale.z).abs().ln() / 3.0;
+        let log_scales = self.log_scales.val() + log_scale;
+
+        let sh_coeffs = rotate_sh(self.sh_coeffs.val(), rotation);
+
+        Self::from_tensor_data(
+            means,
+            rotations,
+            log_scales,
+            sh_coeffs,
+            self.raw_opacity.val(),
+        )
+    }
+
+    /// Convert splats trained on linear colors to sRGB, as most viewers expect.
+    ///
+    /// The base color is converted exactly. The sRGB curve isn't linear, so the view dependent
+    /// bands are scaled by its slope at the base color, which is close for small variations.
+    pub fn encode_srgb(self) -> Self {
+        let sh_coeffs = self.sh_coeffs.val();
+        let [n, coeffs, _] = sh_coeffs.dims();
+
+        let color = (sh_coeffs.clone().slice([0..n, 0..1, 0..3]) * SH_C0 + 0.5).clamp_min(0.0);
+        let is_low = color.clone().lower_equal_elem(0.0031308);
+        let srgb = (color.clone().powf_scalar(1.0 / 2.4) * 1.055 - 0.055)
+            .mask_where(is_low.clone(), color.clone() * 12.92);
+        let slope = (color.clamp_min(0.0031308).powf_scalar(1.0 / 2.4 - 1.0) * (1.055 / 2.4))
+            .mask_fill(is_low, 12.92);
+
+        let mut bands = vec![(srgb - 0.5) / SH_C0];
+        if coeffs > 1 {
+            bands.push(sh_coeffs.slice([0..n, 1..coeffs, 0..3]) * slope);
+        }
+
+        Self::from_tensor_data(
+            self.means.val(),
+            self.rotation.val(),
+            self.log_scales.val(),
+            Tensor::cat(bands, 1),
+            self.raw_opacity.val(),
+        )
+    }
+
+    /// Fold the view dependent colors into the base color, for viewers that only show SH
+    /// degree 0.
+    ///
+    /// Each splat is baked as seen along its average direction from `view_positions`, eg. the
+    /// training cameras.
+    pub fn bake_to_dc(self, view_positions: &[Vec3]) -> Self {
+        // Plenty to find the average direction, without a pass per view of large datasets.
+        const MAX_VIEWS: usize = 64;
+
+        let sh_coeffs = self.sh_coeffs.val();
+        let [n, coeffs, _] = sh_coeffs.dims();
+        if coeffs == 1 || view_positions.is_empty() {
+            return self.with_sh_degree(0);
+        }
+
+        let device = self.device();
+        let means = self.means.val();
+        let step = view_positions.len().div_ceil(MAX_VIEWS);
+        let dirs = view_positions
+            .iter()
+            .step_by(step)
+            .map(|pos| {
+                let pos = Tensor::<B, 1>::from_floats(pos.to_array(), &device).unsqueeze();
+                norm_vec(means.clone() - pos)
+            })
+            .reduce(|a, b| a + b)
+            .expect("Views aren't empty");
+
+        let basis = sh_basis_tensor(self.sh_degree(), norm_vec(dirs))
+            .slice([0..n, 1..coeffs])
+            .unsqueeze_dim(2);
+        let view_color = (sh_coeffs.clone().slice([0..n, 1..coeffs]) * basis).sum_dim(1);
+        let dc = sh_coeffs.slice([0..n, 0..1]) + view_color / SH_C0;
+
+        Self::from_tensor_data(
+            means,
+            self.rotation.val(),
+            self.log_scales.val(),
+            dc,
+            self.raw_opacity.val(),
+        )
+    }
+
     pub fn from_tensor_data(
         means: Tensor<B, 2>,
         rotation: Tensor<B, 2>,
@@ -216,6 +577,68 @@ impl<B: Backend> Splats<B> {
         self.log_scales.val().exp()
     }
 
+    /// Normal of each splat, along its shortest axis. Splats are flat discs after training
+    /// for most surfaces, so this approximates the surface normal. The sign is arbitrary.
+    pub fn normals(&self) -> Tensor<B, 2> {
+        let n = self.num_splats() as usize;
+        let quats = norm_vec(self.rotation.val());
+        let [w, x, y, z] = [0, 1, 2, 3].map(|i| quats.clone().slice([0..n, i..i + 1]));
+
+        // Columns of the rotation matrix are the axes of the splat.
+        let axes = [
+            [
+                (y.clone().powi_scalar(2) + z.clone().powi_scalar(2)) * -2.0 + 1.0,
+                (x.clone() * y.clone() + w.clone() * z.clone()) * 2.0,
+                (x.clone() * z.clone() - w.clone() * y.clone()) * 2.0,
+            ],
+            [
+                (x.clone() * y.clone() - w.clone() * z.clone()) * 2.0,
+                (x.clone().powi_scalar(2) + z.clone().powi_scalar(2)) * -2.0 + 1.0,
+                (y.clone() * z.clone() + w.clone() * x.clone()) * 2.0,
+            ],
+            [
+                (x.clone() * z.clone() + w.clone() * y.clone()) * 2.0,
+                (y.clone() * z.clone() - w * x.clone()) * 2.0,
+                (x.powi_scalar(2) + y.powi_scalar(2)) * -2.0 + 1.0,
+            ],
+        ]
+        .map(|axis| Tensor::cat(axis.to_vec(), 1));
+        let axes = Tensor::stack::<3>(axes.to_vec(), 1);
+
+        let shortest = self
+            .log_scales
+            .val()
+            .argmin(1)
+            .reshape([n, 1, 1])
+            .repeat_dim(2, 3);
+        axes.gather(1, shortest).reshape([n, 3])
+    }
+
+    /// Copy of the splats with a fixed RGB color per splat, of shape [N, 3].
+    fn with_colors(&self, colors: Tensor<B, 2>) -> Self {
+        let sh_coeffs = ((colors - 0.5) / SH_C0).unsqueeze_dim(1);
+        Self::from_tensor_data(
+            self.means.val(),
+            self.rotation.val(),
+            self.log_scales.val(),
+            sh_coeffs,
+            self.raw_opacity.val(),
+        )
+    }
+
+    /// Normals flipped to face towards `camera`, so the splats are lit from the front.
+    pub fn normals_facing(&self, camera: &Camera) -> Tensor<B, 2> {
+        let normals = self.normals();
+        let to_camera = Tensor::<B, 1>::from_floats(camera.position.to_array(), &self.device())
+            .unsqueeze()
+            - self.means.val();
+        let facing = (normals.clone() * to_camera)
+            .sum_dim(1)
+            .greater_equal_elem(0.0)
+            .float();
+        normals * (facing * 2.0 - 1.0)
+    }
+
     pub fn num_splats(&self) -> u32 {
         self.means.dims()[0] as u32
     }
@@ -237,6 +660,115 @@ impl<B: Backend> Splats<B> {
     pub fn device(&self) -> B::Device {
         self.means.device()
     }
+
+    /// Serialize the raw parameters to safetensors, eg. to load them in PyTorch.
+    ///
+    /// Values are stored exactly as they are optimized: `means` [N, 3], `quats` [N, 4] as
+    /// w, x, y, z, `scales` [N, 3] as log scales, `coeffs` [N, C, 3] with the SH
+    /// coefficients per color channel, and `opacities` [N] as logits.
+    pub async fn to_safetensors(&self) -> Result<Vec<u8>, SafeTensorError> {
+        async fn bytes<B: Backend, const D: usize>(tensor: Tensor<B, D>) -> (Vec<usize>, Vec<u8>) {
+            let data = tensor.into_data_async().await.convert::<f32>();
+            (data.shape.clone(), data.as_bytes().to_vec())
+        }
+
+        let tensors = [
+            ("means", bytes(self.means.val()).await),
+            ("quats", bytes(self.rotation.val()).await),
+            ("scales", bytes(self.log_scales.val()).await),
+            ("coeffs", bytes(self.sh_coeffs.val()).await),
+            ("opacities", bytes(self.raw_opacity.val()).await),
+        ];
+        let views = tensors
+            .iter()
+            .map(|(name, (shape, data))| {
+                Ok((*name, TensorView::new(Dtype::F32, shape.clone(), data)?))
+            })
+            .collect::<Result<Vec<_>, SafeTensorError>>()?;
+
+        let metadata = HashMap::from([
+            ("format".to_owned(), "brush".to_owned()),
+            ("sh_degree".to_owned(), self.sh_degree().to_string()),
+            ("quats".to_owned(), "wxyz".to_owned()),
+            ("scales".to_owned(), "log".to_owned()),
+            ("opacities".to_owned(), "logit".to_owned()),
+        ]);
+        safetensors::serialize(views, &Some(metadata))
+    }
+
+    /// Load splats saved with [`Self::to_safetensors`], or tensors in the same layout.
+    pub fn from_safetensors(data: &[u8], device: &B::Device) -> Result<Self, SplatsFileError> {
+        let tensors = SafeTensors::deserialize(data)?;
+
+        // Check a tensor against its layout, where `None` is a dimension of any size.
+        fn view<'a>(
+            tensors: &SafeTensors<'a>,
+            name: &'static str,
+            layout: &[Option<usize>],
+            expected: &'static str,
+        ) -> Result<TensorView<'a>, SplatsFileError> {
+            let view = tensors.tensor(name)?;
+            if view.dtype() != Dtype::F32 {
+                return Err(SplatsFileError::WrongDtype {
+                    name,
+                    dtype: view.dtype(),
+                });
+            }
+            let shape = view.shape();
+            let fits = shape.len() == layout.len()
+                && shape
+                    .iter()
+                    .zip(layout)
+                    .all(|(&dim, expected)| expected.is_none_or(|e| dim == e));
+            if !fits {
+                return Err(SplatsFileError::WrongShape {
+                    name,
+                    shape: shape.to_vec(),
+                    expected,
+                });
+            }
+            Ok(view)
+        }
+
+        let means = view(&tensors, "means", &[None, Some(3)], "[N, 3]")?;
+        let quats = view(&tensors, "quats", &[None, Some(4)], "[N, 4]")?;
+        let scales = view(&tensors, "scales", &[None, Some(3)], "[N, 3]")?;
+        let coeffs = view(&tensors, "coeffs", &[None, None, Some(3)], "[N, C, 3]")?;
+        let opacities = view(&tensors, "opacities", &[None], "[N]")?;
+
+        let count = means.shape()[0];
+        for (name, view) in [
+            ("quats", &quats),
+            ("scales", &scales),
+            ("coeffs", &coeffs),
+            ("opacities", &opacities),
+        ] {
+            if view.shape()[0] != count {
+                return Err(SplatsFileError::MismatchedCount {
+                    name,
+                    count: view.shape()[0],
+                    expected: count,
+                });
+            }
+        }
+
+        fn load<B: Backend, const D: usize>(view: &TensorView, device: &B::Device) -> Tensor<B, D> {
+            let values: Vec<f32> = view
+                .data()
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes(b.try_into().expect("Chunks of 4")))
+                .collect();
+            Tensor::from_data(TensorData::new(values, view.shape().to_vec()), device)
+        }
+
+        Ok(Self::from_tensor_data(
+            load(&means, device),
+            load(&quats, device),
+            load(&scales, device),
+            load(&coeffs, device),
+            load(&opacities, device),
+        ))
+    }
 }
 
 impl<B: Backend + SplatForward<B>> Splats<B> {
@@ -265,4 +797,75 @@ impl<B: Backend + SplatForward<B>> Splats<B> {
         }
         (img, aux)
     }
+
+    /// Render the normals of the splats, see [`Splats::normals`]. World space normals are
+    /// mapped from [-1, 1] to [0, 1] colors.
+    pub fn render_normals(
+        &self,
+        camera: &Camera,
+        img_size: glam::UVec2,
+        float_buffer: bool,
+    ) -> (Tensor<B, 3>, RenderAux<B>) {
+        let colors = self.normals_facing(camera) * 0.5 + 0.5;
+        self.with_colors(colors)
+            .render(camera, img_size, float_buffer)
+    }
+
+    /// Render the splats as grey clay, lit by a directional light. This makes it easier to
+    /// judge the geometry without the colors hiding it.
+    ///
+    /// `light_dir` points towards the light, in world space.
+    pub fn render_shaded(
+        &self,
+        camera: &Camera,
+        img_size: glam::UVec2,
+        light_dir: Vec3,
+        float_buffer: bool,
+    ) -> (Tensor<B, 3>, RenderAux<B>) {
+        const ALBEDO: f32 = 0.8;
+        const AMBIENT: f32 = 0.15;
+
+        let device = self.device();
+        let light =
+            Tensor::<B, 1>::from_floats(light_dir.normalize().to_array(), &device).reshape([3, 1]);
+        let lambert = self.normals_facing(camera).matmul(light).clamp_min(0.0);
+        let shade = (lambert * (1.0 - AMBIENT) + AMBIENT) * ALBEDO;
+        self.with_colors(shade.repeat_dim(1, 3))
+            .render(camera, img_size, float_buffer)
+    }
+
+    /// Render the depth of the splats along the view direction, in each color channel.
+    ///
+    /// Depths are blended like colors, so divide by alpha for the average depth of a pixel.
+    pub fn render_depth(
+        &self,
+        camera: &Camera,
+        img_size: glam::UVec2,
+    ) -> (Tensor<B, 3>, RenderAux<B>) {
+        let device = self.device();
+        let forward = Tensor::<B, 1>::from_floats((camera.rotation * Vec3::Z).to_array(), &device)
+            .reshape([3, 1]);
+        let origin = Tensor::<B, 1>::from_floats(camera.position.to_array(), &device).unsqueeze();
+        let depth = (self.means.val() - origin).matmul(forward).clamp_min(0.0);
+        self.with_colors(depth.repeat_dim(1, 3))
+            .render(camera, img_size, true)
+    }
+
+    /// Render the splats to a float image as the viewer shows them, with `post_process`
+    /// applied. Ambient occlusion renders the depth too.
+    pub fn render_post_processed(
+        &self,
+        camera: &Camera,
+        img_size: glam::UVec2,
+        post_process: &PostProcess,
+    ) -> Tensor<B, 3> {
+        let (img, _) = self.render(camera, img_size, true);
+        let img = if post_process.ambient_occlusion > 0.0 {
+            let (depth, _) = self.render_depth(camera, img_size);
+            post_process.occlude(img, depth)
+        } else {
+            img
+        };
+        post_process.apply(img)
+    }
 }
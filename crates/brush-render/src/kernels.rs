@@ -2,6 +2,6 @@ use super::shaders::{map_gaussian_to_intersects, project_forward, project_visibl
 use brush_kernel::kernel_source_gen;
 
 kernel_source_gen!(ProjectSplats {}, project_forward);
-kernel_source_gen!(ProjectVisible {}, project_visible);
+kernel_source_gen!(ProjectVisible { ray_eval }, project_visible);
 kernel_source_gen!(MapGaussiansToIntersect {}, map_gaussian_to_intersects);
-kernel_source_gen!(Rasterize { bwd_info }, rasterize);
+kernel_source_gen!(Rasterize { bwd_info, ray_eval }, rasterize);
@@ -23,7 +23,9 @@ mod tests;
 
 pub mod bounding_box;
 pub mod camera;
+pub mod error;
 pub mod gaussian_splats;
+pub mod post_process;
 pub mod render;
 
 #[derive(Debug, Clone)]
@@ -231,3 +233,11 @@ pub async fn burn_init_setup() -> WgpuDevice {
         .await;
     WgpuDevice::DefaultDevice
 }
+
+/// Like [`burn_init_setup`], but also returns the limits of the adapter that was picked.
+pub async fn burn_init_setup_with_limits() -> (WgpuDevice, wgpu::Limits) {
+    let setup =
+        burn_wgpu::init_setup_async::<AutoGraphicsApi>(&WgpuDevice::DefaultDevice, burn_options())
+            .await;
+    (WgpuDevice::DefaultDevice, setup.adapter.limits())
+}
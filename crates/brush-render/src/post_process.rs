@@ -0,0 +1,219 @@
+//! Effects applied to rendered images for presentation.
+
+use burn::tensor::{
+    Int, Tensor,
+    backend::Backend,
+    module::{conv2d, interpolate},
+    ops::{ConvOptions, InterpolateMode, InterpolateOptions},
+};
+use serde::{Deserialize, Serialize};
+
+/// Bloom is blurred at a lower resolution, which makes wide glows cheap.
+const BLOOM_DOWNSCALE: usize = 8;
+/// Radius of the bloom blur, in downscaled pixels.
+const BLOOM_RADIUS: usize = 6;
+/// Neighbourhood the depth is compared to for ambient occlusion, in pixels.
+const AO_RADIUS: usize = 10;
+/// How far behind its neighbourhood a pixel has to be to be fully occluded, relative to
+/// its depth.
+const AO_DEPTH_RANGE: f32 = 0.05;
+
+/// A small chain of post-processing effects.
+///
+/// Effects are applied in a fixed order: ambient occlusion, exposure, bloom, vignette, the
+/// tonemap, and finally the sRGB encoding of linear colors.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PostProcess {
+    /// Exposure adjustment in stops.
+    pub exposure: f32,
+    /// Strength of the glow around bright areas, 0 disables bloom.
+    pub bloom: f32,
+    /// Brightness above which areas start to glow.
+    pub bloom_threshold: f32,
+    /// How much the corners are darkened, 0 disables the vignette.
+    pub vignette: f32,
+    /// Map colors with the ACES filmic curve, instead of clipping them.
+    pub tonemap: bool,
+    /// How much creases and corners are darkened, 0 disables ambient occlusion. This needs
+    /// a depth render, see [`PostProcess::occlude`].
+    pub ambient_occlusion: f32,
+    /// The image holds linear colors, eg. of splats trained in linear color, which are
+    /// encoded as sRGB to be shown. This follows the splats, so isn't saved.
+    #[serde(skip)]
+    pub linear_colors: bool,
+}
+
+impl Default for PostProcess {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            bloom: 0.0,
+            bloom_threshold: 0.8,
+            vignette: 0.0,
+            tonemap: false,
+            ambient_occlusion: 0.0,
+            linear_colors: false,
+        }
+    }
+}
+
+impl PostProcess {
+    /// Whether any effect changes the image.
+    pub fn is_enabled(&self) -> bool {
+        self.exposure != 0.0
+            || self.bloom > 0.0
+            || self.vignette > 0.0
+            || self.tonemap
+            || self.ambient_occlusion > 0.0
+            || self.linear_colors
+    }
+
+    /// Darken the parts of a float RGBA image of shape [H, W, 4] that are behind their
+    /// surroundings, going by `depth`, a depth render of the same view.
+    ///
+    /// This is a screen space approximation, that only sees what's on screen. Apply it before
+    /// [`PostProcess::apply`].
+    pub fn occlude<B: Backend>(&self, img: Tensor<B, 3>, depth: Tensor<B, 3>) -> Tensor<B, 3> {
+        if self.ambient_occlusion <= 0.0 {
+            return img;
+        }
+        let [h, w, _] = img.dims();
+        let alpha = img.clone().slice([0..h, 0..w, 3..4]);
+        let rgb = img.slice([0..h, 0..w, 0..3]);
+        let occlusion = occlusion(depth) * self.ambient_occlusion.min(1.0);
+        Tensor::cat(vec![rgb * (occlusion * -1.0 + 1.0), alpha], 2)
+    }
+
+    /// Apply the effects to a float RGBA image of shape [H, W, 4]. Alpha is left as is.
+    pub fn apply<B: Backend>(&self, img: Tensor<B, 3>) -> Tensor<B, 3> {
+        if !self.is_enabled() {
+            return img;
+        }
+
+        let [h, w, _] = img.dims();
+        let device = img.device();
+        let alpha = img.clone().slice([0..h, 0..w, 3..4]);
+        let mut rgb = img.slice([0..h, 0..w, 0..3]);
+
+        if self.exposure != 0.0 {
+            rgb = rgb * 2.0f32.powf(self.exposure);
+        }
+        if self.bloom > 0.0 {
+            rgb = rgb.clone() + bloom(rgb, self.bloom_threshold) * self.bloom;
+        }
+        if self.vignette > 0.0 {
+            rgb = rgb * vignette_mask::<B>(h, w, self.vignette, &device);
+        }
+        if self.tonemap {
+            rgb = aces(rgb);
+        }
+        if self.linear_colors {
+            rgb = encode_srgb(rgb);
+        }
+
+        Tensor::cat(vec![rgb, alpha], 2)
+    }
+}
+
+/// Encode linear colors as sRGB, clipped to the displayable range.
+pub fn encode_srgb<B: Backend>(img: Tensor<B, 3>) -> Tensor<B, 3> {
+    let img = img.clamp(0.0, 1.0);
+    let low = img.clone().lower_equal_elem(0.0031308);
+    (img.clone().powf_scalar(1.0 / 2.4) * 1.055 - 0.055).mask_where(low, img * 12.92)
+}
+
+// Fit of the ACES filmic curve, see https://knarkowicz.wordpress.com/2016/01/06/aces-filmic-tone-mapping-curve/
+fn aces<B: Backend>(x: Tensor<B, 3>) -> Tensor<B, 3> {
+    let num = x.clone() * (x.clone() * 2.51 + 0.03);
+    let denom = x.clone() * (x * 2.43 + 0.59) + 0.14;
+    (num / denom).clamp(0.0, 1.0)
+}
+
+fn gaussian<B: Backend>(radius: usize, device: &B::Device) -> Tensor<B, 1> {
+    let sigma = radius as f32 / 2.0;
+    let vals: Vec<_> = (0..2 * radius + 1)
+        .map(|x| f32::exp(-(x as f32 - radius as f32).powf(2.0) / (2.0 * sigma.powf(2.0))))
+        .collect();
+    let gauss = Tensor::from_floats(vals.as_slice(), device);
+    gauss.clone() / gauss.sum()
+}
+
+// Separable blur of a [N, C, H, W] image, each channel on its own.
+fn blur<B: Backend>(img: Tensor<B, 4>, radius: usize) -> Tensor<B, 4> {
+    let [_, channels, _, _] = img.dims();
+    let window = 2 * radius + 1;
+    let kernel_v = gaussian::<B>(radius, &img.device())
+        .reshape([window, 1])
+        .unsqueeze::<4>()
+        .repeat_dim(0, channels);
+    let kernel_h = kernel_v.clone().reshape([channels, 1, 1, window]);
+
+    let options_v = ConvOptions::new([1, 1], [radius, 0], [1, 1], channels);
+    let options_h = ConvOptions::new([1, 1], [0, radius], [1, 1], channels);
+    conv2d(
+        conv2d(img, kernel_v, None, options_v),
+        kernel_h,
+        None,
+        options_h,
+    )
+}
+
+fn bloom<B: Backend>(rgb: Tensor<B, 3>, threshold: f32) -> Tensor<B, 3> {
+    let [h, w, _] = rgb.dims();
+    let bright = (rgb - threshold).clamp_min(0.0);
+
+    // Images are [H, W, C], need them as [N, C, H, W].
+    let bright = bright.permute([2, 0, 1]).unsqueeze::<4>();
+    let small = [(h / BLOOM_DOWNSCALE).max(1), (w / BLOOM_DOWNSCALE).max(1)];
+    let bilinear = || InterpolateOptions::new(InterpolateMode::Bilinear);
+    let glow = blur(interpolate(bright, small, bilinear()), BLOOM_RADIUS);
+    let glow = interpolate(glow, [h, w], bilinear());
+
+    let glow: Tensor<B, 3> = glow.squeeze(0);
+    glow.permute([1, 2, 0])
+}
+
+// How occluded each pixel is, from 0 to 1 as a [H, W, 1] mask, by comparing its depth to the
+// average depth around it. See "Image Enhancement by Unsharp Masking the Depth Buffer",
+// Luft et al. 2006.
+fn occlusion<B: Backend>(depth: Tensor<B, 3>) -> Tensor<B, 3> {
+    let [h, w, _] = depth.dims();
+    // Depths are blended like colors, so are weighted by alpha already.
+    let weighted = depth.clone().slice([0..h, 0..w, 0..1]);
+    let alpha = depth.slice([0..h, 0..w, 3..4]);
+
+    // Images are [H, W, C], need them as [N, C, H, W].
+    let to_nchw = |t: Tensor<B, 3>| t.permute([2, 0, 1]).unsqueeze::<4>();
+    let blurred = blur(
+        to_nchw(Tensor::cat(vec![weighted.clone(), alpha.clone()], 2)),
+        AO_RADIUS,
+    );
+    let blurred: Tensor<B, 3> = blurred.squeeze::<3>(0).permute([1, 2, 0]);
+    let around = blurred.clone().slice([0..h, 0..w, 0..1])
+        / blurred.slice([0..h, 0..w, 1..2]).clamp_min(1e-4);
+    let depth = weighted / alpha.clone().clamp_min(1e-4);
+
+    // Only pixels behind their surroundings are occluded, empty pixels never are.
+    let behind = (depth.clone() - around) / (depth.clamp_min(1e-4) * AO_DEPTH_RANGE);
+    behind.clamp(0.0, 1.0) * alpha.clamp(0.0, 1.0)
+}
+
+// Darkens towards the corners, as a [H, W, 1] mask.
+fn vignette_mask<B: Backend>(
+    h: usize,
+    w: usize,
+    strength: f32,
+    device: &B::Device,
+) -> Tensor<B, 3> {
+    // Coordinates from -1 to 1 across the image.
+    let coords = |n: usize| {
+        (Tensor::<B, 1, Int>::arange(0..n as i64, device).float() + 0.5) * (2.0 / n as f32) - 1.0
+    };
+    let dy = coords(h).reshape([h, 1, 1]);
+    let dx = coords(w).reshape([1, w, 1]);
+
+    // Squared distance to the center, 1 in the corners.
+    let dist_sq = (dx.powi_scalar(2) + dy.powi_scalar(2)) * 0.5;
+    (dist_sq * -strength + 1.0).clamp(0.0, 1.0)
+}
@@ -107,6 +107,9 @@ pub(crate) fn render_forward<BT: BoolElement>(
         tile_bounds: tile_bounds.into(),
         sh_degree,
         total_splats: total_splats as u32,
+        clip_plane_0: camera.clip_planes[0].into(),
+        clip_plane_1: camera.clip_planes[1].into(),
+        clip_plane_2: camera.clip_planes[2].into(),
         // Nb: Bit of a hack as these aren't _really_ uniforms but are written to by the shaders.
         num_visible: 0,
         num_intersections: 0,
@@ -164,6 +167,14 @@ pub(crate) fn render_forward<BT: BoolElement>(
     let projected_splats =
         create_tensor::<2, _>([total_splats, projected_size], device, client, DType::F32);
 
+    // The backward pass differentiates the projected splats, so exact ray evaluation
+    // is only used when rendering without gradients.
+    let ray_eval = camera.exact_projection && !bwd_info;
+    let ray_splats = ray_eval.then(|| {
+        let ray_size = size_of::<shaders::helpers::RaySplat>() / size_of::<f32>();
+        create_tensor::<2, WgpuRuntime>([total_splats, ray_size], device, client, DType::F32)
+    });
+
     let max_intersects = max_intersections(img_size, total_splats as u32);
     // 1 extra length to make this an exclusive sum.
     let tiles_hit_per_splat = BBase::<BT>::int_zeros([total_splats + 1].into(), device);
@@ -173,25 +184,29 @@ pub(crate) fn render_forward<BT: BoolElement>(
     // Create a buffer to determine how many threads to dispatch for all visible splats.
     let num_vis_wg = create_dispatch_buffer(num_visible.clone(), [shaders::helpers::MAIN_WG, 1, 1]);
 
+    let mut project_bindings = Bindings::new().with_buffers(vec![
+        uniforms_buffer.clone().handle.binding(),
+        means.handle.binding(),
+        log_scales.handle.binding(),
+        quats.handle.binding(),
+        sh_coeffs.handle.binding(),
+        opacities.handle.binding(),
+        global_from_compact_gid.handle.clone().binding(),
+        projected_splats.handle.clone().binding(),
+        tiles_hit_per_splat.handle.clone().binding(),
+        isect_info.handle.clone().binding(),
+    ]);
+    if let Some(ray_splats) = &ray_splats {
+        project_bindings = project_bindings.with_buffers(vec![ray_splats.handle.clone().binding()]);
+    }
+
     tracing::trace_span!("ProjectVisible", sync_burn = true).in_scope(||
         // SAFETY: Kernel has to contain no OOB indexing.
         unsafe {
         client.execute_unchecked(
-            ProjectVisible::task(),
+            ProjectVisible::task(ray_eval),
             CubeCount::Dynamic(num_vis_wg.clone().handle.binding()),
-            Bindings::new().with_buffers(
-            vec![
-                uniforms_buffer.clone().handle.binding(),
-                means.handle.binding(),
-                log_scales.handle.binding(),
-                quats.handle.binding(),
-                sh_coeffs.handle.binding(),
-                opacities.handle.binding(),
-                global_from_compact_gid.handle.clone().binding(),
-                projected_splats.handle.clone().binding(),
-                tiles_hit_per_splat.handle.clone().binding(),
-                isect_info.handle.clone().binding(),
-            ]),
+            project_bindings,
         );
     });
 
@@ -320,12 +335,16 @@ pub(crate) fn render_forward<BT: BoolElement>(
 
         // Buffer containing the final visible splat per tile.
         let final_index = create_tensor::<2, _>([1, 1], device, client, DType::I32);
+
+        if let Some(ray_splats) = &ray_splats {
+            bindings = bindings.with_buffers(vec![ray_splats.handle.clone().binding()]);
+        }
         (visible, final_index)
     };
 
     // Compile the kernel, including/excluding info for backwards pass.
-    // see the BWD_INFO define in the rasterize shader.
-    let raster_task = Rasterize::task(bwd_info);
+    // see the BWD_INFO and RAY_EVAL defines in the rasterize shader.
+    let raster_task = Rasterize::task(bwd_info, ray_eval);
 
     // SAFETY: Kernel has to contain no OOB indexing.
     unsafe {
@@ -1,8 +1,8 @@
-use glam::Vec3;
+use glam::{DVec3, Quat, Vec3};
 
 use crate::shaders;
 
-const SH_C0: f32 = shaders::project_visible::SH_C0;
+pub const SH_C0: f32 = shaders::project_visible::SH_C0;
 
 pub const fn sh_coeffs_for_degree(degree: u32) -> u32 {
     (degree + 1).pow(2)
@@ -30,3 +30,174 @@ pub fn rgb_to_sh(rgb: Vec3) -> Vec3 {
         channel_to_sh(rgb.z),
     )
 }
+
+/// Values of the SH bases in `dir`, up to `degree`, in the order and convention used by
+/// the renderer.
+pub fn sh_basis(degree: u32, dir: DVec3) -> Vec<f64> {
+    let (x, y, z) = (dir.x, dir.y, dir.z);
+    let mut basis = vec![SH_C0 as f64];
+
+    if degree >= 1 {
+        let c = 0.48860251190292;
+        basis.extend([-c * y, c * z, -c * x]);
+    }
+
+    let z2 = z * z;
+    let fc1 = x * x - y * y;
+    let fs1 = 2.0 * x * y;
+    let sh6 = 0.9461746957575601 * z2 - 0.3153915652525201;
+    if degree >= 2 {
+        let tmp0 = -1.092548430592079 * z;
+        let tmp1 = 0.5462742152960395;
+        basis.extend([tmp1 * fs1, tmp0 * y, sh6, tmp0 * x, tmp1 * fc1]);
+    }
+
+    let fc2 = x * fc1 - y * fs1;
+    let fs2 = x * fs1 + y * fc1;
+    let sh12 = z * (1.865881662950577 * z2 - 1.119528997770346);
+    if degree >= 3 {
+        let tmp0 = -2.285228997322329 * z2 + 0.4570457994644658;
+        let tmp1 = 1.445305721320277 * z;
+        let tmp2 = -0.5900435899266435;
+        basis.extend([
+            tmp2 * fs2,
+            tmp1 * fs1,
+            tmp0 * y,
+            sh12,
+            tmp0 * x,
+            tmp1 * fc1,
+            tmp2 * fc2,
+        ]);
+    }
+
+    if degree >= 4 {
+        let tmp0 = z * (-4.683325804901025 * z2 + 2.007139630671868);
+        let tmp1 = 3.31161143515146 * z2 - 0.47308734787878;
+        let tmp2 = -1.770130769779931 * z;
+        let tmp3 = 0.6258357354491763;
+        let fc3 = x * fc2 - y * fs2;
+        let fs3 = x * fs2 + y * fc2;
+        basis.extend([
+            tmp3 * fs3,
+            tmp2 * fs2,
+            tmp1 * fs1,
+            tmp0 * y,
+            1.984313483298443 * z * sh12 - 1.006230589874905 * sh6,
+            tmp0 * x,
+            tmp1 * fc1,
+            tmp2 * fc2,
+            tmp3 * fc3,
+        ]);
+    }
+
+    basis
+}
+
+/// Matrices that rotate the SH coefficients of each band along with `rotation`, for the
+/// bands `1..=degree`. Band `l` has a row major `(2l + 1)²` matrix, that maps the old
+/// coefficients of a channel to the new ones. The first band doesn't change.
+pub fn sh_rotation(degree: u32, rotation: Quat) -> Vec<Vec<f32>> {
+    // The rotated function is f(R⁻¹ d). Rotations keep each band separate, so fit that per
+    // band from samples spread over the sphere.
+    let rotation = rotation.as_dquat().normalize().inverse();
+    let samples = 64;
+    let dirs: Vec<DVec3> = (0..samples)
+        .map(|i| {
+            let z = 1.0 - (2 * i + 1) as f64 / samples as f64;
+            let r = (1.0 - z * z).sqrt();
+            let phi = i as f64 * std::f64::consts::PI * (3.0 - 5.0f64.sqrt());
+            DVec3::new(r * phi.cos(), r * phi.sin(), z)
+        })
+        .collect();
+    let bases: Vec<_> = dirs.iter().map(|&d| sh_basis(degree, d)).collect();
+    let rotated: Vec<_> = dirs
+        .iter()
+        .map(|&d| sh_basis(degree, rotation * d))
+        .collect();
+
+    (1..=degree as usize)
+        .map(|l| {
+            let (start, size) = (l * l, 2 * l + 1);
+            let band = |b: &[f64], i: usize| b[start + i];
+
+            // Least squares: (AᵀA) D = AᵀB.
+            let mut ata = vec![0.0; size * size];
+            let mut atb = vec![0.0; size * size];
+            for (a, b) in bases.iter().zip(&rotated) {
+                for i in 0..size {
+                    for j in 0..size {
+                        ata[i * size + j] += band(a, i) * band(a, j);
+                        atb[i * size + j] += band(a, i) * band(b, j);
+                    }
+                }
+            }
+            solve(&mut ata, &mut atb, size);
+            atb.into_iter().map(|v| v as f32).collect()
+        })
+        .collect()
+}
+
+/// Solve `A X = B` for square `A` in place, leaving `X` in `b`.
+fn solve(a: &mut [f64], b: &mut [f64], n: usize) {
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&i, &j| a[i * n + col].abs().total_cmp(&a[j * n + col].abs()))
+            .expect("Non empty range");
+        for k in 0..n {
+            a.swap(col * n + k, pivot * n + k);
+            b.swap(col * n + k, pivot * n + k);
+        }
+        for row in 0..n {
+            if row == col {
+                continue;
+            }
+            let f = a[row * n + col] / a[col * n + col];
+            for k in 0..n {
+                a[row * n + k] -= f * a[col * n + k];
+                b[row * n + k] -= f * b[col * n + k];
+            }
+        }
+    }
+    for row in 0..n {
+        let d = a[row * n + row];
+        for k in 0..n {
+            b[row * n + k] /= d;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{DVec3, Quat};
+
+    use super::{sh_basis, sh_rotation};
+
+    #[test]
+    fn rotated_coefficients_follow_rotation() {
+        let rotation = Quat::from_euler(glam::EulerRot::XYZ, 0.3, -1.2, 2.0);
+        let degree = 4;
+        let coeffs: Vec<f64> = (0..25).map(|i| ((i * 7) % 5) as f64 - 2.0).collect();
+
+        let mut rotated = coeffs.clone();
+        for (l, matrix) in sh_rotation(degree, rotation).iter().enumerate() {
+            let (start, size) = ((l + 1) * (l + 1), 2 * l + 3);
+            for i in 0..size {
+                rotated[start + i] = (0..size)
+                    .map(|j| matrix[i * size + j] as f64 * coeffs[start + j])
+                    .sum();
+            }
+        }
+
+        let eval = |c: &[f64], d: DVec3| -> f64 {
+            sh_basis(degree, d).iter().zip(c).map(|(b, c)| b * c).sum()
+        };
+        for dir in [
+            DVec3::X,
+            DVec3::new(0.3, -0.5, 0.8).normalize(),
+            DVec3::NEG_Z,
+        ] {
+            let moved = rotation.as_dquat() * dir;
+            assert!((eval(&coeffs, dir) - eval(&rotated, moved)).abs() < 1e-4);
+        }
+    }
+}
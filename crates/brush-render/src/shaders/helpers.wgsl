@@ -30,6 +30,10 @@ struct RenderUniforms {
     num_intersections: i32,
 #endif
     total_splats: u32,
+    // Planes (normal, offset) to cut splats away with, zero when unused.
+    clip_plane_0: vec4f,
+    clip_plane_1: vec4f,
+    clip_plane_2: vec4f,
 }
 
 // nb: this struct has a bunch of padding but that's probably fine.
@@ -49,12 +53,40 @@ fn create_projected_splat(xy: vec2f, conic: vec3f, color: vec4f) -> ProjectedSpl
     return ProjectedSplat(xy.x, xy.y, conic.x, conic.y, conic.z, color.r, color.g, color.b, color.a);
 }
 
+// Camera space mean and inverse covariance of a splat, for evaluating it
+// exactly along pixel rays instead of through the projected 2D conic.
+struct RaySplat {
+    mean_x: f32,
+    mean_y: f32,
+    mean_z: f32,
+    inv_xx: f32,
+    inv_xy: f32,
+    inv_xz: f32,
+    inv_yy: f32,
+    inv_yz: f32,
+    inv_zz: f32,
+}
+
+fn create_ray_splat(mean_c: vec3f, inv_cov: mat3x3f) -> RaySplat {
+    return RaySplat(
+        mean_c.x, mean_c.y, mean_c.z,
+        inv_cov[0][0], inv_cov[0][1], inv_cov[0][2],
+        inv_cov[1][1], inv_cov[1][2], inv_cov[2][2]
+    );
+}
+
 struct PackedVec3 {
     x: f32,
     y: f32,
     z: f32,
 }
 
+// Whether a point is on the negative side of a plane (normal, offset).
+// Zero planes never are.
+fn behind_plane(point: vec3f, plane: vec4f) -> bool {
+    return dot(plane.xyz, point) + plane.w < 0.0;
+}
+
 fn get_bbox(center: vec2f, dims: vec2f, bounds: vec2u) -> vec4u {
     // get bounding box with center and dims, within bounds
     // bounding box coords returned in tile coords, inclusive min, exclusive max
@@ -176,6 +208,82 @@ fn inverse(m: mat2x2f) -> mat2x2f {
 
 const COV_BLUR: f32 = 0.3;
 
+fn inverse3(m: mat3x3f) -> mat3x3f {
+    let det = determinant(m);
+    if (det <= 0.0f) {
+        return mat3x3f(vec3f(0.0), vec3f(0.0), vec3f(0.0));
+    }
+    // Transpose of the cofactor matrix, built from cross products of the columns.
+    let inv_det = 1.0f / det;
+    return transpose(mat3x3f(
+        cross(m[1], m[2]),
+        cross(m[2], m[0]),
+        cross(m[0], m[1]),
+    )) * inv_det;
+}
+
+// Camera space covariance of a splat, with the blur of calc_cov2d carried over
+// to 3D so it covers about the same pixel footprint.
+fn calc_ray_cov(cov3d: mat3x3f, mean_c: vec3f, focal: vec2f, viewmat: mat4x4f) -> mat3x3f {
+    let R = mat3x3f(viewmat[0].xyz, viewmat[1].xyz, viewmat[2].xyz);
+    var covar_cam = R * cov3d * transpose(R);
+
+    let pixel_size = mean_c.z / min(focal.x, focal.y);
+    let blur = COV_BLUR * pixel_size * pixel_size;
+    covar_cam[0][0] += blur;
+    covar_cam[1][1] += blur;
+    covar_cam[2][2] += blur;
+    return covar_cam;
+}
+
+// Screen space bounds (min xy, max xy) of the 3 sigma ellipsoid of a camera space
+// covariance, found from the planes through the camera that touch it. Unlike the radius of
+// the 2D covariance this is exact at any field of view. When the ellipsoid reaches behind
+// the camera it covers everything.
+fn ellipsoid_screen_bounds(cov: mat3x3f, mean_c: vec3f, focal: vec2f, pixel_center: vec2f) -> vec4f {
+    let k2 = 9.0f;
+    // The plane x = u * z touches the ellipsoid where
+    // (m.x - u * m.z)^2 = k^2 * (cov_xx - 2 * u * cov_xz + u^2 * cov_zz), same for y.
+    let a = mean_c.z * mean_c.z - k2 * cov[2][2];
+    if a <= 0.0f {
+        return vec4f(vec2f(-1e12f), vec2f(1e12f));
+    }
+    let b = mean_c.xy * mean_c.z - k2 * vec2f(cov[0][2], cov[1][2]);
+    let c = mean_c.xy * mean_c.xy - k2 * vec2f(cov[0][0], cov[1][1]);
+    let d = sqrt(max(b * b - a * c, vec2f(0.0f)));
+    let min_uv = (b - d) / a;
+    let max_uv = (b + d) / a;
+    return vec4f(focal * min_uv + pixel_center, focal * max_uv + pixel_center);
+}
+
+// Tiles covered by the pixel bounds (min xy, max xy), as get_tile_bbox.
+fn get_tile_bbox_from_bounds(bounds: vec4f, tile_bounds: vec2u) -> vec4u {
+    let tile_min = bounds.xy / f32(TILE_WIDTH);
+    let tile_max = bounds.zw / f32(TILE_WIDTH);
+    return get_bbox(0.5f * (tile_min + tile_max), 0.5f * (tile_max - tile_min), tile_bounds);
+}
+
+// Sigma of a splat at its maximum along the ray from the camera through a pixel.
+//
+// For a ray o + t * d the exponent is minimized at t = -(d^T M o) / (d^T M d), which
+// leaves 0.5 * (o^T M o - (d^T M o)^2 / (d^T M d)), with o the camera relative to the mean.
+fn calc_ray_sigma(ray: RaySplat, pixel_coord: vec2f, focal: vec2f, pixel_center: vec2f) -> f32 {
+    let inv_cov = mat3x3f(
+        vec3f(ray.inv_xx, ray.inv_xy, ray.inv_xz),
+        vec3f(ray.inv_xy, ray.inv_yy, ray.inv_yz),
+        vec3f(ray.inv_xz, ray.inv_yz, ray.inv_zz),
+    );
+    let dir = vec3f((pixel_coord - pixel_center) / focal, 1.0);
+    let origin = -vec3f(ray.mean_x, ray.mean_y, ray.mean_z);
+
+    let m_origin = inv_cov * origin;
+    let m_dir = inv_cov * dir;
+    let d_m_o = dot(dir, m_origin);
+    let d_m_d = dot(dir, m_dir);
+    return 0.5f * (dot(origin, m_origin) - d_m_o * d_m_o / d_m_d);
+}
+
+
 fn cov_compensation(cov2d: vec3f) -> f32 {
     let cov_orig = cov2d - vec3f(COV_BLUR, 0.0, COV_BLUR);
     let det_orig = cov_orig.x * cov_orig.z - cov_orig.y * cov_orig.y;
@@ -25,6 +25,13 @@ fn main(@builtin(global_invocation_id) global_id: vec3u) {
     // Project world space to camera space.
     let mean = helpers::as_vec(means[global_gid]);
 
+    // Cut away splats behind any of the clipping planes.
+    if helpers::behind_plane(mean, uniforms.clip_plane_0) ||
+        helpers::behind_plane(mean, uniforms.clip_plane_1) ||
+        helpers::behind_plane(mean, uniforms.clip_plane_2) {
+        return;
+    }
+
     let img_size = uniforms.img_size;
     let viewmat = uniforms.viewmat;
     let R = mat3x3f(viewmat[0].xyz, viewmat[1].xyz, viewmat[2].xyz);
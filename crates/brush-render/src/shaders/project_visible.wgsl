@@ -21,6 +21,10 @@ struct IsectInfo {
 @group(0) @binding(8) var<storage, read_write> num_tiles: array<i32>;
 @group(0) @binding(9) var<storage, read_write> isect_info: array<IsectInfo>;
 
+#ifdef RAY_EVAL
+    @group(0) @binding(10) var<storage, read_write> ray_splats: array<helpers::RaySplat>;
+#endif
+
 struct ShCoeffs {
     b0_c0: vec3f,
 
@@ -249,8 +253,18 @@ fn main(@builtin(global_invocation_id) gid: vec3u) {
         vec4f(color, opac)
     );
 
-    let radius = helpers::radius_from_cov(cov2d, opac);
-    let tile_minmax = helpers::get_tile_bbox(mean2d, radius, uniforms.tile_bounds);
+    #ifdef RAY_EVAL
+        let ray_cov = helpers::calc_ray_cov(covar, mean_c, uniforms.focal, viewmat);
+        ray_splats[compact_gid] = helpers::create_ray_splat(mean_c, helpers::inverse3(ray_cov));
+
+        // The 2D covariance underestimates the footprint far from the center of wide
+        // views, so bound the ellipsoid itself.
+        let bounds = helpers::ellipsoid_screen_bounds(ray_cov, mean_c, uniforms.focal, uniforms.pixel_center);
+        let tile_minmax = helpers::get_tile_bbox_from_bounds(bounds, uniforms.tile_bounds);
+    #else
+        let radius = helpers::radius_from_cov(cov2d, opac);
+        let tile_minmax = helpers::get_tile_bbox(mean2d, radius, uniforms.tile_bounds);
+    #endif
     let tile_min = tile_minmax.xy;
     let tile_max = tile_minmax.zw;
 
@@ -258,7 +272,15 @@ fn main(@builtin(global_invocation_id) gid: vec3u) {
 
     for (var ty = tile_min.y; ty < tile_max.y; ty++) {
         for (var tx = tile_min.x; tx < tile_max.x; tx++) {
-            if helpers::can_be_visible(vec2u(tx, ty), mean2d, conic, opac) {
+            // The projected conic is only an approximation when evaluating along rays,
+            // so don't cull tiles with it.
+            #ifdef RAY_EVAL
+                let tile_visible = true;
+            #else
+                let tile_visible = helpers::can_be_visible(vec2u(tx, ty), mean2d, conic, opac);
+            #endif
+
+            if tile_visible {
                 // Add to the tile hit count.
                 num_tiles_hit += 1;
                 let isect_id = atomicAdd(&uniforms.num_intersections, 1);
@@ -15,12 +15,23 @@
     @group(0) @binding(4) var<storage, read_write> out_img: array<u32>;
 #endif
 
+// Only used without BWD_INFO, so binding 5 is free.
+#ifdef RAY_EVAL
+    @group(0) @binding(5) var<storage, read> ray_splats: array<helpers::RaySplat>;
+#endif
+
 var<workgroup> local_batch: array<helpers::ProjectedSplat, helpers::TILE_SIZE>;
 
 #ifdef BWD_INFO
     var<workgroup> load_gid: array<u32, helpers::TILE_SIZE>;
 #endif
 
+// Ray splats are read from global memory, there's not enough workgroup memory to
+// keep them next to the projected splats.
+#ifdef RAY_EVAL
+    var<workgroup> load_compact_gid: array<i32, helpers::TILE_SIZE>;
+#endif
+
 var<workgroup> done_count: atomic<u32>;
 var<workgroup> done_count_uniform: u32;
 
@@ -88,6 +99,10 @@ fn main(
             #ifdef BWD_INFO
                 load_gid[local_idx] = u32(global_from_compact_gid[compact_gid]);
             #endif
+
+            #ifdef RAY_EVAL
+                load_compact_gid[local_idx] = compact_gid;
+            #endif
         }
         // Wait for all writes to complete.
         workgroupBarrier();
@@ -99,8 +114,13 @@ fn main(
             let conic = vec3f(projected.conic_x, projected.conic_y, projected.conic_z);
             let color = vec4f(projected.color_r, projected.color_g, projected.color_b, projected.color_a);
 
-            let delta = xy - pixel_coord;
-            let sigma = 0.5f * (conic.x * delta.x * delta.x + conic.z * delta.y * delta.y) + conic.y * delta.x * delta.y;
+            #ifdef RAY_EVAL
+                let ray = ray_splats[load_compact_gid[t]];
+                let sigma = helpers::calc_ray_sigma(ray, pixel_coord, uniforms.focal, uniforms.pixel_center);
+            #else
+                let delta = xy - pixel_coord;
+                let sigma = 0.5f * (conic.x * delta.x * delta.x + conic.z * delta.y * delta.y) + conic.y * delta.x * delta.y;
+            #endif
             let alpha = min(0.999f, color.a * exp(-sigma));
 
             if (sigma < 0.0f || alpha < 1.0f / 255.0f) {
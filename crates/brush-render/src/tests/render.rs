@@ -1,6 +1,6 @@
-use crate::{SplatForward, camera::Camera};
+use crate::{SplatForward, camera::Camera, post_process::PostProcess};
 use assert_approx_eq::assert_approx_eq;
-use burn::tensor::{Tensor, TensorPrimitive};
+use burn::tensor::{Int, Tensor, TensorPrimitive};
 use burn_wgpu::{Wgpu, WgpuDevice};
 
 type Back = Wgpu;
@@ -52,3 +52,133 @@ fn renders_at_all() {
     assert_approx_eq!(rgb_mean, 0.0, 1e-5);
     assert_approx_eq!(alpha_mean, 0.0);
 }
+
+#[test]
+fn clip_planes_cut_splats() {
+    let device = WgpuDevice::DefaultDevice;
+    let num_points = 2;
+    // One splat in front of the other.
+    let means = Tensor::<Back, 2>::from_floats([[0.0, 0.0, 0.0], [0.0, 0.0, 2.0]], &device);
+    let log_scales = Tensor::<Back, 2>::ones([num_points, 3], &device) * -2.0;
+    let quats: Tensor<Back, 2> =
+        Tensor::<Back, 1>::from_floats(glam::Quat::IDENTITY.to_array(), &device)
+            .unsqueeze_dim(0)
+            .repeat_dim(0, num_points);
+    let sh_coeffs = Tensor::<Back, 3>::ones([num_points, 1, 3], &device);
+    let opacity = Tensor::<Back, 1>::ones([num_points], &device);
+
+    let num_visible = |cam: &Camera| {
+        let (_, aux) = <Back as SplatForward<Back>>::render_splats(
+            cam,
+            glam::uvec2(32, 32),
+            means.clone().into_primitive().tensor(),
+            log_scales.clone().into_primitive().tensor(),
+            quats.clone().into_primitive().tensor(),
+            sh_coeffs.clone().into_primitive().tensor(),
+            opacity.clone().into_primitive().tensor(),
+            false,
+        );
+        let num_visible: Tensor<Back, 1, Int> = Tensor::from_primitive(aux.num_visible);
+        num_visible.into_scalar()
+    };
+
+    let mut cam = Camera::new(
+        glam::vec3(0.0, 0.0, -5.0),
+        glam::Quat::IDENTITY,
+        0.5,
+        0.5,
+        glam::vec2(0.5, 0.5),
+    );
+    assert_eq!(num_visible(&cam), 2);
+
+    // Keep what's beyond z = 1, the splat nearest to the camera is cut away.
+    cam.clip_planes[0] = glam::vec4(0.0, 0.0, 1.0, -1.0);
+    assert_eq!(num_visible(&cam), 1);
+
+    cam.clip_planes[2] = glam::vec4(0.0, 0.0, -1.0, 1.0);
+    assert_eq!(num_visible(&cam), 0);
+}
+
+/// Alpha of each pixel of a render, unpacking renders to the packed u32 buffer.
+fn alphas(img: Tensor<Back, 3>) -> Vec<f32> {
+    let packed = img.dims()[2] == 1;
+    let values = img.into_data().to_vec::<f32>().expect("Wrong type");
+    if packed {
+        values
+            .iter()
+            .map(|v| (v.to_bits() >> 24) as f32 / 255.0)
+            .collect()
+    } else {
+        values.chunks(4).map(|pixel| pixel[3]).collect()
+    }
+}
+
+#[test]
+fn exact_projection_matches_at_the_center_of_wide_views() {
+    let device = WgpuDevice::DefaultDevice;
+    // A single splat straight ahead, where projecting it is exact even at a wide field of view.
+    let means = Tensor::<Back, 2>::from_floats([[0.0, 0.0, 3.0]], &device);
+    let log_scales = Tensor::<Back, 2>::ones([1, 3], &device) * -1.0;
+    let quats = Tensor::<Back, 2>::from_floats([glam::Quat::IDENTITY.to_array()], &device);
+    let sh_coeffs = Tensor::<Back, 3>::ones([1, 1, 3], &device);
+    let opacity = Tensor::<Back, 1>::ones([1], &device) * 4.0;
+
+    let render = |cam: &Camera, bwd_info: bool| {
+        let (img, _) = <Back as SplatForward<Back>>::render_splats(
+            cam,
+            glam::uvec2(64, 64),
+            means.clone().into_primitive().tensor(),
+            log_scales.clone().into_primitive().tensor(),
+            quats.clone().into_primitive().tensor(),
+            sh_coeffs.clone().into_primitive().tensor(),
+            opacity.clone().into_primitive().tensor(),
+            bwd_info,
+        );
+        Tensor::<Back, 3>::from_primitive(TensorPrimitive::Float(img))
+    };
+
+    // About 150 degrees wide.
+    let mut cam = Camera::new(
+        glam::Vec3::ZERO,
+        glam::Quat::IDENTITY,
+        2.6,
+        2.6,
+        glam::vec2(0.5, 0.5),
+    );
+    let projected = render(&cam, true);
+    cam.exact_projection = true;
+    let exact = render(&cam, false);
+    // Exact projection only applies to the packed renders of the viewer, and is ignored
+    // when rendering with gradients.
+    let exact_bwd = render(&cam, true);
+
+    // The footprints differ a little by perspective, but cover about the same.
+    let (exact, projected, exact_bwd) = (alphas(exact), alphas(projected), alphas(exact_bwd));
+    let (exact_coverage, projected_coverage) =
+        (exact.iter().sum::<f32>(), projected.iter().sum::<f32>());
+    assert!((exact_coverage - projected_coverage).abs() < 0.1 * projected_coverage);
+    assert!(exact[32 * 64 + 32] > 0.5);
+
+    assert_eq!(exact_bwd, projected);
+}
+
+#[test]
+fn post_process_encodes_linear_colors() {
+    let device = WgpuDevice::DefaultDevice;
+    let img = Tensor::<Back, 1>::from_floats([0.5, 0.0, 1.0, 0.5], &device).reshape([1, 1, 4]);
+    let post_process = PostProcess {
+        linear_colors: true,
+        ..Default::default()
+    };
+    assert!(post_process.is_enabled());
+    let pixel = post_process
+        .apply(img)
+        .into_data()
+        .into_vec::<f32>()
+        .expect("Wrong type");
+    // Color is encoded, alpha is left as is.
+    assert_approx_eq!(pixel[0], 0.7354, 1e-3);
+    assert_approx_eq!(pixel[1], 0.0, 1e-6);
+    assert_approx_eq!(pixel[2], 1.0, 1e-5);
+    assert_approx_eq!(pixel[3], 0.5, 1e-6);
+}
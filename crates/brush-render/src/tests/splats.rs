@@ -0,0 +1,269 @@
+use crate::{error::SplatsFileError, gaussian_splats::Splats};
+use assert_approx_eq::assert_approx_eq;
+use burn::tensor::Tensor;
+use burn_wgpu::{Wgpu, WgpuDevice};
+use glam::{Affine3A, Quat, Vec3};
+use safetensors::{Dtype, SafeTensors, tensor::TensorView};
+
+type Back = Wgpu;
+
+fn test_splats(device: &WgpuDevice) -> Splats<Back> {
+    let means = [Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::Z];
+    let colors = [Vec3::ONE, Vec3::X, Vec3::Y, Vec3::Z];
+    let scales = [Vec3::splat(0.1); 4];
+    Splats::from_colors(&means, &colors, Some(&scales), None, device)
+}
+
+fn to_vec<const D: usize>(tensor: burn::tensor::Tensor<Back, D>) -> Vec<f32> {
+    tensor.into_data().into_vec::<f32>().expect("Wrong type")
+}
+
+#[test]
+fn append_and_remove() {
+    let device = WgpuDevice::DefaultDevice;
+    let splats = test_splats(&device)
+        .append(test_splats(&device).with_sh_degree(2))
+        .remove_range(1..3);
+
+    assert_eq!(splats.num_splats(), 6);
+    assert_eq!(splats.sh_degree(), 2);
+    let means = to_vec(splats.means.val());
+    assert_eq!(&means[..6], &[0.0, 0.0, 0.0, 0.0, 0.0, 1.0]);
+    assert_eq!(&means[6..9], &[0.0, 0.0, 0.0]);
+}
+
+#[test]
+fn transform_moves_splats() {
+    let device = WgpuDevice::DefaultDevice;
+    let rotation = Quat::from_rotation_y(0.7);
+    let transform = Affine3A::from_scale_rotation_translation(Vec3::splat(2.0), rotation, Vec3::X);
+    let splats = test_splats(&device).transform(transform);
+
+    let means = to_vec(splats.means.val());
+    let expected = transform.transform_point3(Vec3::Z);
+    for (a, b) in means[9..12].iter().zip(expected.to_array()) {
+        assert_approx_eq!(a, b, 1e-5);
+    }
+
+    // Identity rotations become the transform rotation, in (w, x, y, z).
+    let rotations = to_vec(splats.rotation.val());
+    let expected = [rotation.w, rotation.x, rotation.y, rotation.z];
+    for (a, b) in rotations[..4].iter().zip(expected) {
+        assert_approx_eq!(a, b, 1e-5);
+    }
+
+    let scales = to_vec(splats.scales());
+    assert_approx_eq!(scales[0], 0.2, 1e-5);
+}
+
+#[test]
+fn normals_along_shortest_axis() {
+    let device = WgpuDevice::DefaultDevice;
+    let rotation = Quat::from_euler(glam::EulerRot::XYZ, 0.4, 1.1, -0.3);
+    let scales = [Vec3::new(1.0, 0.01, 1.0), Vec3::new(1.0, 1.0, 0.01)];
+    let splats = Splats::<Back>::from_colors(
+        &[Vec3::ZERO; 2],
+        &[Vec3::ONE; 2],
+        Some(&scales),
+        None,
+        &device,
+    )
+    .transform(Affine3A::from_quat(rotation));
+
+    let normals = to_vec(splats.normals());
+    for (normal, axis) in normals.chunks(3).zip([Vec3::Y, Vec3::Z]) {
+        let expected = rotation * axis;
+        // Normals have no particular sign.
+        let dot = Vec3::from_slice(normal).dot(expected);
+        assert_approx_eq!(dot.abs(), 1.0, 1e-5);
+    }
+}
+
+#[test]
+fn encode_srgb_converts_base_color() {
+    let device = WgpuDevice::DefaultDevice;
+    let splats =
+        Splats::<Back>::from_colors(&[Vec3::ZERO], &[Vec3::splat(0.2)], None, None, &device)
+            .with_sh_degree(1)
+            .encode_srgb();
+
+    let coeffs = to_vec(splats.sh_coeffs.val());
+    let color = coeffs[0] * crate::sh::SH_C0 + 0.5;
+    assert_approx_eq!(color, 1.055 * 0.2f32.powf(1.0 / 2.4) - 0.055, 1e-4);
+    assert!(coeffs[3..].iter().all(|c| *c == 0.0));
+}
+
+#[test]
+fn bake_to_dc_uses_average_view() {
+    let device = WgpuDevice::DefaultDevice;
+    let splats =
+        Splats::<Back>::from_colors(&[Vec3::ZERO], &[Vec3::splat(0.5)], None, None, &device);
+    // Only the y basis of the first band, -c * y along the view direction.
+    let mut coeffs = [0.0; 12];
+    coeffs[3..6].fill(1.0);
+    let sh_coeffs = Tensor::<Back, 1>::from_floats(coeffs, &device).reshape([1, 4, 3]);
+    let splats = Splats::from_tensor_data(
+        splats.means.val(),
+        splats.rotation.val(),
+        splats.log_scales.val(),
+        sh_coeffs,
+        splats.raw_opacity.val(),
+    );
+    assert_eq!(splats.sh_degree(), 1);
+
+    let baked = splats.clone().bake_to_dc(&[Vec3::NEG_Y]);
+    assert_eq!(baked.sh_degree(), 0);
+    let color = to_vec(baked.sh_coeffs.val())[0] * crate::sh::SH_C0;
+    assert_approx_eq!(color, -0.48860251, 1e-5);
+
+    // Views on opposite sides average out to no direction.
+    let baked = splats.bake_to_dc(&[Vec3::NEG_Y, Vec3::Y]);
+    assert_approx_eq!(to_vec(baked.sh_coeffs.val())[0], 0.0, 1e-5);
+}
+
+#[test]
+fn keep_most_important_splats() {
+    let device = WgpuDevice::DefaultDevice;
+    let means = [Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::Z];
+    let colors = [Vec3::ONE; 4];
+    let scales = [
+        Vec3::splat(0.1),
+        Vec3::splat(0.5),
+        Vec3::splat(0.1),
+        Vec3::splat(0.5),
+    ];
+    let opacities = [0.9, 0.9, 0.5, 0.1];
+    let splats =
+        Splats::<Back>::from_colors(&means, &colors, Some(&scales), Some(&opacities), &device)
+            .keep_most_important(2);
+
+    assert_eq!(splats.num_splats(), 2);
+    // Big and opaque first, then the big but transparent splat beats the small ones.
+    let means = to_vec(splats.means.val());
+    assert_eq!(means, [1.0, 0.0, 0.0, 0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn blend_weights_fade_across_overlap() {
+    let device = WgpuDevice::DefaultDevice;
+    let means = [
+        Vec3::ZERO,
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(1.5, 0.0, 0.0),
+        Vec3::new(3.0, 0.0, 0.0),
+    ];
+    let splats = Splats::<Back>::from_colors(&means, &[Vec3::ONE; 4], None, None, &device);
+
+    // Bounded on the right at x = 1, with a fade of 1, and unbounded elsewhere.
+    let min = Vec3::NEG_INFINITY;
+    let max = Vec3::new(1.0, f32::INFINITY, f32::INFINITY);
+    let weights = to_vec(splats.blend_weights(min, max, Vec3::ONE));
+    let expected = [1.0, 0.5, 0.0, 0.0];
+    for (a, b) in weights.iter().zip(expected) {
+        assert_approx_eq!(a, b, 1e-5);
+    }
+
+    // The neighbor on the other side gets the rest.
+    let neighbor_min = Vec3::new(1.0, f32::NEG_INFINITY, f32::NEG_INFINITY);
+    let neighbor = to_vec(splats.blend_weights(neighbor_min, Vec3::INFINITY, Vec3::ONE));
+    for (a, b) in weights.iter().zip(&neighbor) {
+        assert_approx_eq!(a + b, 1.0, 1e-5);
+    }
+
+    let opacities = to_vec(splats.opacities());
+    let faded = splats.with_opacity_scaled(Tensor::from_floats([1.0, 0.5, 0.5, 0.5], &device));
+    let faded = to_vec(faded.opacities());
+    assert_approx_eq!(faded[0], opacities[0], 1e-5);
+    assert_approx_eq!(faded[1], opacities[1] * 0.5, 1e-5);
+}
+
+#[tokio::test]
+async fn safetensors_round_trip() {
+    let device = WgpuDevice::DefaultDevice;
+    let means = [Vec3::new(0.1, 0.2, 0.3), Vec3::new(-1.5, 2.0, 1e-7)];
+    let rotations = [Quat::IDENTITY, Quat::from_xyzw(0.1, 0.2, 0.3, 0.4)];
+    let log_scales = [Vec3::splat(-2.0), Vec3::new(0.5, -0.25, 1.0 / 3.0)];
+    let coeffs: Vec<f32> = (0..2 * 4 * 3).map(|i| i as f32 / 7.0).collect();
+    let opacities = [0.123, -4.5];
+
+    let splats = Splats::<Back>::from_raw(
+        &means,
+        Some(&rotations),
+        Some(&log_scales),
+        Some(&coeffs),
+        Some(&opacities),
+        &device,
+    );
+    let bytes = splats.to_safetensors().await.expect("Failed to serialize");
+    let loaded = Splats::<Back>::from_safetensors(&bytes, &device).expect("Failed to load");
+
+    assert_eq!(loaded.sh_degree(), 1);
+    assert_eq!(to_vec(loaded.means.val()), to_vec(splats.means.val()));
+    assert_eq!(to_vec(loaded.rotation.val()), to_vec(splats.rotation.val()));
+    assert_eq!(
+        to_vec(loaded.log_scales.val()),
+        to_vec(splats.log_scales.val())
+    );
+    assert_eq!(
+        to_vec(loaded.sh_coeffs.val()),
+        to_vec(splats.sh_coeffs.val())
+    );
+    assert_eq!(
+        to_vec(loaded.raw_opacity.val()),
+        to_vec(splats.raw_opacity.val())
+    );
+
+    let metadata = SafeTensors::read_metadata(&bytes)
+        .expect("Valid safetensors")
+        .1;
+    let metadata = metadata.metadata().as_ref().expect("Has metadata");
+    assert_eq!(metadata.get("sh_degree").map(String::as_str), Some("1"));
+}
+
+#[test]
+fn safetensors_with_mismatched_tensors_are_rejected() {
+    let device = WgpuDevice::DefaultDevice;
+    let tensor = |shape: Vec<usize>| {
+        let bytes = vec![0; shape.iter().product::<usize>() * 4];
+        (shape, bytes)
+    };
+    let file = |opacities: (Vec<usize>, Vec<u8>), dtype| {
+        let tensors = [
+            ("means", tensor(vec![2, 3]), Dtype::F32),
+            ("quats", tensor(vec![2, 4]), Dtype::F32),
+            ("scales", tensor(vec![2, 3]), Dtype::F32),
+            ("coeffs", tensor(vec![2, 1, 3]), Dtype::F32),
+            ("opacities", opacities, dtype),
+        ];
+        let views = tensors.iter().map(|(name, (shape, data), dtype)| {
+            let view = TensorView::new(*dtype, shape.clone(), data).expect("Valid view");
+            (*name, view)
+        });
+        safetensors::serialize(views, &None).expect("Failed to serialize")
+    };
+
+    let load = |bytes: Vec<u8>| Splats::<Back>::from_safetensors(&bytes, &device).err();
+    assert!(load(file(tensor(vec![2]), Dtype::F32)).is_none());
+    assert!(matches!(
+        load(file(tensor(vec![3]), Dtype::F32)),
+        Some(SplatsFileError::MismatchedCount {
+            name: "opacities",
+            count: 3,
+            expected: 2
+        })
+    ));
+    assert!(matches!(
+        load(file(tensor(vec![2, 1]), Dtype::F32)),
+        Some(SplatsFileError::WrongShape {
+            name: "opacities",
+            ..
+        })
+    ));
+    assert!(matches!(
+        load(file(tensor(vec![2]), Dtype::I32)),
+        Some(SplatsFileError::WrongDtype {
+            name: "opacities",
+            dtype: Dtype::I32
+        })
+    ));
+}
@@ -0,0 +1,259 @@
+//! Tiny synthetic datasets, for end-to-end tests of the whole pipeline.
+//!
+//! A checkered sphere and a striped cube are made of splats, and rendered with the forward
+//! rasterizer from cameras orbiting them. The renders are written out as a regular dataset
+//! zip with the known camera poses, so loading, training, evaluating and exporting can be
+//! tested without downloading anything, on the same scene every time.
+
+use std::f32::consts::TAU;
+use std::io::{Cursor, Seek, Write};
+use std::path::Path;
+
+use brush_dataset::capture::CaptureWriter;
+use brush_render::{camera::Camera, gaussian_splats::Splats};
+use burn_wgpu::{Wgpu, WgpuDevice};
+use glam::{Mat3, Mat4, Quat, UVec2, Vec3, uvec2, vec2, vec3};
+use image::{ImageFormat, Rgb, RgbImage};
+
+/// Layout of a synthetic dataset.
+#[derive(Debug, Clone)]
+pub struct SyntheticScene {
+    /// Number of cameras orbiting the primitives.
+    pub views: u32,
+    pub image_size: UVec2,
+    /// Distance of the cameras to the center of the scene.
+    pub radius: f32,
+    /// Field of view of the cameras, in radians.
+    pub fov: f64,
+    /// Height of the cameras above and below the primitives, alternating, so not all views
+    /// are in one plane.
+    pub elevation: f32,
+}
+
+impl Default for SyntheticScene {
+    fn default() -> Self {
+        Self {
+            views: 24,
+            image_size: uvec2(96, 96),
+            radius: 3.0,
+            fov: 0.9,
+            elevation: 0.8,
+        }
+    }
+}
+
+// Number of splats on the sphere, and along each side of the faces of the cube.
+const SPHERE_SPLATS: usize = 800;
+const CUBE_GRID: usize = 10;
+
+/// Splats of a checkered sphere next to a striped cube, centered on the origin.
+pub fn primitives(device: &WgpuDevice) -> Splats<Wgpu> {
+    let mut means = vec![];
+    let mut colors = vec![];
+
+    // Spread points evenly over the sphere along a spiral.
+    let golden_angle = TAU * (1.0 - 1.0 / 1.618_034);
+    for i in 0..SPHERE_SPLATS {
+        let y = 1.0 - 2.0 * (i as f32 + 0.5) / SPHERE_SPLATS as f32;
+        let angle = i as f32 * golden_angle;
+        let ring = (1.0 - y * y).sqrt();
+        let dir = vec3(ring * angle.cos(), y, ring * angle.sin());
+        means.push(vec3(-0.6, 0.0, 0.0) + dir * 0.5);
+
+        let u = (angle.rem_euclid(TAU) / TAU * 8.0) as u32;
+        let v = ((y + 1.0) * 4.0) as u32;
+        colors.push(if (u + v) % 2 == 0 {
+            vec3(0.9, 0.2, 0.1)
+        } else {
+            vec3(0.95, 0.95, 0.9)
+        });
+    }
+
+    let grid = |k: usize| (k as f32 + 0.5) / CUBE_GRID as f32 * 2.0 - 1.0;
+    for face in 0..6 {
+        let axis = face / 2;
+        let side = if face % 2 == 0 { 1.0 } else { -1.0 };
+        for a in 0..CUBE_GRID {
+            for b in 0..CUBE_GRID {
+                let mut p = Vec3::ZERO;
+                p[axis] = side;
+                p[(axis + 1) % 3] = grid(a);
+                p[(axis + 2) % 3] = grid(b);
+                means.push(vec3(0.6, 0.0, 0.0) + p * 0.35);
+                colors.push(if a % 2 == 0 {
+                    vec3(0.1, 0.3, 0.9)
+                } else {
+                    vec3(0.2, 0.8, 0.3)
+                });
+            }
+        }
+    }
+
+    Splats::from_colors(&means, &colors, None, None, device)
+}
+
+/// Rotation of a camera at `position` looking at `target`, with +Y up. Cameras look along
+/// their +Z axis, with +Y down in the image.
+fn look_at(position: Vec3, target: Vec3) -> Quat {
+    let forward = (target - position).normalize();
+    let right = Vec3::NEG_Y.cross(forward).normalize();
+    let down = forward.cross(right);
+    Quat::from_mat3(&Mat3::from_cols(right, down, forward))
+}
+
+/// Cameras on a circle around the primitives, looking at the center.
+pub fn orbit_cameras(scene: &SyntheticScene) -> Vec<Camera> {
+    (0..scene.views)
+        .map(|i| {
+            let angle = i as f32 / scene.views as f32 * TAU;
+            let height = if i % 2 == 0 {
+                scene.elevation
+            } else {
+                -scene.elevation
+            };
+            let position = vec3(
+                angle.cos() * scene.radius,
+                height,
+                angle.sin() * scene.radius,
+            );
+            Camera::new(
+                position,
+                look_at(position, Vec3::ZERO),
+                scene.fov,
+                scene.fov,
+                vec2(0.5, 0.5),
+            )
+        })
+        .collect()
+}
+
+/// Render the primitives from the orbiting cameras, and write them as a dataset zip.
+pub async fn write_dataset<W: Write + Seek>(
+    scene: &SyntheticScene,
+    writer: W,
+    device: &WgpuDevice,
+) -> anyhow::Result<W> {
+    let splats = primitives(device);
+    let size = scene.image_size;
+    let mut capture = CaptureWriter::new(writer);
+
+    for camera in orbit_cameras(scene) {
+        let (img, _) = splats.render(&camera, size, true);
+        let data = img
+            .into_data_async()
+            .await
+            .into_vec::<f32>()
+            .map_err(|e| anyhow::anyhow!("Failed to read render: {e:?}"))?;
+
+        // The renders are on black, which is what's trained against without alpha.
+        let rgb = RgbImage::from_fn(size.x, size.y, |x, y| {
+            let i = ((y * size.x + x) * 4) as usize;
+            Rgb(std::array::from_fn(|c| {
+                (data[i + c].clamp(0.0, 1.0) * 255.0).round() as u8
+            }))
+        });
+        let mut png = Cursor::new(vec![]);
+        rgb.write_to(&mut png, ImageFormat::Png)?;
+
+        // To the OpenGL convention of the transforms file.
+        let mut cam_to_world = Mat4::from(camera.local_to_world());
+        cam_to_world.y_axis *= -1.0;
+        cam_to_world.z_axis *= -1.0;
+
+        capture.add_frame(
+            png.get_ref(),
+            "png",
+            cam_to_world,
+            camera.focal(size),
+            camera.center(size),
+            size,
+        )?;
+    }
+
+    capture.finish()
+}
+
+/// Write a synthetic dataset zip to `path`.
+pub async fn write_dataset_zip(
+    scene: &SyntheticScene,
+    path: &Path,
+    device: &WgpuDevice,
+) -> anyhow::Result<()> {
+    let file = std::fs::File::create(path)?;
+    write_dataset(scene, file, device).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use brush_process::{
+        data_source::DataSource,
+        process_loop::{ProcessArgs, ProcessMessage, process_stream},
+    };
+    use burn_wgpu::WgpuDevice;
+    use glam::Vec3;
+    use tokio_stream::StreamExt;
+
+    use super::{SyntheticScene, look_at, orbit_cameras, write_dataset_zip};
+
+    #[test]
+    fn cameras_look_at_center() {
+        for camera in orbit_cameras(&SyntheticScene::default()) {
+            let forward = camera.rotation * Vec3::Z;
+            assert!(forward.dot(-camera.position.normalize()) > 0.999);
+        }
+        // Image down is world down.
+        let rotation = look_at(Vec3::new(0.0, 0.0, -3.0), Vec3::ZERO);
+        assert!((rotation * Vec3::Y).dot(Vec3::NEG_Y) > 0.999);
+    }
+
+    #[tokio::test]
+    async fn load_train_eval_export() -> anyhow::Result<()> {
+        let device = WgpuDevice::DefaultDevice;
+        let dir = std::env::temp_dir().join(format!("brush_testdata_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let zip = dir.join("synthetic.zip");
+        write_dataset_zip(&SyntheticScene::default(), &zip, &device).await?;
+
+        let mut args = ProcessArgs::default();
+        args.train_config.total_steps = 500;
+        args.load_config.eval_split_every = Some(6);
+        args.process_config.eval_every = 500;
+        args.process_config.export_every = 500;
+        args.process_config.output_dir = Some(dir.to_string_lossy().into_owned());
+        args.process_config.run_name = Some("run".to_owned());
+
+        let source =
+            DataSource::from_str(&zip.to_string_lossy()).map_err(|e| anyhow::anyhow!(e))?;
+        let stream = process_stream(source, args, device);
+        let mut stream = std::pin::pin!(stream);
+
+        let mut views = None;
+        let mut psnr = None;
+        while let Some(message) = stream.next().await {
+            match message? {
+                ProcessMessage::Dataset { dataset } => {
+                    let eval_views = dataset.eval.map_or(0, |e| e.views.len());
+                    views = Some((dataset.train.views.len(), eval_views));
+                }
+                ProcessMessage::EvalResult { avg_psnr, .. } => psnr = Some(avg_psnr),
+                _ => {}
+            }
+        }
+
+        // Every 6th of the 24 views is held out for eval.
+        assert_eq!(views, Some((20, 4)));
+        // Training isn't bit exact on all GPUs, only check it learned the scene.
+        let psnr = psnr.expect("Eval should have run");
+        assert!(psnr > 15.0, "PSNR too low: {psnr}");
+
+        let run_dir = dir.join("run");
+        assert!(run_dir.join("export_500.ply").is_file());
+        assert!(run_dir.join("report.html").is_file());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+use burn_cubecl::cubecl;
+use burn_cubecl::cubecl::{cube, prelude::*};
+
+/// The whole Adam update of one parameter in a single pass, instead of a dozen tensor ops
+/// that each read and write all moments.
+#[cube(launch)]
+pub fn adam_step_kernel(
+    param: &Tensor<f32>,
+    grad: &Tensor<f32>,
+    moment_1: &Tensor<f32>,
+    moment_2: &Tensor<f32>,
+    // Learning rate scale per element, broadcast over the parameter.
+    lr_scale: &Tensor<f32>,
+    out_param: &mut Tensor<f32>,
+    out_moment_1: &mut Tensor<f32>,
+    out_moment_2: &mut Tensor<f32>,
+    lr: f32,
+    beta_1: f32,
+    beta_2: f32,
+    epsilon: f32,
+    bias_correction_1: f32,
+    bias_correction_2: f32,
+) {
+    let pos = ABSOLUTE_POS;
+    if pos >= param.len() {
+        terminate!();
+    }
+
+    let mut scale_index = 0u32;
+    for dim in 0..param.rank() {
+        let coord = (pos / param.stride(dim)) % param.shape(dim);
+        scale_index += (coord % lr_scale.shape(dim)) * lr_scale.stride(dim);
+    }
+
+    let g = grad[pos];
+    let m = moment_1[pos] * beta_1 + g * (1.0 - beta_1);
+    let v = moment_2[pos] * beta_2 + g * g * (1.0 - beta_2);
+    out_moment_1[pos] = m;
+    out_moment_2[pos] = v;
+
+    let step = (m / bias_correction_1) / (f32::sqrt(v / bias_correction_2) + epsilon);
+    out_param[pos] = param[pos] - step * lr * lr_scale[scale_index];
+}
@@ -1,5 +1,6 @@
 use burn::{
     LearningRate,
+    backend::wgpu::WgpuRuntime,
     config::Config,
     grad_clipping::GradientClippingConfig,
     module::AutodiffModule,
@@ -10,14 +11,25 @@ use burn::{
     },
     prelude::Backend,
     record::Record,
-    tensor::{Device, ElementConversion, Tensor, backend::AutodiffBackend},
+    tensor::{DType, Device, FloatDType, Tensor, TensorPrimitive, backend::AutodiffBackend},
 };
+use burn_cubecl::cubecl::{CubeDim, calculate_cube_count_elemwise, prelude::ScalarArg};
+use burn_cubecl::{BoolElement, fusion::FusionCubeRuntime, kernel::into_contiguous};
+use burn_fusion::{Fusion, FusionHandle, client::FusionClient, stream::Operation};
+use burn_ir::{CustomOpIr, HandleContainer, OperationIr};
+use std::marker::PhantomData;
+
+use brush_kernel::create_tensor;
+use brush_render::BBase;
+
+use crate::adam_kernel::adam_step_kernel;
 
 /// Adam optimizer as described in the paper [Adam: A Method for Stochastic Optimization](https://arxiv.org/pdf/1412.6980.pdf).
 #[derive(Clone)]
 pub struct AdamScaled {
     momentum: AdaptiveMomentum,
     weight_decay: Option<WeightDecay>,
+    half_precision_moments: bool,
 }
 
 /// Adam configuration.
@@ -36,6 +48,10 @@ pub struct AdamScaledConfig {
     weight_decay: Option<WeightDecayConfig>,
     /// [Gradient Clipping](GradientClippingConfig) config.
     grad_clipping: Option<GradientClippingConfig>,
+    /// Store the moments in half precision between steps, halving the memory they take.
+    /// The parameters themselves, and the update, stay in full precision.
+    #[config(default = false)]
+    half_precision_moments: bool,
 }
 
 #[derive(Clone)]
@@ -51,6 +67,8 @@ pub struct AdamState<B: Backend, const D: usize> {
     /// The current adaptive momentum.
     pub momentum: Option<AdaptiveMomentumState<B, D>>,
     pub scaling: Option<Tensor<B, D>>,
+    /// When the moments are stored in half precision, what they're scaled by.
+    pub moment_scales: Option<Tensor<B, 1>>,
 }
 
 impl AdamScaledConfig {
@@ -65,6 +83,7 @@ impl AdamScaledConfig {
                 epsilon: self.epsilon,
             },
             weight_decay: self.weight_decay.as_ref().map(WeightDecay::new),
+            half_precision_moments: self.half_precision_moments,
         };
 
         let mut optim = OptimizerAdaptor::from(optim);
@@ -75,7 +94,7 @@ impl AdamScaledConfig {
     }
 }
 
-impl<B: Backend> SimpleOptimizer<B> for AdamScaled {
+impl<B: FusedAdam> SimpleOptimizer<B> for AdamScaled {
     type State<const D: usize> = AdamState<B, D>;
 
     fn step<const D: usize>(
@@ -89,7 +108,9 @@ impl<B: Backend> SimpleOptimizer<B> for AdamScaled {
         let mut scaling = None;
 
         if let Some(state) = state {
-            state_momentum = state.momentum;
+            state_momentum = state
+                .momentum
+                .map(|momentum| full_precision_moments(momentum, state.moment_scales));
             scaling = state.scaling;
         }
 
@@ -97,70 +118,304 @@ impl<B: Backend> SimpleOptimizer<B> for AdamScaled {
             grad = weight_decay.transform(grad, tensor.clone());
         }
 
-        let (grad, state_momentum) = self.momentum.transform(grad, state_momentum);
+        let device = tensor.device();
+        let momentum = state_momentum
+            .unwrap_or_else(|| AdaptiveMomentumState::new(0, grad.zeros_like(), grad.zeros_like()));
+        let time = momentum.time + 1;
+        let lr_scale = scaling
+            .clone()
+            .unwrap_or_else(|| Tensor::ones([1; D], &device));
+        let [tensor, moment_1, moment_2] = B::adam_step(
+            tensor,
+            grad,
+            [momentum.moment_1, momentum.moment_2],
+            lr_scale,
+            AdamStepArgs {
+                lr: lr as f32,
+                beta_1: self.momentum.beta_1,
+                beta_2: self.momentum.beta_2,
+                epsilon: self.momentum.epsilon,
+                time: time as i32,
+            },
+        );
+        let state_momentum = AdaptiveMomentumState::new(time, moment_1, moment_2);
 
-        let state = AdamState {
-            momentum: Some(state_momentum),
-            scaling: scaling.clone(),
+        let (state_momentum, moment_scales) = if self.half_precision_moments {
+            let (momentum, scales) = half_precision_moments(state_momentum);
+            (momentum, Some(scales))
+        } else {
+            (state_momentum, None)
         };
 
-        let delta = if let Some(scale) = scaling {
-            grad * (scale * lr).unsqueeze()
-        } else {
-            grad * lr
+        let state = AdamState {
+            momentum: Some(state_momentum),
+            scaling,
+            moment_scales,
         };
 
-        (tensor - delta, Some(state))
+        (tensor, Some(state))
     }
 
     fn to_device<const D: usize>(mut state: Self::State<D>, device: &Device<B>) -> Self::State<D> {
         state.momentum = state.momentum.map(|m| m.to_device(device));
+        state.scaling = state.scaling.map(|s| s.to_device(device));
+        state.moment_scales = state.moment_scales.map(|s| s.to_device(device));
         state
     }
 }
 
-impl AdaptiveMomentum {
-    pub fn transform<B: Backend, const D: usize>(
-        &self,
-        grad: Tensor<B, D>,
-        momentum_state: Option<AdaptiveMomentumState<B, D>>,
-    ) -> (Tensor<B, D>, AdaptiveMomentumState<B, D>) {
-        let state = if let Some(mut state) = momentum_state {
-            let factor = 1.0 - self.beta_1;
-            state.moment_1 = state
-                .moment_1
-                .mul_scalar(self.beta_1)
-                .add(grad.clone().mul_scalar(factor));
-
-            let factor = 1.0 - self.beta_2;
-            state.moment_2 = state
-                .moment_2
-                .mul_scalar(self.beta_2)
-                .add(grad.powf_scalar(2.0).mul_scalar(factor));
-
-            state.time += 1;
-
-            state
-        } else {
-            let factor = 1.0 - self.beta_1;
-            let moment_1 = grad.clone().mul_scalar(factor);
+/// Smallest normal f16. Smaller values lose precision, down to rounding to zero.
+const F16_MIN_POSITIVE: f32 = 6.103_515_6e-5;
+
+/// Store the moments in half precision. Both are divided by their largest value first, so
+/// they don't overflow. The second moment is stored as its square root, which needs half
+/// the range.
+///
+/// Second moments too small to store are rounded up to the smallest normal f16 instead.
+/// That only makes their steps smaller, where rounding them to zero would blow them up.
+fn half_precision_moments<B: Backend, const D: usize>(
+    mut state: AdaptiveMomentumState<B, D>,
+) -> (AdaptiveMomentumState<B, D>, Tensor<B, 1>) {
+    let rms = state.moment_2.sqrt();
+    let scale_1 = state
+        .moment_1
+        .clone()
+        .abs()
+        .max()
+        .clamp_min(f32::MIN_POSITIVE);
+    let scale_2 = rms.clone().max().clamp_min(f32::MIN_POSITIVE);
+
+    state.moment_2 = (rms / scale_2.clone().unsqueeze())
+        .clamp_min(F16_MIN_POSITIVE)
+        .cast(FloatDType::F16);
+    state.moment_1 = (state.moment_1 / scale_1.clone().unsqueeze()).cast(FloatDType::F16);
+    (state, Tensor::cat(vec![scale_1, scale_2], 0))
+}
+
+/// The moments in full precision, undoing [`half_precision_moments`] if `scales` are set.
+pub(crate) fn full_precision_moments<B: Backend, const D: usize>(
+    mut state: AdaptiveMomentumState<B, D>,
+    scales: Option<Tensor<B, 1>>,
+) -> AdaptiveMomentumState<B, D> {
+    if let Some(scales) = scales {
+        let scale_1 = scales.clone().slice([0..1]).unsqueeze();
+        let scale_2 = scales.slice([1..2]).unsqueeze();
+        state.moment_1 = state.moment_1.cast(FloatDType::F32) * scale_1;
+        state.moment_2 = (state.moment_2.cast(FloatDType::F32) * scale_2).powf_scalar(2.0);
+    }
+    state
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AdamStepArgs {
+    pub lr: f32,
+    pub beta_1: f32,
+    pub beta_2: f32,
+    pub epsilon: f32,
+    /// Number of steps taken, including this one.
+    pub time: i32,
+}
+
+/// Backends that can run the Adam update of a parameter as one kernel.
+pub trait FusedAdam: Backend {
+    /// Update `param` with `grad`, returning the new parameter and moments.
+    fn adam_step<const D: usize>(
+        param: Tensor<Self, D>,
+        grad: Tensor<Self, D>,
+        moments: [Tensor<Self, D>; 2],
+        lr_scale: Tensor<Self, D>,
+        args: AdamStepArgs,
+    ) -> [Tensor<Self, D>; 3];
+}
+
+impl<BT: BoolElement> FusedAdam for BBase<BT> {
+    fn adam_step<const D: usize>(
+        param: Tensor<Self, D>,
+        grad: Tensor<Self, D>,
+        moments: [Tensor<Self, D>; 2],
+        lr_scale: Tensor<Self, D>,
+        args: AdamStepArgs,
+    ) -> [Tensor<Self, D>; 3] {
+        let contiguous =
+            |tensor: Tensor<Self, D>| into_contiguous(tensor.into_primitive().tensor());
+        let [moment_1, moment_2] = moments.map(contiguous);
+        let param = contiguous(param);
+        let grad = contiguous(grad);
+        let lr_scale = lr_scale.into_primitive().tensor();
+
+        let client = param.client.clone();
+        let device = param.device.clone();
+        let num_elems = param.shape.num_elements();
+        let [out_param, out_moment_1, out_moment_2] = [(); 3].map(|_| {
+            create_tensor::<D, WgpuRuntime>(param.shape.dims(), &device, &client, DType::F32)
+        });
+
+        let cube_dim = CubeDim::default();
+        adam_step_kernel::launch(
+            &client,
+            calculate_cube_count_elemwise(num_elems, cube_dim),
+            cube_dim,
+            param.as_tensor_arg::<f32>(1),
+            grad.as_tensor_arg::<f32>(1),
+            moment_1.as_tensor_arg::<f32>(1),
+            moment_2.as_tensor_arg::<f32>(1),
+            lr_scale.as_tensor_arg::<f32>(1),
+            out_param.as_tensor_arg::<f32>(1),
+            out_moment_1.as_tensor_arg::<f32>(1),
+            out_moment_2.as_tensor_arg::<f32>(1),
+            ScalarArg::new(args.lr),
+            ScalarArg::new(args.beta_1),
+            ScalarArg::new(args.beta_2),
+            ScalarArg::new(args.epsilon),
+            ScalarArg::new(1.0 - args.beta_1.powi(args.time)),
+            ScalarArg::new(1.0 - args.beta_2.powi(args.time)),
+        );
 
-            let factor = 1.0 - self.beta_2;
-            let moment_2 = grad.powf_scalar(2.0).mul_scalar(factor);
+        [out_param, out_moment_1, out_moment_2]
+            .map(|tensor| Tensor::from_primitive(TensorPrimitive::Float(tensor)))
+    }
+}
+
+impl<BT: BoolElement> FusedAdam for Fusion<BBase<BT>> {
+    fn adam_step<const D: usize>(
+        param: Tensor<Self, D>,
+        grad: Tensor<Self, D>,
+        moments: [Tensor<Self, D>; 2],
+        lr_scale: Tensor<Self, D>,
+        args: AdamStepArgs,
+    ) -> [Tensor<Self, D>; 3] {
+        struct CustomOp<BT: BoolElement, const D: usize> {
+            desc: CustomOpIr,
+            args: AdamStepArgs,
+            _bool: PhantomData<BT>,
+        }
+
+        impl<BT: BoolElement, const D: usize> Operation<FusionCubeRuntime<WgpuRuntime, BT>>
+            for CustomOp<BT, D>
+        {
+            fn execute(
+                self: Box<Self>,
+                h: &mut HandleContainer<FusionHandle<FusionCubeRuntime<WgpuRuntime, BT>>>,
+            ) {
+                let (
+                    [param, grad, moment_1, moment_2, lr_scale],
+                    [out_param, out_moment_1, out_moment_2],
+                ) = self.desc.consume();
+
+                let mut input = |ir| {
+                    Tensor::<BBase<BT>, D>::from_primitive(TensorPrimitive::Float(
+                        h.get_float_tensor::<BBase<BT>>(&ir),
+                    ))
+                };
+                let param = input(param);
+                let grad = input(grad);
+                let moments = [input(moment_1), input(moment_2)];
+                let lr_scale = input(lr_scale);
+
+                let outputs =
+                    <BBase<BT> as FusedAdam>::adam_step(param, grad, moments, lr_scale, self.args);
 
-            AdaptiveMomentumState::new(1, moment_1, moment_2)
+                // Register output.
+                for (out, tensor) in [out_param, out_moment_1, out_moment_2]
+                    .into_iter()
+                    .zip(outputs)
+                {
+                    h.register_float_tensor::<BBase<BT>>(&out.id, tensor.into_primitive().tensor());
+                }
+            }
+        }
+
+        let [moment_1, moment_2] = moments;
+        let [param, grad, moment_1, moment_2, lr_scale] =
+            [param, grad, moment_1, moment_2, lr_scale]
+                .map(|tensor| tensor.into_primitive().tensor());
+        let client = param.client.clone();
+        let streams = vec![
+            param.stream,
+            grad.stream,
+            moment_1.stream,
+            moment_2.stream,
+            lr_scale.stream,
+        ];
+
+        let outputs = [(); 3].map(|_| client.tensor_uninitialized(param.shape.clone(), DType::F32));
+
+        let desc = CustomOpIr::new(
+            "adam_step",
+            &[
+                param.into_ir(),
+                grad.into_ir(),
+                moment_1.into_ir(),
+                moment_2.into_ir(),
+                lr_scale.into_ir(),
+            ],
+            &outputs.each_ref().map(|out| out.to_ir_out()),
+        );
+
+        let op = CustomOp::<BT, D> {
+            desc: desc.clone(),
+            args,
+            _bool: PhantomData,
         };
 
-        let time = (state.time as i32).elem();
-        let moment_1_corrected = state
-            .moment_1
-            .clone()
-            .div_scalar(1f32 - self.beta_1.powi(time));
-        let moment_2_corrected = state
-            .moment_2
-            .clone()
-            .div_scalar(1f32 - self.beta_2.powi(time));
-        let grad = moment_1_corrected.div(moment_2_corrected.sqrt().add_scalar(self.epsilon));
-        (grad, state)
+        client.register(streams, OperationIr::Custom(desc), op);
+
+        outputs.map(|tensor| Tensor::from_primitive(TensorPrimitive::Float(tensor)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use burn::{
+        backend::{Wgpu, wgpu::WgpuDevice},
+        tensor::Tensor,
+    };
+
+    use super::{AdamStepArgs, FusedAdam};
+
+    #[test]
+    fn matches_reference_adam() {
+        let device = WgpuDevice::DefaultDevice;
+        let param = [0.5, -1.0, 2.0, 0.0, 0.25, 1.5];
+        let grad = [0.1, -0.2, 0.0, 1.0, -3.0, 0.05];
+        let moment_1 = [0.01, 0.02, -0.03, 0.0, 0.1, -0.2];
+        let moment_2 = [0.001, 0.002, 0.003, 0.0, 0.5, 0.04];
+        // Scaled per row, like the positions of young splats.
+        let lr_scale = [1.0, 2.0];
+        let args = AdamStepArgs {
+            lr: 0.01,
+            beta_1: 0.9,
+            beta_2: 0.999,
+            epsilon: 1e-8,
+            time: 3,
+        };
+
+        let tensor = |data: &[f32]| Tensor::<Wgpu, 1>::from_floats(data, &device).reshape([2, 3]);
+        let [new_param, new_moment_1, new_moment_2] = Wgpu::adam_step(
+            tensor(&param),
+            tensor(&grad),
+            [tensor(&moment_1), tensor(&moment_2)],
+            Tensor::<Wgpu, 1>::from_floats(lr_scale, &device).reshape([2, 1]),
+            args,
+        );
+        let to_vec = |t: Tensor<Wgpu, 2>| t.into_data().to_vec::<f32>().expect("Wrong type");
+        let (new_param, new_moment_1, new_moment_2) = (
+            to_vec(new_param),
+            to_vec(new_moment_1),
+            to_vec(new_moment_2),
+        );
+
+        for (i, &g) in grad.iter().enumerate() {
+            let m = moment_1[i] * args.beta_1 + g * (1.0 - args.beta_1);
+            let v = moment_2[i] * args.beta_2 + g * g * (1.0 - args.beta_2);
+            let m_hat = m / (1.0 - args.beta_1.powi(args.time));
+            let v_hat = v / (1.0 - args.beta_2.powi(args.time));
+            let expected =
+                param[i] - args.lr * lr_scale[i / 3] * m_hat / (v_hat.sqrt() + args.epsilon);
+
+            assert!((new_moment_1[i] - m).abs() < 1e-6);
+            assert!((new_moment_2[i] - v).abs() < 1e-6);
+            assert!((new_param[i] - expected).abs() < 1e-5);
+        }
     }
 }
@@ -0,0 +1,122 @@
+//! A color transform per capture session, for datasets shot under different lighting, eg. in
+//! the morning and in the evening.
+//!
+//! The splats hold one set of colors, and each session maps them with its own affine color
+//! transform, learned along with the splats. Views of a session are rendered with its
+//! transform, so the splats don't have to average the lighting of all sessions. As the
+//! transforms are linear, each can be baked into the splats to export that session's look.
+
+use brush_render::{gaussian_splats::Splats, sh::SH_C0};
+use burn::{
+    module::{Module, Param, ParamId},
+    prelude::Backend,
+    tensor::Tensor,
+};
+
+#[derive(Module, Debug)]
+pub struct Appearance<B: Backend> {
+    /// Color transform of each session, [sessions, 3, 4]. Each row maps RGB and a constant
+    /// to one output channel.
+    pub transforms: Param<Tensor<B, 3>>,
+}
+
+impl<B: Backend> Appearance<B> {
+    /// All sessions start out with the identity transform.
+    pub fn new(sessions: usize, device: &B::Device) -> Self {
+        let identity = [
+            1.0, 0.0, 0.0, 0.0, //
+            0.0, 1.0, 0.0, 0.0, //
+            0.0, 0.0, 1.0, 0.0,
+        ];
+        let transforms = Tensor::<B, 1>::from_floats(identity.as_slice(), device)
+            .reshape([1, 3, 4])
+            .repeat_dim(0, sessions);
+        Self {
+            transforms: Param::initialized(ParamId::new(), transforms.require_grad()),
+        }
+    }
+
+    pub fn sessions(&self) -> usize {
+        self.transforms.dims()[0]
+    }
+
+    /// SH coefficients of shape [N, coeffs, 3] as seen in `session`.
+    pub fn apply(&self, sh_coeffs: Tensor<B, 3>, session: usize) -> Tensor<B, 3> {
+        let [n, coeffs, _] = sh_coeffs.dims();
+        let transform = self
+            .transforms
+            .val()
+            .slice([session..session + 1, 0..3, 0..4])
+            .reshape([3, 4]);
+        let matrix = transform.clone().slice([0..3, 0..3]).transpose();
+        let offset = transform.slice([0..3, 3..4]).reshape([1, 3]);
+
+        // The base color is offset by 0.5, the rest of the coefficients are only scaled.
+        let dc = sh_coeffs.clone().slice([0..n, 0..1]).reshape([n, 3]);
+        let color = (dc * SH_C0 + 0.5).matmul(matrix.clone()) + offset;
+        let dc = ((color - 0.5) / SH_C0).unsqueeze_dim(1);
+        if coeffs == 1 {
+            return dc;
+        }
+        let rest = sh_coeffs
+            .slice([0..n, 1..coeffs])
+            .reshape([n * (coeffs - 1), 3])
+            .matmul(matrix)
+            .reshape([n, coeffs - 1, 3]);
+        Tensor::cat(vec![dc, rest], 1)
+    }
+
+    /// Bake the look of `session` into the colors of `splats`, eg. to export it.
+    pub fn bake(&self, splats: Splats<B>, session: usize) -> Splats<B> {
+        let sh_coeffs = self.apply(splats.sh_coeffs.val(), session);
+        Splats::from_tensor_data(
+            splats.means.val(),
+            splats.rotation.val(),
+            splats.log_scales.val(),
+            sh_coeffs,
+            splats.raw_opacity.val(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use burn::{
+        backend::{Wgpu, wgpu::WgpuDevice},
+        module::{Param, ParamId},
+        tensor::Tensor,
+    };
+
+    use super::Appearance;
+
+    #[test]
+    fn transforms_map_colors() {
+        let device = WgpuDevice::DefaultDevice;
+        let mut appearance = Appearance::<Wgpu>::new(2, &device);
+        let coeffs = Tensor::<Wgpu, 1>::from_floats([0.5, -0.2, 0.1, 0.3, 0.3, 0.3], &device)
+            .reshape([1, 2, 3]);
+
+        // The identity keeps the colors as they are.
+        let same = appearance.apply(coeffs.clone(), 1);
+        let diff = (same - coeffs.clone()).abs().max().into_scalar();
+        assert!(diff < 1e-5);
+
+        // Halving the colors of the second session halves the view dependent part, and the
+        // base color around 0.
+        let mut transforms = [[1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0]; 2];
+        for row in 0..3 {
+            transforms[1][row * 4 + row] = 0.5;
+        }
+        let transforms =
+            Tensor::<Wgpu, 1>::from_floats(transforms.as_flattened(), &device).reshape([2, 3, 4]);
+        appearance.transforms = Param::initialized(ParamId::new(), transforms);
+        let halved = appearance
+            .apply(coeffs, 1)
+            .into_data()
+            .into_vec::<f32>()
+            .expect("Wrong type");
+        let dc_color = |dc: f32| dc * brush_render::sh::SH_C0 + 0.5;
+        assert!((dc_color(halved[0]) - dc_color(0.5) * 0.5).abs() < 1e-5);
+        assert!((halved[3] - 0.15).abs() < 1e-5);
+    }
+}
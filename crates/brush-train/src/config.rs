@@ -8,6 +8,27 @@ pub struct TrainConfig {
     #[arg(long, help_heading = "Training options", default_value = "30000")]
     pub total_steps: u32,
 
+    /// Stop before total_steps once the training loss improves by less than this fraction
+    /// per 1000 steps. Only checked after growth has stopped. 0 disables early stopping.
+    #[config(default = 0.0)]
+    #[arg(long, help_heading = "Training options", default_value = "0.0")]
+    pub stop_min_improvement: f32,
+
+    /// Number of batches to average the gradients of before each optimizer step. This
+    /// emulates a larger batch size without needing more GPU memory, but each step takes as
+    /// long as this many steps.
+    #[config(default = 1)]
+    #[arg(long, help_heading = "Training options", default_value = "1")]
+    pub grad_accum: u32,
+
+    /// Store the optimizer moments in half precision, which takes half the memory. The
+    /// optimizer state is about as big as the splats themselves, so this makes room for more
+    /// splats on GPUs with little memory. GPUs without f16 support keep them in full
+    /// precision.
+    #[config(default = false)]
+    #[arg(long, help_heading = "Training options", default_value = "false")]
+    pub half_precision_optimizer: bool,
+
     /// Weight of SSIM loss (compared to l1 loss)
     #[config(default = 0.2)]
     #[clap(long, help_heading = "Training options", default_value = "0.2")]
@@ -18,17 +39,24 @@ pub struct TrainConfig {
     #[clap(long, help_heading = "Training options", default_value = "11")]
     pub ssim_window_size: usize,
 
-    /// Start learning rate for the mean parameters.
+    /// Size of the scene that spatial settings, like the learning rate of the means, are
+    /// relative to. Estimated from the spread of the cameras by default. Set this when the
+    /// estimate is off, for example when all images are taken from about the same spot.
+    #[arg(long, help_heading = "Training options")]
+    pub scene_extent: Option<f32>,
+
+    /// Start learning rate for the mean parameters, relative to the scene extent.
     #[config(default = 4e-5)]
     #[arg(long, help_heading = "Training options", default_value = "4e-5")]
     pub lr_mean: f64,
 
-    /// Start learning rate for the mean parameters.
+    /// End learning rate for the mean parameters, relative to the scene extent.
     #[config(default = 4e-7)]
     #[arg(long, help_heading = "Training options", default_value = "4e-7")]
     pub lr_mean_end: f64,
 
-    /// How much noise to add to the mean parameters of low opacity gaussians.
+    /// How much noise to add to the mean parameters of low opacity gaussians, relative to
+    /// their size.
     #[config(default = 1e4)]
     #[arg(long, help_heading = "Training options", default_value = "1e4")]
     pub mean_noise_weight: f32,
@@ -79,6 +107,13 @@ pub struct TrainConfig {
     #[arg(long, help_heading = "Refine options", default_value = "0.00085")]
     pub growth_grad_threshold: f32,
 
+    /// Automatically adjust the growth threshold so the number of splats reaches this count
+    /// by growth_stop_iter. growth_grad_threshold is then only the starting point. 0 keeps the
+    /// threshold fixed.
+    #[config(default = 0)]
+    #[arg(long, help_heading = "Refine options", default_value = "0")]
+    pub growth_target_splats: u32,
+
     /// What fraction of splats that are deemed as needing to grow do actually grow.
     /// Increase this to make splats grow more aggressively.
     #[config(default = 0.1)]
@@ -99,4 +134,61 @@ pub struct TrainConfig {
     #[config(default = 10000000)]
     #[arg(long, help_heading = "Refine options", default_value = "10000000")]
     pub max_splats: u32,
+
+    /// Prune splats whose total blend weight between refines is below this, measured in
+    /// pixels: 1.0 is as much as one fully opaque pixel. This removes splats that are never
+    /// visible, even if their opacity is high. 0 disables contribution pruning.
+    #[config(default = 0.0)]
+    #[arg(long, help_heading = "Refine options", default_value = "0.0")]
+    pub min_contribution: f32,
+
+    /// Max ratio between the largest and smallest axis of a splat. Thinner splats are widened
+    /// after every step, which stops needle like splats that shimmer when the view moves.
+    /// 0 allows any ratio.
+    #[config(default = 0.0)]
+    #[arg(long, help_heading = "Refine options", default_value = "0.0")]
+    pub max_anisotropy: f32,
+
+    /// Reorder the splats along a Z-order curve through their positions at the first refine
+    /// after every this many steps. Splats that are close in space are then close in memory,
+    /// which makes rendering them faster on large scenes. 0 never reorders.
+    #[config(default = 0)]
+    #[arg(long, help_heading = "Refine options", default_value = "0")]
+    pub morton_sort_every: u32,
+
+    /// Newly grown splats are not pruned for this many iterations, to give them a chance
+    /// to settle in.
+    #[config(default = 0)]
+    #[arg(long, help_heading = "Refine options", default_value = "0")]
+    pub new_splat_protect_iters: u32,
+
+    /// Multiplier on the position learning rate of splats that are still protected.
+    #[config(default = 1.0)]
+    #[arg(long, help_heading = "Refine options", default_value = "1.0")]
+    pub new_splat_lr_boost: f32,
+
+    /// When growth would go over max_splats, prune the least important splats to make room,
+    /// instead of stopping growth. Importance is opacity × volume × accumulated contribution.
+    #[config(default = false)]
+    #[arg(long, help_heading = "Refine options", default_value = "false")]
+    pub budget_pruning: bool,
+
+    /// Max fraction of max_splats that budget pruning removes in a single refine step.
+    #[config(default = 0.02)]
+    #[arg(long, help_heading = "Refine options", default_value = "0.02")]
+    pub budget_prune_fraction: f32,
+
+    /// Experimental: split the colors into an albedo, lit by a low order environment light
+    /// that is learned along with the splats. The splats then hold the albedo, and the light
+    /// is exported next to them, for crude relighting.
+    #[config(default = false)]
+    #[arg(long, help_heading = "Experimental options", default_value = "false")]
+    pub decompose_lighting: bool,
+
+    /// Experimental: learn a color transform per capture session, for datasets shot under
+    /// different lighting. Views are grouped into sessions by the folder their image is in.
+    /// The look of each session is exported next to the splats, and can be picked in the viewer.
+    #[config(default = false)]
+    #[arg(long, help_heading = "Experimental options", default_value = "false")]
+    pub appearance_per_session: bool,
 }
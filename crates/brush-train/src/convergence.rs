@@ -0,0 +1,123 @@
+//! Detects when training has stopped making meaningful progress.
+
+use std::collections::VecDeque;
+
+/// Steps over which the loss improvement is measured.
+pub const CONVERGENCE_WINDOW: u32 = 1000;
+
+/// Tracks the training loss to find the point where it flattens out.
+///
+/// The mean loss of the last [`CONVERGENCE_WINDOW`] steps is compared to the window
+/// before it. Once the relative improvement between the two drops below `min_improvement`
+/// training is considered converged. Averaging over whole windows smooths out the noise
+/// of single views, which can differ a lot in loss.
+pub struct ConvergenceMonitor {
+    min_improvement: f32,
+    first_iter: Option<u32>,
+    samples: VecDeque<(u32, f32)>,
+}
+
+impl ConvergenceMonitor {
+    pub fn new(min_improvement: f32) -> Self {
+        Self {
+            min_improvement,
+            first_iter: None,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Record the loss at an iteration. Iterations must be increasing.
+    pub fn add_loss(&mut self, iter: u32, loss: f32) {
+        self.first_iter.get_or_insert(iter);
+        self.samples.push_back((iter, loss));
+        let oldest = iter.saturating_sub(2 * CONVERGENCE_WINDOW);
+        while self.samples.front().is_some_and(|(i, _)| *i <= oldest) {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Relative loss improvement over the last window, if two full windows have been seen.
+    pub fn improvement(&self) -> Option<f32> {
+        let (last_iter, _) = *self.samples.back()?;
+        if last_iter < self.first_iter? + 2 * CONVERGENCE_WINDOW {
+            return None;
+        }
+        let split = last_iter - CONVERGENCE_WINDOW;
+
+        let mean = |window: &dyn Fn(u32) -> bool| {
+            let losses: Vec<f32> = self
+                .samples
+                .iter()
+                .filter(|(i, _)| window(*i))
+                .map(|(_, loss)| *loss)
+                .collect();
+            (!losses.is_empty()).then(|| losses.iter().sum::<f32>() / losses.len() as f32)
+        };
+        let recent = mean(&|i| i > split)?;
+        let previous = mean(&|i| i <= split)?;
+
+        if previous <= 0.0 {
+            return Some(0.0);
+        }
+        Some((previous - recent) / previous)
+    }
+
+    /// Whether the loss improvement fell below the threshold.
+    pub fn converged(&self) -> bool {
+        self.min_improvement > 0.0
+            && self
+                .improvement()
+                .is_some_and(|improvement| improvement < self.min_improvement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CONVERGENCE_WINDOW, ConvergenceMonitor};
+
+    #[test]
+    fn needs_two_windows() {
+        let mut monitor = ConvergenceMonitor::new(0.01);
+        for iter in (0..CONVERGENCE_WINDOW * 2).step_by(50) {
+            monitor.add_loss(iter, 1.0);
+        }
+        assert!(!monitor.converged());
+        monitor.add_loss(CONVERGENCE_WINDOW * 2, 1.0);
+        assert!(monitor.converged());
+    }
+
+    #[test]
+    fn keeps_going_while_improving() {
+        let mut monitor = ConvergenceMonitor::new(0.01);
+        for iter in (0..CONVERGENCE_WINDOW * 5).step_by(50) {
+            monitor.add_loss(iter, 1.0 / (1.0 + iter as f32 / 100.0));
+            assert!(!monitor.converged());
+        }
+    }
+
+    #[test]
+    fn stops_when_flat() {
+        let mut monitor = ConvergenceMonitor::new(0.01);
+        let mut stopped = None;
+        for iter in (0..CONVERGENCE_WINDOW * 30).step_by(50) {
+            // Decays towards a floor, with some per view noise.
+            let noise = if (iter / 50) % 2 == 0 { 0.01 } else { -0.01 };
+            monitor.add_loss(iter, 0.1 + (-(iter as f32) / 2000.0).exp() + noise);
+            if monitor.converged() {
+                stopped = Some(iter);
+                break;
+            }
+        }
+        let stopped = stopped.expect("Should converge");
+        assert!(stopped > CONVERGENCE_WINDOW * 4);
+    }
+
+    #[test]
+    fn disabled_never_stops() {
+        let mut monitor = ConvergenceMonitor::new(0.0);
+        for iter in (0..CONVERGENCE_WINDOW * 5).step_by(50) {
+            monitor.add_loss(iter, 1.0);
+        }
+        assert!(!monitor.converged());
+    }
+}
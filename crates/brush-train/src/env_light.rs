@@ -0,0 +1,135 @@
+//! Experimental decomposition of the splat colors into an albedo, lit by a low order
+//! environment light.
+//!
+//! The light is second order spherical harmonics of the irradiance, evaluated at the normal
+//! of each splat. The base (DC) color of the splats is then the albedo, and the higher SH
+//! orders still capture what the light can't explain, eg. reflections.
+
+use brush_render::{camera::Camera, gaussian_splats::Splats, sh::SH_C0};
+use burn::{
+    module::{Module, Param, ParamId},
+    prelude::Backend,
+    tensor::Tensor,
+};
+
+/// Number of SH coefficients per channel of the light.
+const LIGHT_COEFFS: usize = 9;
+
+#[derive(Module, Debug)]
+pub struct EnvLight<B: Backend> {
+    /// SH coefficients of the irradiance, [9, 3].
+    pub coeffs: Param<Tensor<B, 2>>,
+}
+
+impl<B: Backend> EnvLight<B> {
+    /// White light with the same irradiance from all directions, so the albedo starts out as
+    /// the current colors.
+    pub fn new(device: &B::Device) -> Self {
+        let mut coeffs = [[0.0; 3]; LIGHT_COEFFS];
+        coeffs[0] = [1.0 / SH_C0; 3];
+        Self::from_coeffs(coeffs, device)
+    }
+
+    pub fn from_coeffs(coeffs: [[f32; 3]; LIGHT_COEFFS], device: &B::Device) -> Self {
+        let coeffs =
+            Tensor::<B, 1>::from_floats(coeffs.as_flattened(), device).reshape([LIGHT_COEFFS, 3]);
+        Self {
+            coeffs: Param::initialized(ParamId::new(), coeffs.require_grad()),
+        }
+    }
+
+    /// Read back the coefficients, eg. to save them next to an export.
+    pub async fn to_coeffs(&self) -> Vec<[f32; 3]> {
+        let data = self
+            .coeffs
+            .val()
+            .into_data_async()
+            .await
+            .into_vec::<f32>()
+            .expect("Light coefficients must be floats");
+        data.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect()
+    }
+
+    /// RGB irradiance for unit normals of shape [N, 3].
+    pub fn irradiance(&self, normals: Tensor<B, 2>) -> Tensor<B, 2> {
+        let n = normals.dims()[0];
+        let [x, y, z] = [0, 1, 2].map(|i| normals.clone().slice([0..n, i..i + 1]));
+
+        // Same bases as the renderer uses for view dependent colors.
+        let c1 = 0.48860251190292;
+        let bases = [
+            Tensor::ones([n, 1], &normals.device()) * SH_C0,
+            y.clone() * -c1,
+            z.clone() * c1,
+            x.clone() * -c1,
+            x.clone() * y.clone() * (2.0 * 0.5462742152960395),
+            z.clone() * y.clone() * -1.092548430592079,
+            z.clone().powi_scalar(2) * 0.9461746957575601 - 0.3153915652525201,
+            z * x.clone() * -1.092548430592079,
+            (x.powi_scalar(2) - y.powi_scalar(2)) * 0.5462742152960395,
+        ];
+        Tensor::cat(bases.to_vec(), 1)
+            .matmul(self.coeffs.val())
+            .clamp_min(0.0)
+    }
+
+    /// SH coefficients of `splats` as seen from `camera`, with their base color used as the
+    /// albedo, lit by this light.
+    pub fn lit_coeffs(&self, splats: &Splats<B>, camera: &Camera) -> Tensor<B, 3> {
+        let [n, coeffs, _] = splats.sh_coeffs.dims();
+        let sh_coeffs = splats.sh_coeffs.val();
+
+        // The light shouldn't bend the splats to explain colors, only color them.
+        let normals = splats.normals_facing(camera).detach();
+        let albedo = sh_coeffs.clone().slice([0..n, 0..1]).reshape([n, 3]) * SH_C0 + 0.5;
+        let lit = albedo * self.irradiance(normals);
+        let dc = ((lit - 0.5) / SH_C0).unsqueeze_dim(1);
+
+        if coeffs == 1 {
+            dc
+        } else {
+            Tensor::cat(vec![dc, sh_coeffs.slice([0..n, 1..coeffs])], 1)
+        }
+    }
+
+    /// Light splats trained with this light as seen from `camera`, the way they're rendered
+    /// while training. Used to evaluate and view them.
+    ///
+    /// Lighting differs per view, as the normals are flipped towards the camera.
+    pub fn relight(&self, splats: Splats<B>, camera: &Camera) -> Splats<B> {
+        let sh_coeffs = self.lit_coeffs(&splats, camera);
+        Splats::from_tensor_data(
+            splats.means.val(),
+            splats.rotation.val(),
+            splats.log_scales.val(),
+            sh_coeffs,
+            splats.raw_opacity.val(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use burn::{
+        backend::{Wgpu, wgpu::WgpuDevice},
+        tensor::Tensor,
+    };
+
+    use super::EnvLight;
+
+    #[test]
+    fn default_light_is_uniform_white() {
+        let device = WgpuDevice::DefaultDevice;
+        let light = EnvLight::<Wgpu>::new(&device);
+        let normals = Tensor::<Wgpu, 1>::from_floats([0.0, 0.0, 1.0, 0.6, -0.8, 0.0], &device)
+            .reshape([2, 3]);
+        let irradiance = light
+            .irradiance(normals)
+            .into_data()
+            .into_vec::<f32>()
+            .expect("Wrong type");
+        for value in irradiance {
+            assert!((value - 1.0).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,26 @@
+//! Errors of training that the user can do something about.
+
+use brush_render::error::Suggestion;
+use thiserror::Error;
+
+const MB: u64 = 1024 * 1024;
+
+#[derive(Debug, Error)]
+pub enum TrainError {
+    /// The splats training starts from need a larger buffer than the device can allocate.
+    #[error(
+        "The initial splats need {} MB buffers, but the GPU allows at most {} MB",
+        .needed / MB,
+        .available / MB
+    )]
+    OutOfMemory { needed: u64, available: u64 },
+}
+
+impl TrainError {
+    /// What the user can do to fix the error.
+    pub fn suggestion(&self) -> Suggestion {
+        match self {
+            Self::OutOfMemory { .. } => Suggestion::OutOfMemory,
+        }
+    }
+}
@@ -0,0 +1,103 @@
+/// Adjusts the growth gradient threshold between refines, so that the number of splats
+/// reaches a target count by the time growth stops.
+///
+/// Each refine there's an ideal number of splats to add: what's left to reach the target,
+/// spread evenly over the remaining refines. When more splats than that cross the threshold
+/// it's raised, when fewer cross it it's lowered. Changes are damped and clamped per step so
+/// a single noisy refine can't throw the threshold off.
+#[derive(Clone, Debug)]
+pub(crate) struct GrowthController {
+    threshold: f32,
+    target_splats: u32,
+}
+
+/// Max factor the threshold changes by in one refine.
+const MAX_STEP: f32 = 2.0;
+/// Exponent applied to the ratio of actual vs. wanted growth.
+const GAIN: f32 = 0.5;
+
+impl GrowthController {
+    pub(crate) fn new(threshold: f32, target_splats: u32) -> Self {
+        Self {
+            threshold,
+            target_splats,
+        }
+    }
+
+    pub(crate) fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    /// Number of splats that should cross the threshold this refine to stay on track.
+    fn wanted_above_threshold(
+        &self,
+        num_splats: u32,
+        refines_left: u32,
+        select_fraction: f32,
+    ) -> f32 {
+        let remaining = self.target_splats.saturating_sub(num_splats) as f32;
+        remaining / refines_left.max(1) as f32 / select_fraction.max(1e-6)
+    }
+
+    /// Update the threshold after a refine, given how many splats were above it.
+    pub(crate) fn update(
+        &mut self,
+        num_above_threshold: u32,
+        num_splats: u32,
+        refines_left: u32,
+        select_fraction: f32,
+    ) {
+        if self.target_splats == 0 {
+            return;
+        }
+        let wanted = self.wanted_above_threshold(num_splats, refines_left, select_fraction);
+        let factor = if wanted < 1.0 {
+            // Target reached, stop growing.
+            MAX_STEP
+        } else {
+            (num_above_threshold as f32 / wanted)
+                .powf(GAIN)
+                .clamp(1.0 / MAX_STEP, MAX_STEP)
+        };
+        self.threshold *= factor;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GrowthController;
+
+    #[test]
+    fn disabled_keeps_threshold() {
+        let mut control = GrowthController::new(0.001, 0);
+        control.update(1_000_000, 0, 10, 0.1);
+        assert_eq!(control.threshold(), 0.001);
+    }
+
+    #[test]
+    fn adjusts_towards_target() {
+        // Wants 1000 splats over 10 refines -> 100 per refine -> 1000 above threshold.
+        let mut control = GrowthController::new(0.001, 1000);
+        control.update(4000, 0, 10, 0.1);
+        assert!(control.threshold() > 0.001);
+
+        let mut control = GrowthController::new(0.001, 1000);
+        control.update(250, 0, 10, 0.1);
+        assert!(control.threshold() < 0.001);
+
+        let mut control = GrowthController::new(0.001, 1000);
+        control.update(1000, 0, 10, 0.1);
+        assert_eq!(control.threshold(), 0.001);
+    }
+
+    #[test]
+    fn step_is_clamped() {
+        let mut control = GrowthController::new(0.001, 1000);
+        control.update(0, 0, 10, 0.1);
+        assert_eq!(control.threshold(), 0.0005);
+
+        let mut control = GrowthController::new(0.001, 1000);
+        control.update(10, 2000, 10, 0.1);
+        assert_eq!(control.threshold(), 0.002);
+    }
+}
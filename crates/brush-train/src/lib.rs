@@ -1,8 +1,16 @@
 #![recursion_limit = "256"]
+pub mod appearance;
 pub mod config;
+pub mod convergence;
+pub mod env_light;
+pub mod error;
+pub mod memory;
 pub mod train;
 
+mod adam_kernel;
 mod adam_scaled;
+mod growth_control;
+mod morton;
 mod multinomial;
 mod quat_vec;
 mod stats;
@@ -0,0 +1,211 @@
+//! How much GPU memory training uses: measured from the allocator while training, and
+//! rough estimates to pick limits that fit on a device before it starts.
+
+use brush_render::BBase;
+use burn::backend::wgpu::{WgpuDevice, WgpuRuntime};
+use burn::prelude::Backend;
+use burn::tensor::Tensor;
+use burn_cubecl::cubecl::Runtime;
+use wgpu::Limits;
+
+use crate::error::TrainError;
+use crate::train::InnerBack;
+
+const GIB: u64 = 1024 * 1024 * 1024;
+// Some adapters report a tiny or effectively unlimited buffer size, keep the budget sane.
+const MIN_MEMORY: u64 = GIB;
+const MAX_MEMORY: u64 = 64 * GIB;
+
+/// GPU memory used by each part of training, measured from the allocator.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MemoryUse {
+    /// The buffers of the splat parameters.
+    pub splats: u64,
+    /// The buffers of the Adam moments of the splat parameters.
+    pub optimizer: u64,
+    /// What rendering and the backward pass allocated on top of that, at the most.
+    pub render: u64,
+    /// The buffers of the images trained on.
+    pub images: u64,
+}
+
+/// Training measures its memory use every this many steps. Measuring waits for the GPU to
+/// finish its work, so it's not done every step.
+pub const MEASURE_MEMORY_EVERY: u32 = 50;
+
+/// Bytes the allocator has handed out, once all queued work is done.
+pub(crate) fn bytes_in_use(device: &WgpuDevice) -> u64 {
+    InnerBack::sync(device);
+    WgpuRuntime::client(device).memory_usage().bytes_in_use
+}
+
+/// Size of the buffer the allocator handed out for `tensor`.
+pub(crate) fn allocated_bytes<const D: usize>(tensor: Tensor<InnerBack, D>) -> u64 {
+    let tensor = tensor.into_primitive().tensor();
+    let client = tensor.client.clone();
+    client
+        .resolve_tensor_float::<BBase<u32>>(tensor)
+        .handle
+        .size()
+}
+
+/// Floats stored per splat: means, rotation, scales, opacity and SH coefficients.
+fn floats_per_splat(sh_degree: u32) -> u64 {
+    let coeffs = (sh_degree as u64 + 1).pow(2);
+    3 + 4 + 3 + 1 + coeffs * 3
+}
+
+/// Bytes used per splat while training.
+///
+/// Each parameter has a gradient and two Adam moments, and the renderer keeps a projected
+/// copy of each splat (~16 floats) and on average a handful of tile intersections.
+pub fn splat_train_bytes(sh_degree: u32) -> u64 {
+    let params = floats_per_splat(sh_degree) * 4;
+    4 * params + 16 * 4 + 10 * 8
+}
+
+/// Bytes used per pixel of the training images.
+///
+/// Ground truth, prediction and their gradients, and the SSIM intermediates.
+pub fn pixel_train_bytes() -> u64 {
+    10 * 4 * 4
+}
+
+/// Estimate of the peak GPU memory used when training with these limits.
+pub fn estimate_train_bytes(max_splats: u32, sh_degree: u32, max_resolution: u32) -> u64 {
+    let pixels = max_resolution as u64 * max_resolution as u64;
+    max_splats as u64 * splat_train_bytes(sh_degree) + pixels * pixel_train_bytes()
+}
+
+/// Bytes of the SH coefficients of a splat. These are the largest buffers of training, along
+/// with their gradient and Adam moments.
+fn sh_bytes_per_splat(sh_degree: u32) -> u64 {
+    (sh_degree as u64 + 1).pow(2) * 3 * 4
+}
+
+/// Lower `max_splats` to what fits in the largest buffer the device can allocate at once,
+/// with a warning. Going over the limit would otherwise only fail once the splats have grown
+/// that far.
+pub fn clamp_to_buffer_limit(max_splats: u32, sh_degree: u32, max_buffer_bytes: u64) -> u32 {
+    let fits = (max_buffer_bytes / sh_bytes_per_splat(sh_degree)).min(u32::MAX as u64) as u32;
+    if max_splats > fits {
+        log::warn!(
+            "Lowering the max number of splats from {max_splats} to {fits}, the GPU can't allocate buffers for more at SH degree {sh_degree}"
+        );
+    }
+    max_splats.min(fits)
+}
+
+/// Check that the buffers of `num_splats` splats, eg. the splats training starts from, fit in
+/// what the device can allocate at once.
+pub fn check_buffer_limit(
+    num_splats: u32,
+    sh_degree: u32,
+    max_buffer_bytes: u64,
+) -> Result<(), TrainError> {
+    let needed = num_splats as u64 * sh_bytes_per_splat(sh_degree);
+    if needed > max_buffer_bytes {
+        return Err(TrainError::OutOfMemory {
+            needed,
+            available: max_buffer_bytes,
+        });
+    }
+    Ok(())
+}
+
+/// How many splats budget pruning removes to make room for growing `wanted` splats, when
+/// there are `num_splats` of at most `max_splats`. Growth that fits under the max needs no
+/// room, and at most `max_fraction` of `max_splats` is pruned at once.
+pub(crate) fn budget_prune_count(
+    wanted: u32,
+    num_splats: u32,
+    max_splats: u32,
+    max_fraction: f32,
+) -> u32 {
+    let free = max_splats.saturating_sub(num_splats);
+    let max_prune = (max_splats as f32 * max_fraction) as u32;
+    wanted.saturating_sub(free).min(max_prune)
+}
+
+/// How many of `wanted` splats can grow without going over `max_splats`.
+pub(crate) fn clamp_growth(wanted: u32, num_splats: u32, max_splats: u32) -> u32 {
+    wanted.min(max_splats.saturating_sub(num_splats))
+}
+
+/// How much GPU memory is available for training, going by the limits of the adapter.
+///
+/// wgpu doesn't report the memory size of a device. The largest buffer it can allocate is
+/// the closest hint: drivers cap it at the size of the device memory, or some part of it.
+/// Part of that is kept free for the UI and other apps.
+pub fn memory_budget(limits: &Limits) -> u64 {
+    let total = limits.max_buffer_size.clamp(MIN_MEMORY, MAX_MEMORY);
+    total / 4 * 3
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LimitSuggestion {
+    pub max_splats: u32,
+    pub max_resolution: u32,
+}
+
+/// Suggest limits that are predicted to fit in the given memory budget.
+///
+/// The resolution is capped so images take at most a quarter of the budget, the rest of the
+/// budget goes to splats.
+pub fn suggest_limits(budget: u64, sh_degree: u32, max_resolution: u32) -> LimitSuggestion {
+    let max_pixels = budget / 4 / pixel_train_bytes();
+    let max_resolution = max_resolution.min(max_pixels.isqrt() as u32).max(256);
+
+    let image_bytes = max_resolution as u64 * max_resolution as u64 * pixel_train_bytes();
+    let splats = budget.saturating_sub(image_bytes) / splat_train_bytes(sh_degree);
+    // Round down to a multiple of 100k to make it look less precise than it is.
+    let max_splats = (splats / 100_000 * 100_000).clamp(100_000, u32::MAX as u64) as u32;
+
+    LimitSuggestion {
+        max_splats,
+        max_resolution,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{budget_prune_count, check_buffer_limit, clamp_growth, clamp_to_buffer_limit};
+    use crate::error::TrainError;
+
+    #[test]
+    fn max_splats_are_clamped_to_buffer_limit() {
+        // Each splat at SH degree 3 needs 16 * 3 * 4 bytes of SH coefficients.
+        let limit = 1024 * 1024 * 1024;
+        assert_eq!(clamp_to_buffer_limit(10_000_000, 0, limit), 10_000_000);
+        assert_eq!(clamp_to_buffer_limit(10_000_000, 3, limit), 5_592_405);
+    }
+
+    #[test]
+    fn sh_buffer_over_limit_is_reported() {
+        // 10M splats at SH degree 3 need 10M * 16 * 3 * 4 bytes of SH coefficients.
+        let limit = 1024 * 1024 * 1024;
+        assert!(check_buffer_limit(10_000_000, 0, limit).is_ok());
+        let err = check_buffer_limit(10_000_000, 3, limit).expect_err("Should be over the limit");
+        let TrainError::OutOfMemory { needed, available } = err;
+        assert_eq!(needed, 1_920_000_000);
+        assert_eq!(available, limit);
+    }
+
+    #[test]
+    fn budget_pruning_makes_room_for_growth() {
+        // Growth that fits under the max doesn't prune anything.
+        assert_eq!(budget_prune_count(100, 900, 1000, 0.02), 0);
+        // Only what doesn't fit is pruned.
+        assert_eq!(budget_prune_count(10, 995, 1000, 0.02), 5);
+        // At most the fraction of the max is pruned at once.
+        assert_eq!(budget_prune_count(100, 1000, 1000, 0.02), 20);
+        assert_eq!(budget_prune_count(100, 1100, 1000, 0.02), 20);
+    }
+
+    #[test]
+    fn growth_is_clamped_to_max_splats() {
+        assert_eq!(clamp_growth(50, 900, 1000), 50);
+        assert_eq!(clamp_growth(50, 990, 1000), 10);
+        assert_eq!(clamp_growth(50, 1010, 1000), 0);
+    }
+}
@@ -0,0 +1,65 @@
+use glam::{UVec3, Vec3};
+
+/// Bits per axis of a 64 bit Morton code.
+const BITS: u32 = 21;
+
+/// Spread the lower 21 bits of `x` out to every third bit.
+fn spread_bits(x: u32) -> u64 {
+    let mut x = (x & ((1 << BITS) - 1)) as u64;
+    x = (x | (x << 32)) & 0x001f_0000_0000_ffff;
+    x = (x | (x << 16)) & 0x001f_0000_ff00_00ff;
+    x = (x | (x << 8)) & 0x100f_00f0_0f00_f00f;
+    x = (x | (x << 4)) & 0x10c3_0c30_c30c_30c3;
+    x = (x | (x << 2)) & 0x1249_2492_4924_9249;
+    x
+}
+
+fn morton_code(p: UVec3) -> u64 {
+    spread_bits(p.x) | (spread_bits(p.y) << 1) | (spread_bits(p.z) << 2)
+}
+
+/// Indices that sort `positions` along a Z-order curve, so positions that are close in space
+/// end up close together.
+pub(crate) fn morton_order(positions: &[Vec3]) -> Vec<i32> {
+    let (min, max) = positions
+        .iter()
+        .fold((Vec3::MAX, Vec3::MIN), |(min, max), p| {
+            (min.min(*p), max.max(*p))
+        });
+    let scale = ((1 << BITS) - 1) as f32 / (max - min).max_element().max(f32::EPSILON);
+
+    let mut codes: Vec<(u64, i32)> = positions
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (morton_code(((*p - min) * scale).as_uvec3()), i as i32))
+        .collect();
+    codes.sort_unstable_by_key(|(code, _)| *code);
+    codes.into_iter().map(|(_, i)| i).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{UVec3, Vec3};
+
+    use super::{morton_code, morton_order};
+
+    #[test]
+    fn interleaves_bits() {
+        assert_eq!(morton_code(UVec3::new(1, 0, 0)), 0b001);
+        assert_eq!(morton_code(UVec3::new(0, 1, 0)), 0b010);
+        assert_eq!(morton_code(UVec3::new(0, 0, 1)), 0b100);
+        assert_eq!(morton_code(UVec3::new(3, 0, 0)), 0b001_001);
+        assert_eq!(morton_code(UVec3::splat((1 << 21) - 1)), u64::MAX >> 1);
+    }
+
+    #[test]
+    fn nearby_positions_are_adjacent() {
+        let positions = [
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(10.0, 10.0, 10.0),
+            Vec3::new(0.1, 0.0, 0.0),
+            Vec3::new(10.0, 10.1, 10.0),
+        ];
+        assert_eq!(morton_order(&positions), [0, 2, 1, 3]);
+    }
+}
@@ -16,14 +16,70 @@ pub(crate) struct RefineRecord<B: Backend> {
     // Helper tensors for accumulating the viewspace_xy gradients and the number
     // of observations per gaussian. Used in pruning and densification.
     pub refine_weight_norm: Tensor<B, 1>,
+    // Total blend weight of each gaussian over all pixels it was rendered to. Gaussians
+    // with a near zero contribution are invisible, even if their opacity is high.
+    pub contribution: Tensor<B, 1>,
+    // Iteration each gaussian was created at, 0 for the initial gaussians.
+    // Unlike the stats above, this is kept between refines.
+    pub birth_iter: Tensor<B, 1, Int>,
 }
 
 impl<B: Backend> RefineRecord<B> {
     pub(crate) fn new(num_points: u32, device: &B::Device) -> Self {
         Self {
             refine_weight_norm: Tensor::<B, 1>::zeros([num_points as usize], device),
+            contribution: Tensor::<B, 1>::zeros([num_points as usize], device),
+            birth_iter: Tensor::<B, 1, Int>::zeros([num_points as usize], device),
         }
     }
+
+    /// Reset the stats for the next refine, keeping the ages of the gaussians.
+    pub(crate) fn next_round(self) -> Self {
+        let device = self.birth_iter.device();
+        let num_points = self.birth_iter.dims()[0];
+        Self {
+            refine_weight_norm: Tensor::zeros([num_points], &device),
+            contribution: Tensor::zeros([num_points], &device),
+            birth_iter: self.birth_iter,
+        }
+    }
+
+    /// Mask of gaussians created less than `iters` ago.
+    pub(crate) fn younger_than(&self, iter: u32, iters: u32) -> Tensor<B, 1, Bool> {
+        let since = iter.saturating_sub(iters) as i32;
+        self.birth_iter
+            .clone()
+            .greater_elem(since)
+            .bool_and(self.birth_iter.clone().greater_elem(0))
+    }
+
+    /// Mask of gaussians below `min_raw_opacity`, except those created less than
+    /// `protect_iters` ago.
+    pub(crate) fn transparent(
+        &self,
+        raw_opacity: Tensor<B, 1>,
+        min_raw_opacity: f32,
+        iter: u32,
+        protect_iters: u32,
+    ) -> Tensor<B, 1, Bool> {
+        raw_opacity
+            .lower_elem(min_raw_opacity)
+            .bool_and(self.younger_than(iter, protect_iters).bool_not())
+    }
+
+    /// Mask of gaussians that contributed less than `min_contribution` to the images, except
+    /// those created less than `protect_iters` ago.
+    pub(crate) fn invisible(
+        &self,
+        min_contribution: f32,
+        iter: u32,
+        protect_iters: u32,
+    ) -> Tensor<B, 1, Bool> {
+        self.contribution
+            .clone()
+            .lower_elem(min_contribution)
+            .bool_and(self.younger_than(iter, protect_iters).bool_not())
+    }
 }
 
 impl<BT: BoolElement> RefineRecord<Fused<BT>> {
@@ -54,6 +110,8 @@ impl<BT: BoolElement> RefineRecord<Fused<BT>> {
         let refine_accum = client.resolve_tensor_float::<BBase<BT>>(
             self.refine_weight_norm.clone().into_primitive().tensor(),
         );
+        let contribution_accum = client
+            .resolve_tensor_float::<BBase<BT>>(self.contribution.clone().into_primitive().tensor());
 
         const WG_SIZE: u32 = 256;
         // Execute lazily the kernel with the launch information and the given buffers. For
@@ -68,8 +126,9 @@ impl<BT: BoolElement> RefineRecord<Fused<BT>> {
             CubeDim::new(WG_SIZE, 1, 1),
             compact_gid.as_tensor_arg::<u32>(1),
             num_visible.as_tensor_arg::<u32>(1),
-            refine_weight.as_tensor_arg::<f32>(2),
+            refine_weight.as_tensor_arg::<f32>(1),
             refine_accum.as_tensor_arg::<f32>(1),
+            contribution_accum.as_tensor_arg::<f32>(1),
             w,
             h,
         );
@@ -79,7 +138,76 @@ impl<BT: BoolElement> RefineRecord<Fused<BT>> {
 impl<B: Backend> RefineRecord<B> {
     pub fn keep(self, indices: Tensor<B, 1, Int>) -> Self {
         Self {
-            refine_weight_norm: self.refine_weight_norm.select(0, indices),
+            refine_weight_norm: self.refine_weight_norm.select(0, indices.clone()),
+            contribution: self.contribution.select(0, indices.clone()),
+            birth_iter: self.birth_iter.select(0, indices),
         }
     }
+
+    /// Add records for `count` new gaussians, created at `iter`.
+    pub fn add_born(self, count: usize, iter: u32) -> Self {
+        let device = self.birth_iter.device();
+        Self {
+            refine_weight_norm: Tensor::cat(
+                vec![self.refine_weight_norm, Tensor::zeros([count], &device)],
+                0,
+            ),
+            contribution: Tensor::cat(vec![self.contribution, Tensor::zeros([count], &device)], 0),
+            birth_iter: Tensor::cat(
+                vec![self.birth_iter, Tensor::full([count], iter as i32, &device)],
+                0,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use burn::{
+        backend::{Wgpu, wgpu::WgpuDevice},
+        tensor::{Bool, Tensor},
+    };
+
+    use super::RefineRecord;
+
+    fn mask(mask: Tensor<Wgpu, 1, Bool>) -> Vec<i32> {
+        mask.int()
+            .into_data()
+            .into_vec::<i32>()
+            .expect("Wrong type")
+    }
+
+    #[test]
+    fn young_gaussians_are_protected() {
+        let device = WgpuDevice::DefaultDevice;
+        let record = RefineRecord::<Wgpu>::new(2, &device).add_born(2, 100);
+        // The initial gaussians are never young.
+        assert_eq!(mask(record.younger_than(150, 100)), [0, 0, 1, 1]);
+        assert_eq!(mask(record.younger_than(200, 100)), [0, 0, 0, 0]);
+        assert_eq!(mask(record.younger_than(150, 0)), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn transparent_gaussians_are_pruned_unless_young() {
+        let device = WgpuDevice::DefaultDevice;
+        let record = RefineRecord::<Wgpu>::new(2, &device).add_born(2, 100);
+        let raw_opacity = Tensor::<Wgpu, 1>::from_floats([-10.0, 5.0, -10.0, 5.0], &device);
+        assert_eq!(
+            mask(record.transparent(raw_opacity.clone(), -5.0, 150, 100)),
+            [1, 0, 0, 0]
+        );
+        assert_eq!(
+            mask(record.transparent(raw_opacity, -5.0, 250, 100)),
+            [1, 0, 1, 0]
+        );
+    }
+
+    #[test]
+    fn invisible_gaussians_are_pruned_unless_young() {
+        let device = WgpuDevice::DefaultDevice;
+        let mut record = RefineRecord::<Wgpu>::new(2, &device).add_born(2, 100);
+        record.contribution = Tensor::from_floats([0.0, 2.0, 2.0, 0.0], &device);
+        assert_eq!(mask(record.invisible(0.5, 150, 100)), [1, 0, 0, 0]);
+        assert_eq!(mask(record.invisible(0.5, 250, 100)), [1, 0, 0, 1]);
+    }
 }
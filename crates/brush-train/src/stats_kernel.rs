@@ -2,12 +2,13 @@ use burn_cubecl::cubecl;
 use burn_cubecl::cubecl::{cube, prelude::*};
 
 #[cube(launch)]
-#[allow(clippy::useless_conversion)]
 pub fn stats_gather_kernel(
     gs_ids: &Tensor<u32>,
     num_visible: &Tensor<u32>,
-    refine_weight: &Tensor<Line<f32>>,
+    // Per splat the xy gradient, and the summed blend weight over all pixels.
+    refine_weight: &Tensor<f32>,
     accum_refine_weight: &mut Tensor<f32>,
+    accum_contribution: &mut Tensor<f32>,
     #[comptime] w: u32,
     #[comptime] h: u32,
 ) {
@@ -20,14 +21,10 @@ pub fn stats_gather_kernel(
 
     let global_gid = gs_ids[compact_gid];
 
-    let mut line: Line<f32> = Line::empty(2u32);
-    // Nb: Clippy reports a warning here about a useless conversion but it's wrong.
-    line[0] = comptime!(w as f32 / 2.0);
-    line[1] = comptime!(h as f32 / 2.0);
-
-    let refine_grads = refine_weight[compact_gid] * line;
-    let refine_norm =
-        f32::sqrt(refine_grads[0] * refine_grads[0] + refine_grads[1] * refine_grads[1]);
+    let grad_x = refine_weight[compact_gid * 3] * comptime!(w as f32 / 2.0);
+    let grad_y = refine_weight[compact_gid * 3 + 1] * comptime!(h as f32 / 2.0);
+    let refine_norm = f32::sqrt(grad_x * grad_x + grad_y * grad_y);
 
     accum_refine_weight[global_gid] = f32::max(accum_refine_weight[global_gid], refine_norm);
+    accum_contribution[global_gid] += refine_weight[compact_gid * 3 + 2];
 }
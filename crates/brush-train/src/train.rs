@@ -7,12 +7,15 @@ use burn::{
         LrScheduler,
         exponential::{ExponentialLrScheduler, ExponentialLrSchedulerConfig},
     },
-    module::ParamId,
-    optim::{GradientsParams, Optimizer, adaptor::OptimizerAdaptor, record::AdaptorRecord},
+    module::{Param, ParamId},
+    optim::{
+        Adam, AdamConfig, GradientsParams, Optimizer, adaptor::OptimizerAdaptor,
+        record::AdaptorRecord,
+    },
     prelude::Backend,
     tensor::{
-        Bool, Distribution, Int, Tensor, TensorData, TensorPrimitive, activation::sigmoid,
-        backend::AutodiffBackend,
+        Bool, DType, Distribution, Int, Tensor, TensorData, TensorPrimitive, activation::sigmoid,
+        backend::AutodiffBackend, ops::IntTensor,
     },
 };
 use burn_cubecl::cubecl::Runtime;
@@ -24,10 +27,19 @@ use brush_render::sh::sh_coeffs_for_degree;
 use brush_render_bwd::burn_glue::SplatForwardDiff;
 use brush_ssim::Ssim;
 use hashbrown::{HashMap, HashSet};
+use serde::Serialize;
 use tracing::trace_span;
 
-use crate::adam_scaled::{AdamScaled, AdamScaledConfig, AdamState};
+use crate::adam_scaled::{AdamScaled, AdamScaledConfig, AdamState, full_precision_moments};
+use crate::appearance::Appearance;
 use crate::config::TrainConfig;
+use crate::env_light::EnvLight;
+use crate::growth_control::GrowthController;
+use crate::memory::{
+    MEASURE_MEMORY_EVERY, MemoryUse, allocated_bytes, budget_prune_count, bytes_in_use,
+    clamp_growth,
+};
+use crate::morton::morton_order;
 use crate::multinomial::multinomial_sample;
 use crate::quat_vec::quaternion_vec_multiply;
 use crate::stats::RefineRecord;
@@ -37,10 +49,22 @@ const MIN_OPACITY: f32 = 0.9 / 255.0;
 pub type InnerBack = Wgpu;
 pub type TrainBack = Autodiff<InnerBack>;
 
-#[derive(Clone)]
+#[derive(Clone, Debug, Serialize)]
 pub struct RefineStats {
     pub num_added: u32,
+    /// Total number of pruned splats, for any of the reasons below.
     pub num_pruned: u32,
+    /// Pruned as their opacity dropped below the minimum.
+    pub num_transparent: u32,
+    /// Pruned as they barely contributed to any pixel.
+    pub num_invisible: u32,
+    /// Pruned to make room for growth.
+    pub num_budget_pruned: u32,
+    /// Number of splats with a gradient above the growth threshold.
+    pub num_above_threshold: u32,
+    pub growth_grad_threshold: f32,
+    /// Number of splats after refining.
+    pub num_splats: u32,
 }
 
 #[derive(Clone)]
@@ -49,16 +73,62 @@ pub struct TrainStepStats<B: Backend> {
 
     pub num_intersections: Tensor<B, 1, Int>,
     pub num_visible: Tensor<B, 1, Int>,
+    /// Loss of the step, the mean of the loss of each batch.
     pub loss: Tensor<B, 1>,
+    pub batch_losses: Vec<Tensor<B, 1>>,
 
     pub lr_mean: f64,
     pub lr_rotation: f64,
     pub lr_scale: f64,
     pub lr_coeffs: f64,
     pub lr_opac: f64,
+
+    /// Memory use of this step, only measured every [`MEASURE_MEMORY_EVERY`] steps.
+    pub memory: Option<MemoryUse>,
+}
+
+/// What rendering a single batch produced.
+struct TrainPass {
+    pred_image: Tensor<TrainBack, 3>,
+    visible: Tensor<TrainBack, 1>,
+    num_visible: IntTensor<TrainBack>,
+    num_intersections: IntTensor<TrainBack>,
+    loss: Tensor<TrainBack, 1>,
+}
+
+/// Add the gradient of `param` to the sum of its gradients in `sum`.
+fn accumulate_grad<const D: usize>(
+    sum: &mut GradientsParams,
+    param: &Param<Tensor<TrainBack, D>>,
+    grads: &mut <TrainBack as AutodiffBackend>::Gradients,
+) {
+    let Some(grad) = param.val().grad_remove(grads) else {
+        return;
+    };
+    let grad = match sum.remove::<InnerBack, D>(param.id) {
+        Some(prev) => prev + grad,
+        None => grad,
+    };
+    sum.register(param.id, grad);
+}
+
+/// The average gradient of one parameter, out of the gradients summed over `count` batches.
+fn take_grad<const D: usize>(
+    sum: &mut GradientsParams,
+    id: ParamId,
+    count: usize,
+) -> GradientsParams {
+    let mut grads = GradientsParams::new();
+    if let Some(grad) = sum.remove::<InnerBack, D>(id) {
+        let grad = if count > 1 { grad / count as f32 } else { grad };
+        grads.register(id, grad);
+    }
+    grads
 }
 
 type OptimizerType = OptimizerAdaptor<AdamScaled, Splats<TrainBack>, TrainBack>;
+type LightOptimizerType = OptimizerAdaptor<Adam, EnvLight<TrainBack>, TrainBack>;
+type AppearanceOptimizerType = OptimizerAdaptor<Adam, Appearance<TrainBack>, TrainBack>;
 
 pub struct SplatTrainer {
     config: TrainConfig,
@@ -67,18 +137,38 @@ pub struct SplatTrainer {
     ssim: Ssim<TrainBack>,
     refine_record: Option<RefineRecord<InnerBack>>,
     optim: Option<OptimizerType>,
+    growth_control: GrowthController,
+    // Only with decompose_lighting.
+    env_light: Option<(EnvLight<TrainBack>, LightOptimizerType)>,
+    // Only with appearance_per_session, see `learn_appearance`.
+    appearance: Option<(Appearance<TrainBack>, AppearanceOptimizerType)>,
+    // Capture session of each view.
+    view_sessions: Vec<usize>,
+    // Most bytes in use seen during the passes of a step that measures memory.
+    passes_peak_bytes: Option<u64>,
 }
 
 pub fn inv_sigmoid<B: Backend>(x: Tensor<B, 1>) -> Tensor<B, 1> {
     (x.clone() / (-x + 1.0)).log()
 }
 
-fn create_default_optimizer() -> OptimizerType {
-    AdamScaledConfig::new().with_epsilon(1e-15).init()
+fn create_default_optimizer(config: &TrainConfig) -> OptimizerType {
+    AdamScaledConfig::new()
+        .with_epsilon(1e-15)
+        .with_half_precision_moments(config.half_precision_optimizer)
+        .init()
 }
 
 impl SplatTrainer {
     pub fn new(config: &TrainConfig, device: &WgpuDevice) -> Self {
+        let mut config = config.clone();
+        if config.half_precision_optimizer && !InnerBack::supports_dtype(device, DType::F16) {
+            log::warn!(
+                "This GPU doesn't support f16, keeping the optimizer moments in full precision"
+            );
+            config.half_precision_optimizer = false;
+        }
+
         let ssim = Ssim::new(config.ssim_window_size, 3, device);
 
         let decay = (config.lr_mean_end / config.lr_mean).powf(1.0 / config.total_steps as f64);
@@ -88,28 +178,78 @@ impl SplatTrainer {
         let lr_scale = ExponentialLrSchedulerConfig::new(config.lr_scale, decay);
 
         Self {
-            config: config.clone(),
             sched_mean: lr_mean.init().expect("Mean lr schedule must be valid."),
             sched_scale: lr_scale.init().expect("Scale lr schedule must be valid."),
             optim: None,
             refine_record: None,
+            growth_control: GrowthController::new(
+                config.growth_grad_threshold,
+                config.growth_target_splats.min(config.max_splats),
+            ),
             ssim,
+            env_light: config
+                .decompose_lighting
+                .then(|| (EnvLight::new(device), AdamConfig::new().init())),
+            appearance: None,
+            view_sessions: vec![],
+            passes_peak_bytes: None,
+            config,
         }
     }
 
-    pub fn step(
+    /// Learn a color transform for each capture session, given the session of each view.
+    /// Only with appearance_per_session, and when there's more than one session.
+    pub fn learn_appearance(&mut self, view_sessions: Vec<usize>, device: &WgpuDevice) {
+        let sessions = view_sessions.iter().max().map_or(0, |s| s + 1);
+        if !self.config.appearance_per_session || sessions < 2 {
+            return;
+        }
+        self.appearance = Some((Appearance::new(sessions, device), AdamConfig::new().init()));
+        self.view_sessions = view_sessions;
+    }
+
+    /// The learned environment light, when decomposing the lighting.
+    pub fn env_light(&self) -> Option<&EnvLight<TrainBack>> {
+        self.env_light.as_ref().map(|(light, _)| light)
+    }
+
+    /// The learned color transforms of the capture sessions, when learning them.
+    pub fn appearance(&self) -> Option<&Appearance<TrainBack>> {
+        self.appearance.as_ref().map(|(appearance, _)| appearance)
+    }
+
+    /// Note the bytes in use, when measuring the memory use of this step.
+    fn sample_peak_bytes(&mut self, device: &WgpuDevice) {
+        if let Some(peak) = &mut self.passes_peak_bytes {
+            *peak = (*peak).max(bytes_in_use(device));
+        }
+    }
+
+    /// Render a batch, and add the gradients of its loss to `grads`, `light_grads` and
+    /// `appearance_grads`.
+    fn forward_backward(
         &mut self,
-        scene_extent: f32,
-        iter: u32,
         batch: &SceneBatch<TrainBack>,
-        splats: Splats<TrainBack>,
-    ) -> (Splats<TrainBack>, TrainStepStats<TrainBack>) {
-        let mut splats = splats;
-
+        splats: &Splats<TrainBack>,
+        train_t: f32,
+        grads: &mut GradientsParams,
+        light_grads: &mut GradientsParams,
+        appearance_grads: &mut GradientsParams,
+    ) -> TrainPass {
         let [img_h, img_w, _] = batch.img_tensor.dims();
         let camera = &batch.camera;
 
         let current_opacity = splats.opacities();
+        let sh_coeffs = match &self.env_light {
+            Some((light, _)) => light.lit_coeffs(splats, camera),
+            None => splats.sh_coeffs.val(),
+        };
+        let sh_coeffs = match &self.appearance {
+            Some((appearance, _)) => {
+                appearance.apply(sh_coeffs, self.view_sessions[batch.view_index])
+            }
+            None => sh_coeffs,
+        };
 
         let (
             pred_image,
@@ -125,7 +265,7 @@ impl SplatTrainer {
                 splats.means.val().into_primitive().tensor(),
                 splats.log_scales.val().into_primitive().tensor(),
                 splats.rotation.val().into_primitive().tensor(),
-                splats.sh_coeffs.val().into_primitive().tensor(),
+                sh_coeffs.into_primitive().tensor(),
                 current_opacity.clone().into_primitive().tensor(),
             );
             let img = Tensor::from_primitive(TensorPrimitive::Float(diff_out.img));
@@ -139,7 +279,7 @@ impl SplatTrainer {
             )
         };
 
-        let train_t = (iter as f32 / self.config.total_steps as f32).clamp(0.0, 1.0);
+        self.sample_peak_bytes(&splats.device());
 
         let _span = trace_span!("Calculate losses", sync_burn = true).entered();
 
@@ -182,7 +322,102 @@ impl SplatTrainer {
             loss
         };
 
-        let mut grads = trace_span!("Backward pass", sync_burn = true).in_scope(|| loss.backward());
+        let mut autodiff_grads =
+            trace_span!("Backward pass", sync_burn = true).in_scope(|| loss.clone().backward());
+        self.sample_peak_bytes(&splats.device());
+
+        accumulate_grad(grads, &splats.sh_coeffs, &mut autodiff_grads);
+        accumulate_grad(grads, &splats.rotation, &mut autodiff_grads);
+        accumulate_grad(grads, &splats.log_scales, &mut autodiff_grads);
+        accumulate_grad(grads, &splats.means, &mut autodiff_grads);
+        accumulate_grad(grads, &splats.raw_opacity, &mut autodiff_grads);
+        if let Some((light, _)) = &self.env_light {
+            accumulate_grad(light_grads, &light.coeffs, &mut autodiff_grads);
+        }
+        if let Some((appearance, _)) = &self.appearance {
+            accumulate_grad(
+                appearance_grads,
+                &appearance.transforms,
+                &mut autodiff_grads,
+            );
+        }
+
+        trace_span!("Housekeeping", sync_burn = true).in_scope(|| {
+            // Get the xy gradient norm from the dummy tensor.
+            let refine_weight = refine_weight_holder
+                .grad_remove(&mut autodiff_grads)
+                .expect("XY gradients need to be calculated.");
+
+            let device = splats.device();
+            let num_splats = splats.num_splats();
+            let record = self
+                .refine_record
+                .get_or_insert_with(|| RefineRecord::new(num_splats, &device));
+
+            record.gather_stats(
+                refine_weight,
+                glam::uvec2(img_w as u32, img_h as u32),
+                global_from_compact_gid,
+                num_visible.clone(),
+            );
+        });
+
+        TrainPass {
+            pred_image,
+            visible,
+            num_visible,
+            num_intersections,
+            loss,
+        }
+    }
+
+    /// Train on `batches`, with one optimizer step on the average of their gradients. More
+    /// than one batch emulates a larger batch size, without the memory cost of rendering
+    /// them all at once.
+    pub fn step(
+        &mut self,
+        scene_extent: f32,
+        iter: u32,
+        batches: &[SceneBatch<TrainBack>],
+        splats: Splats<TrainBack>,
+    ) -> (Splats<TrainBack>, TrainStepStats<TrainBack>) {
+        let mut splats = splats;
+        let train_t = (iter as f32 / self.config.total_steps as f32).clamp(0.0, 1.0);
+
+        let measure_memory = iter % MEASURE_MEMORY_EVERY == 0;
+        let bytes_before = measure_memory.then(|| bytes_in_use(&splats.device()));
+        self.passes_peak_bytes = bytes_before;
+
+        let count = batches.len();
+        let mut grads = GradientsParams::new();
+        let mut light_grads = GradientsParams::new();
+        let mut appearance_grads = GradientsParams::new();
+        let mut passes: Vec<_> = batches
+            .iter()
+            .map(|batch| {
+                self.forward_backward(
+                    batch,
+                    &splats,
+                    train_t,
+                    &mut grads,
+                    &mut light_grads,
+                    &mut appearance_grads,
+                )
+            })
+            .collect();
+        let batch_losses: Vec<_> = passes.iter().map(|pass| pass.loss.clone()).collect();
+        let loss = Tensor::cat(batch_losses.clone(), 0).mean();
+        let TrainPass {
+            pred_image,
+            visible,
+            num_visible,
+            num_intersections,
+            ..
+        } = passes.pop().expect("Need at least one batch to train on");
+
+        let render_bytes = bytes_before
+            .zip(self.passes_peak_bytes.take())
+            .map(|(before, peak)| peak - before);
 
         let (lr_mean, lr_rotation, lr_scale, lr_coeffs, lr_opac) = (
             self.sched_mean.step() * scene_extent as f64,
@@ -207,68 +442,70 @@ impl SplatTrainer {
             let sh_lr_scales = Tensor::<_, 1>::from_floats(sh_lr_scales.as_slice(), &device)
                 .reshape([1, coeff_count, 1]);
 
-            create_default_optimizer().load_record(HashMap::from([(
+            create_default_optimizer(&self.config).load_record(HashMap::from([(
                 splats.sh_coeffs.id,
                 AdaptorRecord::from_state(AdamState {
                     momentum: None,
                     scaling: Some(sh_lr_scales),
+                    moment_scales: None,
                 }),
             )]))
         });
 
         splats = trace_span!("Optimizer step", sync_burn = true).in_scope(|| {
             splats = trace_span!("SH Coeffs step", sync_burn = true).in_scope(|| {
-                let grad_coeff =
-                    GradientsParams::from_params(&mut grads, &splats, &[splats.sh_coeffs.id]);
+                let grad_coeff = take_grad::<3>(&mut grads, splats.sh_coeffs.id, count);
                 optimizer.step(lr_coeffs, splats, grad_coeff)
             });
             splats = trace_span!("Rotation step", sync_burn = true).in_scope(|| {
-                let grad_rot =
-                    GradientsParams::from_params(&mut grads, &splats, &[splats.rotation.id]);
+                let grad_rot = take_grad::<2>(&mut grads, splats.rotation.id, count);
                 optimizer.step(lr_rotation, splats, grad_rot)
             });
 
             splats = trace_span!("Scale step", sync_burn = true).in_scope(|| {
-                let grad_scale =
-                    GradientsParams::from_params(&mut grads, &splats, &[splats.log_scales.id]);
+                let grad_scale = take_grad::<2>(&mut grads, splats.log_scales.id, count);
                 optimizer.step(lr_scale, splats, grad_scale)
             });
 
             splats = trace_span!("Mean step", sync_burn = true).in_scope(|| {
-                let grad_means =
-                    GradientsParams::from_params(&mut grads, &splats, &[splats.means.id]);
+                let grad_means = take_grad::<2>(&mut grads, splats.means.id, count);
                 optimizer.step(lr_mean, splats, grad_means)
             });
 
             splats = trace_span!("Opacity step", sync_burn = true).in_scope(|| {
-                let grad_opac =
-                    GradientsParams::from_params(&mut grads, &splats, &[splats.raw_opacity.id]);
+                let grad_opac = take_grad::<1>(&mut grads, splats.raw_opacity.id, count);
                 optimizer.step(lr_opac, splats, grad_opac)
             });
 
-            // Make sure rotations are still valid after optimization step.
             splats
         });
 
-        trace_span!("Housekeeping", sync_burn = true).in_scope(|| {
-            // Get the xy gradient norm from the dummy tensor.
-            let refine_weight = refine_weight_holder
-                .grad_remove(&mut grads)
-                .expect("XY gradients need to be calculated.");
+        if let Some((light, mut optim)) = self.env_light.take() {
+            let light = trace_span!("Light step", sync_burn = true).in_scope(|| {
+                let grad_light = take_grad::<2>(&mut light_grads, light.coeffs.id, count);
+                optim.step(lr_coeffs, light, grad_light)
+            });
+            self.env_light = Some((light, optim));
+        }
 
-            let device = splats.device();
-            let num_splats = splats.num_splats();
-            let record = self
-                .refine_record
-                .get_or_insert_with(|| RefineRecord::new(num_splats, &device));
+        if let Some((appearance, mut optim)) = self.appearance.take() {
+            let appearance = trace_span!("Appearance step", sync_burn = true).in_scope(|| {
+                let grad_appearance =
+                    take_grad::<3>(&mut appearance_grads, appearance.transforms.id, count);
+                optim.step(lr_coeffs, appearance, grad_appearance)
+            });
+            self.appearance = Some((appearance, optim));
+        }
 
-            record.gather_stats(
-                refine_weight,
-                glam::uvec2(img_w as u32, img_h as u32),
-                global_from_compact_gid,
-                num_visible.clone(),
-            );
-        });
+        if self.config.max_anisotropy > 0.0 {
+            // Widen the smaller axes, so the largest axis still covers what it did.
+            let max_ratio = self.config.max_anisotropy.max(1.0).ln();
+            splats.log_scales = splats.log_scales.map(|s| {
+                let s = s.inner();
+                let min_allowed = (s.clone().max_dim(1) - max_ratio).repeat_dim(1, 3);
+                Tensor::from_inner(s.max_pair(min_allowed)).require_grad()
+            });
+        }
 
         let mean_noise_weight_scale = self.config.mean_noise_weight * (1.0 - train_t);
 
@@ -293,27 +530,62 @@ impl SplatTrainer {
                 ) * splats.scales().inner(),
             );
 
-            let noise_weight = noise_weight * (lr_mean as f32 * mean_noise_weight_scale);
+            // The noise is relative to the size of each splat, so it shouldn't grow with the
+            // scene extent like the learning rate does.
+            let lr_relative = (lr_mean / scene_extent as f64) as f32;
+            let noise_weight = noise_weight * (lr_relative * mean_noise_weight_scale);
             splats.means = splats
                 .means
                 .map(|m| Tensor::from_inner(m.inner() + samples * noise_weight).require_grad());
         }
 
+        let memory = render_bytes.map(|render| MemoryUse {
+            splats: splat_bytes(&splats),
+            optimizer: self
+                .optim
+                .as_ref()
+                .map_or(0, |optim| moment_bytes(optim, &splats)),
+            render,
+            images: batches
+                .iter()
+                .map(|batch| allocated_bytes(batch.img_tensor.clone().inner()))
+                .sum(),
+        });
+
         let stats = TrainStepStats {
             pred_image,
             num_visible: Tensor::from_primitive(num_visible),
             num_intersections: Tensor::from_primitive(num_intersections),
             loss,
+            batch_losses,
             lr_mean,
             lr_rotation,
             lr_scale,
             lr_coeffs,
             lr_opac,
+            memory,
         };
 
         (splats, stats)
     }
 
+    /// Number of splats with an accumulated gradient above the growth threshold.
+    async fn above_threshold_count(&self, refiner: &RefineRecord<InnerBack>) -> u32 {
+        refiner
+            .refine_weight_norm
+            .clone()
+            .greater_elem(self.growth_control.threshold())
+            .int()
+            .sum()
+            .into_scalar_async()
+            .await as u32
+    }
+
+    /// Number of splats that want to grow, going by the accumulated gradients.
+    fn growth_count(&self, above_threshold: u32) -> u32 {
+        (above_threshold as f32 * self.config.growth_select_fraction).round() as u32
+    }
+
     pub async fn refine_if_needed(
         &mut self,
         iter: u32,
@@ -334,18 +606,52 @@ impl SplatTrainer {
             .take()
             .expect("Can only refine after optimizer is initialized")
             .to_record();
+        let splats = canonicalize_rotations(splats, &mut record);
         let refiner = self
             .refine_record
             .take()
             .expect("Can only refine if refine stats are initialized");
-        let alpha_mask = splats
-            .raw_opacity
-            .val()
-            .inner()
-            .lower_elem(inverse_sigmoid(MIN_OPACITY));
+        let protect_iters = self.config.new_splat_protect_iters;
+        let transparent = refiner.transparent(
+            splats.raw_opacity.val().inner(),
+            inverse_sigmoid(MIN_OPACITY),
+            iter,
+            protect_iters,
+        );
+        let num_transparent = count_true(transparent.clone()).await;
+        let mut prune_mask = transparent.clone();
+
+        let mut num_invisible = 0;
+        if self.config.min_contribution > 0.0 {
+            let invisible = refiner
+                .invisible(self.config.min_contribution, iter, protect_iters)
+                .bool_and(transparent.bool_not());
+            num_invisible = count_true(invisible.clone()).await;
+            prune_mask = prune_mask.bool_or(invisible);
+        }
 
-        let (mut splats, refiner, pruned_count) =
-            prune_points(splats, &mut record, refiner, alpha_mask).await;
+        let (mut splats, mut refiner, pruned_count) =
+            prune_points(splats, &mut record, refiner, prune_mask).await;
+
+        // Make room for growth by removing the least important splats. These aren't replaced
+        // like dead splats, so the room goes to growing where the gradients are high.
+        let num_above_threshold = self.above_threshold_count(&refiner).await;
+        let mut budget_pruned = 0;
+        if self.config.budget_pruning && iter < self.config.growth_stop_iter {
+            let prune_count = budget_prune_count(
+                self.growth_count(num_above_threshold),
+                splats.num_splats(),
+                self.config.max_splats,
+                self.config.budget_prune_fraction,
+            );
+
+            if prune_count > 0 {
+                let protected = refiner.younger_than(iter, protect_iters);
+                let mask = least_important_mask(&splats, &refiner, protected, prune_count).await;
+                (splats, refiner, budget_pruned) =
+                    prune_points(splats, &mut record, refiner, mask).await;
+            }
+        }
 
         let mut add_indices = HashSet::new();
 
@@ -366,22 +672,25 @@ impl SplatTrainer {
             let above_threshold = refiner
                 .refine_weight_norm
                 .clone()
-                .greater_elem(self.config.growth_grad_threshold)
+                .greater_elem(self.growth_control.threshold())
                 .int();
-            let threshold_count = above_threshold.clone().sum().into_scalar_async().await as u32;
-
-            let grow_count =
-                (threshold_count as f32 * self.config.growth_select_fraction).round() as u32;
+            // Budget pruning can remove splats that were above the threshold, so count
+            // what's left to grow from.
+            let grow_count = if budget_pruned > 0 {
+                self.growth_count(self.above_threshold_count(&refiner).await)
+            } else {
+                self.growth_count(num_above_threshold)
+            };
 
             let sample_high_grad = grow_count.saturating_sub(pruned_count);
 
             // Only grow to the max nr. of splats.
             let cur_splats = splats.num_splats() + add_indices.len() as u32;
-            let grow_count = sample_high_grad.min(self.config.max_splats - cur_splats);
+            let grow_count = clamp_growth(sample_high_grad, cur_splats, self.config.max_splats);
 
             // If still growing, sample from indices which are over the threshold.
             if grow_count > 0 {
-                let weights = above_threshold.float() * refiner.refine_weight_norm;
+                let weights = above_threshold.float() * refiner.refine_weight_norm.clone();
                 let weights = weights
                     .into_data_async()
                     .await
@@ -480,9 +789,60 @@ impl SplatTrainer {
                 },
                 |x| Tensor::cat(vec![x, Tensor::zeros([refine_count], &device)], 0),
             );
+            refiner = refiner.add_born(refine_count, iter);
+        }
+
+        let sort_every = self.config.morton_sort_every;
+        if sort_every > 0 && iter % sort_every < self.config.refine_every {
+            let _span = trace_span!("Morton sort").entered();
+            let order = morton_indices(splats.means.val().inner()).await;
+            splats = map_splats_and_opt(
+                splats,
+                &mut record,
+                |x| x.select(0, order.clone()),
+                |x| x.select(0, order.clone()),
+                |x| x.select(0, order.clone()),
+                |x| x.select(0, order.clone()),
+                |x| x.select(0, order.clone()),
+                |x| x.select(0, order.clone()),
+                |x| x.select(0, order.clone()),
+                |x| x.select(0, order.clone()),
+                |x| x.select(0, order.clone()),
+                |x| x.select(0, order.clone()),
+            );
+            refiner = refiner.keep(order);
+        }
+
+        // Boost the position learning rate of young splats. The scaling of the means is only
+        // ever this boost, and it's replaced rather than multiplied: it has a row per splat,
+        // which pruning and growing above don't carry over, and rebuilding it from the ages
+        // each refine keeps splats that stay young from compounding the boost.
+        if self.config.new_splat_lr_boost != 1.0 {
+            let young = refiner
+                .younger_than(iter, self.config.new_splat_protect_iters)
+                .float();
+            let scaling = (young * (self.config.new_splat_lr_boost - 1.0) + 1.0).unsqueeze_dim(1);
+            let mut state: AdamState<InnerBack, 2> = record
+                .remove(&splats.means.id)
+                .expect("failed to get optimizer record")
+                .into_state();
+            state.scaling = Some(scaling);
+            record.insert(splats.means.id, AdaptorRecord::from_state(state));
         }
 
-        self.optim = Some(create_default_optimizer().load_record(record));
+        self.optim = Some(create_default_optimizer(&self.config).load_record(record));
+        self.refine_record = Some(refiner.next_round());
+
+        let growth_grad_threshold = self.growth_control.threshold();
+        if iter < self.config.growth_stop_iter {
+            let refines_left = (self.config.growth_stop_iter - iter) / self.config.refine_every;
+            self.growth_control.update(
+                num_above_threshold,
+                splats.num_splats(),
+                refines_left,
+                self.config.growth_select_fraction,
+            );
+        }
 
         client.memory_cleanup();
 
@@ -490,12 +850,136 @@ impl SplatTrainer {
             splats,
             Some(RefineStats {
                 num_added: refine_count as u32,
-                num_pruned: pruned_count,
+                num_pruned: pruned_count + budget_pruned,
+                num_transparent,
+                num_invisible,
+                num_budget_pruned: budget_pruned,
+                num_above_threshold,
+                growth_grad_threshold,
+                num_splats: splats.num_splats(),
             }),
         )
     }
 }
 
+/// Bytes the allocator handed out for the splat parameters.
+fn splat_bytes(splats: &Splats<TrainBack>) -> u64 {
+    allocated_bytes(splats.means.val().inner())
+        + allocated_bytes(splats.rotation.val().inner())
+        + allocated_bytes(splats.log_scales.val().inner())
+        + allocated_bytes(splats.sh_coeffs.val().inner())
+        + allocated_bytes(splats.raw_opacity.val().inner())
+}
+
+/// Bytes the allocator handed out for the Adam moments of the splat parameters.
+fn moment_bytes(optim: &OptimizerType, splats: &Splats<TrainBack>) -> u64 {
+    fn state_bytes<const D: usize>(
+        record: &HashMap<ParamId, AdaptorRecord<AdamScaled, TrainBack>>,
+        id: ParamId,
+    ) -> u64 {
+        record
+            .get(&id)
+            .and_then(|record| record.clone().into_state::<D>().momentum)
+            .map_or(0, |momentum| {
+                allocated_bytes(momentum.moment_1) + allocated_bytes(momentum.moment_2)
+            })
+    }
+
+    let record = optim.to_record();
+    state_bytes::<2>(&record, splats.means.id)
+        + state_bytes::<2>(&record, splats.rotation.id)
+        + state_bytes::<2>(&record, splats.log_scales.id)
+        + state_bytes::<3>(&record, splats.sh_coeffs.id)
+        + state_bytes::<1>(&record, splats.raw_opacity.id)
+}
+
+/// Indices that sort the splats along a Z-order curve through `means`.
+async fn morton_indices(means: Tensor<InnerBack, 2>) -> Tensor<InnerBack, 1, Int> {
+    let device = means.device();
+    let means = means
+        .into_data_async()
+        .await
+        .to_vec::<f32>()
+        .expect("Failed to read means");
+    let positions: Vec<_> = means.chunks_exact(3).map(glam::Vec3::from_slice).collect();
+    let order = morton_order(&positions);
+    let count = order.len();
+    Tensor::from_data(TensorData::new(order, [count]), &device)
+}
+
+async fn count_true(mask: Tensor<InnerBack, 1, Bool>) -> u32 {
+    mask.int().sum().into_scalar_async().await as u32
+}
+
+// Mask of the `count` least important splats, where importance is
+// opacity × volume × accumulated contribution. Splats that are faint, tiny, or
+// rarely seen score low. Protected splats are never picked.
+async fn least_important_mask(
+    splats: &Splats<TrainBack>,
+    refiner: &RefineRecord<InnerBack>,
+    protected: Tensor<InnerBack, 1, Bool>,
+    count: u32,
+) -> Tensor<InnerBack, 1, Bool> {
+    let device = splats.means.device();
+    let volume = splats.log_scales.val().inner().sum_dim(1).squeeze(1).exp();
+    let importance = (splats.opacities().inner() * volume * refiner.contribution.clone())
+        .mask_fill(protected, f32::MAX);
+    let importance = importance
+        .into_data_async()
+        .await
+        .to_vec::<f32>()
+        .expect("Failed to read importance");
+
+    let mut order: Vec<usize> = (0..importance.len()).collect();
+    let count = (count as usize).min(order.len());
+    if count > 0 && count < order.len() {
+        order.select_nth_unstable_by(count, |&a, &b| importance[a].total_cmp(&importance[b]));
+    }
+
+    let mut mask = vec![false; importance.len()];
+    for &i in &order[..count] {
+        mask[i] = true;
+    }
+    Tensor::from_data(TensorData::new(mask, [importance.len()]), &device)
+}
+
+/// Normalize the rotations, and flip them to have a positive real part. The renderer
+/// normalizes the rotations anyway, but left alone their length slowly drifts, which changes
+/// the size of their gradients and costs precision. As q and -q are the same rotation, the
+/// first moment of the optimizer is flipped along with the rotation.
+fn canonicalize_rotations(
+    mut splats: Splats<TrainBack>,
+    record: &mut HashMap<ParamId, AdaptorRecord<AdamScaled, TrainBack>>,
+) -> Splats<TrainBack> {
+    let rotation = splats.rotation.val().inner();
+    let norm = rotation
+        .clone()
+        .powf_scalar(2.0)
+        .sum_dim(1)
+        .sqrt()
+        .clamp_min(1e-12);
+    let flip = rotation.clone().narrow(1, 0, 1).lower_elem(0.0);
+    let sign = norm.ones_like().mask_fill(flip, -1.0);
+    let canonical = rotation * (sign.clone() / norm);
+    splats.rotation = splats
+        .rotation
+        .map(|_| Tensor::from_inner(canonical).require_grad());
+
+    let mut state: AdamState<InnerBack, 2> = record
+        .remove(&splats.rotation.id)
+        .expect("failed to get optimizer record")
+        .into_state();
+    let moment_scales = state.moment_scales.take();
+    state.momentum = state.momentum.map(|moment| {
+        let mut moment = full_precision_moments(moment, moment_scales);
+        moment.moment_1 = moment.moment_1 * sign;
+        moment
+    });
+    record.insert(splats.rotation.id, AdaptorRecord::from_state(state));
+
+    splats
+}
+
 fn map_splats_and_opt(
     mut splats: Splats<TrainBack>,
     record: &mut HashMap<ParamId, AdaptorRecord<AdamScaled, TrainBack>>,
@@ -539,17 +1023,20 @@ fn map_splats_and_opt(
     splats
 }
 
-fn map_opt<B: AutodiffBackend, const D: usize>(
+fn map_opt<const D: usize>(
     param_id: ParamId,
-    record: &mut HashMap<ParamId, AdaptorRecord<AdamScaled, B>>,
-    map_opt: &impl Fn(Tensor<B::InnerBackend, D>) -> Tensor<B::InnerBackend, D>,
+    record: &mut HashMap<ParamId, AdaptorRecord<AdamScaled, TrainBack>>,
+    map_opt: &impl Fn(Tensor<InnerBack, D>) -> Tensor<InnerBack, D>,
 ) {
     let mut state: AdamState<_, D> = record
         .remove(&param_id)
         .expect("failed to get optimizer record")
         .into_state();
 
-    state.momentum = state.momentum.map(|mut moment| {
+    let moment_scales = state.moment_scales.take();
+    state.momentum = state.momentum.map(|moment| {
+        // Map the moments in full precision, the next step stores them in half precision again.
+        let mut moment = full_precision_moments(moment, moment_scales);
         moment.moment_1 = map_opt(moment.moment_1);
         moment.moment_2 = map_opt(moment.moment_2);
         moment
@@ -38,6 +38,52 @@ fn create_texture(size: glam::UVec2, device: &wgpu::Device) -> wgpu::Texture {
     })
 }
 
+/// How a rendered image gets copied into the texture.
+#[derive(Debug, PartialEq, Eq)]
+struct UploadPlan {
+    /// Whether this is a float RGBA image that first needs to be packed into u32s.
+    pack: bool,
+    /// Width of the buffer, padded to the required row alignment for copies.
+    padded_width: usize,
+}
+
+fn upload_plan(dims: [usize; 3]) -> UploadPlan {
+    let [_, width, channels] = dims;
+    assert!(
+        channels == 1 || channels == 4,
+        "Can only display packed u32 or RGBA images, got {channels} channels"
+    );
+    let pixels_per_row = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize / 4;
+    UploadPlan {
+        pack: channels == 4,
+        padded_width: width.div_ceil(pixels_per_row) * pixels_per_row,
+    }
+}
+
+/// The packed u32 buffer of a rendered image, on the base backend. Float RGBA images are
+/// packed on the GPU first.
+fn packed_buffer<BT: BoolElement>(img: Tensor<BFused<BT>, 3>) -> Tensor<BBase<BT>, 3, Int> {
+    let plan = upload_plan(img.dims());
+
+    let img = if plan.pack {
+        // Pack channels into a u32 like the rasterizer does for the u32 buffer.
+        let device = img.device();
+        let channels = (img.clamp(0.0, 1.0) * 255.0).int();
+        let shifts = Tensor::<BFused<BT>, 1, Int>::from_ints([0, 8, 16, 24], &device);
+        let packed = channels.bitwise_left_shift(shifts.reshape([1, 1, 4]));
+        // Channels don't overlap, so summing is the same as or-ing them together.
+        packed.sum_dim(2).into_primitive()
+    } else {
+        // The u32 buffer is stored as a float tensor, reinterpret it.
+        img.into_primitive().tensor()
+    };
+
+    // Resolve the fused tensor to the underlying buffer.
+    let fusion_client = img.client.clone();
+    let img = fusion_client.resolve_tensor_int::<BBase<BT>>(img);
+    Tensor::from_primitive(img)
+}
+
 impl BurnTexture {
     pub fn new(
         renderer: Arc<EguiRwLock<Renderer>>,
@@ -52,15 +98,28 @@ impl BurnTexture {
         }
     }
 
+    /// Copy a rendered image to the texture.
+    ///
+    /// Takes either a packed u32 buffer, as rendered for viewing, or a float RGBA image. Either
+    /// way the image never leaves the GPU.
     pub fn update_texture<BT: BoolElement>(&mut self, img: Tensor<BFused<BT>, 3>) -> TextureId {
+        self.update_texture_base(packed_buffer(img))
+    }
+
+    /// Copy a packed u32 buffer on the base backend to the texture.
+    pub fn update_texture_base<BT: BoolElement>(
+        &mut self,
+        img: Tensor<BBase<BT>, 3, Int>,
+    ) -> TextureId {
         let mut encoder = self
             .device
             .create_command_encoder(&CommandEncoderDescriptor {
                 label: Some("viewer encoder"),
             });
 
-        let [h, w, _] = img.shape().dims();
-        let size = glam::uvec2(w as u32, h as u32);
+        let [height, width, c] = img.dims();
+        assert_eq!(c, 1, "Expected a packed u32 buffer");
+        let size = glam::uvec2(width as u32, height as u32);
 
         let dirty = if let Some(s) = self.state.as_ref() {
             s.texture.width() != size.x || s.texture.height() != size.y
@@ -69,7 +128,7 @@ impl BurnTexture {
         };
 
         if dirty {
-            let texture = create_texture(glam::uvec2(w as u32, h as u32), &self.device);
+            let texture = create_texture(size, &self.device);
 
             if let Some(s) = self.state.as_mut() {
                 s.texture = texture;
@@ -95,19 +154,13 @@ impl BurnTexture {
         };
         let texture: &wgpu::Texture = &s.texture;
 
-        let [height, width, c] = img.dims();
-
-        let padded_shape = vec![height, width.div_ceil(64) * 64, c];
-
-        let img_prim = img.into_primitive().tensor();
-        let fusion_client = img_prim.client.clone();
-        let img = fusion_client.resolve_tensor_int::<BBase<BT>>(img_prim);
-        let img: Tensor<BBase<BT>, 3, Int> = Tensor::from_primitive(img);
+        let padded_width = upload_plan([height, width, c]).padded_width;
 
         // Create padded tensor if needed. The bytes_per_row needs to be divisible
         // by 256 in WebGPU, so 4 bytes per pixel means width needs to be divisible by 64.
-        let img = if width % 64 != 0 {
-            let padded: Tensor<BBase<BT>, 3, Int> = Tensor::zeros(&padded_shape, &img.device());
+        let img = if padded_width != width {
+            let padded: Tensor<BBase<BT>, 3, Int> =
+                Tensor::zeros([height, padded_width, c], &img.device());
             padded.slice_assign([0..height, 0..width], img)
         } else {
             img
@@ -123,7 +176,7 @@ impl BurnTexture {
         client.flush();
 
         // Put compute passes in encoder before copying the buffer.
-        let bytes_per_row = Some(4 * padded_shape[1] as u32);
+        let bytes_per_row = Some(4 * padded_width as u32);
 
         // Now copy the buffer to the texture.
         encoder.copy_buffer_to_texture(
@@ -157,3 +210,58 @@ impl BurnTexture {
         self.state.as_ref().map(|s| s.id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use burn::tensor::TensorData;
+
+    fn packed_values(img: Tensor<BFused<u32>, 3>) -> Vec<u32> {
+        let packed = packed_buffer(img);
+        let data = packed.into_data().to_vec::<i32>().expect("Wrong type");
+        data.into_iter().map(|v| v as u32).collect()
+    }
+
+    #[test]
+    fn packed_buffers_pass_through_on_gpu() {
+        let device = Default::default();
+        // The rasterizer stores packed pixels in a float tensor, the bits have to come out as is.
+        let pixels = [0xFF00_7FFF_u32, 0x3F80_0000, 0x4000_0000];
+        let values: Vec<f32> = pixels.iter().map(|p| f32::from_bits(*p)).collect();
+        let img = Tensor::<BFused<u32>, 3>::from_data(TensorData::new(values, [1, 3, 1]), &device);
+        assert_eq!(packed_values(img), pixels);
+    }
+
+    #[test]
+    fn float_images_are_packed_on_gpu() {
+        let device = Default::default();
+        let rgba = vec![1.0, 0.5, 0.0, 1.0, 0.0, 0.0, 2.0, -1.0];
+        let img = Tensor::<BFused<u32>, 3>::from_data(TensorData::new(rgba, [1, 2, 4]), &device);
+        // Channels are clamped, and packed with red in the lowest byte.
+        assert_eq!(packed_values(img), [0xFF00_7FFF, 0x00FF_0000]);
+    }
+
+    #[test]
+    fn upload_plan_detects_float_images() {
+        assert_eq!(
+            upload_plan([32, 128, 1]),
+            UploadPlan {
+                pack: false,
+                padded_width: 128
+            }
+        );
+        assert_eq!(
+            upload_plan([32, 100, 4]),
+            UploadPlan {
+                pack: true,
+                padded_width: 128
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only display packed u32 or RGBA images")]
+    fn upload_plan_rejects_rgb() {
+        upload_plan([32, 32, 3]);
+    }
+}
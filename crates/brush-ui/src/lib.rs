@@ -7,8 +7,15 @@ use wgpu::{Adapter, Features};
 
 pub mod burn_texture;
 
-pub fn create_egui_options() -> WgpuConfiguration {
+/// Options for the egui renderer. Without `vsync`, frames are presented as soon as they're
+/// ready, which can tear, but shows how fast the viewer really renders.
+pub fn create_egui_options(vsync: bool) -> WgpuConfiguration {
     WgpuConfiguration {
+        present_mode: if vsync {
+            wgpu::PresentMode::AutoVsync
+        } else {
+            wgpu::PresentMode::AutoNoVsync
+        },
         wgpu_setup: eframe::egui_wgpu::WgpuSetup::CreateNew(
             eframe::egui_wgpu::WgpuSetupCreateNew {
                 power_preference: wgpu::PowerPreference::HighPerformance,
@@ -71,3 +78,13 @@ pub fn size_for_splat_view(ui: &mut egui::Ui) -> egui::Vec2 {
     size.y -= 25.0;
     size.floor()
 }
+
+/// Size in pixels to render a view that's `size` points large. On high-DPI screens a point
+/// is several pixels, so rendering one pixel per point is faster, but looks soft. The render
+/// is scaled to the view when drawn, so more pixels per point supersample it.
+pub fn render_size(size: egui::Vec2, pixels_per_point: f32) -> glam::UVec2 {
+    let size = (size * pixels_per_point)
+        .round()
+        .max(egui::Vec2::splat(1.0));
+    glam::uvec2(size.x as u32, size.y as u32)
+}
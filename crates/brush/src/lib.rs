@@ -0,0 +1,313 @@
+//! High level API to load, render and train Gaussian splats.
+//!
+//! This is the crate to depend on from other Rust projects. The other brush crates are
+//! the internals of the app, and change freely between versions. This crate wraps them in
+//! types of its own, so their changes don't reach you.
+//!
+//! # Stability
+//!
+//! This crate follows semver: breaking changes to its API only come with a new minor version
+//! while it's below 1.0, and a new major version after. CI checks this with
+//! `cargo-semver-checks`.
+//!
+//! Two crates are part of the API, and are re-exported so you can use the same versions:
+//! [`glam`] 0.28 for vectors and transforms, and [`image`] 0.25 for rendered images. Moving
+//! to a newer version of either is a breaking change of this crate.
+//!
+//! Anything behind the `unstable` feature, like adding dataset formats, uses the internal
+//! types directly, and is not covered by this.
+//!
+//! ```no_run
+//! # async fn run() -> anyhow::Result<()> {
+//! let device = brush::init_device().await;
+//! let splats = brush::load_splats("scene.ply", &device).await?;
+//! let camera = brush::Camera::new(
+//!     glam::vec3(0.0, 0.0, -5.0),
+//!     glam::Quat::IDENTITY,
+//!     0.8,
+//!     0.8,
+//!     glam::vec2(0.5, 0.5),
+//! );
+//! let image = brush::render(&splats, &camera, glam::uvec2(512, 512)).await?;
+//! image.save("render.png")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{path::PathBuf, str::FromStr};
+
+use anyhow::Context;
+use brush_process::{
+    data_source::DataSource,
+    process_loop::{ProcessArgs, ProcessMessage, process_stream},
+};
+use brush_train::train::InnerBack;
+use image::{DynamicImage, Rgba32FImage, RgbaImage};
+use tokio_stream::StreamExt;
+use web_time::Duration;
+
+#[cfg(feature = "unstable")]
+pub use brush_dataset::DatasetFormat;
+pub use glam;
+pub use image;
+
+type InnerSplats = brush_render::gaussian_splats::Splats<InnerBack>;
+
+/// The GPU device that splats are loaded, rendered and trained on.
+#[derive(Debug, Clone)]
+pub struct Device(burn_wgpu::WgpuDevice);
+
+/// Set up the GPU device that splats are loaded, rendered and trained on.
+pub async fn init_device() -> Device {
+    Device(brush_render::burn_init_setup().await)
+}
+
+/// A pinhole camera to render splats with.
+#[derive(Debug, Clone)]
+pub struct Camera(brush_render::camera::Camera);
+
+impl Camera {
+    /// A camera at `position`, looking along the +Z axis of `rotation`. The fields of view
+    /// are in radians, and `center_uv` is the principal point as a fraction of the image
+    /// size, usually (0.5, 0.5).
+    pub fn new(
+        position: glam::Vec3,
+        rotation: glam::Quat,
+        fov_x: f64,
+        fov_y: f64,
+        center_uv: glam::Vec2,
+    ) -> Self {
+        Self(brush_render::camera::Camera::new(
+            position, rotation, fov_x, fov_y, center_uv,
+        ))
+    }
+
+    pub fn position(&self) -> glam::Vec3 {
+        self.0.position
+    }
+
+    pub fn rotation(&self) -> glam::Quat {
+        self.0.rotation
+    }
+}
+
+/// A set of Gaussian splats, on the GPU.
+#[derive(Clone)]
+pub struct Splats(InnerSplats);
+
+impl Splats {
+    pub fn num_splats(&self) -> u32 {
+        self.0.num_splats()
+    }
+
+    /// Degree of the spherical harmonics used for view dependent color.
+    pub fn sh_degree(&self) -> u32 {
+        self.0.sh_degree()
+    }
+
+    /// Serialize to a `.ply` file, in the layout used by most splat viewers.
+    pub async fn to_ply(&self) -> anyhow::Result<Vec<u8>> {
+        brush_dataset::splat_export::splat_to_ply(self.0.clone()).await
+    }
+
+    /// Serialize the raw parameters to safetensors, eg. to load them in PyTorch.
+    pub async fn to_safetensors(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(self.0.to_safetensors().await?)
+    }
+
+    /// Load splats from safetensors written by [`Self::to_safetensors`].
+    pub fn from_safetensors(data: &[u8], device: &Device) -> anyhow::Result<Self> {
+        Ok(Self(InnerSplats::from_safetensors(data, &device.0)?))
+    }
+
+    /// Create splats at the given positions, with plain RGB colors. Scales are sized to
+    /// the neighbouring splats when not given, and opacities default to opaque.
+    pub fn from_colors(
+        means: &[glam::Vec3],
+        colors: &[glam::Vec3],
+        scales: Option<&[glam::Vec3]>,
+        opacities: Option<&[f32]>,
+        device: &Device,
+    ) -> Self {
+        Self(InnerSplats::from_colors(
+            means, colors, scales, opacities, &device.0,
+        ))
+    }
+
+    /// Add the splats of `other` after these splats.
+    pub fn append(self, other: Self) -> Self {
+        Self(self.0.append(other.0))
+    }
+
+    /// Remove the splats at the indices in `range`.
+    pub fn remove_range(self, range: std::ops::Range<usize>) -> Self {
+        Self(self.0.remove_range(range))
+    }
+
+    /// Move the splats by `transform`, rotating their view dependent colors along. Non-uniform
+    /// scales are averaged.
+    pub fn transform(self, transform: glam::Affine3A) -> Self {
+        Self(self.0.transform(transform))
+    }
+}
+
+fn data_source(source: &str) -> anyhow::Result<DataSource> {
+    DataSource::from_str(source).map_err(|e| anyhow::anyhow!(e))
+}
+
+/// Load splats from a path or URL, of any format the app can view (eg. `.ply`, or a zip
+/// of them). For animations, the last frame is returned.
+pub async fn load_splats(source: &str, device: &Device) -> anyhow::Result<Splats> {
+    let stream = process_stream(
+        data_source(source)?,
+        ProcessArgs::default(),
+        device.0.clone(),
+    );
+    let mut stream = std::pin::pin!(stream);
+
+    let mut splats = None;
+    while let Some(message) = stream.next().await {
+        match message? {
+            ProcessMessage::StartLoading { training: true } => {
+                anyhow::bail!("{source} is a dataset, not splats. Use `train` instead.");
+            }
+            ProcessMessage::ViewSplats { splats: loaded, .. } => splats = Some(*loaded),
+            _ => {}
+        }
+    }
+    splats.map(Splats).context("No splats found")
+}
+
+/// Render an image of the splats as seen by `camera`.
+pub async fn render(
+    splats: &Splats,
+    camera: &Camera,
+    size: glam::UVec2,
+) -> anyhow::Result<RgbaImage> {
+    let (img, _) = splats.0.render(&camera.0, size, true);
+    let data = img
+        .into_data_async()
+        .await
+        .into_vec::<f32>()
+        .map_err(|e| anyhow::anyhow!("Failed to read render: {e:?}"))?;
+    let img = Rgba32FImage::from_raw(size.x, size.y, data).context("Render has unexpected size")?;
+    Ok(DynamicImage::ImageRgba32F(img).into_rgba8())
+}
+
+/// Settings for [`train`]. Start from the default and change what's needed, new
+/// settings might be added in minor versions.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct TrainOptions {
+    /// Number of training steps.
+    pub total_steps: u32,
+    /// Degree of the spherical harmonics of the splats.
+    pub sh_degree: u32,
+    /// Images of the dataset are downscaled to at most this resolution.
+    pub max_resolution: u32,
+    /// Random seed.
+    pub seed: u64,
+    /// Folder the run is saved to. The trained splats are saved in a subfolder
+    /// named `run_name` at the end of training, and every `export_every` steps.
+    /// Defaults to the working directory.
+    pub output_dir: Option<PathBuf>,
+    /// Name of the folder of this run. Defaults to the time training started.
+    pub run_name: Option<String>,
+    /// Save the splats every this many steps, besides at the end.
+    pub export_every: u32,
+}
+
+impl Default for TrainOptions {
+    fn default() -> Self {
+        let args = ProcessArgs::default();
+        Self {
+            total_steps: args.train_config.total_steps,
+            sh_degree: args.model_config.sh_degree,
+            max_resolution: args.load_config.max_resolution,
+            seed: args.process_config.seed,
+            output_dir: None,
+            run_name: None,
+            export_every: args.process_config.export_every,
+        }
+    }
+}
+
+impl TrainOptions {
+    fn into_args(self) -> ProcessArgs {
+        let mut args = ProcessArgs::default();
+        args.train_config.total_steps = self.total_steps;
+        args.model_config.sh_degree = self.sh_degree;
+        args.load_config.max_resolution = self.max_resolution;
+        args.process_config.seed = self.seed;
+        args.process_config.output_dir = self.output_dir.map(|p| p.to_string_lossy().into_owned());
+        args.process_config.run_name = self.run_name;
+        args.process_config.export_every = self.export_every;
+        args
+    }
+}
+
+/// Progress of [`train`], passed to its callback.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct TrainProgress {
+    /// The current training step.
+    pub iter: u32,
+    pub total_steps: u32,
+    /// Time spent training, not counting loading.
+    pub elapsed: Duration,
+    pub num_splats: u32,
+}
+
+/// Train splats on a dataset (a COLMAP or nerfstudio folder, or a zip of one).
+///
+/// `on_progress` is called regularly while training, with the latest splats. Returns
+/// the trained splats.
+pub async fn train(
+    source: &str,
+    options: TrainOptions,
+    device: &Device,
+    mut on_progress: impl FnMut(&TrainProgress, &Splats),
+) -> anyhow::Result<Splats> {
+    let total_steps = options.total_steps;
+    let stream = process_stream(data_source(source)?, options.into_args(), device.0.clone());
+    let mut stream = std::pin::pin!(stream);
+
+    let mut last = None;
+    while let Some(message) = stream.next().await {
+        if let ProcessMessage::TrainStep {
+            splats,
+            iter,
+            total_elapsed,
+            ..
+        } = message?
+        {
+            let Some(splats) = splats.and_then(|s| s.front()) else {
+                continue;
+            };
+            let splats = Splats(splats);
+            let progress = TrainProgress {
+                iter,
+                total_steps,
+                elapsed: total_elapsed,
+                num_splats: splats.num_splats(),
+            };
+            on_progress(&progress, &splats);
+            last = Some(splats);
+        }
+    }
+    last.context("Training produced no splats, is the source a dataset?")
+}
+
+/// The backend datasets are loaded for, to implement [`DatasetFormat`] with.
+#[cfg(feature = "unstable")]
+pub type TrainBackend = brush_train::train::TrainBack;
+
+/// Add a format of dataset that [`train`] can load, eg. the output of another
+/// photogrammetry tool. Added formats are tried before the built in ones.
+///
+/// Formats are written with the types of `brush_dataset`, so this is only available with
+/// the `unstable` feature.
+#[cfg(feature = "unstable")]
+pub fn register_dataset_format(format: impl DatasetFormat<TrainBackend> + 'static) {
+    brush_process::register_dataset_format(format);
+}
@@ -153,6 +153,15 @@ impl Camera {
     }
 }
 
+// Counts in binary files are read before the items they count, and could be anything in a
+// corrupt or malicious file. Only this many items are allocated for up front, past that
+// vectors grow as items are actually read.
+const MAX_PREALLOC: u64 = 1 << 16;
+
+fn preallocated<T>(count: u64) -> Vec<T> {
+    Vec::with_capacity(count.min(MAX_PREALLOC) as usize)
+}
+
 fn parse<T: std::str::FromStr>(s: &str) -> io::Result<T> {
     s.parse()
         .map_err(|_e| io::Error::new(io::ErrorKind::InvalidData, "Parse error"))
@@ -260,8 +269,14 @@ async fn read_images_text<R: AsyncRead + Unpin>(mut reader: R) -> io::Result<Has
             break;
         }
 
-        if !line.is_empty() && !line.starts_with('#') {
+        if !line.trim().is_empty() && !line.starts_with('#') {
             let elems: Vec<&str> = line.split_whitespace().collect();
+            if elems.len() < 10 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Invalid image data",
+                ));
+            }
             let id: i32 = parse(elems[0])?;
 
             let [w, x, y, z] = [
@@ -282,6 +297,12 @@ async fn read_images_text<R: AsyncRead + Unpin>(mut reader: R) -> io::Result<Has
             let mut point3d_ids = Vec::new();
 
             for chunk in elems.chunks(3) {
+                if chunk.len() < 3 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Invalid image points data",
+                    ));
+                }
                 xys.push(glam::vec2(parse(chunk[0])?, parse(chunk[1])?));
                 point3d_ids.push(parse(chunk[2])?);
             }
@@ -328,14 +349,20 @@ async fn read_images_binary<R: AsyncBufRead + Unpin>(
         let camera_id = reader.read_i32_le().await?;
         let mut name_bytes = Vec::new();
         reader.read_until(b'\0', &mut name_bytes).await?;
+        if name_bytes.pop() != Some(b'\0') {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Unterminated image name",
+            ));
+        }
 
-        let name = std::str::from_utf8(&name_bytes[..name_bytes.len() - 1])
+        let name = std::str::from_utf8(&name_bytes)
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
             .to_owned();
 
         let num_points2d = reader.read_u64_le().await?;
-        let mut xys = Vec::with_capacity(num_points2d as usize);
-        let mut point3d_ids = Vec::with_capacity(num_points2d as usize);
+        let mut xys = preallocated(num_points2d);
+        let mut point3d_ids = preallocated(num_points2d);
 
         for _ in 0..num_points2d {
             xys.push(glam::Vec2::new(
@@ -442,8 +469,8 @@ async fn read_points3d_binary<R: AsyncRead + Unpin>(
         let error = reader.read_f64_le().await?;
 
         let track_length = reader.read_u64_le().await?;
-        let mut image_ids = Vec::with_capacity(track_length as usize);
-        let mut point2d_idxs = Vec::with_capacity(track_length as usize);
+        let mut image_ids = preallocated(track_length);
+        let mut point2d_idxs = preallocated(track_length);
 
         for _ in 0..track_length {
             image_ids.push(reader.read_i32_le().await?);
@@ -1,20 +1,45 @@
 use anyhow::{Context, Result, anyhow};
 use jni::JNIEnv;
-use jni::objects::{GlobalRef, JClass, JStaticMethodID};
+use jni::objects::{GlobalRef, JByteArray, JClass, JFloatArray, JStaticMethodID, JString};
 use jni::signature::Primitive;
 use jni::sys::jint;
 use lazy_static::lazy_static;
 use std::os::fd::FromRawFd;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::RwLock;
 use tokio::fs::File;
-use tokio::sync::mpsc::Sender;
+use tokio::sync::mpsc::{Sender, UnboundedReceiver, UnboundedSender};
 
 lazy_static! {
     static ref VM: RwLock<Option<Arc<jni::JavaVM>>> = RwLock::new(None);
     static ref CHANNEL: RwLock<Option<Sender<Option<File>>>> = RwLock::new(None);
     static ref START_FILE_PICKER: RwLock<Option<JStaticMethodID>> = RwLock::new(None);
     static ref FILE_PICKER_CLASS: RwLock<Option<GlobalRef>> = RwLock::new(None);
+    static ref CAPTURE_CHANNEL: RwLock<Option<UnboundedSender<CapturedFrame>>> = RwLock::new(None);
+    static ref START_CAPTURE: RwLock<Option<JStaticMethodID>> = RwLock::new(None);
+    static ref CAPTURE_CLASS: RwLock<Option<GlobalRef>> = RwLock::new(None);
+}
+
+/// A single frame recorded by the AR capture activity.
+pub struct CapturedFrame {
+    /// JPEG encoded camera image.
+    pub jpeg: Vec<u8>,
+    /// Column major camera to world matrix, in the OpenGL convention (-Z forward, +Y up).
+    pub cam_to_world: [f32; 16],
+    /// Focal length in pixels.
+    pub focal: [f32; 2],
+    /// Principal point in pixels.
+    pub principal_point: [f32; 2],
+    pub width: u32,
+    pub height: u32,
+}
+
+pub struct Capture {
+    /// Directory in app storage where the captured dataset can be written to.
+    pub output_dir: PathBuf,
+    /// Recorded frames. The channel closes when the user finishes capturing.
+    pub frames: UnboundedReceiver<CapturedFrame>,
 }
 
 #[allow(unused)]
@@ -30,9 +55,29 @@ pub fn jni_initialize(vm: Arc<jni::JavaVM>) {
     *START_FILE_PICKER
         .write()
         .expect("Failed to write JNI data.") = Some(method);
+
+    // Without AR capture the app still works, starting a capture then fails with an error.
+    match capture_handles(&mut env) {
+        Ok((class, method)) => {
+            *CAPTURE_CLASS.write().expect("Failed to write JNI data.") = Some(class);
+            *START_CAPTURE.write().expect("Failed to write JNI data.") = Some(method);
+        }
+        Err(e) => {
+            // Clear the Java exception of the failed lookup, later JNI calls fail otherwise.
+            let _ = env.exception_clear();
+            log::error!("AR capture is unavailable: {e}");
+        }
+    }
+
     *VM.write().unwrap() = Some(vm);
 }
 
+fn capture_handles(env: &mut JNIEnv) -> jni::errors::Result<(GlobalRef, JStaticMethodID)> {
+    let class = env.find_class("com/splats/app/ArCapture")?;
+    let method = env.get_static_method_id(&class, "startCapture", "()Ljava/lang/String;")?;
+    Ok((env.new_global_ref(class)?, method))
+}
+
 #[allow(unused)]
 pub(crate) async fn pick_file() -> Result<File> {
     let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
@@ -105,3 +150,100 @@ extern "system" fn Java_com_splats_app_FilePicker_onFilePickerResult<'local>(
         }
     }
 }
+
+/// Start the AR capture activity. Frames are streamed back as they are recorded.
+pub fn start_capture() -> Result<Capture> {
+    let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+    {
+        let channel = CAPTURE_CHANNEL.write();
+        if let Ok(mut channel) = channel {
+            *channel = Some(sender);
+        } else {
+            anyhow::bail!("Failed to initialize capture");
+        }
+    }
+
+    let java_vm = VM
+        .read()
+        .unwrap()
+        .clone()
+        .expect("Failed to initialize Java VM");
+    let mut env = java_vm.attach_current_thread()?;
+
+    let class = CAPTURE_CLASS
+        .read()
+        .expect("Failed to initialize ArCapture class");
+    let method = START_CAPTURE
+        .read()
+        .expect("Failed to initialize ArCapture method");
+
+    let class = class.as_ref().context("AR capture is unavailable")?;
+    let method = method.as_ref().context("AR capture is unavailable")?;
+
+    // SAFETY: Same as for the file picker, the method id is cached from
+    // the matching Java signature.
+    let output_dir = unsafe {
+        env.call_static_method_unchecked(class, method, jni::signature::ReturnType::Object, &[])
+    }?
+    .l()?;
+    let output_dir: String = env.get_string(&JString::from(output_dir))?.into();
+
+    Ok(Capture {
+        output_dir: PathBuf::from(output_dir),
+        frames: receiver,
+    })
+}
+
+fn read_floats<const N: usize>(env: &JNIEnv<'_>, array: &JFloatArray<'_>) -> Result<[f32; N]> {
+    let mut buf = [0.0; N];
+    env.get_float_array_region(array, 0, &mut buf)?;
+    Ok(buf)
+}
+
+#[unsafe(no_mangle)]
+extern "system" fn Java_com_splats_app_ArCapture_onCaptureFrame<'local>(
+    env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    jpeg: JByteArray<'local>,
+    pose: JFloatArray<'local>,
+    intrinsics: JFloatArray<'local>,
+    width: jint,
+    height: jint,
+) {
+    let frame = (|| -> Result<CapturedFrame> {
+        let jpeg = env.convert_byte_array(&jpeg)?;
+        let cam_to_world = read_floats::<16>(&env, &pose)?;
+        let [fx, fy, cx, cy] = read_floats::<4>(&env, &intrinsics)?;
+        Ok(CapturedFrame {
+            jpeg,
+            cam_to_world,
+            focal: [fx, fy],
+            principal_point: [cx, cy],
+            width: width as u32,
+            height: height as u32,
+        })
+    })();
+
+    // Dropping a single frame isn't fatal, the capture can continue.
+    let Ok(frame) = frame else {
+        return;
+    };
+
+    // Channel can be gone if the app stopped listening, that's fine.
+    if let Ok(ch) = CAPTURE_CHANNEL.read() {
+        if let Some(ch) = ch.as_ref() {
+            let _ = ch.send(frame);
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+extern "system" fn Java_com_splats_app_ArCapture_onCaptureFinished<'local>(
+    _env: JNIEnv<'local>,
+    _class: JClass<'local>,
+) {
+    // Dropping the sender closes the channel, which marks the end of the capture.
+    if let Ok(mut channel) = CAPTURE_CHANNEL.write() {
+        *channel = None;
+    }
+}
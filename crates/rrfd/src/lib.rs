@@ -26,6 +26,16 @@ impl FileHandle {
         }
     }
 
+    /// Name of the file, empty if it isn't known.
+    pub fn file_name(&self) -> String {
+        match self {
+            #[cfg(not(target_os = "android"))]
+            Self::Rfd(file_handle) => file_handle.file_name(),
+            #[cfg(target_os = "android")]
+            Self::Android(_) => String::new(),
+        }
+    }
+
     pub async fn read(mut self) -> Vec<u8> {
         match &mut self {
             #[cfg(not(target_os = "android"))]
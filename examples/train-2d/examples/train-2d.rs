@@ -58,12 +58,13 @@ fn spawn_train_loop(
             img_tensor: sample_to_tensor(&image, &device).unsqueeze(),
             alpha_is_mask: false,
             camera: cam,
+            view_index: 0,
         };
 
         let mut iter = 0;
 
         loop {
-            let (new_splats, _) = trainer.step(1.0, iter, &batch, splats);
+            let (new_splats, _) = trainer.step(1.0, iter, std::slice::from_ref(&batch), splats);
             let (new_splats, _) = trainer.refine_if_needed(iter, new_splats).await;
 
             splats = new_splats;
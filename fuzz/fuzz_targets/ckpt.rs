@@ -0,0 +1,27 @@
+#![no_main]
+
+use std::{io::Cursor, path::Path, sync::Arc};
+
+use brush_dataset::{
+    brush_vfs::{BrushVfs, PathReader},
+    ckpt_import::load_splat_from_ckpt,
+};
+use burn::backend::NdArray;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // The pickle of an unpacked checkpoint, with a storage for each of the six splat
+    // parameters to point at. Each file can only be read once.
+    let storage: Vec<u8> = (0..256).flat_map(|i| (i as f32).to_le_bytes()).collect();
+    let mut paths = PathReader::default();
+    paths.add(Path::new("archive/data.pkl"), Cursor::new(data.to_vec()));
+    for key in 0..6 {
+        let path = format!("archive/data/{key}");
+        paths.add(Path::new(&path), Cursor::new(storage.clone()));
+    }
+    let vfs = Arc::new(BrushVfs::from_paths(paths, Default::default()));
+
+    brush_fuzz::block_on(async {
+        let _ = load_splat_from_ckpt::<NdArray>(vfs, &Default::default()).await;
+    });
+});
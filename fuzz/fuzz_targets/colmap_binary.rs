@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // The same bytes through each of the readers, they all start with a count.
+    brush_fuzz::block_on(async {
+        let _ = colmap_reader::read_cameras(data, true).await;
+        let _ = colmap_reader::read_images(data, true).await;
+        let _ = colmap_reader::read_points3d(data, true).await;
+    });
+});
@@ -0,0 +1,33 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Uncompressed splats, as written by the reference implementation and most tools.
+const HEADER: &str = "ply
+format binary_little_endian 1.0
+element vertex 4
+property float x
+property float y
+property float z
+property float scale_0
+property float scale_1
+property float scale_2
+property float rot_0
+property float rot_1
+property float rot_2
+property float rot_3
+property float opacity
+property float f_dc_0
+property float f_dc_1
+property float f_dc_2
+property float f_rest_0
+property float f_rest_1
+property float f_rest_2
+end_header
+";
+
+fuzz_target!(|data: &[u8]| {
+    // Both ways a ply is read, the tolerant one shows what's there of truncated files.
+    brush_fuzz::load_ply(data, HEADER, false);
+    brush_fuzz::load_ply(data, HEADER, true);
+});
@@ -0,0 +1,47 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Quantized splats in chunks of 256, as written by SuperSplat.
+const HEADER: &str = "ply
+format binary_little_endian 1.0
+element chunk 1
+property float min_x
+property float min_y
+property float min_z
+property float max_x
+property float max_y
+property float max_z
+property float min_scale_x
+property float min_scale_y
+property float min_scale_z
+property float max_scale_x
+property float max_scale_y
+property float max_scale_z
+property float min_r
+property float min_g
+property float min_b
+property float max_r
+property float max_g
+property float max_b
+element vertex 4
+property uint packed_position
+property uint packed_rotation
+property uint packed_scale
+property uint packed_color
+element sh 4
+property uchar f_rest_0
+property uchar f_rest_1
+property uchar f_rest_2
+property uchar f_rest_3
+property uchar f_rest_4
+property uchar f_rest_5
+property uchar f_rest_6
+property uchar f_rest_7
+property uchar f_rest_8
+end_header
+";
+
+fuzz_target!(|data: &[u8]| {
+    brush_fuzz::load_ply(data, HEADER, false);
+});
@@ -0,0 +1,61 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Animated splats, a base frame followed by quantized deltas per frame.
+const HEADER: &str = "ply
+format binary_little_endian 1.0
+element vertex 4
+property float x
+property float y
+property float z
+property float scale_0
+property float scale_1
+property float scale_2
+property float rot_0
+property float rot_1
+property float rot_2
+property float rot_3
+property float opacity
+property float f_dc_0
+property float f_dc_1
+property float f_dc_2
+element meta_delta_min_0 1
+property float x
+property float y
+property float z
+property float scale_0
+property float scale_1
+property float scale_2
+property float rot_0
+property float rot_1
+property float rot_2
+property float rot_3
+element meta_delta_max_0 1
+property float x
+property float y
+property float z
+property float scale_0
+property float scale_1
+property float scale_2
+property float rot_0
+property float rot_1
+property float rot_2
+property float rot_3
+element delta_vertex_0 4
+property uchar x
+property uchar y
+property uchar z
+property uchar scale_0
+property uchar scale_1
+property uchar scale_2
+property uchar rot_0
+property uchar rot_1
+property uchar rot_2
+property uchar rot_3
+end_header
+";
+
+fuzz_target!(|data: &[u8]| {
+    brush_fuzz::load_ply(data, HEADER, false);
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use brush_dataset::nerfstudio::parse_cameras;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = parse_cameras(data, glam::uvec2(640, 480));
+});
@@ -0,0 +1,44 @@
+//! Shared code of the fuzz targets.
+
+use std::io::Cursor;
+
+use brush_dataset::splat_import::load_splat_from_ply;
+use burn::backend::NdArray;
+use tokio_stream::StreamExt;
+
+/// Run an async parser to completion on the current thread.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("Failed to make runtime")
+        .block_on(future)
+}
+
+/// Parse `data` as a ply file, to the end or the first error. Errors are fine, panics and
+/// aborts are bugs.
+///
+/// Data that doesn't start like a ply file gets `header` put in front, so the fuzzer spends
+/// most of its time on the body of the format the header declares, while still trying out
+/// other headers.
+pub fn load_ply(data: &[u8], header: &str, tolerant: bool) {
+    let mut file = vec![];
+    if !data.starts_with(b"ply") {
+        file.extend_from_slice(header.as_bytes());
+    }
+    file.extend_from_slice(data);
+
+    block_on(async {
+        let stream = load_splat_from_ply::<_, NdArray>(
+            Cursor::new(file),
+            None,
+            tolerant,
+            Default::default(),
+        );
+        let mut stream = std::pin::pin!(stream);
+        while let Some(message) = stream.next().await {
+            if message.is_err() {
+                break;
+            }
+        }
+    });
+}